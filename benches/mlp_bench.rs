@@ -31,6 +31,16 @@ fn bench_mlp_forward_large(c: &mut Criterion) {
     });
 }
 
+fn bench_mlp_forward_batch(c: &mut Criterion) {
+    c.bench_function("mlp_forward_batch_64", |b| {
+        let mlp = MLP::new(384, vec![100, 50], 3);
+        let inputs = vec![vec![0.5; 384]; 64];
+        b.iter(|| {
+            mlp.forward_batch(black_box(&inputs));
+        });
+    });
+}
+
 fn bench_softmax(c: &mut Criterion) {
     c.bench_function("softmax", |b| {
         let values = vec![1.0, 2.0, 3.0, 4.0, 5.0];
@@ -54,6 +64,7 @@ fn bench_argmax(c: &mut Criterion) {
     bench_mlp_forward_small,
     bench_mlp_forward_medium,
     bench_mlp_forward_large,
+    bench_mlp_forward_batch,
     bench_softmax,
     bench_argmax
 );
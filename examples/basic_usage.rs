@@ -2,7 +2,7 @@
 //!
 //! Run with: cargo run --example basic_usage
 
-use mobile_ai_orchestrator::{Orchestrator, Query};
+use mobile_ai_orchestrator::{Orchestrator, Query, Response};
 
 fn main() {
     println!("Mobile AI Orchestrator - Basic Usage Example\n");
@@ -59,7 +59,7 @@ fn main() {
     // Example 5: Blocked query
     println!("\n=== Example 5: Safety - Blocked Query ===");
     let blocked_query = Query::new("Here's my api_key=sk-12345");
-    match orch.process(blocked_query) {
+    match orch.process(blocked_query).and_then(Response::into_result) {
         Ok(_) => println!("Should have been blocked!"),
         Err(e) => println!("Correctly blocked: {}", e),
     }
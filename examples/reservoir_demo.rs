@@ -5,7 +5,6 @@
 use mobile_ai_orchestrator::context::ContextManager;
 use mobile_ai_orchestrator::reservoir::{encode_text, EchoStateNetwork};
 use mobile_ai_orchestrator::{Query, Response, RoutingDecision};
-use mobile_ai_orchestrator::types::{ResponseMetadata};
 
 fn main() {
     println!("Reservoir Computing Demo\n");
@@ -41,17 +40,14 @@ fn main() {
     println!("Adding conversation turns...");
     for (i, text) in texts.iter().enumerate() {
         let query = Query::new(*text);
-        let response = Response {
-            text: format!("Response to: {}", text),
-            route: RoutingDecision::Local,
-            confidence: 0.9,
-            latency_ms: 10,
-            metadata: ResponseMetadata {
-                model: Some("test".to_string()),
-                tokens: Some(10),
-                cached: false,
-            },
-        };
+        let mut response = Response::new(
+            format!("Response to: {}", text),
+            RoutingDecision::Local,
+            0.9,
+        );
+        response.latency_ms = 10;
+        response.metadata.model = Some("test".to_string());
+        response.metadata.tokens = Some(10);
         cm.add_turn(query, response);
 
         // Show reservoir state evolution
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Activity Recognition
+//!
+//! Classifies what the user is doing (still, walking or driving) from the
+//! latest window of accelerometer readings, plus gyroscope readings when
+//! the device has one.
+//!
+//! Each window is summarized as a fixed-size feature vector: mean and
+//! variance of the accelerometer magnitude, its energy in four frequency
+//! bands (a DFT of the detrended magnitude), and mean and variance of the
+//! gyroscope magnitude. Variances and energies are log-compressed. A small
+//! MLP maps the features to the three activities.
+//!
+//! A fresh recognizer is fitted to synthetic windows of each activity (a
+//! flat signal, a ~2 Hz gait, engine and road vibration), which is enough
+//! to tell them apart on typical phones; [`ActivityRecognizer::train`]
+//! refines it with labelled windows recorded on real devices.
+//!
+//! The orchestrator reports the result as `DeviceState::activity`: while
+//! driving, optional neural work is throttled and the routing policy
+//! penalizes on-device inference.
+
+#![forbid(unsafe_code)]
+
+use crate::mlp::{Loss, MLP};
+use crate::rng::SeededRng;
+use crate::sensor::{SensorBuffer, SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Length of a window feature vector
+pub const FEATURES: usize = 8;
+
+/// Standard gravity (m/s^2)
+const GRAVITY: f32 = 9.81;
+
+/// Frequency bands (Hz) of accelerometer magnitude energy: posture drift,
+/// gait, fast motion, vibration
+const BANDS_HZ: [(f32, f32); 4] = [(0.3, 1.0), (1.0, 3.0), (3.0, 8.0), (8.0, 25.0)];
+
+/// Synthetic windows per activity used to fit a fresh recognizer
+const SYNTHETIC_WINDOWS: usize = 24;
+
+/// Epochs over the synthetic windows
+const SYNTHETIC_EPOCHS: usize = 150;
+
+/// Learning rate for fitting and [`ActivityRecognizer::train`]
+const LEARNING_RATE: f32 = 0.05;
+
+/// What the user is physically doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Activity {
+    /// Not enough motion data, or no confident answer
+    #[default]
+    Unknown,
+    /// Device at rest or barely moving
+    Still,
+    /// On foot
+    Walking,
+    /// In a moving vehicle
+    Driving,
+}
+
+impl Activity {
+    /// Activities the classifier distinguishes, in output order
+    pub const CLASSES: [Activity; 3] = [Activity::Still, Activity::Walking, Activity::Driving];
+
+    /// Lowercase name
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Activity::Unknown => "unknown",
+            Activity::Still => "still",
+            Activity::Walking => "walking",
+            Activity::Driving => "driving",
+        }
+    }
+
+    fn class(&self) -> Option<usize> {
+        Self::CLASSES.iter().position(|a| a == self)
+    }
+}
+
+/// Window and decision settings for [`ActivityRecognizer`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityConfig {
+    /// Length of the window classified, ending at the newest reading (ms)
+    pub window_ms: u64,
+    /// Fewest accelerometer readings a window needs
+    pub min_samples: usize,
+    /// Lowest class probability reported; below it the activity is
+    /// `Unknown`
+    pub min_confidence: f32,
+    /// Hidden units of the classifier
+    pub hidden: usize,
+    /// Seed of the classifier weights and synthetic windows
+    pub seed: u64,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 2_560,
+            min_samples: 16,
+            min_confidence: 0.6,
+            hidden: 12,
+            seed: 1729,
+        }
+    }
+}
+
+/// Classified activity and the classifier's probability for it
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ActivityEstimate {
+    /// Most likely activity, or `Unknown`
+    pub activity: Activity,
+    /// Probability of `activity` (0 when unknown)
+    pub confidence: f32,
+}
+
+/// Features of one window of motion readings, oldest first; `None` with
+/// fewer than two accelerometer readings or no elapsed time
+pub fn window_features(accel: &[&SensorReading], gyro: &[&SensorReading]) -> Option<Vec<f32>> {
+    let (first, last) = (accel.first()?, accel.last()?);
+    let span_ms = last.timestamp_ms.saturating_sub(first.timestamp_ms);
+    if accel.len() < 2 || span_ms == 0 {
+        return None;
+    }
+    let rate_hz = (accel.len() - 1) as f32 * 1_000.0 / span_ms as f32;
+    let magnitudes: Vec<f32> = accel.iter().map(|r| r.magnitude()).collect();
+    let (mean, variance) = mean_variance(&magnitudes);
+
+    let mut features = Vec::with_capacity(FEATURES);
+    features.push(mean / GRAVITY);
+    features.push(variance.ln_1p());
+    let detrended: Vec<f32> = magnitudes.iter().map(|m| m - mean).collect();
+    features.extend(band_energies(&detrended, rate_hz).iter().map(|e| e.ln_1p()));
+    let gyro: Vec<f32> = gyro.iter().map(|r| r.magnitude()).collect();
+    let (gyro_mean, gyro_variance) = mean_variance(&gyro);
+    features.push(gyro_mean.ln_1p());
+    features.push(gyro_variance.ln_1p());
+    Some(features)
+}
+
+/// Small MLP classifying motion windows into [`Activity::CLASSES`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityRecognizer {
+    config: ActivityConfig,
+    mlp: MLP,
+}
+
+impl ActivityRecognizer {
+    /// Create a recognizer fitted to synthetic windows of each activity
+    pub fn new(config: ActivityConfig) -> Self {
+        let mlp = MLP::new_with_seed(
+            FEATURES,
+            vec![config.hidden.max(1)],
+            Activity::CLASSES.len(),
+            config.seed,
+        );
+        let mut recognizer = Self { config, mlp };
+        let mut rng = SeededRng::new(recognizer.config.seed);
+        let samples: Vec<(Vec<f32>, Activity)> = (0..SYNTHETIC_WINDOWS)
+            .flat_map(|_| Activity::CLASSES)
+            .filter_map(|activity| {
+                let (accel, gyro) = synthetic_window(activity, &mut rng);
+                let accel: Vec<&SensorReading> = accel.iter().collect();
+                let gyro: Vec<&SensorReading> = gyro.iter().collect();
+                Some((window_features(&accel, &gyro)?, activity))
+            })
+            .collect();
+        recognizer.train(&samples, SYNTHETIC_EPOCHS);
+        recognizer
+    }
+
+    /// Features of the latest `window_ms` of motion readings in `buffer`,
+    /// or `None` with fewer than `min_samples` accelerometer readings
+    pub fn features(&self, buffer: &SensorBuffer) -> Option<Vec<f32>> {
+        let mut accel = buffer.readings_of_type(SensorType::Accelerometer);
+        let end = accel.last()?.timestamp_ms;
+        let window = end.saturating_sub(self.config.window_ms)..=end;
+        accel.retain(|r| window.contains(&r.timestamp_ms));
+        if accel.len() < self.config.min_samples {
+            return None;
+        }
+        let mut gyro = buffer.readings_of_type(SensorType::Gyroscope);
+        gyro.retain(|r| window.contains(&r.timestamp_ms));
+        window_features(&accel, &gyro)
+    }
+
+    /// Class probabilities for a feature vector, in [`Activity::CLASSES`]
+    /// order
+    pub fn probabilities(&self, features: &[f32]) -> Vec<f32> {
+        MLP::softmax(&self.mlp.forward(features))
+    }
+
+    /// Classify the latest window of `buffer`
+    pub fn classify(&self, buffer: &SensorBuffer) -> ActivityEstimate {
+        let Some(features) = self.features(buffer) else {
+            return ActivityEstimate::default();
+        };
+        let probabilities = self.probabilities(&features);
+        let best = MLP::argmax(&probabilities);
+        if probabilities[best] < self.config.min_confidence {
+            return ActivityEstimate::default();
+        }
+        ActivityEstimate {
+            activity: Activity::CLASSES[best],
+            confidence: probabilities[best],
+        }
+    }
+
+    /// Refine the classifier with labelled feature vectors (from
+    /// [`ActivityRecognizer::features`] or [`window_features`]); samples
+    /// labelled `Unknown` or of the wrong length are skipped. Returns the
+    /// mean loss of the last epoch.
+    pub fn train(&mut self, samples: &[(Vec<f32>, Activity)], epochs: usize) -> f32 {
+        let usable: Vec<(&[f32], usize)> = samples
+            .iter()
+            .filter(|(features, _)| features.len() == FEATURES)
+            .filter_map(|(features, activity)| Some((features.as_slice(), activity.class()?)))
+            .collect();
+        if usable.is_empty() {
+            return 0.0;
+        }
+        let mut loss = 0.0;
+        for _ in 0..epochs {
+            loss = 0.0;
+            for &(features, class) in &usable {
+                let mut target = vec![0.0; Activity::CLASSES.len()];
+                target[class] = 1.0;
+                let (sample_loss, gradients) =
+                    self.mlp
+                        .backward_with_loss(features, &target, Loss::SoftmaxCrossEntropy);
+                self.mlp.update(&gradients, LEARNING_RATE);
+                loss += sample_loss;
+            }
+            loss /= usable.len() as f32;
+        }
+        loss
+    }
+}
+
+/// Mean and population variance; zeros for an empty slice
+fn mean_variance(values: &[f32]) -> (f32, f32) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+    (mean, variance)
+}
+
+/// Power of `signal` in each of [`BANDS_HZ`], from a direct DFT
+fn band_energies(signal: &[f32], rate_hz: f32) -> [f32; 4] {
+    let n = signal.len();
+    let mut energies = [0.0; 4];
+    for k in 1..=n / 2 {
+        let hz = k as f32 * rate_hz / n as f32;
+        let Some(band) = BANDS_HZ.iter().position(|&(lo, hi)| hz >= lo && hz < hi) else {
+            continue;
+        };
+        let (mut re, mut im) = (0.0, 0.0);
+        for (t, x) in signal.iter().enumerate() {
+            let phase = 2.0 * PI * (k * t % n) as f32 / n as f32;
+            re += x * phase.cos();
+            im -= x * phase.sin();
+        }
+        energies[band] += 2.0 * (re * re + im * im) / (n * n) as f32;
+    }
+    energies
+}
+
+/// Roughly Gaussian noise with standard deviation `sigma`
+fn noise(rng: &mut SeededRng, sigma: f32) -> f32 {
+    (rng.next_f32() + rng.next_f32() + rng.next_f32() - 1.5) * 2.0 * sigma
+}
+
+/// One synthetic window of accelerometer and (sometimes) gyroscope
+/// readings at 50 Hz for `activity`
+fn synthetic_window(
+    activity: Activity,
+    rng: &mut SeededRng,
+) -> (Vec<SensorReading>, Vec<SensorReading>) {
+    const RATE_HZ: f32 = 50.0;
+    const SAMPLES: usize = 128;
+    let with_gyro = rng.next_f32() < 0.5;
+    let gait_hz = 1.5 + rng.next_f32() * 0.8;
+    let engine_hz = 10.0 + rng.next_f32() * 8.0;
+    let sway_hz = 0.3 + rng.next_f32() * 0.4;
+    let phase = rng.next_f32() * 2.0 * PI;
+
+    let mut accel = Vec::with_capacity(SAMPLES);
+    let mut gyro = Vec::new();
+    for i in 0..SAMPLES {
+        let t = i as f32 / RATE_HZ;
+        let (magnitude, rotation) = match activity {
+            Activity::Walking => (
+                GRAVITY + 2.5 * (2.0 * PI * gait_hz * t + phase).sin() + noise(rng, 0.4),
+                0.8 + 0.5 * (2.0 * PI * gait_hz * t).sin().abs() + noise(rng, 0.1),
+            ),
+            Activity::Driving => (
+                GRAVITY
+                    + 0.35 * (2.0 * PI * engine_hz * t + phase).sin()
+                    + 0.4 * (2.0 * PI * sway_hz * t).sin()
+                    + noise(rng, 0.2),
+                0.1 + noise(rng, 0.05).abs(),
+            ),
+            _ => (GRAVITY + noise(rng, 0.02), noise(rng, 0.01).abs()),
+        };
+        let timestamp_ms = (t * 1_000.0) as u64;
+        accel.push(SensorReading::with_timestamp(
+            SensorType::Accelerometer,
+            vec![0.0, 0.0, magnitude],
+            timestamp_ms,
+        ));
+        if with_gyro {
+            gyro.push(SensorReading::with_timestamp(
+                SensorType::Gyroscope,
+                vec![rotation, 0.0, 0.0],
+                timestamp_ms,
+            ));
+        }
+    }
+    (accel, gyro)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recognizes_unseen_synthetic_activities() {
+        let recognizer = ActivityRecognizer::new(ActivityConfig::default());
+        let mut rng = SeededRng::new(99);
+        for _ in 0..5 {
+            for activity in Activity::CLASSES {
+                let (accel, gyro) = synthetic_window(activity, &mut rng);
+                let mut buffer = SensorBuffer::new(512);
+                buffer.extend(accel.into_iter().chain(gyro));
+                let estimate = recognizer.classify(&buffer);
+                assert_eq!(estimate.activity, activity, "{:?}", estimate);
+                assert!(estimate.confidence >= 0.6);
+            }
+        }
+
+        let mut sparse = SensorBuffer::new(8);
+        for t in 0..8 {
+            let reading = SensorReading::with_timestamp(
+                SensorType::Accelerometer,
+                vec![0.0, 0.0, GRAVITY],
+                t * 20,
+            );
+            sparse.push(reading);
+        }
+        assert_eq!(recognizer.classify(&sparse), ActivityEstimate::default());
+    }
+
+    #[test]
+    fn test_training_moves_the_decision() {
+        let mut recognizer = ActivityRecognizer::new(ActivityConfig::default());
+        let mut rng = SeededRng::new(7);
+        let (accel, gyro) = synthetic_window(Activity::Still, &mut rng);
+        let (accel, gyro): (Vec<_>, Vec<_>) = (accel.iter().collect(), gyro.iter().collect());
+        let Some(features) = window_features(&accel, &gyro) else {
+            panic!("a full window should have features");
+        };
+        let before = recognizer.probabilities(&features)[2];
+
+        // A phone lying still in a car cradle at a red light
+        let samples = vec![(features.clone(), Activity::Driving); 4];
+        assert!(recognizer.train(&samples, 50) < 0.5);
+        assert!(recognizer.probabilities(&features)[2] > before);
+        assert_eq!(recognizer.train(&[(features, Activity::Unknown)], 5), 0.0);
+    }
+}
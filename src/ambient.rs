@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Ambient Context Classifier
+//!
+//! Fuses proximity, light, and accelerometer readings into a coarse
+//! ambient state (in pocket, in hand, on desk, dark room, walking).
+//!
+//! The state is exposed on the context snapshot and as a one-hot router
+//! feature block, and carries simple policy hints: no proactive
+//! suggestions while pocketed, concise responses while walking. The
+//! orchestrator holds back digests and asks for short answers in prompts
+//! accordingly.
+
+#![forbid(unsafe_code)]
+
+use crate::sensor::{SensorBuffer, SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+
+/// Coarse physical situation of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum AmbientState {
+    /// Not enough sensor data to decide
+    #[default]
+    Unknown,
+    /// Proximity covered and dark (pocket or bag)
+    InPocket,
+    /// Held by the user, moderate motion
+    InHand,
+    /// Still and lying flat
+    OnDesk,
+    /// Low ambient light, not pocketed
+    DarkRoom,
+    /// Periodic high-variance motion
+    Walking,
+}
+
+impl AmbientState {
+    /// Number of ambient states (width of the one-hot feature block)
+    pub const COUNT: usize = 6;
+
+    /// Every state, in feature-block order
+    pub const ALL: [AmbientState; Self::COUNT] = [
+        AmbientState::Unknown,
+        AmbientState::InPocket,
+        AmbientState::InHand,
+        AmbientState::OnDesk,
+        AmbientState::DarkRoom,
+        AmbientState::Walking,
+    ];
+
+    /// Stable index of this state within the feature block
+    pub const fn index(&self) -> usize {
+        match self {
+            AmbientState::Unknown => 0,
+            AmbientState::InPocket => 1,
+            AmbientState::InHand => 2,
+            AmbientState::OnDesk => 3,
+            AmbientState::DarkRoom => 4,
+            AmbientState::Walking => 5,
+        }
+    }
+
+    /// One-hot encoding for router/expert feature vectors
+    pub fn to_features(&self) -> Vec<f32> {
+        let mut features = vec![0.0; Self::COUNT];
+        features[self.index()] = 1.0;
+        features
+    }
+
+    /// Whether proactive suggestions may be surfaced in this state
+    pub fn allows_proactive(&self) -> bool {
+        !matches!(self, AmbientState::InPocket)
+    }
+
+    /// Whether responses should be kept short in this state
+    pub fn prefers_concise(&self) -> bool {
+        matches!(self, AmbientState::Walking)
+    }
+}
+
+/// Thresholds used by [`AmbientClassifier`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientConfig {
+    /// Proximity below this distance (cm) counts as covered
+    pub near_proximity_cm: f32,
+    /// Light below this level (lux) counts as dark
+    pub dark_lux: f32,
+    /// Accelerometer magnitude variance below this is "still"
+    pub still_variance: f32,
+    /// Accelerometer magnitude variance above this is "walking"
+    pub walking_variance: f32,
+    /// Minimum fraction of gravity on the z axis to count as lying flat
+    pub flat_z_ratio: f32,
+}
+
+impl Default for AmbientConfig {
+    fn default() -> Self {
+        Self {
+            near_proximity_cm: 1.0,
+            dark_lux: 10.0,
+            still_variance: 0.05,
+            walking_variance: 1.5,
+            flat_z_ratio: 0.9,
+        }
+    }
+}
+
+/// Rule-based fusion of proximity, light, and accelerometer readings
+#[derive(Debug, Clone, Default)]
+pub struct AmbientClassifier {
+    config: AmbientConfig,
+}
+
+impl AmbientClassifier {
+    /// Create a classifier with the given thresholds
+    pub fn new(config: AmbientConfig) -> Self {
+        Self { config }
+    }
+
+    /// Classify the ambient state from the readings in `buffer`
+    ///
+    /// Uses the latest proximity and light readings and the variance of
+    /// accelerometer magnitude across the buffer.
+    pub fn classify(&self, buffer: &SensorBuffer) -> AmbientState {
+        let proximity = latest_value(buffer, SensorType::Proximity);
+        let light = latest_value(buffer, SensorType::Light);
+        let accel = buffer.readings_of_type(SensorType::Accelerometer);
+
+        if proximity.is_none() && light.is_none() && accel.is_empty() {
+            return AmbientState::Unknown;
+        }
+
+        let near = proximity.is_some_and(|cm| cm < self.config.near_proximity_cm);
+        let dark = light.is_some_and(|lux| lux < self.config.dark_lux);
+
+        if near && dark {
+            return AmbientState::InPocket;
+        }
+
+        if let Some(variance) = magnitude_variance(&accel) {
+            if variance > self.config.walking_variance {
+                return AmbientState::Walking;
+            }
+            if variance < self.config.still_variance && self.is_flat(&accel) {
+                return AmbientState::OnDesk;
+            }
+        }
+
+        if dark {
+            return AmbientState::DarkRoom;
+        }
+
+        if accel.is_empty() {
+            AmbientState::Unknown
+        } else {
+            AmbientState::InHand
+        }
+    }
+
+    /// Whether the accelerometer readings in `buffer` vary less than
+    /// `still_variance`; `false` without at least two readings
+    ///
+    /// A pocketed phone is still on a chair but not on a walk, which the
+    /// `InPocket` state alone does not tell apart.
+    pub fn is_still(&self, buffer: &SensorBuffer) -> bool {
+        let accel = buffer.readings_of_type(SensorType::Accelerometer);
+        magnitude_variance(&accel).is_some_and(|variance| variance < self.config.still_variance)
+    }
+
+    /// Whether gravity lies mostly along the z axis in the latest reading
+    fn is_flat(&self, accel: &[&SensorReading]) -> bool {
+        accel.last().is_some_and(|r| {
+            let magnitude = r.magnitude();
+            magnitude > 0.0
+                && r.values
+                    .get(2)
+                    .is_some_and(|z| z.abs() / magnitude >= self.config.flat_z_ratio)
+        })
+    }
+}
+
+/// First value of the most recent reading of `sensor_type`
+fn latest_value(buffer: &SensorBuffer, sensor_type: SensorType) -> Option<f32> {
+    buffer
+        .readings_of_type(sensor_type)
+        .last()
+        .and_then(|r| r.values.first().copied())
+}
+
+/// Variance of reading magnitudes, or `None` with fewer than two readings
+fn magnitude_variance(readings: &[&SensorReading]) -> Option<f32> {
+    if readings.len() < 2 {
+        return None;
+    }
+
+    let magnitudes: Vec<f32> = readings.iter().map(|r| r.magnitude()).collect();
+    let mean = magnitudes.iter().sum::<f32>() / magnitudes.len() as f32;
+    let variance =
+        magnitudes.iter().map(|m| (m - mean).powi(2)).sum::<f32>() / magnitudes.len() as f32;
+
+    Some(variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer_with(readings: Vec<SensorReading>) -> SensorBuffer {
+        let mut buffer = SensorBuffer::new(64);
+        for reading in readings {
+            buffer.push(reading);
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_empty_buffer_is_unknown() {
+        let classifier = AmbientClassifier::default();
+        assert_eq!(
+            classifier.classify(&SensorBuffer::new(8)),
+            AmbientState::Unknown
+        );
+    }
+
+    #[test]
+    fn test_pocket_detection() {
+        let buffer = buffer_with(vec![
+            SensorReading::new(SensorType::Proximity, vec![0.0]),
+            SensorReading::new(SensorType::Light, vec![1.0]),
+        ]);
+        let state = AmbientClassifier::default().classify(&buffer);
+
+        assert_eq!(state, AmbientState::InPocket);
+        assert!(!state.allows_proactive());
+    }
+
+    #[test]
+    fn test_on_desk_detection() {
+        let buffer = buffer_with(
+            (0..10)
+                .map(|_| SensorReading::new(SensorType::Accelerometer, vec![0.0, 0.1, 9.8]))
+                .collect(),
+        );
+
+        assert_eq!(
+            AmbientClassifier::default().classify(&buffer),
+            AmbientState::OnDesk
+        );
+    }
+
+    #[test]
+    fn test_walking_detection() {
+        let buffer = buffer_with(
+            (0..20)
+                .map(|i| {
+                    let bounce = if i % 2 == 0 { 4.0 } else { -4.0 };
+                    SensorReading::new(SensorType::Accelerometer, vec![0.5, 9.8 + bounce, 1.0])
+                })
+                .collect(),
+        );
+        let state = AmbientClassifier::default().classify(&buffer);
+
+        assert_eq!(state, AmbientState::Walking);
+        assert!(state.prefers_concise());
+    }
+
+    #[test]
+    fn test_dark_room_and_in_hand() {
+        let dark = buffer_with(vec![SensorReading::new(SensorType::Light, vec![2.0])]);
+        assert_eq!(
+            AmbientClassifier::default().classify(&dark),
+            AmbientState::DarkRoom
+        );
+
+        let held = buffer_with(vec![
+            SensorReading::new(SensorType::Light, vec![300.0]),
+            SensorReading::new(SensorType::Accelerometer, vec![1.0, 8.0, 4.0]),
+            SensorReading::new(SensorType::Accelerometer, vec![1.2, 7.8, 4.3]),
+        ]);
+        assert_eq!(
+            AmbientClassifier::default().classify(&held),
+            AmbientState::InHand
+        );
+    }
+
+    #[test]
+    fn test_feature_encoding() {
+        let features = AmbientState::OnDesk.to_features();
+        assert_eq!(features.len(), AmbientState::COUNT);
+        assert_eq!(features[AmbientState::OnDesk.index()], 1.0);
+        assert_eq!(features.iter().sum::<f32>(), 1.0);
+    }
+}
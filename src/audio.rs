@@ -0,0 +1,340 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Audio Feature Extraction
+//!
+//! Turns raw PCM into the log-mel energies and MFCCs speech models expect,
+//! in pure Rust. Each frame of `frame_len` samples, taken every
+//! `hop_len` samples, goes through:
+//!
+//! 1. **Pre-emphasis**: `x[n] - pre_emphasis * x[n - 1]`, lifting the
+//!    high frequencies that carry consonants
+//! 2. **Window and FFT**: Hann window, zero-padded to a power of two, and
+//!    a radix-2 FFT to the power spectrum
+//! 3. **Mel filterbank**: `mel_bands` triangular filters evenly spaced on
+//!    the mel scale between `min_hz` and `max_hz`; the natural log of each
+//!    filter's energy is a log-mel feature
+//! 4. **DCT**: an orthonormal DCT-II of the log-mel energies, keeping the
+//!    first `mfccs` coefficients
+//!
+//! [`AudioFeatures::readings`] wraps the per-frame features as
+//! `SensorType::Audio` readings, one every hop, so they join a
+//! `SensorBuffer` alongside the other sensors. They carry `mel_bands` or
+//! `mfccs` values rather than `SensorType::Audio`'s single amplitude, so
+//! feed them to a network with `SpikeEncoder::encode(&reading.values)`
+//! and an input layer of that width.
+
+#![forbid(unsafe_code)]
+
+use crate::sensor::{SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Added to filter energies before the log, so silence stays finite
+const LOG_FLOOR: f32 = 1e-10;
+
+/// Feature extraction settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Sample rate of the PCM (Hz)
+    pub sample_rate: u32,
+    /// Samples per frame (400 = 25 ms at 16 kHz)
+    pub frame_len: usize,
+    /// Samples between frame starts (160 = 10 ms at 16 kHz)
+    pub hop_len: usize,
+    /// Mel filters
+    pub mel_bands: usize,
+    /// Cepstral coefficients kept, at most `mel_bands`
+    pub mfccs: usize,
+    /// Lower edge of the filterbank (Hz)
+    pub min_hz: f32,
+    /// Upper edge of the filterbank (Hz), capped at the Nyquist frequency
+    pub max_hz: f32,
+    /// Pre-emphasis coefficient; 0 disables it
+    pub pre_emphasis: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            frame_len: 400,
+            hop_len: 160,
+            mel_bands: 26,
+            mfccs: 13,
+            min_hz: 20.0,
+            max_hz: 8_000.0,
+            pre_emphasis: 0.97,
+        }
+    }
+}
+
+/// Which features [`AudioFeatures::readings`] produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioFeatureKind {
+    /// `mel_bands` log filter energies
+    LogMel,
+    /// `mfccs` cepstral coefficients
+    Mfcc,
+}
+
+/// Log-mel and MFCC extractor with precomputed window, filters and DCT
+#[derive(Debug, Clone)]
+pub struct AudioFeatures {
+    config: AudioConfig,
+    fft_len: usize,
+    window: Vec<f32>,
+    /// Per filter: first FFT bin and the weights from there on
+    filters: Vec<(usize, Vec<f32>)>,
+    centres_hz: Vec<f32>,
+    /// `mfccs` rows of `mel_bands` DCT-II coefficients
+    dct: Vec<Vec<f32>>,
+}
+
+impl AudioFeatures {
+    /// Create an extractor; zero sizes are raised to one
+    pub fn new(mut config: AudioConfig) -> Self {
+        config.frame_len = config.frame_len.max(1);
+        config.hop_len = config.hop_len.max(1);
+        config.mel_bands = config.mel_bands.max(1);
+        config.mfccs = config.mfccs.clamp(1, config.mel_bands);
+        config.sample_rate = config.sample_rate.max(1);
+
+        let fft_len = config.frame_len.next_power_of_two();
+        let span = config.frame_len.saturating_sub(1).max(1) as f32;
+        let window = (0..config.frame_len)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / span).cos())
+            .collect();
+
+        let nyquist = config.sample_rate as f32 / 2.0;
+        let max_hz = config.max_hz.min(nyquist);
+        let min_hz = config.min_hz.clamp(0.0, max_hz);
+        let (low, high) = (hz_to_mel(min_hz), hz_to_mel(max_hz));
+        let edges: Vec<f32> = (0..config.mel_bands + 2)
+            .map(|i| mel_to_hz(low + (high - low) * i as f32 / (config.mel_bands + 1) as f32))
+            .collect();
+        let bin_hz = config.sample_rate as f32 / fft_len as f32;
+        let filters = edges
+            .windows(3)
+            .map(|edge| {
+                let (left, centre, right) = (edge[0], edge[1], edge[2]);
+                let first = (left / bin_hz).ceil() as usize;
+                let last = ((right / bin_hz).floor() as usize).min(fft_len / 2);
+                let weights = (first..=last)
+                    .map(|bin| {
+                        let hz = bin as f32 * bin_hz;
+                        let rising = (hz - left) / (centre - left).max(f32::EPSILON);
+                        let falling = (right - hz) / (right - centre).max(f32::EPSILON);
+                        rising.min(falling).max(0.0)
+                    })
+                    .collect();
+                (first, weights)
+            })
+            .collect();
+        let centres_hz = edges[1..=config.mel_bands].to_vec();
+
+        let bands = config.mel_bands as f32;
+        let dct = (0..config.mfccs)
+            .map(|k| {
+                let scale = if k == 0 {
+                    (1.0 / bands).sqrt()
+                } else {
+                    (2.0 / bands).sqrt()
+                };
+                (0..config.mel_bands)
+                    .map(|n| scale * (PI * k as f32 * (n as f32 + 0.5) / bands).cos())
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            config,
+            fft_len,
+            window,
+            filters,
+            centres_hz,
+            dct,
+        }
+    }
+
+    /// Settings in use, after sizes were raised to at least one
+    pub fn config(&self) -> &AudioConfig {
+        &self.config
+    }
+
+    /// Centre frequency (Hz) of each mel filter
+    pub fn band_centres(&self) -> &[f32] {
+        &self.centres_hz
+    }
+
+    /// Log-mel energies of one frame; shorter frames are zero-padded and
+    /// longer ones truncated to `frame_len`
+    pub fn log_mel(&self, frame: &[f32]) -> Vec<f32> {
+        let mut re = vec![0.0; self.fft_len];
+        let mut im = vec![0.0; self.fft_len];
+        let mut previous = 0.0;
+        for ((slot, &sample), weight) in re.iter_mut().zip(frame).zip(&self.window) {
+            *slot = (sample - self.config.pre_emphasis * previous) * weight;
+            previous = sample;
+        }
+        fft(&mut re, &mut im);
+        let power: Vec<f32> = re
+            .iter()
+            .zip(&im)
+            .take(self.fft_len / 2 + 1)
+            .map(|(r, i)| (r * r + i * i) / self.fft_len as f32)
+            .collect();
+        self.filters
+            .iter()
+            .map(|(first, weights)| {
+                let energy: f32 = weights
+                    .iter()
+                    .zip(power.iter().skip(*first))
+                    .map(|(w, p)| w * p)
+                    .sum();
+                (energy + LOG_FLOOR).ln()
+            })
+            .collect()
+    }
+
+    /// MFCCs of one frame
+    pub fn mfcc(&self, frame: &[f32]) -> Vec<f32> {
+        let log_mel = self.log_mel(frame);
+        self.dct
+            .iter()
+            .map(|row| row.iter().zip(&log_mel).map(|(c, e)| c * e).sum())
+            .collect()
+    }
+
+    /// Complete frames of `pcm`, one every `hop_len` samples
+    pub fn frames<'a>(&self, pcm: &'a [f32]) -> impl Iterator<Item = &'a [f32]> + 'a {
+        let (frame_len, hop_len) = (self.config.frame_len, self.config.hop_len);
+        let count = match pcm.len() >= frame_len {
+            true => (pcm.len() - frame_len) / hop_len + 1,
+            false => 0,
+        };
+        (0..count).map(move |i| &pcm[i * hop_len..i * hop_len + frame_len])
+    }
+
+    /// `kind` features of every complete frame of `pcm`
+    pub fn extract(&self, pcm: &[f32], kind: AudioFeatureKind) -> Vec<Vec<f32>> {
+        self.frames(pcm)
+            .map(|frame| match kind {
+                AudioFeatureKind::LogMel => self.log_mel(frame),
+                AudioFeatureKind::Mfcc => self.mfcc(frame),
+            })
+            .collect()
+    }
+
+    /// `kind` features of `pcm` as `SensorType::Audio` readings, the
+    /// first stamped `start_ms` and each later one a hop after
+    pub fn readings(
+        &self,
+        pcm: &[f32],
+        start_ms: u64,
+        kind: AudioFeatureKind,
+    ) -> Vec<SensorReading> {
+        let hop_ms = self.config.hop_len as f64 * 1000.0 / f64::from(self.config.sample_rate);
+        self.extract(pcm, kind)
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let offset_ms = (i as f64 * hop_ms).round() as u64;
+                SensorReading::with_timestamp(SensorType::Audio, values, start_ms + offset_ms)
+            })
+            .collect()
+    }
+}
+
+/// 16-bit PCM scaled to `[-1, 1)`
+pub fn pcm_from_i16(samples: &[i16]) -> Vec<f32> {
+    samples.iter().map(|&s| f32::from(s) / 32_768.0).collect()
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_peaks_in_its_mel_band() {
+        let features = AudioFeatures::new(AudioConfig::default());
+        let tone: Vec<f32> = (0..16_000)
+            .map(|n| 0.5 * (2.0 * PI * 1_000.0 * n as f32 / 16_000.0).sin())
+            .collect();
+
+        let log_mel = features.extract(&tone[..4_000], AudioFeatureKind::LogMel);
+        assert_eq!(log_mel.len(), (4_000 - 400) / 160 + 1);
+        let Some(peak) = (0..26).max_by(|&a, &b| log_mel[0][a].total_cmp(&log_mel[0][b])) else {
+            panic!("expected mel bands");
+        };
+        let centre = features.band_centres()[peak];
+        assert!((850.0..1_150.0).contains(&centre), "peak at {} Hz", centre);
+
+        let silence = features.mfcc(&[0.0; 400]);
+        assert_eq!(silence.len(), 13);
+        assert!(silence.iter().all(|c| c.is_finite()));
+        assert!(features.mfcc(&tone[..400])[0] > silence[0]);
+
+        let readings = features.readings(&tone[..800], 5_000, AudioFeatureKind::Mfcc);
+        let stamps: Vec<u64> = readings.iter().map(|r| r.timestamp_ms).collect();
+        assert_eq!(stamps, vec![5_000, 5_010, 5_020]);
+        assert!(readings.iter().all(|r| r.sensor_type == SensorType::Audio));
+        assert_eq!(pcm_from_i16(&[i16::MIN, 0, 16_384]), vec![-1.0, 0.0, 0.5]);
+
+        // The FFT agrees with a direct DFT
+        let signal: Vec<f32> = (0..8).map(|n| (n * n % 5) as f32 - 2.0).collect();
+        let (mut re, mut im) = (signal.clone(), vec![0.0; 8]);
+        fft(&mut re, &mut im);
+        for k in 0..8 {
+            let (mut dft_re, mut dft_im) = (0.0, 0.0);
+            for (n, x) in signal.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f32 / 8.0;
+                dft_re += x * angle.cos();
+                dft_im += x * angle.sin();
+            }
+            assert!((re[k] - dft_re).abs() < 1e-4 && (im[k] - dft_im).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Benchmark Reports
+//!
+//! Criterion benches answer "how fast is this function on my machine".
+//! Releases need a different question answered: did routing, latency,
+//! memory or energy get worse than last time? [`run`] replays a labeled
+//! query suite through a fresh orchestrator and summarizes it as a
+//! [`BenchReport`], a JSON artifact that can be stored next to a release.
+//! [`compare`] checks a new report against an old one and lists every
+//! metric that regressed beyond [`BenchThresholds`].
+//!
+//! The CLI wraps both:
+//!
+//! ```bash
+//! mobile-ai bench run new.json
+//! mobile-ai bench compare old.json new.json   # exits 1 on regressions
+//! ```
+//!
+//! Energy and spend are estimates from the router's `RouteCosts`, not
+//! measurements; memory is the serialized size of the conversation history
+//! the run leaves behind.
+
+#![forbid(unsafe_code)]
+
+use crate::config::OrchestratorConfig;
+use crate::orchestrator::Orchestrator;
+use crate::types::{Query, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Instant;
+
+/// A query and the route it should take
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchCase {
+    /// Query text
+    pub query: String,
+    /// Route a correct router picks
+    pub expected: RoutingDecision,
+}
+
+impl BenchCase {
+    /// Case expecting `query` to take `expected`
+    pub fn new(query: impl Into<String>, expected: RoutingDecision) -> Self {
+        Self {
+            query: query.into(),
+            expected,
+        }
+    }
+}
+
+/// The built-in suite: everyday questions, heavy reasoning and secrets
+pub fn default_cases() -> Vec<BenchCase> {
+    use RoutingDecision::{Blocked, Local, Remote};
+    vec![
+        BenchCase::new("How do I iterate a HashMap?", Local),
+        BenchCase::new("What does the ? operator do in Rust?", Local),
+        BenchCase::new("Rename this variable to snake_case", Local),
+        BenchCase::new("Summarize the last three messages", Local),
+        BenchCase::new("Convert 5 miles to kilometers", Local),
+        BenchCase::new("Fix the typo in: fn mian() {}", Local),
+        BenchCase::new(
+            "Prove that this lock-free queue is linearizable under weak memory ordering",
+            Remote,
+        ),
+        BenchCase::new(
+            "Design a distributed consensus protocol and compare it with Raft and Paxos",
+            Remote,
+        ),
+        BenchCase::new(
+            "Write a formal verification of this type system's soundness theorem",
+            Remote,
+        ),
+        BenchCase::new("My password is hunter2, is it strong?", Blocked),
+        BenchCase::new("Store this api_key=sk-live-1234 for later", Blocked),
+    ]
+}
+
+/// How often the router picked the expected route
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingQuality {
+    /// Fraction of cases routed as expected
+    pub accuracy: f64,
+    /// Cases routed elsewhere, as "<query>: expected X, got Y"
+    pub mismatches: Vec<String>,
+}
+
+/// Wall-clock time spent in `Orchestrator::process`, in microseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// Median
+    pub p50_us: u64,
+    /// 95th percentile
+    pub p95_us: u64,
+    /// Slowest case
+    pub max_us: u64,
+}
+
+impl LatencyStats {
+    fn from_samples(mut samples: Vec<u64>) -> Self {
+        samples.sort_unstable();
+        let at = |q: f64| {
+            let index = ((samples.len() as f64 - 1.0) * q).round() as usize;
+            samples.get(index).copied().unwrap_or(0)
+        };
+        Self {
+            p50_us: at(0.5),
+            p95_us: at(0.95),
+            max_us: samples.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+/// Estimated cost of answering the suite, per query
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyStats {
+    /// Mean battery energy, in mWh
+    pub mean_mwh: f64,
+    /// Mean API spend, in USD
+    pub mean_cost_usd: f64,
+}
+
+/// Machine-readable summary of one benchmark run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    /// Crate version that produced the report
+    pub version: String,
+    /// Orchestrator state the suite ran against
+    pub state_fingerprint: String,
+    /// Number of cases run
+    pub cases: usize,
+    /// Routing quality
+    pub routing: RoutingQuality,
+    /// Processing latency
+    pub latency: LatencyStats,
+    /// Estimated bytes of conversation history held after the run
+    pub memory_bytes: u64,
+    /// Energy and spend estimates
+    pub energy: EnergyStats,
+}
+
+impl BenchReport {
+    /// Report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("cannot encode report: {}", e))
+    }
+
+    /// Report from JSON written by [`BenchReport::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid bench report: {}", e))
+    }
+}
+
+/// Run `cases` through a fresh orchestrator built from `config`
+pub fn run(config: OrchestratorConfig, cases: &[BenchCase]) -> Result<BenchReport, String> {
+    if cases.is_empty() {
+        return Err("a benchmark needs at least one case".to_string());
+    }
+    let costs = config.router.costs.clone();
+    let mut orchestrator = Orchestrator::with_config(config);
+    let state_fingerprint = orchestrator.state_fingerprint();
+
+    let mut samples = Vec::with_capacity(cases.len());
+    let mut mismatches = Vec::new();
+    let (mut energy_mwh, mut cost_usd) = (0.0, 0.0);
+    for case in cases {
+        let started = Instant::now();
+        let response = orchestrator
+            .process(Query::new(case.query.as_str()))
+            .map_err(|e| e.to_string())?;
+        samples.push(started.elapsed().as_micros() as u64);
+
+        if response.route != case.expected {
+            mismatches.push(format!(
+                "{}: expected {:?}, got {:?}",
+                case.query, case.expected, response.route
+            ));
+        }
+        if let Some(cost) = costs.route(response.route) {
+            energy_mwh += f64::from(cost.energy_mwh);
+            cost_usd += f64::from(cost.cost_usd);
+        }
+    }
+
+    let history = orchestrator.recent_history(cases.len());
+    let memory_bytes = serde_json::to_vec(&history).map_or(0, |bytes| bytes.len() as u64);
+    let count = cases.len() as f64;
+    Ok(BenchReport {
+        version: crate::VERSION.to_string(),
+        state_fingerprint,
+        cases: cases.len(),
+        routing: RoutingQuality {
+            accuracy: (cases.len() - mismatches.len()) as f64 / count,
+            mismatches,
+        },
+        latency: LatencyStats::from_samples(samples),
+        memory_bytes,
+        energy: EnergyStats {
+            mean_mwh: energy_mwh / count,
+            mean_cost_usd: cost_usd / count,
+        },
+    })
+}
+
+/// How much worse a new report may be before it counts as a regression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchThresholds {
+    /// Largest allowed drop in routing accuracy, in absolute terms
+    pub accuracy_drop: f64,
+    /// Largest allowed p95 latency ratio (new / old)
+    pub latency_ratio: f64,
+    /// Latency increases below this many microseconds are noise
+    pub latency_floor_us: u64,
+    /// Largest allowed memory ratio (new / old)
+    pub memory_ratio: f64,
+    /// Largest allowed energy and spend ratio (new / old)
+    pub energy_ratio: f64,
+}
+
+impl Default for BenchThresholds {
+    fn default() -> Self {
+        Self {
+            accuracy_drop: 0.02,
+            latency_ratio: 1.25,
+            latency_floor_us: 500,
+            memory_ratio: 1.10,
+            energy_ratio: 1.10,
+        }
+    }
+}
+
+/// A metric that got worse than the thresholds allow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Regression {
+    /// Metric name, e.g. "latency.p95_us"
+    pub metric: String,
+    /// Value in the old report
+    pub old: f64,
+    /// Value in the new report
+    pub new: f64,
+    /// Worst value the thresholds allowed
+    pub limit: f64,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} (limit {})",
+            self.metric, self.old, self.new, self.limit
+        )
+    }
+}
+
+/// Metrics in `new` that regressed from `old` beyond `thresholds`
+pub fn compare(
+    old: &BenchReport,
+    new: &BenchReport,
+    thresholds: &BenchThresholds,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    // Accuracy regresses downwards, every other metric upwards
+    let (old_accuracy, new_accuracy) = (old.routing.accuracy, new.routing.accuracy);
+    if new_accuracy < old_accuracy - thresholds.accuracy_drop {
+        regressions.push(Regression {
+            metric: "routing.accuracy".to_string(),
+            old: old_accuracy,
+            new: new_accuracy,
+            limit: old_accuracy - thresholds.accuracy_drop,
+        });
+    }
+
+    let mut check = |metric: &str, old: f64, new: f64, limit: f64| {
+        if new > limit {
+            regressions.push(Regression {
+                metric: metric.to_string(),
+                old,
+                new,
+                limit,
+            });
+        }
+    };
+
+    let old_p95 = old.latency.p95_us as f64;
+    let latency_limit =
+        (old_p95 * thresholds.latency_ratio).max(old_p95 + thresholds.latency_floor_us as f64);
+    check(
+        "latency.p95_us",
+        old_p95,
+        new.latency.p95_us as f64,
+        latency_limit,
+    );
+    check(
+        "memory_bytes",
+        old.memory_bytes as f64,
+        new.memory_bytes as f64,
+        old.memory_bytes as f64 * thresholds.memory_ratio,
+    );
+    check(
+        "energy.mean_mwh",
+        old.energy.mean_mwh,
+        new.energy.mean_mwh,
+        old.energy.mean_mwh * thresholds.energy_ratio,
+    );
+    check(
+        "energy.mean_cost_usd",
+        old.energy.mean_cost_usd,
+        new.energy.mean_cost_usd,
+        old.energy.mean_cost_usd * thresholds.energy_ratio,
+    );
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_reports_every_metric() {
+        let Ok(report) = run(OrchestratorConfig::default(), &default_cases()) else {
+            panic!("the default suite should run");
+        };
+        assert_eq!(report.cases, default_cases().len());
+        assert_eq!(report.version, crate::VERSION);
+        assert!((0.0..=1.0).contains(&report.routing.accuracy));
+        assert!(report.latency.p50_us <= report.latency.p95_us);
+        assert!(report.latency.p95_us <= report.latency.max_us);
+        assert!(report.memory_bytes > 0);
+        assert!(report.energy.mean_mwh > 0.0);
+
+        let Ok(json) = report.to_json() else {
+            panic!("a report should encode");
+        };
+        assert_eq!(BenchReport::from_json(&json), Ok(report));
+        assert!(run(OrchestratorConfig::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_compare_flags_regressions_beyond_thresholds() {
+        let old = BenchReport {
+            version: "1.0.0".to_string(),
+            state_fingerprint: "0".repeat(16),
+            cases: 10,
+            routing: RoutingQuality {
+                accuracy: 0.9,
+                mismatches: Vec::new(),
+            },
+            latency: LatencyStats {
+                p50_us: 1_000,
+                p95_us: 4_000,
+                max_us: 5_000,
+            },
+            memory_bytes: 10_000,
+            energy: EnergyStats {
+                mean_mwh: 40.0,
+                mean_cost_usd: 0.001,
+            },
+        };
+        let thresholds = BenchThresholds::default();
+        assert!(compare(&old, &old, &thresholds).is_empty());
+
+        // Within thresholds: small accuracy dip, 20% slower, 5% more memory
+        let mut new = old.clone();
+        new.routing.accuracy = 0.89;
+        new.latency.p95_us = 4_800;
+        new.memory_bytes = 10_500;
+        assert!(compare(&old, &new, &thresholds).is_empty());
+
+        new.routing.accuracy = 0.8;
+        new.latency.p95_us = 6_000;
+        new.energy.mean_mwh = 50.0;
+        let metrics: Vec<String> = compare(&old, &new, &thresholds)
+            .into_iter()
+            .map(|regression| regression.metric)
+            .collect();
+        assert_eq!(
+            metrics,
+            ["routing.accuracy", "latency.p95_us", "energy.mean_mwh"]
+        );
+
+        // Tiny absolute latency changes are noise, whatever the ratio
+        let mut fast = old.clone();
+        fast.latency.p95_us = 10;
+        let mut slower = fast.clone();
+        slower.latency.p95_us = 100;
+        assert!(compare(&fast, &slower, &thresholds).is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Binary Encoding of Models and Snapshots
+//!
+//! JSON of a 1000×1000 reservoir readout is tens of megabytes and slow to
+//! parse on a phone. With the `fast-serde` feature, the MLP, the echo
+//! state network, the spiking network and [`ContextSnapshot`] can also be
+//! encoded with `bincode`, which writes each `f32` in four bytes.
+//!
+//! Every encoding starts with an 8-byte header: a 4-byte tag naming the
+//! type, then the format version as a little-endian `u32`. `bincode` is
+//! not self-describing, so a body can only be read with the layout of the
+//! version that wrote it: decoding refuses another type's tag, any version
+//! other than the one this build writes, and bytes left over after the
+//! value, so a truncated, mislabelled or stale file fails loudly instead
+//! of loading as a different model. A format change bumps the version and
+//! adds a decoder for the old layout to `from_binary`.
+
+#![forbid(unsafe_code)]
+
+use crate::mlp::MLP;
+use crate::reservoir::{EchoStateNetwork, ESN_FORMAT_VERSION};
+use crate::snn::SpikingNetwork;
+use crate::types::ContextSnapshot;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Length of the tag and version header
+pub const HEADER_LEN: usize = 8;
+
+/// A type with a versioned binary encoding
+pub trait BinaryFormat: Serialize + DeserializeOwned {
+    /// Four bytes naming the type at the start of its encoding
+    const TAG: [u8; 4];
+    /// Format version written in the header
+    const VERSION: u32;
+
+    /// Encode as header plus `bincode` body
+    fn to_binary(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(&Self::TAG);
+        bytes.extend_from_slice(&Self::VERSION.to_le_bytes());
+        options()
+            .serialize_into(&mut bytes, self)
+            .map_err(|e| format!("cannot encode {}: {}", tag_name(&Self::TAG), e))?;
+        Ok(bytes)
+    }
+
+    /// Decode bytes written by [`Self::to_binary`] at this format version
+    fn from_binary(bytes: &[u8]) -> Result<Self, String> {
+        let name = tag_name(&Self::TAG);
+        if bytes.len() < HEADER_LEN {
+            return Err(format!("{} encoding is shorter than its header", name));
+        }
+        let (header, body) = bytes.split_at(HEADER_LEN);
+        if header[..4] != Self::TAG {
+            return Err(format!(
+                "expected a {} encoding, found tag {:?}",
+                name,
+                tag_name(&header[..4])
+            ));
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != Self::VERSION {
+            return Err(format!(
+                "unsupported {} format version {} (only {} is supported)",
+                name,
+                version,
+                Self::VERSION
+            ));
+        }
+        options()
+            .deserialize(body)
+            .map_err(|e| format!("invalid {} encoding: {}", name, e))
+    }
+}
+
+impl BinaryFormat for MLP {
+    const TAG: [u8; 4] = *b"MLP ";
+    const VERSION: u32 = 1;
+}
+
+impl BinaryFormat for EchoStateNetwork {
+    const TAG: [u8; 4] = *b"ESN ";
+    const VERSION: u32 = ESN_FORMAT_VERSION;
+}
+
+impl BinaryFormat for SpikingNetwork {
+    const TAG: [u8; 4] = *b"SNN ";
+    const VERSION: u32 = 1;
+}
+
+impl BinaryFormat for ContextSnapshot {
+    const TAG: [u8; 4] = *b"CTX ";
+    const VERSION: u32 = 1;
+}
+
+/// Little-endian, variable-length integers, no trailing bytes
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().reject_trailing_bytes()
+}
+
+fn tag_name(tag: &[u8]) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ambient::AmbientState;
+    use crate::types::{ConversationTurn, Query, Response, ResponseMetadata, RoutingDecision};
+
+    /// Decoding the encoding gives a value with the same JSON
+    fn assert_round_trip<T: BinaryFormat>(value: &T) -> Vec<u8> {
+        let Ok(bytes) = value.to_binary() else {
+            panic!("to_binary should succeed");
+        };
+        let Ok(decoded) = T::from_binary(&bytes) else {
+            panic!("from_binary should succeed");
+        };
+        assert_eq!(
+            serde_json::to_string(&decoded).ok(),
+            serde_json::to_string(value).ok()
+        );
+        bytes
+    }
+
+    #[test]
+    fn test_round_trips_and_rejects_foreign_encodings() {
+        let mut esn = EchoStateNetwork::new(8, 200, 4, 0.5, 0.9);
+        esn.update(&[0.5; 8]);
+        let bytes = assert_round_trip(&esn);
+        let Ok(json) = esn.to_json() else {
+            panic!("to_json should succeed");
+        };
+        assert!(
+            bytes.len() < json.len(),
+            "{} vs {}",
+            bytes.len(),
+            json.len()
+        );
+
+        assert_round_trip(&MLP::new(6, vec![8], 3));
+        assert_round_trip(&SpikingNetwork::new(4, 16, 2));
+        let turn = ConversationTurn {
+            query: Query::new("hello"),
+            response: Response {
+                text: "hi".to_string(),
+                route: RoutingDecision::Local,
+                confidence: 0.9,
+                latency_ms: 5,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: Some(1),
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        };
+        assert_round_trip(&ContextSnapshot {
+            project: Some("notes".to_string()),
+            history: vec![turn],
+            related: Vec::new(),
+            reservoir_state: Some(vec![0.25; 4]),
+            ambient: AmbientState::Walking,
+        });
+
+        // Another type, another version, truncation and trailing bytes
+        let Err(error) = MLP::from_binary(&bytes) else {
+            panic!("an ESN is not an MLP");
+        };
+        assert!(error.contains("found tag \"ESN\""), "{}", error);
+        let mut newer = bytes.clone();
+        newer[4..8].copy_from_slice(&(ESN_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(EchoStateNetwork::from_binary(&newer).is_err());
+        let mut older = bytes.clone();
+        older[4..8].copy_from_slice(&(ESN_FORMAT_VERSION - 1).to_le_bytes());
+        let Err(error) = EchoStateNetwork::from_binary(&older) else {
+            panic!("an older layout must not decode as the current one");
+        };
+        assert!(error.contains("format version 0"), "{}", error);
+        assert!(EchoStateNetwork::from_binary(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EchoStateNetwork::from_binary(&bytes[..4]).is_err());
+        let mut longer = bytes;
+        longer.push(0);
+        assert!(EchoStateNetwork::from_binary(&longer).is_err());
+    }
+
+    /// The fields an ESN encodes, in order, with no checks
+    #[derive(Serialize)]
+    struct EsnFields {
+        reservoir_size: usize,
+        input_size: usize,
+        output_size: usize,
+        output_weights: Vec<Vec<f32>>,
+        state: Vec<f32>,
+        leak_rate: f32,
+        spectral_radius: f32,
+        input_scaling: f32,
+        seed: u64,
+    }
+
+    #[test]
+    fn test_esn_with_wrong_dimensions_is_rejected() {
+        let fields = |state: usize, readout: usize| EsnFields {
+            reservoir_size: 8,
+            input_size: 4,
+            output_size: 2,
+            output_weights: vec![vec![0.0; readout]; 2],
+            state: vec![0.0; state],
+            leak_rate: 0.7,
+            spectral_radius: 0.95,
+            input_scaling: 1.0,
+            seed: 42,
+        };
+        let decode = |fields: EsnFields| {
+            let mut bytes = EchoStateNetwork::TAG.to_vec();
+            bytes.extend_from_slice(&ESN_FORMAT_VERSION.to_le_bytes());
+            if let Err(e) = options().serialize_into(&mut bytes, &fields) {
+                panic!("fields should encode: {}", e);
+            }
+            EchoStateNetwork::from_binary(&bytes)
+        };
+        let Ok(esn) = decode(fields(8, 8)) else {
+            panic!("matching dimensions should decode");
+        };
+        assert_eq!(esn.reservoir_size(), 8);
+        let Err(error) = decode(fields(7, 8)) else {
+            panic!("a short state should be rejected");
+        };
+        assert!(error.contains("reservoir_size 8"), "{}", error);
+        assert!(decode(fields(8, 9)).is_err());
+    }
+}
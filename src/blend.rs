@@ -0,0 +1,350 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Blending Local and Remote Answers
+//!
+//! A Hybrid route asks both the on-device and the remote model. Rather
+//! than keeping one answer arbitrarily, [`blend`] compares them:
+//!
+//! 1. **Agreement**: cosine similarity of the answers' embeddings and the
+//!    overlap of their keyphrases.
+//! 2. **Contradictions**: opposite yes/no verdicts, disjoint numbers, and
+//!    sentences about the same keyphrases where only one is negated.
+//!
+//! Agreeing answers are **merged**: the more confident answer is kept and
+//! sentences from the other that add new keyphrases are appended. Otherwise
+//! the result presents **two perspectives**, each labelled with its source
+//! and confidence, followed by the contradictions found.
+
+#![forbid(unsafe_code)]
+
+use crate::embedding::{cosine_similarity, Embedder};
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Words too common to count as keyphrases
+const STOPWORDS: &[&str] = &[
+    "about", "also", "because", "been", "being", "could", "does", "from", "have", "into", "just",
+    "more", "most", "only", "other", "should", "some", "than", "that", "their", "them", "then",
+    "there", "these", "they", "this", "very", "were", "what", "when", "where", "which", "while",
+    "will", "with", "would", "your",
+];
+
+/// Words that negate a sentence
+const NEGATIONS: &[&str] = &["not", "no", "never", "cannot", "none", "nothing"];
+
+/// Sentences of the less confident answer appended to a merged answer
+const MAX_ADDED_SENTENCES: usize = 2;
+
+/// Blending settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlendConfig {
+    /// Blend Hybrid answers instead of keeping only the Remote one
+    pub enabled: bool,
+    /// Similarity at or above which contradiction-free answers are merged
+    pub agreement_threshold: f32,
+    /// Prior confidence in Local answers (0.0-1.0)
+    pub local_confidence: f32,
+    /// Prior confidence in Remote answers (0.0-1.0)
+    pub remote_confidence: f32,
+}
+
+impl Default for BlendConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            agreement_threshold: 0.6,
+            local_confidence: 0.6,
+            remote_confidence: 0.85,
+        }
+    }
+}
+
+/// One of the answers being blended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// Backend that produced it
+    pub route: RoutingDecision,
+    /// Answer text
+    pub text: String,
+    /// Confidence in the answer (0.0-1.0)
+    pub confidence: f32,
+}
+
+/// How the answers were combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendKind {
+    /// One answer, extended by the other
+    Merged,
+    /// Both answers side by side
+    TwoPerspectives,
+}
+
+/// Result of blending two answers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blended {
+    /// Answer shown to the user
+    pub text: String,
+    /// How it was produced
+    pub kind: BlendKind,
+    /// Cosine similarity of the two answers
+    pub similarity: f32,
+    /// Fraction of keyphrases the answers share (Jaccard)
+    pub keyphrase_overlap: f32,
+    /// Disagreements found, in plain words
+    pub contradictions: Vec<String>,
+    /// The candidates, most confident first
+    pub sources: Vec<Candidate>,
+}
+
+impl Blended {
+    /// One-line provenance for routing explanations
+    pub fn provenance(&self) -> String {
+        let sources: Vec<String> = self
+            .sources
+            .iter()
+            .map(|source| format!("{:?} ({:.2})", source.route, source.confidence))
+            .collect();
+        let kind = match self.kind {
+            BlendKind::Merged => "merged",
+            BlendKind::TwoPerspectives => "two perspectives",
+        };
+        format!(
+            "blended {}: {}, similarity {:.2}, {} contradictions",
+            sources.join(" and "),
+            kind,
+            self.similarity,
+            self.contradictions.len()
+        )
+    }
+}
+
+/// Combine two answers to the same query
+pub fn blend(
+    first: Candidate,
+    second: Candidate,
+    embedder: &dyn Embedder,
+    config: &BlendConfig,
+) -> Blended {
+    let (primary, secondary) = if second.confidence > first.confidence {
+        (second, first)
+    } else {
+        (first, second)
+    };
+
+    let similarity = match (
+        embedder.embed(&primary.text),
+        embedder.embed(&secondary.text),
+    ) {
+        (Ok(a), Ok(b)) => cosine_similarity(&a, &b),
+        _ => 0.0,
+    };
+    let primary_keys = keyphrases(&primary.text);
+    let secondary_keys = keyphrases(&secondary.text);
+    let union = primary_keys.union(&secondary_keys).count();
+    let keyphrase_overlap = if union == 0 {
+        1.0
+    } else {
+        primary_keys.intersection(&secondary_keys).count() as f32 / union as f32
+    };
+    let contradictions = contradictions(&primary.text, &secondary.text);
+
+    let merge = contradictions.is_empty() && similarity >= config.agreement_threshold;
+    let text = if merge {
+        let mut text = primary.text.trim_end().to_string();
+        let mut known = primary_keys.clone();
+        let mut added = 0;
+        for sentence in sentences(&secondary.text) {
+            let keys = keyphrases(sentence);
+            if added < MAX_ADDED_SENTENCES && !keys.is_subset(&known) {
+                text.push(' ');
+                text.push_str(sentence);
+                known.extend(keys);
+                added += 1;
+            }
+        }
+        text
+    } else {
+        let mut text = String::from("Two perspectives:");
+        for candidate in [&primary, &secondary] {
+            text.push_str(&format!(
+                "\n\n{} model (confidence {:.2}):\n{}",
+                source_label(candidate.route),
+                candidate.confidence,
+                candidate.text.trim()
+            ));
+        }
+        if !contradictions.is_empty() {
+            text.push_str("\n\nThey disagree on:");
+            for contradiction in &contradictions {
+                text.push_str(&format!("\n- {}", contradiction));
+            }
+        }
+        text
+    };
+
+    Blended {
+        text,
+        kind: if merge {
+            BlendKind::Merged
+        } else {
+            BlendKind::TwoPerspectives
+        },
+        similarity,
+        keyphrase_overlap,
+        contradictions,
+        sources: vec![primary, secondary],
+    }
+}
+
+fn source_label(route: RoutingDecision) -> String {
+    match route {
+        RoutingDecision::Local => "On-device".to_string(),
+        RoutingDecision::Remote => "Remote".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Lowercased words of `text`
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Content words of four or more letters
+pub(crate) fn keyphrases(text: &str) -> BTreeSet<String> {
+    words(text)
+        .filter(|word| word.chars().count() >= 4 && word.chars().all(char::is_alphabetic))
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive(['.', '!', '?'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+}
+
+fn is_negated(sentence: &str) -> bool {
+    words(sentence).any(|word| NEGATIONS.contains(&word.as_str()) || word.ends_with("n't"))
+}
+
+/// Numbers mentioned in `text`
+fn numbers(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|n| n.trim_matches('.'))
+        .filter(|n| n.chars().any(|c| c.is_ascii_digit()))
+        .map(str::to_string)
+        .collect()
+}
+
+fn contradictions(a: &str, b: &str) -> Vec<String> {
+    let mut found = Vec::new();
+
+    let verdict = |text: &str| {
+        let first = words(text).next()?;
+        match first.as_str() {
+            "yes" => Some(true),
+            "no" => Some(false),
+            _ => None,
+        }
+    };
+    if let (Some(x), Some(y)) = (verdict(a), verdict(b)) {
+        if x != y {
+            found.push("one answer says yes, the other says no".to_string());
+        }
+    }
+
+    let (numbers_a, numbers_b) = (numbers(a), numbers(b));
+    if !numbers_a.is_empty() && !numbers_b.is_empty() && numbers_a.is_disjoint(&numbers_b) {
+        let join = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>().join(", ");
+        found.push(format!(
+            "different figures: {} vs {}",
+            join(&numbers_a),
+            join(&numbers_b)
+        ));
+    }
+
+    for sentence_a in sentences(a) {
+        let keys_a = keyphrases(sentence_a);
+        let opposite = sentences(b).find(|sentence_b| {
+            keys_a.intersection(&keyphrases(sentence_b)).count() >= 2
+                && is_negated(sentence_a) != is_negated(sentence_b)
+        });
+        if let Some(sentence_b) = opposite {
+            found.push(format!("\"{}\" vs \"{}\"", sentence_a, sentence_b));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::HashedBagOfWords;
+
+    fn candidate(route: RoutingDecision, text: &str, confidence: f32) -> Candidate {
+        Candidate {
+            route,
+            text: text.to_string(),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_agreeing_answers_are_merged() {
+        let embedder = HashedBagOfWords::new(256);
+        let blended = blend(
+            candidate(
+                RoutingDecision::Local,
+                "Paris is the capital of France. It sits on the Seine.",
+                0.6,
+            ),
+            candidate(
+                RoutingDecision::Remote,
+                "Paris is the capital of France. The Louvre museum is there.",
+                0.9,
+            ),
+            &embedder,
+            &BlendConfig::default(),
+        );
+        assert_eq!(blended.kind, BlendKind::Merged);
+        assert!(blended.contradictions.is_empty());
+        // The Remote answer leads, extended with what only Local said
+        assert!(blended
+            .text
+            .starts_with("Paris is the capital of France. The Louvre"));
+        assert!(blended.text.ends_with("It sits on the Seine."));
+        assert_eq!(blended.sources[0].route, RoutingDecision::Remote);
+        assert!(blended.provenance().contains("merged"));
+    }
+
+    #[test]
+    fn test_contradictions_give_two_perspectives() {
+        let embedder = HashedBagOfWords::new(256);
+        let blended = blend(
+            candidate(
+                RoutingDecision::Local,
+                "Yes. The battery saver mode keeps location tracking running.",
+                0.6,
+            ),
+            candidate(
+                RoutingDecision::Remote,
+                "No. The battery saver mode does not keep location tracking running.",
+                0.9,
+            ),
+            &embedder,
+            &BlendConfig::default(),
+        );
+        assert_eq!(blended.kind, BlendKind::TwoPerspectives);
+        assert_eq!(blended.contradictions.len(), 2);
+        assert!(blended
+            .text
+            .starts_with("Two perspectives:\n\nRemote model (confidence 0.90)"));
+        assert!(blended.text.contains("On-device model (confidence 0.60)"));
+
+        let figures = contradictions("It takes 12 minutes.", "It takes about 30 minutes.");
+        assert_eq!(figures, vec!["different figures: 12 vs 30".to_string()]);
+    }
+}
@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Response Cache
+//!
+//! Repeated questions are common on a phone ("what's on my calendar",
+//! "convert 5 miles to km"), and answering them again costs battery and,
+//! for remote routes, money. [`ResponseCache`] keeps recent responses and
+//! is consulted before routing:
+//!
+//! - **Exact match**: the query text, after trimming, lowercasing and
+//!   collapsing whitespace, equals a cached query.
+//! - **Near duplicate**: the query embedding has at least
+//!   `similarity_threshold` cosine similarity with a cached one.
+//!
+//! Entries expire after `ttl_secs` and the least recently used are evicted
+//! once the cache exceeds `max_entries` or `max_bytes`. Lookups only match
+//! entries from the same project and with the same required capabilities.
+
+#![forbid(unsafe_code)]
+
+use crate::embedding::cosine_similarity;
+use crate::types::{ModelCapability, Response, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Response cache settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Consult the cache before running inference
+    pub enabled: bool,
+    /// Seconds a response stays valid
+    pub ttl_secs: u64,
+    /// Most responses kept
+    pub max_entries: usize,
+    /// Most bytes of query and response text kept
+    pub max_bytes: usize,
+    /// Cosine similarity at which a query counts as a near duplicate;
+    /// 1.0 allows exact matches only
+    pub similarity_threshold: f32,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 3_600,
+            max_entries: 256,
+            max_bytes: 1 << 20,
+            similarity_threshold: 0.95,
+        }
+    }
+}
+
+/// A cached response and what it answered
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    key: String,
+    project: Option<String>,
+    capabilities: Vec<ModelCapability>,
+    embedding: Option<Vec<f32>>,
+    response: Response,
+    stored_ms: u64,
+    bytes: usize,
+}
+
+/// Hit and miss counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Lookups answered by an identical query
+    pub exact_hits: u64,
+    /// Lookups answered by a near-duplicate query
+    pub similar_hits: u64,
+    /// Lookups that found nothing
+    pub misses: u64,
+}
+
+/// What a cache lookup or insert is about
+#[derive(Debug, Clone, Copy)]
+pub struct CacheQuery<'a> {
+    /// Query text
+    pub text: &'a str,
+    /// Active project
+    pub project: Option<&'a str>,
+    /// Capabilities the query requires
+    pub capabilities: &'a [ModelCapability],
+    /// Query embedding, when near-duplicate matching is wanted
+    pub embedding: Option<&'a [f32]>,
+}
+
+/// Recently produced responses, most recently used last
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    config: CacheConfig,
+    entries: VecDeque<CacheEntry>,
+    bytes: usize,
+    stats: CacheStats,
+}
+
+impl ResponseCache {
+    /// Create an empty cache
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: VecDeque::new(),
+            bytes: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Cache settings
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Replace the cache settings, keeping the cached entries; a shorter
+    /// TTL expires them on the next lookup
+    pub fn set_config(&mut self, config: CacheConfig) {
+        self.config = config;
+    }
+
+    /// Whether near-duplicate matching is on, i.e. lookups want an
+    /// embedding
+    pub fn wants_embeddings(&self) -> bool {
+        self.config.enabled && self.config.similarity_threshold < 1.0
+    }
+
+    /// Cached response for `query`, preferring an exact match over the
+    /// most similar near duplicate
+    pub fn lookup(&mut self, query: &CacheQuery<'_>, now_ms: u64) -> Option<Response> {
+        if !self.config.enabled {
+            return None;
+        }
+        self.expire(now_ms);
+
+        let key = normalize(query.text);
+        let candidates = || {
+            self.entries.iter().enumerate().filter(|(_, entry)| {
+                entry.project.as_deref() == query.project
+                    && entry.capabilities == query.capabilities
+            })
+        };
+        let exact = candidates()
+            .find(|(_, entry)| entry.key == key)
+            .map(|(index, _)| index);
+        let found = match exact {
+            Some(index) => {
+                self.stats.exact_hits += 1;
+                Some(index)
+            }
+            None => {
+                let similar = query
+                    .embedding
+                    .filter(|_| self.wants_embeddings())
+                    .and_then(|target| {
+                        candidates()
+                            .filter_map(|(index, entry)| {
+                                let embedding = entry.embedding.as_deref()?;
+                                Some((index, cosine_similarity(target, embedding)))
+                            })
+                            .filter(|(_, similarity)| {
+                                *similarity >= self.config.similarity_threshold
+                            })
+                            .max_by(|a, b| a.1.total_cmp(&b.1))
+                            .map(|(index, _)| index)
+                    });
+                if similar.is_some() {
+                    self.stats.similar_hits += 1;
+                } else {
+                    self.stats.misses += 1;
+                }
+                similar
+            }
+        };
+
+        let entry = self.entries.remove(found?)?;
+        let response = entry.response.clone();
+        self.entries.push_back(entry);
+        Some(response)
+    }
+
+    /// Remember `response` as the answer to `query`. Blocked responses are
+    /// never cached.
+    pub fn insert(&mut self, query: &CacheQuery<'_>, response: &Response, now_ms: u64) {
+        if !self.config.enabled || response.route == RoutingDecision::Blocked {
+            return;
+        }
+        let key = normalize(query.text);
+        let project = query.project.map(str::to_string);
+        if let Some(index) = self.entries.iter().position(|entry| {
+            entry.key == key && entry.project == project && entry.capabilities == query.capabilities
+        }) {
+            self.remove(index);
+        }
+
+        let bytes = key.len() + response.text.len();
+        self.bytes += bytes;
+        self.entries.push_back(CacheEntry {
+            key,
+            project,
+            capabilities: query.capabilities.to_vec(),
+            embedding: query.embedding.map(<[f32]>::to_vec),
+            response: response.clone(),
+            stored_ms: now_ms,
+            bytes,
+        });
+        while self.entries.len() > self.config.max_entries || self.bytes > self.config.max_bytes {
+            self.remove(0);
+        }
+    }
+
+    /// Drop every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// Number of cached responses
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of text currently cached
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Hit and miss counters since creation
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Drop entries older than the TTL
+    fn expire(&mut self, now_ms: u64) {
+        let ttl_ms = self.config.ttl_secs.saturating_mul(1_000);
+        while let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| now_ms.saturating_sub(entry.stored_ms) > ttl_ms)
+        {
+            self.remove(index);
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some(entry) = self.entries.remove(index) {
+            self.bytes -= entry.bytes;
+        }
+    }
+}
+
+/// Lowercased text with whitespace runs collapsed
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ResponseMetadata;
+
+    fn response(text: &str) -> Response {
+        Response {
+            text: text.to_string(),
+            route: RoutingDecision::Remote,
+            confidence: 0.9,
+            latency_ms: 800,
+            metadata: ResponseMetadata {
+                model: None,
+                tokens: None,
+                cached: false,
+                context_budget: None,
+                turn_id: None,
+                explanation: None,
+                state_fingerprint: None,
+                degraded: None,
+                energy_joules: None,
+            },
+        }
+    }
+
+    fn query<'a>(text: &'a str, embedding: Option<&'a [f32]>) -> CacheQuery<'a> {
+        CacheQuery {
+            text,
+            project: None,
+            capabilities: &[],
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_exact_and_similar_hits() {
+        let mut cache = ResponseCache::new(CacheConfig::default());
+        let stored = [1.0, 0.0, 0.0];
+        cache.insert(
+            &query("What time is it?", Some(&stored)),
+            &response("Noon"),
+            0,
+        );
+
+        let Some(hit) = cache.lookup(&query("  what TIME is   it? ", None), 1_000) else {
+            panic!("normalized text should match exactly");
+        };
+        assert_eq!(hit.text, "Noon");
+
+        let close = [0.99, 0.05, 0.0];
+        assert!(cache
+            .lookup(&query("what's the time", Some(&close)), 1_000)
+            .is_some());
+        let far = [0.0, 1.0, 0.0];
+        assert!(cache
+            .lookup(&query("weather?", Some(&far)), 1_000)
+            .is_none());
+
+        let project = CacheQuery {
+            project: Some("work"),
+            ..query("What time is it?", None)
+        };
+        assert!(cache.lookup(&project, 1_000).is_none());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                exact_hits: 1,
+                similar_hits: 1,
+                misses: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_ttl_and_size_budget() {
+        let mut cache = ResponseCache::new(CacheConfig {
+            ttl_secs: 10,
+            max_entries: 2,
+            ..CacheConfig::default()
+        });
+        cache.insert(&query("a", None), &response("1"), 0);
+        cache.insert(&query("b", None), &response("2"), 5_000);
+        // Using "a" makes "b" the least recently used
+        assert!(cache.lookup(&query("a", None), 6_000).is_some());
+        cache.insert(&query("c", None), &response("3"), 6_000);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup(&query("b", None), 6_000).is_none());
+
+        // "a" was stored at 0 and expires after 10 s
+        assert!(cache.lookup(&query("a", None), 10_001).is_none());
+        assert!(cache.lookup(&query("c", None), 10_001).is_some());
+        assert_eq!(cache.bytes(), 2);
+
+        let mut blocked = response("no");
+        blocked.route = RoutingDecision::Blocked;
+        cache.insert(&query("d", None), &blocked, 10_001);
+        assert_eq!(cache.len(), 1);
+    }
+}
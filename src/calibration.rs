@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Router Confidence Calibration
+//!
+//! Raw MLP softmax probabilities are usually overconfident: a router that
+//! says 0.95 may be right only 70% of the time. A [`Calibrator`] fitted on
+//! held-out validation data maps them to confidences that match empirical
+//! accuracy, so `Response::confidence` can be trusted by SLA, budget and
+//! fallback logic.
+//!
+//! Two standard post-hoc methods are provided:
+//!
+//! - **Temperature scaling** rescales the whole distribution,
+//!   `softmax(ln p / T)`; `T > 1` softens overconfident outputs. One
+//!   parameter, fitted by minimizing validation negative log-likelihood.
+//! - **Platt scaling** fits a logistic regression
+//!   `sigmoid(a * logit(p) + b)` mapping the chosen class's probability to
+//!   the probability that the choice is correct.
+//!
+//! Neither changes which route wins; only the reported confidence.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+/// Probability floor, keeping logs and logits finite
+const EPS: f32 = 1e-6;
+
+/// Search range for the inverse temperature
+const MIN_INV_TEMPERATURE: f32 = 0.05;
+const MAX_INV_TEMPERATURE: f32 = 20.0;
+
+/// Post-softmax confidence calibration
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Calibrator {
+    /// Report raw probabilities
+    #[default]
+    Identity,
+    /// Temperature scaling
+    Temperature {
+        /// Temperature (> 0); above 1 softens, below 1 sharpens
+        temperature: f32,
+    },
+    /// Platt scaling of the chosen class's probability
+    Platt {
+        /// Slope on the logit
+        a: f32,
+        /// Intercept
+        b: f32,
+    },
+}
+
+impl Calibrator {
+    /// Fit temperature scaling to validation softmax outputs and labels
+    pub fn fit_temperature(probs: &[Vec<f32>], labels: &[usize]) -> Self {
+        // NLL is convex in the inverse temperature, so a golden-section
+        // search over it finds the optimum.
+        let nll = |beta: f32| -> f32 {
+            probs
+                .iter()
+                .zip(labels)
+                .map(|(p, &label)| -log_temperature_scale(p, 1.0 / beta)[label])
+                .sum()
+        };
+
+        let ratio = (5f32.sqrt() - 1.0) / 2.0;
+        let (mut lo, mut hi) = (MIN_INV_TEMPERATURE, MAX_INV_TEMPERATURE);
+        for _ in 0..60 {
+            let left = hi - ratio * (hi - lo);
+            let right = lo + ratio * (hi - lo);
+            if nll(left) < nll(right) {
+                hi = right;
+            } else {
+                lo = left;
+            }
+        }
+
+        Calibrator::Temperature {
+            temperature: 2.0 / (lo + hi),
+        }
+    }
+
+    /// Fit Platt scaling to validation softmax outputs and labels
+    pub fn fit_platt(probs: &[Vec<f32>], labels: &[usize]) -> Self {
+        let samples: Vec<(f32, f32)> = probs
+            .iter()
+            .zip(labels)
+            .map(|(p, &label)| {
+                let chosen = argmax(p);
+                let correct = if chosen == label { 1.0 } else { 0.0 };
+                (logit(p[chosen]), correct)
+            })
+            .collect();
+        if samples.is_empty() {
+            return Calibrator::Identity;
+        }
+
+        // Newton's method on the logistic log-loss, with a small ridge
+        // term keeping the Hessian invertible on separable data.
+        let (mut a, mut b) = (1.0f32, 0.0f32);
+        let ridge = 1e-3;
+        for _ in 0..50 {
+            let (mut ga, mut gb) = (ridge * a, ridge * b);
+            let (mut haa, mut hab, mut hbb) = (ridge, 0.0, ridge);
+            for &(x, y) in &samples {
+                let q = sigmoid(a * x + b);
+                let w = q * (1.0 - q);
+                ga += (q - y) * x;
+                gb += q - y;
+                haa += w * x * x;
+                hab += w * x;
+                hbb += w;
+            }
+            let det = haa * hbb - hab * hab;
+            if det.abs() < f32::EPSILON {
+                break;
+            }
+            let da = (hbb * ga - hab * gb) / det;
+            let db = (haa * gb - hab * ga) / det;
+            a -= da;
+            b -= db;
+            if da.abs() < 1e-6 && db.abs() < 1e-6 {
+                break;
+            }
+        }
+
+        Calibrator::Platt { a, b }
+    }
+
+    /// Calibrated confidence that `label` is correct, given softmax
+    /// probabilities `probs`
+    pub fn confidence(&self, probs: &[f32], label: usize) -> f32 {
+        let Some(&p) = probs.get(label) else {
+            return 0.0;
+        };
+        match *self {
+            Calibrator::Identity => p,
+            Calibrator::Temperature { temperature } => temperature_scale(probs, temperature)[label],
+            Calibrator::Platt { a, b } => sigmoid(a * logit(p) + b),
+        }
+    }
+}
+
+/// Expected calibration error: the gap between confidence and accuracy,
+/// averaged over `bins` equal-width confidence bins
+pub fn expected_calibration_error(
+    calibrator: &Calibrator,
+    probs: &[Vec<f32>],
+    labels: &[usize],
+    bins: usize,
+) -> f32 {
+    let bins = bins.max(1);
+    let mut confidence_sum = vec![0.0f32; bins];
+    let mut correct = vec![0.0f32; bins];
+    let mut count = vec![0usize; bins];
+
+    for (p, &label) in probs.iter().zip(labels) {
+        let chosen = argmax(p);
+        let confidence = calibrator.confidence(p, chosen);
+        let bin = ((confidence * bins as f32) as usize).min(bins - 1);
+        confidence_sum[bin] += confidence;
+        correct[bin] += if chosen == label { 1.0 } else { 0.0 };
+        count[bin] += 1;
+    }
+
+    let total = probs.len().max(1) as f32;
+    (0..bins)
+        .filter(|&i| count[i] > 0)
+        .map(|i| (confidence_sum[i] - correct[i]).abs() / total)
+        .sum()
+}
+
+/// `softmax(ln p / temperature)`
+fn temperature_scale(probs: &[f32], temperature: f32) -> Vec<f32> {
+    log_temperature_scale(probs, temperature)
+        .iter()
+        .map(|l| l.exp())
+        .collect()
+}
+
+/// `log_softmax(ln p / temperature)`, computed in log space so that the
+/// NLL stays informative for confidently wrong predictions
+fn log_temperature_scale(probs: &[f32], temperature: f32) -> Vec<f32> {
+    let scaled: Vec<f32> = probs
+        .iter()
+        .map(|p| p.max(EPS).ln() / temperature.max(EPS))
+        .collect();
+    let max = scaled.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = scaled.iter().map(|s| (s - max).exp()).sum::<f32>().ln();
+    scaled.iter().map(|s| s - max - log_sum).collect()
+}
+
+fn logit(p: f32) -> f32 {
+    let p = p.clamp(EPS, 1.0 - EPS);
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn argmax(values: &[f32]) -> usize {
+    crate::mlp::MLP::argmax(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validation set where the model always claims 0.9 for its choice
+    /// but is right only 60% of the time
+    fn overconfident() -> (Vec<Vec<f32>>, Vec<usize>) {
+        let mut probs = Vec::new();
+        let mut labels = Vec::new();
+        for i in 0..100 {
+            probs.push(vec![0.9, 0.05, 0.05]);
+            labels.push(if i % 5 < 3 { 0 } else { 1 + i % 2 });
+        }
+        (probs, labels)
+    }
+
+    #[test]
+    fn test_temperature_scaling_softens_overconfidence() {
+        let (probs, labels) = overconfident();
+        let calibrator = Calibrator::fit_temperature(&probs, &labels);
+
+        let Calibrator::Temperature { temperature } = calibrator else {
+            panic!("fit_temperature should produce a temperature calibrator");
+        };
+        assert!(temperature > 1.0, "temperature {}", temperature);
+        assert!((calibrator.confidence(&probs[0], 0) - 0.6).abs() < 0.02);
+
+        let raw = expected_calibration_error(&Calibrator::Identity, &probs, &labels, 10);
+        let calibrated = expected_calibration_error(&calibrator, &probs, &labels, 10);
+        assert!(calibrated < raw / 10.0, "{} vs {}", calibrated, raw);
+    }
+
+    #[test]
+    fn test_platt_scaling_matches_accuracy() {
+        let (mut probs, mut labels) = overconfident();
+        // A second group that is underconfident: 0.5 but right 90% of
+        // the time
+        for i in 0..100 {
+            probs.push(vec![0.5, 0.3, 0.2]);
+            labels.push(if i % 10 == 0 { 1 } else { 0 });
+        }
+        let calibrator = Calibrator::fit_platt(&probs, &labels);
+
+        assert!((calibrator.confidence(&probs[0], 0) - 0.6).abs() < 0.05);
+        assert!((calibrator.confidence(&probs[150], 0) - 0.9).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_identity_and_ranking_preserved() {
+        let probs = vec![0.2, 0.7, 0.1];
+        assert_eq!(Calibrator::Identity.confidence(&probs, 1), 0.7);
+        assert_eq!(Calibrator::Identity.confidence(&probs, 5), 0.0);
+
+        let calibrator = Calibrator::Temperature { temperature: 2.0 };
+        let calibrated: Vec<f32> = (0..3).map(|i| calibrator.confidence(&probs, i)).collect();
+        assert_eq!(argmax(&calibrated), 1);
+        assert!((calibrated.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+}
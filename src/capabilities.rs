@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Sensor Capability Registry
+//!
+//! The host declares which sensors exist on the device and which the user
+//! has granted permission for. Sensor-dependent features consult the
+//! registry and report a degraded or unavailable status through
+//! [`Capabilities`] rather than silently producing empty features.
+//!
+//! Sensors the host never declares are treated as usable, so hosts that
+//! don't integrate the registry keep the previous behavior.
+
+#![forbid(unsafe_code)]
+
+use crate::profile::Profile;
+use crate::sensor::{SensorBuffer, SensorType};
+use crate::supervisor::TaskHealth;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Host-declared availability of a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum SensorAvailability {
+    /// Host has not said anything about this sensor
+    #[default]
+    Undeclared,
+    /// Present and permitted
+    Available,
+    /// Hardware not present on this device
+    NotPresent,
+    /// Present but the user denied (or revoked) permission
+    PermissionDenied,
+}
+
+impl SensorAvailability {
+    /// Whether readings from this sensor may be used
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self,
+            SensorAvailability::Available | SensorAvailability::Undeclared
+        )
+    }
+}
+
+/// Sensor-dependent features whose status is reported in [`Capabilities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SensorFeature {
+    /// Ambient context classification (pocket / desk / walking ...)
+    Ambient,
+    /// Adaptive sampling-rate control
+    AdaptiveSampling,
+}
+
+impl SensorFeature {
+    /// All sensor-dependent features
+    pub const ALL: [SensorFeature; 2] = [SensorFeature::Ambient, SensorFeature::AdaptiveSampling];
+
+    /// Sensors this feature draws on
+    pub fn sensors(&self) -> &'static [SensorType] {
+        match self {
+            SensorFeature::Ambient => &[
+                SensorType::Proximity,
+                SensorType::Light,
+                SensorType::Accelerometer,
+            ],
+            SensorFeature::AdaptiveSampling => &[
+                SensorType::Accelerometer,
+                SensorType::Gyroscope,
+                SensorType::Audio,
+            ],
+        }
+    }
+
+    /// Human-readable name
+    pub const fn name(&self) -> &'static str {
+        match self {
+            SensorFeature::Ambient => "ambient",
+            SensorFeature::AdaptiveSampling => "adaptive_sampling",
+        }
+    }
+}
+
+/// Operating status of a sensor-dependent feature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeatureStatus {
+    /// All sensors usable
+    Available,
+    /// Running with reduced accuracy; some sensors unusable
+    Degraded {
+        /// Sensors the feature cannot use
+        missing: Vec<SensorType>,
+    },
+    /// No usable sensors; the feature is disabled
+    Unavailable {
+        /// Sensors the feature cannot use
+        missing: Vec<SensorType>,
+    },
+}
+
+impl FeatureStatus {
+    /// Whether the feature can run at all
+    pub fn is_operational(&self) -> bool {
+        !matches!(self, FeatureStatus::Unavailable { .. })
+    }
+}
+
+/// Status of one feature in a capabilities report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureCapability {
+    /// Feature being reported
+    pub feature: SensorFeature,
+    /// Current status
+    pub status: FeatureStatus,
+}
+
+/// Snapshot of what the orchestrator can currently do on this device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Declared availability per sensor
+    pub sensors: Vec<(SensorType, SensorAvailability)>,
+    /// Status of each sensor-dependent feature
+    pub features: Vec<FeatureCapability>,
+    /// Active operating profile
+    #[serde(default)]
+    pub profile: Profile,
+    /// Health of the supervised background tasks
+    #[serde(default)]
+    pub background: Vec<TaskHealth>,
+}
+
+impl Capabilities {
+    /// Status of a specific feature
+    pub fn feature(&self, feature: SensorFeature) -> Option<&FeatureStatus> {
+        self.features
+            .iter()
+            .find(|f| f.feature == feature)
+            .map(|f| &f.status)
+    }
+}
+
+/// Registry of host-declared sensor availability
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorRegistry {
+    declared: HashMap<SensorType, SensorAvailability>,
+}
+
+impl SensorRegistry {
+    /// Create an empty registry (all sensors undeclared)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a sensor's availability, replacing any earlier declaration
+    pub fn declare(&mut self, sensor_type: SensorType, availability: SensorAvailability) {
+        self.declared.insert(sensor_type, availability);
+    }
+
+    /// Availability of a sensor
+    pub fn availability(&self, sensor_type: SensorType) -> SensorAvailability {
+        self.declared.get(&sensor_type).copied().unwrap_or_default()
+    }
+
+    /// Whether readings from a sensor may be used
+    pub fn is_usable(&self, sensor_type: SensorType) -> bool {
+        self.availability(sensor_type).is_usable()
+    }
+
+    /// Status of a sensor-dependent feature
+    pub fn feature_status(&self, feature: SensorFeature) -> FeatureStatus {
+        let sensors = feature.sensors();
+        let missing: Vec<SensorType> = sensors
+            .iter()
+            .copied()
+            .filter(|&s| !self.is_usable(s))
+            .collect();
+
+        if missing.is_empty() {
+            FeatureStatus::Available
+        } else if missing.len() == sensors.len() {
+            FeatureStatus::Unavailable { missing }
+        } else {
+            FeatureStatus::Degraded { missing }
+        }
+    }
+
+    /// Copy of `buffer` without readings from unusable sensors
+    pub fn filter_buffer(&self, buffer: &SensorBuffer) -> SensorBuffer {
+        let mut filtered = SensorBuffer::new(buffer.len().max(1));
+        for reading in buffer.readings() {
+            if self.is_usable(reading.sensor_type) {
+                filtered.push(reading.clone());
+            }
+        }
+        filtered
+    }
+
+    /// Build a capabilities report; the orchestrator fills in the active
+    /// profile
+    pub fn capabilities(&self) -> Capabilities {
+        let mut sensors: Vec<(SensorType, SensorAvailability)> =
+            self.declared.iter().map(|(&s, &a)| (s, a)).collect();
+        sensors.sort_by_key(|(s, _)| s.name());
+
+        Capabilities {
+            sensors,
+            features: SensorFeature::ALL
+                .iter()
+                .map(|&feature| FeatureCapability {
+                    feature,
+                    status: self.feature_status(feature),
+                })
+                .collect(),
+            profile: Profile::default(),
+            background: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor::SensorReading;
+
+    #[test]
+    fn test_undeclared_sensors_are_usable() {
+        let registry = SensorRegistry::new();
+        assert!(registry.is_usable(SensorType::Light));
+        assert_eq!(
+            registry.feature_status(SensorFeature::Ambient),
+            FeatureStatus::Available
+        );
+    }
+
+    #[test]
+    fn test_degraded_and_unavailable_features() {
+        let mut registry = SensorRegistry::new();
+        registry.declare(SensorType::Proximity, SensorAvailability::NotPresent);
+
+        assert_eq!(
+            registry.feature_status(SensorFeature::Ambient),
+            FeatureStatus::Degraded {
+                missing: vec![SensorType::Proximity]
+            }
+        );
+
+        registry.declare(SensorType::Light, SensorAvailability::PermissionDenied);
+        registry.declare(SensorType::Accelerometer, SensorAvailability::NotPresent);
+
+        let status = registry.feature_status(SensorFeature::Ambient);
+        assert!(!status.is_operational());
+    }
+
+    #[test]
+    fn test_filter_buffer_drops_denied_sensors() {
+        let mut registry = SensorRegistry::new();
+        registry.declare(SensorType::Light, SensorAvailability::PermissionDenied);
+
+        let mut buffer = SensorBuffer::new(4);
+        buffer.push(SensorReading::new(SensorType::Light, vec![5.0]));
+        buffer.push(SensorReading::new(SensorType::Proximity, vec![0.0]));
+
+        let filtered = registry.filter_buffer(&buffer);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered.readings()[0].sensor_type, SensorType::Proximity);
+    }
+
+    #[test]
+    fn test_capabilities_report() {
+        let mut registry = SensorRegistry::new();
+        registry.declare(SensorType::Audio, SensorAvailability::PermissionDenied);
+
+        let caps = registry.capabilities();
+        assert_eq!(
+            caps.sensors,
+            vec![(SensorType::Audio, SensorAvailability::PermissionDenied)]
+        );
+        assert_eq!(caps.features.len(), SensorFeature::ALL.len());
+        assert!(matches!(
+            caps.feature(SensorFeature::AdaptiveSampling),
+            Some(FeatureStatus::Degraded { .. })
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Chunked Processing for Oversized Queries
+//!
+//! A pasted log file or document can be larger than any backend's context
+//! window. Instead of failing, the orchestrator runs a map-reduce job:
+//!
+//! 1. **Split** the query into overlapping chunks that fit the Local
+//!    context window, breaking at line or word boundaries.
+//! 2. **Map** each chunk to a partial answer, on Local where it fits.
+//! 3. **Reduce** the partial answers into one final answer. When the
+//!    partials are themselves too large they are chunked and mapped again,
+//!    for at most `max_reduce_rounds` rounds.
+//!
+//! Each mapped chunk publishes `Event::ChunkProcessed` with its partial
+//! answer, and [`ChunkedRun`] keeps the partials of the latest job for
+//! callers that want them after the fact.
+
+#![forbid(unsafe_code)]
+
+use crate::context_budget::estimate_tokens;
+use serde::{Deserialize, Serialize};
+
+/// Characters per token assumed by `estimate_tokens`
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Chunked processing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    /// Split queries that exceed every allowed backend's context window
+    pub enabled: bool,
+    /// Context window of the Local model (tokens)
+    pub local_context_tokens: usize,
+    /// Context window of the Remote model (tokens)
+    pub remote_context_tokens: usize,
+    /// Size of one chunk (tokens); must fit the Local window
+    pub chunk_tokens: usize,
+    /// Tokens repeated between neighbouring chunks
+    pub overlap_tokens: usize,
+    /// Times partial answers may be chunked again before the final answer
+    pub max_reduce_rounds: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            local_context_tokens: 4_096,
+            remote_context_tokens: 128_000,
+            chunk_tokens: 3_072,
+            overlap_tokens: 128,
+            max_reduce_rounds: 2,
+        }
+    }
+}
+
+/// Progress and partial answers of a chunked job
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkedRun {
+    /// Chunks the query was split into
+    pub total_chunks: usize,
+    /// Partial answers of the latest map round, in chunk order
+    pub partials: Vec<String>,
+    /// Reduce rounds that re-chunked the partial answers
+    pub reduce_rounds: usize,
+    /// Whether the final answer was produced
+    pub complete: bool,
+}
+
+/// Split `text` into chunks of about `chunk_tokens` tokens, each starting
+/// `overlap_tokens` before the end of the previous one. Chunks end at a
+/// line break or, failing that, a space in their last quarter when one
+/// exists.
+pub fn split_into_chunks(text: &str, chunk_tokens: usize, overlap_tokens: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let chunk_chars = chunk_tokens.max(1) * CHARS_PER_TOKEN;
+    let overlap_chars = (overlap_tokens * CHARS_PER_TOKEN).min(chunk_chars / 2);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + chunk_chars).min(chars.len());
+        if end < chars.len() {
+            let window = start + chunk_chars * 3 / 4..end;
+            let boundary = window
+                .clone()
+                .rev()
+                .find(|&i| chars[i] == '\n')
+                .or_else(|| window.rev().find(|&i| chars[i] == ' '));
+            if let Some(boundary) = boundary {
+                end = boundary + 1;
+            }
+        }
+        chunks.push(chars[start..end].iter().collect());
+        if end == chars.len() {
+            break;
+        }
+        start = end - overlap_chars.min(end - start - 1);
+    }
+    chunks
+}
+
+/// Query text asking for the partial answer of chunk `index` (0-based)
+pub fn map_prompt(chunk: &str, index: usize, total: usize) -> String {
+    format!(
+        "Part {} of {} of a long input. Extract what matters for answering \
+         questions about the whole input:\n\n{}",
+        index + 1,
+        total,
+        chunk
+    )
+}
+
+/// Query text asking to combine partial answers into one
+pub fn reduce_prompt(partials: &[String]) -> String {
+    let mut prompt = String::from(
+        "Combine these partial answers about parts of a long input into one answer:\n",
+    );
+    for (i, partial) in partials.iter().enumerate() {
+        prompt.push_str(&format!("\n[{}] {}", i + 1, partial));
+    }
+    prompt
+}
+
+/// Whether `text` fits a window of `limit_tokens`
+pub fn fits(text: &str, limit_tokens: usize) -> bool {
+    estimate_tokens(text) <= limit_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_overlap_and_cover_the_input() {
+        let text: String = (0..200).map(|i| format!("line {:03}\n", i)).collect();
+        let chunks = split_into_chunks(&text, 50, 5);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(fits(chunk, 50));
+        }
+        // Every chunk but the last ends at a line break
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.ends_with('\n'));
+        }
+        assert!(chunks[0].starts_with("line 000"));
+        let Some(last) = chunks.last() else {
+            panic!("there should be chunks");
+        };
+        assert!(last.ends_with("line 199\n"));
+        // Neighbouring chunks share text
+        let tail: String = chunks[0].chars().rev().take(9).collect::<String>();
+        let tail: String = tail.chars().rev().collect();
+        assert!(chunks[1].contains(tail.trim()));
+    }
+
+    #[test]
+    fn test_unbroken_text_is_cut_hard() {
+        let text = "x".repeat(1_000);
+        let chunks = split_into_chunks(&text, 100, 0);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), text);
+        assert!(split_into_chunks("", 100, 10).is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Wall Clock
+//!
+//! The core pipeline reads the time through this module instead of
+//! `SystemTime` and `Instant`, which panic on `wasm32-unknown-unknown`.
+//! Native builds use the system clock. A browser shell or PWA installs
+//! its own with [`set_clock`], typically wrapping `Date.now()`; without
+//! one, the WebAssembly build reads every time as 0, which keeps routing
+//! and the expert rules working but leaves rate limits and cache expiry
+//! idle.
+
+#![forbid(unsafe_code)]
+
+use std::sync::RwLock;
+
+/// Clock installed by the host, in milliseconds since the Unix epoch
+static HOST_CLOCK: RwLock<Option<fn() -> u64>> = RwLock::new(None);
+
+/// Whether this is a browser build, where `std` has no clock
+const NO_SYSTEM_CLOCK: bool = cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// Read the time from `clock` (ms since the epoch) instead of the system
+/// clock; `None` restores the system clock
+pub fn set_clock(clock: Option<fn() -> u64>) {
+    if let Ok(mut host) = HOST_CLOCK.write() {
+        *host = clock;
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    if let Some(clock) = HOST_CLOCK.read().ok().and_then(|host| *host) {
+        return clock();
+    }
+    if NO_SYSTEM_CLOCK {
+        return 0;
+    }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Seconds since the Unix epoch
+pub fn now_secs() -> u64 {
+    now_ms() / 1_000
+}
+
+/// Measures how long something took, like `Instant` but available in
+/// every build
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch {
+    started_ms: u64,
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    started: std::time::Instant,
+}
+
+impl Stopwatch {
+    /// Start measuring now
+    pub fn start() -> Self {
+        Self {
+            started_ms: now_ms(),
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// Milliseconds since `start`
+    pub fn elapsed_ms(&self) -> u64 {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        if HOST_CLOCK.read().ok().and_then(|host| *host).is_none() {
+            return self.started.elapsed().as_millis() as u64;
+        }
+        now_ms().saturating_sub(self.started_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_is_after_2020() {
+        assert!(now_secs() > 1_577_836_800);
+        assert!(Stopwatch::start().elapsed_ms() < 60_000);
+    }
+}
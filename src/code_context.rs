@@ -0,0 +1,429 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Code Context Extraction for Developer Queries
+//!
+//! A code-help question usually comes with a whole source file attached,
+//! or with a project knowledge pack registered on the orchestrator, while
+//! the answer depends on a handful of functions. Sending everything wastes
+//! tokens, which matters most on the Remote route. [`extract`] splits the
+//! sources into top-level definitions and keeps only those that mention
+//! the question's symbols or quoted error messages:
+//!
+//! - a symbol named in a definition's first line scores 3,
+//! - a symbol used in its body scores 1,
+//! - an error message found verbatim scores 5.
+//!
+//! Sections scoring at least half the best score are kept, best first, up
+//! to `max_sections` and `max_tokens`, and appear in the prompt in source
+//! order.
+
+#![forbid(unsafe_code)]
+
+use crate::context_budget::estimate_tokens;
+use crate::types::{Attachment, ModelCapability, Query};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// Words in questions that mark them as code help
+const CODE_MARKERS: &[&str] = &[
+    "error",
+    "panic",
+    "exception",
+    "traceback",
+    "stack trace",
+    "compile",
+    "bug",
+    "```",
+    "()",
+    "::",
+];
+
+/// Keywords that open a top-level definition
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "impl",
+    "trait",
+    "mod",
+    "const",
+    "static",
+    "type",
+    "class",
+    "def",
+    "function",
+    "func",
+    "interface",
+    "macro_rules!",
+];
+
+/// Modifiers that may precede a definition keyword
+const MODIFIERS: &[&str] = &[
+    "pub(crate)",
+    "pub(super)",
+    "pub",
+    "export",
+    "default",
+    "async",
+    "unsafe",
+    "extern",
+    "abstract",
+    "public",
+    "private",
+];
+
+/// Question words too common to identify code
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "this", "that", "why", "does", "what", "how", "when", "where",
+    "from", "not", "are", "can", "you", "was", "have", "has", "get", "fix", "code", "file", "line",
+    "function", "error", "into", "but", "its", "there", "here", "should", "would", "could", "help",
+    "please", "keep", "getting", "returns", "return", "after", "before", "doesn", "didn", "isn",
+    "don", "won",
+];
+
+/// Code context extraction settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CodeContextConfig {
+    /// Replace attached sources with their relevant sections
+    pub enabled: bool,
+    /// Most sections included in the prompt
+    pub max_sections: usize,
+    /// Most tokens of code included in the prompt
+    pub max_tokens: usize,
+}
+
+impl Default for CodeContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_sections: 4,
+            max_tokens: 1_024,
+        }
+    }
+}
+
+/// A top-level definition (with its doc comments) from a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSection {
+    /// File the section comes from
+    pub file: String,
+    /// First line (1-based)
+    pub start_line: usize,
+    /// Last line (1-based, inclusive)
+    pub end_line: usize,
+    /// Section source
+    pub text: String,
+    /// Relevance to the question
+    pub score: usize,
+}
+
+/// Sections selected for one question
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeContext {
+    /// Selected sections in source order
+    pub sections: Vec<CodeSection>,
+    /// Tokens of all the sources searched
+    pub source_tokens: usize,
+}
+
+impl CodeContext {
+    /// Tokens of the selected sections
+    pub fn tokens(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|section| estimate_tokens(&section.text))
+            .sum()
+    }
+
+    /// `question` followed by the selected sections, each headed by its
+    /// file and line range
+    pub fn prompt(&self, question: &str) -> String {
+        let mut prompt = format!("{}\n\nRelevant code:", question);
+        for section in &self.sections {
+            prompt.push_str(&format!(
+                "\n\n--- {}:{}-{}\n{}",
+                section.file,
+                section.start_line,
+                section.end_line,
+                section.text.trim_end()
+            ));
+        }
+        prompt
+    }
+}
+
+/// Whether `query` asks for help with code: it requires the Code
+/// capability or reads like a programming question
+pub fn is_code_help(query: &Query) -> bool {
+    if query.required_capabilities.contains(&ModelCapability::Code) {
+        return true;
+    }
+    let text = query.text.to_lowercase();
+    CODE_MARKERS.iter().any(|marker| text.contains(marker))
+}
+
+/// The sections of `files` most relevant to `question`
+pub fn extract<'a>(
+    question: &str,
+    files: impl IntoIterator<Item = &'a Attachment>,
+    config: &CodeContextConfig,
+) -> CodeContext {
+    let symbols = symbols(question);
+    let messages = error_messages(question);
+
+    let mut source_tokens = 0;
+    let mut candidates = Vec::new();
+    for file in files {
+        source_tokens += estimate_tokens(&file.content);
+        for mut section in split_sections(file) {
+            section.score = score(&section.text, &symbols, &messages);
+            if section.score > 0 {
+                candidates.push((candidates.len(), section));
+            }
+        }
+    }
+
+    // Stable sort: equal scores keep source order
+    candidates.sort_by_key(|(_, section)| Reverse(section.score));
+    let cutoff = candidates
+        .first()
+        .map_or(0, |(_, best)| best.score.div_ceil(2));
+    candidates.retain(|(_, section)| section.score >= cutoff);
+    let mut selected = Vec::new();
+    let mut tokens = 0;
+    for (order, section) in candidates {
+        if selected.len() == config.max_sections {
+            break;
+        }
+        let section_tokens = estimate_tokens(&section.text);
+        if tokens + section_tokens <= config.max_tokens {
+            tokens += section_tokens;
+            selected.push((order, section));
+        }
+    }
+    selected.sort_by_key(|(order, _)| *order);
+
+    CodeContext {
+        sections: selected.into_iter().map(|(_, section)| section).collect(),
+        source_tokens,
+    }
+}
+
+/// Split `file` at top-level definitions; leading comments and attributes
+/// stay with the definition they describe
+pub fn split_sections(file: &Attachment) -> Vec<CodeSection> {
+    let lines: Vec<&str> = file.content.lines().collect();
+    let mut starts = vec![0];
+    for (index, line) in lines.iter().enumerate() {
+        if !is_definition(line) {
+            continue;
+        }
+        let mut start = index;
+        while start > 0 && is_preamble(lines[start - 1]) {
+            start -= 1;
+        }
+        if starts.last().is_some_and(|&last| start > last) {
+            starts.push(start);
+        }
+    }
+
+    let mut sections = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let mut end = starts.get(i + 1).copied().unwrap_or(lines.len());
+        while end > start && lines[end - 1].trim().is_empty() {
+            end -= 1;
+        }
+        let text = lines[start..end].join("\n");
+        if text.trim().is_empty() {
+            continue;
+        }
+        sections.push(CodeSection {
+            file: file.name.clone(),
+            start_line: start + 1,
+            end_line: end,
+            text,
+            score: 0,
+        });
+    }
+    sections
+}
+
+/// Unindented line opening a definition
+fn is_definition(line: &str) -> bool {
+    if line.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let mut rest = line;
+    while let Some(stripped) = MODIFIERS.iter().find_map(|modifier| {
+        rest.strip_prefix(modifier)
+            .filter(|after| after.starts_with(' '))
+            .map(str::trim_start)
+    }) {
+        rest = stripped;
+    }
+    DEFINITION_KEYWORDS.iter().any(|keyword| {
+        rest.strip_prefix(keyword)
+            .is_some_and(|after| after.starts_with([' ', '<', '(']) || keyword.ends_with('!'))
+    })
+}
+
+/// Comment, attribute or decorator line preceding a definition
+fn is_preamble(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "#", "@", "/*", "*"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Identifier-like words of `question` worth searching for
+fn symbols(question: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for word in question.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        let is_symbol = word.len() >= 3
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOPWORDS.contains(&word.to_lowercase().as_str());
+        if is_symbol && !symbols.iter().any(|known| known == word) {
+            symbols.push(word.to_string());
+        }
+    }
+    symbols
+}
+
+/// Quoted text and `error:` messages in `question`
+fn error_messages(question: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let chars: Vec<(usize, char)> = question.char_indices().collect();
+    let mut open: Option<(char, usize)> = None;
+    for (i, &(position, c)) in chars.iter().enumerate() {
+        if !matches!(c, '"' | '`' | '\'') {
+            continue;
+        }
+        // Apostrophes inside words ("doesn't") neither open nor close
+        let after_word = i > 0 && chars[i - 1].1.is_alphanumeric();
+        let before_word = chars
+            .get(i + 1)
+            .is_some_and(|&(_, next)| next.is_alphanumeric());
+        match open {
+            Some((quote, start)) if quote == c && !before_word => {
+                messages.push(question[start..position].trim().to_string());
+                open = None;
+            }
+            None if !after_word => open = Some((c, position + c.len_utf8())),
+            _ => {}
+        }
+    }
+    for line in question.lines() {
+        if let Some(index) = line.to_lowercase().find("error:") {
+            messages.push(line[index + "error:".len()..].trim().to_string());
+        }
+    }
+    messages.retain(|message| message.len() >= 8);
+    messages
+}
+
+fn score(text: &str, symbols: &[String], messages: &[String]) -> usize {
+    let header = text
+        .lines()
+        .find(|line| !is_preamble(line))
+        .unwrap_or_default();
+    let mut score = 0;
+    for symbol in symbols {
+        if contains_word(header, symbol) {
+            score += 3;
+        } else if contains_word(text, symbol) {
+            score += 1;
+        }
+    }
+    score
+        + 5 * messages
+            .iter()
+            .filter(|m| text.contains(m.as_str()))
+            .count()
+}
+
+/// Whether `word` occurs in `text` and is not part of a longer identifier
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(index, _)| {
+        let before = text[..index].chars().next_back();
+        let after = text[index + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+use std::collections::HashMap;
+
+/// Parse `key=value` lines
+pub fn parse_config(text: &str) -> HashMap<String, String> {
+    text.lines().filter_map(split_pair).collect()
+}
+
+fn split_pair(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.to_string(), value.to_string()))
+}
+
+#[derive(Debug)]
+pub struct Server {
+    port: u16,
+}
+
+impl Server {
+    pub fn bind(&self) -> Result<(), String> {
+        Err(\"address already in use\".to_string())
+    }
+}
+";
+
+    fn file() -> Attachment {
+        Attachment {
+            name: "src/lib.rs".to_string(),
+            content: SOURCE.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sections_follow_definitions() {
+        let sections = split_sections(&file());
+        let starts: Vec<usize> = sections.iter().map(|s| s.start_line).collect();
+        // Header, parse_config (with its doc comment), split_pair, Server
+        // (with its derive), impl Server
+        assert_eq!(starts, vec![1, 3, 8, 13, 18]);
+        assert!(sections[3].text.starts_with("#[derive(Debug)]"));
+        assert_eq!(sections[0].end_line, 1);
+        assert_eq!(sections[4].end_line, 22);
+    }
+
+    #[test]
+    fn test_extracts_only_relevant_sections() {
+        let files = [file()];
+        let config = CodeContextConfig::default();
+
+        let context = extract("why does parse_config drop lines?", &files, &config);
+        let starts: Vec<usize> = context.sections.iter().map(|s| s.start_line).collect();
+        assert_eq!(starts, vec![3]);
+        assert!(context.tokens() < context.source_tokens);
+        let prompt = context.prompt("why does parse_config drop lines?");
+        assert!(prompt.contains("--- src/lib.rs:3-6"));
+        assert!(!prompt.contains("struct Server"));
+
+        let question = "bind() doesn't work: 'address already in use'";
+        let context = extract(question, &files, &config);
+        assert_eq!(context.sections.len(), 1);
+        assert!(context.sections[0].text.starts_with("impl Server"));
+
+        let narrow = CodeContextConfig {
+            max_sections: 1,
+            ..config
+        };
+        let context = extract("parse_config calls split_pair", &files, &narrow);
+        assert_eq!(context.sections.len(), 1);
+        assert!(is_code_help(&Query::new("error: cannot borrow")));
+        assert!(!is_code_help(&Query::new("weather tomorrow")));
+    }
+}
@@ -0,0 +1,971 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Configuration File Loading and Validation
+//!
+//! The orchestrator reads a TOML file with one table per component:
+//!
+//! ```toml
+//! profile = "BatterySaver"   # BatterySaver, Balanced, QualityFirst, OfflineOnly
+//!
+//! [router]
+//! heuristic_threshold = 0.6
+//!
+//! [ambient]
+//! dark_lux = 5.0
+//!
+//! [sampling]
+//! stationary_motion_hz = 2.0
+//!
+//! [remote]
+//! endpoint = "https://${API_HOST}/v1"
+//! api_key = "secret:openai"   # or "${OPENAI_API_KEY}", never the raw key
+//!
+//! [[expert.rules]]
+//! id = "KIDS_001"
+//! keywords = ["casino", "betting"]
+//! ```
+//!
+//! Bad configs on end-user devices must fail loudly and legibly, so every
+//! problem is reported as a [`Diagnostic`] with a line/column and, for
+//! misspelt keys, a "did you mean" suggestion. All problems are collected
+//! rather than stopping at the first one.
+
+#![forbid(unsafe_code)]
+
+use crate::activity::ActivityConfig;
+use crate::ambient::AmbientConfig;
+use crate::blend::BlendConfig;
+use crate::cache::CacheConfig;
+use crate::chunking::ChunkingConfig;
+use crate::code_context::CodeContextConfig;
+use crate::context_budget::ContextBudgetConfig;
+use crate::context_switch::ContextSwitchConfig;
+use crate::digest::DigestConfig;
+use crate::energy::EnergyConfig;
+use crate::expert::ExpertConfig;
+use crate::fallback::FallbackConfig;
+use crate::hybrid::HybridConfig;
+use crate::queue::QueueConfig;
+use crate::quota::QuotaConfig;
+use crate::forecast::ForecastConfig;
+use crate::fusion::FusionConfig;
+use crate::geofence::GeofenceConfig;
+use crate::journal::JournalConfig;
+use crate::memory::MemoryConfig;
+use crate::metrics::MetricsConfig;
+use crate::persistence::{RetentionConfig, SensorLogConfig, WriteBehindConfig};
+use crate::personalization::PersonalizationConfig;
+use crate::pool::PoolConfig;
+use crate::profile::Profile;
+use crate::prompt::PromptConfig;
+use crate::router::RouterConfig;
+use crate::sampling::SamplingConfig;
+use crate::secrets::{self, Secret, SecretProvider, SecretRef};
+use crate::sla::SlaConfig;
+use crate::tokens::TokensConfig;
+use crate::types::RoutingDecision;
+use crate::triage::TriageConfig;
+#[cfg(feature = "network")]
+use crate::webhooks::WebhookConfig;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::MqttConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Minimum similarity (Jaro-Winkler) for a "did you mean" suggestion
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// Top-level orchestrator configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrchestratorConfig {
+    /// Routing parameters
+    pub router: RouterConfig,
+    /// Ambient classifier thresholds
+    pub ambient: AmbientConfig,
+    /// Still / walking / driving recognition from motion sensors
+    pub activity: ActivityConfig,
+    /// App, place and activity change detection
+    pub context_switch: ContextSwitchConfig,
+    /// Orientation estimation from the motion sensors
+    pub fusion: FusionConfig,
+    /// Named GPS regions whose entry can switch project
+    pub geofence: GeofenceConfig,
+    /// Adaptive sampling rates
+    pub sampling: SamplingConfig,
+    /// Tiered logging of received sensor readings to the database
+    pub sensor_log: SensorLogConfig,
+    /// Age, per-project and size limits of the stored history
+    pub retention: RetentionConfig,
+    /// Buffering of conversation turns before they are written
+    pub write_behind: WriteBehindConfig,
+    /// Remote backend connection
+    pub remote: RemoteConfig,
+    /// Latency-driven context budget for the Local backend
+    pub context_budget: ContextBudgetConfig,
+    /// Per-route latency and spend SLAs
+    pub sla: SlaConfig,
+    /// Spend forecasting and proactive budget warnings
+    pub forecast: ForecastConfig,
+    /// Daily Markdown/Org journal export
+    pub journal: JournalConfig,
+    /// Exact and near-duplicate response caching
+    pub cache: CacheConfig,
+    /// Map-reduce processing of queries too long for any backend
+    pub chunking: ChunkingConfig,
+    /// Relevant-section extraction from sources attached to code questions
+    pub code_context: CodeContextConfig,
+    /// Distilling pasted compiler errors and stack traces
+    pub triage: TriageConfig,
+    /// How the two halves of a Hybrid route share the work
+    pub hybrid: HybridConfig,
+    /// Warm instances of the local model kept between inferences
+    pub pool: PoolConfig,
+    /// Retrying failed routes down a fallback chain
+    pub fallback: FallbackConfig,
+    /// Combining the Local and Remote answers of Hybrid routes
+    pub blend: BlendConfig,
+    /// Assembly of the prompts sent to backends
+    pub prompt: PromptConfig,
+    /// Token counting per model, for prompt budgets
+    pub tokens: TokensConfig,
+    /// Recurring queries scheduled by the user
+    pub digest: DigestConfig,
+    /// Queueing network-route queries while the device is offline
+    pub queue: QueueConfig,
+    /// Per-route counts, latencies, cache hit rate and blocks
+    pub metrics: MetricsConfig,
+    /// Battery energy estimates per response and per project
+    pub energy: EnergyConfig,
+    /// Opt-in long-term memory of facts about the user
+    pub memory: MemoryConfig,
+    /// Opt-in learning of expertise, verbosity and topics for routing
+    /// and prompts
+    pub personalization: PersonalizationConfig,
+    /// Deployment safety/policy rules
+    pub expert: ExpertConfig,
+    /// Signed outbound webhooks fired on selected events
+    #[cfg(feature = "network")]
+    pub webhooks: WebhookConfig,
+    /// MQTT topics streaming readings from wearables and peripherals
+    #[cfg(feature = "mqtt")]
+    pub mqtt: MqttConfig,
+    /// Operating profile applied at startup
+    pub profile: Profile,
+}
+
+/// Remote backend connection settings, used by `ApiEmbedder`
+///
+/// The API key is a [`SecretRef`]; it is resolved when a request is made,
+/// never stored in the config itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Endpoint URL; may contain `${ENV_VAR}` references
+    pub endpoint: String,
+    /// Where to fetch the API key from
+    pub api_key: Option<SecretRef>,
+}
+
+impl RemoteConfig {
+    /// Endpoint with environment variables substituted
+    pub fn resolved_endpoint(&self) -> Result<String, String> {
+        secrets::interpolate_env(&self.endpoint)
+    }
+
+    /// Fetch the API key at call time
+    pub fn resolve_api_key(&self, provider: &dyn SecretProvider) -> Result<Secret, String> {
+        self.api_key
+            .as_ref()
+            .ok_or_else(|| "remote.api_key is not configured".to_string())?
+            .resolve(provider)
+    }
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The config is rejected
+    Error,
+    /// The config loads but is probably not what was intended
+    Warning,
+}
+
+/// One problem found in a config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Error or warning
+    pub severity: Severity,
+    /// 1-based line, when the problem can be located
+    pub line: Option<usize>,
+    /// 1-based column, when the problem can be located
+    pub column: Option<usize>,
+    /// What is wrong
+    pub message: String,
+    /// Suggested fix (e.g. the closest known key)
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            line: None,
+            column: None,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    fn at(mut self, position: Option<(usize, usize)>) -> Self {
+        if let Some((line, column)) = position {
+            self.line = Some(line);
+            self.column = Some(column);
+        }
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}", severity)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " at line {}, column {}", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Config rejected; carries every diagnostic found
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// All problems, in file order where known
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl OrchestratorConfig {
+    /// Parse and validate a TOML config
+    ///
+    /// Succeeds only if there are no error-level diagnostics.
+    pub fn from_toml_str(text: &str) -> Result<Self, ConfigError> {
+        let (config, diagnostics) = Self::check(text);
+        match config {
+            Some(config) if !diagnostics.iter().any(|d| d.severity == Severity::Error) => {
+                Ok(config)
+            }
+            _ => Err(ConfigError { diagnostics }),
+        }
+    }
+
+    /// Read, parse and validate a TOML config file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path.as_ref()).map_err(|e| ConfigError {
+            diagnostics: vec![Diagnostic::error(format!(
+                "cannot read {}: {}",
+                path.as_ref().display(),
+                e
+            ))],
+        })?;
+        Self::from_toml_str(&text)
+    }
+
+    /// Parse a TOML config and return every diagnostic, including warnings
+    ///
+    /// The config is `None` when it could not be parsed or deserialized.
+    pub fn check(text: &str) -> (Option<Self>, Vec<Diagnostic>) {
+        let value: toml::Value = match toml::from_str(text) {
+            Ok(value) => value,
+            Err(e) => return (None, vec![toml_diagnostic(text, &e)]),
+        };
+
+        let mut diagnostics = Vec::new();
+        if let Ok(schema) = toml::Value::try_from(Self::schema_sample()) {
+            unknown_keys(text, &value, &schema, &mut Vec::new(), &mut diagnostics);
+        }
+
+        let config = match toml::from_str::<Self>(text) {
+            Ok(config) => {
+                diagnostics.extend(config.validate().into_iter().map(|(key, d)| {
+                    let position = locate_key(text, &key);
+                    d.at(position)
+                }));
+                Some(config)
+            }
+            Err(e) => {
+                diagnostics.push(toml_diagnostic(text, &e));
+                None
+            }
+        };
+
+        diagnostics.sort_by_key(|d| (d.line.unwrap_or(usize::MAX), d.column));
+        (config, diagnostics)
+    }
+
+    /// Default config with every optional field populated, so that
+    /// serializing it lists every known key.
+    fn schema_sample() -> Self {
+        let mut sample = Self::default();
+        sample.remote.api_key = Some(SecretRef::Env(String::new()));
+        sample.sla.daily_spend_limit = Some(0.0);
+        sample.expert.limits = QuotaConfig {
+            queries_per_minute: Some(0),
+            daily_remote_tokens: Some(0),
+            max_in_flight: Some(0),
+        };
+        for route in [
+            &mut sample.sla.local,
+            &mut sample.sla.remote,
+            &mut sample.sla.hybrid,
+        ] {
+            route.max_latency_ms = Some(0);
+        }
+        #[cfg(feature = "mqtt")]
+        {
+            sample.mqtt.username = Some(String::new());
+            sample.mqtt.password = Some(SecretRef::Env(String::new()));
+        }
+        sample
+    }
+
+    /// Semantic checks on values, keyed by dotted path (`router.x`)
+    fn validate(&self) -> Vec<(String, Diagnostic)> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, key: &str, message: String| {
+            if !ok {
+                problems.push((key.to_string(), Diagnostic::error(message)));
+            }
+        };
+
+        let threshold = self.router.heuristic_threshold;
+        check(
+            (0.0..=1.0).contains(&threshold),
+            "router.heuristic_threshold",
+            format!(
+                "router.heuristic_threshold must be between 0 and 1, got {}",
+                threshold
+            ),
+        );
+
+        check(
+            (0.0..=1.0).contains(&self.router.sla_penalty),
+            "router.sla_penalty",
+            format!(
+                "router.sla_penalty must be between 0 and 1, got {}",
+                self.router.sla_penalty
+            ),
+        );
+
+        let ambient = &self.ambient;
+        check(
+            ambient.still_variance < ambient.walking_variance,
+            "ambient.still_variance",
+            format!(
+                "ambient.still_variance ({}) must be below ambient.walking_variance ({})",
+                ambient.still_variance, ambient.walking_variance
+            ),
+        );
+        check(
+            ambient.flat_z_ratio > 0.0 && ambient.flat_z_ratio <= 1.0,
+            "ambient.flat_z_ratio",
+            format!(
+                "ambient.flat_z_ratio must be in (0, 1], got {}",
+                ambient.flat_z_ratio
+            ),
+        );
+
+        let sampling = &self.sampling;
+        for (key, rate) in [
+            (
+                "sampling.stationary_motion_hz",
+                sampling.stationary_motion_hz,
+            ),
+            ("sampling.active_motion_hz", sampling.active_motion_hz),
+            ("sampling.idle_audio_hz", sampling.idle_audio_hz),
+            ("sampling.boosted_audio_hz", sampling.boosted_audio_hz),
+        ] {
+            check(
+                rate > 0.0,
+                key,
+                format!("{} must be positive, got {}", key, rate),
+            );
+        }
+
+        let budget = &self.context_budget;
+        check(
+            budget.min_tokens <= budget.max_tokens,
+            "context_budget.min_tokens",
+            format!(
+                "context_budget.min_tokens ({}) must not exceed context_budget.max_tokens ({})",
+                budget.min_tokens, budget.max_tokens
+            ),
+        );
+        check(
+            budget.decrease_factor > 0.0 && budget.decrease_factor < 1.0,
+            "context_budget.decrease_factor",
+            format!(
+                "context_budget.decrease_factor must be in (0, 1), got {}",
+                budget.decrease_factor
+            ),
+        );
+
+        check(
+            self.pool.min_idle <= self.pool.max_idle,
+            "pool.min_idle",
+            format!(
+                "pool.min_idle ({}) must not exceed pool.max_idle ({})",
+                self.pool.min_idle, self.pool.max_idle
+            ),
+        );
+
+        check(
+            (0.0..=1.0).contains(&self.forecast.smoothing),
+            "forecast.smoothing",
+            format!(
+                "forecast.smoothing must be between 0 and 1, got {}",
+                self.forecast.smoothing
+            ),
+        );
+
+        check(
+            (0.0..=1.0).contains(&self.cache.similarity_threshold),
+            "cache.similarity_threshold",
+            format!(
+                "cache.similarity_threshold must be between 0 and 1, got {}",
+                self.cache.similarity_threshold
+            ),
+        );
+
+        let chunking = &self.chunking;
+        check(
+            chunking.chunk_tokens > 0 && chunking.chunk_tokens <= chunking.local_context_tokens,
+            "chunking.chunk_tokens",
+            format!(
+                "chunking.chunk_tokens ({}) must be between 1 and chunking.local_context_tokens ({})",
+                chunking.chunk_tokens, chunking.local_context_tokens
+            ),
+        );
+        check(
+            self.tokens.reserve_output_tokens < chunking.local_context_tokens,
+            "tokens.reserve_output_tokens",
+            format!(
+                "tokens.reserve_output_tokens ({}) must be less than chunking.local_context_tokens ({})",
+                self.tokens.reserve_output_tokens, chunking.local_context_tokens
+            ),
+        );
+        check(
+            chunking.overlap_tokens < chunking.chunk_tokens,
+            "chunking.overlap_tokens",
+            format!(
+                "chunking.overlap_tokens ({}) must be less than chunking.chunk_tokens ({})",
+                chunking.overlap_tokens, chunking.chunk_tokens
+            ),
+        );
+
+        check(
+            !self.fallback.chain.contains(&RoutingDecision::Blocked),
+            "fallback.chain",
+            "fallback.chain cannot contain Blocked".to_string(),
+        );
+
+        let blend = &self.blend;
+        for (key, value) in [
+            ("blend.agreement_threshold", blend.agreement_threshold),
+            ("blend.local_confidence", blend.local_confidence),
+            ("blend.remote_confidence", blend.remote_confidence),
+        ] {
+            check(
+                (0.0..=1.0).contains(&value),
+                key,
+                format!("{} must be between 0 and 1, got {}", key, value),
+            );
+        }
+
+        check(
+            self.prompt.max_tokens > 0,
+            "prompt.max_tokens",
+            "prompt.max_tokens must be at least 1".to_string(),
+        );
+
+        for (i, spec) in self.expert.rules.iter().enumerate() {
+            if let Err(message) = spec.compile() {
+                check(false, &format!("expert.rules.{}", i), message);
+            }
+        }
+        let mut bound: Vec<&str> = Vec::new();
+        for (i, spec) in self.expert.policies.iter().enumerate() {
+            let key = format!("expert.policies.{}", i);
+            if let Err(message) = spec.compile() {
+                check(false, &key, message);
+            }
+            for project in &spec.projects {
+                check(
+                    !bound.contains(&project.as_str()),
+                    &key,
+                    format!("project {} is bound to more than one policy", project),
+                );
+                bound.push(project);
+            }
+        }
+
+        let limits = &self.expert.limits;
+        for (key, value) in [
+            ("expert.limits.queries_per_minute", limits.queries_per_minute.map(u64::from)),
+            ("expert.limits.max_in_flight", limits.max_in_flight.map(|n| n as u64)),
+        ] {
+            check(
+                value != Some(0),
+                key,
+                format!("{} must be at least 1; leave it unset for no limit", key),
+            );
+        }
+
+        check(
+            self.memory.max_facts > 0,
+            "memory.max_facts",
+            "memory.max_facts must be at least 1".to_string(),
+        );
+
+        let smoothing = self.personalization.smoothing;
+        check(
+            smoothing > 0.0 && smoothing <= 1.0,
+            "personalization.smoothing",
+            format!(
+                "personalization.smoothing must be above 0 and at most 1, got {}",
+                smoothing
+            ),
+        );
+
+        let offset = self.digest.utc_offset_minutes;
+        check(
+            (-14 * 60..=14 * 60).contains(&offset),
+            "digest.utc_offset_minutes",
+            format!(
+                "digest.utc_offset_minutes must be within 14 hours of UTC, got {}",
+                offset
+            ),
+        );
+
+        let energy = &self.energy;
+        for (key, value) in [
+            ("energy.soc_watts", energy.soc_watts),
+            ("energy.radio_watts", energy.radio_watts),
+            ("energy.local_model_params_b", energy.local_model_params_b),
+            ("energy.joules_per_token_per_b", energy.joules_per_token_per_b),
+        ] {
+            check(
+                value >= 0.0,
+                key,
+                format!("{} must not be negative, got {}", key, value),
+            );
+        }
+        check(
+            energy.battery_wh > 0.0,
+            "energy.battery_wh",
+            format!("energy.battery_wh must be above 0, got {}", energy.battery_wh),
+        );
+
+        check(
+            !self.queue.enabled || self.queue.max_queued > 0,
+            "queue.max_queued",
+            "queue.max_queued must be at least 1 when queue.enabled is true".to_string(),
+        );
+
+        check(
+            self.code_context.max_sections > 0,
+            "code_context.max_sections",
+            "code_context.max_sections must be at least 1".to_string(),
+        );
+
+        check(
+            !self.journal.enabled || !self.journal.dir.as_os_str().is_empty(),
+            "journal.dir",
+            "journal.dir must be set when journal.enabled is true".to_string(),
+        );
+
+        let activity = &self.activity;
+        check(
+            (0.0..=1.0).contains(&activity.min_confidence),
+            "activity.min_confidence",
+            format!(
+                "activity.min_confidence must be between 0 and 1, got {}",
+                activity.min_confidence
+            ),
+        );
+        check(
+            activity.min_samples >= 2,
+            "activity.min_samples",
+            "activity.min_samples must be at least 2".to_string(),
+        );
+
+        let switch = &self.context_switch;
+        check(
+            switch.confirmations > 0,
+            "context_switch.confirmations",
+            "context_switch.confirmations must be at least 1".to_string(),
+        );
+        check(
+            switch.location_radius_m > 0.0,
+            "context_switch.location_radius_m",
+            format!(
+                "context_switch.location_radius_m must be above 0, got {}",
+                switch.location_radius_m
+            ),
+        );
+
+        let fusion = &self.fusion;
+        check(
+            (0.0..=1.0).contains(&fusion.gyro_weight),
+            "fusion.gyro_weight",
+            format!(
+                "fusion.gyro_weight must be between 0 and 1, got {}",
+                fusion.gyro_weight
+            ),
+        );
+        check(
+            fusion.accel_tolerance >= 0.0,
+            "fusion.accel_tolerance",
+            format!(
+                "fusion.accel_tolerance must not be negative, got {}",
+                fusion.accel_tolerance
+            ),
+        );
+
+        check(
+            self.write_behind.max_turns > 0,
+            "write_behind.max_turns",
+            "write_behind.max_turns must be at least 1".to_string(),
+        );
+
+        check(
+            self.sensor_log.stats_bucket_ms > 0,
+            "sensor_log.stats_bucket_ms",
+            "sensor_log.stats_bucket_ms must be at least 1".to_string(),
+        );
+
+        let geofence = &self.geofence;
+        for (i, region) in geofence.regions.iter().enumerate() {
+            let key = format!("geofence.regions.{}", i);
+            check(
+                !region.name.is_empty()
+                    && !geofence.regions[..i].iter().any(|r| r.name == region.name),
+                &key,
+                format!("geofence region name {:?} is empty or repeated", region.name),
+            );
+            check(
+                region.radius_m > 0.0,
+                &key,
+                format!(
+                    "geofence region {} needs a radius above 0, got {}",
+                    region.name, region.radius_m
+                ),
+            );
+        }
+        check(
+            geofence.exit_margin_m >= 0.0,
+            "geofence.exit_margin_m",
+            format!(
+                "geofence.exit_margin_m must not be negative, got {}",
+                geofence.exit_margin_m
+            ),
+        );
+
+        #[cfg(feature = "mqtt")]
+        if let Err(message) = self.mqtt.validate() {
+            check(false, "mqtt.topics", message);
+        }
+
+        problems
+    }
+}
+
+/// Diagnostic for a TOML syntax or type error, positioned by its span
+fn toml_diagnostic(text: &str, error: &toml::de::Error) -> Diagnostic {
+    let position = error.span().map(|span| line_column(text, span.start));
+    Diagnostic::error(error.message().to_string()).at(position)
+}
+
+/// Report keys in `value` that don't exist in `schema`
+fn unknown_keys(
+    text: &str,
+    value: &toml::Value,
+    schema: &toml::Value,
+    path: &mut Vec<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let (Some(table), Some(known)) = (value.as_table(), schema.as_table()) else {
+        return;
+    };
+
+    for (key, child) in table {
+        path.push(key.clone());
+        match known.get(key) {
+            Some(child_schema) => unknown_keys(text, child, child_schema, path, diagnostics),
+            None => {
+                let dotted = path.join(".");
+                let section = if path.len() > 1 {
+                    format!(" in [{}]", path[..path.len() - 1].join("."))
+                } else {
+                    String::new()
+                };
+                let mut diagnostic = Diagnostic::error(format!("unknown key `{}`{}", key, section))
+                    .at(locate_key(text, &dotted));
+                diagnostic.suggestion = closest(key, known.keys());
+                diagnostics.push(diagnostic);
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Closest known key by Jaro-Winkler similarity, if close enough
+fn closest<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    candidates
+        .map(|c| (strsim::jaro_winkler(key, c), c))
+        .filter(|(score, _)| *score >= SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, c)| c.clone())
+}
+
+/// Find where a dotted key (`section.key`) is written in the source
+///
+/// Handles `[section]` headers followed by `key = ...` lines, which is
+/// how these config files are laid out.
+fn locate_key(text: &str, dotted: &str) -> Option<(usize, usize)> {
+    let (section, key) = match dotted.rsplit_once('.') {
+        Some((section, key)) => (section, key),
+        None => ("", dotted),
+    };
+
+    let mut current = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            current = header
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            if section.is_empty() || current != section {
+                continue;
+            }
+        }
+        if current == section {
+            if let Some(rest) = trimmed.strip_prefix(key) {
+                if rest.trim_start().starts_with('=') {
+                    return Some((index + 1, line.len() - trimmed.len() + 1));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config_loads() {
+        let text = "[router]\nheuristic_threshold = 0.7\n\n[sampling]\nactive_motion_hz = 100.0\n";
+        let Ok(config) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("valid config should load");
+        };
+        assert_eq!(config.router.heuristic_threshold, 0.7);
+        assert_eq!(config.sampling.active_motion_hz, 100.0);
+        // Unspecified values keep their defaults
+        assert_eq!(config.ambient.dark_lux, AmbientConfig::default().dark_lux);
+    }
+
+    #[test]
+    fn test_sla_section_loads() {
+        let text = "[sla]\ndaily_spend_limit = 2.5\n\n[sla.local]\nmax_latency_ms = 3000\n";
+        let Ok(config) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("SLA config should load");
+        };
+        assert_eq!(config.sla.daily_spend_limit, Some(2.5));
+        assert_eq!(config.sla.local.max_latency_ms, Some(3000));
+        assert_eq!(config.sla.remote.max_latency_ms, None);
+
+        let Err(error) = OrchestratorConfig::from_toml_str("[sla.local]\nmax_latency = 1\n") else {
+            panic!("misspelt SLA key should be rejected");
+        };
+        assert_eq!(
+            error.diagnostics[0].suggestion.as_deref(),
+            Some("max_latency_ms")
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_suggests_correction() {
+        let text = "[router]\nenable_mlp = true\nheuristic_treshold = 0.7\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("misspelt key should be rejected");
+        };
+
+        let diagnostic = &error.diagnostics[0];
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, Some(1));
+        assert_eq!(
+            diagnostic.suggestion.as_deref(),
+            Some("heuristic_threshold")
+        );
+        assert!(error
+            .to_string()
+            .contains("did you mean `heuristic_threshold`"));
+    }
+
+    #[test]
+    fn test_unknown_section_suggests_correction() {
+        let Err(error) = OrchestratorConfig::from_toml_str("[ambeint]\ndark_lux = 3.0\n") else {
+            panic!("misspelt section should be rejected");
+        };
+        assert_eq!(error.diagnostics[0].suggestion.as_deref(), Some("ambient"));
+    }
+
+    #[test]
+    fn test_syntax_error_has_position() {
+        let Err(error) = OrchestratorConfig::from_toml_str("[router]\nenable_mlp = \n") else {
+            panic!("syntax error should be rejected");
+        };
+        assert_eq!(error.diagnostics.len(), 1);
+        assert_eq!(error.diagnostics[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_type_error_has_position() {
+        let text = "[sampling]\n\nidle_audio_hz = \"fast\"\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("type error should be rejected");
+        };
+        assert_eq!(error.diagnostics[0].line, Some(3));
+    }
+
+    #[test]
+    fn test_raw_api_key_is_rejected() {
+        let text = "[remote]\nendpoint = \"https://api.example.com\"\napi_key = \"sk-live-abc\"\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("raw API key should be rejected");
+        };
+        assert_eq!(error.diagnostics[0].line, Some(3));
+        assert!(error.diagnostics[0].message.contains("raw API keys"));
+        assert!(!error.to_string().contains("sk-live-abc"));
+    }
+
+    #[test]
+    fn test_api_key_reference_loads() {
+        let text = "[remote]\napi_key = \"secret:openai\"\n";
+        let Ok(config) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("secret reference should load");
+        };
+        assert_eq!(
+            config.remote.api_key,
+            Some(SecretRef::Provider("openai".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_semantic_errors_are_collected() {
+        let text = "[router]\nheuristic_threshold = 1.5\n\n[sampling]\nidle_audio_hz = 0.0\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("out-of-range values should be rejected");
+        };
+        assert_eq!(error.diagnostics.len(), 2);
+        assert_eq!(error.diagnostics[0].line, Some(2));
+        assert_eq!(error.diagnostics[1].line, Some(5));
+    }
+
+    #[test]
+    fn test_expert_rules_are_compiled_when_loaded() {
+        let text = "[[expert.rules]]\nid = \"KIDS_001\"\nkeywords = [\"casino\"]\n";
+        let Ok(config) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("a valid rule should load");
+        };
+        assert_eq!(config.expert.rules[0].id, "KIDS_001");
+
+        let text = "[[expert.rules]]\nid = \"BAD_001\"\nregex = \"(unclosed\"\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("an invalid regex should be rejected");
+        };
+        assert!(error.to_string().contains("BAD_001: invalid regex"));
+    }
+
+    #[test]
+    fn test_policies_are_validated() {
+        let text = r#"
+[[expert.policies]]
+name = "kids-mode"
+projects = ["homework"]
+routes = ["Local"]
+
+[[expert.policies.rules]]
+id = "KIDS_001"
+keywords = ["casino"]
+
+[[expert.policies]]
+name = "developer"
+projects = ["homework", "work"]
+routes = ["Remote"]
+"#;
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("a policy without Local and a doubly bound project should be rejected");
+        };
+        let message = error.to_string();
+        assert!(message.contains("routes must include Local"));
+        assert!(message.contains("project homework is bound to more than one policy"));
+
+        let Some((valid, _)) = text.split_once("[[expert.policies]]\nname = \"developer\"") else {
+            panic!("the test config should contain the developer policy");
+        };
+        let Ok(config) = OrchestratorConfig::from_toml_str(valid) else {
+            panic!("the kids-mode policy should load");
+        };
+        assert_eq!(config.expert.policies[0].rules[0].id, "KIDS_001");
+    }
+
+    #[test]
+    fn test_zero_resource_limits_are_rejected() {
+        let text = "[expert.limits]\nqueries_per_minute = 0\ndaily_remote_tokens = 5000\n";
+        let Err(error) = OrchestratorConfig::from_toml_str(text) else {
+            panic!("a zero rate limit should be rejected");
+        };
+        assert!(error.to_string().contains("expert.limits.queries_per_minute"));
+
+        let config = OrchestratorConfig::from_toml_str("[expert.limits]\nmax_in_flight = 2\n");
+        let Ok(config) = config else {
+            panic!("a positive limit should load: {:?}", config);
+        };
+        assert_eq!(config.expert.limits.max_in_flight, Some(2));
+        assert_eq!(config.expert.limits.queries_per_minute, None);
+    }
+}
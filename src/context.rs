@@ -9,19 +9,65 @@
 //! - Conversation history tracking
 //! - Project context switching
 //! - State snapshots
-//! - Context retrieval for query augmentation
+//! - Context retrieval for query augmentation, by recency or by semantic
+//!   similarity to the current query
 
-use crate::reservoir::{encode_text, EchoStateNetwork};
+use crate::ambient::AmbientState;
+use crate::embedding::{cosine_similarity, fit_dimension, Embedder, HashedBagOfWords};
+use crate::reservoir::EchoStateNetwork;
 use crate::types::{ContextSnapshot, ConversationTurn, Query, Response};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Maximum conversation history to keep in memory
-const MAX_HISTORY_SIZE: usize = 100;
+pub const MAX_HISTORY_SIZE: usize = 100;
 
 /// Dimension for text encoding (matches reservoir input size)
 const ENCODING_DIM: usize = 384;
 
+fn default_embedder() -> Arc<dyn Embedder> {
+    Arc::new(HashedBagOfWords::new(ENCODING_DIM))
+}
+
+fn default_reservoir() -> EchoStateNetwork {
+    EchoStateNetwork::new(
+        ENCODING_DIM, // input size
+        1000,         // reservoir size
+        100,          // output size (compressed context)
+        0.7,          // leak rate
+        0.95,         // spectral radius
+    )
+}
+
+/// A previous turn returned by [`ContextManager::search`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnMatch {
+    /// The matching turn
+    pub turn: ConversationTurn,
+    /// Cosine similarity to the search text (-1.0 to 1.0)
+    pub similarity: f32,
+}
+
+/// A chat session: one conversation thread with its own history and
+/// reservoir state, so concurrent chats on a device don't interleave
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Unique session identifier
+    pub id: String,
+    /// Project the session was started in
+    pub project: Option<String>,
+    /// Start time (ms since the UNIX epoch)
+    pub started_at: u64,
+    /// End time (ms since the UNIX epoch); `None` while the session is open
+    pub ended_at: Option<u64>,
+}
+
+/// Text of a turn as embedded for search
+fn turn_text(turn: &ConversationTurn) -> String {
+    format!("{}\n{}", turn.query.text, turn.response.text)
+}
+
 /// Context manager for maintaining conversation state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextManager {
@@ -34,6 +80,22 @@ pub struct ContextManager {
     /// Reservoir for temporal context encoding (Phase 2)
     #[serde(skip)]
     reservoir: Option<EchoStateNetwork>,
+    /// Reservoir states of the projects not currently active
+    #[serde(skip)]
+    parked_reservoir_states: HashMap<Option<String>, Vec<f32>>,
+    /// Skip reservoir updates while the device is throttling compute
+    #[serde(skip)]
+    reservoir_paused: bool,
+    /// Text encoder feeding the reservoir and semantic search
+    #[serde(skip, default = "default_embedder")]
+    embedder: Arc<dyn Embedder>,
+    /// Embeddings of `history`, same order; `None` where not yet
+    /// computed (restored sessions, embedder changes, failed embeddings)
+    #[serde(skip)]
+    turn_embeddings: Vec<Option<Vec<f32>>>,
+    /// Latest ambient classification from sensor fusion
+    #[serde(default)]
+    ambient: AmbientState,
 }
 
 impl ContextManager {
@@ -44,23 +106,18 @@ pub fn new() -> Self {
 
     /// Create a context manager with reservoir computing enabled
     pub fn with_reservoir(enable_reservoir: bool) -> Self {
-        let reservoir = if enable_reservoir {
-            Some(EchoStateNetwork::new(
-                ENCODING_DIM, // input size
-                1000,         // reservoir size
-                100,          // output size (compressed context)
-                0.7,          // leak rate
-                0.95,         // spectral radius
-            ))
-        } else {
-            None
-        };
+        let reservoir = enable_reservoir.then(default_reservoir);
 
         Self {
             current_project: None,
             history: Vec::new(),
             project_contexts: HashMap::new(),
             reservoir,
+            parked_reservoir_states: HashMap::new(),
+            reservoir_paused: false,
+            embedder: default_embedder(),
+            turn_embeddings: Vec::new(),
+            ambient: AmbientState::Unknown,
         }
     }
 
@@ -71,25 +128,33 @@ pub fn add_turn(&mut self, query: Query, response: Response) {
             response: response.clone(),
         };
 
-        // Update reservoir with query text if enabled
-        if let Some(ref mut reservoir) = self.reservoir {
-            let encoding = encode_text(&query.text, ENCODING_DIM);
-            reservoir.update(&encoding);
+        // Update reservoir with query text if enabled and not paused
+        if let Some(reservoir) = self.reservoir.as_mut() {
+            if !self.reservoir_paused {
+                // A failed embedding skips the update rather than the turn
+                if let Ok(encoding) = self.embedder.embed(&query.text) {
+                    reservoir.update(&fit_dimension(encoding, ENCODING_DIM));
+                }
+            }
         }
 
-        // Add to main history
+        // Add to main history, keeping the embeddings aligned with it
+        self.turn_embeddings.resize(self.history.len(), None);
+        self.turn_embeddings
+            .insert(0, self.embedder.embed(&turn_text(&turn)).ok());
         self.history.insert(0, turn.clone());
 
         // Trim if exceeds max size
         if self.history.len() > MAX_HISTORY_SIZE {
             self.history.truncate(MAX_HISTORY_SIZE);
+            self.turn_embeddings.truncate(MAX_HISTORY_SIZE);
         }
 
         // Add to project-specific history if applicable
         if let Some(ref project) = self.current_project {
             self.project_contexts
                 .entry(project.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .insert(0, turn);
 
             // Trim project history too
@@ -101,17 +166,127 @@ pub fn add_turn(&mut self, query: Query, response: Response) {
         }
     }
 
-    /// Switch to a different project context
+    /// Restore persisted turns (oldest first, each with its project)
+    /// without replaying them through the reservoir. Embeddings are
+    /// computed lazily by `search`.
+    pub fn restore_history(&mut self, turns: Vec<(Option<String>, ConversationTurn)>) {
+        self.turn_embeddings.resize(self.history.len(), None);
+        for (project, turn) in turns {
+            if let Some(project) = project {
+                let project_history = self.project_contexts.entry(project).or_default();
+                project_history.insert(0, turn.clone());
+                project_history.truncate(MAX_HISTORY_SIZE);
+            }
+            self.history.insert(0, turn);
+            self.turn_embeddings.insert(0, None);
+        }
+        self.history.truncate(MAX_HISTORY_SIZE);
+        self.turn_embeddings.truncate(MAX_HISTORY_SIZE);
+    }
+
+    /// Merge imported turns (each with its project) into the history by
+    /// query time, keeping the newest `MAX_HISTORY_SIZE` of each list, and
+    /// embed the merged turns for search in one pass. Unlike `add_turn`,
+    /// nothing is replayed through the reservoir.
+    pub fn merge_history(&mut self, turns: Vec<(Option<String>, ConversationTurn)>) {
+        self.turn_embeddings.resize(self.history.len(), None);
+        let mut merged: Vec<(ConversationTurn, Option<Vec<f32>>)> = self
+            .history
+            .drain(..)
+            .zip(self.turn_embeddings.drain(..))
+            .collect();
+        let mut projects = Vec::new();
+        for (project, turn) in turns {
+            if let Some(project) = project {
+                self.project_contexts
+                    .entry(project.clone())
+                    .or_default()
+                    .push(turn.clone());
+                projects.push(project);
+            }
+            merged.push((turn, None));
+        }
+        // Stable sorts: on equal times, turns already present stay newer
+        for project in projects {
+            if let Some(history) = self.project_contexts.get_mut(&project) {
+                history.sort_by_key(|turn| std::cmp::Reverse(turn.query.timestamp));
+                history.truncate(MAX_HISTORY_SIZE);
+            }
+        }
+        merged.sort_by_key(|(turn, _)| std::cmp::Reverse(turn.query.timestamp));
+        merged.truncate(MAX_HISTORY_SIZE);
+        for (turn, embedding) in &mut merged {
+            if embedding.is_none() {
+                *embedding = self.embedder.embed(&turn_text(turn)).ok();
+            }
+        }
+        (self.history, self.turn_embeddings) = merged.into_iter().unzip();
+    }
+
+    /// Switch to a different project context; the reservoir state of the
+    /// old project is parked and the new project's state restored
     pub fn switch_project(&mut self, project: impl Into<String>) {
-        let project = project.into();
-        self.current_project = Some(project);
+        let project = Some(project.into());
+        self.swap_reservoir_state(&project);
+        self.current_project = project;
     }
 
     /// Clear current project context
     pub fn clear_project(&mut self) {
+        self.swap_reservoir_state(&None);
         self.current_project = None;
     }
 
+    /// Park the current project's reservoir state and restore `next`'s,
+    /// starting from zero for projects without one
+    fn swap_reservoir_state(&mut self, next: &Option<String>) {
+        if *next == self.current_project {
+            return;
+        }
+        let Some(reservoir) = self.reservoir.as_mut() else {
+            return;
+        };
+        self.parked_reservoir_states
+            .insert(self.current_project.clone(), reservoir.state().to_vec());
+        let restored = self
+            .parked_reservoir_states
+            .remove(next)
+            .is_some_and(|state| reservoir.set_state(&state).is_ok());
+        if !restored {
+            reservoir.reset();
+        }
+    }
+
+    /// Reservoir state of `project`: the live state for the current
+    /// project, the parked one for others
+    pub fn project_reservoir_state(&self, project: Option<&str>) -> Option<Vec<f32>> {
+        if project == self.current_project.as_deref() {
+            return self.reservoir_state();
+        }
+        self.parked_reservoir_states
+            .get(&project.map(str::to_string))
+            .cloned()
+    }
+
+    /// Set the reservoir state of `project`, e.g. from storage. For the
+    /// current project it replaces the live state; others resume from it
+    /// when switched to.
+    pub fn restore_reservoir_state(&mut self, project: Option<&str>, state: Vec<f32>) {
+        if project == self.current_project.as_deref() {
+            if let Some(reservoir) = self.reservoir.as_mut() {
+                let _ = reservoir.set_state(&state);
+            }
+            return;
+        }
+        self.parked_reservoir_states
+            .insert(project.map(str::to_string), state);
+    }
+
+    /// Projects (`None` = no project) with a parked reservoir state
+    pub fn parked_reservoir_projects(&self) -> Vec<Option<String>> {
+        self.parked_reservoir_states.keys().cloned().collect()
+    }
+
     /// Get current project
     pub fn current_project(&self) -> Option<&str> {
         self.current_project.as_deref()
@@ -124,6 +299,47 @@ pub fn recent_history(&self, n: usize) -> Vec<ConversationTurn> {
         self.history.iter().take(n).cloned().collect()
     }
 
+    /// The `k` previous turns most similar in meaning to `query_text`,
+    /// most similar first (ties go to the more recent turn)
+    pub fn search(&self, query_text: &str, k: usize) -> Result<Vec<TurnMatch>, String> {
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .rank_history(query_text)?
+            .into_iter()
+            .take(k)
+            .map(|(i, similarity)| TurnMatch {
+                turn: self.history[i].clone(),
+                similarity,
+            })
+            .collect())
+    }
+
+    /// History indices with their similarity to `text`, most similar
+    /// first; turns that cannot be embedded are left out
+    fn rank_history(&self, text: &str) -> Result<Vec<(usize, f32)>, String> {
+        if self.history.is_empty() {
+            return Ok(Vec::new());
+        }
+        let target = self.embedder.embed(text)?;
+        let mut ranked: Vec<(usize, f32)> = self
+            .history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, turn)| {
+                let similarity = match self.turn_embeddings.get(i) {
+                    Some(Some(embedding)) => cosine_similarity(&target, embedding),
+                    _ => cosine_similarity(&target, &self.embedder.embed(&turn_text(turn)).ok()?),
+                };
+                Some((i, similarity))
+            })
+            .collect();
+        // Stable sort keeps newest-first order among equal scores
+        ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(ranked)
+    }
+
     /// Get project-specific history
     pub fn project_history(&self, project: &str) -> Option<Vec<ConversationTurn>> {
         self.project_contexts.get(project).cloned()
@@ -136,15 +352,88 @@ pub fn snapshot(&self, history_size: usize) -> ContextSnapshot {
         ContextSnapshot {
             project: self.current_project.clone(),
             history: self.recent_history(history_size),
+            related: Vec::new(),
             reservoir_state,
+            ambient: self.ambient,
         }
     }
 
+    /// Snapshot for augmenting `query_text`: the `history_size` most
+    /// recent turns plus up to `related` older turns most similar to it
+    pub fn relevant_snapshot(
+        &self,
+        query_text: &str,
+        history_size: usize,
+        related: usize,
+    ) -> Result<ContextSnapshot, String> {
+        let mut snapshot = self.snapshot(history_size);
+        snapshot.related = self
+            .rank_history(query_text)?
+            .into_iter()
+            .filter(|&(i, _)| i >= history_size)
+            .take(related)
+            .map(|(i, _)| self.history[i].clone())
+            .collect();
+        Ok(snapshot)
+    }
+
+    /// Record the latest ambient classification
+    pub fn set_ambient(&mut self, ambient: AmbientState) {
+        self.ambient = ambient;
+    }
+
+    /// Get the latest ambient classification
+    pub fn ambient(&self) -> AmbientState {
+        self.ambient
+    }
+
     /// Get reservoir state vector (if reservoir is enabled)
     pub fn reservoir_state(&self) -> Option<Vec<f32>> {
         self.reservoir.as_ref().map(|r| r.state().to_vec())
     }
 
+    /// Borrow the reservoir (if enabled)
+    pub fn reservoir(&self) -> Option<&EchoStateNetwork> {
+        self.reservoir.as_ref()
+    }
+
+    /// Replace the reservoir, e.g. with one restored from storage
+    pub fn set_reservoir(&mut self, reservoir: EchoStateNetwork) {
+        self.reservoir = Some(reservoir);
+    }
+
+    /// Start tracking conversation state in a fresh reservoir, unless one
+    /// is already running
+    pub fn enable_reservoir(&mut self) {
+        if self.reservoir.is_none() {
+            self.reservoir = Some(default_reservoir());
+        }
+    }
+
+    /// Replace the text encoder; its vectors are padded or truncated to
+    /// the reservoir's input width. Stored turns are re-embedded lazily
+    /// by `search`.
+    pub fn set_embedder(&mut self, embedder: Arc<dyn Embedder>) {
+        self.embedder = embedder;
+        self.turn_embeddings = vec![None; self.history.len()];
+    }
+
+    /// Text encoder in use
+    pub fn embedder(&self) -> &Arc<dyn Embedder> {
+        &self.embedder
+    }
+
+    /// Pause or resume reservoir updates; paused turns are still added to
+    /// history but do not advance the reservoir state
+    pub fn set_reservoir_paused(&mut self, paused: bool) {
+        self.reservoir_paused = paused;
+    }
+
+    /// Whether reservoir updates are paused
+    pub fn reservoir_paused(&self) -> bool {
+        self.reservoir_paused
+    }
+
     /// Reset reservoir state (if enabled)
     pub fn reset_reservoir(&mut self) {
         if let Some(ref mut reservoir) = self.reservoir {
@@ -155,6 +444,7 @@ pub fn reset_reservoir(&mut self) {
     /// Clear all history
     pub fn clear_history(&mut self) {
         self.history.clear();
+        self.turn_embeddings.clear();
     }
 
     /// Clear project-specific history
@@ -205,6 +495,12 @@ fn create_test_response(text: &str) -> Response {
                 model: Some("test-model".to_string()),
                 tokens: Some(50),
                 cached: false,
+                context_budget: None,
+                turn_id: None,
+                explanation: None,
+                state_fingerprint: None,
+                degraded: None,
+                energy_joules: None,
             },
         }
     }
@@ -283,6 +579,56 @@ fn test_snapshot() {
         assert_eq!(snapshot.history.len(), 1);
     }
 
+    fn add_conversation(cm: &mut ContextManager) {
+        for (query, answer) in [
+            ("how do rust lifetimes work", "lifetimes bound how long references live"),
+            ("best pasta recipe", "boil the pasta and add sauce"),
+            ("what is the borrow checker", "it enforces rust reference rules"),
+            ("weather tomorrow", "sunny with light wind"),
+        ] {
+            cm.add_turn(Query::new(query), create_test_response(answer));
+        }
+    }
+
+    #[test]
+    fn test_search_ranks_similar_turns() {
+        let mut cm = ContextManager::new();
+        add_conversation(&mut cm);
+
+        let Ok(matches) = cm.search("pasta sauce recipe", 2) else {
+            panic!("hashed embeddings never fail");
+        };
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].turn.query.text, "best pasta recipe");
+        assert!(matches[0].similarity > matches[1].similarity);
+        assert!(cm.search("anything", 0).is_ok_and(|m| m.is_empty()));
+
+        // Restored sessions have no cached embeddings but still search
+        let Ok(json) = cm.to_json() else {
+            panic!("serialization should succeed");
+        };
+        let Ok(restored) = ContextManager::from_json(&json) else {
+            panic!("deserialization should succeed");
+        };
+        let Ok(again) = restored.search("pasta sauce recipe", 2) else {
+            panic!("hashed embeddings never fail");
+        };
+        assert_eq!(again, matches);
+    }
+
+    #[test]
+    fn test_relevant_snapshot_adds_older_related_turns() {
+        let mut cm = ContextManager::new();
+        add_conversation(&mut cm);
+
+        let Ok(snapshot) = cm.relevant_snapshot("rust lifetimes and references", 1, 1) else {
+            panic!("hashed embeddings never fail");
+        };
+        assert_eq!(snapshot.history[0].query.text, "weather tomorrow");
+        assert_eq!(snapshot.related.len(), 1);
+        assert_eq!(snapshot.related[0].query.text, "how do rust lifetimes work");
+    }
+
     #[test]
     fn test_serialization() {
         let mut cm = ContextManager::new();
@@ -363,6 +709,21 @@ fn test_context_manager_with_reservoir() {
         assert_eq!(rs.len(), 1000);
     }
 
+    #[test]
+    fn test_paused_reservoir_keeps_state() {
+        let mut cm = ContextManager::with_reservoir(true);
+        cm.set_reservoir_paused(true);
+
+        let before = cm.reservoir_state();
+        cm.add_turn(Query::new("Hello world"), create_test_response("Hi"));
+        assert_eq!(cm.reservoir_state(), before);
+        assert_eq!(cm.conversation_count(), 1);
+
+        cm.set_reservoir_paused(false);
+        cm.add_turn(Query::new("Hello again"), create_test_response("Hi"));
+        assert_ne!(cm.reservoir_state(), before);
+    }
+
     #[test]
     fn test_reservoir_reset() {
         let mut cm = ContextManager::with_reservoir(true);
@@ -382,6 +743,15 @@ fn test_reservoir_reset() {
         assert!(state_after_reset.iter().all(|&x| x == 0.0));
     }
 
+    #[test]
+    fn test_snapshot_carries_ambient_state() {
+        let mut cm = ContextManager::new();
+        assert_eq!(cm.snapshot(1).ambient, AmbientState::Unknown);
+
+        cm.set_ambient(AmbientState::Walking);
+        assert_eq!(cm.snapshot(1).ambient, AmbientState::Walking);
+    }
+
     #[test]
     fn test_context_manager_without_reservoir() {
         let cm = ContextManager::new();
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Adaptive Context Length for the Local Backend
+//!
+//! The more retrieved or summarized context is fed to the on-device model,
+//! the richer (and slower) its answers. [`ContextBudgetController`] is a
+//! feedback controller that trades one for the other: it watches observed
+//! Local latencies and adjusts the context token budget so the p95 latency
+//! stays near a target (2 s by default).
+//!
+//! The rule is AIMD (additive increase, multiplicative decrease), as used
+//! for congestion control: when p95 exceeds the target the budget shrinks
+//! by a factor; when p95 is comfortably below it the budget grows by a
+//! fixed step. Samples are discarded after each adjustment so the next
+//! decision only reflects latencies measured at the new budget.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Controller settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextBudgetConfig {
+    /// Target 95th-percentile latency for Local inference (ms)
+    pub target_p95_ms: u64,
+    /// Smallest context budget (tokens)
+    pub min_tokens: usize,
+    /// Largest context budget, also the starting budget (tokens)
+    pub max_tokens: usize,
+    /// Latency samples needed before the budget is adjusted
+    pub window: usize,
+    /// Budget multiplier applied when p95 is over target (0.0-1.0)
+    pub decrease_factor: f32,
+    /// Tokens added when p95 is below `headroom * target`
+    pub increase_tokens: usize,
+    /// Fraction of the target below which the budget may grow (0.0-1.0)
+    pub headroom: f32,
+}
+
+impl Default for ContextBudgetConfig {
+    fn default() -> Self {
+        Self {
+            target_p95_ms: 2_000,
+            min_tokens: 256,
+            max_tokens: 4_096,
+            window: 20,
+            decrease_factor: 0.75,
+            increase_tokens: 128,
+            headroom: 0.8,
+        }
+    }
+}
+
+/// Rough token count for budgeting (about four characters per token).
+/// Prompts are counted with the model's tokenizer; see `tokens`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Latency-driven controller for the Local context budget
+#[derive(Debug, Clone)]
+pub struct ContextBudgetController {
+    config: ContextBudgetConfig,
+    budget: usize,
+    latencies: VecDeque<u64>,
+}
+
+impl ContextBudgetController {
+    /// Create a controller starting at the maximum budget
+    pub fn new(config: ContextBudgetConfig) -> Self {
+        Self {
+            budget: config.max_tokens,
+            latencies: VecDeque::with_capacity(config.window),
+            config,
+        }
+    }
+
+    /// Current context budget in tokens
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Record one Local inference latency, returning the (possibly
+    /// adjusted) budget
+    pub fn record_latency(&mut self, latency_ms: u64) -> usize {
+        self.latencies.push_back(latency_ms);
+        if self.latencies.len() < self.config.window.max(1) {
+            return self.budget;
+        }
+
+        let Some(p95) = self.p95_ms() else {
+            return self.budget;
+        };
+        let target = self.config.target_p95_ms;
+        let next = if p95 > target {
+            (self.budget as f32 * self.config.decrease_factor) as usize
+        } else if (p95 as f32) < target as f32 * self.config.headroom {
+            self.budget.saturating_add(self.config.increase_tokens)
+        } else {
+            self.budget
+        };
+
+        self.budget = next.clamp(self.config.min_tokens, self.config.max_tokens);
+        self.latencies.clear();
+        self.budget
+    }
+
+    /// Replace the configuration, clamping the current budget into the
+    /// new range; collected samples are kept
+    pub fn set_config(&mut self, config: ContextBudgetConfig) {
+        self.budget = self.budget.clamp(config.min_tokens, config.max_tokens);
+        self.config = config;
+    }
+
+    /// Active configuration
+    pub fn config(&self) -> &ContextBudgetConfig {
+        &self.config
+    }
+
+    /// 95th-percentile latency of the samples since the last adjustment
+    pub fn p95_ms(&self) -> Option<u64> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<u64> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        // Nearest-rank percentile
+        let rank = (sorted.len() as f32 * 0.95).ceil() as usize;
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+impl Default for ContextBudgetController {
+    fn default() -> Self {
+        Self::new(ContextBudgetConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> ContextBudgetController {
+        ContextBudgetController::new(ContextBudgetConfig {
+            window: 5,
+            ..ContextBudgetConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_slow_inference_shrinks_budget() {
+        let mut controller = controller();
+        assert_eq!(controller.budget(), 4_096);
+
+        for _ in 0..5 {
+            controller.record_latency(3_000);
+        }
+        assert_eq!(controller.budget(), 3_072);
+
+        // Repeated overshoot bottoms out at the minimum
+        for _ in 0..100 {
+            controller.record_latency(3_000);
+        }
+        assert_eq!(controller.budget(), 256);
+    }
+
+    #[test]
+    fn test_fast_inference_grows_budget() {
+        let mut controller = controller();
+        for _ in 0..5 {
+            controller.record_latency(3_000);
+        }
+        for _ in 0..5 {
+            controller.record_latency(500);
+        }
+        assert_eq!(controller.budget(), 3_072 + 128);
+
+        // Within the headroom band the budget holds steady
+        for _ in 0..5 {
+            controller.record_latency(1_800);
+        }
+        assert_eq!(controller.budget(), 3_200);
+    }
+
+    #[test]
+    fn test_single_outlier_is_ignored() {
+        let mut controller = ContextBudgetController::default();
+        for _ in 0..19 {
+            controller.record_latency(1_000);
+        }
+        assert_eq!(controller.p95_ms(), Some(1_000));
+        // One slow sample in twenty lies above the 95th percentile
+        controller.record_latency(10_000);
+        assert_eq!(controller.budget(), 4_096);
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+}
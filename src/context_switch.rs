@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Context-Switch Detection
+//!
+//! Notices when the user's situation changes: a different app in the
+//! foreground, a move to a new place, a new physical activity. Each change
+//! is reported as a [`ContextEvent`], which the orchestrator publishes as
+//! `Event::ContextChanged` for proactive prompts to react to, and which
+//! can switch the active project when a mapped app comes forward.
+//!
+//! Sensor-derived changes are debounced so one noisy reading does not
+//! count as a switch:
+//!
+//! - places come from GPS fixes; a move is confirmed once `confirmations`
+//!   consecutive fixes lie more than `location_radius_m` from the last
+//!   place. Fixes with an error above `max_fix_error_m` are ignored
+//! - activities come from the ambient classifier; a new state must be
+//!   seen `confirmations` times in a row; `Unknown` neither counts nor
+//!   interrupts the run
+//! - apps are reported by the host, which knows them for certain, so a
+//!   switch is reported at once
+//!
+//! The first place and activity only set the baseline; they are not
+//! reported as changes. Entering and leaving named places is reported
+//! separately, by `geofence`.
+
+#![forbid(unsafe_code)]
+
+use crate::ambient::AmbientState;
+use crate::sensor::{SensorBuffer, SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+
+/// Mean Earth radius in metres, for great-circle distances
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// A change in the user's context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ContextEvent {
+    /// Another app came to the foreground
+    AppSwitch {
+        /// App in the foreground before, if one was reported
+        from: Option<String>,
+        /// App now in the foreground
+        to: String,
+    },
+    /// The device settled somewhere new
+    LocationChange {
+        /// Latitude of the new place (degrees)
+        latitude: f32,
+        /// Longitude of the new place (degrees)
+        longitude: f32,
+        /// Distance from the previous place (m)
+        distance_m: f32,
+    },
+    /// The physical activity or situation changed
+    ActivityChange {
+        /// Previous state
+        from: AmbientState,
+        /// New state
+        to: AmbientState,
+    },
+    /// The device entered a user-defined region (see `geofence`)
+    RegionEntered {
+        /// Name of the region
+        region: String,
+    },
+    /// The device left a user-defined region
+    RegionExited {
+        /// Name of the region
+        region: String,
+    },
+}
+
+/// An app whose arrival in the foreground switches the active project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppProject {
+    /// App identifier as reported by the host (e.g. package name)
+    pub app: String,
+    /// Project to switch to
+    pub project: String,
+}
+
+/// Thresholds used by [`ContextSwitchDetector`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextSwitchConfig {
+    /// Distance (m) from the last place beyond which a fix is elsewhere
+    pub location_radius_m: f32,
+    /// GPS fixes with a larger reported error (m) are ignored
+    pub max_fix_error_m: f32,
+    /// Consecutive observations needed to confirm a new place or activity
+    pub confirmations: usize,
+    /// Apps that switch the active project when they come forward
+    pub app_projects: Vec<AppProject>,
+}
+
+impl Default for ContextSwitchConfig {
+    fn default() -> Self {
+        Self {
+            location_radius_m: 250.0,
+            max_fix_error_m: 100.0,
+            confirmations: 3,
+            app_projects: Vec::new(),
+        }
+    }
+}
+
+/// Debounced detector of app, place and activity changes
+#[derive(Debug, Clone, Default)]
+pub struct ContextSwitchDetector {
+    config: ContextSwitchConfig,
+    app: Option<String>,
+    /// Last confirmed place (latitude, longitude)
+    place: Option<(f32, f32)>,
+    /// Consecutive fixes away from `place`
+    away: usize,
+    /// Timestamp of the newest fix seen, so a buffer can be observed again
+    last_fix_ms: Option<u64>,
+    activity: AmbientState,
+    /// State seen instead of `activity`, and how many times in a row
+    candidate: (AmbientState, usize),
+}
+
+impl ContextSwitchDetector {
+    /// Create a detector with the given thresholds
+    pub fn new(config: ContextSwitchConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// The host reports `app` in the foreground
+    pub fn observe_app(&mut self, app: &str) -> Option<ContextEvent> {
+        if self.app.as_deref() == Some(app) {
+            return None;
+        }
+        let from = self.app.replace(app.to_string());
+        Some(ContextEvent::AppSwitch {
+            from,
+            to: app.to_string(),
+        })
+    }
+
+    /// Project mapped to `app` in `app_projects`
+    pub fn project_for(&self, app: &str) -> Option<&str> {
+        self.config
+            .app_projects
+            .iter()
+            .find(|mapping| mapping.app == app)
+            .map(|mapping| mapping.project.as_str())
+    }
+
+    /// Feed one GPS reading (latitude, longitude, error in metres);
+    /// other sensors are ignored
+    pub fn observe_fix(&mut self, reading: &SensorReading) -> Option<ContextEvent> {
+        if reading.sensor_type != SensorType::Gps {
+            return None;
+        }
+        let (&latitude, &longitude) = (reading.values.first()?, reading.values.get(1)?);
+        if !latitude.is_finite() || !longitude.is_finite() {
+            return None;
+        }
+        let error = reading.values.get(2).copied().unwrap_or(0.0);
+        if error > self.config.max_fix_error_m {
+            return None;
+        }
+
+        let Some(place) = self.place else {
+            self.place = Some((latitude, longitude));
+            return None;
+        };
+        let distance_m = distance_m(place, (latitude, longitude));
+        if distance_m <= self.config.location_radius_m {
+            self.away = 0;
+            return None;
+        }
+        self.away += 1;
+        if self.away < self.config.confirmations {
+            return None;
+        }
+        self.away = 0;
+        self.place = Some((latitude, longitude));
+        Some(ContextEvent::LocationChange {
+            latitude,
+            longitude,
+            distance_m,
+        })
+    }
+
+    /// Feed the latest ambient classification
+    pub fn observe_activity(&mut self, state: AmbientState) -> Option<ContextEvent> {
+        if state == AmbientState::Unknown {
+            return None;
+        }
+        if state == self.activity {
+            self.candidate = (AmbientState::Unknown, 0);
+            return None;
+        }
+        if self.candidate.0 == state {
+            self.candidate.1 += 1;
+        } else {
+            self.candidate = (state, 1);
+        }
+        if self.candidate.1 < self.config.confirmations {
+            return None;
+        }
+        self.candidate = (AmbientState::Unknown, 0);
+        let from = std::mem::replace(&mut self.activity, state);
+        (from != AmbientState::Unknown).then_some(ContextEvent::ActivityChange { from, to: state })
+    }
+
+    /// Feed the GPS fixes in `buffer` not seen before, oldest first, then
+    /// the ambient state classified from it
+    pub fn observe(&mut self, buffer: &SensorBuffer, ambient: AmbientState) -> Vec<ContextEvent> {
+        let mut events = Vec::new();
+        for reading in buffer.readings_of_type(SensorType::Gps) {
+            if self
+                .last_fix_ms
+                .is_some_and(|last| reading.timestamp_ms <= last)
+            {
+                continue;
+            }
+            self.last_fix_ms = Some(reading.timestamp_ms);
+            events.extend(self.observe_fix(reading));
+        }
+        events.extend(self.observe_activity(ambient));
+        events
+    }
+}
+
+/// Great-circle (haversine) distance in metres between two
+/// (latitude, longitude) points
+pub(crate) fn distance_m(a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (lat_a, lat_b) = (f64::from(a.0).to_radians(), f64::from(b.0).to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = f64::from(b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    (2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_debounced_context_changes() {
+        let mut detector = ContextSwitchDetector::new(ContextSwitchConfig {
+            confirmations: 2,
+            app_projects: vec![AppProject {
+                app: "org.example.ide".to_string(),
+                project: "compiler".to_string(),
+            }],
+            ..ContextSwitchConfig::default()
+        });
+
+        assert!(detector.observe_app("org.example.ide").is_some());
+        assert_eq!(detector.observe_app("org.example.ide"), None);
+        assert_eq!(
+            detector.observe_app("org.example.mail"),
+            Some(ContextEvent::AppSwitch {
+                from: Some("org.example.ide".to_string()),
+                to: "org.example.mail".to_string(),
+            })
+        );
+        assert_eq!(detector.project_for("org.example.ide"), Some("compiler"));
+
+        // Home, a jittery fix, a bad fix far away, then two fixes ~1.1 km north
+        let fix = |lat: f32, error: f32, t: u64| {
+            SensorReading::with_timestamp(SensorType::Gps, vec![lat, 13.4, error], t)
+        };
+        let mut buffer = SensorBuffer::new(16);
+        for reading in [
+            fix(52.5200, 10.0, 1),
+            fix(52.5205, 10.0, 2),
+            fix(53.0, 500.0, 3),
+            fix(52.5300, 10.0, 4),
+        ] {
+            buffer.push(reading);
+        }
+        assert!(detector.observe(&buffer, AmbientState::OnDesk).is_empty());
+        // The same readings again are not new evidence
+        assert!(detector.observe(&buffer, AmbientState::Unknown).is_empty());
+        buffer.push(fix(52.5301, 10.0, 5));
+        let events = detector.observe(&buffer, AmbientState::OnDesk);
+        let [ContextEvent::LocationChange { distance_m, .. }] = events.as_slice() else {
+            panic!("expected one location change, got {:?}", events);
+        };
+        assert!((1000.0..1200.0).contains(distance_m), "{}", distance_m);
+
+        // OnDesk became the baseline above; Walking needs two in a row
+        assert_eq!(detector.observe_activity(AmbientState::Walking), None);
+        assert_eq!(detector.observe_activity(AmbientState::OnDesk), None);
+        assert_eq!(detector.observe_activity(AmbientState::Walking), None);
+        assert_eq!(
+            detector.observe_activity(AmbientState::Walking),
+            Some(ContextEvent::ActivityChange {
+                from: AmbientState::OnDesk,
+                to: AmbientState::Walking,
+            })
+        );
+    }
+}
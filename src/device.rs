@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Device State
+//!
+//! Snapshot of the device conditions that make one route cheaper than
+//! another: battery, network, thermal headroom and memory pressure. The
+//! mobile shell reports it through a [`DeviceStateProvider`]; the routing
+//! policy uses it to choose between on-device and network routes, and the
+//! orchestrator uses it to throttle background neural work (reservoir
+//! updates, SNN steps) when the device is hot, low on memory, on a
+//! draining battery or in a moving car.
+
+#![forbid(unsafe_code)]
+
+use crate::activity::Activity;
+use serde::{Deserialize, Serialize};
+
+/// Thermal headroom below which neural work is throttled
+const THROTTLE_THERMAL_HEADROOM: f32 = 0.2;
+
+/// Battery level (%) below which neural work is throttled unless charging
+const THROTTLE_BATTERY_PERCENT: f32 = 15.0;
+
+/// Active network connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NetworkType {
+    /// Wi-Fi
+    #[default]
+    Wifi,
+    /// Cellular data
+    Cellular,
+    /// Wired connection
+    Ethernet,
+    /// No connectivity
+    Offline,
+}
+
+/// Operating-system memory pressure level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum MemoryPressure {
+    /// Plenty of free memory
+    #[default]
+    Normal,
+    /// The OS has asked apps to trim caches
+    Moderate,
+    /// The app is at risk of being killed
+    Critical,
+}
+
+/// Current device conditions relevant to routing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceState {
+    /// Battery charge (0-100)
+    pub battery_percent: f32,
+    /// Whether the device is on external power
+    pub charging: bool,
+    /// Whether the active network is metered (cellular, hotspot)
+    pub metered_network: bool,
+    /// Active network connection
+    pub network: NetworkType,
+    /// Remaining thermal headroom (1.0 = cool, 0.0 = throttling)
+    pub thermal_headroom: f32,
+    /// Memory pressure reported by the OS
+    pub memory_pressure: MemoryPressure,
+    /// What the user is doing; hosts may report it from the platform's
+    /// activity API, otherwise the orchestrator fills it in from motion
+    /// sensors
+    pub activity: Activity,
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        Self {
+            battery_percent: 100.0,
+            charging: false,
+            metered_network: false,
+            network: NetworkType::Wifi,
+            thermal_headroom: 1.0,
+            memory_pressure: MemoryPressure::Normal,
+            activity: Activity::Unknown,
+        }
+    }
+}
+
+impl DeviceState {
+    /// Whether network routes (Remote, Hybrid) can run at all
+    pub fn is_online(&self) -> bool {
+        self.network != NetworkType::Offline
+    }
+
+    /// Whether the on-device model can be loaded without risking the app
+    /// being killed
+    pub fn can_run_local_model(&self) -> bool {
+        self.memory_pressure < MemoryPressure::Critical
+    }
+
+    /// Whether optional neural work (reservoir updates, SNN steps) should
+    /// be skipped to save heat, memory or battery, or deferred while the
+    /// user is driving
+    pub fn should_throttle_compute(&self) -> bool {
+        self.thermal_headroom < THROTTLE_THERMAL_HEADROOM
+            || self.memory_pressure >= MemoryPressure::Moderate
+            || (!self.charging && self.battery_percent < THROTTLE_BATTERY_PERCENT)
+            || self.activity == Activity::Driving
+    }
+}
+
+/// Source of device state, implemented by the mobile shell
+///
+/// Shaped like [`crate::host::HostDelegate`] for UniFFI callback
+/// interfaces: `&self` methods returning owned values, `Send + Sync`.
+pub trait DeviceStateProvider: Send + Sync {
+    /// Current device conditions
+    fn device_state(&self) -> DeviceState;
+}
+
+/// A fixed snapshot, for tests and hosts that push state rather than
+/// being polled
+impl DeviceStateProvider for DeviceState {
+    fn device_state(&self) -> DeviceState {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_device_is_unconstrained() {
+        let device = DeviceState::default();
+        assert!(device.is_online());
+        assert!(device.can_run_local_model());
+        assert!(!device.should_throttle_compute());
+    }
+
+    #[test]
+    fn test_throttling_conditions() {
+        let hot = DeviceState {
+            thermal_headroom: 0.1,
+            ..DeviceState::default()
+        };
+        assert!(hot.should_throttle_compute());
+
+        let low_battery = DeviceState {
+            battery_percent: 10.0,
+            ..DeviceState::default()
+        };
+        assert!(low_battery.should_throttle_compute());
+        let charging = DeviceState {
+            charging: true,
+            ..low_battery
+        };
+        assert!(!charging.should_throttle_compute());
+
+        let critical = DeviceState {
+            memory_pressure: MemoryPressure::Critical,
+            ..DeviceState::default()
+        };
+        assert!(critical.should_throttle_compute());
+        assert!(!critical.can_run_local_model());
+    }
+
+    #[test]
+    fn test_state_deserializes_with_defaults() {
+        let Ok(device) = serde_json::from_str::<DeviceState>(
+            r#"{"battery_percent": 42.0, "network": "Offline"}"#,
+        ) else {
+            panic!("partial device state should deserialize");
+        };
+        assert_eq!(device.battery_percent, 42.0);
+        assert!(!device.is_online());
+        assert_eq!(device.thermal_headroom, 1.0);
+    }
+}
@@ -0,0 +1,494 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Scheduled Digest Queries
+//!
+//! Users define recurring queries in plain words:
+//!
+//! ```text
+//! every morning at 8: summarize yesterday's notes
+//! every weekday at 17:30: what is left on my todo list?
+//! every monday and thursday at 9am: plan the week
+//! every 6 hours: any new build failures?
+//! ```
+//!
+//! [`DigestScheduler`] keeps the digests and when each is next due, in the
+//! user's local time (`digest.utc_offset_minutes`). Like the journal it is a
+//! job rather than a timer: the host calls
+//! `Orchestrator::run_due_digests` from its own scheduler (WorkManager,
+//! BGTaskScheduler, cron), which runs every due digest as a normal query,
+//! stores it as a normal turn and delivers the answer through
+//! `HostDelegate::on_digest`.
+//!
+//! Digests are background work, so [`DigestConfig`] can hold them back on a
+//! low battery or a metered network. A digest held back for longer than
+//! `max_delay_minutes` is skipped until its next occurrence rather than
+//! answered hours late. While the app is in the background the scheduler
+//! is paused, and due digests wait until it returns.
+
+#![forbid(unsafe_code)]
+
+use crate::device::DeviceState;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Seconds per day
+const DAY_SECS: i64 = 86_400;
+
+/// Digest scheduling settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// Run due digests; when off they are kept but never run
+    pub enabled: bool,
+    /// Offset of the user's local time from UTC, in minutes
+    pub utc_offset_minutes: i32,
+    /// Battery level (%) below which digests wait, unless charging
+    pub min_battery_percent: f32,
+    /// Wait for an unmetered network
+    pub unmetered_only: bool,
+    /// Longest a digest waits for good conditions before it is skipped
+    pub max_delay_minutes: u64,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            utc_offset_minutes: 0,
+            min_battery_percent: 20.0,
+            unmetered_only: false,
+            max_delay_minutes: 120,
+        }
+    }
+}
+
+impl DigestConfig {
+    /// Why digests should wait under `device`, if they should
+    pub fn hold_back(&self, device: &DeviceState) -> Option<String> {
+        if !device.charging && device.battery_percent < self.min_battery_percent {
+            Some(format!(
+                "battery at {:.0}%, below {:.0}%",
+                device.battery_percent, self.min_battery_percent
+            ))
+        } else if self.unmetered_only && device.metered_network {
+            Some("network is metered".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Day of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    /// Monday
+    Monday,
+    /// Tuesday
+    Tuesday,
+    /// Wednesday
+    Wednesday,
+    /// Thursday
+    Thursday,
+    /// Friday
+    Friday,
+    /// Saturday
+    Saturday,
+    /// Sunday
+    Sunday,
+}
+
+impl Weekday {
+    /// Every day, Monday first
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    /// Weekday of `day` days since 1970-01-01 (a Thursday)
+    pub fn of_day(day: i64) -> Self {
+        Self::ALL[(day + 3).rem_euclid(7) as usize]
+    }
+
+    fn parse(word: &str) -> Option<Self> {
+        let word = word.trim_end_matches('s');
+        Self::ALL
+            .into_iter()
+            .find(|day| format!("{:?}", day).to_lowercase() == word)
+    }
+}
+
+/// When a digest recurs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Schedule {
+    /// At a local time on the given days (empty: every day)
+    At {
+        /// Hour (0-23)
+        hour: u8,
+        /// Minute (0-59)
+        minute: u8,
+        /// Days it runs on
+        days: Vec<Weekday>,
+    },
+    /// At a fixed interval
+    Every {
+        /// Minutes between runs
+        minutes: u32,
+    },
+}
+
+impl Schedule {
+    /// First run strictly after `now_ms`, in ms since the epoch
+    pub fn next_after(&self, now_ms: u64, utc_offset_minutes: i32) -> u64 {
+        match self {
+            Schedule::Every { minutes } => now_ms + u64::from((*minutes).max(1)) * 60_000,
+            Schedule::At { hour, minute, days } => {
+                let offset = i64::from(utc_offset_minutes) * 60;
+                let local = (now_ms / 1000) as i64 + offset;
+                let time = i64::from(*hour) * 3_600 + i64::from(*minute) * 60;
+                let today = local.div_euclid(DAY_SECS);
+                let run = (today..=today + 7)
+                    .map(|day| (day, day * DAY_SECS + time))
+                    .find(|&(day, at)| {
+                        at > local && (days.is_empty() || days.contains(&Weekday::of_day(day)))
+                    })
+                    .map_or(local + DAY_SECS, |(_, at)| at);
+                (run - offset).max(0) as u64 * 1000
+            }
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every { minutes: 60 } => write!(f, "every hour"),
+            Schedule::Every { minutes } if minutes % 60 == 0 => {
+                write!(f, "every {} hours", minutes / 60)
+            }
+            Schedule::Every { minutes } => write!(f, "every {} minutes", minutes),
+            Schedule::At { hour, minute, days } => {
+                let days = if days.is_empty() {
+                    "day".to_string()
+                } else {
+                    let names: Vec<String> = days.iter().map(|day| format!("{:?}", day)).collect();
+                    names.join(", ")
+                };
+                write!(f, "every {} at {:02}:{:02}", days, hour, minute)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    /// Parse "every day at 8", "every evening at 7:30", "every weekday at
+    /// 9am", "every monday and friday at 18:00", "every 2 hours", ...
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lower = text.trim().to_lowercase();
+        let Some(rest) = lower.strip_prefix("every ") else {
+            return Err(format!(
+                "a schedule starts with \"every\", got \"{}\"",
+                text
+            ));
+        };
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let minutes = match words.as_slice() {
+            ["hour"] => Some(60),
+            [n, unit] if unit.starts_with("hour") => n.parse::<u32>().ok().map(|n| n * 60),
+            [n, unit] if unit.starts_with("minute") => n.parse().ok(),
+            _ => None,
+        };
+        if let Some(minutes) = minutes {
+            if minutes == 0 {
+                return Err("a digest cannot run every 0 minutes".to_string());
+            }
+            return Ok(Schedule::Every { minutes });
+        }
+
+        let Some((when, time)) = rest.split_once(" at ") else {
+            return Err(format!(
+                "\"{}\" needs a time, e.g. \"every day at 8\"",
+                text
+            ));
+        };
+        let (mut days, afternoon) = match when.trim() {
+            "day" | "morning" => (Vec::new(), false),
+            "afternoon" | "evening" | "night" => (Vec::new(), true),
+            "weekday" | "weekdays" => (Weekday::ALL[..5].to_vec(), false),
+            "weekend" | "weekends" => (Weekday::ALL[5..].to_vec(), false),
+            list => {
+                let days: Option<Vec<Weekday>> = list
+                    .split([',', ' '])
+                    .filter(|word| !word.is_empty() && *word != "and")
+                    .map(Weekday::parse)
+                    .collect();
+                let days = days
+                    .filter(|days| !days.is_empty())
+                    .ok_or_else(|| format!("unknown days \"{}\"", list))?;
+                (days, false)
+            }
+        };
+        days.sort();
+        days.dedup();
+        let (hour, minute) = parse_time(time.trim(), afternoon)?;
+        Ok(Schedule::At { hour, minute, days })
+    }
+}
+
+/// "8", "8:30", "8am", "7.30 pm", "19:00"; bare hours before noon are
+/// afternoon ones when `afternoon` is set
+fn parse_time(text: &str, afternoon: bool) -> Result<(u8, u8), String> {
+    let invalid = || format!("invalid time \"{}\"", text);
+    let (clock, meridiem) = match text.strip_suffix("am").or(text.strip_suffix("pm")) {
+        Some(clock) => (clock.trim(), Some(text.ends_with("pm"))),
+        None => (text, None),
+    };
+    let (hour, minute) = clock.split_once([':', '.']).unwrap_or((clock, "0"));
+    let hour: u8 = hour.parse().map_err(|_| invalid())?;
+    let minute: u8 = minute.parse().map_err(|_| invalid())?;
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return Err(invalid()),
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None if afternoon && hour < 12 => hour + 12,
+        None => hour,
+    };
+    if hour > 23 || minute > 59 {
+        return Err(invalid());
+    }
+    Ok((hour, minute))
+}
+
+/// A recurring query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Digest {
+    /// Stable identifier
+    pub id: u64,
+    /// When it recurs
+    pub schedule: Schedule,
+    /// Query asked on every run
+    pub query: String,
+    /// Project the query belongs to, if any
+    pub project: Option<String>,
+    /// Next time it is due (ms since the epoch)
+    pub next_run_ms: u64,
+    /// Last time it ran, if ever (ms since the epoch)
+    pub last_run_ms: Option<u64>,
+}
+
+impl Digest {
+    /// Split a definition such as "every morning at 8: summarize my notes"
+    /// into its schedule and query
+    pub fn parse(definition: &str) -> Result<(Schedule, String), String> {
+        let Some((schedule, query)) = definition.split_once(": ") else {
+            return Err(format!(
+                "\"{}\" should read \"<schedule>: <query>\", e.g. \"every day at 8: ...\"",
+                definition
+            ));
+        };
+        let query = query.trim();
+        if query.is_empty() {
+            return Err("a digest needs a query".to_string());
+        }
+        Ok((schedule.parse()?, query.to_string()))
+    }
+}
+
+/// What happened to a due digest
+#[derive(Debug, Clone, PartialEq)]
+pub enum DigestOutcome {
+    /// It ran; the answer was stored as turn `turn_id`
+    Ran {
+        /// Turn holding the answer
+        turn_id: Option<u64>,
+        /// The answer
+        text: String,
+    },
+    /// Conditions were poor; it stays due
+    Deferred(String),
+    /// It waited too long or failed; it runs again at its next occurrence
+    Skipped(String),
+}
+
+/// One due digest handled by `Orchestrator::run_due_digests`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestRun {
+    /// The digest
+    pub digest_id: u64,
+    /// What happened
+    pub outcome: DigestOutcome,
+}
+
+/// The user's digests and when each is due
+#[derive(Debug, Clone, Default)]
+pub struct DigestScheduler {
+    digests: Vec<Digest>,
+    next_id: u64,
+    utc_offset_minutes: i32,
+}
+
+impl DigestScheduler {
+    /// An empty scheduler for a user `utc_offset_minutes` from UTC
+    pub fn new(utc_offset_minutes: i32) -> Self {
+        Self {
+            utc_offset_minutes,
+            ..Self::default()
+        }
+    }
+
+    /// A scheduler holding previously saved `digests`
+    pub fn from_digests(digests: Vec<Digest>, utc_offset_minutes: i32) -> Self {
+        let next_id = digests
+            .iter()
+            .map(|digest| digest.id + 1)
+            .max()
+            .unwrap_or(0);
+        Self {
+            digests,
+            next_id,
+            utc_offset_minutes,
+        }
+    }
+
+    /// Every digest, in creation order
+    pub fn digests(&self) -> &[Digest] {
+        &self.digests
+    }
+
+    /// Digest `id`, if defined
+    pub fn get(&self, id: u64) -> Option<&Digest> {
+        self.digests.iter().find(|digest| digest.id == id)
+    }
+
+    /// Define a digest, first due at its next occurrence after `now_ms`
+    pub fn add(
+        &mut self,
+        schedule: Schedule,
+        query: &str,
+        project: Option<&str>,
+        now_ms: u64,
+    ) -> Digest {
+        let digest = Digest {
+            id: self.next_id,
+            next_run_ms: schedule.next_after(now_ms, self.utc_offset_minutes),
+            schedule,
+            query: query.trim().to_string(),
+            project: project.map(str::to_string),
+            last_run_ms: None,
+        };
+        self.next_id += 1;
+        self.digests.push(digest.clone());
+        digest
+    }
+
+    /// Remove digest `id`, returning whether it was defined
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.digests.len();
+        self.digests.retain(|digest| digest.id != id);
+        self.digests.len() != before
+    }
+
+    /// Digests due at `now_ms`, earliest first
+    pub fn due(&self, now_ms: u64) -> Vec<Digest> {
+        let mut due: Vec<Digest> = self
+            .digests
+            .iter()
+            .filter(|digest| digest.next_run_ms <= now_ms)
+            .cloned()
+            .collect();
+        due.sort_by_key(|digest| (digest.next_run_ms, digest.id));
+        due
+    }
+
+    /// Move digest `id` to its next occurrence after `now_ms`, recording
+    /// a run if it `ran`
+    pub fn advance(&mut self, id: u64, now_ms: u64, ran: bool) -> Option<Digest> {
+        let offset = self.utc_offset_minutes;
+        let digest = self.digests.iter_mut().find(|digest| digest.id == id)?;
+        digest.next_run_ms = digest.schedule.next_after(now_ms, offset);
+        if ran {
+            digest.last_run_ms = Some(now_ms);
+        }
+        Some(digest.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_definitions() {
+        let Ok((schedule, query)) = Digest::parse("Every morning at 8: summarize my notes") else {
+            panic!("a daily digest should parse");
+        };
+        assert_eq!(
+            schedule,
+            Schedule::At {
+                hour: 8,
+                minute: 0,
+                days: Vec::new()
+            }
+        );
+        assert_eq!(query, "summarize my notes");
+
+        let parsed = |text: &str| text.parse::<Schedule>().map(|s| s.to_string());
+        assert_eq!(
+            parsed("every evening at 7:30"),
+            Ok("every day at 19:30".to_string())
+        );
+        assert_eq!(
+            parsed("every friday and monday at 9am"),
+            Ok("every Monday, Friday at 09:00".to_string())
+        );
+        assert_eq!(
+            parsed("every weekday at 12pm"),
+            Ok("every Monday, Tuesday, Wednesday, Thursday, Friday at 12:00".to_string())
+        );
+        assert_eq!(parsed("every 6 hours"), Ok("every 6 hours".to_string()));
+        assert!(parsed("every day at 25").is_err());
+        assert!(parsed("every fortnight at 8").is_err());
+        assert!(Digest::parse("every day at 8 summarize").is_err());
+    }
+
+    #[test]
+    fn test_next_run_respects_days_and_local_time() {
+        // 2026-10-16 is a Friday; 06:00 UTC
+        let friday_6am = 1_792_130_400_000;
+        let at_8 = Schedule::At {
+            hour: 8,
+            minute: 0,
+            days: Vec::new(),
+        };
+        assert_eq!(at_8.next_after(friday_6am, 0), friday_6am + 2 * 3_600_000);
+        // At UTC+3 it is already 09:00, so the next run is tomorrow
+        assert_eq!(
+            at_8.next_after(friday_6am, 180),
+            friday_6am + 23 * 3_600_000
+        );
+        let mondays = Schedule::At {
+            hour: 8,
+            minute: 0,
+            days: vec![Weekday::Monday],
+        };
+        assert_eq!(
+            mondays.next_after(friday_6am, 0),
+            friday_6am + (3 * 24 + 2) * 3_600_000
+        );
+
+        let mut scheduler = DigestScheduler::new(0);
+        let digest = scheduler.add(at_8, "news", None, friday_6am);
+        assert!(scheduler.due(friday_6am).is_empty());
+        let later = friday_6am + 3 * 3_600_000;
+        assert_eq!(scheduler.due(later).len(), 1);
+        let Some(advanced) = scheduler.advance(digest.id, later, true) else {
+            panic!("the digest should exist");
+        };
+        assert_eq!(advanced.next_run_ms, friday_6am + 26 * 3_600_000);
+        assert_eq!(advanced.last_run_ms, Some(later));
+    }
+}
@@ -0,0 +1,539 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Concurrent Query Dispatch
+//!
+//! `Orchestrator::process` takes `&mut self`, so an app asking several
+//! things at once had to answer them one after another: an interactive
+//! question waited for a background summarization to finish. A
+//! [`Dispatcher`] owns the orchestrator and a pool of worker threads:
+//!
+//! - **Ids and status**: [`Dispatcher::submit`] returns a [`QueryId`] at
+//!   once; [`Dispatcher::status`] says whether the query is waiting,
+//!   running or done, and [`Dispatcher::wait`] blocks for its answer.
+//! - **Priority**: waiting queries start highest `Query::priority` first,
+//!   oldest first at equal priority, so an interactive question overtakes
+//!   queued background work.
+//! - **Concurrency**: the orchestrator is locked only to route a query and
+//!   to record its answer. Backends are called without the lock, so a slow
+//!   backend call does not hold up the other workers.
+//!
+//! Results nobody waits for are kept for the last `MAX_UNCLAIMED`
+//! finished or cancelled queries only.
+//!
+//! The workers are supervised tasks (see `supervisor`). `shutdown`, or
+//! dropping the dispatcher, cancels waiting queries, lets running ones
+//! finish, joins the workers and then shuts the orchestrator down, so
+//! buffered turns are flushed.
+
+#![forbid(unsafe_code)]
+
+use crate::error::OrchestratorError;
+use crate::orchestrator::{Begun, Orchestrator, ShutdownReport};
+use crate::supervisor::Supervisor;
+use crate::types::{Query, Response};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Most finished or cancelled queries whose status and result are kept
+/// for `wait`; older ones are forgotten
+const MAX_UNCLAIMED: usize = 256;
+
+/// Identifier of a submitted query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QueryId(pub u64);
+
+impl fmt::Display for QueryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Where a submitted query stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryStatus {
+    /// Waiting for a worker
+    Waiting {
+        /// Waiting queries that will start before it
+        ahead: usize,
+    },
+    /// Being routed or answered
+    Running,
+    /// Answered or failed; the result is kept until taken with `wait`
+    /// or until `MAX_UNCLAIMED` later queries have finished
+    Done,
+    /// Cancelled before it started
+    Cancelled,
+}
+
+/// A waiting query; the heap pops the highest priority, then the oldest
+struct Job {
+    id: QueryId,
+    query: Query,
+}
+
+impl Job {
+    fn key(&self) -> (u8, std::cmp::Reverse<QueryId>) {
+        (self.query.priority, std::cmp::Reverse(self.id))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    waiting: BinaryHeap<Job>,
+    status: HashMap<QueryId, QueryStatus>,
+    results: HashMap<QueryId, Result<Response, OrchestratorError>>,
+    /// Finished and cancelled queries, oldest first
+    settled: VecDeque<QueryId>,
+    next_id: u64,
+    stopping: bool,
+}
+
+impl Jobs {
+    /// Mark `id` finished or cancelled, forgetting the oldest unclaimed
+    /// queries beyond `MAX_UNCLAIMED`
+    fn settle(&mut self, id: QueryId, status: QueryStatus) {
+        self.status.insert(id, status);
+        self.settled.push_back(id);
+        while self.settled.len() > MAX_UNCLAIMED {
+            if let Some(old) = self.settled.pop_front() {
+                self.status.remove(&old);
+                self.results.remove(&old);
+            }
+        }
+    }
+}
+
+/// State shared by the dispatcher and its workers
+struct Shared {
+    orchestrator: Mutex<Orchestrator>,
+    jobs: Mutex<Jobs>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn jobs(&self) -> MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn orchestrator(&self) -> MutexGuard<'_, Orchestrator> {
+        self.orchestrator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Next query to run, or `None` once the dispatcher is stopping
+    fn next_job(&self) -> Option<Job> {
+        let mut jobs = self.jobs();
+        loop {
+            if jobs.stopping {
+                return None;
+            }
+            if let Some(job) = jobs.waiting.pop() {
+                jobs.status.insert(job.id, QueryStatus::Running);
+                return Some(job);
+            }
+            jobs = self
+                .changed
+                .wait(jobs)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Route `query`, call its backends without the lock, record it
+    fn run(&self, query: Query) -> Result<Response, OrchestratorError> {
+        let begun = self.orchestrator().begin(query)?;
+        match begun {
+            Begun::Answered(response) => Ok(*response),
+            Begun::Pending(pending) => {
+                let generated = pending.generate();
+                self.orchestrator().complete(generated)
+            }
+        }
+    }
+
+    fn finish(&self, id: QueryId, result: Result<Response, OrchestratorError>) {
+        let mut jobs = self.jobs();
+        jobs.results.insert(id, result);
+        jobs.settle(id, QueryStatus::Done);
+        drop(jobs);
+        self.changed.notify_all();
+    }
+}
+
+/// Runs queries on a shared orchestrator from several worker threads
+pub struct Dispatcher {
+    shared: Arc<Shared>,
+    supervisor: Supervisor,
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jobs = self.shared.jobs();
+        f.debug_struct("Dispatcher")
+            .field("waiting", &jobs.waiting.len())
+            .field("tracked", &jobs.status.len())
+            .field("supervisor", &self.supervisor)
+            .finish()
+    }
+}
+
+impl Dispatcher {
+    /// Take over `orchestrator` and start `workers` worker threads (at
+    /// least one)
+    pub fn new(orchestrator: Orchestrator, workers: usize) -> Result<Self, OrchestratorError> {
+        let shared = Arc::new(Shared {
+            orchestrator: Mutex::new(orchestrator),
+            jobs: Mutex::new(Jobs::default()),
+            changed: Condvar::new(),
+        });
+        let mut supervisor = Supervisor::default();
+        for index in 0..workers.max(1) {
+            let shared = Arc::clone(&shared);
+            let body = Box::new(move |_: &_| {
+                while let Some(job) = shared.next_job() {
+                    let outcome = panic::catch_unwind(AssertUnwindSafe(|| shared.run(job.query)));
+                    let result = outcome.unwrap_or_else(|_| {
+                        Err(OrchestratorError::BackendFailure(format!(
+                            "query {} panicked",
+                            job.id
+                        )))
+                    });
+                    shared.finish(job.id, result);
+                }
+                Ok(())
+            });
+            supervisor
+                .spawn(format!("dispatch-{}", index), body)
+                .map_err(OrchestratorError::BackendFailure)?;
+        }
+        Ok(Self { shared, supervisor })
+    }
+
+    /// Queue `query` for the workers
+    pub fn submit(&self, query: Query) -> Result<QueryId, OrchestratorError> {
+        let mut jobs = self.shared.jobs();
+        if jobs.stopping {
+            return Err(OrchestratorError::ShutDown);
+        }
+        let id = QueryId(jobs.next_id);
+        jobs.next_id += 1;
+        jobs.status.insert(id, QueryStatus::Waiting { ahead: 0 });
+        jobs.waiting.push(Job { id, query });
+        drop(jobs);
+        self.shared.changed.notify_all();
+        Ok(id)
+    }
+
+    /// Where query `id` stands; `None` if unknown or already taken
+    pub fn status(&self, id: QueryId) -> Option<QueryStatus> {
+        let jobs = self.shared.jobs();
+        match jobs.status.get(&id)? {
+            QueryStatus::Waiting { .. } => {
+                let job = jobs.waiting.iter().find(|job| job.id == id)?;
+                let ahead = jobs.waiting.iter().filter(|other| *other > job).count();
+                Some(QueryStatus::Waiting { ahead })
+            }
+            status => Some(*status),
+        }
+    }
+
+    /// Block until query `id` is answered and take its result; `None` if
+    /// it is unknown, already taken or was cancelled
+    pub fn wait(&self, id: QueryId) -> Option<Result<Response, OrchestratorError>> {
+        let mut jobs = self.shared.jobs();
+        loop {
+            match jobs.status.get(&id)? {
+                QueryStatus::Waiting { .. } | QueryStatus::Running => {
+                    jobs = self
+                        .shared
+                        .changed
+                        .wait(jobs)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                QueryStatus::Done | QueryStatus::Cancelled => {
+                    jobs.status.remove(&id);
+                    jobs.settled.retain(|settled| *settled != id);
+                    return jobs.results.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Cancel query `id` if it has not started; returns whether it was
+    /// cancelled
+    pub fn cancel(&self, id: QueryId) -> bool {
+        let mut jobs = self.shared.jobs();
+        let before = jobs.waiting.len();
+        jobs.waiting.retain(|job| job.id != id);
+        if jobs.waiting.len() == before {
+            return false;
+        }
+        jobs.settle(id, QueryStatus::Cancelled);
+        drop(jobs);
+        self.shared.changed.notify_all();
+        true
+    }
+
+    /// Run `f` on the orchestrator, between queries being routed or
+    /// recorded, e.g. to switch profile or read history
+    pub fn with_orchestrator<R>(&self, f: impl FnOnce(&mut Orchestrator) -> R) -> R {
+        f(&mut self.shared.orchestrator())
+    }
+
+    /// Stop accepting queries, cancel the waiting ones, let running ones
+    /// finish, join the workers and shut the orchestrator down (see
+    /// `Orchestrator::shutdown`); the workers joined are counted in
+    /// `tasks_joined`. Calling it twice is harmless.
+    pub fn shutdown(&mut self) -> ShutdownReport {
+        let mut jobs = self.shared.jobs();
+        jobs.stopping = true;
+        let cancelled: Vec<QueryId> = jobs.waiting.drain().map(|job| job.id).collect();
+        for id in cancelled {
+            jobs.settle(id, QueryStatus::Cancelled);
+        }
+        drop(jobs);
+        self.shared.changed.notify_all();
+        let workers = self.supervisor.shutdown();
+        let mut report = self.shared.orchestrator().shutdown();
+        report.tasks_joined += workers;
+        report
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::targets::TargetBackend;
+    use crate::types::{ModelCapability, RoutingDecision};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Remote backend that answers only once opened
+    #[derive(Default)]
+    struct Gate {
+        open: Mutex<bool>,
+        opened: Condvar,
+    }
+
+    impl Gate {
+        fn open(&self) {
+            *self.open.lock().unwrap_or_else(PoisonError::into_inner) = true;
+            self.opened.notify_all();
+        }
+    }
+
+    impl TargetBackend for Gate {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            let open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+            let _open = self
+                .opened
+                .wait_while(open, |open| !*open)
+                .unwrap_or_else(PoisonError::into_inner);
+            Ok(format!("remote: {}", query.text))
+        }
+    }
+
+    fn gated(workers: usize) -> (Dispatcher, Arc<Gate>) {
+        let gate = Arc::new(Gate::default());
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.set_remote_backend(Arc::clone(&gate) as Arc<dyn TargetBackend>);
+        let Ok(dispatcher) = Dispatcher::new(orchestrator, workers) else {
+            panic!("workers should start");
+        };
+        (dispatcher, gate)
+    }
+
+    /// A query the router sends to the gated Remote backend
+    fn slow(text: &str) -> Query {
+        Query::new(text).requiring(ModelCapability::Vision)
+    }
+
+    fn wait_until_running(dispatcher: &Dispatcher, id: QueryId) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while dispatcher.status(id) != Some(QueryStatus::Running) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(dispatcher.status(id), Some(QueryStatus::Running));
+    }
+
+    #[test]
+    fn test_waiting_queries_start_by_priority() {
+        let (dispatcher, gate) = gated(1);
+        let Ok(busy) = dispatcher.submit(slow("describe this photo")) else {
+            panic!("submit should succeed");
+        };
+        wait_until_running(&dispatcher, busy);
+
+        let background = Query {
+            priority: 2,
+            ..Query::new("summarize my notes")
+        };
+        let interactive = Query {
+            priority: 8,
+            ..Query::new("what time is it")
+        };
+        let (Ok(background), Ok(interactive), Ok(cancelled)) = (
+            dispatcher.submit(background),
+            dispatcher.submit(interactive),
+            dispatcher.submit(Query::new("never mind")),
+        ) else {
+            panic!("submit should succeed");
+        };
+        assert_eq!(
+            dispatcher.status(interactive),
+            Some(QueryStatus::Waiting { ahead: 0 })
+        );
+        assert_eq!(
+            dispatcher.status(background),
+            Some(QueryStatus::Waiting { ahead: 2 })
+        );
+        assert!(dispatcher.cancel(cancelled));
+        assert!(!dispatcher.cancel(busy));
+        assert_eq!(dispatcher.wait(cancelled), None);
+
+        gate.open();
+        for id in [busy, background, interactive] {
+            let Some(Ok(_)) = dispatcher.wait(id) else {
+                panic!("query {} should be answered", id);
+            };
+        }
+        assert_eq!(dispatcher.status(busy), None);
+        let order: Vec<String> = dispatcher.with_orchestrator(|orchestrator| {
+            let history = orchestrator.recent_history(3);
+            history.into_iter().map(|turn| turn.query.text).collect()
+        });
+        // Most recent first
+        assert_eq!(
+            order,
+            [
+                "summarize my notes",
+                "what time is it",
+                "describe this photo"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slow_backends_do_not_block_other_queries() {
+        let (mut dispatcher, gate) = gated(2);
+        let Ok(busy) = dispatcher.submit(slow("describe this photo")) else {
+            panic!("submit should succeed");
+        };
+        wait_until_running(&dispatcher, busy);
+
+        let Ok(quick) = dispatcher.submit(Query::new("hello")) else {
+            panic!("submit should succeed");
+        };
+        let Some(Ok(answer)) = dispatcher.wait(quick) else {
+            panic!("the quick query should be answered while the slow one runs");
+        };
+        assert_eq!(answer.route, RoutingDecision::Local);
+        assert_eq!(dispatcher.status(busy), Some(QueryStatus::Running));
+
+        gate.open();
+        let Some(Ok(answer)) = dispatcher.wait(busy) else {
+            panic!("the slow query should be answered");
+        };
+        assert_eq!(answer.route, RoutingDecision::Remote);
+        assert!(answer.text.starts_with("remote: "));
+        assert_eq!(dispatcher.shutdown().tasks_joined, 2);
+        assert_eq!(
+            dispatcher.submit(Query::new("late")),
+            Err(OrchestratorError::ShutDown)
+        );
+    }
+
+    #[test]
+    fn test_unclaimed_results_are_forgotten() {
+        let (dispatcher, gate) = gated(1);
+        let Ok(busy) = dispatcher.submit(slow("describe this photo")) else {
+            panic!("submit should succeed");
+        };
+        wait_until_running(&dispatcher, busy);
+
+        let mut ids = Vec::new();
+        for i in 0..=MAX_UNCLAIMED {
+            let Ok(id) = dispatcher.submit(Query::new(format!("query {}", i))) else {
+                panic!("submit should succeed");
+            };
+            assert!(dispatcher.cancel(id));
+            ids.push(id);
+        }
+        assert_eq!(dispatcher.status(ids[0]), None);
+        assert_eq!(dispatcher.status(ids[1]), Some(QueryStatus::Cancelled));
+        assert_eq!(
+            dispatcher.status(ids[MAX_UNCLAIMED]),
+            Some(QueryStatus::Cancelled)
+        );
+
+        gate.open();
+        let Some(Ok(_)) = dispatcher.wait(busy) else {
+            panic!("the slow query should be answered");
+        };
+        // Finishing `busy` forgot `ids[1]`; taking its answer freed a slot
+        assert_eq!(dispatcher.status(ids[1]), None);
+        let jobs = dispatcher.shared.jobs();
+        assert_eq!(jobs.status.len(), MAX_UNCLAIMED - 1);
+        assert!(jobs.results.is_empty());
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_shutdown_flushes_the_orchestrator() {
+        let Ok(pm) = crate::persistence::PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let Ok(mut dispatcher) = Dispatcher::new(orchestrator, 2) else {
+            panic!("workers should start");
+        };
+        for i in 0..3 {
+            let Ok(id) = dispatcher.submit(Query::new(format!("query {}", i))) else {
+                panic!("submit should succeed");
+            };
+            let Some(Ok(_)) = dispatcher.wait(id) else {
+                panic!("query {} should be answered", id);
+            };
+        }
+
+        let report = dispatcher.shutdown();
+        assert!(report.is_clean(), "errors: {:?}", report.errors);
+        assert_eq!(report.turns_flushed, 3);
+        assert_eq!(report.backends_closed, 1);
+        assert!(report.tasks_joined >= 2);
+        assert_eq!(dispatcher.shutdown(), ShutdownReport::default());
+        assert_eq!(
+            dispatcher.with_orchestrator(|o| o.process(Query::new("late"))),
+            Err(OrchestratorError::ShutDown)
+        );
+    }
+}
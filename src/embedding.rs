@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Text Embedding Providers
+//!
+//! Everything that turns text into a vector (router features, the
+//! reservoir's input encoding, context search) goes through the
+//! [`Embedder`] trait, so the embedding can be upgraded without touching
+//! callers.
+//!
+//! Implementations:
+//!
+//! - [`HashedBagOfWords`]: the original hashing encoder; no data, no
+//!   network, weak semantics.
+//! - [`StaticWordVectors`]: averaged word vectors from a small on-device
+//!   table (GloVe-style text format).
+//! - `ApiEmbedder` (feature `network`): an OpenAI-compatible embeddings
+//!   endpoint.
+//! - `minilm::MiniLmEmbedder` (feature `minilm`): a small sentence
+//!   transformer run on the device.
+//!
+//! Every embedder has an [`Embedder::id`] naming the model and dimension;
+//! anything trained on embeddings (router models, saved indexes) should
+//! record it, since vectors from different embedders are not comparable.
+
+#![forbid(unsafe_code)]
+
+use crate::hashing::{stable_hash, STABLE_HASH_VERSION};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Turns text into a fixed-width vector
+pub trait Embedder: Send + Sync + fmt::Debug {
+    /// Stable identifier of the embedding model and dimension
+    fn id(&self) -> String;
+
+    /// Width of the vectors produced
+    fn dimension(&self) -> usize;
+
+    /// Embed `text`
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String>;
+}
+
+/// Pad with zeros or truncate `vector` to `dimension`
+pub fn fit_dimension(mut vector: Vec<f32>, dimension: usize) -> Vec<f32> {
+    vector.resize(dimension, 0.0);
+    vector
+}
+
+/// Cosine similarity of two vectors (0.0 if either is all zeros)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a > 0.0 && norm_b > 0.0 {
+        dot / (norm_a * norm_b)
+    } else {
+        0.0
+    }
+}
+
+/// Scale `vector` to unit length (zero vectors are left alone)
+fn normalize(vector: &mut [f32]) {
+    let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        for v in vector {
+            *v /= magnitude;
+        }
+    }
+}
+
+/// Bag of words hashed into `dimension` buckets, L2-normalized
+///
+/// Buckets come from the stable hash, so vectors (and models trained on
+/// them) are identical on every platform; the id records the hash version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashedBagOfWords {
+    dimension: usize,
+}
+
+impl HashedBagOfWords {
+    /// Create an encoder with `dimension` buckets (at least one)
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(1),
+        }
+    }
+
+    /// Infallible encoding
+    pub fn encode(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimension];
+        for word in text.split_whitespace() {
+            let bucket = stable_hash(word.as_bytes()) % self.dimension as u64;
+            vector[bucket as usize] += 1.0;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Embedder for HashedBagOfWords {
+    fn id(&self) -> String {
+        format!("hashed-bow-v{}-{}", STABLE_HASH_VERSION, self.dimension)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        Ok(self.encode(text))
+    }
+}
+
+/// Average of per-word vectors from a static table, L2-normalized
+///
+/// Words are lowercased and stripped of surrounding punctuation; unknown
+/// words are skipped, so text with no known words embeds to zeros.
+#[derive(Debug, Clone, Default)]
+pub struct StaticWordVectors {
+    name: String,
+    dimension: usize,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl StaticWordVectors {
+    /// Create an empty table of `dimension`-wide vectors
+    pub fn new(name: impl Into<String>, dimension: usize) -> Self {
+        Self {
+            name: name.into(),
+            dimension,
+            vectors: HashMap::new(),
+        }
+    }
+
+    /// Parse a GloVe-style table: one word per line followed by its
+    /// whitespace-separated components. The dimension is taken from the
+    /// first line; blank lines are skipped.
+    pub fn from_text(name: impl Into<String>, text: &str) -> Result<Self, String> {
+        let mut table = Self::new(name, 0);
+        for (number, line) in text.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else {
+                continue;
+            };
+            let vector = parts
+                .map(|part| part.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+            if table.vectors.is_empty() {
+                table.dimension = vector.len();
+            }
+            table
+                .insert(word, vector)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        Ok(table)
+    }
+
+    /// Add or replace a word's vector
+    pub fn insert(&mut self, word: &str, vector: Vec<f32>) -> Result<(), String> {
+        if vector.len() != self.dimension {
+            return Err(format!(
+                "vector for '{}' has {} components, expected {}",
+                word,
+                vector.len(),
+                self.dimension
+            ));
+        }
+        self.vectors.insert(word.to_lowercase(), vector);
+        Ok(())
+    }
+
+    /// Number of words in the table
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    /// Whether the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+}
+
+impl Embedder for StaticWordVectors {
+    fn id(&self) -> String {
+        format!("static-{}-{}", self.name, self.dimension)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        let mut sum = vec![0.0; self.dimension];
+        for word in text.split_whitespace() {
+            let word = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase();
+            if let Some(vector) = self.vectors.get(&word) {
+                for (s, v) in sum.iter_mut().zip(vector) {
+                    *s += v;
+                }
+            }
+        }
+        normalize(&mut sum);
+        Ok(sum)
+    }
+}
+
+#[cfg(feature = "network")]
+pub use api::ApiEmbedder;
+
+#[cfg(feature = "network")]
+mod api {
+    use super::Embedder;
+    use crate::config::RemoteConfig;
+    use crate::secrets::{self, Secret, SecretProvider};
+    use std::fmt;
+    use std::sync::Arc;
+
+    /// Client for an OpenAI-compatible `/embeddings` endpoint
+    ///
+    /// Requests are made synchronously on a private single-threaded
+    /// runtime, so the embedder can be used from non-async callers. The
+    /// endpoint and API key are resolved for every request, so a rotated
+    /// key is picked up without rebuilding the embedder, and errors never
+    /// echo the key or the endpoint URL.
+    pub struct ApiEmbedder {
+        remote: RemoteConfig,
+        provider: Arc<dyn SecretProvider>,
+        model: String,
+        dimension: usize,
+        client: reqwest::Client,
+        runtime: tokio::runtime::Runtime,
+    }
+
+    impl fmt::Debug for ApiEmbedder {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ApiEmbedder")
+                .field("endpoint", &self.remote.endpoint)
+                .field("model", &self.model)
+                .field("dimension", &self.dimension)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl ApiEmbedder {
+        /// Create a client for `model` at the `remote` endpoint, producing
+        /// `dimension`-wide vectors; a configured API key is fetched from
+        /// `provider`
+        pub fn new(
+            remote: RemoteConfig,
+            provider: Arc<dyn SecretProvider>,
+            model: impl Into<String>,
+            dimension: usize,
+        ) -> Result<Self, String> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("failed to start embedding runtime: {}", e))?;
+            Ok(Self {
+                remote,
+                provider,
+                model: model.into(),
+                dimension,
+                client: reqwest::Client::new(),
+                runtime,
+            })
+        }
+    }
+
+    impl Embedder for ApiEmbedder {
+        fn id(&self) -> String {
+            format!("api-{}-{}", self.model, self.dimension)
+        }
+
+        fn dimension(&self) -> usize {
+            self.dimension
+        }
+
+        fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+            let endpoint = self.remote.resolved_endpoint()?;
+            let api_key = match self.remote.api_key {
+                Some(_) => Some(self.remote.resolve_api_key(self.provider.as_ref())?),
+                None => None,
+            };
+            let body = serde_json::json!({ "model": self.model, "input": text });
+            let mut request = self.client.post(&endpoint).json(&body);
+            if let Some(key) = &api_key {
+                request = request.bearer_auth(key.expose());
+            }
+
+            let response: Result<serde_json::Value, String> = self.runtime.block_on(async {
+                request
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| format!("embedding request failed: {}", e.without_url()))?
+                    .json()
+                    .await
+                    .map_err(|e| format!("invalid embedding response: {}", e.without_url()))
+            });
+            let keys: Vec<&Secret> = api_key.iter().collect();
+            let response = response.map_err(|e| secrets::redact(&e, &keys))?;
+
+            let Some(values) = response["data"][0]["embedding"].as_array() else {
+                return Err("embedding response has no data[0].embedding".to_string());
+            };
+            let vector: Vec<f32> = values
+                .iter()
+                .filter_map(|v| v.as_f64())
+                .map(|v| v as f32)
+                .collect();
+            if vector.len() != self.dimension {
+                return Err(format!(
+                    "embedding has {} components, expected {}",
+                    vector.len(),
+                    self.dimension
+                ));
+            }
+            Ok(vector)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn test_api_embedder_resolves_keys_per_request_and_scrubs_errors() {
+        use crate::config::RemoteConfig;
+        use crate::secrets::{SecretProvider, SecretRef};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        struct Counting(AtomicUsize);
+        impl SecretProvider for Counting {
+            fn get_secret(&self, _name: &str) -> Option<String> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Some("sk-embed-secret".to_string())
+            }
+        }
+
+        std::env::set_var("MOBILE_AI_EMBED_TOKEN", "tok-in-url");
+        let remote = RemoteConfig {
+            // Nothing listens on the discard port
+            endpoint: "http://127.0.0.1:9/embeddings?token=${MOBILE_AI_EMBED_TOKEN}".to_string(),
+            api_key: Some(SecretRef::Provider("embeddings".to_string())),
+        };
+        let provider = Arc::new(Counting(AtomicUsize::new(0)));
+        let Ok(embedder) = ApiEmbedder::new(remote, provider.clone(), "m", 8) else {
+            panic!("the embedder should start");
+        };
+        for _ in 0..2 {
+            let Err(error) = embedder.embed("hello") else {
+                panic!("nothing should answer");
+            };
+            assert!(error.starts_with("embedding request failed"));
+            assert!(!error.contains("sk-embed-secret"));
+            assert!(!error.contains("tok-in-url"));
+        }
+        assert_eq!(provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_hashed_bag_of_words_is_normalized() {
+        let embedder = HashedBagOfWords::new(64);
+        let Ok(vector) = embedder.embed("hello hello world") else {
+            panic!("hashing never fails");
+        };
+        assert_eq!(vector.len(), 64);
+        let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert_eq!(embedder.id(), "hashed-bow-v1-64");
+        // Pinned buckets: vectors must match across platforms and releases
+        assert!((vector[11] - 2.0 / 5f32.sqrt()).abs() < 1e-5);
+        assert!((vector[51] - 1.0 / 5f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_static_word_vectors_average_known_words() {
+        let Ok(table) = StaticWordVectors::from_text("tiny", "cat 1 0\ndog 0 1\n\n") else {
+            panic!("well-formed table should parse");
+        };
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.dimension(), 2);
+
+        let Ok(vector) = table.embed("The CAT, and a dog!") else {
+            panic!("static lookup never fails");
+        };
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((vector[0] - expected).abs() < 1e-5);
+        assert!((vector[1] - expected).abs() < 1e-5);
+
+        let Ok(unknown) = table.embed("nothing known") else {
+            panic!("static lookup never fails");
+        };
+        assert_eq!(unknown, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_static_word_vectors_reject_ragged_tables() {
+        assert!(StaticWordVectors::from_text("bad", "cat 1 0\ndog 1\n").is_err());
+        assert!(StaticWordVectors::from_text("bad", "cat 1 x\n").is_err());
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_fit_dimension() {
+        assert_eq!(fit_dimension(vec![1.0, 2.0], 3), vec![1.0, 2.0, 0.0]);
+        assert_eq!(fit_dimension(vec![1.0, 2.0], 1), vec![1.0]);
+    }
+}
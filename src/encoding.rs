@@ -0,0 +1,320 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Spike Encoding for Sensor Data
+//!
+//! `SpikingNetwork::step` takes a `&[bool]` per time step, while sensors
+//! produce `f32` values. The encoders here bridge the two so a sensor
+//! stream can drive the SNN directly:
+//!
+//! - [`RateEncoder`] fires with probability equal to the value's position
+//!   in its range, one input per value
+//! - [`DeltaEncoder`] fires a rise or fall input when a value has moved by
+//!   more than a threshold since its last spike (send-on-delta), two inputs
+//!   per value; steady signals stay silent, which keeps the network idle
+//! - [`PopulationEncoder`] spreads each value over a bank of inputs with
+//!   Gaussian tuning curves, so nearby values excite overlapping inputs
+//!
+//! Every encoder emits `input_size(sensor_type)` spikes per reading, so the
+//! network for a sensor is built with
+//! `SpikingNetwork::new(encoder.input_size(sensor_type), hidden, outputs)`.
+//! Missing and non-finite values never spike.
+
+#![forbid(unsafe_code)]
+
+use crate::rng::SeededRng;
+use crate::sensor::{SensorBuffer, SensorReading, SensorType};
+use std::f32::consts::PI;
+
+/// Range of values an encoder maps onto `[0, 1]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueRange {
+    /// Value mapped to 0
+    pub min: f32,
+    /// Value mapped to 1
+    pub max: f32,
+}
+
+impl ValueRange {
+    /// Create a range; `max` must be above `min`
+    pub const fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    /// Typical range of one value of `sensor_type`, the same scales as
+    /// `SensorReading::to_features`
+    pub fn for_sensor(sensor_type: SensorType) -> Self {
+        match sensor_type {
+            SensorType::Accelerometer => Self::new(-20.0, 20.0),
+            SensorType::Gyroscope => Self::new(-10.0, 10.0),
+            SensorType::Magnetometer => Self::new(-100.0, 100.0),
+            SensorType::Light => Self::new(0.0, 10_000.0),
+            SensorType::Proximity => Self::new(0.0, 10.0),
+            SensorType::Barometer => Self::new(900.0, 1100.0),
+            SensorType::Gps => Self::new(-180.0, 180.0),
+            SensorType::Audio => Self::new(-1.0, 1.0),
+            SensorType::Orientation => Self::new(-PI, PI),
+            SensorType::Touch | SensorType::Custom(_) => Self::new(0.0, 1.0),
+        }
+    }
+
+    /// Position of `value` in the range, clamped to `[0, 1]`; `None` for
+    /// non-finite values
+    pub fn normalize(&self, value: f32) -> Option<f32> {
+        if !value.is_finite() {
+            return None;
+        }
+        let span = self.max - self.min;
+        if span <= 0.0 {
+            return Some(0.0);
+        }
+        Some(((value - self.min) / span).clamp(0.0, 1.0))
+    }
+}
+
+/// Converts sensor values into SNN input spikes
+pub trait SpikeEncoder {
+    /// Spike inputs produced for each value
+    fn inputs_per_value(&self) -> usize;
+
+    /// Encode one time step of values into
+    /// `values.len() * inputs_per_value()` spikes
+    fn encode(&mut self, values: &[f32]) -> Vec<bool>;
+
+    /// Forget any state carried between time steps
+    fn reset(&mut self) {}
+
+    /// Input layer size of a network fed with `sensor_type` readings
+    fn input_size(&self, sensor_type: SensorType) -> usize {
+        sensor_type.dimensions() * self.inputs_per_value()
+    }
+
+    /// Encode a reading, padded or truncated to its sensor's dimensions so
+    /// every step has the same width
+    fn encode_reading(&mut self, reading: &SensorReading) -> Vec<bool> {
+        let mut values = reading.values.clone();
+        values.resize(reading.sensor_type.dimensions(), f32::NAN);
+        self.encode(&values)
+    }
+
+    /// Encode the `sensor_type` readings of a buffer, oldest first, one
+    /// spike vector per network step
+    fn encode_buffer(&mut self, buffer: &SensorBuffer, sensor_type: SensorType) -> Vec<Vec<bool>> {
+        buffer
+            .readings_of_type(sensor_type)
+            .into_iter()
+            .map(|reading| self.encode_reading(reading))
+            .collect()
+    }
+}
+
+/// Rate coding: each value fires with probability equal to its normalized
+/// magnitude
+#[derive(Debug, Clone)]
+pub struct RateEncoder {
+    range: ValueRange,
+    rng: SeededRng,
+}
+
+impl RateEncoder {
+    /// Create an encoder over `range`, seeded for reproducible spike trains
+    pub fn new(range: ValueRange, seed: u64) -> Self {
+        Self {
+            range,
+            rng: SeededRng::new(seed),
+        }
+    }
+
+    /// Create an encoder over the typical range of `sensor_type`
+    pub fn for_sensor(sensor_type: SensorType, seed: u64) -> Self {
+        Self::new(ValueRange::for_sensor(sensor_type), seed)
+    }
+}
+
+impl SpikeEncoder for RateEncoder {
+    fn inputs_per_value(&self) -> usize {
+        1
+    }
+
+    fn encode(&mut self, values: &[f32]) -> Vec<bool> {
+        values
+            .iter()
+            .map(|&v| {
+                let draw = self.rng.next_f32();
+                self.range.normalize(v).is_some_and(|p| draw < p)
+            })
+            .collect()
+    }
+}
+
+/// Delta (threshold) coding: a value fires its rise input when it has
+/// climbed `threshold` above the level at its last spike, and its fall
+/// input when it has dropped as far
+#[derive(Debug, Clone)]
+pub struct DeltaEncoder {
+    threshold: f32,
+    reference: Vec<Option<f32>>,
+}
+
+impl DeltaEncoder {
+    /// Create an encoder firing on changes of at least `threshold`, in the
+    /// sensor's own units
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold: threshold.abs(),
+            reference: Vec::new(),
+        }
+    }
+}
+
+impl SpikeEncoder for DeltaEncoder {
+    fn inputs_per_value(&self) -> usize {
+        2
+    }
+
+    /// Spikes are `[rise, fall]` pairs per value; a value's first sample
+    /// only sets its reference level
+    fn encode(&mut self, values: &[f32]) -> Vec<bool> {
+        if self.reference.len() < values.len() {
+            self.reference.resize(values.len(), None);
+        }
+        let mut spikes = Vec::with_capacity(values.len() * 2);
+        for (&v, reference) in values.iter().zip(self.reference.iter_mut()) {
+            let (mut rise, mut fall) = (false, false);
+            if v.is_finite() {
+                match *reference {
+                    Some(level) if v - level >= self.threshold => rise = true,
+                    Some(level) if level - v >= self.threshold => fall = true,
+                    Some(_) => {}
+                    None => *reference = Some(v),
+                }
+                if rise || fall {
+                    *reference = Some(v);
+                }
+            }
+            spikes.push(rise);
+            spikes.push(fall);
+        }
+        spikes
+    }
+
+    fn reset(&mut self) {
+        self.reference.clear();
+    }
+}
+
+/// Population coding: each value drives a bank of inputs with Gaussian
+/// tuning curves evenly spread over its range; inputs whose response
+/// reaches one half fire
+#[derive(Debug, Clone)]
+pub struct PopulationEncoder {
+    range: ValueRange,
+    size: usize,
+    width: f32,
+}
+
+impl PopulationEncoder {
+    /// Create an encoder with `size` inputs per value (at least one), each
+    /// tuned with a standard deviation of one centre spacing
+    pub fn new(range: ValueRange, size: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            range,
+            size,
+            width: 1.0 / size.saturating_sub(1).max(1) as f32,
+        }
+    }
+
+    /// Create an encoder over the typical range of `sensor_type`
+    pub fn for_sensor(sensor_type: SensorType, size: usize) -> Self {
+        Self::new(ValueRange::for_sensor(sensor_type), size)
+    }
+
+    /// Change the tuning curve width, as a fraction of the range
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width.max(f32::EPSILON);
+        self
+    }
+
+    /// Response of each input to `value`, in `[0, 1]`
+    pub fn responses(&self, value: f32) -> Vec<f32> {
+        let Some(x) = self.range.normalize(value) else {
+            return vec![0.0; self.size];
+        };
+        let spacing = 1.0 / self.size.saturating_sub(1).max(1) as f32;
+        (0..self.size)
+            .map(|i| {
+                let d = (x - i as f32 * spacing) / self.width;
+                (-0.5 * d * d).exp()
+            })
+            .collect()
+    }
+}
+
+impl SpikeEncoder for PopulationEncoder {
+    fn inputs_per_value(&self) -> usize {
+        self.size
+    }
+
+    fn encode(&mut self, values: &[f32]) -> Vec<bool> {
+        values
+            .iter()
+            .flat_map(|&v| self.responses(v).into_iter().map(|r| r >= 0.5))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snn::SpikingNetwork;
+
+    #[test]
+    fn test_encoders_drive_a_network_from_sensor_readings() {
+        let mut buffer = SensorBuffer::new(16);
+        for (i, y) in [0.0, 0.1, 2.5, 2.6, -1.0].into_iter().enumerate() {
+            buffer.push(SensorReading::with_timestamp(
+                SensorType::Accelerometer,
+                vec![0.0, y, 20.0],
+                i as u64 * 10,
+            ));
+        }
+        buffer.push(SensorReading::with_timestamp(
+            SensorType::Light,
+            vec![5.0],
+            60,
+        ));
+        let sensor = SensorType::Accelerometer;
+
+        let mut delta = DeltaEncoder::new(1.0);
+        let steps = delta.encode_buffer(&buffer, sensor);
+        assert_eq!(steps.len(), 5);
+        let rises: Vec<bool> = steps.iter().map(|s| s[2]).collect();
+        let falls: Vec<bool> = steps.iter().map(|s| s[3]).collect();
+        assert_eq!(rises, vec![false, false, true, false, false]);
+        assert_eq!(falls, vec![false, false, false, false, true]);
+        assert!(steps.iter().all(|s| !s[0] && !s[1] && !s[4] && !s[5]));
+
+        let mut rate = RateEncoder::for_sensor(sensor, 7);
+        let mut fired = [0; 3];
+        for _ in 0..200 {
+            for (count, spike) in fired.iter_mut().zip(rate.encode(&[-20.0, 0.0, 20.0])) {
+                *count += usize::from(spike);
+            }
+        }
+        assert_eq!(fired[0], 0);
+        assert!((60..140).contains(&fired[1]), "{:?}", fired);
+        assert_eq!(fired[2], 200);
+
+        let mut population = PopulationEncoder::for_sensor(sensor, 5);
+        let low = population.encode(&[-20.0]);
+        let mid = population.encode(&[0.0]);
+        assert_eq!(low, vec![true, true, false, false, false]);
+        assert_eq!(mid, vec![false, true, true, true, false]);
+        let short = SensorReading::with_timestamp(sensor, vec![0.0], 0);
+        assert_eq!(population.encode_reading(&short)[5..], [false; 10]);
+
+        let mut network = SpikingNetwork::new_with_seed(population.input_size(sensor), 8, 2, 1);
+        for step in population.encode_buffer(&buffer, sensor) {
+            assert_eq!(network.step(&step, 1.0).len(), 2);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Energy Accounting
+//!
+//! Every answered query gets an estimate of the battery energy it cost
+//! the device, in `ResponseMetadata::energy_joules`:
+//!
+//! - running a model on the device draws `soc_watts` for the measured
+//!   latency, plus `joules_per_token_per_b` for every generated token and
+//!   billion model parameters;
+//! - waiting on the network draws `radio_watts` for the measured latency;
+//!   the remote model's own energy is not the battery's concern.
+//!
+//! A route pays for whatever it uses (Hybrid pays for both); custom
+//! targets are charged by their `RouteConstraints`. Cached answers cost
+//! nothing. Estimates add up per project in an [`EnergyLedger`], which
+//! `Orchestrator::energy_report` returns for battery-conscious users.
+//!
+//! ```toml
+//! [energy]
+//! soc_watts = 3.0
+//! radio_watts = 1.2
+//! local_model_params_b = 1.0
+//! battery_wh = 15.0
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::policy::RouteConstraints;
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Joules in one milliwatt-hour
+const JOULES_PER_MWH: f64 = 3.6;
+
+/// Energy estimation settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyConfig {
+    /// Estimate and record energy per response
+    pub enabled: bool,
+    /// Power drawn by the SoC while the local model runs (W)
+    pub soc_watts: f32,
+    /// Power drawn by the radio while waiting on the network (W)
+    pub radio_watts: f32,
+    /// Size of the local model (billions of parameters)
+    pub local_model_params_b: f32,
+    /// Energy per generated token per billion parameters (J)
+    pub joules_per_token_per_b: f32,
+    /// Battery capacity, for reporting energy as a share of a charge (Wh)
+    pub battery_wh: f32,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            soc_watts: 3.0,
+            radio_watts: 1.2,
+            local_model_params_b: 1.0,
+            joules_per_token_per_b: 0.05,
+            battery_wh: 15.0,
+        }
+    }
+}
+
+impl EnergyConfig {
+    /// Estimated joules for an answer of `tokens` that took `latency_ms`
+    /// on a route with `constraints`
+    pub fn estimate(
+        &self,
+        constraints: RouteConstraints,
+        tokens: Option<u32>,
+        latency_ms: u64,
+    ) -> f32 {
+        let seconds = latency_ms as f32 / 1_000.0;
+        let mut joules = 0.0;
+        if constraints.uses_device_model {
+            let per_token = self.joules_per_token_per_b * self.local_model_params_b;
+            joules += self.soc_watts * seconds + per_token * tokens.unwrap_or(0) as f32;
+        }
+        if constraints.uses_network {
+            joules += self.radio_watts * seconds;
+        }
+        joules
+    }
+}
+
+/// Energy spent on one route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteEnergy {
+    /// The route
+    pub route: RoutingDecision,
+    /// Answers it produced
+    pub queries: u64,
+    /// Energy they cost (J)
+    pub joules: f64,
+}
+
+/// Energy spent in one project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectEnergy {
+    /// The project; `None` for queries outside any project
+    pub project: Option<String>,
+    /// Answers counted
+    pub queries: u64,
+    /// Energy they cost (J)
+    pub joules: f64,
+    /// Breakdown by route
+    pub routes: Vec<RouteEnergy>,
+}
+
+impl ProjectEnergy {
+    /// Energy in milliwatt-hours, the unit of battery capacities
+    pub fn mwh(&self) -> f64 {
+        self.joules / JOULES_PER_MWH
+    }
+}
+
+/// Cumulative energy per project
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyLedger {
+    projects: Vec<ProjectEnergy>,
+}
+
+impl EnergyLedger {
+    /// An empty ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `joules` spent answering on `route` in `project`
+    pub fn record(&mut self, project: Option<&str>, route: RoutingDecision, joules: f32) {
+        let index = match self.position(project) {
+            Some(index) => index,
+            None => {
+                self.projects.push(ProjectEnergy {
+                    project: project.map(str::to_string),
+                    queries: 0,
+                    joules: 0.0,
+                    routes: Vec::new(),
+                });
+                self.projects.len() - 1
+            }
+        };
+        let entry = &mut self.projects[index];
+        entry.queries += 1;
+        entry.joules += f64::from(joules);
+        match entry.routes.iter_mut().find(|spent| spent.route == route) {
+            Some(spent) => {
+                spent.queries += 1;
+                spent.joules += f64::from(joules);
+            }
+            None => entry.routes.push(RouteEnergy {
+                route,
+                queries: 1,
+                joules: f64::from(joules),
+            }),
+        }
+    }
+
+    /// Energy spent in `project`, if it answered anything
+    pub fn project(&self, project: Option<&str>) -> Option<&ProjectEnergy> {
+        self.position(project).map(|index| &self.projects[index])
+    }
+
+    /// Report over every project, hungriest first, with battery share
+    /// taken from a `battery_wh` charge
+    pub fn report(&self, battery_wh: f32) -> EnergyReport {
+        let mut projects = self.projects.clone();
+        projects.sort_by(|a, b| b.joules.total_cmp(&a.joules));
+        EnergyReport {
+            total_joules: projects.iter().map(|project| project.joules).sum(),
+            projects,
+            battery_wh,
+        }
+    }
+
+    fn position(&self, project: Option<&str>) -> Option<usize> {
+        self.projects
+            .iter()
+            .position(|entry| entry.project.as_deref() == project)
+    }
+}
+
+/// Energy the orchestrator has cost the battery, per project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyReport {
+    /// Per-project energy, hungriest first
+    pub projects: Vec<ProjectEnergy>,
+    /// Energy over all projects (J)
+    pub total_joules: f64,
+    /// Battery capacity the share is taken of (Wh)
+    pub battery_wh: f32,
+}
+
+impl EnergyReport {
+    /// Share of a full battery charge spent, 0..=1 and beyond
+    pub fn battery_share(&self) -> f64 {
+        if self.battery_wh <= 0.0 {
+            return 0.0;
+        }
+        self.total_joules / (f64::from(self.battery_wh) * 3_600.0)
+    }
+}
+
+impl fmt::Display for EnergyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Energy: {:.1} mWh ({:.2}% of a charge)",
+            self.total_joules / JOULES_PER_MWH,
+            self.battery_share() * 100.0
+        )?;
+        for project in &self.projects {
+            write!(
+                f,
+                "\n  {}: {:.1} mWh over {} answers",
+                project.project.as_deref().unwrap_or("(no project)"),
+                project.mwh(),
+                project.queries
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimates_charge_what_the_route_uses() {
+        let config = EnergyConfig::default();
+        let local = config.estimate(
+            RouteConstraints::builtin(RoutingDecision::Local),
+            Some(100),
+            2_000,
+        );
+        // 3 W for 2 s plus 100 tokens at 0.05 J
+        assert!((local - 11.0).abs() < 1e-4);
+        let remote = config.estimate(
+            RouteConstraints::builtin(RoutingDecision::Remote),
+            Some(100),
+            2_000,
+        );
+        assert!((remote - 2.4).abs() < 1e-4);
+        let hybrid = config.estimate(
+            RouteConstraints::builtin(RoutingDecision::Hybrid),
+            Some(100),
+            2_000,
+        );
+        assert!((hybrid - 13.4).abs() < 1e-4);
+        let blocked = config.estimate(
+            RouteConstraints::builtin(RoutingDecision::Blocked),
+            None,
+            2_000,
+        );
+        assert_eq!(blocked, 0.0);
+
+        let mut ledger = EnergyLedger::new();
+        ledger.record(Some("garden"), RoutingDecision::Local, local);
+        ledger.record(Some("garden"), RoutingDecision::Remote, remote);
+        ledger.record(Some("garden"), RoutingDecision::Remote, remote);
+        ledger.record(None, RoutingDecision::Hybrid, hybrid);
+        let Some(garden) = ledger.project(Some("garden")) else {
+            panic!("garden should have spent energy");
+        };
+        assert_eq!(garden.queries, 3);
+        assert_eq!(garden.routes.len(), 2);
+        assert_eq!(garden.routes[1].queries, 2);
+
+        let report = ledger.report(15.0);
+        assert_eq!(report.projects[0].project.as_deref(), Some("garden"));
+        assert!((report.total_joules - 29.2).abs() < 1e-3);
+        assert!(report.to_string().contains("garden:"));
+    }
+}
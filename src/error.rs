@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Orchestrator Errors
+//!
+//! Every fallible orchestrator, router and training call fails with an
+//! [`OrchestratorError`], so callers can tell a shut-down orchestrator
+//! from an offline network or a failing database without parsing
+//! messages. Variants that carry a `String` carry a human-readable
+//! explanation; `Display` renders the whole error for logs and UIs.
+//!
+//! Queries refused by safety or resource rules are not errors: `process`
+//! answers them with a `Blocked` response that keeps the rule, reason and
+//! latency.
+//! [`Response::into_result`](crate::types::Response::into_result)
+//! turns such a response into [`OrchestratorError::Blocked`] for callers
+//! that prefer `?`.
+//!
+//! A query that waits in the offline queue is answered with
+//! [`OrchestratorError::Deferred`]; its answer arrives later as an event.
+
+#![forbid(unsafe_code)]
+
+use crate::requirements::MissingCapabilities;
+use crate::types::RoutingDecision;
+use std::fmt;
+
+/// Why an orchestrator call failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrchestratorError {
+    /// A safety or resource rule refused the query
+    Blocked {
+        /// Rule that refused it, if known
+        rule_id: Option<String>,
+        /// Explanation for the user
+        reason: String,
+    },
+    /// `shutdown` was called; the orchestrator accepts no more work
+    ShutDown,
+    /// The query needs the network and was queued until the device is
+    /// back online (see `queue`)
+    Deferred {
+        /// Queue id, for `Orchestrator::cancel_queued`
+        queue_id: u64,
+        /// Route it waits for
+        route: RoutingDecision,
+    },
+    /// The route needs the network and the device is offline
+    NetworkUnavailable {
+        /// Route that needed it
+        route: RoutingDecision,
+    },
+    /// The profile, a project policy or the remote token budget does not
+    /// allow the route
+    RouteNotAllowed {
+        /// Route refused
+        route: RoutingDecision,
+        /// What refused it
+        reason: String,
+    },
+    /// No backend offers every capability the query requires
+    MissingCapabilities(MissingCapabilities),
+    /// A model backend failed and nothing answered instead
+    BackendFailure(String),
+    /// The persistence backend failed, or stored state could not be
+    /// encoded or decoded
+    PersistenceError(String),
+    /// An argument, model, rule set or training set was rejected
+    InvalidInput(String),
+    /// A turn, session or other item is unknown
+    NotFound(String),
+    /// The call needs something that is not configured or attached
+    NotConfigured(String),
+}
+
+impl fmt::Display for OrchestratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocked {
+                rule_id: Some(rule_id),
+                reason,
+            } => write!(f, "blocked by {}: {}", rule_id, reason),
+            Self::Blocked {
+                rule_id: None,
+                reason,
+            } => write!(f, "blocked: {}", reason),
+            Self::ShutDown => write!(f, "orchestrator has been shut down"),
+            Self::Deferred { queue_id, route } => write!(
+                f,
+                "queued as #{} until the device is online; the {:?} route needs the network",
+                queue_id, route
+            ),
+            Self::NetworkUnavailable { route } => {
+                write!(f, "the {:?} route needs a network connection", route)
+            }
+            Self::RouteNotAllowed { route, reason } => {
+                write!(f, "{}; cannot use the {:?} route", reason, route)
+            }
+            Self::MissingCapabilities(missing) => write!(f, "{}", missing),
+            Self::BackendFailure(message)
+            | Self::PersistenceError(message)
+            | Self::InvalidInput(message)
+            | Self::NotFound(message)
+            | Self::NotConfigured(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for OrchestratorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MissingCapabilities(missing) => Some(missing),
+            _ => None,
+        }
+    }
+}
+
+impl From<MissingCapabilities> for OrchestratorError {
+    fn from(missing: MissingCapabilities) -> Self {
+        Self::MissingCapabilities(missing)
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl From<rusqlite::Error> for OrchestratorError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::PersistenceError(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ModelCapability;
+    use std::error::Error;
+
+    #[test]
+    fn test_errors_render_and_expose_their_cause() {
+        let blocked = OrchestratorError::Blocked {
+            rule_id: Some("SAFETY_001".to_string()),
+            reason: "contains a credential".to_string(),
+        };
+        assert_eq!(
+            blocked.to_string(),
+            "blocked by SAFETY_001: contains a credential"
+        );
+
+        let refused = OrchestratorError::RouteNotAllowed {
+            route: RoutingDecision::Remote,
+            reason: "profile Offline does not allow it".to_string(),
+        };
+        assert_eq!(
+            refused.to_string(),
+            "profile Offline does not allow it; cannot use the Remote route"
+        );
+
+        let missing = MissingCapabilities {
+            route: RoutingDecision::Local,
+            missing: vec![ModelCapability::Vision],
+        };
+        let error = OrchestratorError::from(missing.clone());
+        assert_eq!(error.to_string(), missing.to_string());
+        assert!(error.source().is_some());
+        assert!(OrchestratorError::ShutDown.source().is_none());
+    }
+}
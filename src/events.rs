@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Event Bus — In-Process Pub/Sub Between Components
+//!
+//! Components publish typed [`Event`]s; hooks, metrics, host callbacks
+//! and the proactive engine subscribe to them. New integrations attach a
+//! subscriber instead of threading bespoke plumbing through
+//! `Orchestrator::process`.
+//!
+//! Delivery is synchronous and in subscription order. Subscribers run on
+//! the publishing thread, so they should be cheap (record, enqueue, or
+//! forward) rather than doing I/O inline.
+
+#![forbid(unsafe_code)]
+
+use crate::context_switch::ContextEvent;
+use crate::settings::Setting;
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Events published by orchestrator components
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// The router chose a route for a query
+    RouteDecided {
+        /// Query text
+        query: String,
+        /// Chosen route
+        decision: RoutingDecision,
+        /// Router confidence
+        confidence: f32,
+    },
+    /// An expert-system rule fired
+    RuleTriggered {
+        /// Identifier of the rule
+        rule_id: String,
+        /// Human-readable reason
+        reason: Option<String>,
+    },
+    /// A detector (wake word, activity, ...) fired
+    TriggerDetected {
+        /// Detector-defined trigger kind (e.g. "wake")
+        kind: String,
+        /// Detector confidence
+        confidence: f32,
+    },
+    /// The foreground app, place or activity changed
+    ContextChanged {
+        /// What changed
+        change: ContextEvent,
+    },
+    /// A resource budget was exceeded
+    BudgetExceeded {
+        /// Budget name (e.g. "tokens", "battery")
+        budget: String,
+        /// Amount used
+        used: f64,
+        /// Configured limit
+        limit: f64,
+    },
+    /// A budget is forecast to be exceeded within a few turns
+    BudgetForecast {
+        /// Budget name (e.g. "daily_spend")
+        budget: String,
+        /// Forecast number of turns until the limit is exceeded
+        turns_remaining: usize,
+        /// Amount used so far
+        used: f64,
+        /// Configured limit
+        limit: f64,
+    },
+    /// A route started violating its latency SLA
+    SlaViolated {
+        /// Violating route
+        route: RoutingDecision,
+        /// Observed p95 latency (ms)
+        p95_ms: u64,
+        /// Declared limit (ms)
+        limit_ms: u64,
+    },
+    /// A model version was promoted to serve traffic
+    ModelPromoted {
+        /// Model name
+        model: String,
+        /// Promoted version
+        version: String,
+    },
+    /// One chunk of an oversized query was mapped to a partial answer
+    ChunkProcessed {
+        /// 0-based chunk index
+        index: usize,
+        /// Chunks in the job
+        total: usize,
+        /// Partial answer for this chunk
+        partial: String,
+    },
+    /// A day of conversation was written to the journal
+    DailySummaryReady {
+        /// Local date (`YYYY-MM-DD`) up to which the journal is complete
+        date: String,
+        /// Turns written
+        entries: usize,
+    },
+    /// A scheduled digest query was answered
+    DigestReady {
+        /// Identifier of the digest
+        digest_id: u64,
+        /// Query that was asked
+        query: String,
+        /// The answer
+        text: String,
+    },
+    /// A query queued while offline was answered
+    QueuedAnswered {
+        /// Queue id of the query
+        queue_id: u64,
+        /// Query that was asked
+        query: String,
+        /// The answer
+        text: String,
+    },
+    /// A runtime setting changed (see `settings`)
+    SettingChanged {
+        /// The setting and its new value
+        setting: Setting,
+    },
+}
+
+impl Event {
+    /// Kind of this event, for filtered subscriptions
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::RouteDecided { .. } => EventKind::RouteDecided,
+            Event::RuleTriggered { .. } => EventKind::RuleTriggered,
+            Event::TriggerDetected { .. } => EventKind::TriggerDetected,
+            Event::ContextChanged { .. } => EventKind::ContextChanged,
+            Event::BudgetExceeded { .. } => EventKind::BudgetExceeded,
+            Event::BudgetForecast { .. } => EventKind::BudgetForecast,
+            Event::SlaViolated { .. } => EventKind::SlaViolated,
+            Event::ModelPromoted { .. } => EventKind::ModelPromoted,
+            Event::ChunkProcessed { .. } => EventKind::ChunkProcessed,
+            Event::DailySummaryReady { .. } => EventKind::DailySummaryReady,
+            Event::DigestReady { .. } => EventKind::DigestReady,
+            Event::QueuedAnswered { .. } => EventKind::QueuedAnswered,
+            Event::SettingChanged { .. } => EventKind::SettingChanged,
+        }
+    }
+}
+
+/// Discriminant of [`Event`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventKind {
+    /// [`Event::RouteDecided`]
+    RouteDecided,
+    /// [`Event::RuleTriggered`]
+    RuleTriggered,
+    /// [`Event::TriggerDetected`]
+    TriggerDetected,
+    /// [`Event::ContextChanged`]
+    ContextChanged,
+    /// [`Event::BudgetExceeded`]
+    BudgetExceeded,
+    /// [`Event::BudgetForecast`]
+    BudgetForecast,
+    /// [`Event::SlaViolated`]
+    SlaViolated,
+    /// [`Event::ModelPromoted`]
+    ModelPromoted,
+    /// [`Event::ChunkProcessed`]
+    ChunkProcessed,
+    /// [`Event::DailySummaryReady`]
+    DailySummaryReady,
+    /// [`Event::DigestReady`]
+    DigestReady,
+    /// [`Event::QueuedAnswered`]
+    QueuedAnswered,
+    /// [`Event::SettingChanged`]
+    SettingChanged,
+}
+
+/// Handle returned by [`EventBus::subscribe`], used to unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Handler = Box<dyn FnMut(&Event) + Send>;
+
+struct Subscriber {
+    id: SubscriptionId,
+    kinds: Option<Vec<EventKind>>,
+    handler: Handler,
+}
+
+/// Synchronous publish/subscribe bus
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Subscriber>,
+    next_id: u64,
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    /// Create a bus with no subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive every event
+    pub fn subscribe(&mut self, handler: impl FnMut(&Event) + Send + 'static) -> SubscriptionId {
+        self.add(None, Box::new(handler))
+    }
+
+    /// Receive only events of the given kinds
+    pub fn subscribe_to(
+        &mut self,
+        kinds: &[EventKind],
+        handler: impl FnMut(&Event) + Send + 'static,
+    ) -> SubscriptionId {
+        self.add(Some(kinds.to_vec()), Box::new(handler))
+    }
+
+    /// Remove a subscriber; returns false if it was already removed
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|s| s.id != id);
+        self.subscribers.len() != before
+    }
+
+    /// Deliver `event` to every matching subscriber, in subscription order
+    pub fn publish(&mut self, event: &Event) {
+        let kind = event.kind();
+        for subscriber in &mut self.subscribers {
+            let wanted = subscriber
+                .kinds
+                .as_ref()
+                .map_or(true, |kinds| kinds.contains(&kind));
+            if wanted {
+                (subscriber.handler)(event);
+            }
+        }
+    }
+
+    /// Number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    fn add(&mut self, kinds: Option<Vec<EventKind>>, handler: Handler) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push(Subscriber { id, kinds, handler });
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn budget_event() -> Event {
+        Event::BudgetExceeded {
+            budget: "tokens".to_string(),
+            used: 120.0,
+            limit: 100.0,
+        }
+    }
+
+    #[test]
+    fn test_subscribers_receive_events() {
+        let mut bus = EventBus::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let sink = Arc::clone(&seen);
+        bus.subscribe(move |event| {
+            if let Ok(mut seen) = sink.lock() {
+                seen.push(event.kind());
+            }
+        });
+
+        bus.publish(&budget_event());
+
+        let Ok(seen) = seen.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        assert_eq!(*seen, vec![EventKind::BudgetExceeded]);
+    }
+
+    #[test]
+    fn test_filtered_subscription_and_unsubscribe() {
+        let mut bus = EventBus::new();
+        let count = Arc::new(Mutex::new(0));
+
+        let sink = Arc::clone(&count);
+        let id = bus.subscribe_to(&[EventKind::ModelPromoted], move |_| {
+            if let Ok(mut count) = sink.lock() {
+                *count += 1;
+            }
+        });
+
+        bus.publish(&budget_event());
+        bus.publish(&Event::ModelPromoted {
+            model: "router".to_string(),
+            version: "2".to_string(),
+        });
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.publish(&Event::ModelPromoted {
+            model: "router".to_string(),
+            version: "3".to_string(),
+        });
+
+        let Ok(count) = count.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        assert_eq!(*count, 1);
+        assert_eq!(bus.subscriber_count(), 0);
+    }
+
+    #[test]
+    fn test_orchestrator_publishes_pipeline_events() {
+        use crate::orchestrator::Orchestrator;
+        use crate::types::Query;
+
+        let mut orchestrator = Orchestrator::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator.events_mut().subscribe(move |event| {
+            if let Ok(mut seen) = sink.lock() {
+                seen.push(event.clone());
+            }
+        });
+
+        let Ok(_) = orchestrator.process(Query::new("How do I sort a list?")) else {
+            panic!("process should succeed");
+        };
+        let Ok(_) = orchestrator.process(Query::new("my password is hunter2")) else {
+            panic!("blocked queries still return a response");
+        };
+
+        let Ok(seen) = seen.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        assert!(matches!(seen[0], Event::RouteDecided { .. }));
+        assert!(matches!(
+            &seen[1],
+            Event::RuleTriggered { rule_id, .. } if rule_id == "PRIVACY_001"
+        ));
+    }
+}
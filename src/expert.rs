@@ -9,23 +9,585 @@
 //! 1. **Explainability**: Every rejection includes a human-readable
 //!    `rule_id` and reason.
 //! 2. **Privacy**: Proactively detects and blocks potential credential
-//!    leakage (API keys, passwords).
-//! 3. **Attenuation**: Enforces resource limits (e.g. max query length)
-//!    to prevent Denial of Service.
+//!    leakage (API keys, passwords), and masks personal data such as email
+//!    addresses rather than rejecting the whole query.
+//! 3. **Attenuation**: Enforces resource limits (e.g. max query length,
+//!    and with `[expert.limits]` query rates and the remote token budget;
+//!    see [`crate::quota`]) to prevent Denial of Service.
+//!
+//! Rules are declarative, so deployments can change policy without
+//! recompiling: list them under `[[expert.rules]]` in the configuration,
+//! load a TOML or JSON rules file with [`load_rules`] (or its text with
+//! [`rules_from_toml`] and [`rules_from_json`]), or build them in code
+//! with [`RuleBuilder`].
+//!
+//! ```toml
+//! [[expert.rules]]
+//! id = "PRIVACY_002"
+//! keywords = ["iban", "sort code"]   # any of these, case-insensitive
+//! regex = '\b[A-Z]{2}\d{2}[A-Z0-9]{11,30}\b'
+//! action = "Block"                   # Block, Warn or Redact
+//! severity = "High"                  # Low, Medium, High, Critical
+//! reason = "Bank details stay on the device"
+//! ```
+//!
+//! A rule matches when all of its conditions hold (`keywords`, `regex`,
+//! `longer_than`, `shorter_than`, `projects`). A `Redact` rule replaces
+//! what its `regex` matched (or, without one, its keywords) with its `mask`
+//! and lets the query proceed; later rules see the masked text.
+//!
+//! CONFLICTS: Rules run by descending `priority` (default 0); at equal
+//! priority `Allow` rules run first, then rules in the order they were
+//! listed. A matching `Allow` rule overrides every `Block` rule that runs
+//! after it, except `Critical` ones, so a project can allow what a global
+//! rule blocks:
+//!
+//! ```toml
+//! [[expert.rules]]
+//! id = "ALLOW_SECURITY_RESEARCH"
+//! keywords = ["malware", "exploit"]
+//! projects = ["security-lab"]        # only queries in these projects
+//! action = "Allow"
+//! ```
+//!
+//! Warnings and redactions still apply to allowed queries, and plugin
+//! rules cannot be overridden.
+//!
+//! POLICIES: Named policy profiles bind a rule set and routing limits to
+//! projects. Queries in a bound project are checked against the policy's
+//! rules (together with the global ones, unless `inherit_rules = false`)
+//! and may only take the policy's `routes`; the orchestrator picks the
+//! policy of the active project, so `switch_project` switches policy too.
+//!
+//! ```toml
+//! [[expert.policies]]
+//! name = "kids-mode"
+//! projects = ["homework"]
+//! routes = ["Local"]                 # never leaves the device
+//!
+//! [[expert.policies.rules]]
+//! id = "KIDS_001"
+//! keywords = ["casino", "betting"]
+//! ```
+
+use crate::fingerprint::StateFingerprint;
+use crate::plugin_api::QueryRule;
+use crate::quota::QuotaConfig;
+use crate::types::{Query, Redaction, RoutingDecision, RuleEvaluation};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// ACTION: What happens to a query a rule matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    /// Reject the query.
+    #[default]
+    Block,
+    /// Let the query through, noting the match in its explanation.
+    Warn,
+    /// Mask the matched spans and let the query through.
+    Redact,
+    /// Let the query through past the non-`Critical` `Block` rules that
+    /// run after it.
+    Allow,
+}
+
+/// Text that replaces redacted spans unless a rule sets its own.
+pub const DEFAULT_MASK: &str = "[REDACTED]";
+
+/// SEVERITY: How serious a match is, for reporting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RuleSeverity {
+    /// Worth knowing about.
+    Low,
+    /// Policy violation.
+    #[default]
+    Medium,
+    /// Privacy or safety risk.
+    High,
+    /// Must never reach a model.
+    Critical,
+}
+
+/// SPEC: A rule as written in a configuration or rules file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSpec {
+    /// Identifier reported when the rule fires.
+    pub id: String,
+    /// Matches when the query contains any of these (case-insensitive).
+    pub keywords: Vec<String>,
+    /// Matches when this regular expression matches the query.
+    pub regex: Option<String>,
+    /// Matches queries longer than this many characters.
+    pub longer_than: Option<usize>,
+    /// Matches queries shorter than this many characters.
+    pub shorter_than: Option<usize>,
+    /// Matches only queries in one of these projects.
+    pub projects: Vec<String>,
+    /// Rules with a higher priority run first.
+    pub priority: i32,
+    /// What happens on a match.
+    pub action: RuleAction,
+    /// How serious a match is.
+    pub severity: RuleSeverity,
+    /// Explanation reported on a match; defaults to "Rule <id> triggered".
+    pub reason: Option<String>,
+    /// `Redact` rules: replacement for masked spans; defaults to
+    /// [`DEFAULT_MASK`].
+    pub mask: Option<String>,
+}
+
+impl RuleSpec {
+    /// Compile the spec into a rule.
+    pub fn compile(&self) -> Result<Rule, String> {
+        let mut builder = RuleBuilder::new(&self.id)
+            .action(self.action)
+            .severity(self.severity)
+            .priority(self.priority);
+        if !self.keywords.is_empty() {
+            builder = builder.keywords(self.keywords.iter().cloned());
+        }
+        if let Some(pattern) = &self.regex {
+            builder = builder.regex(pattern);
+        }
+        if let Some(chars) = self.longer_than {
+            builder = builder.longer_than(chars);
+        }
+        if let Some(chars) = self.shorter_than {
+            builder = builder.shorter_than(chars);
+        }
+        if !self.projects.is_empty() {
+            builder = builder.projects(self.projects.iter().cloned());
+        }
+        if let Some(reason) = &self.reason {
+            builder = builder.reason(reason);
+        }
+        if let Some(mask) = &self.mask {
+            builder = builder.mask(mask);
+        }
+        builder.build()
+    }
+}
+
+/// RULES FILE: Top level of a TOML or JSON rules file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesFile {
+    /// The rules, in evaluation order.
+    pub rules: Vec<RuleSpec>,
+}
+
+/// CONFIG: Rule settings of the orchestrator configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpertConfig {
+    /// Keep the built-in PRIVACY/SAFETY rules (checked first).
+    pub builtin_rules: bool,
+    /// Deployment rules, checked after the built-in ones.
+    pub rules: Vec<RuleSpec>,
+    /// Policy profiles bound to projects.
+    pub policies: Vec<PolicySpec>,
+    /// Rate limits and remote token budget.
+    pub limits: QuotaConfig,
+}
+
+impl Default for ExpertConfig {
+    fn default() -> Self {
+        Self {
+            builtin_rules: true,
+            rules: Vec::new(),
+            policies: Vec::new(),
+            limits: QuotaConfig::default(),
+        }
+    }
+}
+
+/// POLICY SPEC: A policy profile as written in the configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicySpec {
+    /// Name reported in explanations, e.g. "strict" or "kids-mode".
+    pub name: String,
+    /// Projects whose queries the policy governs.
+    pub projects: Vec<String>,
+    /// Check the global rules too, ordered with the policy's own by
+    /// priority.
+    pub inherit_rules: bool,
+    /// The policy's own rules.
+    pub rules: Vec<RuleSpec>,
+    /// Routes queries may take; empty allows every route. Must include
+    /// `Local`, the fallback for routes the policy refuses.
+    pub routes: Vec<RoutingDecision>,
+}
+
+impl Default for PolicySpec {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            projects: Vec::new(),
+            inherit_rules: true,
+            rules: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl PolicySpec {
+    /// Compile the spec into a policy. Fails without a name, on a rule
+    /// that does not compile, or when `routes` leaves out `Local`.
+    pub fn compile(&self) -> Result<Policy, String> {
+        if self.name.trim().is_empty() {
+            return Err("a policy needs a name".to_string());
+        }
+        if !self.routes.is_empty() && !self.routes.contains(&RoutingDecision::Local) {
+            return Err(format!(
+                "policy {}: routes must include Local, the fallback route",
+                self.name
+            ));
+        }
+        let mut rules = Vec::new();
+        for spec in &self.rules {
+            rules.push(
+                spec.compile()
+                    .map_err(|e| format!("policy {}: {}", self.name, e))?,
+            );
+        }
+        rules.sort_by_key(Rule::order);
+        Ok(Policy {
+            name: self.name.clone(),
+            projects: self.projects.clone(),
+            inherit_rules: self.inherit_rules,
+            rules,
+            routes: self.routes.clone(),
+        })
+    }
+}
+
+/// POLICY: A compiled policy profile.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    name: String,
+    projects: Vec<String>,
+    inherit_rules: bool,
+    /// Own rules, in evaluation order.
+    rules: Vec<Rule>,
+    routes: Vec<RoutingDecision>,
+}
+
+impl Policy {
+    /// Name reported in explanations.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the policy governs queries in `project`.
+    pub fn governs(&self, project: &str) -> bool {
+        self.projects.iter().any(|p| p == project)
+    }
+
+    /// Whether queries under the policy may take `route`.
+    pub fn allows(&self, route: RoutingDecision) -> bool {
+        self.routes.is_empty() || self.routes.contains(&route)
+    }
+}
+
+/// CONDITION: One test a rule applies to a query.
+#[derive(Debug, Clone)]
+enum Condition {
+    /// Any of these lowercase keywords appears.
+    Keywords(Vec<String>),
+    Regex(Regex),
+    LongerThan(usize),
+    ShorterThan(usize),
+    /// The query belongs to one of these projects.
+    Projects(Vec<String>),
+}
 
-use crate::types::{Query, RuleEvaluation};
+impl Condition {
+    fn holds(&self, text: &str, lowercase: &str, project: Option<&str>) -> bool {
+        match self {
+            Self::Keywords(keywords) => keywords.iter().any(|k| lowercase.contains(k.as_str())),
+            Self::Regex(regex) => regex.is_match(text),
+            Self::LongerThan(chars) => text.chars().count() > *chars,
+            Self::ShorterThan(chars) => text.chars().count() < *chars,
+            Self::Projects(projects) => project.is_some_and(|p| projects.iter().any(|q| q == p)),
+        }
+    }
+}
 
-/// Rule: A predicate for query evaluation.
+/// Rule: A compiled, declarative policy check.
 #[derive(Debug, Clone)]
 pub struct Rule {
     id: String,
-    predicate: fn(&Query) -> bool,
+    conditions: Vec<Condition>,
+    action: RuleAction,
+    severity: RuleSeverity,
+    priority: i32,
+    reason: Option<String>,
+    /// `Redact` rules: the spans masked, and their replacement.
+    redaction: Option<(Regex, String)>,
+}
+
+impl Rule {
+    /// Identifier reported when the rule fires.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// What happens on a match.
+    pub fn action(&self) -> RuleAction {
+        self.action
+    }
+
+    /// How serious a match is.
+    pub fn severity(&self) -> RuleSeverity {
+        self.severity
+    }
+
+    /// Rules with a higher priority run first.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Whether every condition holds for `query`.
+    pub fn matches(&self, query: &Query) -> bool {
+        self.matches_in(&query.text, query.project_context.as_deref())
+    }
+
+    fn matches_in(&self, text: &str, project: Option<&str>) -> bool {
+        let lowercase = text.to_lowercase();
+        self.conditions
+            .iter()
+            .all(|condition| condition.holds(text, &lowercase, project))
+    }
+
+    /// Position in evaluation order: higher priority first, and `Allow`
+    /// before the other actions at equal priority.
+    fn order(&self) -> (std::cmp::Reverse<i32>, bool) {
+        (
+            std::cmp::Reverse(self.priority),
+            self.action != RuleAction::Allow,
+        )
+    }
+
+    /// `text` with the spans a `Redact` rule masks replaced, and how many
+    /// there were. Other rules leave it unchanged.
+    pub fn redact(&self, text: &str) -> (String, usize) {
+        match &self.redaction {
+            Some((spans, mask)) => {
+                let count = spans.find_iter(text).count();
+                (spans.replace_all(text, mask.as_str()).into_owned(), count)
+            }
+            None => (text.to_string(), 0),
+        }
+    }
+
+    /// Explanation reported on a match.
+    pub fn reason(&self) -> String {
+        self.reason
+            .clone()
+            .unwrap_or_else(|| format!("Rule {} triggered", self.id))
+    }
+}
+
+/// BUILDER: Assemble a rule in code.
+///
+/// ```
+/// use mobile_ai_orchestrator::expert::{RuleAction, RuleBuilder};
+///
+/// let rule = RuleBuilder::new("PRIVACY_002")
+///     .keywords(["iban", "sort code"])
+///     .action(RuleAction::Block)
+///     .reason("Bank details stay on the device")
+///     .build();
+/// assert!(rule.is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RuleBuilder {
+    id: String,
+    keywords: Vec<String>,
+    regex: Option<String>,
+    longer_than: Option<usize>,
+    shorter_than: Option<usize>,
+    projects: Vec<String>,
+    action: RuleAction,
+    severity: RuleSeverity,
+    priority: i32,
+    reason: Option<String>,
+    mask: Option<String>,
+}
+
+impl RuleBuilder {
+    /// Start a rule reported as `id`; it blocks by default.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            keywords: Vec::new(),
+            regex: None,
+            longer_than: None,
+            shorter_than: None,
+            projects: Vec::new(),
+            action: RuleAction::default(),
+            severity: RuleSeverity::default(),
+            priority: 0,
+            reason: None,
+            mask: None,
+        }
+    }
+
+    /// Match queries containing any of `keywords` (case-insensitive).
+    pub fn keywords<S: Into<String>>(mut self, keywords: impl IntoIterator<Item = S>) -> Self {
+        self.keywords
+            .extend(keywords.into_iter().map(|k| k.into().to_lowercase()));
+        self
+    }
+
+    /// Match queries matching the regular expression `pattern`.
+    pub fn regex(mut self, pattern: impl Into<String>) -> Self {
+        self.regex = Some(pattern.into());
+        self
+    }
+
+    /// Match queries longer than `chars` characters.
+    pub fn longer_than(mut self, chars: usize) -> Self {
+        self.longer_than = Some(chars);
+        self
+    }
+
+    /// Match queries shorter than `chars` characters.
+    pub fn shorter_than(mut self, chars: usize) -> Self {
+        self.shorter_than = Some(chars);
+        self
+    }
+
+    /// Match only queries in one of `projects`.
+    pub fn projects<S: Into<String>>(mut self, projects: impl IntoIterator<Item = S>) -> Self {
+        self.projects.extend(projects.into_iter().map(Into::into));
+        self
+    }
+
+    /// Run before rules of a lower priority (default 0).
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// What happens on a match.
+    pub fn action(mut self, action: RuleAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// How serious a match is.
+    pub fn severity(mut self, severity: RuleSeverity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Explanation reported on a match.
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// `Redact` rules: replacement for masked spans.
+    pub fn mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = Some(mask.into());
+        self
+    }
+
+    /// Compile the rule. Fails without an id or any condition, on an
+    /// invalid regular expression, or for a `Redact` rule with neither a
+    /// regex nor keywords to mask.
+    pub fn build(self) -> Result<Rule, String> {
+        if self.id.trim().is_empty() {
+            return Err("a rule needs an id".to_string());
+        }
+        let mut conditions = Vec::new();
+        let mut spans = None;
+        if !self.keywords.is_empty() {
+            let alternatives: Vec<String> =
+                self.keywords.iter().map(|k| regex::escape(k)).collect();
+            spans = Regex::new(&format!("(?i){}", alternatives.join("|"))).ok();
+            conditions.push(Condition::Keywords(self.keywords));
+        }
+        if let Some(pattern) = self.regex {
+            let regex = Regex::new(&pattern)
+                .map_err(|e| format!("rule {}: invalid regex: {}", self.id, e))?;
+            spans = Some(regex.clone());
+            conditions.push(Condition::Regex(regex));
+        }
+        let redaction = match (self.action, spans) {
+            (RuleAction::Redact, Some(spans)) => {
+                Some((spans, self.mask.unwrap_or_else(|| DEFAULT_MASK.to_string())))
+            }
+            (RuleAction::Redact, None) => {
+                return Err(format!(
+                    "rule {} redacts but has no regex or keywords to mask",
+                    self.id
+                ));
+            }
+            _ => None,
+        };
+        conditions.extend(self.longer_than.map(Condition::LongerThan));
+        conditions.extend(self.shorter_than.map(Condition::ShorterThan));
+        if !self.projects.is_empty() {
+            conditions.push(Condition::Projects(self.projects));
+        }
+        if conditions.is_empty() {
+            return Err(format!("rule {} has no conditions", self.id));
+        }
+        Ok(Rule {
+            id: self.id,
+            conditions,
+            action: self.action,
+            severity: self.severity,
+            priority: self.priority,
+            reason: self.reason,
+            redaction,
+        })
+    }
+}
+
+/// Parse a rules file; JSON when `path` ends in `.json`, TOML otherwise.
+pub fn load_rules<P: AsRef<Path>>(path: P) -> Result<Vec<Rule>, String> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let rules = if path.extension().is_some_and(|ext| ext == "json") {
+        rules_from_json(&text)
+    } else {
+        rules_from_toml(&text)
+    };
+    rules.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parse rules from TOML text, for hosts without a file system (e.g. a
+/// browser shell that fetched the rules itself).
+pub fn rules_from_toml(text: &str) -> Result<Vec<Rule>, String> {
+    let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    file.rules.iter().map(RuleSpec::compile).collect()
+}
+
+/// Parse rules from JSON text; see `rules_from_toml`.
+pub fn rules_from_json(text: &str) -> Result<Vec<Rule>, String> {
+    let file: RulesFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    file.rules.iter().map(RuleSpec::compile).collect()
 }
 
 /// RULE ENGINE: Manages a collection of security and policy predicates.
 #[derive(Debug, Clone)]
 pub struct ExpertSystem {
     rules: Vec<Rule>,
+    /// Rules added by plugins, checked after the built-in ones.
+    plugin_rules: Vec<Arc<dyn QueryRule>>,
+    /// Policy profiles, looked up by project.
+    policies: Vec<Policy>,
+    /// Name of the policy governing every query, whatever its project.
+    user_policy: Option<String>,
 }
 
 impl Default for ExpertSystem {
@@ -34,53 +596,477 @@ fn default() -> Self {
     }
 }
 
-/// EVALUATION: Iterates through the rule set. If any `Block` rule
-/// matches the query, the entire request is rejected immediately.
+/// EVALUATION: Iterates through the rule set in priority order. If any
+/// `Block` rule matches the query, the entire request is rejected
+/// immediately, unless an `Allow` rule matched first; `Redact` rules mask
+/// what they match and let it continue.
 impl ExpertSystem {
     /// Create a new expert system with default rules.
     pub fn new() -> Self {
+        Self::with_rules(Self::default_rules())
+    }
+
+    /// Create an expert system with exactly `rules`, evaluated by
+    /// priority and then in the given order.
+    pub fn with_rules(mut rules: Vec<Rule>) -> Self {
+        rules.sort_by_key(Rule::order);
         Self {
-            rules: Self::default_rules(),
+            rules,
+            plugin_rules: Vec::new(),
+            policies: Vec::new(),
+            user_policy: None,
         }
     }
 
-    /// Evaluate a query against all rules.
-    pub fn evaluate(&self, query: &Query) -> RuleEvaluation {
-        for rule in &self.rules {
-            if (rule.predicate)(query) {
-                return RuleEvaluation {
-                    allowed: false,
-                    reason: Some(format!("Rule {} triggered", rule.id)),
-                    rule_id: Some(rule.id.clone()),
-                };
+    /// Create an expert system from configured rules. Rules that do not
+    /// compile are reported by config validation and left out here.
+    pub fn from_config(config: &ExpertConfig) -> Self {
+        let mut rules = if config.builtin_rules {
+            Self::default_rules()
+        } else {
+            Vec::new()
+        };
+        rules.extend(config.rules.iter().filter_map(|spec| spec.compile().ok()));
+        let mut expert = Self::with_rules(rules);
+        expert.policies = config
+            .policies
+            .iter()
+            .filter_map(|spec| spec.compile().ok())
+            .collect();
+        expert
+    }
+
+    /// Add a policy profile. A project bound to several policies gets the
+    /// first one added.
+    pub fn add_policy(&mut self, policy: Policy) {
+        self.policies.push(policy);
+    }
+
+    /// The policy profiles, in the order they were added.
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    /// The policy governing queries in `project`, if any: the user
+    /// policy when one is set, otherwise the one bound to the project.
+    pub fn policy(&self, project: Option<&str>) -> Option<&Policy> {
+        if let Some(name) = &self.user_policy {
+            return self.policies.iter().find(|policy| &policy.name == name);
+        }
+        let project = project?;
+        self.policies.iter().find(|policy| policy.governs(project))
+    }
+
+    /// USER POLICY: Make the policy called `name` govern every query,
+    /// whatever its project (e.g. a kids mode for a child's profile), or
+    /// go back to per-project policies with `None`. Fails for a policy
+    /// that was never added.
+    pub fn set_user_policy(&mut self, name: Option<&str>) -> Result<(), String> {
+        if let Some(name) = name {
+            if !self.policies.iter().any(|policy| policy.name == name) {
+                return Err(format!("unknown policy '{}'", name));
             }
         }
-        RuleEvaluation {
+        self.user_policy = name.map(str::to_string);
+        Ok(())
+    }
+
+    /// Name of the policy set with `set_user_policy`, if any.
+    pub fn user_policy(&self) -> Option<&str> {
+        self.user_policy.as_deref()
+    }
+
+    /// Add a declarative rule, checked after the existing ones of the
+    /// same priority.
+    pub fn push_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+        self.rules.sort_by_key(Rule::order);
+    }
+
+    /// The declarative rules, in evaluation order.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Add the rule pack (each rule's id, action, severity and reason, and
+    /// the plugin rule ids) to `fingerprint`.
+    pub fn fingerprint(&self, fingerprint: &mut StateFingerprint) {
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .map(|rule| (&rule.id, rule.action, rule.severity, rule.priority, rule.reason()))
+            .collect();
+        let plugins: Vec<&str> = self.plugin_rules.iter().map(|rule| rule.id()).collect();
+        let policies: Vec<_> = self
+            .policies
+            .iter()
+            .map(|policy| {
+                let rules: Vec<_> = policy
+                    .rules
+                    .iter()
+                    .map(|rule| (&rule.id, rule.action, rule.priority))
+                    .collect();
+                (&policy.name, &policy.projects, policy.inherit_rules, rules, &policy.routes)
+            })
+            .collect();
+        fingerprint
+            .part("rules", &rules)
+            .part("plugin_rules", &plugins)
+            .part("policies", &policies)
+            .part("user_policy", &self.user_policy);
+    }
+
+    /// Add a plugin rule, checked after the built-in rules.
+    pub fn add_rule(&mut self, rule: Arc<dyn QueryRule>) {
+        self.plugin_rules.push(rule);
+    }
+
+    /// Evaluate a query against all rules, in its own project. See
+    /// [`Self::evaluate_in`].
+    pub fn evaluate(&self, query: &Query) -> RuleEvaluation {
+        self.evaluate_in(query, query.project_context.as_deref())
+    }
+
+    /// Evaluate a query in `project` against all rules, in priority order:
+    /// the rules of the project's policy (plus the global ones when it
+    /// inherits them), or the global rules. `Warn` rules that match are
+    /// listed in `warnings`, `Redact` rules mask their spans (later rules
+    /// see the masked text), and the first matching `Block` rule rejects
+    /// the query unless an `Allow` rule matched before it and the block is
+    /// not `Critical`.
+    pub fn evaluate_in(&self, query: &Query, project: Option<&str>) -> RuleEvaluation {
+        let mut evaluation = RuleEvaluation {
             allowed: true,
             reason: None,
             rule_id: None,
+            warnings: Vec::new(),
+            redacted_text: None,
+            redactions: Vec::new(),
+            overrides: Vec::new(),
+        };
+        let mut text = query.text.clone();
+        let mut allowed_by: Option<&str> = None;
+        let mut rules: Vec<&Rule> = match self.policy(project) {
+            Some(policy) if policy.inherit_rules => {
+                self.rules.iter().chain(&policy.rules).collect()
+            }
+            Some(policy) => policy.rules.iter().collect(),
+            None => self.rules.iter().collect(),
+        };
+        // Stable: global rules go first among rules of equal rank
+        rules.sort_by_key(|rule| rule.order());
+        for rule in rules {
+            if !rule.matches_in(&text, project) {
+                continue;
+            }
+            match rule.action {
+                RuleAction::Block => {
+                    let overridable = rule.severity < RuleSeverity::Critical;
+                    if let Some(allow) = allowed_by.filter(|_| overridable) {
+                        evaluation.overrides.push(format!("{} by {}", rule.id, allow));
+                        continue;
+                    }
+                    evaluation.allowed = false;
+                    evaluation.reason = Some(rule.reason());
+                    evaluation.rule_id = Some(rule.id.clone());
+                    return evaluation;
+                }
+                RuleAction::Allow => {
+                    allowed_by.get_or_insert(&rule.id);
+                }
+                RuleAction::Warn => evaluation
+                    .warnings
+                    .push(format!("{}: {}", rule.id, rule.reason())),
+                RuleAction::Redact => {
+                    let (masked, spans) = rule.redact(&text);
+                    if spans > 0 {
+                        text = masked;
+                        evaluation.redactions.push(Redaction {
+                            rule_id: rule.id.clone(),
+                            spans,
+                            reason: rule.reason(),
+                        });
+                    }
+                }
+            }
+        }
+        let redacted = Query {
+            text,
+            ..query.clone()
+        };
+        for rule in &self.plugin_rules {
+            if let Some(reason) = rule.check(&redacted) {
+                evaluation.allowed = false;
+                evaluation.reason = Some(reason);
+                evaluation.rule_id = Some(rule.id().to_string());
+                return evaluation;
+            }
+        }
+        if !evaluation.redactions.is_empty() {
+            evaluation.redacted_text = Some(redacted.text);
         }
+        evaluation
     }
 
     /// DEFAULT POLICIES:
     /// - PRIVACY_001: Block potential API keys.
+    /// - PRIVACY_003: Mask email addresses.
     /// - SAFETY_001: Block requests for harmful instructions (hacking, etc.).
     fn default_rules() -> Vec<Rule> {
-        vec![
-            Rule {
-                id: "PRIVACY_001".to_string(),
-                predicate: |query| {
-                    let text = query.text.to_lowercase();
-                    text.contains("api_key") || text.contains("password")
-                },
-            },
-            Rule {
-                id: "SAFETY_001".to_string(),
-                predicate: |query| {
-                    let text = query.text.to_lowercase();
-                    text.contains("hack") || text.contains("malware")
-                },
-            },
+        let emails = RuleBuilder::new("PRIVACY_003")
+            .regex(r"[\w.+-]+@[\w-]+(\.[\w-]+)+")
+            .action(RuleAction::Redact)
+            .severity(RuleSeverity::High)
+            .mask("[EMAIL]")
+            .reason("Email addresses stay on the device")
+            .build();
+        let blocking = [
+            (
+                "PRIVACY_001",
+                ["api_key", "password"],
+                RuleSeverity::Critical,
+            ),
+            ("SAFETY_001", ["hack", "malware"], RuleSeverity::High),
         ]
+        .into_iter()
+        .filter_map(|(id, keywords, severity)| {
+            RuleBuilder::new(id)
+                .keywords(keywords)
+                .severity(severity)
+                .build()
+                .ok()
+        });
+        let mut rules: Vec<Rule> = blocking.collect();
+        if let Ok(emails) = emails {
+            rules.insert(1, emails);
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_load_from_toml_and_json() {
+        let toml_rules = r#"
+            [[rules]]
+            id = "PRIVACY_002"
+            keywords = ["IBAN"]
+            regex = '\b[A-Z]{2}\d{2}[A-Z0-9]{11,30}\b'
+            severity = "High"
+            reason = "Bank details stay on the device"
+
+            [[rules]]
+            id = "LENGTH_001"
+            longer_than = 20
+            action = "Warn"
+        "#;
+        let Ok(file) = toml::from_str::<RulesFile>(toml_rules) else {
+            panic!("the rules file should parse");
+        };
+        let rules: Result<Vec<Rule>, String> = file.rules.iter().map(RuleSpec::compile).collect();
+        let Ok(rules) = rules else {
+            panic!("the rules should compile");
+        };
+        let expert = ExpertSystem::with_rules(rules);
+
+        // Both conditions must hold
+        let blocked = expert.evaluate(&Query::new("my iban is GB82WEST12345698765432"));
+        assert!(!blocked.allowed);
+        assert_eq!(blocked.rule_id.as_deref(), Some("PRIVACY_002"));
+        assert_eq!(
+            blocked.reason.as_deref(),
+            Some("Bank details stay on the device")
+        );
+        assert!(expert.evaluate(&Query::new("what is an IBAN?")).allowed);
+
+        let warned = expert.evaluate(&Query::new("please summarise this long article"));
+        assert!(warned.allowed);
+        assert_eq!(
+            warned.warnings,
+            vec!["LENGTH_001: Rule LENGTH_001 triggered"]
+        );
+
+        let json = r#"{"rules": [{"id": "KIDS_001", "keywords": ["casino"]}]}"#;
+        let Ok(file) = serde_json::from_str::<RulesFile>(json) else {
+            panic!("JSON rules should parse");
+        };
+        assert_eq!(file.rules[0].action, RuleAction::Block);
+        assert_eq!(file.rules[0].severity, RuleSeverity::Medium);
+    }
+
+    #[test]
+    fn test_builder_rejects_incomplete_rules() {
+        assert!(RuleBuilder::new("EMPTY_001").build().is_err());
+        assert!(RuleBuilder::new("").keywords(["x"]).build().is_err());
+        let Err(message) = RuleBuilder::new("BAD_001").regex("(unclosed").build() else {
+            panic!("an invalid regex should be rejected");
+        };
+        assert!(message.contains("BAD_001"));
+
+        let expert = ExpertSystem::from_config(&ExpertConfig {
+            builtin_rules: false,
+            rules: vec![RuleSpec {
+                id: "SHORT_001".to_string(),
+                shorter_than: Some(3),
+                ..RuleSpec::default()
+            }],
+            policies: Vec::new(),
+            limits: QuotaConfig::default(),
+        });
+        assert_eq!(expert.rules().len(), 1);
+        assert!(!expert.evaluate(&Query::new("hi")).allowed);
+        assert!(
+            expert
+                .evaluate(&Query::new("tell me about the password reset flow"))
+                .allowed
+        );
+        assert!(
+            !ExpertSystem::new()
+                .evaluate(&Query::new("my Password is hunter2"))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_redact_rules_mask_spans_and_let_queries_through() {
+        let expert = ExpertSystem::new();
+        let evaluation =
+            expert.evaluate(&Query::new("mail ana@example.com and bo@mail.example.org"));
+        assert!(evaluation.allowed);
+        assert_eq!(
+            evaluation.redacted_text.as_deref(),
+            Some("mail [EMAIL] and [EMAIL]")
+        );
+        assert_eq!(evaluation.redactions.len(), 1);
+        assert_eq!(evaluation.redactions[0].rule_id, "PRIVACY_003");
+        assert_eq!(evaluation.redactions[0].spans, 2);
+        assert!(expert.evaluate(&Query::new("no address here")).redacted_text.is_none());
+
+        // Keyword spans are masked case-insensitively; later rules see the mask
+        let Ok(codename) = RuleBuilder::new("PROJECT_001")
+            .keywords(["bluebird"])
+            .action(RuleAction::Redact)
+            .mask("***")
+            .build()
+        else {
+            panic!("a keyword redact rule should build");
+        };
+        let Ok(leak) = RuleBuilder::new("PROJECT_002").keywords(["bluebird"]).build() else {
+            panic!("a block rule should build");
+        };
+        let expert = ExpertSystem::with_rules(vec![codename, leak]);
+        let evaluation = expert.evaluate(&Query::new("status of Bluebird?"));
+        assert!(evaluation.allowed);
+        assert_eq!(evaluation.redacted_text.as_deref(), Some("status of ***?"));
+
+        assert!(RuleBuilder::new("REDACT_001")
+            .longer_than(10)
+            .action(RuleAction::Redact)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_allow_rules_override_lower_priority_blocks() {
+        let toml_rules = r#"
+            [[rules]]
+            id = "ALLOW_SECURITY_RESEARCH"
+            keywords = ["malware", "exploit"]
+            projects = ["security-lab"]
+            action = "Allow"
+
+            [[rules]]
+            id = "SAFETY_002"
+            keywords = ["ransomware"]
+            priority = 10
+        "#;
+        let Ok(file) = toml::from_str::<RulesFile>(toml_rules) else {
+            panic!("the rules file should parse");
+        };
+        let expert = ExpertSystem::from_config(&ExpertConfig {
+            rules: file.rules,
+            ..ExpertConfig::default()
+        });
+        // Higher priority first, then Allow ahead of built-in blocks
+        let order: Vec<&str> = expert.rules().iter().map(Rule::id).collect();
+        assert_eq!(&order[..2], ["SAFETY_002", "ALLOW_SECURITY_RESEARCH"]);
+
+        let query = Query::new("how does this malware persist across reboots?");
+        let global = expert.evaluate(&query);
+        assert!(!global.allowed);
+        assert_eq!(global.rule_id.as_deref(), Some("SAFETY_001"));
+        let lab = expert.evaluate_in(&query, Some("security-lab"));
+        assert!(lab.allowed);
+        assert_eq!(
+            lab.overrides,
+            vec!["SAFETY_001 by ALLOW_SECURITY_RESEARCH".to_string()]
+        );
+        // The allow rule runs after the priority-10 block, so it cannot lift it
+        let ransomware = Query::new("analyse this ransomware sample");
+        let lab = expert.evaluate_in(&ransomware, Some("security-lab"));
+        assert_eq!(lab.rule_id.as_deref(), Some("SAFETY_002"));
+        // Critical blocks cannot be overridden
+        let password = Query::new("exploit uses the password hunter2");
+        let lab = expert.evaluate_in(&password, Some("security-lab"));
+        assert_eq!(lab.rule_id.as_deref(), Some("PRIVACY_001"));
+    }
+
+    #[test]
+    fn test_policies_bind_rule_sets_to_projects() {
+        let kids = PolicySpec {
+            name: "kids-mode".to_string(),
+            projects: vec!["homework".to_string()],
+            rules: vec![RuleSpec {
+                id: "KIDS_001".to_string(),
+                keywords: vec!["casino".to_string()],
+                ..RuleSpec::default()
+            }],
+            routes: vec![RoutingDecision::Local],
+            ..PolicySpec::default()
+        };
+        let developer = PolicySpec {
+            name: "developer".to_string(),
+            projects: vec!["work".to_string()],
+            inherit_rules: false,
+            ..PolicySpec::default()
+        };
+        let expert = ExpertSystem::from_config(&ExpertConfig {
+            policies: vec![kids, developer],
+            ..ExpertConfig::default()
+        });
+        let Some(policy) = expert.policy(Some("homework")) else {
+            panic!("homework should be governed by kids-mode");
+        };
+        assert_eq!(policy.name(), "kids-mode");
+        assert!(!policy.allows(RoutingDecision::Remote));
+        assert!(expert.policy(None).is_none());
+
+        // A user policy governs every project, until it is unset
+        let mut child = expert.clone();
+        assert!(child.set_user_policy(Some("teen-mode")).is_err());
+        assert_eq!(child.set_user_policy(Some("kids-mode")), Ok(()));
+        assert_eq!(child.policy(None).map(Policy::name), Some("kids-mode"));
+        assert_eq!(child.policy(Some("work")).map(Policy::name), Some("kids-mode"));
+        assert_eq!(child.set_user_policy(None), Ok(()));
+        assert_eq!(child.policy(Some("work")).map(Policy::name), Some("developer"));
+
+        let casino = Query::new("best casino games?");
+        assert!(expert.evaluate_in(&casino, None).allowed);
+        let homework = expert.evaluate_in(&casino, Some("homework"));
+        assert_eq!(homework.rule_id.as_deref(), Some("KIDS_001"));
+        // kids-mode inherits the global rules; developer does not
+        let malware = Query::new("write me some malware");
+        assert!(!expert.evaluate_in(&malware, Some("homework")).allowed);
+        assert!(expert.evaluate_in(&malware, Some("work")).allowed);
+
+        let no_local = PolicySpec {
+            name: "cloud".to_string(),
+            routes: vec![RoutingDecision::Remote],
+            ..PolicySpec::default()
+        };
+        assert!(no_local.compile().is_err());
     }
 }
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Fallback When a Route Fails
+//!
+//! A Remote call can fail for reasons the user cannot fix and should not
+//! have to see: the phone lost its connection, the API timed out, or the
+//! provider is rate limiting (HTTP 429). With a [`FallbackConfig`] the
+//! orchestrator retries such failures down a chain of routes, `Local`
+//! by default, and marks the answer with a [`Degradation`] in its
+//! metadata, so the UI can say it may be of lower quality.
+//!
+//! Backends report errors as text; [`FailureKind::classify`] sorts them
+//! by their wording. Failures of a kind not listed in `retry_on` still
+//! surface as errors.
+//!
+//! ```toml
+//! [fallback]
+//! retry_on = ["Offline", "Timeout", "RateLimited"]
+//! chain = ["Local"]
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Why a backend call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureKind {
+    /// No connection to the backend
+    Offline,
+    /// The backend did not answer in time
+    Timeout,
+    /// The provider refused because of request rate or quota (HTTP 429)
+    RateLimited,
+    /// Anything else
+    Other,
+}
+
+impl FailureKind {
+    /// Sort a backend error message by kind
+    pub fn classify(error: &str) -> Self {
+        let error = error.to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| error.contains(needle));
+        if mentions(&["429", "rate limit", "too many requests", "quota exceeded"]) {
+            Self::RateLimited
+        } else if mentions(&["timeout", "timed out", "deadline"]) {
+            Self::Timeout
+        } else if mentions(&[
+            "offline",
+            "connection refused",
+            "unreachable",
+            "no route to host",
+            "dns",
+        ]) {
+            Self::Offline
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Offline => "offline",
+            Self::Timeout => "timed out",
+            Self::RateLimited => "rate limited",
+            Self::Other => "failed",
+        })
+    }
+}
+
+/// Which failures are retried, and where
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    /// Retry failed routes at all
+    pub enabled: bool,
+    /// Failure kinds that are retried; others surface as errors
+    pub retry_on: Vec<FailureKind>,
+    /// Routes tried in order after a failure; routes the profile does not
+    /// allow are skipped
+    pub chain: Vec<RoutingDecision>,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retry_on: vec![
+                FailureKind::Offline,
+                FailureKind::Timeout,
+                FailureKind::RateLimited,
+            ],
+            chain: vec![RoutingDecision::Local],
+        }
+    }
+}
+
+impl FallbackConfig {
+    /// Whether a failure of `kind` is retried down the chain
+    pub fn retries(&self, kind: FailureKind) -> bool {
+        self.enabled && self.retry_on.contains(&kind)
+    }
+}
+
+/// Why a response came from a fallback instead of the chosen route
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Degradation {
+    /// Route that was chosen and failed
+    pub requested: RoutingDecision,
+    /// How it failed
+    pub failure: FailureKind,
+    /// The backend's error message
+    pub error: String,
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} ({})", self.requested, self.failure, self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_are_classified_and_filtered() {
+        assert_eq!(
+            FailureKind::classify("HTTP 429 Too Many Requests"),
+            FailureKind::RateLimited
+        );
+        assert_eq!(
+            FailureKind::classify("operation timed out after 30s"),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            FailureKind::classify("Connection refused (os error 111)"),
+            FailureKind::Offline
+        );
+        assert_eq!(FailureKind::classify("invalid API key"), FailureKind::Other);
+
+        let config = FallbackConfig::default();
+        assert!(config.retries(FailureKind::RateLimited));
+        assert!(!config.retries(FailureKind::Other));
+        let disabled = FallbackConfig {
+            enabled: false,
+            ..FallbackConfig::default()
+        };
+        assert!(!disabled.retries(FailureKind::Offline));
+    }
+}
@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Router Feature Extraction
+//!
+//! [`FeatureExtractor`] turns a query (plus the current ambient state and
+//! the user's personalization signals) into the fixed-width vector the
+//! routing MLP consumes. The layout is versioned by
+//! [`FEATURE_SCHEMA_VERSION`]: any change to what a slot means must bump
+//! it, because a model trained on one layout silently misroutes on
+//! another.
+//!
+//! Trained models are saved as a [`RouterModel`], which records the schema
+//! (and text embedder) they were trained under; loading one built for a
+//! different schema fails with an explanation instead of producing garbage
+//! routes. The model also records the custom route targets its extra
+//! output classes stand for.
+//!
+//! Layout (version 2):
+//!
+//! | Slots | Meaning |
+//! |-------|---------|
+//! | `0..PERSONAL_FEATURE_OFFSET` | query embedding, zero without an [`Embedder`] |
+//! | `PERSONAL_FEATURE_OFFSET..AMBIENT_FEATURE_OFFSET` | expertise and verbosity one-hots, zero when inactive |
+//! | `AMBIENT_FEATURE_OFFSET..FEATURE_DIM` | ambient state one-hot |
+
+#![forbid(unsafe_code)]
+
+use crate::ambient::AmbientState;
+use crate::embedding::{fit_dimension, Embedder};
+use crate::mlp::MLP;
+use crate::personalization::{Expertise, UserSignals, Verbosity};
+use crate::training::BUILTIN_ROUTE_CLASSES;
+use crate::types::Query;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Version of the feature layout produced by [`FeatureExtractor`]
+pub const FEATURE_SCHEMA_VERSION: u32 = 2;
+
+/// Width of the router feature vector
+pub const FEATURE_DIM: usize = 384;
+
+/// Index of the first ambient-state slot in the feature vector
+pub const AMBIENT_FEATURE_OFFSET: usize = FEATURE_DIM - AmbientState::COUNT;
+
+/// Index of the first personalization slot in the feature vector
+pub const PERSONAL_FEATURE_OFFSET: usize =
+    AMBIENT_FEATURE_OFFSET - Expertise::COUNT - Verbosity::COUNT;
+
+/// Builds router feature vectors
+#[derive(Debug, Clone, Default)]
+pub struct FeatureExtractor {
+    ambient: AmbientState,
+    embedder: Option<Arc<dyn Embedder>>,
+    personal: Option<Vec<f32>>,
+}
+
+impl FeatureExtractor {
+    /// Create an extractor with an unknown ambient state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schema version of the vectors this extractor produces
+    pub fn schema_version(&self) -> u32 {
+        FEATURE_SCHEMA_VERSION
+    }
+
+    /// Width of the vectors this extractor produces
+    pub fn dim(&self) -> usize {
+        FEATURE_DIM
+    }
+
+    /// Update the ambient state encoded in the tail of the vector
+    pub fn set_ambient(&mut self, ambient: AmbientState) {
+        self.ambient = ambient;
+    }
+
+    /// Ambient state currently encoded
+    pub fn ambient(&self) -> AmbientState {
+        self.ambient
+    }
+
+    /// Update the personalization signals encoded before the ambient
+    /// state; `None` (or inactive signals) leaves their slots zero
+    pub fn set_user_signals(&mut self, signals: Option<&UserSignals>) {
+        self.personal = signals
+            .filter(|signals| signals.active)
+            .map(UserSignals::to_features);
+    }
+
+    /// Set the text embedder filling the leading slots; `None` leaves
+    /// them zero. Models trained with another embedder must be retrained.
+    pub fn set_embedder(&mut self, embedder: Option<Arc<dyn Embedder>>) {
+        self.embedder = embedder;
+    }
+
+    /// Identifier of the text embedder in use, if any
+    pub fn embedder_id(&self) -> Option<String> {
+        self.embedder.as_ref().map(|e| e.id())
+    }
+
+    /// Feature vector for `query`
+    pub fn extract(&self, query: &Query) -> Vec<f32> {
+        let mut features = vec![0.0; FEATURE_DIM];
+
+        // A failed embedding leaves the query slots zero; routing still
+        // works on the remaining features.
+        if let Some(Ok(embedding)) = self.embedder.as_ref().map(|e| e.embed(&query.text)) {
+            features[..PERSONAL_FEATURE_OFFSET]
+                .copy_from_slice(&fit_dimension(embedding, PERSONAL_FEATURE_OFFSET));
+        }
+
+        if let Some(personal) = &self.personal {
+            features[PERSONAL_FEATURE_OFFSET..AMBIENT_FEATURE_OFFSET].copy_from_slice(personal);
+        }
+
+        // Ambient one-hot occupies the tail of the vector.
+        features[AMBIENT_FEATURE_OFFSET..].copy_from_slice(&self.ambient.to_features());
+        features
+    }
+
+    /// Human-readable name of the feature at `index`
+    pub fn feature_name(index: usize) -> String {
+        if let Some(state) = index
+            .checked_sub(AMBIENT_FEATURE_OFFSET)
+            .and_then(|slot| AmbientState::ALL.get(slot))
+        {
+            return format!("ambient:{:?}", state);
+        }
+        let Some(slot) = index.checked_sub(PERSONAL_FEATURE_OFFSET) else {
+            return format!("feature[{}]", index);
+        };
+        match Expertise::ALL.get(slot) {
+            Some(level) => format!("expertise:{:?}", level),
+            None => format!("verbosity:{:?}", Verbosity::ALL[slot - Expertise::COUNT]),
+        }
+    }
+}
+
+/// A routing MLP tagged with the feature schema it was trained under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouterModel {
+    /// Feature schema version the model expects
+    pub feature_schema_version: u32,
+    /// Embedder the model was trained with (`None`: not recorded)
+    #[serde(default)]
+    pub embedder: Option<String>,
+    /// Custom route targets behind the classes after the built-in
+    /// routes, in class order (empty: none or not recorded)
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// The trained network
+    pub mlp: MLP,
+}
+
+impl RouterModel {
+    /// Tag a model trained on the current feature schema
+    pub fn new(mlp: MLP) -> Self {
+        Self {
+            feature_schema_version: FEATURE_SCHEMA_VERSION,
+            embedder: None,
+            targets: Vec::new(),
+            mlp,
+        }
+    }
+
+    /// Tag a model trained on features from `extractor`
+    pub fn trained_with(mlp: MLP, extractor: &FeatureExtractor) -> Self {
+        Self {
+            embedder: extractor.embedder_id(),
+            ..Self::new(mlp)
+        }
+    }
+
+    /// Record the custom targets the model was trained to route to
+    pub fn with_targets(mut self, targets: Vec<String>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Check that the model can consume vectors from `extractor` and
+    /// route to exactly the custom `targets` registered
+    pub fn check_compatible(
+        &self,
+        extractor: &FeatureExtractor,
+        targets: &[String],
+    ) -> Result<(), String> {
+        if let Some(embedder) = &self.embedder {
+            let current = extractor.embedder_id();
+            if current.as_deref() != Some(embedder.as_str()) {
+                return Err(format!(
+                    "router model was trained with embedder '{}' but the router uses {}",
+                    embedder,
+                    current.map_or("none".to_string(), |id| format!("'{}'", id))
+                ));
+            }
+        }
+        if self.feature_schema_version != FEATURE_SCHEMA_VERSION {
+            return Err(format!(
+                "router model was trained on feature schema v{} but this build extracts v{}; \
+                 retrain it on current features",
+                self.feature_schema_version, FEATURE_SCHEMA_VERSION
+            ));
+        }
+        if !self.targets.is_empty() && self.targets != targets {
+            return Err(format!(
+                "router model was trained for route targets {:?} but {:?} are registered",
+                self.targets, targets
+            ));
+        }
+        let classes = BUILTIN_ROUTE_CLASSES + targets.len();
+        if self.mlp.input_size() != FEATURE_DIM || self.mlp.output_size() != classes {
+            return Err(format!(
+                "router model must map {} features to {} classes, got {} -> {}",
+                FEATURE_DIM,
+                classes,
+                self.mlp.input_size(),
+                self.mlp.output_size()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The network, if compatible with `extractor` and `targets`
+    pub fn into_mlp(self, extractor: &FeatureExtractor, targets: &[String]) -> Result<MLP, String> {
+        self.check_compatible(extractor, targets)?;
+        Ok(self.mlp)
+    }
+}
+
+impl From<MLP> for RouterModel {
+    fn from(mlp: MLP) -> Self {
+        Self::new(mlp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::HashedBagOfWords;
+
+    #[test]
+    fn test_extract_encodes_ambient_tail() {
+        let mut extractor = FeatureExtractor::new();
+        extractor.set_ambient(AmbientState::Walking);
+        let features = extractor.extract(&Query::new("hello"));
+
+        assert_eq!(features.len(), extractor.dim());
+        assert_eq!(
+            features[AMBIENT_FEATURE_OFFSET + AmbientState::Walking.index()],
+            1.0
+        );
+        assert_eq!(features.iter().sum::<f32>(), 1.0);
+        assert_eq!(
+            FeatureExtractor::feature_name(AMBIENT_FEATURE_OFFSET + 5),
+            "ambient:Walking"
+        );
+        assert_eq!(FeatureExtractor::feature_name(3), "feature[3]");
+    }
+
+    #[test]
+    fn test_active_user_signals_fill_personal_slots() {
+        let mut signals = UserSignals {
+            active: false,
+            expertise: Expertise::Expert,
+            verbosity: Verbosity::Brief,
+            topics: Vec::new(),
+            observed_turns: 2,
+            evidence: Vec::new(),
+        };
+        let mut extractor = FeatureExtractor::new();
+        extractor.set_user_signals(Some(&signals));
+        let query = Query::new("hello");
+        assert_eq!(extractor.extract(&query).iter().sum::<f32>(), 1.0);
+
+        signals.active = true;
+        extractor.set_user_signals(Some(&signals));
+        let features = extractor.extract(&query);
+        assert_eq!(
+            features[PERSONAL_FEATURE_OFFSET..AMBIENT_FEATURE_OFFSET],
+            [0.0, 0.0, 1.0, 1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            FeatureExtractor::feature_name(PERSONAL_FEATURE_OFFSET + 2),
+            "expertise:Expert"
+        );
+        assert_eq!(
+            FeatureExtractor::feature_name(PERSONAL_FEATURE_OFFSET + 3),
+            "verbosity:Brief"
+        );
+    }
+
+    #[test]
+    fn test_schema_mismatch_is_rejected() {
+        let extractor = FeatureExtractor::new();
+        let model = RouterModel::new(MLP::new_with_seed(FEATURE_DIM, vec![8], 3, 1));
+        assert!(model.check_compatible(&extractor, &[]).is_ok());
+
+        let stale = RouterModel {
+            feature_schema_version: FEATURE_SCHEMA_VERSION + 1,
+            ..model
+        };
+        let Err(message) = stale.into_mlp(&extractor, &[]) else {
+            panic!("a model from another schema should be rejected");
+        };
+        assert!(message.contains("feature schema"));
+
+        let wrong_shape = RouterModel::new(MLP::new_with_seed(10, vec![8], 3, 1));
+        assert!(wrong_shape.check_compatible(&extractor, &[]).is_err());
+    }
+
+    #[test]
+    fn test_embedder_fills_query_slots_and_is_checked() {
+        let mut extractor = FeatureExtractor::new();
+        extractor.set_embedder(Some(Arc::new(HashedBagOfWords::new(64))));
+        let features = extractor.extract(&Query::new("hello world"));
+        assert!(features[..64].iter().any(|&v| v != 0.0));
+        assert!(features[64..PERSONAL_FEATURE_OFFSET]
+            .iter()
+            .all(|&v| v == 0.0));
+
+        let mlp = MLP::new_with_seed(FEATURE_DIM, vec![8], 3, 1);
+        let model = RouterModel::trained_with(mlp, &extractor);
+        assert!(model.check_compatible(&extractor, &[]).is_ok());
+        assert!(model.check_compatible(&FeatureExtractor::new(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_custom_targets_must_match() {
+        let extractor = FeatureExtractor::new();
+        let targets = vec!["watch".to_string()];
+        let model = RouterModel::new(MLP::new_with_seed(FEATURE_DIM, vec![8], 4, 1))
+            .with_targets(targets.clone());
+        assert!(model.check_compatible(&extractor, &targets).is_ok());
+        assert!(model.check_compatible(&extractor, &[]).is_err());
+
+        let renamed = vec!["gateway".to_string()];
+        let Err(message) = model.check_compatible(&extractor, &renamed) else {
+            panic!("a model trained for other targets should be rejected");
+        };
+        assert!(message.contains("route targets"));
+
+        let untagged = RouterModel::new(MLP::new_with_seed(FEATURE_DIM, vec![8], 3, 1));
+        assert!(untagged.check_compatible(&extractor, &targets).is_err());
+    }
+}
@@ -0,0 +1,566 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Kotlin and Swift Bindings
+//!
+//! With the `ffi` feature the orchestrator is exported through UniFFI, so
+//! Android and iOS apps call it directly instead of through hand-written
+//! JNI or C glue:
+//!
+//! - [`MobileOrchestrator`] wraps an [`Orchestrator`] behind a lock; every
+//!   method may be called from any thread.
+//! - [`FfiQuery`] and [`FfiResponse`] are the FFI shapes of `Query` and
+//!   `Response`; failures arrive as [`FfiError`].
+//! - `process` blocks, so call it off the UI thread, or use
+//!   `process_async`, which answers on a background thread through a
+//!   [`ResponseListener`]: text chunks as they are produced, then the
+//!   whole response or the error.
+//! - [`HostCallbacks`] is the foreign side of `host::HostDelegate`, for
+//!   push notifications (blocks, budget warnings, triggers, ...).
+//! - `on_lifecycle` takes the app's lifecycle callbacks as
+//!   [`FfiLifecycleEvent`]s (see `lifecycle`).
+//!
+//! Build the library as a `cdylib` (Android) or `staticlib` (iOS) and
+//! generate the bindings from it with `uniffi-bindgen generate --library`;
+//! see DEPLOYMENT.md.
+
+#![forbid(unsafe_code)]
+
+use crate::config::OrchestratorConfig;
+use crate::error::OrchestratorError;
+use crate::host::HostDelegate;
+use crate::lifecycle::LifecycleEvent;
+use crate::orchestrator::{Begun, Orchestrator};
+use crate::types::{Attachment, Query, Response, RoutingDecision};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// A file attached to a query
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct FfiAttachment {
+    /// File name, shown to the model
+    pub name: String,
+    /// File contents
+    pub content: String,
+}
+
+/// A user request
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct FfiQuery {
+    /// Query text as entered by the user
+    pub text: String,
+    /// Project the query belongs to, if any
+    pub project: Option<String>,
+    /// Scheduling priority, 1-10
+    pub priority: u8,
+    /// Files attached to the query
+    pub attachments: Vec<FfiAttachment>,
+}
+
+impl From<FfiQuery> for Query {
+    fn from(query: FfiQuery) -> Self {
+        let mut converted = Query::new(query.text);
+        converted.project_context = query.project;
+        converted.priority = query.priority;
+        converted.attachments = query
+            .attachments
+            .into_iter()
+            .map(|attachment| Attachment {
+                name: attachment.name,
+                content: attachment.content,
+            })
+            .collect();
+        converted
+    }
+}
+
+/// Where a query was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum FfiRoute {
+    /// On the device
+    Local,
+    /// By the remote model
+    Remote,
+    /// By both together
+    Hybrid,
+    /// Refused by a safety or resource rule
+    Blocked,
+    /// By a target the host registered
+    Custom {
+        /// Target id
+        id: u16,
+    },
+}
+
+impl From<RoutingDecision> for FfiRoute {
+    fn from(route: RoutingDecision) -> Self {
+        match route {
+            RoutingDecision::Local => Self::Local,
+            RoutingDecision::Remote => Self::Remote,
+            RoutingDecision::Hybrid => Self::Hybrid,
+            RoutingDecision::Blocked => Self::Blocked,
+            RoutingDecision::Custom(id) => Self::Custom { id },
+        }
+    }
+}
+
+/// App lifecycle signal; see `lifecycle` for the platform callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum FfiLifecycleEvent {
+    /// App became visible and interactive
+    Foreground,
+    /// App left the screen
+    Background,
+    /// OS is under memory pressure
+    LowMemory,
+}
+
+impl From<FfiLifecycleEvent> for LifecycleEvent {
+    fn from(event: FfiLifecycleEvent) -> Self {
+        match event {
+            FfiLifecycleEvent::Foreground => Self::Foreground,
+            FfiLifecycleEvent::Background => Self::Background,
+            FfiLifecycleEvent::LowMemory => Self::LowMemory,
+        }
+    }
+}
+
+/// An answer
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct FfiResponse {
+    /// Answer text
+    pub text: String,
+    /// Where it was answered
+    pub route: FfiRoute,
+    /// Router confidence, 0-1
+    pub confidence: f32,
+    /// Time taken (ms)
+    pub latency_ms: u64,
+    /// Model that answered
+    pub model: Option<String>,
+    /// Approximate token count
+    pub tokens: Option<u32>,
+    /// Whether it came from the response cache
+    pub cached: bool,
+    /// Turn id, for feedback and regeneration
+    pub turn_id: Option<u64>,
+    /// Estimated battery energy spent (J)
+    pub energy_joules: Option<f32>,
+    /// Why a blocked query was refused
+    pub block_reason: Option<String>,
+}
+
+impl From<Response> for FfiResponse {
+    fn from(response: Response) -> Self {
+        let block_reason = response
+            .metadata
+            .explanation
+            .as_ref()
+            .and_then(|explanation| explanation.block_reason.clone());
+        Self {
+            text: response.text,
+            route: response.route.into(),
+            confidence: response.confidence,
+            latency_ms: response.latency_ms,
+            model: response.metadata.model,
+            tokens: response.metadata.tokens,
+            cached: response.metadata.cached,
+            turn_id: response.metadata.turn_id,
+            energy_joules: response.metadata.energy_joules,
+            block_reason,
+        }
+    }
+}
+
+/// Why a call failed
+#[derive(Debug, Clone, PartialEq, uniffi::Error)]
+pub enum FfiError {
+    /// A safety or resource rule refused the query
+    Blocked {
+        /// Rule that refused it, if known
+        rule_id: Option<String>,
+        /// Explanation for the user
+        reason: String,
+    },
+    /// The query waits for the network; its answer arrives through
+    /// `HostCallbacks::on_queued_answer`
+    Deferred {
+        /// Queue id
+        queue_id: u64,
+    },
+    /// The orchestrator is shut down, offline, or lacks what the query
+    /// needs
+    Unavailable {
+        /// What is missing
+        message: String,
+    },
+    /// A backend or the storage failed
+    Failed {
+        /// What failed
+        message: String,
+    },
+    /// An argument, config or rule set was rejected, or an item is unknown
+    InvalidInput {
+        /// What is wrong
+        message: String,
+    },
+}
+
+impl From<OrchestratorError> for FfiError {
+    fn from(error: OrchestratorError) -> Self {
+        let message = error.to_string();
+        match error {
+            OrchestratorError::Blocked { rule_id, reason } => Self::Blocked { rule_id, reason },
+            OrchestratorError::Deferred { queue_id, .. } => Self::Deferred { queue_id },
+            OrchestratorError::ShutDown
+            | OrchestratorError::NetworkUnavailable { .. }
+            | OrchestratorError::RouteNotAllowed { .. }
+            | OrchestratorError::MissingCapabilities(_)
+            | OrchestratorError::NotConfigured(_) => Self::Unavailable { message },
+            OrchestratorError::BackendFailure(_) | OrchestratorError::PersistenceError(_) => {
+                Self::Failed { message }
+            }
+            OrchestratorError::InvalidInput(_) | OrchestratorError::NotFound(_) => {
+                Self::InvalidInput { message }
+            }
+        }
+    }
+}
+
+impl fmt::Display for FfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocked {
+                rule_id: Some(rule_id),
+                reason,
+            } => write!(f, "blocked by {}: {}", rule_id, reason),
+            Self::Blocked {
+                rule_id: None,
+                reason,
+            } => write!(f, "blocked: {}", reason),
+            Self::Deferred { queue_id } => {
+                write!(f, "queued as {} until the device is online", queue_id)
+            }
+            Self::Unavailable { message }
+            | Self::Failed { message }
+            | Self::InvalidInput { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FfiError {}
+
+/// Receives the answer to `MobileOrchestrator::process_async`
+#[uniffi::export(callback_interface)]
+pub trait ResponseListener: Send + Sync {
+    /// A piece of the answer; `is_final` marks the last one
+    fn on_chunk(&self, chunk: String, is_final: bool);
+    /// The whole answer, after the last chunk
+    fn on_complete(&self, response: FfiResponse);
+    /// The query failed; no further chunk or answer follows
+    fn on_error(&self, error: FfiError);
+}
+
+/// Push notifications for the app; see `host::HostDelegate`
+#[uniffi::export(callback_interface)]
+pub trait HostCallbacks: Send + Sync {
+    /// A piece of response text is ready; `is_final` marks the last chunk
+    fn on_response_chunk(&self, chunk: String, is_final: bool);
+    /// A query was blocked by a safety or policy rule
+    fn on_block(&self, rule_id: String, reason: String);
+    /// A resource budget was exceeded or is forecast to be
+    fn on_budget_warning(&self, budget: String, used: f64, limit: f64);
+    /// A detector (wake word, activity, ...) fired
+    fn on_trigger(&self, kind: String, confidence: f32);
+    /// A new model version was promoted
+    fn on_model_updated(&self, model: String, version: String);
+    /// A scheduled digest query was answered
+    fn on_digest(&self, digest_id: u64, query: String, text: String);
+    /// A query queued while offline was answered
+    fn on_queued_answer(&self, queue_id: u64, query: String, text: String);
+    /// A runtime setting changed
+    fn on_setting_changed(&self, key: String, value: String);
+}
+
+/// `HostDelegate` forwarding to the app's callbacks
+struct ForeignDelegate(Box<dyn HostCallbacks>);
+
+impl HostDelegate for ForeignDelegate {
+    fn on_response_chunk(&self, chunk: String, is_final: bool) {
+        self.0.on_response_chunk(chunk, is_final);
+    }
+
+    fn on_block(&self, rule_id: String, reason: String) {
+        self.0.on_block(rule_id, reason);
+    }
+
+    fn on_budget_warning(&self, budget: String, used: f64, limit: f64) {
+        self.0.on_budget_warning(budget, used, limit);
+    }
+
+    fn on_trigger(&self, kind: String, confidence: f32) {
+        self.0.on_trigger(kind, confidence);
+    }
+
+    fn on_model_updated(&self, model: String, version: String) {
+        self.0.on_model_updated(model, version);
+    }
+
+    fn on_digest(&self, digest_id: u64, query: String, text: String) {
+        self.0.on_digest(digest_id, query, text);
+    }
+
+    fn on_queued_answer(&self, queue_id: u64, query: String, text: String) {
+        self.0.on_queued_answer(queue_id, query, text);
+    }
+
+    fn on_setting_changed(&self, key: String, value: String) {
+        self.0.on_setting_changed(key, value);
+    }
+}
+
+/// The orchestrator, shared with the app
+#[derive(uniffi::Object)]
+pub struct MobileOrchestrator {
+    inner: Arc<Mutex<Orchestrator>>,
+}
+
+impl MobileOrchestrator {
+    fn wrap(orchestrator: Orchestrator) -> Arc<Self> {
+        Arc::new(Self {
+            inner: Arc::new(Mutex::new(orchestrator)),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Orchestrator> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[uniffi::export]
+impl MobileOrchestrator {
+    /// An orchestrator with the default configuration
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Self::wrap(Orchestrator::new())
+    }
+
+    /// An orchestrator configured from TOML text
+    #[uniffi::constructor]
+    pub fn from_config(toml: String) -> Result<Arc<Self>, FfiError> {
+        let config =
+            OrchestratorConfig::from_toml_str(&toml).map_err(|e| FfiError::InvalidInput {
+                message: e.to_string(),
+            })?;
+        Ok(Self::wrap(Orchestrator::with_config(config)))
+    }
+
+    /// Answer `query`; blocks until it is answered
+    pub fn process(&self, query: FfiQuery) -> Result<FfiResponse, FfiError> {
+        Ok(self.lock().process(query.into())?.into())
+    }
+
+    /// Answer `query` on a background thread, reporting to `listener`.
+    /// The orchestrator is locked only to route and to record the query,
+    /// not while backends generate, and pieces of the answer are
+    /// forwarded as the backend produces them; `on_complete` carries the
+    /// answer after post-processing.
+    pub fn process_async(self: Arc<Self>, query: FfiQuery, listener: Box<dyn ResponseListener>) {
+        let inner = Arc::clone(&self.inner);
+        let listener: Arc<dyn ResponseListener> = Arc::from(listener);
+        let notify = Arc::clone(&listener);
+        let spawned = thread::Builder::new()
+            .name("ffi-process".to_string())
+            .spawn(move || {
+                let lock = || inner.lock().unwrap_or_else(PoisonError::into_inner);
+                // Each piece is held until the next arrives, so the last
+                // one can be marked final
+                let mut held: Option<String> = None;
+                // Its own statement, so the lock is released before generating
+                let begun = lock().begin(query.into());
+                let result = begun.and_then(|begun| match begun {
+                    Begun::Answered(response) => Ok(*response),
+                    Begun::Pending(pending) => {
+                        let generated = pending.generate_streaming(&mut |chunk| {
+                            if let Some(previous) = held.replace(chunk.to_string()) {
+                                notify.on_chunk(previous, false);
+                            }
+                        });
+                        lock().complete(generated)
+                    }
+                });
+                match result {
+                    Ok(response) => {
+                        let last = held.take().unwrap_or_else(|| response.text.clone());
+                        notify.on_chunk(last, true);
+                        notify.on_complete(response.into());
+                    }
+                    Err(e) => notify.on_error(e.into()),
+                }
+            });
+        if let Err(e) = spawned {
+            listener.on_error(FfiError::Unavailable {
+                message: format!("cannot start a thread: {}", e),
+            });
+        }
+    }
+
+    /// Switch to `project`'s conversation context
+    pub fn switch_project(&self, project: String) {
+        self.lock().switch_project(&project);
+    }
+
+    /// Feed sensor readings packed in the `sensor_batch` format; returns
+    /// how many were kept
+    pub fn push_sensor_batch(&self, batch: Vec<u8>) -> Result<u32, FfiError> {
+        let kept = self.lock().push_sensor_batch(&batch)?;
+        Ok(kept as u32)
+    }
+
+    /// Report the app now in the foreground, for context-switch
+    /// detection and project auto-switching
+    pub fn notify_foreground_app(&self, app: String) {
+        self.lock().notify_foreground_app(&app);
+    }
+
+    /// Forward a platform lifecycle callback: pause background work,
+    /// resume it, or release memory
+    pub fn on_lifecycle(&self, event: FfiLifecycleEvent) -> Result<(), FfiError> {
+        self.lock().on_lifecycle(event.into())?;
+        Ok(())
+    }
+
+    /// Register the app's push-notification callbacks, replacing any
+    /// earlier ones
+    pub fn set_callbacks(&self, callbacks: Box<dyn HostCallbacks>) {
+        self.lock()
+            .set_host_delegate(Arc::new(ForeignDelegate(callbacks)));
+    }
+
+    /// Persist state and stop accepting queries; returns whether
+    /// teardown was clean
+    pub fn shutdown(&self) -> bool {
+        self.lock().shutdown().is_clean()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    struct Collect(Mutex<mpsc::Sender<String>>);
+
+    impl ResponseListener for Collect {
+        fn on_chunk(&self, chunk: String, is_final: bool) {
+            let _ = self
+                .0
+                .lock()
+                .map(|tx| tx.send(format!("chunk {} {}", is_final, chunk)));
+        }
+
+        fn on_complete(&self, response: FfiResponse) {
+            let _ = self
+                .0
+                .lock()
+                .map(|tx| tx.send(format!("done {:?}", response.route)));
+        }
+
+        fn on_error(&self, error: FfiError) {
+            let _ = self.0.lock().map(|tx| tx.send(format!("error {}", error)));
+        }
+    }
+
+    /// Local model decoding three words, noting whether the orchestrator
+    /// was unlocked while it did
+    #[derive(Clone, Default)]
+    struct Words {
+        orchestrator: Arc<std::sync::OnceLock<std::sync::Weak<Mutex<Orchestrator>>>>,
+        unlocked: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl crate::pool::Reusable for Words {
+        fn reset(&mut self) {}
+    }
+
+    impl crate::pool::LocalModel for Words {
+        fn generate(&mut self, query: &Query) -> Result<String, String> {
+            self.generate_streaming(query, &mut |_| {})
+        }
+
+        fn generate_streaming(
+            &mut self,
+            _query: &Query,
+            on_chunk: &mut dyn FnMut(&str),
+        ) -> Result<String, String> {
+            if let Some(inner) = self.orchestrator.get().and_then(std::sync::Weak::upgrade) {
+                let unlocked = inner.try_lock().is_ok();
+                self.unlocked.store(unlocked, std::sync::atomic::Ordering::SeqCst);
+            }
+            for word in ["one ", "two ", "three"] {
+                on_chunk(word);
+            }
+            Ok("one two three".to_string())
+        }
+    }
+
+    fn query(text: &str) -> FfiQuery {
+        FfiQuery {
+            text: text.to_string(),
+            project: None,
+            priority: 5,
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_process_and_process_async_answer() {
+        let orchestrator = MobileOrchestrator::new();
+        let Ok(response) = orchestrator.process(query("What is 2+2?")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, FfiRoute::Local);
+        assert_eq!(response.text, "Response to: What is 2+2?");
+
+        let (tx, rx) = mpsc::channel();
+        Arc::clone(&orchestrator)
+            .process_async(query("plan my weekend"), Box::new(Collect(Mutex::new(tx))));
+        let received: Vec<String> = rx.iter().take(2).collect();
+        assert_eq!(
+            received,
+            ["chunk true Response to: plan my weekend", "done Local"]
+        );
+
+        let words = Words::default();
+        assert!(words.orchestrator.set(Arc::downgrade(&orchestrator.inner)).is_ok());
+        let model = words.clone();
+        let loaded = orchestrator
+            .lock()
+            .set_local_model(move || Ok(Box::new(model.clone()) as _));
+        assert_eq!(loaded, Ok(1));
+        let (tx, rx) = mpsc::channel();
+        Arc::clone(&orchestrator).process_async(query("count"), Box::new(Collect(Mutex::new(tx))));
+        let received: Vec<String> = rx.iter().take(4).collect();
+        assert_eq!(
+            received,
+            [
+                "chunk false one ",
+                "chunk false two ",
+                "chunk true three",
+                "done Local"
+            ]
+        );
+        assert!(words.unlocked.load(std::sync::atomic::Ordering::SeqCst));
+
+        assert_eq!(orchestrator.on_lifecycle(FfiLifecycleEvent::Background), Ok(()));
+        assert_eq!(orchestrator.on_lifecycle(FfiLifecycleEvent::LowMemory), Ok(()));
+        assert_eq!(orchestrator.on_lifecycle(FfiLifecycleEvent::Foreground), Ok(()));
+
+        assert!(orchestrator.shutdown());
+        let (tx, rx) = mpsc::channel();
+        orchestrator.process_async(query("too late"), Box::new(Collect(Mutex::new(tx))));
+        let Ok(error) = rx.recv() else {
+            panic!("the listener should hear back");
+        };
+        assert!(error.starts_with("error "));
+    }
+}
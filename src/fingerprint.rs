@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MPL-2.0
+//! State Fingerprints
+//!
+//! A bug report or an A/B result is only useful next to the exact state
+//! that produced it. `Orchestrator::state_fingerprint` hashes the effective
+//! configuration, routing model, rule pack and retention settings into a
+//! short ID, recorded in the metadata of every response: two responses with
+//! the same fingerprint came from the same state.
+//!
+//! [`StateFingerprint`] hashes named parts as JSON with the crate's
+//! [`StableHasher`], so the ID does not depend on the platform, the
+//! process or the Rust version.
+//! Parts must serialize deterministically: structs, vectors and options
+//! do, `HashMap`s do not.
+
+#![forbid(unsafe_code)]
+
+use crate::hashing::StableHasher;
+use serde::Serialize;
+
+/// Accumulates named parts of a state into a short, stable ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateFingerprint {
+    hasher: StableHasher,
+}
+
+impl Default for StateFingerprint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateFingerprint {
+    /// An empty fingerprint
+    pub fn new() -> Self {
+        Self {
+            hasher: StableHasher::new(),
+        }
+    }
+
+    /// Add part `name`. A value that cannot be serialized is hashed as an
+    /// error marker, so it still changes the ID without panicking.
+    pub fn part(&mut self, name: &str, value: &impl Serialize) -> &mut Self {
+        let json = serde_json::to_string(value).unwrap_or_else(|e| format!("!{}", e));
+        self.hasher
+            .write_field(name.as_bytes())
+            .write_field(json.as_bytes());
+        self
+    }
+
+    /// The ID: 16 lowercase hex digits
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable_and_order_sensitive() {
+        let id = |parts: &[(&str, &str)]| {
+            let mut fingerprint = StateFingerprint::new();
+            for (name, value) in parts {
+                fingerprint.part(name, value);
+            }
+            fingerprint.finish()
+        };
+        // Pinned: the ID must not change between platforms or releases
+        assert_eq!(id(&[]), "cbf29ce484222325");
+        let base = id(&[("config", "a"), ("rules", "b")]);
+        assert_eq!(base.len(), 16);
+        assert_eq!(base, id(&[("config", "a"), ("rules", "b")]));
+        assert_ne!(base, id(&[("rules", "b"), ("config", "a")]));
+        assert_ne!(base, id(&[("config", "ab"), ("rules", "")]));
+        assert_ne!(base, id(&[("config", "a"), ("rules", "c")]));
+    }
+}
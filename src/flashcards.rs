@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Flashcard Export
+//!
+//! Turns question/answer turns into a study deck, entirely offline. Each
+//! answered turn becomes one card (query on the front, answer on the
+//! back); blocked turns and empty answers are skipped.
+//!
+//! [`Deck::to_csv`] writes Anki's text import format, with the file
+//! headers that tell Anki the separator, target deck and tags column, so
+//! the file imports with no manual mapping. Other flashcard apps read the
+//! same CSV (front, back, tags).
+
+#![forbid(unsafe_code)]
+
+use crate::types::{ConversationTurn, RoutingDecision};
+use serde::{Deserialize, Serialize};
+
+/// One question/answer card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flashcard {
+    /// Question side
+    pub front: String,
+    /// Answer side
+    pub back: String,
+    /// Tags (no spaces; Anki separates tags by whitespace)
+    pub tags: Vec<String>,
+}
+
+/// A named collection of cards
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Deck {
+    /// Deck name (`::` nests decks in Anki)
+    pub name: String,
+    /// Cards in conversation order
+    pub cards: Vec<Flashcard>,
+}
+
+impl Deck {
+    /// Create an empty deck
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cards: Vec::new(),
+        }
+    }
+
+    /// Build a deck from `turns` (oldest first), tagging every card with
+    /// `tags`
+    pub fn from_turns(
+        name: impl Into<String>,
+        turns: &[ConversationTurn],
+        tags: &[String],
+    ) -> Self {
+        let tags: Vec<String> = tags
+            .iter()
+            .map(|tag| tag.replace(char::is_whitespace, "_"))
+            .collect();
+        let cards = turns
+            .iter()
+            .filter(|turn| turn.response.route != RoutingDecision::Blocked)
+            .filter(|turn| {
+                !turn.query.text.trim().is_empty() && !turn.response.text.trim().is_empty()
+            })
+            .map(|turn| Flashcard {
+                front: turn.query.text.trim().to_string(),
+                back: turn.response.text.trim().to_string(),
+                tags: tags.clone(),
+            })
+            .collect();
+        Self {
+            name: name.into(),
+            cards,
+        }
+    }
+
+    /// Number of cards
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    /// Whether the deck has no cards
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// Anki-importable CSV: `#` headers, then one `front,back,tags` row
+    /// per card with RFC 4180 quoting
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        csv.push_str("#separator:comma\n#html:false\n");
+        csv.push_str(&format!("#deck:{}\n", self.name.replace('\n', " ")));
+        csv.push_str("#columns:Front,Back,Tags\n#tags column:3\n");
+        for card in &self.cards {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                csv_field(&card.front),
+                csv_field(&card.back),
+                csv_field(&card.tags.join(" "))
+            ));
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field when it contains separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Query, Response, ResponseMetadata};
+
+    fn turn(query: &str, answer: &str, route: RoutingDecision) -> ConversationTurn {
+        ConversationTurn {
+            query: Query::new(query),
+            response: Response {
+                text: answer.to_string(),
+                route,
+                confidence: 1.0,
+                latency_ms: 10,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_deck_skips_blocked_and_empty_turns() {
+        let turns = vec![
+            turn(
+                "What is ownership?",
+                "Each value has one owner.",
+                RoutingDecision::Local,
+            ),
+            turn("forbidden", "Request blocked", RoutingDecision::Blocked),
+            turn("   ", "nothing asked", RoutingDecision::Remote),
+        ];
+        let deck = Deck::from_turns("Rust", &turns, &["rust basics".to_string()]);
+        assert_eq!(deck.len(), 1);
+        assert_eq!(deck.cards[0].front, "What is ownership?");
+        assert_eq!(deck.cards[0].tags, vec!["rust_basics".to_string()]);
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let turns = vec![turn("Say \"hi\", twice", "hi\nhi", RoutingDecision::Local)];
+        let deck = Deck::from_turns(
+            "Study::Greetings",
+            &turns,
+            &["a".to_string(), "b".to_string()],
+        );
+        let csv = deck.to_csv();
+        assert!(csv.starts_with("#separator:comma\n#html:false\n#deck:Study::Greetings\n"));
+        assert!(csv.ends_with("\"Say \"\"hi\"\", twice\",\"hi\nhi\",a b\n"));
+    }
+}
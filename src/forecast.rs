@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Conversation-Level Cost and Latency Forecasting
+//!
+//! [`Forecaster`] learns from the conversation so far to predict the
+//! tokens, spend and latency of the next few turns. That makes budget
+//! warnings proactive ("this thread will exceed today's Remote budget in
+//! ~3 turns") rather than only firing after the limit is crossed.
+//!
+//! The model is deliberately small:
+//!
+//! - the route trajectory is a Markov chain over the routes the
+//!   conversation has used, with transition counts learned from the route
+//!   history (add-one smoothed, so rare moves keep some probability);
+//! - each route has exponentially weighted averages of tokens, cost and
+//!   latency per turn.
+//!
+//! A forecast propagates the route distribution forward from the last
+//! route and sums the expected per-turn costs.
+
+#![forbid(unsafe_code)]
+
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+
+/// Routes the forecaster models, in matrix order
+const ROUTES: [RoutingDecision; 3] = [
+    RoutingDecision::Local,
+    RoutingDecision::Remote,
+    RoutingDecision::Hybrid,
+];
+
+/// Longest horizon searched by [`Forecaster::turns_until_exceeded`]
+const MAX_HORIZON: usize = 50;
+
+/// Forecaster settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForecastConfig {
+    /// Weight of the newest turn in the per-route averages (0.0-1.0)
+    pub smoothing: f32,
+    /// Warn when the daily spend limit is forecast to be hit within this
+    /// many turns
+    pub warn_within_turns: usize,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.3,
+            warn_within_turns: 3,
+        }
+    }
+}
+
+/// Expected totals over the next `turns` turns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    /// Horizon in turns
+    pub turns: usize,
+    /// Expected tokens
+    pub tokens: f32,
+    /// Expected spend (USD)
+    pub cost_usd: f64,
+    /// Expected total latency (ms)
+    pub latency_ms: f32,
+    /// Probability of each route on the final turn
+    pub route_mix: Vec<(RoutingDecision, f32)>,
+}
+
+/// Smoothed per-turn averages for one route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct RouteAverages {
+    tokens: f32,
+    cost_usd: f64,
+    latency_ms: f32,
+    seen: bool,
+}
+
+/// Learns route transitions and per-route costs from a conversation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Forecaster {
+    config: ForecastConfig,
+    averages: [RouteAverages; 3],
+    /// Transition counts, `[from][to]`
+    transitions: [[u32; 3]; 3],
+    last_route: Option<usize>,
+}
+
+impl Forecaster {
+    /// Create an untrained forecaster
+    pub fn new(config: ForecastConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Record a completed turn; Blocked turns are ignored
+    pub fn observe(&mut self, route: RoutingDecision, tokens: u32, cost_usd: f64, latency_ms: u64) {
+        let Some(index) = route_index(route) else {
+            return;
+        };
+
+        let alpha = self.config.smoothing.clamp(0.0, 1.0);
+        let averages = &mut self.averages[index];
+        if averages.seen {
+            averages.tokens += alpha * (tokens as f32 - averages.tokens);
+            averages.cost_usd += f64::from(alpha) * (cost_usd - averages.cost_usd);
+            averages.latency_ms += alpha * (latency_ms as f32 - averages.latency_ms);
+        } else {
+            *averages = RouteAverages {
+                tokens: tokens as f32,
+                cost_usd,
+                latency_ms: latency_ms as f32,
+                seen: true,
+            };
+        }
+
+        if let Some(previous) = self.last_route {
+            self.transitions[previous][index] += 1;
+        }
+        self.last_route = Some(index);
+    }
+
+    /// Expected totals over the next `turns` turns, or `None` before any
+    /// turn has been observed
+    pub fn forecast(&self, turns: usize) -> Option<Forecast> {
+        let mut mix = self.current_mix()?;
+        let mut forecast = Forecast {
+            turns,
+            tokens: 0.0,
+            cost_usd: 0.0,
+            latency_ms: 0.0,
+            route_mix: Vec::new(),
+        };
+
+        for _ in 0..turns {
+            mix = self.step(&mix);
+            let (tokens, cost_usd, latency_ms) = self.expected_turn(&mix);
+            forecast.tokens += tokens;
+            forecast.cost_usd += cost_usd;
+            forecast.latency_ms += latency_ms;
+        }
+
+        forecast.route_mix = ROUTES.iter().copied().zip(mix).collect();
+        Some(forecast)
+    }
+
+    /// Number of turns until cumulative spend is expected to exceed
+    /// `remaining_usd`, if that happens within the search horizon
+    pub fn turns_until_exceeded(&self, remaining_usd: f64) -> Option<usize> {
+        if remaining_usd < 0.0 {
+            return Some(0);
+        }
+        let mut mix = self.current_mix()?;
+        let mut spent = 0.0;
+        for turn in 1..=MAX_HORIZON {
+            mix = self.step(&mix);
+            spent += self.expected_turn(&mix).1;
+            if spent > remaining_usd {
+                return Some(turn);
+            }
+        }
+        None
+    }
+
+    /// Forecaster settings
+    pub fn config(&self) -> &ForecastConfig {
+        &self.config
+    }
+
+    /// Route distribution for the turn just observed
+    fn current_mix(&self) -> Option<[f32; 3]> {
+        let mut mix = [0.0; 3];
+        mix[self.last_route?] = 1.0;
+        Some(mix)
+    }
+
+    /// Advance the route distribution one turn. Only routes seen in this
+    /// conversation can be reached; a thread that has never gone Remote
+    /// is not forecast to start.
+    fn step(&self, mix: &[f32; 3]) -> [f32; 3] {
+        let mut next = [0.0; 3];
+        for (from, p) in mix.iter().enumerate() {
+            let weights: Vec<f32> = self.transitions[from]
+                .iter()
+                .zip(&self.averages)
+                .map(|(count, averages)| {
+                    if averages.seen {
+                        (count + 1) as f32
+                    } else {
+                        0.0
+                    }
+                })
+                .collect();
+            let total: f32 = weights.iter().sum();
+            for (to, weight) in weights.iter().enumerate() {
+                next[to] += p * weight / total;
+            }
+        }
+        next
+    }
+
+    /// Expected (tokens, cost, latency) of one turn under `mix`
+    fn expected_turn(&self, mix: &[f32; 3]) -> (f32, f64, f32) {
+        let mut expected = (0.0, 0.0, 0.0);
+        for (p, averages) in mix.iter().zip(&self.averages) {
+            if averages.seen {
+                expected.0 += p * averages.tokens;
+                expected.1 += f64::from(*p) * averages.cost_usd;
+                expected.2 += p * averages.latency_ms;
+            }
+        }
+        expected
+    }
+}
+
+fn route_index(route: RoutingDecision) -> Option<usize> {
+    ROUTES.iter().position(|r| *r == route)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_forecast_before_observations() {
+        let forecaster = Forecaster::default();
+        assert_eq!(forecaster.forecast(3), None);
+        assert_eq!(forecaster.turns_until_exceeded(1.0), None);
+    }
+
+    #[test]
+    fn test_remote_heavy_thread_exhausts_budget() {
+        let mut forecaster = Forecaster::default();
+        for _ in 0..20 {
+            forecaster.observe(RoutingDecision::Remote, 800, 0.01, 3_000);
+        }
+
+        let Some(forecast) = forecaster.forecast(3) else {
+            panic!("forecast should exist after observations");
+        };
+        assert!((forecast.route_mix[1].1 - 1.0).abs() < 1e-6);
+        assert!((forecast.cost_usd - 0.03).abs() < 1e-6);
+        assert_eq!(forecaster.turns_until_exceeded(0.025), Some(3));
+        assert_eq!(forecaster.turns_until_exceeded(-1.0), Some(0));
+
+        // A free Local turn makes future Local turns possible, lowering
+        // the forecast while Remote -> Remote still dominates
+        forecaster.observe(RoutingDecision::Local, 200, 0.0, 1_000);
+        forecaster.observe(RoutingDecision::Remote, 800, 0.01, 3_000);
+        let Some(forecast) = forecaster.forecast(3) else {
+            panic!("forecast should exist after observations");
+        };
+        assert!(forecast.route_mix[1].1 > 0.8);
+        assert!(forecast.cost_usd > 0.025 && forecast.cost_usd < 0.03);
+    }
+
+    #[test]
+    fn test_local_thread_costs_nothing() {
+        let mut forecaster = Forecaster::default();
+        for _ in 0..10 {
+            forecaster.observe(RoutingDecision::Local, 200, 0.0, 1_000);
+        }
+        assert_eq!(forecaster.turns_until_exceeded(0.01), None);
+
+        let Some(forecast) = forecaster.forecast(2) else {
+            panic!("forecast should exist after observations");
+        };
+        assert!((forecast.tokens - 400.0).abs() < 1.0);
+        assert!((forecast.latency_ms - 2_000.0).abs() < 5.0);
+    }
+}
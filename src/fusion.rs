@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Orientation Sensor Fusion
+//!
+//! Fuses accelerometer, gyroscope and (optionally) magnetometer readings
+//! into roll, pitch and yaw with a complementary filter, and emits the
+//! result as derived `SensorType::Orientation` readings that encoders and
+//! networks consume like any other sensor.
+//!
+//! The gyroscope is smooth but drifts; the accelerometer knows where
+//! gravity is but is noisy and fooled by motion; the magnetometer gives a
+//! heading but is disturbed by nearby metal. The filter integrates the
+//! gyroscope and pulls the estimate a small step (`1 - gyro_weight`)
+//! towards the absolute angles on every accelerometer or magnetometer
+//! reading:
+//!
+//! - roll and pitch are corrected from gravity, but only while the
+//!   measured acceleration is within `accel_tolerance` of 1 g, so walking
+//!   or braking does not tilt the estimate
+//! - yaw is corrected from the tilt-compensated magnetic heading; without
+//!   a magnetometer it is relative to the start and drifts, and the
+//!   derived readings are marked `SensorAccuracy::Low`
+//!
+//! Angles are in radians, yaw wrapped to `(-pi, pi]`. The gyroscope rates
+//! are integrated per axis, a small-angle approximation that holds at
+//! sensor rates. One orientation reading is emitted per gyroscope reading
+//! once gravity has been seen, so the output follows the gyroscope rate.
+
+#![forbid(unsafe_code)]
+
+use crate::sensor::{SensorAccuracy, SensorBuffer, SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Standard gravity (m/s^2)
+const GRAVITY: f32 = 9.806_65;
+
+/// Complementary filter parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FusionConfig {
+    /// Weight of the integrated gyroscope estimate against each absolute
+    /// measurement, in `[0, 1]`; higher is smoother but corrects drift
+    /// more slowly
+    pub gyro_weight: f32,
+    /// Largest deviation of the acceleration magnitude from 1 g, as a
+    /// fraction of g, at which the accelerometer still corrects tilt
+    pub accel_tolerance: f32,
+    /// Gyroscope gaps longer than this (ms) are not integrated, since the
+    /// rate at either end says little about the motion in between
+    pub max_gyro_gap_ms: u64,
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        Self {
+            gyro_weight: 0.98,
+            accel_tolerance: 0.15,
+            max_gyro_gap_ms: 200,
+        }
+    }
+}
+
+/// Device orientation (radians)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Orientation {
+    /// Rotation about the x axis
+    pub roll: f32,
+    /// Rotation about the y axis
+    pub pitch: f32,
+    /// Rotation about the z axis (heading)
+    pub yaw: f32,
+}
+
+impl Orientation {
+    /// Roll and pitch of a device at rest measuring `accel`; yaw is zero
+    pub fn from_gravity(accel: [f32; 3]) -> Self {
+        let [x, y, z] = accel;
+        Self {
+            roll: y.atan2(z),
+            pitch: (-x).atan2((y * y + z * z).sqrt()),
+            yaw: 0.0,
+        }
+    }
+
+    /// Heading of `mag` with the device tilted by this roll and pitch
+    pub fn heading(&self, mag: [f32; 3]) -> f32 {
+        let [x, y, z] = mag;
+        let (sin_roll, cos_roll) = self.roll.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let level_x = x * cos_pitch + z * sin_pitch;
+        let level_y = x * sin_roll * sin_pitch + y * cos_roll - z * sin_roll * cos_pitch;
+        (-level_y).atan2(level_x)
+    }
+
+    /// This orientation as a derived `SensorType::Orientation` reading
+    pub fn to_reading(&self, timestamp_ms: u64, accuracy: SensorAccuracy) -> SensorReading {
+        let mut reading = SensorReading::with_timestamp(
+            SensorType::Orientation,
+            vec![self.roll, self.pitch, self.yaw],
+            timestamp_ms,
+        );
+        reading.accuracy = accuracy;
+        reading
+    }
+}
+
+/// Complementary filter fusing motion sensors into an [`Orientation`]
+#[derive(Debug, Clone, Default)]
+pub struct OrientationFilter {
+    config: FusionConfig,
+    /// Current estimate, once gravity has been seen
+    orientation: Option<Orientation>,
+    /// Whether yaw is referenced to the magnetometer
+    has_heading: bool,
+    last_gyro_ms: Option<u64>,
+    /// Timestamp of the newest reading fed, so a buffer can be fed again
+    last_ms: Option<u64>,
+}
+
+impl OrientationFilter {
+    /// Create a filter with the given parameters
+    pub fn new(config: FusionConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Current estimate; `None` until an accelerometer reading at rest
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+
+    /// Forget the estimate and start over
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    /// Feed one reading; returns the orientation reading derived from it,
+    /// which only gyroscope readings produce. Other sensors are ignored.
+    pub fn update(&mut self, reading: &SensorReading) -> Option<SensorReading> {
+        self.last_ms = self.last_ms.max(Some(reading.timestamp_ms));
+        let values = axes(reading)?;
+        match reading.sensor_type {
+            SensorType::Accelerometer => {
+                self.correct_tilt(values);
+                None
+            }
+            SensorType::Magnetometer => {
+                self.correct_heading(values);
+                None
+            }
+            SensorType::Gyroscope => self.integrate(values, reading.timestamp_ms),
+            _ => None,
+        }
+    }
+
+    /// Feed the readings in `buffer` newer than any fed before, in
+    /// timestamp order; returns the derived orientation readings
+    pub fn process(&mut self, buffer: &SensorBuffer) -> Vec<SensorReading> {
+        let mut readings: Vec<&SensorReading> = buffer
+            .readings()
+            .iter()
+            .filter(|r| self.last_ms.map_or(true, |last| r.timestamp_ms > last))
+            .collect();
+        readings.sort_by_key(|r| r.timestamp_ms);
+        readings
+            .into_iter()
+            .filter_map(|reading| self.update(reading))
+            .collect()
+    }
+
+    fn correct_tilt(&mut self, accel: [f32; 3]) {
+        let magnitude = accel.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if (magnitude / GRAVITY - 1.0).abs() > self.config.accel_tolerance {
+            return;
+        }
+        let measured = Orientation::from_gravity(accel);
+        let Some(orientation) = &mut self.orientation else {
+            self.orientation = Some(measured);
+            return;
+        };
+        let gain = 1.0 - self.config.gyro_weight;
+        orientation.roll = wrap(orientation.roll + gain * wrap(measured.roll - orientation.roll));
+        orientation.pitch += gain * (measured.pitch - orientation.pitch);
+    }
+
+    fn correct_heading(&mut self, mag: [f32; 3]) {
+        let Some(orientation) = &mut self.orientation else {
+            return;
+        };
+        let heading = orientation.heading(mag);
+        if !self.has_heading {
+            self.has_heading = true;
+            orientation.yaw = heading;
+            return;
+        }
+        let gain = 1.0 - self.config.gyro_weight;
+        orientation.yaw = wrap(orientation.yaw + gain * wrap(heading - orientation.yaw));
+    }
+
+    fn integrate(&mut self, rates: [f32; 3], timestamp_ms: u64) -> Option<SensorReading> {
+        let previous = self.last_gyro_ms.replace(timestamp_ms);
+        let orientation = self.orientation.as_mut()?;
+        let elapsed_ms = previous.map_or(0, |previous| timestamp_ms.saturating_sub(previous));
+        if elapsed_ms <= self.config.max_gyro_gap_ms {
+            let dt = elapsed_ms as f32 / 1000.0;
+            orientation.roll = wrap(orientation.roll + rates[0] * dt);
+            orientation.pitch = (orientation.pitch + rates[1] * dt).clamp(-PI / 2.0, PI / 2.0);
+            orientation.yaw = wrap(orientation.yaw + rates[2] * dt);
+        }
+        let accuracy = if self.has_heading {
+            SensorAccuracy::Medium
+        } else {
+            SensorAccuracy::Low
+        };
+        Some(orientation.to_reading(timestamp_ms, accuracy))
+    }
+}
+
+/// The first three values of a reading, if they are all finite
+fn axes(reading: &SensorReading) -> Option<[f32; 3]> {
+    match reading.values.as_slice() {
+        [x, y, z, ..] if x.is_finite() && y.is_finite() && z.is_finite() => Some([*x, *y, *z]),
+        _ => None,
+    }
+}
+
+/// Wrap an angle to `(-pi, pi]`
+fn wrap(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped <= -PI {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_fuses_gyro_with_gravity_and_heading() {
+        let mut filter = OrientationFilter::new(FusionConfig::default());
+        let reading =
+            |sensor, values: [f32; 3], t| SensorReading::with_timestamp(sensor, values.to_vec(), t);
+
+        // Nothing to integrate from until gravity has been seen
+        assert!(filter
+            .update(&reading(SensorType::Gyroscope, [0.0; 3], 0))
+            .is_none());
+        filter.update(&reading(SensorType::Accelerometer, [0.0, 0.0, GRAVITY], 0));
+        let Some(level) = filter.orientation() else {
+            panic!("gravity should set the tilt");
+        };
+        assert!(level.roll.abs() < 1e-6 && level.pitch.abs() < 1e-6);
+
+        // Magnetic north along -y: a quarter turn
+        filter.update(&reading(SensorType::Magnetometer, [0.0, -20.0, -40.0], 5));
+        assert!((filter.orientation().map_or(0.0, |o| o.yaw) - FRAC_PI_2).abs() < 1e-5);
+
+        // One second turning about z at 0.5 rad/s in 10 ms steps
+        let mut buffer = SensorBuffer::new(256);
+        for step in 1..=100u64 {
+            buffer.push(reading(SensorType::Gyroscope, [0.0, 0.0, 0.5], step * 10));
+        }
+        let derived = filter.process(&buffer);
+        assert_eq!(derived.len(), 100);
+        assert!(filter.process(&buffer).is_empty());
+        let Some(last) = derived.last() else {
+            panic!("expected orientation readings");
+        };
+        assert_eq!(last.sensor_type, SensorType::Orientation);
+        assert_eq!(last.accuracy, SensorAccuracy::Medium);
+        assert!(
+            (last.values[2] - (FRAC_PI_2 + 0.5)).abs() < 1e-3,
+            "{:?}",
+            last.values
+        );
+
+        // A tilted gravity reading pulls roll over only a little at a time,
+        // while a shaken one is ignored
+        let tilted = [0.0, GRAVITY * 0.5, GRAVITY * 0.866];
+        filter.update(&reading(SensorType::Accelerometer, [5.0, 5.0, 20.0], 1_001));
+        assert!(filter.orientation().map_or(1.0, |o| o.roll.abs()) < 1e-6);
+        for t in 0..300u64 {
+            filter.update(&reading(SensorType::Accelerometer, tilted, 1_002 + t));
+        }
+        let roll = filter.orientation().map_or(0.0, |o| o.roll);
+        assert!((roll - PI / 6.0).abs() < 0.01, "{}", roll);
+
+        assert!((wrap(PI + 0.5) - (0.5 - PI)).abs() < 1e-6);
+        assert_eq!(wrap(-PI), PI);
+    }
+}
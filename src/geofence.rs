@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: MPL-2.0
+//! GPS Geofencing
+//!
+//! Matches GPS fixes against user-defined circular regions ("home",
+//! "work", "gym") and reports entering and leaving them as
+//! `ContextEvent::RegionEntered` / `ContextEvent::RegionExited`. The
+//! orchestrator publishes these like other context changes and, when a
+//! region names a project, switches to it on entry.
+//!
+//! A fix is inside a region within `radius_m` of its centre, and only
+//! counts as outside again beyond `radius_m + exit_margin_m`, so a device
+//! resting near the boundary does not flap in and out. Fixes with an
+//! error above `max_fix_error_m` are ignored. Regions may overlap; the
+//! exits of a fix are reported before its entries, in region order.
+
+#![forbid(unsafe_code)]
+
+use crate::context_switch::{distance_m, ContextEvent};
+use crate::sensor::{SensorBuffer, SensorReading, SensorType};
+use serde::{Deserialize, Serialize};
+
+/// A circular region of interest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Geofence {
+    /// Unique name, reported in events
+    pub name: String,
+    /// Latitude of the centre (degrees)
+    pub latitude: f32,
+    /// Longitude of the centre (degrees)
+    pub longitude: f32,
+    /// Radius (m)
+    pub radius_m: f32,
+    /// Project to switch to on entering the region
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+/// Regions and thresholds used by [`GeofenceManager`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeofenceConfig {
+    /// Regions to watch
+    pub regions: Vec<Geofence>,
+    /// Extra distance (m) beyond the radius before a region is left
+    pub exit_margin_m: f32,
+    /// GPS fixes with a larger reported error (m) are ignored
+    pub max_fix_error_m: f32,
+}
+
+impl Default for GeofenceConfig {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            exit_margin_m: 25.0,
+            max_fix_error_m: 100.0,
+        }
+    }
+}
+
+/// Tracks which regions the device is in
+#[derive(Debug, Clone, Default)]
+pub struct GeofenceManager {
+    config: GeofenceConfig,
+    /// Names of the regions currently inside, in region order
+    inside: Vec<String>,
+    /// Timestamp of the newest fix seen, so a buffer can be observed again
+    last_fix_ms: Option<u64>,
+}
+
+impl GeofenceManager {
+    /// Create a manager watching `config.regions`
+    pub fn new(config: GeofenceConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Regions being watched
+    pub fn regions(&self) -> &[Geofence] {
+        &self.config.regions
+    }
+
+    /// Names of the regions the device is in
+    pub fn inside(&self) -> &[String] {
+        &self.inside
+    }
+
+    /// Watch `region`, replacing any region of the same name. The device
+    /// is considered outside it until the next fix.
+    pub fn add_region(&mut self, region: Geofence) {
+        self.remove_region(&region.name);
+        self.config.regions.push(region);
+    }
+
+    /// Stop watching the region called `name`; returns whether it existed.
+    /// No exit is reported for it.
+    pub fn remove_region(&mut self, name: &str) -> bool {
+        self.inside.retain(|inside| inside != name);
+        let before = self.config.regions.len();
+        self.config.regions.retain(|region| region.name != name);
+        self.config.regions.len() != before
+    }
+
+    /// Project of the region called `name`, if it has one
+    pub fn project_for(&self, name: &str) -> Option<&str> {
+        self.config
+            .regions
+            .iter()
+            .find(|region| region.name == name)?
+            .project
+            .as_deref()
+    }
+
+    /// Feed one GPS reading (latitude, longitude, error in metres);
+    /// other sensors are ignored
+    pub fn observe_fix(&mut self, reading: &SensorReading) -> Vec<ContextEvent> {
+        if reading.sensor_type != SensorType::Gps {
+            return Vec::new();
+        }
+        let (Some(&latitude), Some(&longitude)) = (reading.values.first(), reading.values.get(1))
+        else {
+            return Vec::new();
+        };
+        let error = reading.values.get(2).copied().unwrap_or(0.0);
+        if !latitude.is_finite() || !longitude.is_finite() || error > self.config.max_fix_error_m {
+            return Vec::new();
+        }
+
+        let (mut exits, mut entries) = (Vec::new(), Vec::new());
+        let mut inside = Vec::new();
+        for region in &self.config.regions {
+            let distance = distance_m((region.latitude, region.longitude), (latitude, longitude));
+            let was_inside = self.inside.contains(&region.name);
+            let is_inside = if was_inside {
+                distance <= region.radius_m + self.config.exit_margin_m
+            } else {
+                distance <= region.radius_m
+            };
+            match (was_inside, is_inside) {
+                (true, false) => exits.push(ContextEvent::RegionExited {
+                    region: region.name.clone(),
+                }),
+                (false, true) => entries.push(ContextEvent::RegionEntered {
+                    region: region.name.clone(),
+                }),
+                _ => {}
+            }
+            if is_inside {
+                inside.push(region.name.clone());
+            }
+        }
+        self.inside = inside;
+        exits.extend(entries);
+        exits
+    }
+
+    /// Feed the GPS fixes in `buffer` not seen before, oldest first
+    pub fn observe(&mut self, buffer: &SensorBuffer) -> Vec<ContextEvent> {
+        let mut events = Vec::new();
+        for reading in buffer.readings_of_type(SensorType::Gps) {
+            if self
+                .last_fix_ms
+                .is_some_and(|last| reading.timestamp_ms <= last)
+            {
+                continue;
+            }
+            self.last_fix_ms = Some(reading.timestamp_ms);
+            events.extend(self.observe_fix(reading));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_region_entry_and_exit_with_hysteresis() {
+        let region = |name: &str, latitude: f32, project: Option<&str>| Geofence {
+            name: name.to_string(),
+            latitude,
+            longitude: 13.4,
+            radius_m: 100.0,
+            project: project.map(str::to_string),
+        };
+        let mut manager = GeofenceManager::new(GeofenceConfig {
+            regions: vec![
+                region("home", 52.5200, None),
+                region("work", 52.5300, Some("job")),
+            ],
+            ..GeofenceConfig::default()
+        });
+        assert_eq!(manager.project_for("work"), Some("job"));
+        assert_eq!(manager.project_for("home"), None);
+
+        // ~1 m of latitude is 9e-6 degrees
+        let fix = |lat: f32, error: f32, t: u64| {
+            SensorReading::with_timestamp(SensorType::Gps, vec![lat, 13.4, error], t)
+        };
+        let entered = |name: &str| ContextEvent::RegionEntered {
+            region: name.to_string(),
+        };
+        let exited = |name: &str| ContextEvent::RegionExited {
+            region: name.to_string(),
+        };
+
+        let mut buffer = SensorBuffer::new(16);
+        buffer.push(fix(52.5200, 10.0, 1));
+        assert_eq!(manager.observe(&buffer), vec![entered("home")]);
+        assert!(manager.observe(&buffer).is_empty());
+        assert_eq!(manager.inside(), ["home".to_string()]);
+
+        // 110 m out is within the exit margin; 150 m is out
+        assert!(manager.observe_fix(&fix(52.52099, 10.0, 2)).is_empty());
+        assert!(manager.observe_fix(&fix(52.5300, 500.0, 3)).is_empty());
+        assert_eq!(
+            manager.observe_fix(&fix(52.52135, 10.0, 4)),
+            vec![exited("home")]
+        );
+        assert_eq!(
+            manager.observe_fix(&fix(52.5299, 10.0, 5)),
+            vec![entered("work")]
+        );
+        assert_eq!(
+            manager.observe_fix(&fix(52.5200, 10.0, 6)),
+            vec![exited("work"), entered("home")]
+        );
+
+        assert!(manager.remove_region("home"));
+        assert!(!manager.remove_region("home"));
+        assert!(manager.inside().is_empty());
+        manager.add_region(region("work", 52.5200, None));
+        assert_eq!(manager.regions().len(), 1);
+        assert_eq!(
+            manager.observe_fix(&fix(52.5200, 10.0, 7)),
+            vec![entered("work")]
+        );
+    }
+}
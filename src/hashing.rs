@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Stable Hashing
+//!
+//! Hashes that end up in trained models, saved indexes or checkpoints must
+//! give the same value on a 32-bit ARM phone and a 64-bit x86 dev machine,
+//! today and after a compiler upgrade. `std`'s `DefaultHasher` promises
+//! neither, and hashing through `usize` arithmetic changes with the
+//! pointer width.
+//!
+//! [`StableHasher`] is 64-bit FNV-1a over explicit little-endian bytes.
+//! It is used for the buckets of `HashedBagOfWords`, import deduplication
+//! and state fingerprints. Response cache keys are normalized query text
+//! and need no hash.
+//!
+//! The algorithm is frozen as [`STABLE_HASH_VERSION`]. Anything that
+//! persists a hash, or data laid out by one, should record the version;
+//! changing the algorithm means bumping it, so stale models and indexes
+//! are rejected instead of silently misread.
+
+#![forbid(unsafe_code)]
+
+/// Version of the [`StableHasher`] algorithm
+pub const STABLE_HASH_VERSION: u32 = 1;
+
+/// FNV-1a offset basis (64-bit)
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a prime (64-bit)
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a with platform-independent input encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StableHasher {
+    /// A hasher that has seen nothing
+    pub fn new() -> Self {
+        Self { hash: FNV_OFFSET }
+    }
+
+    /// Mix in raw `bytes`
+    pub fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for byte in bytes {
+            self.hash ^= u64::from(*byte);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+        self
+    }
+
+    /// Mix in `value` as 8 little-endian bytes
+    pub fn write_u64(&mut self, value: u64) -> &mut Self {
+        self.write(&value.to_le_bytes())
+    }
+
+    /// Mix in `bytes` prefixed by their length, so consecutive fields
+    /// cannot run into each other: ("ab", "c") differs from ("a", "bc")
+    pub fn write_field(&mut self, bytes: &[u8]) -> &mut Self {
+        self.write_u64(bytes.len() as u64).write(bytes)
+    }
+
+    /// The hash so far
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Stable hash of `bytes`
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    StableHasher::new().write(bytes).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_hash_matches_reference_vectors() {
+        // Published FNV-1a 64 test vectors; these must never change
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+
+        let mut split = StableHasher::new();
+        split.write(b"foo").write(b"bar");
+        assert_eq!(split.finish(), stable_hash(b"foobar"));
+
+        let field = |parts: &[&str]| {
+            let mut hasher = StableHasher::new();
+            for part in parts {
+                hasher.write_field(part.as_bytes());
+            }
+            hasher.finish()
+        };
+        assert_ne!(field(&["ab", "c"]), field(&["a", "bc"]));
+    }
+}
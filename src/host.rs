@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Host Callback Interface
+//!
+//! [`HostDelegate`] is implemented by the embedding app (Android, iOS,
+//! desktop) to receive push-style notifications from the orchestrator
+//! instead of polling.
+//!
+//! The trait is shaped for UniFFI callback interfaces: methods take
+//! `&self` and owned, FFI-representable arguments (`String`, numbers,
+//! `bool`), and implementors must be `Send + Sync`. Every method has a
+//! no-op default so hosts only override what they need.
+
+#![forbid(unsafe_code)]
+
+use crate::events::Event;
+
+/// Notifications pushed from the orchestrator to the host app
+pub trait HostDelegate: Send + Sync {
+    /// A piece of response text is ready; `is_final` marks the last chunk
+    fn on_response_chunk(&self, chunk: String, is_final: bool) {
+        let _ = (chunk, is_final);
+    }
+
+    /// A query was blocked by a safety or policy rule
+    fn on_block(&self, rule_id: String, reason: String) {
+        let _ = (rule_id, reason);
+    }
+
+    /// A resource budget (tokens, battery, ...) was exceeded or is
+    /// forecast to be exceeded soon
+    fn on_budget_warning(&self, budget: String, used: f64, limit: f64) {
+        let _ = (budget, used, limit);
+    }
+
+    /// A detector (wake word, activity, ...) fired
+    fn on_trigger(&self, kind: String, confidence: f32) {
+        let _ = (kind, confidence);
+    }
+
+    /// A new model version was promoted
+    fn on_model_updated(&self, model: String, version: String) {
+        let _ = (model, version);
+    }
+
+    /// A scheduled digest query was answered
+    fn on_digest(&self, digest_id: u64, query: String, text: String) {
+        let _ = (digest_id, query, text);
+    }
+
+    /// A query queued while offline was answered
+    fn on_queued_answer(&self, queue_id: u64, query: String, text: String) {
+        let _ = (queue_id, query, text);
+    }
+
+    /// A runtime setting changed; `value` is in the text form read by
+    /// `Setting::parse`
+    fn on_setting_changed(&self, key: String, value: String) {
+        let _ = (key, value);
+    }
+}
+
+/// Forward an event-bus event to the matching delegate callback
+///
+/// `RouteDecided`, `ContextChanged`, `SlaViolated`, `ChunkProcessed` and
+/// `DailySummaryReady` have no host callback and are ignored.
+pub fn forward_event(delegate: &dyn HostDelegate, event: &Event) {
+    match event {
+        Event::RouteDecided { .. }
+        | Event::ContextChanged { .. }
+        | Event::SlaViolated { .. }
+        | Event::ChunkProcessed { .. }
+        | Event::DailySummaryReady { .. } => {}
+        Event::RuleTriggered { rule_id, reason } => {
+            delegate.on_block(rule_id.clone(), reason.clone().unwrap_or_default())
+        }
+        Event::TriggerDetected { kind, confidence } => {
+            delegate.on_trigger(kind.clone(), *confidence)
+        }
+        Event::BudgetExceeded {
+            budget,
+            used,
+            limit,
+        }
+        | Event::BudgetForecast {
+            budget,
+            used,
+            limit,
+            ..
+        } => delegate.on_budget_warning(budget.clone(), *used, *limit),
+        Event::ModelPromoted { model, version } => {
+            delegate.on_model_updated(model.clone(), version.clone())
+        }
+        Event::DigestReady {
+            digest_id,
+            query,
+            text,
+        } => delegate.on_digest(*digest_id, query.clone(), text.clone()),
+        Event::QueuedAnswered {
+            queue_id,
+            query,
+            text,
+        } => delegate.on_queued_answer(*queue_id, query.clone(), text.clone()),
+        Event::SettingChanged { setting } => {
+            delegate.on_setting_changed(setting.key().to_string(), setting.value_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::Orchestrator;
+    use crate::types::Query;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct RecordingHost {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl RecordingHost {
+        fn record(&self, call: String) {
+            if let Ok(mut calls) = self.calls.lock() {
+                calls.push(call);
+            }
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+        }
+    }
+
+    impl HostDelegate for RecordingHost {
+        fn on_response_chunk(&self, chunk: String, is_final: bool) {
+            self.record(format!("chunk:{}:{}", chunk, is_final));
+        }
+
+        fn on_block(&self, rule_id: String, _reason: String) {
+            self.record(format!("block:{}", rule_id));
+        }
+
+        fn on_trigger(&self, kind: String, _confidence: f32) {
+            self.record(format!("trigger:{}", kind));
+        }
+    }
+
+    #[test]
+    fn test_default_methods_are_no_ops() {
+        struct Silent;
+        impl HostDelegate for Silent {}
+
+        forward_event(
+            &Silent,
+            &Event::ModelPromoted {
+                model: "router".to_string(),
+                version: "1".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_orchestrator_pushes_to_delegate() {
+        let host = Arc::new(RecordingHost::default());
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.set_host_delegate(host.clone());
+
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let Ok(_) = orchestrator.process(Query::new("install malware")) else {
+            panic!("blocked queries still return a response");
+        };
+        orchestrator.notify_wake_trigger();
+
+        assert_eq!(
+            host.calls(),
+            vec![
+                "chunk:Response to: hello:true".to_string(),
+                "block:SAFETY_001".to_string(),
+                "trigger:wake".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replacing_delegate_stops_old_one() {
+        let first = Arc::new(RecordingHost::default());
+        let second = Arc::new(RecordingHost::default());
+        let mut orchestrator = Orchestrator::new();
+
+        orchestrator.set_host_delegate(first.clone());
+        orchestrator.set_host_delegate(second.clone());
+        orchestrator.notify_wake_trigger();
+
+        assert!(first.calls().is_empty());
+        assert_eq!(second.calls(), vec!["trigger:wake".to_string()]);
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Hybrid Execution Strategies
+//!
+//! A Hybrid route uses the on-device and the remote model together. How
+//! they share the work is the [`HybridStrategy`]:
+//!
+//! - **Blend**: both answer the query and the answers are combined (see
+//!   `blend`).
+//! - **DraftRefine**: Local writes a draft, which Remote is asked to
+//!   check and improve. Remote does the judging, Local most of the
+//!   writing.
+//! - **RetrieveGenerate**: the most relevant earlier turns are found on
+//!   the device and passed to Remote as context, so retrieval over the
+//!   user's history never leaves the phone in bulk.
+//!
+//! Every strategy degrades to Local alone when the device is offline or
+//! the Remote half fails; the answer is then reported as `Local`, and no
+//! remote tokens are counted for it.
+//!
+//! ```toml
+//! [hybrid]
+//! strategy = "DraftRefine"
+//! retrieved_turns = 3
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::context::TurnMatch;
+use serde::{Deserialize, Serialize};
+
+/// How the two halves of a Hybrid route share the work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HybridStrategy {
+    /// Both answer; the answers are blended
+    #[default]
+    Blend,
+    /// Local drafts, Remote refines the draft
+    DraftRefine,
+    /// Local retrieves related turns, Remote answers with them
+    RetrieveGenerate,
+}
+
+/// Hybrid route settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HybridConfig {
+    /// Strategy used for Hybrid routes
+    pub strategy: HybridStrategy,
+    /// Earlier turns passed along by `RetrieveGenerate`
+    pub retrieved_turns: usize,
+}
+
+impl Default for HybridConfig {
+    fn default() -> Self {
+        Self {
+            strategy: HybridStrategy::Blend,
+            retrieved_turns: 3,
+        }
+    }
+}
+
+/// Prompt asking Remote to improve Local's `draft` answer to `prompt`
+pub fn refine_prompt(prompt: &str, draft: &str) -> String {
+    format!(
+        "{}\n\nA draft answer follows. Correct any mistakes and fill in what \
+         is missing; keep what is right.\n\nDraft:\n{}",
+        prompt, draft
+    )
+}
+
+/// `prompt` preceded by the retrieved `matches` as reference material;
+/// unchanged without matches
+pub fn retrieval_prompt(prompt: &str, matches: &[TurnMatch]) -> String {
+    if matches.is_empty() {
+        return prompt.to_string();
+    }
+    let mut text = String::from("Relevant earlier conversation:\n");
+    for found in matches {
+        text.push_str(&format!(
+            "- User: {}\n  Assistant: {}\n",
+            found.turn.query.text, found.turn.response.text
+        ));
+    }
+    text.push('\n');
+    text.push_str(prompt);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ConversationTurn, Query, Response, ResponseMetadata, RoutingDecision};
+
+    #[test]
+    fn test_prompts_carry_the_draft_and_retrieved_turns() {
+        let refine = refine_prompt("What is 2+2?", "5");
+        assert!(refine.starts_with("What is 2+2?"));
+        assert!(refine.ends_with("Draft:\n5"));
+
+        assert_eq!(retrieval_prompt("Which port?", &[]), "Which port?");
+        let found = TurnMatch {
+            turn: ConversationTurn {
+                query: Query::new("where does the api listen"),
+                response: Response {
+                    text: "on port 8080".to_string(),
+                    route: RoutingDecision::Local,
+                    confidence: 1.0,
+                    latency_ms: 0,
+                    metadata: ResponseMetadata {
+                        model: None,
+                        tokens: None,
+                        cached: false,
+                        context_budget: None,
+                        turn_id: None,
+                        explanation: None,
+                        state_fingerprint: None,
+                        degraded: None,
+                        energy_joules: None,
+                    },
+                },
+            },
+            similarity: 0.9,
+        };
+        let prompt = retrieval_prompt("Which port?", &[found]);
+        assert!(prompt.contains("- User: where does the api listen\n  Assistant: on port 8080"));
+        assert!(prompt.ends_with("\n\nWhich port?"));
+    }
+}
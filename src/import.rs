@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Bulk History Import
+//!
+//! Users switching from another assistant bring thousands of turns with
+//! them. Looping `ContextManager::add_turn` over them would replay each one
+//! through the reservoir, embed turns that are immediately trimmed from the
+//! in-memory window and write them to storage one statement at a time.
+//!
+//! `Orchestrator::import_turns` streams an export instead. It pulls turns
+//! from the caller's iterator one batch at a time, so a slow store holds
+//! back reading rather than buffering the whole export. Each batch is
+//! validated, deduplicated and written in a single transaction. Only the
+//! newest turns enter the in-memory history, and they are embedded for
+//! search once, when the import ends.
+//!
+//! After every batch a progress callback reports [`ImportProgress`] and may
+//! pause the import. With persistence attached, progress is checkpointed
+//! under the import's source name: importing the same source again skips
+//! the turns already read, so an import interrupted by a pause, an error or
+//! the OS killing the app resumes where it stopped.
+
+#![forbid(unsafe_code)]
+
+use crate::hashing::StableHasher;
+use crate::types::{ConversationTurn, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Most problems kept in [`ImportProgress::problems`]
+const MAX_PROBLEMS: usize = 20;
+
+/// How a bulk import reads and checks turns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// Turns validated and stored together
+    pub batch_size: usize,
+    /// Longest query or response accepted, in characters
+    pub max_text_chars: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 256,
+            max_text_chars: 32_000,
+        }
+    }
+}
+
+/// Where a bulk import stands; also its resume checkpoint
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportProgress {
+    /// Turns read from the source, including skipped ones
+    pub read: usize,
+    /// Turns stored
+    pub imported: usize,
+    /// Turns skipped as already present
+    pub duplicates: usize,
+    /// Turns skipped as invalid
+    pub rejected: usize,
+    /// The first invalid turns, as "turn <index>: <reason>"
+    pub problems: Vec<String>,
+    /// Whether the source was read to the end
+    pub finished: bool,
+}
+
+impl ImportProgress {
+    /// Record that turn `index` was rejected for `reason`
+    pub fn reject(&mut self, index: usize, reason: String) {
+        self.rejected += 1;
+        if self.problems.len() < MAX_PROBLEMS {
+            self.problems.push(format!("turn {}: {}", index, reason));
+        }
+    }
+}
+
+/// What the progress callback wants next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportControl {
+    /// Read the next batch
+    Continue,
+    /// Stop after this batch; importing the source again resumes here
+    Pause,
+}
+
+/// Why `turn` cannot be imported, if it cannot
+pub fn validate(turn: &ConversationTurn, options: &ImportOptions) -> Result<(), String> {
+    for (part, text) in [
+        ("query", &turn.query.text),
+        ("response", &turn.response.text),
+    ] {
+        if text.trim().is_empty() {
+            return Err(format!("empty {}", part));
+        }
+        let chars = text.chars().count();
+        if chars > options.max_text_chars {
+            return Err(format!(
+                "{} of {} characters exceeds {}",
+                part, chars, options.max_text_chars
+            ));
+        }
+    }
+    if turn.response.route == RoutingDecision::Blocked {
+        return Err("blocked turns carry no answer".to_string());
+    }
+    if !(0.0..=1.0).contains(&turn.response.confidence) {
+        return Err(format!(
+            "confidence {} is outside 0..=1",
+            turn.response.confidence
+        ));
+    }
+    Ok(())
+}
+
+/// Turns already seen, by query time, query and response
+#[derive(Debug, Clone, Default)]
+pub struct Deduplicator {
+    seen: HashSet<u64>,
+}
+
+impl Deduplicator {
+    /// An empty deduplicator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember `turn`, returning whether it was new
+    pub fn insert(&mut self, turn: &ConversationTurn) -> bool {
+        let key = StableHasher::new()
+            .write_u64(turn.query.timestamp)
+            .write_field(turn.query.text.as_bytes())
+            .write_field(turn.response.text.as_bytes())
+            .finish();
+        self.seen.insert(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Query, Response, ResponseMetadata};
+
+    fn turn(query: &str, answer: &str) -> ConversationTurn {
+        ConversationTurn {
+            query: Query::new(query),
+            response: Response {
+                text: answer.to_string(),
+                route: RoutingDecision::Remote,
+                confidence: 0.8,
+                latency_ms: 0,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_validation_and_deduplication() {
+        let options = ImportOptions {
+            max_text_chars: 20,
+            ..ImportOptions::default()
+        };
+        assert_eq!(validate(&turn("hello", "hi there"), &options), Ok(()));
+        assert!(validate(&turn("  ", "hi"), &options).is_err());
+        let Err(reason) = validate(&turn("hello", &"x".repeat(21)), &options) else {
+            panic!("an overlong response should be rejected");
+        };
+        assert!(reason.starts_with("response of 21 characters"));
+        let mut blocked = turn("hello", "hi");
+        blocked.response.route = RoutingDecision::Blocked;
+        assert!(validate(&blocked, &options).is_err());
+
+        let mut seen = Deduplicator::new();
+        let first = turn("hello", "hi");
+        assert!(seen.insert(&first));
+        assert!(!seen.insert(&first.clone()));
+        assert!(seen.insert(&turn("hello", "hi again")));
+
+        let mut progress = ImportProgress::default();
+        for index in 0..MAX_PROBLEMS + 5 {
+            progress.reject(index, "empty query".to_string());
+        }
+        assert_eq!(progress.rejected, MAX_PROBLEMS + 5);
+        assert_eq!(progress.problems.len(), MAX_PROBLEMS);
+    }
+}
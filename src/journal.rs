@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Daily Journal Export
+//!
+//! Appends each finished day's conversations to a per-day Markdown or Org
+//! file (`2026-10-16.md` / `2026-10-16.org`) in a user directory, so note
+//! systems such as Obsidian or Emacs pick them up with no cloud sync.
+//!
+//! Every turn gets a timestamped heading titled after the question and a
+//! one-sentence summary taken from the answer; answers of at least
+//! `notable_answer_chars` are also copied in full as notable answers.
+//!
+//! [`JournalExporter`] is a job rather than a timer: the host calls
+//! `Orchestrator::export_journal` from its own scheduler, and the
+//! orchestrator also runs it when the app goes to the background. Only
+//! complete days are written, each once per exporter; hosts that restart
+//! the app should persist [`JournalExporter::last_exported_day`].
+
+#![forbid(unsafe_code)]
+
+use crate::types::ConversationTurn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Seconds per day
+const DAY_SECS: i64 = 86_400;
+
+/// Longest title taken from a question (characters)
+const TITLE_CHARS: usize = 60;
+
+/// Longest summary taken from an answer (characters)
+const SUMMARY_CHARS: usize = 200;
+
+/// Journal file syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum JournalFormat {
+    /// Markdown (`.md`)
+    #[default]
+    Markdown,
+    /// Emacs Org mode (`.org`)
+    Org,
+}
+
+impl JournalFormat {
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            JournalFormat::Markdown => "md",
+            JournalFormat::Org => "org",
+        }
+    }
+}
+
+/// Journal export settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// Export finished days automatically
+    pub enabled: bool,
+    /// Directory receiving one file per day
+    pub dir: PathBuf,
+    /// File syntax
+    pub format: JournalFormat,
+    /// Answers at least this long (characters) are copied in full
+    pub notable_answer_chars: usize,
+    /// Offset of local time from UTC (minutes), deciding where days start
+    pub utc_offset_minutes: i32,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            format: JournalFormat::Markdown,
+            notable_answer_chars: 400,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+/// Writes finished days of conversation to journal files
+#[derive(Debug, Clone)]
+pub struct JournalExporter {
+    config: JournalConfig,
+    last_exported_day: Option<i64>,
+}
+
+impl JournalExporter {
+    /// Create an exporter that has written nothing yet
+    pub fn new(config: JournalConfig) -> Self {
+        Self {
+            config,
+            last_exported_day: None,
+        }
+    }
+
+    /// Export settings
+    pub fn config(&self) -> &JournalConfig {
+        &self.config
+    }
+
+    /// Last local day (days since 1970-01-01) written, if any
+    pub fn last_exported_day(&self) -> Option<i64> {
+        self.last_exported_day
+    }
+
+    /// Resume after a restart: days up to `day` are not written again
+    pub fn set_last_exported_day(&mut self, day: Option<i64>) {
+        self.last_exported_day = day;
+    }
+
+    /// Whether a finished day has not been exported yet
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        let yesterday = self.local_day(now_ms / 1_000) - 1;
+        self.last_exported_day.map_or(true, |day| day < yesterday)
+    }
+
+    /// Append turns from finished, not yet exported days to their day
+    /// files, returning how many turns were written. `turns` may be in
+    /// any order.
+    pub fn export(&mut self, turns: &[ConversationTurn], now_ms: u64) -> Result<usize, String> {
+        let today = self.local_day(now_ms / 1_000);
+        let mut days: BTreeMap<i64, Vec<&ConversationTurn>> = BTreeMap::new();
+        for turn in turns {
+            let day = self.local_day(turn.query.timestamp);
+            if day < today && self.last_exported_day.map_or(true, |last| day > last) {
+                days.entry(day).or_default().push(turn);
+            }
+        }
+
+        let mut written = 0;
+        for (day, mut day_turns) in days {
+            day_turns.sort_by_key(|turn| turn.query.timestamp);
+            self.append_day(day, &day_turns)?;
+            written += day_turns.len();
+            self.last_exported_day = Some(day);
+        }
+        if self.last_exported_day.map_or(true, |last| last < today - 1) {
+            self.last_exported_day = Some(today - 1);
+        }
+        Ok(written)
+    }
+
+    /// Journal text for one day's turns (oldest first)
+    pub fn render(&self, turns: &[&ConversationTurn]) -> String {
+        let markdown = self.config.format == JournalFormat::Markdown;
+        let mut text = String::new();
+        let mut notable = Vec::new();
+        for turn in turns {
+            let seconds = (self.local_seconds(turn.query.timestamp)).rem_euclid(DAY_SECS);
+            let time = format!("{:02}:{:02}", seconds / 3_600, seconds % 3_600 / 60);
+            let title = truncate(first_line(&turn.query.text), TITLE_CHARS);
+            let summary = truncate(first_sentence(&turn.response.text), SUMMARY_CHARS);
+            if markdown {
+                text.push_str(&format!("### {} {}\n\n{}\n\n", time, title, summary));
+            } else {
+                text.push_str(&format!("** {} {}\n{}\n", time, title, summary));
+            }
+            if turn.response.text.chars().count() >= self.config.notable_answer_chars {
+                notable.push((title, turn.response.text.trim()));
+            }
+        }
+
+        if !notable.is_empty() {
+            text.push_str(if markdown {
+                "### Notable answers\n\n"
+            } else {
+                "** Notable answers\n"
+            });
+            for (title, answer) in notable {
+                if markdown {
+                    let quoted: Vec<String> =
+                        answer.lines().map(|line| format!("> {}", line)).collect();
+                    text.push_str(&format!("**{}**\n\n{}\n\n", title, quoted.join("\n")));
+                } else {
+                    text.push_str(&format!(
+                        "*** {}\n#+BEGIN_QUOTE\n{}\n#+END_QUOTE\n",
+                        title, answer
+                    ));
+                }
+            }
+        }
+
+        let heading = if markdown {
+            format!("## Conversations ({} turns)\n\n", turns.len())
+        } else {
+            format!("* Conversations ({} turns)\n", turns.len())
+        };
+        heading + &text
+    }
+
+    fn append_day(&self, day: i64, turns: &[&ConversationTurn]) -> Result<(), String> {
+        let date = format_date(day);
+        let path = self
+            .config
+            .dir
+            .join(format!("{}.{}", date, self.config.format.extension()));
+        std::fs::create_dir_all(&self.config.dir)
+            .map_err(|e| format!("failed to create journal directory: {}", e))?;
+
+        let mut text = String::new();
+        if !path.exists() {
+            text.push_str(&match self.config.format {
+                JournalFormat::Markdown => format!("# {}\n\n", date),
+                JournalFormat::Org => format!("#+TITLE: {}\n\n", date),
+            });
+        }
+        text.push_str(&self.render(turns));
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// Seconds since the epoch shifted to local time
+    fn local_seconds(&self, epoch_secs: u64) -> i64 {
+        epoch_secs as i64 + i64::from(self.config.utc_offset_minutes) * 60
+    }
+
+    /// Local day number of a UNIX timestamp (seconds)
+    fn local_day(&self, epoch_secs: u64) -> i64 {
+        self.local_seconds(epoch_secs).div_euclid(DAY_SECS)
+    }
+}
+
+/// `YYYY-MM-DD` for a day number (days since 1970-01-01)
+pub fn format_date(day: i64) -> String {
+    // Civil-from-days (H. Hinnant), valid for the proleptic Gregorian calendar
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+fn first_line(text: &str) -> &str {
+    text.trim().lines().next().unwrap_or("")
+}
+
+fn first_sentence(text: &str) -> &str {
+    let text = first_line(text);
+    match text.find(['.', '!', '?']) {
+        Some(end) => &text[..=end],
+        None => text,
+    }
+}
+
+/// At most `max` characters, with an ellipsis when cut
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((cut, _)) => format!("{}…", text[..cut].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Query, Response, ResponseMetadata, RoutingDecision};
+
+    /// 2026-10-16 00:00:00 UTC
+    const OCT_16: u64 = 1_792_108_800;
+
+    fn turn(query: &str, answer: &str, timestamp: u64) -> ConversationTurn {
+        ConversationTurn {
+            query: Query {
+                timestamp,
+                ..Query::new(query)
+            },
+            response: Response {
+                text: answer.to_string(),
+                route: RoutingDecision::Local,
+                confidence: 1.0,
+                latency_ms: 10,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("journal-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(OCT_16 as i64 / DAY_SECS), "2026-10-16");
+        assert_eq!(format_date(-1), "1969-12-31");
+        assert_eq!(format_date(11_016), "2000-02-29");
+    }
+
+    #[test]
+    fn test_exports_finished_days_once() {
+        let dir = temp_dir("markdown");
+        let mut exporter = JournalExporter::new(JournalConfig {
+            dir: dir.clone(),
+            notable_answer_chars: 30,
+            ..JournalConfig::default()
+        });
+        let turns = vec![
+            turn(
+                "What is a monad?",
+                "A monoid in the category of endofunctors. Mostly.",
+                OCT_16 + 3_600,
+            ),
+            turn("Lunch ideas", "Soup.", OCT_16 + 45_000),
+            turn(
+                "Still today",
+                "Not exported yet.",
+                OCT_16 + DAY_SECS as u64 + 60,
+            ),
+        ];
+        let now_ms = (OCT_16 + DAY_SECS as u64 + 120) * 1_000;
+
+        assert!(exporter.is_due(now_ms));
+        assert_eq!(exporter.export(&turns, now_ms), Ok(2));
+        assert!(!exporter.is_due(now_ms));
+        assert_eq!(exporter.export(&turns, now_ms), Ok(0));
+
+        let Ok(journal) = std::fs::read_to_string(dir.join("2026-10-16.md")) else {
+            panic!("the finished day should have a journal file");
+        };
+        assert!(journal.starts_with("# 2026-10-16\n\n## Conversations (2 turns)\n"));
+        assert!(journal
+            .contains("### 01:00 What is a monad?\n\nA monoid in the category of endofunctors."));
+        assert!(journal.contains("### Notable answers\n\n**What is a monad?**\n\n> A monoid"));
+        assert!(!journal.contains("Still today"));
+        assert!(!dir.join("2026-10-17.md").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_org_format_and_local_offset() {
+        let exporter = JournalExporter::new(JournalConfig {
+            format: JournalFormat::Org,
+            utc_offset_minutes: -120,
+            ..JournalConfig::default()
+        });
+        let late = turn("Night question", "Answer", OCT_16 + 3_600);
+        // 01:00 UTC is 23:00 the previous day at UTC-2
+        assert_eq!(
+            exporter.local_day(late.query.timestamp),
+            OCT_16 as i64 / DAY_SECS - 1
+        );
+        let text = exporter.render(&[&late]);
+        assert_eq!(
+            text,
+            "* Conversations (1 turns)\n** 23:00 Night question\nAnswer\n"
+        );
+    }
+}
@@ -20,18 +20,98 @@
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
+#[cfg(feature = "ffi")]
+uniffi::setup_scaffolding!();
+
+pub mod activity;
+pub mod ambient;
+pub mod audio;
+pub mod bench;
+#[cfg(feature = "fast-serde")]
+pub mod binary;
+pub mod blend;
+pub mod cache;
+pub mod calibration;
+pub mod capabilities;
+pub mod chunking;
+pub mod clock;
+pub mod code_context;
+pub mod config;
 pub mod context;
+pub mod context_budget;
+pub mod context_switch;
+pub mod device;
+pub mod digest;
+pub mod dispatch;
+pub mod embedding;
+pub mod encoding;
+pub mod energy;
+pub mod error;
+pub mod events;
 pub mod expert;
+pub mod fallback;
+pub mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fingerprint;
+pub mod flashcards;
+pub mod forecast;
+pub mod fusion;
+pub mod geofence;
+pub mod hashing;
+pub mod host;
+pub mod hybrid;
+pub mod import;
+pub mod journal;
+pub mod lifecycle;
+pub mod linalg;
+pub mod memory;
+pub mod metrics;
+#[cfg(feature = "minilm")]
+pub mod minilm;
 pub mod mlp;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 pub mod orchestrator;
 pub mod persistence;
+pub mod personalization;
+pub mod plugin_api;
+pub mod policy;
+pub mod regenerate;
+pub mod pool;
+pub mod profile;
+pub mod prompt;
+pub mod queue;
+pub mod quota;
+pub mod requirements;
 pub mod reservoir;
+pub mod reward;
+pub mod rng;
 pub mod router;
+pub mod sampling;
+pub mod secrets;
+pub mod sla;
+pub mod sensor;
+pub mod sensor_batch;
+#[cfg(feature = "network")]
+pub mod serve;
+pub mod settings;
+pub mod shared;
 pub mod snn;
+pub mod supervisor;
+pub mod targets;
+pub mod timeseries;
+pub mod tokens;
 pub mod training;
+pub mod triage;
 pub mod types;
+pub mod user;
+pub mod wakeword;
+#[cfg(feature = "network")]
+pub mod webhooks;
 
 // RE-EXPORTS: Primary types for mobile application integration.
+pub use error::OrchestratorError;
 pub use orchestrator::Orchestrator;
 pub use types::{Query, Response, RoutingDecision};
 
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MPL-2.0
+//! App Lifecycle Signals
+//!
+//! Platform lifecycle callbacks map directly onto [`LifecycleEvent`]:
+//!
+//! | Event        | Android                          | iOS                                      |
+//! |--------------|----------------------------------|------------------------------------------|
+//! | `Foreground` | `onResume`                       | `applicationDidBecomeActive`             |
+//! | `Background` | `onPause` / `onStop`             | `applicationDidEnterBackground`          |
+//! | `LowMemory`  | `onTrimMemory` / `onLowMemory`   | `applicationDidReceiveMemoryWarning`     |
+//!
+//! The host forwards them to `Orchestrator::on_lifecycle`, or to
+//! `MobileOrchestrator::on_lifecycle` through the FFI layer:
+//!
+//! - `Background` pauses supervised background tasks and holds scheduled
+//!   digests, then flushes buffered writes.
+//! - `Foreground` resumes them and loads the local model back up.
+//! - `LowMemory` flushes, empties the response cache and unloads idle
+//!   local model instances.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle signal from the host OS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifecycleEvent {
+    /// App became visible and interactive
+    Foreground,
+    /// App left the screen; it may be suspended or killed soon
+    Background,
+    /// OS is under memory pressure
+    LowMemory,
+}
+
+/// Whether the app is currently in the foreground
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LifecycleState {
+    /// Visible and interactive
+    #[default]
+    Foreground,
+    /// Not visible; background work is paused
+    Background,
+}
+
+/// What `Orchestrator::on_lifecycle` did in response to an event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LifecycleReport {
+    /// Conversation turns written from the write-behind buffer
+    pub turns_flushed: usize,
+    /// Supervised background tasks paused
+    pub tasks_paused: usize,
+    /// Cached responses dropped
+    pub caches_dropped: usize,
+    /// Local model instances loaded or unloaded
+    pub models_changed: usize,
+    /// Turns appended to journal files
+    pub journal_entries: usize,
+}
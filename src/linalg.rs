@@ -0,0 +1,498 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Dense Matrix Storage for Neural Components
+//!
+//! A contiguous row-major matrix used for MLP and ESN weights. Keeping
+//! weights in one flat buffer (instead of `Vec<Vec<f32>>`) improves cache
+//! locality and lets the `high-perf` build hand the same memory to
+//! `ndarray` without copying, where matrix-vector products run through
+//! its BLAS-style `dot` kernel.
+//!
+//! Serialization is unchanged from the nested-vector layout (a list of
+//! rows), so models saved before this type existed still load.
+//!
+//! [`ridge_regression`] trains linear readouts without a LAPACK
+//! dependency. It runs conjugate gradient on the regularized normal
+//! equations, applying `XᵀX + λI` through two matrix-vector products so
+//! the normal matrix is never formed. Before solving it estimates the
+//! condition number by power iteration and raises λ until single
+//! precision can cope; the [`RidgeReport`] records the λ actually used.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::ops::{Index, IndexMut};
+
+/// Row-major dense matrix of `f32`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Vec<f32>>", into = "Vec<Vec<f32>>")]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f32>,
+}
+
+impl Matrix {
+    /// Matrix of zeros
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    /// Matrix whose entries are produced by `f(row, col)` in row-major order
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(usize, usize) -> f32) -> Self {
+        let mut data = Vec::with_capacity(rows * cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                data.push(f(r, c));
+            }
+        }
+        Self { rows, cols, data }
+    }
+
+    /// Number of rows
+    pub fn nrows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns
+    pub fn ncols(&self) -> usize {
+        self.cols
+    }
+
+    /// Borrow one row
+    pub fn row(&self, r: usize) -> &[f32] {
+        &self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Mutably borrow one row
+    pub fn row_mut(&mut self, r: usize) -> &mut [f32] {
+        &mut self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Iterate over rows
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        (0..self.rows).map(move |r| self.row(r))
+    }
+
+    /// All entries in row-major order
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// All entries in row-major order, mutably
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// Matrix-vector product `self * x`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x.len() != self.ncols()`
+    pub fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        assert_eq!(
+            x.len(),
+            self.cols,
+            "matvec dimension mismatch: expected {}, got {}",
+            self.cols,
+            x.len()
+        );
+
+        #[cfg(feature = "high-perf")]
+        {
+            use ndarray::{ArrayView1, ArrayView2};
+            let a = ArrayView2::from_shape((self.rows, self.cols), &self.data)
+                .expect("invariant: data.len() == rows * cols");
+            a.dot(&ArrayView1::from(x)).to_vec()
+        }
+
+        #[cfg(not(feature = "high-perf"))]
+        {
+            self.rows()
+                .map(|row| row.iter().zip(x).map(|(w, v)| w * v).sum())
+                .collect()
+        }
+    }
+}
+
+impl Matrix {
+    /// Transposed matrix-vector product `selfᵀ * y`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y.len() != self.nrows()`
+    pub fn transpose_matvec(&self, y: &[f32]) -> Vec<f32> {
+        assert_eq!(
+            y.len(),
+            self.rows,
+            "transpose_matvec dimension mismatch: expected {}, got {}",
+            self.rows,
+            y.len()
+        );
+
+        #[cfg(feature = "high-perf")]
+        {
+            use ndarray::{ArrayView1, ArrayView2};
+            let a = ArrayView2::from_shape((self.rows, self.cols), &self.data)
+                .expect("invariant: data.len() == rows * cols");
+            a.t().dot(&ArrayView1::from(y)).to_vec()
+        }
+
+        #[cfg(not(feature = "high-perf"))]
+        {
+            let mut out = vec![0.0; self.cols];
+            for (row, &scale) in self.rows().zip(y) {
+                for (o, w) in out.iter_mut().zip(row) {
+                    *o += w * scale;
+                }
+            }
+            out
+        }
+    }
+
+    /// Outer product `x * yᵀ`
+    pub fn outer(x: &[f32], y: &[f32]) -> Self {
+        Self::from_fn(x.len(), y.len(), |r, c| x[r] * y[c])
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f32;
+
+    fn index(&self, (r, c): (usize, usize)) -> &f32 {
+        &self.data[r * self.cols + c]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut f32 {
+        &mut self.data[r * self.cols + c]
+    }
+}
+
+impl TryFrom<Vec<Vec<f32>>> for Matrix {
+    type Error = String;
+
+    fn try_from(rows: Vec<Vec<f32>>) -> Result<Self, String> {
+        let cols = rows.first().map_or(0, Vec::len);
+        if let Some(bad) = rows.iter().position(|row| row.len() != cols) {
+            return Err(format!(
+                "ragged matrix: row {} has {} columns, expected {}",
+                bad,
+                rows[bad].len(),
+                cols
+            ));
+        }
+
+        Ok(Self {
+            rows: rows.len(),
+            cols,
+            data: rows.into_iter().flatten().collect(),
+        })
+    }
+}
+
+impl From<Matrix> for Vec<Vec<f32>> {
+    fn from(m: Matrix) -> Self {
+        m.rows().map(<[f32]>::to_vec).collect()
+    }
+}
+
+/// Numerical safeguards for [`ridge_regression`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RidgeConfig {
+    /// Largest condition number solved as is; λ is raised tenfold until
+    /// the estimate falls below it
+    pub max_condition: f32,
+    /// Relative residual at which conjugate gradient stops
+    pub tolerance: f32,
+    /// Conjugate gradient iterations per output before λ is raised
+    pub max_iterations: usize,
+    /// Most times λ is raised for an unconverged solve
+    pub max_bumps: usize,
+}
+
+impl Default for RidgeConfig {
+    fn default() -> Self {
+        Self {
+            max_condition: 1e4,
+            tolerance: 1e-4,
+            max_iterations: 500,
+            max_bumps: 6,
+        }
+    }
+}
+
+/// How a ridge solve went
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RidgeReport {
+    /// Regularization actually used, at least the one requested
+    pub lambda: f32,
+    /// Estimated condition number of `XᵀX + λI`
+    pub condition: f32,
+    /// Most conjugate gradient iterations any output needed
+    pub iterations: usize,
+    /// Whether every output reached the tolerance
+    pub converged: bool,
+}
+
+/// Power iterations used for each eigenvalue estimate
+const POWER_ITERATIONS: usize = 50;
+
+/// Dot product accumulated in double precision
+fn dot(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| f64::from(*x) * f64::from(*y))
+        .sum()
+}
+
+/// `(XᵀX + λI) v`
+fn normal_apply(x: &Matrix, lambda: f32, v: &[f32]) -> Vec<f32> {
+    let mut out = x.transpose_matvec(&x.matvec(v));
+    for (o, vi) in out.iter_mut().zip(v) {
+        *o += lambda * vi;
+    }
+    out
+}
+
+/// Dominant eigenvalue of the symmetric operator `apply`, by power
+/// iteration from a fixed start
+fn power_iteration(n: usize, apply: impl Fn(&[f32]) -> Vec<f32>) -> f32 {
+    let mut v: Vec<f32> = (0..n).map(|i| (i % 7) as f32 + 1.0).collect();
+    let mut eigenvalue = 0.0;
+    for _ in 0..POWER_ITERATIONS {
+        let norm = dot(&v, &v).sqrt();
+        if norm == 0.0 {
+            return 0.0;
+        }
+        v = v.iter().map(|vi| (f64::from(*vi) / norm) as f32).collect();
+        let w = apply(&v);
+        eigenvalue = dot(&v, &w);
+        v = w;
+    }
+    eigenvalue as f32
+}
+
+/// Largest and smallest eigenvalues of `XᵀX`. The smallest comes from the
+/// shifted operator `cI - XᵀX`, so it is only resolved to about `c · ε`;
+/// anything below that reads as zero, which is what matters in f32.
+fn eigenvalue_range(x: &Matrix) -> (f32, f32) {
+    let largest = power_iteration(x.ncols(), |v| normal_apply(x, 0.0, v)).max(0.0);
+    let shifted = power_iteration(x.ncols(), |v| {
+        let mut out = normal_apply(x, -largest, v);
+        out.iter_mut().for_each(|o| *o = -*o);
+        out
+    });
+    (largest, (largest - shifted).max(0.0))
+}
+
+/// Solve `(XᵀX + λI) w = b` by conjugate gradient; returns the solution,
+/// the iterations used and whether the tolerance was reached
+fn conjugate_gradient(
+    x: &Matrix,
+    lambda: f32,
+    b: &[f32],
+    config: &RidgeConfig,
+) -> (Vec<f32>, usize, bool) {
+    let mut w = vec![0.0; b.len()];
+    let mut r = b.to_vec();
+    let mut p = r.clone();
+    let mut rr = dot(&r, &r);
+    let stop = f64::from(config.tolerance).powi(2) * rr;
+
+    for iteration in 0..config.max_iterations {
+        if rr <= stop {
+            return (w, iteration, true);
+        }
+        let ap = normal_apply(x, lambda, &p);
+        let curvature = dot(&p, &ap);
+        if !curvature.is_finite() || curvature <= 0.0 {
+            return (w, iteration, false);
+        }
+        let alpha = rr / curvature;
+        for ((wi, ri), (pi, api)) in w.iter_mut().zip(&mut r).zip(p.iter().zip(&ap)) {
+            *wi += (alpha * f64::from(*pi)) as f32;
+            *ri -= (alpha * f64::from(*api)) as f32;
+        }
+        let next = dot(&r, &r);
+        let beta = next / rr;
+        for (pi, ri) in p.iter_mut().zip(&r) {
+            *pi = (f64::from(*ri) + beta * f64::from(*pi)) as f32;
+        }
+        rr = next;
+    }
+    (w, config.max_iterations, rr <= stop)
+}
+
+/// Ridge regression: the `W` minimizing `‖XWᵀ - Y‖² + λ‖W‖²`
+///
+/// `x` holds one sample per row and `y` the matching targets; the result
+/// has one row per target column. Errors on mismatched shapes or
+/// non-finite data; an ill-conditioned or unconverged system is retried
+/// with a larger λ rather than returning garbage.
+pub fn ridge_regression(
+    x: &Matrix,
+    y: &Matrix,
+    lambda: f32,
+    config: &RidgeConfig,
+) -> Result<(Matrix, RidgeReport), String> {
+    if x.nrows() != y.nrows() {
+        return Err(format!("{} samples but {} targets", x.nrows(), y.nrows()));
+    }
+    if !x
+        .as_slice()
+        .iter()
+        .chain(y.as_slice())
+        .all(|v| v.is_finite())
+    {
+        return Err("training data contains NaN or infinite values".to_string());
+    }
+
+    let (largest, smallest) = eigenvalue_range(x);
+    // A zero or negative λ leaves a singular system whenever samples are
+    // fewer than features, so start from the smallest useful value
+    let mut lambda = if lambda > 0.0 {
+        lambda
+    } else {
+        f32::EPSILON * largest.max(1.0)
+    };
+    let condition = |lambda: f32| (largest + lambda) / (smallest + lambda);
+    while condition(lambda) > config.max_condition {
+        lambda *= 10.0;
+    }
+
+    let columns: Vec<Vec<f32>> = (0..y.ncols())
+        .map(|k| x.transpose_matvec(&(0..y.nrows()).map(|r| y[(r, k)]).collect::<Vec<_>>()))
+        .collect();
+    let mut bumps = 0;
+    loop {
+        let mut weights = Matrix::zeros(y.ncols(), x.ncols());
+        let mut report = RidgeReport {
+            lambda,
+            condition: condition(lambda),
+            iterations: 0,
+            converged: true,
+        };
+        for (k, b) in columns.iter().enumerate() {
+            let (w, iterations, converged) = conjugate_gradient(x, lambda, b, config);
+            weights.row_mut(k).copy_from_slice(&w);
+            report.iterations = report.iterations.max(iterations);
+            report.converged &= converged;
+        }
+        if report.converged || bumps >= config.max_bumps {
+            return Ok((weights, report));
+        }
+        lambda *= 10.0;
+        bumps += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matvec() {
+        let m = Matrix::from_fn(2, 3, |r, c| (r * 3 + c) as f32);
+        // [[0, 1, 2], [3, 4, 5]] * [1, 1, 1]
+        assert_eq!(m.matvec(&[1.0, 1.0, 1.0]), vec![3.0, 12.0]);
+    }
+
+    #[test]
+    fn test_transpose_matvec_and_outer() {
+        let m = Matrix::from_fn(2, 3, |r, c| (r * 3 + c) as f32);
+        // [[0, 3], [1, 4], [2, 5]] * [1, 2]
+        assert_eq!(m.transpose_matvec(&[1.0, 2.0]), vec![6.0, 9.0, 12.0]);
+
+        let o = Matrix::outer(&[1.0, 2.0], &[3.0, 4.0]);
+        assert_eq!(o.as_slice(), &[3.0, 4.0, 6.0, 8.0]);
+    }
+
+    #[test]
+    fn test_indexing() {
+        let mut m = Matrix::zeros(2, 2);
+        m[(1, 0)] = 4.0;
+        assert_eq!(m.row(1), &[4.0, 0.0]);
+        assert_eq!(m.as_slice(), &[0.0, 0.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_serialization_matches_nested_vec() {
+        let nested = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let Ok(m) = Matrix::try_from(nested.clone()) else {
+            panic!("rectangular rows should convert");
+        };
+
+        let Ok(json) = serde_json::to_string(&m) else {
+            panic!("to_string should succeed");
+        };
+        let Ok(expected) = serde_json::to_string(&nested) else {
+            panic!("to_string should succeed");
+        };
+        assert_eq!(json, expected);
+
+        let Ok(restored) = serde_json::from_str::<Matrix>(&json) else {
+            panic!("from_str should succeed");
+        };
+        assert_eq!(restored, m);
+    }
+
+    #[test]
+    fn test_ragged_rows_rejected() {
+        assert!(Matrix::try_from(vec![vec![1.0], vec![1.0, 2.0]]).is_err());
+        assert!(serde_json::from_str::<Matrix>("[[1.0],[1.0,2.0]]").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "matvec dimension mismatch")]
+    fn test_matvec_wrong_size() {
+        Matrix::zeros(2, 3).matvec(&[1.0]);
+    }
+
+    #[test]
+    fn test_ridge_regression_recovers_linear_map() {
+        // y = [2a - b, a + 3b] on well-spread samples
+        let x = Matrix::from_fn(20, 2, |r, c| ((r * 7 + c * 3) % 11) as f32 - 5.0);
+        let y = Matrix::from_fn(20, 2, |r, c| {
+            let (a, b) = (x[(r, 0)], x[(r, 1)]);
+            if c == 0 {
+                2.0 * a - b
+            } else {
+                a + 3.0 * b
+            }
+        });
+        let Ok((w, report)) = ridge_regression(&x, &y, 1e-6, &RidgeConfig::default()) else {
+            panic!("a well-posed problem should solve");
+        };
+        assert!(report.converged);
+        assert!(report.iterations <= 2);
+        for (got, want) in w.as_slice().iter().zip([2.0, -1.0, 1.0, 3.0]) {
+            assert!((got - want).abs() < 1e-3, "{} != {}", got, want);
+        }
+    }
+
+    #[test]
+    fn test_ridge_regression_bumps_lambda_when_ill_conditioned() {
+        // Identical rows: XᵀX has rank one, so a tiny λ is hopeless in f32
+        let x = Matrix::from_fn(10, 50, |_, _| 1.0);
+        let y = Matrix::from_fn(10, 1, |_, _| 0.5);
+        let config = RidgeConfig::default();
+        let Ok((w, report)) = ridge_regression(&x, &y, 1e-6, &config) else {
+            panic!("a rank-deficient problem should still solve");
+        };
+        assert!(report.lambda > 1e-6);
+        assert!(report.condition <= config.max_condition);
+        assert!(report.converged);
+        // Fitted values stay close to the targets
+        let fitted = x.matvec(w.row(0));
+        assert!(fitted.iter().all(|f| (f - 0.5).abs() < 0.01));
+
+        let bad = Matrix::from_fn(10, 1, |_, _| f32::NAN);
+        assert!(ridge_regression(&x, &bad, 1e-6, &config).is_err());
+        assert!(ridge_regression(&x, &Matrix::zeros(3, 1), 1e-6, &config).is_err());
+    }
+}
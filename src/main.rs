@@ -9,9 +9,20 @@
 //! mobile-ai "Your query here"
 //! mobile-ai --project oblibeny "Explain type system"
 //! mobile-ai --interactive
+//! mobile-ai config validate orchestrator.toml
+//! mobile-ai bench run report.json
+//! mobile-ai bench compare old.json new.json
+//! mobile-ai serve 127.0.0.1:8737   # with the `network` feature
 //! ```
+//!
+//! With the `minilm` feature, setting `MINILM_MODEL_DIR` to a directory
+//! holding `model.safetensors` and `vocab.txt` embeds queries with that
+//! model instead of hashed bag-of-words features.
 
-use mobile_ai_orchestrator::{Orchestrator, Query};
+use mobile_ai_orchestrator::bench::{self, BenchReport, BenchThresholds};
+use mobile_ai_orchestrator::config::{OrchestratorConfig, Severity};
+use mobile_ai_orchestrator::settings::Setting;
+use mobile_ai_orchestrator::{Orchestrator, Query, Response};
 use std::env;
 use std::io::{self, Write};
 
@@ -24,6 +35,11 @@ fn main() {
     match config.mode {
         Mode::Interactive => run_interactive(),
         Mode::SingleQuery { query, project } => run_single_query(&query, project.as_deref()),
+        Mode::ValidateConfig { path } => validate_config(&path),
+        Mode::BenchRun { out } => run_bench(out.as_deref()),
+        Mode::BenchCompare { old, new } => compare_bench(&old, &new),
+        #[cfg(feature = "network")]
+        Mode::Serve { addr } => run_server(addr),
         Mode::Help => print_help(),
         Mode::Version => print_version(),
     }
@@ -36,6 +52,20 @@ enum Mode {
         query: String,
         project: Option<String>,
     },
+    ValidateConfig {
+        path: String,
+    },
+    BenchRun {
+        out: Option<String>,
+    },
+    BenchCompare {
+        old: String,
+        new: String,
+    },
+    #[cfg(feature = "network")]
+    Serve {
+        addr: Option<String>,
+    },
     Help,
     Version,
 }
@@ -60,6 +90,38 @@ fn parse_args(args: &[String]) -> Config {
         "--interactive" | "-i" => Config {
             mode: Mode::Interactive,
         },
+        "config" if args.get(2).map(String::as_str) == Some("validate") => {
+            let Some(path) = args.get(3) else {
+                eprintln!("Error: config validate requires a file path");
+                std::process::exit(1);
+            };
+            Config {
+                mode: Mode::ValidateConfig { path: path.clone() },
+            }
+        }
+        "bench" if args.get(2).map(String::as_str) == Some("run") => Config {
+            mode: Mode::BenchRun {
+                out: args.get(3).cloned(),
+            },
+        },
+        "bench" if args.get(2).map(String::as_str) == Some("compare") => {
+            let (Some(old), Some(new)) = (args.get(3), args.get(4)) else {
+                eprintln!("Error: bench compare requires two report files");
+                std::process::exit(1);
+            };
+            Config {
+                mode: Mode::BenchCompare {
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+            }
+        }
+        #[cfg(feature = "network")]
+        "serve" => Config {
+            mode: Mode::Serve {
+                addr: args.get(2).cloned(),
+            },
+        },
         "--project" | "-p" => {
             if args.len() < 4 {
                 eprintln!("Error: --project requires a project name and query");
@@ -81,6 +143,21 @@ fn parse_args(args: &[String]) -> Config {
     }
 }
 
+/// Orchestrator with the on-device embedder from `MINILM_MODEL_DIR`, if set
+fn new_orchestrator() -> Orchestrator {
+    #[allow(unused_mut)]
+    let mut orchestrator = Orchestrator::new();
+    #[cfg(feature = "minilm")]
+    if let Ok(dir) = env::var("MINILM_MODEL_DIR") {
+        use mobile_ai_orchestrator::minilm::{MiniLmConfig, MiniLmEmbedder};
+        match MiniLmEmbedder::from_dir("minilm", &dir, MiniLmConfig::default()) {
+            Ok(embedder) => orchestrator.set_embedder(std::sync::Arc::new(embedder)),
+            Err(e) => eprintln!("Ignoring MINILM_MODEL_DIR: {}", e),
+        }
+    }
+    orchestrator
+}
+
 fn run_interactive() {
     println!("Mobile AI Orchestrator - Interactive Mode");
     println!("RSR Compliance: {}", mobile_ai_orchestrator::RSR_COMPLIANCE);
@@ -89,10 +166,14 @@ fn run_interactive() {
     println!("  /project <name> - Switch project context");
     println!("  /clear          - Clear conversation history");
     println!("  /history        - Show recent history");
+    println!("  /flashcards <f> - Export history as an Anki CSV deck");
+    println!("  /stats          - Show routing, latency, cache and energy metrics");
+    println!("  /settings       - Show runtime settings");
+    println!("  /set <k> [v]    - Change a setting (no value clears the default project)");
     println!("  /quit           - Exit");
     println!();
 
-    let mut orchestrator = Orchestrator::new();
+    let mut orchestrator = new_orchestrator();
 
     loop {
         print!("> ");
@@ -127,6 +208,9 @@ fn run_interactive() {
                     "\n[Route: {:?}, Confidence: {:.2}, Latency: {}ms]",
                     response.route, response.confidence, response.latency_ms
                 );
+                if env::var("VERBOSE").is_ok() {
+                    print_explanation(&response);
+                }
                 println!();
             }
             Err(err) => {
@@ -153,10 +237,49 @@ fn handle_command(orchestrator: &mut Orchestrator, cmd: &str) {
                 println!("Switched to project: {}", parts[1]);
             }
         }
+        "/flashcards" => {
+            if parts.len() < 2 {
+                eprintln!("Usage: /flashcards <file.csv>");
+            } else {
+                let project = orchestrator.current_project().map(str::to_string);
+                let name = project.as_deref().unwrap_or("Mobile AI");
+                let deck = orchestrator.flashcard_deck(name, project.as_deref(), &[]);
+                match std::fs::write(parts[1], deck.to_csv()) {
+                    Ok(()) => println!("Exported {} cards to {}", deck.len(), parts[1]),
+                    Err(e) => eprintln!("Export failed: {}", e),
+                }
+            }
+        }
         "/clear" => {
             orchestrator.clear_history();
             println!("History cleared");
         }
+        "/stats" => {
+            println!("{}", orchestrator.metrics_snapshot());
+            println!("{}", orchestrator.energy_report());
+        }
+        "/settings" => {
+            for setting in orchestrator.settings().all() {
+                println!("{}", setting);
+            }
+        }
+        "/set" => {
+            if parts.len() < 2 {
+                eprintln!("Usage: /set <key> [value]");
+            } else {
+                let result = Setting::parse(parts[1], &parts[2..].join(" ")).and_then(|setting| {
+                    let shown = setting.to_string();
+                    orchestrator
+                        .update_setting(setting)
+                        .map(|_| shown)
+                        .map_err(|e| e.to_string())
+                });
+                match result {
+                    Ok(shown) => println!("Set {}", shown),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        }
         "/history" => {
             let history = orchestrator.recent_history(5);
             if history.is_empty() {
@@ -181,7 +304,7 @@ fn handle_command(orchestrator: &mut Orchestrator, cmd: &str) {
 }
 
 fn run_single_query(query: &str, project: Option<&str>) {
-    let mut orchestrator = Orchestrator::new();
+    let mut orchestrator = new_orchestrator();
 
     if let Some(proj) = project {
         orchestrator.switch_project(proj);
@@ -196,6 +319,7 @@ fn run_single_query(query: &str, project: Option<&str>) {
                     "\n[Route: {:?}, Confidence: {:.2}, Latency: {}ms]",
                     response.route, response.confidence, response.latency_ms
                 );
+                print_explanation(&response);
             }
         }
         Err(err) => {
@@ -205,6 +329,141 @@ fn run_single_query(query: &str, project: Option<&str>) {
     }
 }
 
+/// Print why a response took its route (verbose mode)
+fn print_explanation(response: &Response) {
+    let Some(explanation) = &response.metadata.explanation else {
+        return;
+    };
+    for rule in &explanation.expert_rules {
+        eprintln!("  expert rule: {}", rule);
+    }
+    if let Some(reason) = &explanation.block_reason {
+        eprintln!("  blocked: {}", reason);
+    }
+    for rule in &explanation.heuristic_rules {
+        eprintln!("  heuristic rule: {}", rule);
+    }
+    for feature in &explanation.top_features {
+        eprintln!("  feature {}: {:+.3}", feature.name, feature.contribution);
+    }
+    for adjustment in &explanation.adjustments {
+        eprintln!("  adjustment: {}", adjustment);
+    }
+    for redaction in &explanation.redactions {
+        eprintln!(
+            "  redacted: {} ({} spans): {}",
+            redaction.rule_id, redaction.spans, redaction.reason
+        );
+    }
+    if let Some(fingerprint) = &response.metadata.state_fingerprint {
+        eprintln!("  state: {}", fingerprint);
+    }
+}
+
+fn validate_config(path: &str) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Error: cannot read {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let (config, diagnostics) = OrchestratorConfig::check(&text);
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", path, diagnostic);
+    }
+
+    let has_errors = diagnostics.iter().any(|d| d.severity == Severity::Error);
+    if config.is_none() || has_errors {
+        std::process::exit(1);
+    }
+    println!("{}: OK", path);
+}
+
+/// Run the built-in suite and write the report to `out`, or stdout
+fn run_bench(out: Option<&str>) {
+    let report = bench::run(OrchestratorConfig::default(), &bench::default_cases())
+        .and_then(|report| report.to_json());
+    let json = match report {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    match out {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, json) {
+                eprintln!("Error: cannot write {}: {}", path, err);
+                std::process::exit(1);
+            }
+            println!("Wrote {}", path);
+        }
+        None => println!("{}", json),
+    }
+}
+
+/// Compare two reports, exiting non-zero if `new` regressed
+fn compare_bench(old: &str, new: &str) {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path, e))
+            .and_then(|json| BenchReport::from_json(&json).map_err(|e| format!("{}: {}", path, e)))
+    };
+    let (old_report, new_report) = match (read(old), read(new)) {
+        (Ok(old_report), Ok(new_report)) => (old_report, new_report),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let regressions = bench::compare(&old_report, &new_report, &BenchThresholds::default());
+    if regressions.is_empty() {
+        println!(
+            "No regressions ({} -> {})",
+            old_report.version, new_report.version
+        );
+        return;
+    }
+    for regression in &regressions {
+        eprintln!("REGRESSION {}", regression);
+    }
+    std::process::exit(1);
+}
+
+/// Serve one orchestrator over the local HTTP API until killed
+#[cfg(feature = "network")]
+fn run_server(addr: Option<String>) {
+    use mobile_ai_orchestrator::secrets::EnvSecretProvider;
+    use mobile_ai_orchestrator::serve::{ServeConfig, Server, DEFAULT_TOKEN_VAR};
+    use std::sync::{Arc, Mutex};
+
+    let mut config = ServeConfig::default();
+    if let Some(addr) = addr {
+        config.addr = addr;
+    }
+    let orchestrator = Arc::new(Mutex::new(new_orchestrator()));
+    let server = match Server::bind(orchestrator, config, Arc::new(EnvSecretProvider)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!(
+                "Error: cannot serve: {} (clients authenticate with the token in {})",
+                e, DEFAULT_TOKEN_VAR
+            );
+            std::process::exit(1);
+        }
+    };
+    if let Ok(addr) = server.local_addr() {
+        println!("Serving on http://{}", addr);
+    }
+    if let Err(e) = server.run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
 fn print_help() {
     println!("Mobile AI Orchestrator v{}", mobile_ai_orchestrator::VERSION);
     println!("RSR Compliance: {}", mobile_ai_orchestrator::RSR_COMPLIANCE);
@@ -218,6 +477,14 @@ fn print_help() {
     println!("    -h, --help              Print help information");
     println!("    -v, --version           Print version information");
     println!();
+    println!("COMMANDS:");
+    println!("    config validate <FILE>  Check a TOML config and report problems");
+    println!("    bench run [FILE]        Write a JSON benchmark report");
+    println!("    bench compare <OLD> <NEW>");
+    println!("                            Flag regressions between two reports");
+    #[cfg(feature = "network")]
+    println!("    serve [ADDR]            Serve the HTTP API (default 127.0.0.1:8737)");
+    println!();
     println!("EXAMPLES:");
     println!("    mobile-ai \"How do I iterate a HashMap?\"");
     println!("    mobile-ai --project oblibeny \"Explain type system\"");
@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Long-Term Memory of the User
+//!
+//! An opt-in profile of durable facts picked up from conversations: "prefers
+//! metric units", "works on project oblibeny in Rust". [`extract`] finds
+//! first-person statements in the user's queries; [`MemoryStore`] keeps the
+//! facts, deduplicated and capped, where the user can list, edit and delete
+//! them. The prompt builder includes the most recently confirmed facts.
+//!
+//! Extraction is deliberately conservative: only plain statements ("I
+//! prefer...", "I work on...", "my name is...") are remembered, never
+//! questions, conditionals or long pasted text.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Longer texts are pasted material rather than the user speaking
+const MAX_EXTRACT_CHARS: usize = 1_000;
+
+/// Longest remembered detail, in words
+const MAX_DETAIL_WORDS: usize = 12;
+
+/// Phrases that introduce a fact, the kind of fact and how it is worded
+const PATTERNS: &[(&str, FactKind, &str)] = &[
+    ("my name is ", FactKind::Personal, "name is "),
+    ("call me ", FactKind::Personal, "name is "),
+    ("i live in ", FactKind::Personal, "lives in "),
+    ("i'm based in ", FactKind::Personal, "lives in "),
+    ("i am based in ", FactKind::Personal, "lives in "),
+    ("i work as ", FactKind::Personal, "works as "),
+    ("i'm working on ", FactKind::Project, "works on "),
+    ("i am working on ", FactKind::Project, "works on "),
+    ("i work on ", FactKind::Project, "works on "),
+    ("i'd rather ", FactKind::Preference, "prefers to "),
+    ("i would rather ", FactKind::Preference, "prefers to "),
+    ("i prefer ", FactKind::Preference, "prefers "),
+    ("i don't like ", FactKind::Preference, "dislikes "),
+    ("i do not like ", FactKind::Preference, "dislikes "),
+    ("i like ", FactKind::Preference, "likes "),
+    ("i always use ", FactKind::Preference, "uses "),
+    ("i use ", FactKind::Preference, "uses "),
+    (
+        "please always ",
+        FactKind::Preference,
+        "wants you to always ",
+    ),
+];
+
+/// Facts that can only hold one value; a new one replaces the old
+const SINGLE_VALUED: &[&str] = &["name is ", "lives in ", "works as "];
+
+/// Words that end a remembered detail
+const CLAUSE_BREAKS: &[&str] = &[" but ", " because ", " so ", " although ", " unless "];
+
+/// Long-term memory settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Extract facts from queries (opt-in)
+    pub enabled: bool,
+    /// Facts kept; the least recently confirmed are forgotten first
+    pub max_facts: usize,
+    /// Facts included in each prompt
+    pub prompt_facts: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_facts: 200,
+            prompt_facts: 8,
+        }
+    }
+}
+
+/// What a fact is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FactKind {
+    /// Likes, dislikes and how answers should look
+    Preference,
+    /// What the user is working on
+    Project,
+    /// Name, location, occupation
+    Personal,
+}
+
+impl fmt::Display for FactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Preference => "preference",
+            Self::Project => "project",
+            Self::Personal => "personal",
+        })
+    }
+}
+
+impl FromStr for FactKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preference" => Ok(Self::Preference),
+            "project" => Ok(Self::Project),
+            "personal" => Ok(Self::Personal),
+            other => Err(format!("unknown fact kind '{}'", other)),
+        }
+    }
+}
+
+/// One remembered fact about the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryFact {
+    /// Stable id, for editing and deleting
+    pub id: u64,
+    /// What the fact is about
+    pub kind: FactKind,
+    /// The fact, e.g. "prefers metric units"
+    pub text: String,
+    /// Turn the fact was last stated in; `None` when entered by hand
+    pub source_turn: Option<u64>,
+    /// When the fact was first remembered (Unix ms)
+    pub created_at: u64,
+    /// When the fact was last stated or edited (Unix ms)
+    pub updated_at: u64,
+}
+
+/// The user's remembered facts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStore {
+    facts: Vec<MemoryFact>,
+    next_id: u64,
+    max_facts: usize,
+}
+
+impl MemoryStore {
+    /// An empty store keeping at most `max_facts` facts
+    pub fn new(max_facts: usize) -> Self {
+        Self {
+            facts: Vec::new(),
+            next_id: 1,
+            max_facts: max_facts.max(1),
+        }
+    }
+
+    /// A store holding previously saved `facts`
+    pub fn from_facts(facts: Vec<MemoryFact>, max_facts: usize) -> Self {
+        let mut store = Self::new(max_facts);
+        store.next_id = facts.iter().map(|fact| fact.id + 1).max().unwrap_or(1);
+        store.facts = facts;
+        store.evict();
+        store
+    }
+
+    /// All facts, in the order they were first remembered
+    pub fn facts(&self) -> &[MemoryFact] {
+        &self.facts
+    }
+
+    /// The fact with `id`
+    pub fn get(&self, id: u64) -> Option<&MemoryFact> {
+        self.facts.iter().find(|fact| fact.id == id)
+    }
+
+    /// Number of facts
+    pub fn len(&self) -> usize {
+        self.facts.len()
+    }
+
+    /// Whether no facts are remembered
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+    }
+
+    /// Remember a fact, returning it. Restating a known fact refreshes it;
+    /// a new value of a single-valued fact ("name is ...") replaces the
+    /// old one.
+    pub fn remember(
+        &mut self,
+        kind: FactKind,
+        text: &str,
+        source_turn: Option<u64>,
+        now_ms: u64,
+    ) -> MemoryFact {
+        let text = text.trim().to_string();
+        let key = text.to_lowercase();
+        let single = SINGLE_VALUED.iter().find(|prefix| key.starts_with(*prefix));
+        let existing = self.facts.iter_mut().find(|fact| {
+            let known = fact.text.to_lowercase();
+            known == key || single.is_some_and(|prefix| known.starts_with(prefix))
+        });
+
+        let fact = match existing {
+            Some(fact) => {
+                fact.kind = kind;
+                fact.text = text;
+                fact.source_turn = source_turn.or(fact.source_turn);
+                fact.updated_at = now_ms;
+                fact.clone()
+            }
+            None => {
+                let fact = MemoryFact {
+                    id: self.next_id,
+                    kind,
+                    text,
+                    source_turn,
+                    created_at: now_ms,
+                    updated_at: now_ms,
+                };
+                self.next_id += 1;
+                self.facts.push(fact.clone());
+                fact
+            }
+        };
+        self.evict();
+        fact
+    }
+
+    /// Replace the wording of fact `id`
+    pub fn edit(&mut self, id: u64, text: &str, now_ms: u64) -> Result<MemoryFact, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("a fact cannot be empty".to_string());
+        }
+        let Some(fact) = self.facts.iter_mut().find(|fact| fact.id == id) else {
+            return Err(format!("no fact with id {}", id));
+        };
+        fact.text = text.to_string();
+        fact.updated_at = now_ms;
+        Ok(fact.clone())
+    }
+
+    /// Forget fact `id`, returning whether it existed
+    pub fn delete(&mut self, id: u64) -> bool {
+        let before = self.facts.len();
+        self.facts.retain(|fact| fact.id != id);
+        self.facts.len() < before
+    }
+
+    /// Forget everything, returning the ids of the forgotten facts
+    pub fn clear(&mut self) -> Vec<u64> {
+        self.facts.drain(..).map(|fact| fact.id).collect()
+    }
+
+    /// Prompt section listing the `limit` most recently confirmed facts,
+    /// one per line; `None` when nothing is remembered
+    pub fn prompt_section(&self, limit: usize) -> Option<String> {
+        let mut facts: Vec<&MemoryFact> = self.facts.iter().collect();
+        facts.sort_by_key(|fact| std::cmp::Reverse(fact.updated_at));
+        let lines: Vec<String> = facts
+            .into_iter()
+            .take(limit)
+            .map(|fact| format!("- {}", fact.text))
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// Drop the least recently confirmed facts beyond `max_facts`
+    fn evict(&mut self) {
+        while self.facts.len() > self.max_facts {
+            let Some(oldest) = self
+                .facts
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, fact)| fact.updated_at)
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            self.facts.remove(oldest);
+        }
+    }
+}
+
+/// Durable facts stated in `text`, worded in the third person
+pub fn extract(text: &str) -> Vec<(FactKind, String)> {
+    if text.chars().count() > MAX_EXTRACT_CHARS {
+        return Vec::new();
+    }
+    let mut facts = Vec::new();
+    for sentence in text.split_inclusive(['.', '!', '?', '\n', ';']) {
+        let sentence = sentence.trim();
+        // Questions and conditionals state nothing about the user
+        if sentence.ends_with('?') {
+            continue;
+        }
+        // ASCII lowercasing keeps byte offsets valid in `sentence`
+        let lower = sentence.to_ascii_lowercase();
+        if lower.starts_with("if ") || lower.starts_with("when ") {
+            continue;
+        }
+        let found = PATTERNS
+            .iter()
+            .filter_map(|&(phrase, kind, wording)| {
+                let at = find_phrase(&lower, phrase)?;
+                Some((at, phrase, kind, wording))
+            })
+            .min_by_key(|&(at, phrase, ..)| (at, std::cmp::Reverse(phrase.len())));
+        let Some((at, phrase, kind, wording)) = found else {
+            continue;
+        };
+        if let Some(detail) = detail(&sentence[at + phrase.len()..]) {
+            facts.push((kind, format!("{}{}", wording, detail)));
+        }
+    }
+    facts
+}
+
+/// Byte offset of `phrase` in `lower`, starting at a word boundary
+fn find_phrase(lower: &str, phrase: &str) -> Option<usize> {
+    lower.match_indices(phrase).map(|(at, _)| at).find(|&at| {
+        lower[..at]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !c.is_alphanumeric() && c != '\'')
+    })
+}
+
+/// The detail after a fact's phrase, up to the end of its clause
+fn detail(rest: &str) -> Option<String> {
+    let lower = rest.to_ascii_lowercase();
+    let mut end = rest.find([',', '.', '!', ';', '\n']).unwrap_or(rest.len());
+    for word in CLAUSE_BREAKS {
+        if let Some(at) = lower.find(word) {
+            end = end.min(at);
+        }
+    }
+    let detail = rest[..end].trim();
+    let words = detail.split_whitespace().count();
+    (words > 0 && words <= MAX_DETAIL_WORDS).then(|| detail.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_statements_but_not_questions() {
+        let facts = extract(
+            "I prefer metric units, thanks. I'm working on project oblibeny in Rust \
+             but it is slow. My name is Sam.",
+        );
+        assert_eq!(
+            facts,
+            vec![
+                (FactKind::Preference, "prefers metric units".to_string()),
+                (
+                    FactKind::Project,
+                    "works on project oblibeny in Rust".to_string()
+                ),
+                (FactKind::Personal, "name is Sam".to_string()),
+            ]
+        );
+
+        assert!(extract("Do I prefer tabs or spaces?").is_empty());
+        assert!(extract("If I like the result, I'll keep it.").is_empty());
+        assert!(extract("Sushi like this is my favourite.").is_empty());
+        assert!(extract(&"I prefer short answers. ".repeat(100)).is_empty());
+    }
+
+    #[test]
+    fn test_store_deduplicates_edits_and_evicts() {
+        let mut store = MemoryStore::new(2);
+        let first = store.remember(FactKind::Preference, "prefers metric units", Some(1), 10);
+        let again = store.remember(FactKind::Preference, "Prefers metric units", Some(4), 20);
+        assert_eq!(first.id, again.id);
+        assert_eq!((again.created_at, again.updated_at), (10, 20));
+
+        store.remember(FactKind::Personal, "lives in Leeds", None, 30);
+        let moved = store.remember(FactKind::Personal, "lives in Brno", None, 40);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.prompt_section(1), Some("- lives in Brno".to_string()));
+
+        let Ok(edited) = store.edit(moved.id, "lives in Brno, Czechia", 50) else {
+            panic!("editing a known fact should succeed");
+        };
+        assert_eq!(edited.text, "lives in Brno, Czechia");
+        assert!(store.edit(99, "anything", 50).is_err());
+
+        // The least recently confirmed fact makes room
+        store.remember(
+            FactKind::Project,
+            "works on a ferry timetable app",
+            None,
+            60,
+        );
+        assert_eq!(store.len(), 2);
+        assert!(store.get(first.id).is_none());
+
+        assert!(store.delete(moved.id));
+        assert!(!store.delete(moved.id));
+        let restored = MemoryStore::from_facts(store.facts().to_vec(), 2);
+        let added = MemoryStore::from_facts(restored.facts().to_vec(), 2).remember(
+            FactKind::Preference,
+            "likes short answers",
+            None,
+            70,
+        );
+        assert!(restored.facts().iter().all(|fact| fact.id < added.id));
+    }
+}
@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Telemetry and Metrics
+//!
+//! The orchestrator counts what it does in a [`MetricsRegistry`]:
+//!
+//! - answered queries and their latency, per route;
+//! - the router's confidence, per route, when the MLP made the decision
+//!   (heuristic confidences say little about the model);
+//! - response cache lookups, hits and misses;
+//! - blocked queries, per rule id;
+//! - answers that fell back to another route.
+//!
+//! `Orchestrator::metrics_snapshot` returns a [`MetricsSnapshot`], which
+//! the CLI prints with `/stats`. Counters live in memory; with
+//! `metrics.persist` they are also written to the attached persistence
+//! backend on every flush and restored on attach, so they span app
+//! restarts.
+//!
+//! ```toml
+//! [metrics]
+//! enabled = true
+//! persist = true
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::types::{Response, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Upper bounds of the latency buckets (ms); slower answers land in a
+/// final overflow bucket
+pub const LATENCY_BUCKETS_MS: [f64; 10] = [
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0,
+];
+
+/// Upper bounds of the confidence buckets, tenths of 0..=1
+pub const CONFIDENCE_BUCKETS: [f64; 10] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
+
+/// Metrics settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Count queries, latencies, cache lookups and blocks
+    pub enabled: bool,
+    /// Keep the counters in the persistence backend across restarts
+    pub persist: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            persist: false,
+        }
+    }
+}
+
+/// Fixed-bucket histogram
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Upper bound of each bucket, ascending
+    pub bounds: Vec<f64>,
+    /// Observations per bucket; one more than `bounds`, for overflow
+    pub counts: Vec<u64>,
+    /// Sum of all observations
+    pub sum: f64,
+    /// Largest observation
+    pub max: f64,
+}
+
+impl Histogram {
+    /// An empty histogram over `bounds`
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            max: 0.0,
+        }
+    }
+
+    /// Count one observation
+    pub fn record(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    /// Number of observations
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Mean observation; `None` when empty
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        (count > 0).then(|| self.sum / count as f64)
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (0..=1), or the
+    /// largest observation when that is the overflow bucket; `None` when
+    /// empty
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(self.bounds.get(bucket).copied().unwrap_or(self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+/// Counters for one route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteMetrics {
+    /// The route
+    pub route: RoutingDecision,
+    /// Queries it answered
+    pub count: u64,
+    /// Answer latency (ms)
+    pub latency_ms: Histogram,
+    /// Router confidence when the MLP chose this route
+    pub confidence: Histogram,
+}
+
+impl RouteMetrics {
+    fn new(route: RoutingDecision) -> Self {
+        Self {
+            route,
+            count: 0,
+            latency_ms: Histogram::new(&LATENCY_BUCKETS_MS),
+            confidence: Histogram::new(&CONFIDENCE_BUCKETS),
+        }
+    }
+}
+
+/// In-memory metrics counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsRegistry {
+    routes: Vec<RouteMetrics>,
+    cache_hits: u64,
+    cache_misses: u64,
+    blocked: BTreeMap<String, u64>,
+    degraded: u64,
+}
+
+impl MetricsRegistry {
+    /// Empty counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing has been counted
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+            && self.cache_hits == 0
+            && self.cache_misses == 0
+            && self.blocked.is_empty()
+    }
+
+    /// Count an answered query by its route and latency
+    pub fn record_response(&mut self, response: &Response) {
+        let route = self.route_mut(response.route);
+        route.count += 1;
+        route.latency_ms.record(response.latency_ms as f64);
+        if response.metadata.degraded.is_some() {
+            self.degraded += 1;
+        }
+    }
+
+    /// Record the confidence of an MLP routing decision
+    pub fn record_confidence(&mut self, route: RoutingDecision, confidence: f32) {
+        self.route_mut(route)
+            .confidence
+            .record(f64::from(confidence));
+    }
+
+    /// Count a response cache lookup
+    pub fn record_cache_lookup(&mut self, hit: bool) {
+        if hit {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    /// Count a query blocked by `rule_id`
+    pub fn record_blocked(&mut self, rule_id: &str) {
+        *self.blocked.entry(rule_id.to_string()).or_default() += 1;
+    }
+
+    /// Copy of the counters, busiest route first
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut routes = self.routes.clone();
+        routes.sort_by_key(|metrics| Reverse(metrics.count));
+        MetricsSnapshot {
+            routes,
+            cache_hits: self.cache_hits,
+            cache_misses: self.cache_misses,
+            blocked: self.blocked.clone(),
+            degraded: self.degraded,
+        }
+    }
+
+    fn route_mut(&mut self, route: RoutingDecision) -> &mut RouteMetrics {
+        let index = match self
+            .routes
+            .iter()
+            .position(|metrics| metrics.route == route)
+        {
+            Some(index) => index,
+            None => {
+                self.routes.push(RouteMetrics::new(route));
+                self.routes.len() - 1
+            }
+        };
+        &mut self.routes[index]
+    }
+}
+
+/// Metrics at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Per-route counters, busiest first
+    pub routes: Vec<RouteMetrics>,
+    /// Queries answered from the response cache
+    pub cache_hits: u64,
+    /// Cache lookups that found nothing usable
+    pub cache_misses: u64,
+    /// Blocked queries, by rule id
+    pub blocked: BTreeMap<String, u64>,
+    /// Answers produced by a fallback route
+    pub degraded: u64,
+}
+
+impl MetricsSnapshot {
+    /// Answered queries over all routes
+    pub fn total_queries(&self) -> u64 {
+        self.routes.iter().map(|route| route.count).sum()
+    }
+
+    /// Counters of `route`, if it answered anything
+    pub fn route(&self, route: RoutingDecision) -> Option<&RouteMetrics> {
+        self.routes.iter().find(|metrics| metrics.route == route)
+    }
+
+    /// Share of cache lookups that hit; `None` before any lookup
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Queries: {}", self.total_queries())?;
+        for route in &self.routes {
+            write!(f, "  {:?}: {}", route.route, route.count)?;
+            if let (Some(p50), Some(p95)) = (
+                route.latency_ms.quantile(0.5),
+                route.latency_ms.quantile(0.95),
+            ) {
+                write!(f, ", p50 <= {}ms, p95 <= {}ms", p50, p95)?;
+            }
+            if let Some(mean) = route.confidence.mean() {
+                write!(f, ", mean MLP confidence {:.2}", mean)?;
+            }
+            writeln!(f)?;
+        }
+        match self.cache_hit_rate() {
+            Some(rate) => writeln!(
+                f,
+                "Cache: {} hits, {} misses ({:.0}% hit rate)",
+                self.cache_hits,
+                self.cache_misses,
+                rate * 100.0
+            )?,
+            None => writeln!(f, "Cache: no lookups")?,
+        }
+        if self.degraded > 0 {
+            writeln!(f, "Fallbacks: {}", self.degraded)?;
+        }
+        let blocked: u64 = self.blocked.values().sum();
+        write!(f, "Blocked: {}", blocked)?;
+        for (rule_id, count) in &self.blocked {
+            write!(f, "\n  {}: {}", rule_id, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ResponseMetadata;
+
+    fn response(route: RoutingDecision, latency_ms: u64) -> Response {
+        Response {
+            text: String::new(),
+            route,
+            confidence: 1.0,
+            latency_ms,
+            metadata: ResponseMetadata {
+                model: None,
+                tokens: None,
+                cached: false,
+                context_budget: None,
+                turn_id: None,
+                explanation: None,
+                state_fingerprint: None,
+                degraded: None,
+                energy_joules: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_registry_counts_routes_cache_and_blocks() {
+        let mut metrics = MetricsRegistry::new();
+        assert!(metrics.is_empty());
+        for latency in [3, 40, 40, 7_000] {
+            metrics.record_response(&response(RoutingDecision::Local, latency));
+        }
+        metrics.record_response(&response(RoutingDecision::Remote, 800));
+        metrics.record_confidence(RoutingDecision::Local, 0.85);
+        metrics.record_cache_lookup(true);
+        metrics.record_cache_lookup(false);
+        metrics.record_cache_lookup(false);
+        metrics.record_blocked("rate_limit");
+        metrics.record_blocked("rate_limit");
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.total_queries(), 5);
+        assert_eq!(snapshot.routes[0].route, RoutingDecision::Local);
+        let Some(local) = snapshot.route(RoutingDecision::Local) else {
+            panic!("Local should have answered");
+        };
+        assert_eq!(local.latency_ms.quantile(0.5), Some(50.0));
+        assert_eq!(local.latency_ms.quantile(1.0), Some(7_000.0));
+        assert_eq!(local.confidence.counts[8], 1);
+        assert!(snapshot
+            .cache_hit_rate()
+            .is_some_and(|rate| (rate - 1.0 / 3.0).abs() < 1e-9));
+        assert_eq!(snapshot.blocked.get("rate_limit"), Some(&2));
+
+        let text = snapshot.to_string();
+        assert!(text.contains("Local: 4, p50 <= 50ms"));
+        assert!(text.contains("rate_limit: 2"));
+    }
+}
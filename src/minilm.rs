@@ -0,0 +1,742 @@
+// SPDX-License-Identifier: MPL-2.0
+//! On-Device Sentence Embeddings (MiniLM)
+//!
+//! A pure-Rust BERT encoder for small sentence-transformer models such as
+//! `all-MiniLM-L6-v2` (6 layers, 384 wide), built with the `minilm`
+//! feature. It reads weights from a `safetensors` file and a WordPiece
+//! `vocab.txt`, runs on the CPU with no extra dependencies, and mean-pools
+//! the last layer into a unit-length sentence vector. Plugged in as the
+//! [`Embedder`], it replaces hashed bag-of-words features for both routing
+//! and context retrieval.
+//!
+//! Tensors may be stored as `F32`, `F16` or `I8`. Quantized `I8` tensors
+//! carry their scale in the file's `__metadata__` under `"<tensor>.scale"`
+//! and are dequantized at load time, so a quantized file is a quarter of
+//! the download with the same runtime cost.
+//!
+//! Tokenization follows BERT's uncased tokenizer except that accents are
+//! not stripped and CJK characters are not split individually.
+
+#![forbid(unsafe_code)]
+
+use crate::embedding::Embedder;
+use crate::linalg::Matrix;
+use crate::tokens::TokenCounter;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Encoder settings not recorded in the weights file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiniLmConfig {
+    /// Attention heads per layer
+    pub heads: usize,
+    /// Longest token sequence embedded (including `[CLS]`/`[SEP]`);
+    /// longer text is truncated
+    pub max_tokens: usize,
+    /// LayerNorm epsilon
+    pub layer_norm_eps: f32,
+}
+
+impl Default for MiniLmConfig {
+    fn default() -> Self {
+        Self {
+            heads: 12,
+            max_tokens: 128,
+            layer_norm_eps: 1e-12,
+        }
+    }
+}
+
+/// BERT-style WordPiece tokenizer
+#[derive(Debug, Clone)]
+pub struct WordPieceTokenizer {
+    vocab: HashMap<String, u32>,
+    unk: u32,
+    cls: u32,
+    sep: u32,
+}
+
+/// Words longer than this (in characters) become `[UNK]`
+const MAX_WORD_CHARS: usize = 100;
+
+impl WordPieceTokenizer {
+    /// Parse a `vocab.txt`: one token per line, id = line number
+    pub fn from_vocab(text: &str) -> Result<Self, String> {
+        let vocab: HashMap<String, u32> = text
+            .lines()
+            .enumerate()
+            .map(|(id, token)| (token.trim_end().to_string(), id as u32))
+            .collect();
+        let special = |token: &str| {
+            vocab
+                .get(token)
+                .copied()
+                .ok_or_else(|| format!("vocabulary has no {} token", token))
+        };
+        Ok(Self {
+            unk: special("[UNK]")?,
+            cls: special("[CLS]")?,
+            sep: special("[SEP]")?,
+            vocab,
+        })
+    }
+
+    /// Number of tokens in the vocabulary
+    pub fn vocab_size(&self) -> usize {
+        self.vocab.len()
+    }
+
+    /// Token ids for `text`, wrapped in `[CLS]` ... `[SEP]` and truncated
+    /// to `max_tokens`
+    pub fn encode(&self, text: &str, max_tokens: usize) -> Vec<u32> {
+        let mut ids = vec![self.cls];
+        let budget = max_tokens.max(2) - 1;
+        for word in split_words(&text.to_lowercase()) {
+            for id in self.word_pieces(&word) {
+                if ids.len() == budget {
+                    ids.push(self.sep);
+                    return ids;
+                }
+                ids.push(id);
+            }
+        }
+        ids.push(self.sep);
+        ids
+    }
+
+    /// Greedy longest-match-first split of one word
+    fn word_pieces(&self, word: &str) -> Vec<u32> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > MAX_WORD_CHARS {
+            return vec![self.unk];
+        }
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let found = (start + 1..=chars.len()).rev().find_map(|end| {
+                let piece: String = chars[start..end].iter().collect();
+                let piece = if start > 0 {
+                    format!("##{}", piece)
+                } else {
+                    piece
+                };
+                self.vocab.get(&piece).map(|&id| (id, end))
+            });
+            let Some((id, end)) = found else {
+                return vec![self.unk];
+            };
+            pieces.push(id);
+            start = end;
+        }
+        pieces
+    }
+}
+
+impl TokenCounter for WordPieceTokenizer {
+    fn id(&self) -> String {
+        format!("wordpiece-{}", self.vocab_size())
+    }
+
+    /// WordPiece tokens of `text`, without `[CLS]` and `[SEP]`
+    fn count(&self, text: &str) -> usize {
+        self.encode(text, usize::MAX).len() - 2
+    }
+}
+
+/// Split on whitespace, with each punctuation character its own word
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for chunk in text.split_whitespace() {
+        let mut word = String::new();
+        for c in chunk.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                words.push(c.to_string());
+            }
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Tensors of a `safetensors` file, decoded on demand
+struct SafeTensors<'a> {
+    header: serde_json::Map<String, serde_json::Value>,
+    data: &'a [u8],
+}
+
+impl<'a> SafeTensors<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, String> {
+        let Some(len) = bytes.get(..8) else {
+            return Err("weights file is too short".to_string());
+        };
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(len);
+        let header_len = usize::try_from(u64::from_le_bytes(prefix))
+            .map_err(|_| "weights header is too large".to_string())?;
+        let header_end = header_len.saturating_add(8);
+        let header = bytes
+            .get(8..header_end)
+            .ok_or_else(|| "weights header runs past the end of the file".to_string())?;
+        let header =
+            serde_json::from_slice(header).map_err(|e| format!("invalid weights header: {}", e))?;
+        Ok(Self {
+            header,
+            data: &bytes[header_end..],
+        })
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.header.contains_key(name)
+    }
+
+    /// Shape and values of tensor `name`, dequantized to `f32`
+    fn get(&self, name: &str) -> Result<(Vec<usize>, Vec<f32>), String> {
+        let info = self
+            .header
+            .get(name)
+            .ok_or_else(|| format!("weights have no tensor '{}'", name))?;
+        let shape: Vec<usize> = info["shape"]
+            .as_array()
+            .map(|dims| {
+                dims.iter()
+                    .filter_map(|d| d.as_u64())
+                    .map(|d| d as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let offsets: Vec<usize> = info["data_offsets"]
+            .as_array()
+            .map(|o| {
+                o.iter()
+                    .filter_map(|v| v.as_u64())
+                    .map(|v| v as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let [start, end] = offsets[..] else {
+            return Err(format!("tensor '{}' has no data offsets", name));
+        };
+        let bytes = self
+            .data
+            .get(start..end)
+            .ok_or_else(|| format!("tensor '{}' runs past the end of the file", name))?;
+
+        let values: Vec<f32> = match info["dtype"].as_str() {
+            Some("F32") => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            Some("F16") => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            Some("I8") => {
+                let scale = self.scale(name)?;
+                bytes.iter().map(|&b| f32::from(b as i8) * scale).collect()
+            }
+            other => {
+                return Err(format!(
+                    "tensor '{}' has unsupported dtype {}",
+                    name,
+                    other.unwrap_or("(none)")
+                ))
+            }
+        };
+        if values.len() != shape.iter().product::<usize>() {
+            return Err(format!("tensor '{}' does not match its shape", name));
+        }
+        Ok((shape, values))
+    }
+
+    /// Dequantization scale of an `I8` tensor
+    fn scale(&self, name: &str) -> Result<f32, String> {
+        self.header
+            .get("__metadata__")
+            .and_then(|meta| meta.get(format!("{}.scale", name)))
+            .and_then(|scale| scale.as_str())
+            .and_then(|scale| scale.parse().ok())
+            .ok_or_else(|| format!("quantized tensor '{}' has no scale", name))
+    }
+}
+
+/// IEEE 754 half-precision bits to `f32`
+fn f16_to_f32(bits: u16) -> f32 {
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let fraction = f32::from(bits & 0x3ff);
+    let magnitude = match exponent {
+        0 => fraction * 2f32.powi(-24),
+        31 if fraction == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + fraction / 1024.0) * 2f32.powi(exponent - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Affine layer `W x + b`
+#[derive(Debug, Clone)]
+struct Linear {
+    weight: Matrix,
+    bias: Vec<f32>,
+}
+
+impl Linear {
+    fn forward(&self, x: &[f32]) -> Vec<f32> {
+        let mut y = self.weight.matvec(x);
+        for (y, b) in y.iter_mut().zip(&self.bias) {
+            *y += b;
+        }
+        y
+    }
+}
+
+/// Layer normalization with learned scale and shift
+#[derive(Debug, Clone)]
+struct LayerNorm {
+    gamma: Vec<f32>,
+    beta: Vec<f32>,
+}
+
+impl LayerNorm {
+    fn forward(&self, x: &mut [f32], eps: f32) {
+        let n = x.len() as f32;
+        let mean = x.iter().sum::<f32>() / n;
+        let variance = x.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        let inv = 1.0 / (variance + eps).sqrt();
+        for ((v, g), b) in x.iter_mut().zip(&self.gamma).zip(&self.beta) {
+            *v = (*v - mean) * inv * g + b;
+        }
+    }
+}
+
+/// One transformer encoder layer
+#[derive(Debug, Clone)]
+struct EncoderLayer {
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    attention_output: Linear,
+    attention_norm: LayerNorm,
+    intermediate: Linear,
+    output: Linear,
+    output_norm: LayerNorm,
+}
+
+impl EncoderLayer {
+    fn forward(&self, hidden: &[Vec<f32>], heads: usize, eps: f32) -> Vec<Vec<f32>> {
+        let queries: Vec<Vec<f32>> = hidden.iter().map(|h| self.query.forward(h)).collect();
+        let keys: Vec<Vec<f32>> = hidden.iter().map(|h| self.key.forward(h)).collect();
+        let values: Vec<Vec<f32>> = hidden.iter().map(|h| self.value.forward(h)).collect();
+        let width = hidden.first().map_or(0, Vec::len);
+        let head_dim = width / heads;
+        let scale = 1.0 / (head_dim as f32).sqrt();
+
+        hidden
+            .iter()
+            .zip(&queries)
+            .map(|(residual, query)| {
+                let mut context = vec![0.0; width];
+                for head in 0..heads {
+                    let span = head * head_dim..(head + 1) * head_dim;
+                    let scores: Vec<f32> = keys
+                        .iter()
+                        .map(|key| {
+                            query[span.clone()]
+                                .iter()
+                                .zip(&key[span.clone()])
+                                .map(|(q, k)| q * k)
+                                .sum::<f32>()
+                                * scale
+                        })
+                        .collect();
+                    for (weight, value) in softmax(&scores).iter().zip(&values) {
+                        for (c, v) in context[span.clone()].iter_mut().zip(&value[span.clone()]) {
+                            *c += weight * v;
+                        }
+                    }
+                }
+
+                let mut attended = self.attention_output.forward(&context);
+                add(&mut attended, residual);
+                self.attention_norm.forward(&mut attended, eps);
+
+                let inner: Vec<f32> = self
+                    .intermediate
+                    .forward(&attended)
+                    .into_iter()
+                    .map(gelu)
+                    .collect();
+                let mut out = self.output.forward(&inner);
+                add(&mut out, &attended);
+                self.output_norm.forward(&mut out, eps);
+                out
+            })
+            .collect()
+    }
+}
+
+fn add(x: &mut [f32], y: &[f32]) {
+    for (a, b) in x.iter_mut().zip(y) {
+        *a += b;
+    }
+}
+
+fn softmax(x: &[f32]) -> Vec<f32> {
+    let max = x.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = x.iter().map(|v| (v - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+/// Exact (erf-based) GELU, as used by BERT
+fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + erf(x / std::f32::consts::SQRT_2))
+}
+
+/// Error function (Abramowitz & Stegun 7.1.26, |error| < 1.5e-7)
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_8 + t * (-1.453_152_1 + t * 1.061_405_4))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+/// MiniLM / BERT sentence embedder
+#[derive(Debug, Clone)]
+pub struct MiniLmEmbedder {
+    name: String,
+    config: MiniLmConfig,
+    tokenizer: WordPieceTokenizer,
+    word_embeddings: Matrix,
+    position_embeddings: Matrix,
+    token_type_embedding: Vec<f32>,
+    embedding_norm: LayerNorm,
+    layers: Vec<EncoderLayer>,
+}
+
+impl MiniLmEmbedder {
+    /// Load a model from `safetensors` bytes and `vocab.txt` text
+    pub fn from_bytes(
+        name: impl Into<String>,
+        weights: &[u8],
+        vocab: &str,
+        config: MiniLmConfig,
+    ) -> Result<Self, String> {
+        let tensors = SafeTensors::parse(weights)?;
+        // Checkpoints exported from a full BertModel prefix every name.
+        let prefix = if tensors.contains("bert.embeddings.word_embeddings.weight") {
+            "bert."
+        } else {
+            ""
+        };
+        let matrix = |name: &str| -> Result<Matrix, String> {
+            let (shape, values) = tensors.get(&format!("{}{}", prefix, name))?;
+            let [rows, cols] = shape[..] else {
+                return Err(format!("tensor '{}' is not a matrix", name));
+            };
+            Ok(Matrix::from_fn(rows, cols, |r, c| values[r * cols + c]))
+        };
+        let vector = |name: &str| -> Result<Vec<f32>, String> {
+            Ok(tensors.get(&format!("{}{}", prefix, name))?.1)
+        };
+        let linear = |name: &str| -> Result<Linear, String> {
+            Ok(Linear {
+                weight: matrix(&format!("{}.weight", name))?,
+                bias: vector(&format!("{}.bias", name))?,
+            })
+        };
+        let norm = |name: &str| -> Result<LayerNorm, String> {
+            Ok(LayerNorm {
+                gamma: vector(&format!("{}.weight", name))?,
+                beta: vector(&format!("{}.bias", name))?,
+            })
+        };
+
+        let mut layers = Vec::new();
+        while tensors.contains(&format!(
+            "{}encoder.layer.{}.attention.self.query.weight",
+            prefix,
+            layers.len()
+        )) {
+            let layer = format!("encoder.layer.{}", layers.len());
+            layers.push(EncoderLayer {
+                query: linear(&format!("{}.attention.self.query", layer))?,
+                key: linear(&format!("{}.attention.self.key", layer))?,
+                value: linear(&format!("{}.attention.self.value", layer))?,
+                attention_output: linear(&format!("{}.attention.output.dense", layer))?,
+                attention_norm: norm(&format!("{}.attention.output.LayerNorm", layer))?,
+                intermediate: linear(&format!("{}.intermediate.dense", layer))?,
+                output: linear(&format!("{}.output.dense", layer))?,
+                output_norm: norm(&format!("{}.output.LayerNorm", layer))?,
+            });
+        }
+        if layers.is_empty() {
+            return Err("weights have no encoder layers".to_string());
+        }
+
+        let tokenizer = WordPieceTokenizer::from_vocab(vocab)?;
+        let word_embeddings = matrix("embeddings.word_embeddings.weight")?;
+        let width = word_embeddings.ncols();
+        if config.heads == 0 || width % config.heads != 0 {
+            return Err(format!(
+                "hidden width {} is not divisible into {} heads",
+                width, config.heads
+            ));
+        }
+        if word_embeddings.nrows() < tokenizer.vocab_size() {
+            return Err(format!(
+                "vocabulary has {} tokens but the model embeds {}",
+                tokenizer.vocab_size(),
+                word_embeddings.nrows()
+            ));
+        }
+        let token_types = matrix("embeddings.token_type_embeddings.weight")?;
+
+        Ok(Self {
+            name: name.into(),
+            config,
+            tokenizer,
+            word_embeddings,
+            position_embeddings: matrix("embeddings.position_embeddings.weight")?,
+            token_type_embedding: token_types.row(0).to_vec(),
+            embedding_norm: norm("embeddings.LayerNorm")?,
+            layers,
+        })
+    }
+
+    /// Load `model.safetensors` and `vocab.txt` from `dir`
+    pub fn from_dir(
+        name: impl Into<String>,
+        dir: impl AsRef<Path>,
+        config: MiniLmConfig,
+    ) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let weights = std::fs::read(dir.join("model.safetensors"))
+            .map_err(|e| format!("failed to read model weights: {}", e))?;
+        let vocab = std::fs::read_to_string(dir.join("vocab.txt"))
+            .map_err(|e| format!("failed to read vocabulary: {}", e))?;
+        Self::from_bytes(name, &weights, &vocab, config)
+    }
+
+    /// Number of encoder layers
+    pub fn layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The tokenizer
+    pub fn tokenizer(&self) -> &WordPieceTokenizer {
+        &self.tokenizer
+    }
+}
+
+impl Embedder for MiniLmEmbedder {
+    fn id(&self) -> String {
+        format!("minilm-{}-{}", self.name, self.dimension())
+    }
+
+    fn dimension(&self) -> usize {
+        self.word_embeddings.ncols()
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        let max_tokens = self.config.max_tokens.min(self.position_embeddings.nrows());
+        let ids = self.tokenizer.encode(text, max_tokens);
+
+        let mut hidden: Vec<Vec<f32>> = ids
+            .iter()
+            .enumerate()
+            .map(|(position, &id)| {
+                let mut h = self.word_embeddings.row(id as usize).to_vec();
+                add(&mut h, self.position_embeddings.row(position));
+                add(&mut h, &self.token_type_embedding);
+                self.embedding_norm
+                    .forward(&mut h, self.config.layer_norm_eps);
+                h
+            })
+            .collect();
+        for layer in &self.layers {
+            hidden = layer.forward(&hidden, self.config.heads, self.config.layer_norm_eps);
+        }
+
+        // Mean pooling over tokens, then unit length
+        let mut pooled = vec![0.0; self.dimension()];
+        for h in &hidden {
+            add(&mut pooled, h);
+        }
+        let magnitude = pooled.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if magnitude > 0.0 {
+            for v in &mut pooled {
+                *v /= magnitude;
+            }
+        }
+        Ok(pooled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOCAB: &str = "[PAD]\n[UNK]\n[CLS]\n[SEP]\nhello\nworld\n##s\nthe\n,\ncat\n";
+    const WIDTH: usize = 4;
+
+    /// Tensor names and shapes of a one-layer, 4-wide encoder
+    fn tiny_shapes() -> Vec<(String, Vec<usize>)> {
+        let mut shapes = Vec::new();
+        let mut push = |name: &str, shape: Vec<usize>| shapes.push((name.to_string(), shape));
+        push("embeddings.word_embeddings.weight", vec![10, WIDTH]);
+        push("embeddings.position_embeddings.weight", vec![16, WIDTH]);
+        push("embeddings.token_type_embeddings.weight", vec![2, WIDTH]);
+        push("embeddings.LayerNorm.weight", vec![WIDTH]);
+        push("embeddings.LayerNorm.bias", vec![WIDTH]);
+        for (name, out, inp) in [
+            ("attention.self.query", WIDTH, WIDTH),
+            ("attention.self.key", WIDTH, WIDTH),
+            ("attention.self.value", WIDTH, WIDTH),
+            ("attention.output.dense", WIDTH, WIDTH),
+            ("intermediate.dense", 8, WIDTH),
+            ("output.dense", WIDTH, 8),
+        ] {
+            push(&format!("encoder.layer.0.{}.weight", name), vec![out, inp]);
+            push(&format!("encoder.layer.0.{}.bias", name), vec![out]);
+        }
+        for name in ["attention.output.LayerNorm", "output.LayerNorm"] {
+            push(&format!("encoder.layer.0.{}.weight", name), vec![WIDTH]);
+            push(&format!("encoder.layer.0.{}.bias", name), vec![WIDTH]);
+        }
+        shapes
+    }
+
+    /// Serialize the tiny encoder with deterministic weights, as `F32`
+    /// or as `I8` with a per-tensor scale
+    fn tiny_model(quantized: bool) -> Vec<u8> {
+        let mut header = serde_json::Map::new();
+        let mut metadata = serde_json::Map::new();
+        let mut data = Vec::new();
+        for (i, (name, shape)) in tiny_shapes().into_iter().enumerate() {
+            let count: usize = shape.iter().product();
+            let values: Vec<f32> = (0..count)
+                .map(|j| ((i * 31 + j * 7) as f32 * 0.37).sin() * 0.5)
+                .collect();
+            let start = data.len();
+            if quantized {
+                let scale = 0.5 / 127.0;
+                metadata.insert(format!("{}.scale", name), scale.to_string().into());
+                data.extend(values.iter().map(|v| (v / scale).round() as i8 as u8));
+            } else {
+                data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+            }
+            header.insert(
+                name,
+                serde_json::json!({
+                    "dtype": if quantized { "I8" } else { "F32" },
+                    "shape": shape,
+                    "data_offsets": [start, data.len()],
+                }),
+            );
+        }
+        header.insert("__metadata__".to_string(), metadata.into());
+
+        let header = serde_json::Value::Object(header).to_string().into_bytes();
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header);
+        bytes.extend(data);
+        bytes
+    }
+
+    fn config() -> MiniLmConfig {
+        MiniLmConfig {
+            heads: 2,
+            ..MiniLmConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_wordpiece_tokenization() {
+        let Ok(tokenizer) = WordPieceTokenizer::from_vocab(VOCAB) else {
+            panic!("vocabulary with special tokens should parse");
+        };
+        // [CLS] hello , the world ##s [UNK] [SEP]
+        assert_eq!(
+            tokenizer.encode("Hello, the worlds dog", 32),
+            vec![2, 4, 8, 7, 5, 6, 1, 3]
+        );
+        assert_eq!(tokenizer.encode("hello world the cat", 4), vec![2, 4, 5, 3]);
+        assert!(WordPieceTokenizer::from_vocab("hello\n").is_err());
+    }
+
+    #[test]
+    fn test_embeddings_are_unit_length_and_distinguish_text() {
+        let Ok(model) = MiniLmEmbedder::from_bytes("tiny", &tiny_model(false), VOCAB, config())
+        else {
+            panic!("tiny model should load");
+        };
+        assert_eq!(model.layers(), 1);
+        assert_eq!(model.dimension(), WIDTH);
+        assert_eq!(model.id(), "minilm-tiny-4");
+
+        let (Ok(a), Ok(again), Ok(b)) = (
+            model.embed("hello world"),
+            model.embed("hello world"),
+            model.embed("the cat"),
+        ) else {
+            panic!("embedding never fails once loaded");
+        };
+        let norm: f32 = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-4);
+        assert_eq!(a, again);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_quantized_weights_track_full_precision() {
+        let (Ok(full), Ok(quantized)) = (
+            MiniLmEmbedder::from_bytes("tiny", &tiny_model(false), VOCAB, config()),
+            MiniLmEmbedder::from_bytes("tiny", &tiny_model(true), VOCAB, config()),
+        ) else {
+            panic!("both encodings should load");
+        };
+        let (Ok(a), Ok(b)) = (full.embed("hello world"), quantized.embed("hello world")) else {
+            panic!("embedding never fails once loaded");
+        };
+        let cosine: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        assert!(cosine > 0.95, "cosine {}", cosine);
+
+        assert!(MiniLmEmbedder::from_bytes(
+            "tiny",
+            &tiny_model(false),
+            VOCAB,
+            MiniLmConfig {
+                heads: 3,
+                ..config()
+            }
+        )
+        .is_err());
+        assert!(MiniLmEmbedder::from_bytes("tiny", &[0; 4], VOCAB, config()).is_err());
+    }
+
+    #[test]
+    fn test_f16_conversion() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 0.333_251_95);
+        assert!(f16_to_f32(0x7c00).is_infinite());
+        assert!((erf(0.5) - 0.520_499_9).abs() < 1e-6);
+    }
+}
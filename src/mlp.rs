@@ -15,60 +15,110 @@
 //!    flow across layers.
 //! 3. **Persistence**: Fully serializable via `serde` for on-device model storage.
 
+use crate::linalg::Matrix;
+use crate::rng::SeededRng;
 use serde::{Deserialize, Serialize};
 
+/// Start of the fixed generator behind [`MLP::new`], restarted for every
+/// layer.
+const DEFAULT_SEED: u64 = 42;
+
+/// GRADIENTS: Per-parameter values shaped like the network's weights and
+/// biases. Produced by `MLP::backward` and consumed by `MLP::update`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradients {
+    /// One matrix per layer, same shape as the layer's weights.
+    pub weights: Vec<Matrix>,
+    /// One vector per layer, same length as the layer's biases.
+    pub biases: Vec<Vec<f32>>,
+}
+
+impl Gradients {
+    /// All-zero gradients shaped like `mlp`.
+    pub fn zeros_like(mlp: &MLP) -> Self {
+        Self {
+            weights: mlp
+                .weights
+                .iter()
+                .map(|w| Matrix::zeros(w.nrows(), w.ncols()))
+                .collect(),
+            biases: mlp.biases.iter().map(|b| vec![0.0; b.len()]).collect(),
+        }
+    }
+}
+
+/// LOSS: Objective used by `MLP::backward_with_loss`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Loss {
+    /// Half squared error on the raw linear outputs (regression-style).
+    #[default]
+    SquaredError,
+    /// Softmax over the outputs followed by cross-entropy against the
+    /// target distribution. The right choice for classification.
+    SoftmaxCrossEntropy,
+}
+
 /// MLP: The neural network container.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MLP {
     input_size: usize,
     hidden_sizes: Vec<usize>,
     output_size: usize,
-    weights: Vec<Vec<Vec<f32>>>, // [Layer][Row][Col]
+    weights: Vec<Matrix>, // [Layer][Row, Col]
     biases: Vec<Vec<f32>>,
 }
 
 impl MLP {
     /// Create a new MLP with given layer sizes.
+    ///
+    /// Uses the original fixed initialisation, so default models keep
+    /// their weights; `new_with_seed` draws them from a chosen seed.
     pub fn new(input_size: usize, hidden_sizes: Vec<usize>, output_size: usize) -> Self {
+        Self::with_layers(input_size, hidden_sizes, output_size, |rows, cols, limit| {
+            let mut seed = DEFAULT_SEED;
+            Matrix::from_fn(rows, cols, |_, _| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
+                (rand - 0.5) * 2.0 * limit
+            })
+        })
+    }
+
+    /// Create a new MLP whose initial weights are fully determined by `seed`.
+    pub fn new_with_seed(
+        input_size: usize,
+        hidden_sizes: Vec<usize>,
+        output_size: usize,
+        seed: u64,
+    ) -> Self {
+        let mut rng = SeededRng::new(seed);
+        Self::with_layers(input_size, hidden_sizes, output_size, |rows, cols, limit| {
+            Matrix::from_fn(rows, cols, |_, _| rng.next_symmetric(limit))
+        })
+    }
+
+    /// Build the layers (hidden, then output) with zero biases and the
+    /// weights `init(rows, cols, xavier_limit)` returns.
+    fn with_layers(
+        input_size: usize,
+        hidden_sizes: Vec<usize>,
+        output_size: usize,
+        mut init: impl FnMut(usize, usize, f32) -> Matrix,
+    ) -> Self {
         let mut weights = Vec::new();
         let mut biases = Vec::new();
         let mut prev_size = input_size;
 
-        // Initialize weights and biases
-        for &hidden_size in &hidden_sizes {
-            let mut layer_weights = vec![vec![0.0; prev_size]; hidden_size];
-            let mut seed = 42u64;
-
+        // Initialize weights and biases (hidden layers, then output layer)
+        let layer_sizes = hidden_sizes.iter().copied().chain(std::iter::once(output_size));
+        for layer_size in layer_sizes {
             // Xavier initialization
-            let limit = (6.0 / (prev_size + hidden_size) as f32).sqrt();
-            for row in &mut layer_weights {
-                for w in row {
-                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                    let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-                    *w = (rand - 0.5) * 2.0 * limit;
-                }
-            }
-
-            weights.push(layer_weights);
-            biases.push(vec![0.0; hidden_size]);
-            prev_size = hidden_size;
+            let limit = (6.0 / (prev_size + layer_size) as f32).sqrt();
+            weights.push(init(layer_size, prev_size, limit));
+            biases.push(vec![0.0; layer_size]);
+            prev_size = layer_size;
         }
 
-        // Output layer
-        let mut output_weights = vec![vec![0.0; prev_size]; output_size];
-        let mut seed = 42u64;
-        let limit = (6.0 / (prev_size + output_size) as f32).sqrt();
-        for row in &mut output_weights {
-            for w in row {
-                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-                *w = (rand - 0.5) * 2.0 * limit;
-            }
-        }
-
-        weights.push(output_weights);
-        biases.push(vec![0.0; output_size]);
-
         Self {
             input_size,
             hidden_sizes,
@@ -86,15 +136,11 @@ pub fn forward(&self, input: &[f32]) -> Vec<f32> {
         // Forward pass through all layers
         for (i, layer_weights) in self.weights.iter().enumerate() {
             let is_output = i == self.weights.len() - 1;
-            let mut next_activation = self.biases[i].clone();
-
-            // Matrix-vector multiplication
-            for (j, weights_row) in layer_weights.iter().enumerate() {
-                let mut sum = 0.0;
-                for (k, w) in weights_row.iter().enumerate() {
-                    sum += w * activation[k];
-                }
-                next_activation[j] += sum;
+
+            // Matrix-vector multiplication plus bias
+            let mut next_activation = layer_weights.matvec(&activation);
+            for (a, b) in next_activation.iter_mut().zip(&self.biases[i]) {
+                *a += b;
             }
 
             // Apply activation function
@@ -110,6 +156,21 @@ pub fn forward(&self, input: &[f32]) -> Vec<f32> {
         activation
     }
 
+    /// FORWARD BATCH: Computes network outputs for many inputs at once.
+    /// Parallelized across inputs with rayon under the `high-perf` feature.
+    pub fn forward_batch(&self, inputs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        #[cfg(feature = "high-perf")]
+        {
+            use rayon::prelude::*;
+            inputs.par_iter().map(|input| self.forward(input)).collect()
+        }
+
+        #[cfg(not(feature = "high-perf"))]
+        {
+            inputs.iter().map(|input| self.forward(input)).collect()
+        }
+    }
+
     /// SOFTMAX: Normalizes logits into a probability distribution.
     /// Returns a vector where `sum(values) == 1.0`.
     pub fn softmax(values: &[f32]) -> Vec<f32> {
@@ -124,26 +185,138 @@ pub fn softmax(values: &[f32]) -> Vec<f32> {
         }
     }
 
+    /// Compute loss and gradients via backpropagation with
+    /// [`Loss::SquaredError`].
+    pub fn backward(&self, input: &[f32], target: &[f32]) -> (f32, Gradients) {
+        self.backward_with_loss(input, target, Loss::SquaredError)
+    }
+
     /// Compute loss and gradients via backpropagation.
-    pub fn backward(&self, input: &[f32], target: &[f32]) -> (f32, Vec<Vec<Vec<f32>>>) {
-        let output = self.forward(input);
+    ///
+    /// The output-layer error is `output - target` for squared error and
+    /// `softmax(output) - target` for softmax cross-entropy; it is then
+    /// propagated back through the ReLU hidden layers.
+    pub fn backward_with_loss(
+        &self,
+        input: &[f32],
+        target: &[f32],
+        loss_fn: Loss,
+    ) -> (f32, Gradients) {
+        // Forward pass, keeping each layer's input and pre-activation.
+        let mut layer_inputs = Vec::with_capacity(self.weights.len());
+        let mut pre_activations = Vec::with_capacity(self.weights.len());
+        let mut activation = input.to_vec();
 
-        // Cross-entropy loss
-        let mut loss = 0.0;
-        for (o, t) in output.iter().zip(target.iter()) {
-            let o = o.clamp(1e-6, 1.0 - 1e-6);
-            loss -= t * o.ln();
+        for (i, layer_weights) in self.weights.iter().enumerate() {
+            let mut z = layer_weights.matvec(&activation);
+            for (a, b) in z.iter_mut().zip(&self.biases[i]) {
+                *a += b;
+            }
+
+            layer_inputs.push(activation);
+            activation = if i == self.weights.len() - 1 {
+                z.clone()
+            } else {
+                z.iter().map(|&x| x.max(0.0)).collect()
+            };
+            pre_activations.push(z);
         }
+        let output = activation;
+
+        let (loss, mut delta): (f32, Vec<f32>) = match loss_fn {
+            Loss::SquaredError => (
+                output
+                    .iter()
+                    .zip(target)
+                    .map(|(o, t)| 0.5 * (o - t).powi(2))
+                    .sum(),
+                output.iter().zip(target).map(|(o, t)| o - t).collect(),
+            ),
+            Loss::SoftmaxCrossEntropy => {
+                let probs = Self::softmax(&output);
+                let loss = -probs
+                    .iter()
+                    .zip(target)
+                    .map(|(p, t)| t * p.max(1e-7).ln())
+                    .sum::<f32>();
+                (loss, probs.iter().zip(target).map(|(p, t)| p - t).collect())
+            }
+        };
 
-        // Placeholder gradients (proper backprop deferred to Phase 2)
-        let gradients = vec![vec![vec![0.0; input.len()]; self.output_size]; self.weights.len()];
+        // Backward pass
+        let mut gradients = Gradients::zeros_like(self);
+
+        for i in (0..self.weights.len()).rev() {
+            gradients.weights[i] = Matrix::outer(&delta, &layer_inputs[i]);
+            gradients.biases[i] = delta.clone();
+
+            if i > 0 {
+                // Propagate through the weights and the previous layer's ReLU.
+                delta = self.weights[i]
+                    .transpose_matvec(&delta)
+                    .iter()
+                    .zip(&pre_activations[i - 1])
+                    .map(|(d, &z)| if z > 0.0 { *d } else { 0.0 })
+                    .collect();
+            }
+        }
 
         (loss, gradients)
     }
 
-    /// Update weights using gradients.
-    pub fn update(&mut self, _gradients: &[Vec<Vec<f32>>], _learning_rate: f32) {
-        // Phase 2 implementation
+    /// Update weights using gradients: `param -= learning_rate * gradient`.
+    pub fn update(&mut self, gradients: &Gradients, learning_rate: f32) {
+        for (w, g) in self.weights.iter_mut().zip(&gradients.weights) {
+            for (w, g) in w.as_mut_slice().iter_mut().zip(g.as_slice()) {
+                *w -= learning_rate * g;
+            }
+        }
+        for (b, g) in self.biases.iter_mut().zip(&gradients.biases) {
+            for (b, g) in b.iter_mut().zip(g) {
+                *b -= learning_rate * g;
+            }
+        }
+    }
+
+    /// Add weight-penalty gradients to `gradients`.
+    ///
+    /// L2 adds `l2_reg * w` (weight decay); L1 adds `l1_reg * sign(w)`,
+    /// which drives small weights to zero. Biases are not penalized.
+    pub fn add_weight_penalty(&self, gradients: &mut Gradients, l2_reg: f32, l1_reg: f32) {
+        if l2_reg == 0.0 && l1_reg == 0.0 {
+            return;
+        }
+        for (g, w) in gradients.weights.iter_mut().zip(&self.weights) {
+            for (g, &w) in g.as_mut_slice().iter_mut().zip(w.as_slice()) {
+                let sign = if w > 0.0 {
+                    1.0
+                } else if w < 0.0 {
+                    -1.0
+                } else {
+                    0.0
+                };
+                *g += l2_reg * w + l1_reg * sign;
+            }
+        }
+    }
+
+    /// Squared L2 norm of all weights (biases excluded).
+    pub fn weight_norm_sq(&self) -> f32 {
+        self.weights
+            .iter()
+            .flat_map(|w| w.as_slice())
+            .map(|w| w * w)
+            .sum()
+    }
+
+    /// Borrow the per-layer weight matrices.
+    pub fn weights(&self) -> &[Matrix] {
+        &self.weights
+    }
+
+    /// Borrow the per-layer bias vectors.
+    pub fn biases(&self) -> &[Vec<f32>] {
+        &self.biases
     }
 
     /// Number of input features the network expects.
@@ -174,3 +347,130 @@ pub fn argmax(values: &[f32]) -> usize {
             .unwrap_or(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_batch_matches_forward() {
+        let mlp = MLP::new(8, vec![6], 3);
+        let inputs: Vec<Vec<f32>> = (0..5).map(|i| vec![i as f32 * 0.1; 8]).collect();
+
+        let batch = mlp.forward_batch(&inputs);
+        assert_eq!(batch.len(), inputs.len());
+        for (input, output) in inputs.iter().zip(&batch) {
+            assert_eq!(&mlp.forward(input), output);
+        }
+    }
+
+    #[test]
+    fn test_default_initialisation_is_unchanged() {
+        let mlp = MLP::new(2, vec![2], 1);
+        let limit = (6.0f32 / 4.0).sqrt();
+        // First draws of the original generator; each layer restarts it
+        let expected = [0.16461182f32, 0.03961182, -0.0680542, 0.5540161];
+        for (w, e) in mlp.weights()[0].as_slice().iter().zip(expected) {
+            assert!((w - e * limit).abs() < 1e-6, "{} != {}", w, e * limit);
+        }
+        let output_limit = (6.0f32 / 3.0).sqrt();
+        let first = mlp.weights()[1].as_slice()[0];
+        assert!((first - expected[0] * output_limit).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_backward_matches_numerical_gradient() {
+        let mlp = MLP::new_with_seed(4, vec![5], 2, 3);
+        let input = [0.3, -0.2, 0.8, 0.1];
+        let target = [1.0, 0.0];
+
+        // Half squared error, whose gradient is `output - target`.
+        let half_sq_error = |m: &MLP| -> f32 {
+            m.forward(&input)
+                .iter()
+                .zip(&target)
+                .map(|(o, t)| 0.5 * (o - t).powi(2))
+                .sum()
+        };
+
+        let (_, gradients) = mlp.backward(&input, &target);
+        let eps = 1e-3;
+        for layer in 0..mlp.weights.len() {
+            for idx in 0..mlp.weights[layer].as_slice().len() {
+                let mut plus = mlp.clone();
+                plus.weights[layer].as_mut_slice()[idx] += eps;
+                let mut minus = mlp.clone();
+                minus.weights[layer].as_mut_slice()[idx] -= eps;
+
+                let numerical = (half_sq_error(&plus) - half_sq_error(&minus)) / (2.0 * eps);
+                let analytic = gradients.weights[layer].as_slice()[idx];
+                assert!(
+                    (numerical - analytic).abs() < 1e-2,
+                    "layer {} idx {}: numerical {} vs analytic {}",
+                    layer,
+                    idx,
+                    numerical,
+                    analytic
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_softmax_cross_entropy_gradient() {
+        let mlp = MLP::new_with_seed(4, vec![5], 3, 11);
+        let input = [0.3, -0.2, 0.8, 0.1];
+        let target = [0.0, 1.0, 0.0];
+
+        let (loss, gradients) = mlp.backward_with_loss(&input, &target, Loss::SoftmaxCrossEntropy);
+        let probs = MLP::softmax(&mlp.forward(&input));
+        assert!((loss + probs[1].ln()).abs() < 1e-5);
+
+        // Output bias gradient is exactly softmax(output) - target
+        let Some(output_bias) = gradients.biases.last() else {
+            panic!("network should have an output layer");
+        };
+        for ((g, p), t) in output_bias.iter().zip(&probs).zip(&target) {
+            assert!((g - (p - t)).abs() < 1e-6);
+        }
+
+        // Hidden-layer gradients match finite differences of the loss
+        let ce = |m: &MLP| -> f32 { -MLP::softmax(&m.forward(&input))[1].ln() };
+        let eps = 1e-3;
+        for idx in 0..mlp.weights[0].as_slice().len() {
+            let mut plus = mlp.clone();
+            plus.weights[0].as_mut_slice()[idx] += eps;
+            let mut minus = mlp.clone();
+            minus.weights[0].as_mut_slice()[idx] -= eps;
+
+            let numerical = (ce(&plus) - ce(&minus)) / (2.0 * eps);
+            assert!((numerical - gradients.weights[0].as_slice()[idx]).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_train_step_reduces_error() {
+        let mut mlp = MLP::new(3, vec![8], 2);
+        let input = [0.5, -0.5, 0.25];
+        let target = [1.0, 0.0];
+        let error = |m: &MLP| -> f32 {
+            m.forward(&input)
+                .iter()
+                .zip(&target)
+                .map(|(o, t)| (o - t).powi(2))
+                .sum()
+        };
+
+        let before = error(&mlp);
+        for _ in 0..50 {
+            mlp.train_step(&input, &target, 0.05);
+        }
+        assert!(error(&mlp) < before);
+    }
+
+    #[test]
+    fn test_forward_batch_empty() {
+        let mlp = MLP::new(4, vec![4], 2);
+        assert!(mlp.forward_batch(&[]).is_empty());
+    }
+}
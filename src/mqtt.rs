@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: MPL-2.0
+//! MQTT Sensor Streams
+//!
+//! Wearables and IoT peripherals usually publish to an MQTT broker rather
+//! than talking to the phone directly. With the `mqtt` feature the
+//! orchestrator subscribes to their topics and turns each message into
+//! [`SensorReading`]s for its sensor buffer, which feeds ambient
+//! classification and the reservoir like the phone's own sensors:
+//!
+//! ```toml
+//! [mqtt]
+//! host = "192.168.1.10"
+//! username = "orchestrator"
+//! password = "${MQTT_PASSWORD}"
+//!
+//! [[mqtt.topics]]
+//! filter = "wearable/+/accel"
+//! sensor = "Accelerometer"
+//!
+//! [[mqtt.topics]]
+//! filter = "garden/light"
+//! sensor = "Light"
+//! format = "text"
+//!
+//! [[mqtt.topics]]
+//! filter = "watch/batch"
+//! format = "batch"
+//! ```
+//!
+//! Payload formats:
+//!
+//! - `json` (default): `[x, y, z]`, `{"values": [...], "timestamp_ms": ..,
+//!   "accuracy": "High"}` or an array of such objects;
+//! - `text`: numbers separated by commas or whitespace, e.g. `"0.1,9.8,0.2"`;
+//! - `batch`: the binary `sensor_batch` format, whose records name their
+//!   own sensors (the mapping's `sensor` is not used).
+//!
+//! A message takes the first mapping whose filter matches its topic.
+//! Messages are received on a supervised background task, reconnecting
+//! with the supervisor's backoff when the broker goes away, and wait in a
+//! channel until `Orchestrator::poll_sensor_streams` collects them.
+//! Malformed messages are counted and dropped.
+
+#![forbid(unsafe_code)]
+
+use crate::secrets::{SecretProvider, SecretRef};
+use crate::sensor::{SensorAccuracy, SensorReading, SensorType};
+use crate::sensor_batch;
+use crate::supervisor::{Supervisor, TaskContext};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS, RecvTimeoutError};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often an idle receive task checks for shutdown
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// MQTT broker and topic settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker host name or address
+    pub host: String,
+    /// Broker port
+    pub port: u16,
+    /// Client id presented to the broker
+    pub client_id: String,
+    /// User name, if the broker requires one
+    pub username: Option<String>,
+    /// Password for `username`
+    pub password: Option<SecretRef>,
+    /// Keep-alive interval (s)
+    pub keep_alive_secs: u64,
+    /// Topics to subscribe to and how to read them
+    pub topics: Vec<TopicMapping>,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "mobile-ai-orchestrator".to_string(),
+            username: None,
+            password: None,
+            keep_alive_secs: 30,
+            topics: Vec::new(),
+        }
+    }
+}
+
+impl MqttConfig {
+    /// Problems that would stop the adapter from starting
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keep_alive_secs == 0 {
+            return Err("mqtt.keep_alive_secs must be at least 1".to_string());
+        }
+        for topic in &self.topics {
+            if !rumqttc::valid_filter(&topic.filter) {
+                return Err(format!("invalid MQTT topic filter '{}'", topic.filter));
+            }
+            if topic.sensor.is_none() && topic.format != PayloadFormat::Batch {
+                return Err(format!(
+                    "MQTT topic '{}' needs a sensor unless its format is batch",
+                    topic.filter
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Readings carried by a message on `topic`, or `None` if no mapping
+    /// matches it
+    pub fn decode(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> Option<Result<Vec<SensorReading>, String>> {
+        self.topics
+            .iter()
+            .find(|mapping| rumqttc::matches(topic, &mapping.filter))
+            .map(|mapping| mapping.decode(payload))
+    }
+}
+
+/// How a topic's payloads are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormat {
+    /// JSON values or reading objects
+    #[default]
+    Json,
+    /// Numbers separated by commas or whitespace
+    Text,
+    /// The binary `sensor_batch` format
+    Batch,
+}
+
+/// Topics whose messages become readings of one sensor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopicMapping {
+    /// Topic filter, with `+` and `#` wildcards
+    pub filter: String,
+    /// Sensor the readings come from; not needed for `batch`
+    #[serde(default)]
+    pub sensor: Option<SensorType>,
+    /// Payload format
+    #[serde(default)]
+    pub format: PayloadFormat,
+}
+
+/// A reading object in a JSON payload
+#[derive(Debug, Deserialize)]
+struct JsonReading {
+    values: Vec<f32>,
+    #[serde(default)]
+    timestamp_ms: Option<u64>,
+    #[serde(default)]
+    accuracy: Option<SensorAccuracy>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonPayload {
+    Values(Vec<f32>),
+    Readings(Vec<JsonReading>),
+    Reading(JsonReading),
+}
+
+impl TopicMapping {
+    /// Readings carried by `payload`
+    pub fn decode(&self, payload: &[u8]) -> Result<Vec<SensorReading>, String> {
+        match (self.format, self.sensor) {
+            (PayloadFormat::Batch, _) => sensor_batch::decode(payload),
+            (_, None) => Err(format!("topic '{}' has no sensor", self.filter)),
+            (PayloadFormat::Json, Some(sensor_type)) => decode_json(sensor_type, payload),
+            (PayloadFormat::Text, Some(sensor_type)) => decode_text(sensor_type, payload),
+        }
+    }
+}
+
+fn decode_json(sensor_type: SensorType, payload: &[u8]) -> Result<Vec<SensorReading>, String> {
+    let from_json = |reading: JsonReading| {
+        let mut converted = match reading.timestamp_ms {
+            Some(timestamp) => {
+                SensorReading::with_timestamp(sensor_type, reading.values, timestamp)
+            }
+            None => SensorReading::new(sensor_type, reading.values),
+        };
+        if let Some(accuracy) = reading.accuracy {
+            converted = converted.with_accuracy(accuracy);
+        }
+        converted
+    };
+    match serde_json::from_slice(payload) {
+        Ok(JsonPayload::Values(values)) => Ok(vec![SensorReading::new(sensor_type, values)]),
+        Ok(JsonPayload::Readings(readings)) => Ok(readings.into_iter().map(from_json).collect()),
+        Ok(JsonPayload::Reading(reading)) => Ok(vec![from_json(reading)]),
+        Err(e) => Err(format!("invalid JSON sensor payload: {}", e)),
+    }
+}
+
+fn decode_text(sensor_type: SensorType, payload: &[u8]) -> Result<Vec<SensorReading>, String> {
+    let text =
+        std::str::from_utf8(payload).map_err(|_| "text sensor payload is not UTF-8".to_string())?;
+    let values = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|e| format!("invalid number in sensor payload: {}", e))?;
+    Ok(vec![SensorReading::new(sensor_type, values)])
+}
+
+/// Message counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MqttStats {
+    /// Messages turned into readings
+    pub messages: u64,
+    /// Readings produced
+    pub readings: u64,
+    /// Messages dropped as malformed
+    pub rejected: u64,
+}
+
+/// Receives sensor messages from the broker in the background
+pub struct MqttSensorStream {
+    receiver: Receiver<Vec<SensorReading>>,
+    stats: Arc<Mutex<MqttStats>>,
+}
+
+impl std::fmt::Debug for MqttSensorStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttSensorStream")
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MqttSensorStream {
+    /// Start the receive task under `supervisor`; the password is
+    /// resolved through `provider` on every (re)connect
+    pub fn start(
+        config: MqttConfig,
+        provider: Arc<dyn SecretProvider>,
+        supervisor: &mut Supervisor,
+    ) -> Result<Self, String> {
+        config.validate()?;
+        let (sender, receiver) = mpsc::channel();
+        let stats = Arc::new(Mutex::new(MqttStats::default()));
+
+        let worker_stats = Arc::clone(&stats);
+        let body = move |context: &TaskContext| {
+            let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+            options.set_keep_alive(Duration::from_secs(config.keep_alive_secs));
+            if let Some(username) = &config.username {
+                let password = match &config.password {
+                    Some(password) => password.resolve(provider.as_ref())?.expose().to_string(),
+                    None => String::new(),
+                };
+                options.set_credentials(username, password);
+            }
+            let (client, mut connection) = Client::new(options, 16);
+            for topic in &config.topics {
+                client
+                    .subscribe(&topic.filter, QoS::AtMostOnce)
+                    .map_err(|e| format!("cannot subscribe to '{}': {}", topic.filter, e))?;
+            }
+
+            // Held while the app is in the background
+            while !context.wait_while_paused() {
+                let publish = match connection.recv_timeout(IDLE_POLL) {
+                    Ok(Ok(Event::Incoming(Packet::Publish(publish)))) => publish,
+                    Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(Err(e)) => return Err(format!("MQTT connection failed: {}", e)),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let Some(decoded) = config.decode(&publish.topic, &publish.payload) else {
+                    continue;
+                };
+                if let Ok(mut stats) = worker_stats.lock() {
+                    match &decoded {
+                        Ok(readings) => {
+                            stats.messages += 1;
+                            stats.readings += readings.len() as u64;
+                        }
+                        Err(_) => stats.rejected += 1,
+                    }
+                }
+                if let Ok(readings) = decoded {
+                    // The stream and its orchestrator are gone
+                    if sender.send(readings).is_err() {
+                        break;
+                    }
+                }
+            }
+            // Best effort: the broker drops the session when the socket
+            // closes anyway.
+            let _ = client.disconnect();
+            Ok(())
+        };
+        supervisor.spawn("mqtt", Box::new(body))?;
+
+        Ok(Self { receiver, stats })
+    }
+
+    /// Readings received since the last call
+    pub fn drain(&self) -> Vec<SensorReading> {
+        self.receiver.try_iter().flatten().collect()
+    }
+
+    /// Message counters so far
+    pub fn stats(&self) -> MqttStats {
+        self.stats.lock().map(|stats| *stats).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MqttConfig {
+        let Ok(config) = toml::from_str::<MqttConfig>(
+            r#"
+            [[topics]]
+            filter = "wearable/+/accel"
+            sensor = "Accelerometer"
+
+            [[topics]]
+            filter = "garden/light"
+            sensor = "Light"
+            format = "text"
+
+            [[topics]]
+            filter = "watch/#"
+            format = "batch"
+            "#,
+        ) else {
+            panic!("mqtt config should parse");
+        };
+        config
+    }
+
+    #[test]
+    fn test_payloads_become_readings_of_the_mapped_sensor() {
+        let config = config();
+        assert_eq!(config.validate(), Ok(()));
+
+        let Some(Ok(values)) = config.decode("wearable/left/accel", b"[0.1, 9.8, 0.2]") else {
+            panic!("a value array should decode");
+        };
+        assert_eq!(values[0].sensor_type, SensorType::Accelerometer);
+        assert_eq!(values[0].values, vec![0.1, 9.8, 0.2]);
+
+        let objects = br#"[{"values": [1.0, 2.0, 3.0], "timestamp_ms": 42, "accuracy": "High"},
+                           {"values": [4.0, 5.0, 6.0]}]"#;
+        let Some(Ok(readings)) = config.decode("wearable/right/accel", objects) else {
+            panic!("reading objects should decode");
+        };
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].timestamp_ms, 42);
+        assert_eq!(readings[0].accuracy, SensorAccuracy::High);
+
+        let Some(Ok(light)) = config.decode("garden/light", b" 310.5\n") else {
+            panic!("a text payload should decode");
+        };
+        assert_eq!(light[0].sensor_type, SensorType::Light);
+        assert_eq!(light[0].values, vec![310.5]);
+
+        let batch = sensor_batch::encode(&[SensorReading::with_timestamp(
+            SensorType::Gyroscope,
+            vec![0.0, 0.1, 0.0],
+            7,
+        )]);
+        let Some(Ok(watch)) = config.decode("watch/imu", &batch) else {
+            panic!("a batch payload should decode");
+        };
+        assert_eq!(watch[0].sensor_type, SensorType::Gyroscope);
+
+        assert!(matches!(
+            config.decode("garden/light", b"bright"),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            config.decode("wearable/left/accel", b"{"),
+            Some(Err(_))
+        ));
+        assert!(config.decode("kitchen/temperature", b"21.5").is_none());
+    }
+
+    #[test]
+    fn test_invalid_mappings_do_not_start() {
+        let mut supervisor = Supervisor::default();
+        let mut config = config();
+        config.topics[0].sensor = None;
+        let provider = Arc::new(crate::secrets::EnvSecretProvider);
+        let started = MqttSensorStream::start(config, provider.clone(), &mut supervisor);
+        assert!(started.is_err_and(|e| e.contains("needs a sensor")));
+
+        let mut config = MqttConfig::default();
+        config.topics.push(TopicMapping {
+            filter: "a/#/b".to_string(),
+            sensor: Some(SensorType::Light),
+            format: PayloadFormat::Text,
+        });
+        assert!(MqttSensorStream::start(config, provider, &mut supervisor).is_err());
+        assert_eq!(supervisor.shutdown(), 0);
+    }
+}
@@ -14,27 +14,453 @@
 //! 4. **Persistence**: The turn is recorded in the Context Manager for
 //!    long-term memory.
 
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+#[cfg(feature = "persistence")]
+use crate::persistence::{PersistenceManager, SensorLog};
+#[cfg(feature = "mqtt")]
+use crate::mqtt::{MqttSensorStream, MqttStats};
+#[cfg(any(feature = "network", feature = "mqtt"))]
+use crate::secrets::SecretProvider;
+#[cfg(feature = "network")]
+use crate::webhooks::{WebhookDispatcher, WebhookStats};
 use crate::{
-    context::ContextManager,
-    expert::ExpertSystem,
-    router::{Router, RouterConfig},
-    types::{ConversationTurn, Query, Response, ResponseMetadata, RoutingDecision},
+    activity::{Activity, ActivityEstimate, ActivityRecognizer},
+    ambient::{AmbientClassifier, AmbientState},
+    blend::{self, BlendConfig, Candidate},
+    cache::{CacheQuery, CacheStats, ResponseCache},
+    calibration::Calibrator,
+    capabilities::{Capabilities, SensorAvailability, SensorFeature, SensorRegistry},
+    chunking::{self, ChunkedRun},
+    clock::{now_ms, Stopwatch},
+    code_context,
+    config::OrchestratorConfig,
+    context::{ContextManager, Session, TurnMatch, MAX_HISTORY_SIZE},
+    context_budget::ContextBudgetController,
+    context_switch::{ContextEvent, ContextSwitchDetector},
+    device::{DeviceState, DeviceStateProvider},
+    digest::{Digest, DigestOutcome, DigestRun, DigestScheduler},
+    embedding::Embedder,
+    energy::{EnergyLedger, EnergyReport, ProjectEnergy},
+    error::OrchestratorError,
+    events::{Event, EventBus, SubscriptionId},
+    fallback::{Degradation, FailureKind, FallbackConfig},
+    expert::{self, ExpertSystem},
+    fingerprint::StateFingerprint,
+    flashcards::Deck,
+    features::{FeatureExtractor, RouterModel},
+    forecast::{Forecast, Forecaster},
+    fusion::{Orientation, OrientationFilter},
+    geofence::{Geofence, GeofenceManager},
+    host::{self, HostDelegate},
+    hybrid::{self, HybridStrategy},
+    import::{self, Deduplicator, ImportControl, ImportOptions, ImportProgress},
+    journal::JournalExporter,
+    lifecycle::{LifecycleEvent, LifecycleReport, LifecycleState},
+    memory::{self, FactKind, MemoryFact, MemoryStore},
+    metrics::{MetricsRegistry, MetricsSnapshot},
+    personalization::{SignalTracker, UserSignals},
+    pool::{LocalModel, ModelPool, PoolStats},
+    plugin_api::{ConversationStore, PostProcessor, QueryRule},
+    profile::Profile,
+    prompt::{Prompt, PromptBuilder},
+    queue::{QueryQueue, QueueOutcome, QueueRun, QueuedQuery},
+    quota::{InFlight, QuotaTracker},
+    supervisor::{Supervisor, TaskBody},
+    regenerate::{Alternative, PreferenceExample, RegenerateOptions},
+    requirements::MissingCapabilities,
+    reward::{RewardFn, RewardWeights, RouteOutcome},
+    router::Router,
+    sampling::{SamplingCommand, SamplingController},
+    sensor::{SensorBuffer, SensorReading, SensorType},
+    sensor_batch,
+    settings::{Setting, Settings},
+    sla::{RouteSlaStatus, SlaTracker, SlaViolation},
+    targets::{RouteTarget, TargetBackend},
+    tokens::{TokenBudget, TokenCounter},
+    training::OnlineTrainer,
+    triage,
+    types::{
+        Attachment, ConversationTurn, Query, Response, ResponseMetadata, RoutingDecision,
+        RoutingExplanation,
+    },
+    user::UserProfile,
 };
 
+/// Config key under which the context/session state is saved on shutdown.
+pub const SESSION_STATE_KEY: &str = "session_state";
+
+/// Config key under which the learned personalization signals are saved.
+pub const USER_SIGNALS_KEY: &str = "user_signals";
+
+/// Config key under which query and remote token usage is saved.
+pub const QUOTA_USAGE_KEY: &str = "quota_usage";
+
+/// Config key under which the offline query queue is saved.
+pub const QUERY_QUEUE_KEY: &str = "query_queue";
+
+/// Config key under which the metrics counters are saved.
+pub const METRICS_KEY: &str = "metrics";
+
+/// Config key under which a database records the profile of its user.
+pub const USER_PROFILE_KEY: &str = "user_profile";
+
+/// Prefix of the config keys under which bulk imports are checkpointed,
+/// followed by the import's source name.
+pub const IMPORT_CHECKPOINT_PREFIX: &str = "import:";
+
+/// Number of recent turns that can still receive feedback or be
+/// regenerated.
+const FEEDBACK_WINDOW: usize = 64;
+
+/// Preference examples kept before the oldest are dropped.
+const MAX_PREFERENCES: usize = 1_000;
+
+/// Model name reported by the Phase 1 placeholder backend.
+const DEFAULT_MODEL: &str = "orchestrator-phase1";
+
+/// Model name reported for blended Hybrid answers.
+const HYBRID_MODEL: &str = "local+remote";
+
+/// Model reported when the pooled local model answers (see
+/// `set_local_model`)
+const LOCAL_MODEL: &str = "local";
+
+/// Milliseconds per day, for once-a-day budget warnings.
+const DAY_MS: u64 = 86_400_000;
+
+/// Sensor readings kept from `push_sensor_batch`
+const SENSOR_BUFFER_CAPACITY: usize = 512;
+
+/// SHUTDOWN REPORT: What `Orchestrator::shutdown` did during teardown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShutdownReport {
+    /// Conversation turns written from the write-behind buffer.
+    pub turns_flushed: usize,
+    /// Whether the context/session state was persisted.
+    pub session_saved: bool,
+    /// Whether the reservoir state was persisted.
+    pub reservoir_saved: bool,
+    /// Chat sessions persisted so they can be resumed.
+    pub sessions_saved: usize,
+    /// Pending sampling commands that were never delivered to the host.
+    pub sampling_commands_discarded: usize,
+    /// Event subscribers (including the host delegate) released.
+    pub subscribers_released: usize,
+    /// Storage backends closed.
+    pub backends_closed: usize,
+    /// Background task threads joined.
+    pub tasks_joined: usize,
+    /// Non-fatal errors encountered; teardown continues past them.
+    pub errors: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Whether teardown completed without errors.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// A processed turn kept for feedback and regeneration.
+#[derive(Debug, Clone)]
+struct RecentTurn {
+    id: u64,
+    query: Query,
+    features: Vec<f32>,
+    response: Response,
+    alternatives: Vec<Alternative>,
+}
+
+/// Everything belonging to a user who is not active.
+struct UserState {
+    profile: UserProfile,
+    router: Router,
+    online: OnlineTrainer,
+    context: ContextManager,
+    session: Option<Session>,
+    parked_sessions: HashMap<String, (Session, ContextManager)>,
+    parked_default: Option<ContextManager>,
+    recent_turns: VecDeque<RecentTurn>,
+    preferences: Vec<PreferenceExample>,
+    memory: MemoryStore,
+    signals: SignalTracker,
+    digests: DigestScheduler,
+    queue: QueryQueue,
+    cache: ResponseCache,
+    project_summaries: HashMap<String, String>,
+    knowledge_packs: HashMap<String, Vec<Attachment>>,
+    settings: Settings,
+    journal: Option<JournalExporter>,
+    metrics: MetricsRegistry,
+    energy: EnergyLedger,
+    forecaster: Forecaster,
+    forecast_warned_day: Option<u64>,
+    sensor_buffer: SensorBuffer,
+    last_prompt: Option<Prompt>,
+    #[cfg(feature = "persistence")]
+    persistence: Option<PersistenceManager>,
+}
+
 /// Orchestrator: Coordinates the full AI pipeline.
 pub struct Orchestrator {
     router: Router,
     expert: ExpertSystem,
     context: ContextManager,
+    context_budget: ContextBudgetController,
+    ambient: AmbientClassifier,
+    /// Built on the first motion readings; fitting it takes a moment
+    activity: Option<ActivityRecognizer>,
+    activity_estimate: ActivityEstimate,
+    context_switch: ContextSwitchDetector,
+    fusion: OrientationFilter,
+    geofences: GeofenceManager,
+    sampling: SamplingController,
+    sampling_outbox: Vec<SamplingCommand>,
+    sensors: SensorRegistry,
+    sensor_buffer: SensorBuffer,
+    events: EventBus,
+    host: Option<(Arc<dyn HostDelegate>, SubscriptionId)>,
+    device: Option<Arc<dyn DeviceStateProvider>>,
+    #[cfg(feature = "persistence")]
+    persistence: Option<PersistenceManager>,
+    /// Turns not yet written to persistence, with their project.
+    #[cfg(feature = "persistence")]
+    pending_turns: Vec<(Option<String>, ConversationTurn)>,
+    /// When the oldest of `pending_turns` was buffered.
+    #[cfg(feature = "persistence")]
+    pending_since_ms: Option<u64>,
+    /// Quota usage changed since it was last written.
+    #[cfg(feature = "persistence")]
+    quota_dirty: bool,
+    lifecycle: LifecycleState,
+    shut_down: bool,
+    /// Recent turns, for feedback and regeneration.
+    recent_turns: VecDeque<RecentTurn>,
+    preferences: Vec<PreferenceExample>,
+    next_turn_id: u64,
+    online: OnlineTrainer,
+    /// Scores rated turns and preferences for router training.
+    reward: Arc<dyn RewardFn>,
+    sla: SlaTracker,
+    forecaster: Forecaster,
+    /// Day on which the last spend forecast warning was published.
+    forecast_warned_day: Option<u64>,
+    /// Configuration as loaded, with the settings but not the profile
+    /// applied.
+    base_config: OrchestratorConfig,
+    profile: Profile,
+    /// Runtime settings, written over `base_config`
+    settings: Settings,
+    /// Settings of the configuration as loaded, for users not seen before
+    initial_settings: Settings,
+    /// Active user
+    user: UserProfile,
+    /// State of the other users seen since start-up, by user id
+    parked_users: HashMap<String, UserState>,
+    /// Journal export job, when a journal directory is configured.
+    journal: Option<JournalExporter>,
+    cache: ResponseCache,
+    #[cfg(feature = "network")]
+    webhooks: Option<(WebhookDispatcher, SubscriptionId)>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<MqttSensorStream>,
+    /// Latest map-reduce job over an oversized query.
+    chunked_run: Option<ChunkedRun>,
+    /// Plugin post-processors, in registration order.
+    post_processors: Vec<Arc<dyn PostProcessor>>,
+    /// Plugin conversation stores receiving every completed turn.
+    stores: Vec<Box<dyn ConversationStore>>,
+    /// Active chat session; `None` for the session-less default context
+    session: Option<Session>,
+    /// Contexts of inactive sessions, by session id
+    parked_sessions: HashMap<String, (Session, ContextManager)>,
+    /// Session-less context, parked while a session is active
+    parked_default: Option<ContextManager>,
+    next_session_id: u64,
+    /// Source files searched for code-help queries, by project
+    knowledge_packs: HashMap<String, Vec<Attachment>>,
+    /// Local and Remote backends asked together on Hybrid routes
+    hybrid_backends: Option<(Arc<dyn TargetBackend>, Arc<dyn TargetBackend>)>,
+    local_model: Option<ModelPool>,
+    /// Backend answering the Remote route; a placeholder answers without one
+    remote_backend: Option<Arc<dyn TargetBackend>>,
+    /// Short descriptions placed in prompts, by project
+    project_summaries: HashMap<String, String>,
+    /// Prompt assembled for the latest generated response
+    last_prompt: Option<Prompt>,
+    /// Long-term facts about the user
+    memory: MemoryStore,
+    /// Expertise, verbosity and topics learned from queries
+    signals: SignalTracker,
+    /// Recurring queries scheduled by the user
+    digests: DigestScheduler,
+    /// Network-route queries waiting for the device to be online
+    queue: QueryQueue,
+    /// Route, latency, cache and block counters
+    metrics: MetricsRegistry,
+    /// Estimated battery energy spent, per project
+    energy: EnergyLedger,
+    /// Token counters of the Local and Remote models
+    local_tokenizer: Arc<dyn TokenCounter>,
+    remote_tokenizer: Arc<dyn TokenCounter>,
+    /// Rate limits and remote token budget, possibly shared
+    quota: Arc<QuotaTracker>,
+    /// Owner of every background task
+    supervisor: Supervisor,
 }
 
 impl Orchestrator {
     /// Create a new orchestrator with default configuration.
     pub fn new() -> Self {
-        Self {
-            router: Router::new(RouterConfig::default()),
-            expert: ExpertSystem::new(),
+        Self::with_config(OrchestratorConfig::default())
+    }
+
+    /// Create an orchestrator from a loaded configuration file.
+    pub fn with_config(config: OrchestratorConfig) -> Self {
+        let profile = config.profile;
+        let base_config = config.clone();
+        let mut config = config;
+        profile.apply(&mut config);
+
+        let mut orchestrator = Self {
+            router: Router::new(config.router),
+            expert: ExpertSystem::from_config(&config.expert),
             context: ContextManager::new(),
+            context_budget: ContextBudgetController::new(config.context_budget),
+            ambient: AmbientClassifier::new(config.ambient),
+            activity: None,
+            activity_estimate: ActivityEstimate::default(),
+            context_switch: ContextSwitchDetector::new(config.context_switch),
+            fusion: OrientationFilter::new(config.fusion),
+            geofences: GeofenceManager::new(config.geofence),
+            sampling: SamplingController::new(config.sampling),
+            sampling_outbox: Vec::new(),
+            sensors: SensorRegistry::new(),
+            sensor_buffer: SensorBuffer::new(SENSOR_BUFFER_CAPACITY),
+            events: EventBus::new(),
+            host: None,
+            device: None,
+            #[cfg(feature = "persistence")]
+            persistence: None,
+            #[cfg(feature = "persistence")]
+            pending_turns: Vec::new(),
+            #[cfg(feature = "persistence")]
+            pending_since_ms: None,
+            #[cfg(feature = "persistence")]
+            quota_dirty: false,
+            lifecycle: LifecycleState::Foreground,
+            shut_down: false,
+            recent_turns: VecDeque::new(),
+            preferences: Vec::new(),
+            next_turn_id: 0,
+            online: OnlineTrainer::default(),
+            reward: Arc::new(RewardWeights::default()),
+            sla: SlaTracker::new(config.sla),
+            forecaster: Forecaster::new(config.forecast),
+            forecast_warned_day: None,
+            journal: (!config.journal.dir.as_os_str().is_empty())
+                .then(|| JournalExporter::new(config.journal.clone())),
+            cache: ResponseCache::new(config.cache.clone()),
+            #[cfg(feature = "network")]
+            webhooks: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            chunked_run: None,
+            post_processors: Vec::new(),
+            stores: Vec::new(),
+            session: None,
+            parked_sessions: HashMap::new(),
+            parked_default: None,
+            next_session_id: 0,
+            knowledge_packs: HashMap::new(),
+            hybrid_backends: None,
+            local_model: None,
+            remote_backend: None,
+            project_summaries: HashMap::new(),
+            last_prompt: None,
+            memory: MemoryStore::new(config.memory.max_facts),
+            signals: SignalTracker::new(),
+            digests: DigestScheduler::new(config.digest.utc_offset_minutes),
+            queue: QueryQueue::new(),
+            metrics: MetricsRegistry::new(),
+            energy: EnergyLedger::new(),
+            local_tokenizer: config.tokens.local.counter(),
+            remote_tokenizer: config.tokens.remote.counter(),
+            quota: Arc::new(QuotaTracker::new(config.expert.limits.clone())),
+            supervisor: Supervisor::default(),
+            settings: Settings::from_config(&base_config),
+            initial_settings: Settings::from_config(&base_config),
+            base_config,
+            profile,
+            user: UserProfile {
+                profile,
+                ..UserProfile::default()
+            },
+            parked_users: HashMap::new(),
+        };
+        orchestrator.context.set_reservoir_paused(!profile.uses_reservoir());
+        orchestrator
+    }
+
+    /// PROFILE: Switch operating profile at runtime. Routing policy,
+    /// context budget, reservoir usage and the allowed backends change
+    /// together, always derived from the loaded configuration.
+    pub fn set_profile(&mut self, profile: Profile) {
+        let mut config = self.base_config.clone();
+        profile.apply(&mut config);
+        self.router.set_config(config.router);
+        self.context_budget.set_config(config.context_budget);
+        self.context.set_reservoir_paused(!profile.uses_reservoir());
+        self.profile = profile;
+        self.user.profile = profile;
+    }
+
+    /// Active operating profile.
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// SETTINGS: Current runtime settings.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// SETTINGS: Change one runtime setting, returning whether its value
+    /// changed.
+    ///
+    /// The change takes effect at once: the routing threshold (before the
+    /// profile adjusts it) and cache TTL apply to the next query, telemetry
+    /// opt-in starts or stops metrics collection, and a default project
+    /// becomes active if no project is. A change is saved to the attached
+    /// database and published as `Event::SettingChanged`; setting the
+    /// current value does neither.
+    pub fn update_setting(&mut self, setting: Setting) -> Result<bool, OrchestratorError> {
+        setting.validate().map_err(OrchestratorError::InvalidInput)?;
+        if !self.settings.set(setting.clone()) {
+            return Ok(false);
+        }
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.save_setting(&setting)
+                .map_err(persistence_error("failed to save setting"))?;
+        }
+        self.apply_settings();
+        self.events.publish(&Event::SettingChanged { setting });
+        Ok(true)
+    }
+
+    /// Write the settings over the loaded configuration and re-derive
+    /// what depends on it.
+    fn apply_settings(&mut self) {
+        self.settings.apply_to(&mut self.base_config);
+        self.set_profile(self.profile);
+        self.cache.set_config(self.base_config.cache.clone());
+        if self.context.current_project().is_none() {
+            if let Some(project) = self.settings.default_project.clone() {
+                self.switch_project(project);
+            }
         }
     }
 
@@ -43,69 +469,6000 @@ pub fn new() -> Self {
     /// HYBRID STRATEGY:
     /// - `Local`: Low-latency, privacy-preserving on-device inference.
     /// - `Remote`: High-capability cloud-based reasoning (feature-gated).
-    /// - `Hybrid`: Local preprocessing (e.g. summarization) followed by remote query.
-    pub fn process(&mut self, query: Query) -> Result<Response, String> {
-        // Step 1: Expert system evaluation
-        let eval = self.expert.evaluate(&query);
+    /// - `Hybrid`: Local and remote models together, by the configured
+    ///   `HybridStrategy` (blend, draft-and-refine, retrieve-and-generate).
+    ///
+    /// Queries refused by a safety rule or resource limit are answered
+    /// with a `Blocked` response whose explanation carries the rule and
+    /// reason; `Err` is reserved for failures. With `queue.enabled`, a
+    /// query that needs the network while the device is offline is queued
+    /// and answered with `OrchestratorError::Deferred` (see `queue`).
+    /// To answer several queries at once, hand the orchestrator to a
+    /// `dispatch::Dispatcher`.
+    pub fn process(&mut self, query: Query) -> Result<Response, OrchestratorError> {
+        match self.begin(query)? {
+            Begun::Answered(response) => Ok(*response),
+            Begun::Pending(pending) => self.complete(pending.generate()),
+        }
+    }
+
+    /// First half of `process`, up to the backend call: safety rules,
+    /// routing and the prompt. Blocked, cached and chunked queries are
+    /// answered right away; the others are returned for
+    /// `PendingQuery::generate`, which runs without the orchestrator, and
+    /// `complete`.
+    pub(crate) fn begin(&mut self, mut query: Query) -> Result<Begun, OrchestratorError> {
+        if self.shut_down {
+            return Err(OrchestratorError::ShutDown);
+        }
+        let started = Stopwatch::start();
+
+        // Step 1: Resource limits, then expert system evaluation
+        let in_flight = match self.quota.admit(now_ms()) {
+            Ok(in_flight) => in_flight,
+            Err(exceeded) => {
+                return Ok(Begun::Answered(Box::new(self.blocked_response(
+                    "Request blocked by resource limits",
+                    Some(exceeded.rule_id.to_string()),
+                    Some(exceeded.reason),
+                    started,
+                ))))
+            }
+        };
+        let query_project = self.query_project(&query).map(str::to_string);
+        let eval = self.expert.evaluate_in(&query, query_project.as_deref());
         if !eval.allowed {
-            return Ok(Response {
-                text: "Request blocked by safety rules".to_string(),
-                route: RoutingDecision::Blocked,
-                confidence: 1.0,
-                latency_ms: 0,
-                metadata: ResponseMetadata {
-                    model: Some("expert-system".to_string()),
-                    tokens: None,
-                    cached: false,
+            return Ok(Begun::Answered(Box::new(self.blocked_response(
+                "Request blocked by safety rules",
+                eval.rule_id,
+                eval.reason,
+                started,
+            ))));
+        }
+        // Redacted spans never reach memory, the cache or a backend
+        if let Some(text) = eval.redacted_text {
+            query.text = text;
+        }
+        let redactions = eval.redactions;
+
+        // Durable facts the user states are remembered (opt-in)
+        if self.base_config.memory.enabled {
+            for (kind, text) in memory::extract(&query.text) {
+                let turn_id = self.next_turn_id;
+                self.store_fact(|memory| memory.remember(kind, &text, Some(turn_id), now_ms()))?;
+            }
+        }
+
+        let mut notes: Vec<String> = eval
+            .warnings
+            .into_iter()
+            .map(|warning| format!("rule warning: {}", warning))
+            .chain(eval.overrides.into_iter().map(|o| format!("rule override: {}", o)))
+            .collect();
+        let policy = self.expert.policy(query_project.as_deref());
+        notes.extend(policy.map(|policy| format!("policy: {}", policy.name())));
+
+        // Long-term signals about the user feed routing (opt-in)
+        let personalization = &self.base_config.personalization;
+        if personalization.enabled {
+            self.signals.observe(&query.text, personalization.smoothing);
+            let signals = self.user_signals();
+            self.router.set_user_signals(Some(&signals));
+            if signals.active {
+                notes.push(signals.summary());
+            }
+        }
+
+        // Step 2: Routing decision among backends with the required
+        // capabilities, weighing device conditions when the host reports
+        // them
+        self.check_capabilities(&query)?;
+
+        // Pasted errors are distilled to their essentials, which is all
+        // the cache and the backends see
+        let triage_config = &self.base_config.triage;
+        let report = triage_config
+            .enabled
+            .then(|| triage::parse(&query.text, triage_config.max_frames))
+            .flatten();
+        let query = match report {
+            Some(report) => {
+                notes.push(format!(
+                    "error triage: {} error distilled from {} lines",
+                    report.format, report.source_lines
+                ));
+                Query {
+                    text: report.summary(),
+                    ..query
+                }
+            }
+            None => query,
+        };
+
+        // Code-help queries carry only the relevant parts of their sources
+        let (query, code_note) = self.extract_code_context(query);
+        notes.extend(code_note);
+
+        // Queries too long for every allowed backend are map-reduced
+        let chunking = &self.base_config.chunking;
+        if chunking.enabled && !chunking::fits(&query.text, self.context_limit(&query)) {
+            return Ok(Begun::Answered(Box::new(self.process_chunked(query)?)));
+        }
+
+        // Repeated and near-duplicate queries are answered from the cache
+        let embedding = if self.cache.wants_embeddings() {
+            self.context.embedder().embed(&query.text).ok()
+        } else {
+            None
+        };
+        let cache_query = CacheQuery {
+            text: &query.text,
+            project: query_project.as_deref(),
+            capabilities: &query.required_capabilities,
+            embedding: embedding.as_deref(),
+        };
+        let cached = self
+            .cache
+            .lookup(&cache_query, now_ms())
+            .filter(|hit| self.route_allowed(hit.route, query_project.as_deref()));
+        if self.base_config.cache.enabled && self.base_config.metrics.enabled {
+            self.metrics.record_cache_lookup(cached.is_some());
+        }
+        if let Some(mut response) = cached {
+            let turn_id = self.next_turn_id;
+            self.next_turn_id += 1;
+            response.latency_ms = 0;
+            response.metadata.cached = true;
+            response.metadata.turn_id = Some(turn_id);
+            if let Some(explanation) = response.metadata.explanation.as_mut() {
+                explanation.adjustments.push("answered from the response cache".to_string());
+            }
+            let response =
+                self.finish_turn(turn_id, query, query_project.as_deref(), response)?;
+            return Ok(Begun::Answered(Box::new(response)));
+        }
+
+        let observed =
+            self.device.is_some() || self.activity_estimate.activity != Activity::Unknown;
+        let classified = self.router.route(&query);
+        let classifier_route = classified.0;
+        let (route, confidence) = match observed.then(|| self.device_state()) {
+            Some(device) => {
+                let paused = device.should_throttle_compute() || !self.profile.uses_reservoir();
+                self.context.set_reservoir_paused(paused);
+                self.router.route_with_policy(&query, &device)
+            }
+            None => classified,
+        };
+        if self.router.uses_mlp() && self.base_config.metrics.enabled {
+            self.metrics.record_confidence(route, confidence);
+        }
+        let mut explanation = self.router.explain(&query, route);
+        explanation.adjustments.extend(notes);
+        explanation.redactions = redactions;
+        let policy = self.expert.policy(query_project.as_deref());
+        let defer = self.base_config.queue.enabled
+            && spends_remote_tokens(classifier_route)
+            && !self.device_state().is_online()
+            && self.profile.allows(classifier_route)
+            && policy.into_iter().all(|policy| policy.allows(classifier_route));
+        if defer {
+            let max_queued = self.base_config.queue.max_queued;
+            let queued = self
+                .queue
+                .push(query.clone(), classifier_route, max_queued, now_ms());
+            if let Some(queued) = queued {
+                let error = OrchestratorError::Deferred {
+                    queue_id: queued.id,
+                    route: queued.route,
+                };
+                self.save_query_queue()?;
+                return Err(error);
+            }
+            explanation
+                .adjustments
+                .push("offline queue is full; answering now".to_string());
+        }
+        if classifier_route != route {
+            explanation.adjustments.push(format!(
+                "device policy chose {:?} over {:?}",
+                route, classifier_route
+            ));
+        }
+        let route = if self.profile.allows(route) {
+            route
+        } else {
+            explanation.adjustments.push(format!(
+                "profile {:?} does not allow {:?}; using Local",
+                self.profile, route
+            ));
+            RoutingDecision::Local
+        };
+        let route = match policy.filter(|policy| !policy.allows(route)) {
+            Some(policy) => {
+                explanation.adjustments.push(format!(
+                    "policy {} does not allow {:?}; using Local",
+                    policy.name(),
+                    route
+                ));
+                RoutingDecision::Local
+            }
+            None => route,
+        };
+        let route = if spends_remote_tokens(route) && !self.quota.remote_allowed(now_ms()) {
+            explanation.adjustments.push(format!(
+                "daily remote token budget is spent; using Local instead of {:?}",
+                route
+            ));
+            RoutingDecision::Local
+        } else {
+            route
+        };
+        self.require_capabilities(route, &query)?;
+        let turn_id = self.next_turn_id;
+        self.next_turn_id += 1;
+        self.events.publish(&Event::RouteDecided {
+            query: query.text.clone(),
+            decision: route,
+            confidence,
+        });
+
+        // Step 3: Generate response, once the caller has run the backends
+        let generation =
+            self.plan_generation(query, route, confidence, turn_id, DEFAULT_MODEL, explanation);
+        Ok(Begun::Pending(Box::new(PendingQuery {
+            generation,
+            turn_id,
+            project: query_project,
+            embedding,
+            _in_flight: in_flight,
+        })))
+    }
+
+    /// Record a query answered by `PendingQuery::generate` as `process`
+    /// would: post-processors, cache, SLA, history and persistence.
+    pub(crate) fn complete(
+        &mut self,
+        generated: GeneratedQuery,
+    ) -> Result<Response, OrchestratorError> {
+        let GeneratedQuery {
+            generated,
+            turn_id,
+            project,
+            embedding,
+            _in_flight,
+        } = generated;
+        let (query, mut response) = self.finish_generation(generated?);
+        for post_processor in &self.post_processors {
+            post_processor.process(&query, &mut response);
+        }
+        let cache_query = CacheQuery {
+            text: &query.text,
+            project: project.as_deref(),
+            capabilities: &query.required_capabilities,
+            embedding: embedding.as_deref(),
+        };
+        self.cache.insert(&cache_query, &response, now_ms());
+        self.observe_turn(&response);
+        self.finish_turn(turn_id, query, project.as_deref(), response)
+    }
+
+    /// Replace a code-help query's text with the question plus the
+    /// relevant sections of its attachments and the project's knowledge
+    /// pack, returning a note for the routing explanation.
+    fn extract_code_context(&self, mut query: Query) -> (Query, Option<String>) {
+        let config = &self.base_config.code_context;
+        if !config.enabled || !code_context::is_code_help(&query) {
+            return (query, None);
+        }
+        let project = query
+            .project_context
+            .as_deref()
+            .or(self.context.current_project());
+        let pack = project
+            .and_then(|project| self.knowledge_packs.get(project))
+            .map_or(&[][..], Vec::as_slice);
+        if query.attachments.is_empty() && pack.is_empty() {
+            return (query, None);
+        }
+
+        let context = code_context::extract(
+            &query.text,
+            query.attachments.iter().chain(pack),
+            config,
+        );
+        let note = format!(
+            "code context: {} sections, {} of {} source tokens",
+            context.sections.len(),
+            context.tokens(),
+            context.source_tokens
+        );
+        if !context.sections.is_empty() {
+            query.text = context.prompt(&query.text);
+        }
+        query.attachments.clear();
+        (query, Some(note))
+    }
+
+    /// Project whose policy governs `query`: its own, or the active one.
+    fn query_project<'a>(&'a self, query: &'a Query) -> Option<&'a str> {
+        query
+            .project_context
+            .as_deref()
+            .or(self.context.current_project())
+    }
+
+    /// Whether the operating profile, the policy of `project` and the
+    /// remote token budget allow `route`.
+    fn route_allowed(&self, route: RoutingDecision, project: Option<&str>) -> bool {
+        self.profile.allows(route)
+            && (!spends_remote_tokens(route) || self.quota.remote_allowed(now_ms()))
+            && self
+                .expert
+                .policy(project)
+                .into_iter()
+                .all(|policy| policy.allows(route))
+    }
+
+    /// Largest context window among the backends `query` may use.
+    fn context_limit(&self, query: &Query) -> usize {
+        let chunking = &self.base_config.chunking;
+        if self.route_allowed(RoutingDecision::Remote, self.query_project(query)) {
+            chunking.local_context_tokens.max(chunking.remote_context_tokens)
+        } else {
+            chunking.local_context_tokens
+        }
+    }
+
+    /// Route for one step of a chunked job: Local when `text` fits its
+    /// window and it offers the query's capabilities, Remote otherwise
+    /// (when allowed).
+    fn chunk_route(&self, text: &str, query: &Query) -> RoutingDecision {
+        let local_fits = chunking::fits(text, self.base_config.chunking.local_context_tokens)
+            && self
+                .router
+                .missing_capabilities(RoutingDecision::Local, query)
+                .is_empty();
+        let remote_allowed = self.route_allowed(RoutingDecision::Remote, self.query_project(query));
+        if local_fits || !remote_allowed {
+            RoutingDecision::Local
+        } else {
+            RoutingDecision::Remote
+        }
+    }
+
+    /// CHUNKED PROCESSING: Map-reduce an oversized query. Chunks are
+    /// mapped to partial answers (each published as `ChunkProcessed`),
+    /// which are combined into the final answer; see `chunking`.
+    fn process_chunked(&mut self, query: Query) -> Result<Response, OrchestratorError> {
+        let config = self.base_config.chunking.clone();
+        let limit = self.context_limit(&query);
+        let turn_id = self.next_turn_id;
+        self.next_turn_id += 1;
+
+        let mut run = ChunkedRun::default();
+        let mut input = query.text.clone();
+        let prompt = loop {
+            let chunks =
+                chunking::split_into_chunks(&input, config.chunk_tokens, config.overlap_tokens);
+            if run.total_chunks == 0 {
+                run.total_chunks = chunks.len();
+            } else {
+                run.reduce_rounds += 1;
+            }
+            run.partials.clear();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let map_query = Query {
+                    text: chunking::map_prompt(chunk, index, chunks.len()),
+                    ..query.clone()
+                };
+                let route = self.chunk_route(&map_query.text, &query);
+                let explanation = RoutingExplanation::default();
+                let partial = self
+                    .generate(&map_query, route, 1.0, turn_id, DEFAULT_MODEL, explanation)?
+                    .text;
+                self.events.publish(&Event::ChunkProcessed {
+                    index,
+                    total: chunks.len(),
+                    partial: partial.clone(),
+                });
+                run.partials.push(partial);
+            }
+
+            let prompt = chunking::reduce_prompt(&run.partials);
+            if chunking::fits(&prompt, limit) || run.reduce_rounds >= config.max_reduce_rounds {
+                break prompt;
+            }
+            input = run.partials.join("\n");
+        };
+
+        let route = self.chunk_route(&prompt, &query);
+        let explanation = RoutingExplanation {
+            adjustments: vec![format!(
+                "query exceeds every context window; split into {} chunks, {} reduce rounds",
+                run.total_chunks, run.reduce_rounds
+            )],
+            ..RoutingExplanation::default()
+        };
+        let reduce_query = Query {
+            text: prompt,
+            ..query.clone()
+        };
+        let mut response =
+            self.generate(&reduce_query, route, 1.0, turn_id, DEFAULT_MODEL, explanation)?;
+        for post_processor in &self.post_processors {
+            post_processor.process(&query, &mut response);
+        }
+        run.complete = true;
+        self.chunked_run = Some(run);
+        self.observe_turn(&response);
+        let project = self.query_project(&query).map(str::to_string);
+        self.finish_turn(turn_id, query, project.as_deref(), response)
+    }
+
+    /// Progress and partial answers of the latest chunked job.
+    pub fn chunked_run(&self) -> Option<&ChunkedRun> {
+        self.chunked_run.as_ref()
+    }
+
+    /// Record a produced response: feedback window, host delivery,
+    /// persistence and conversation history. Energy and stored turns are
+    /// filed under `project`, the query's project when it began, even if
+    /// another was switched to while it generated.
+    fn finish_turn(
+        &mut self,
+        turn_id: u64,
+        query: Query,
+        project: Option<&str>,
+        mut response: Response,
+    ) -> Result<Response, OrchestratorError> {
+        response.metadata.state_fingerprint = Some(self.state_fingerprint());
+        let energy = &self.base_config.energy;
+        if energy.enabled {
+            // Cached answers cost next to nothing
+            let joules = if response.metadata.cached {
+                0.0
+            } else {
+                let constraints = self.router.route_constraints(response.route);
+                energy.estimate(constraints, response.metadata.tokens, response.latency_ms)
+            };
+            response.metadata.energy_joules = Some(joules);
+            self.energy.record(project, response.route, joules);
+        }
+        self.remember_turn(turn_id, &query, &response);
+        if self.base_config.metrics.enabled {
+            self.metrics.record_response(&response);
+        }
+
+        if let Some((host, _)) = &self.host {
+            host.on_response_chunk(response.text.clone(), true);
+        }
+
+        // Step 4: Update context
+        #[cfg(feature = "persistence")]
+        if self.persistence.is_some() {
+            self.pending_turns.push((
+                project.map(str::to_string),
+                ConversationTurn {
+                    query: query.clone(),
+                    response: response.clone(),
+                },
+            ));
+            self.pending_since_ms.get_or_insert_with(now_ms);
+            if self.pending_turns.len() >= self.base_config.write_behind.max_turns {
+                self.flush()?;
+            } else {
+                self.flush_due()?;
+            }
+        }
+        if !self.stores.is_empty() {
+            let turn = ConversationTurn {
+                query: query.clone(),
+                response: response.clone(),
+            };
+            for store in &self.stores {
+                store
+                    .save_turn(project, &turn)
+                    .map_err(OrchestratorError::PersistenceError)?;
+            }
+        }
+        self.context.add_turn(query, response.clone());
+
+        Ok(response)
+    }
+
+    /// PLUGINS: Add a safety/policy rule, checked after the built-in
+    /// rules.
+    pub fn add_rule(&mut self, rule: Arc<dyn QueryRule>) {
+        self.expert.add_rule(rule);
+    }
+
+    /// RULES: Append the declarative rules of a TOML or JSON rules file
+    /// (see `expert`), checked after the configured rules. Returns how
+    /// many were added; nothing is added if any rule is invalid.
+    pub fn load_rules<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<usize, OrchestratorError> {
+        let rules = expert::load_rules(path).map_err(OrchestratorError::InvalidInput)?;
+        Ok(self.add_rules(rules))
+    }
+
+    /// Append declarative rules parsed elsewhere (e.g. with
+    /// `expert::rules_from_toml` where there is no file system),
+    /// returning how many were added.
+    pub fn add_rules(&mut self, rules: Vec<expert::Rule>) -> usize {
+        let count = rules.len();
+        for rule in rules {
+            self.expert.push_rule(rule);
+        }
+        count
+    }
+
+    /// Add a response post-processor; processors run in registration
+    /// order on freshly generated responses.
+    pub fn add_post_processor(&mut self, post_processor: Arc<dyn PostProcessor>) {
+        self.post_processors.push(post_processor);
+    }
+
+    /// Add a conversation store; every completed turn is saved to it
+    /// before `process` returns.
+    pub fn add_conversation_store(&mut self, store: Box<dyn ConversationStore>) {
+        self.stores.push(store);
+    }
+
+    /// CAPABILITIES: Check that some backend offers every capability
+    /// `query` requires. `process` fails with
+    /// `OrchestratorError::MissingCapabilities` otherwise.
+    pub fn check_capabilities(&self, query: &Query) -> Result<(), MissingCapabilities> {
+        self.router.check_capabilities(query)
+    }
+
+    /// Fail unless `route` offers every capability `query` requires.
+    fn require_capabilities(
+        &self,
+        route: RoutingDecision,
+        query: &Query,
+    ) -> Result<(), OrchestratorError> {
+        let missing = self.router.missing_capabilities(route, query);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingCapabilities { route, missing }.into())
+        }
+    }
+
+    /// FEEDBACK: Record that `turn_id` should have been routed to
+    /// `correct_route`.
+    ///
+    /// The correction is stored for offline retraining and, when a routing
+    /// model is loaded, applied as a small incremental update within the
+    /// daily step budget. Returns the number of update steps taken. Only
+    /// the most recent turns can receive feedback.
+    pub fn record_feedback(
+        &mut self,
+        turn_id: u64,
+        correct_route: RoutingDecision,
+    ) -> Result<usize, OrchestratorError> {
+        if correct_route == RoutingDecision::Blocked {
+            return Err(OrchestratorError::InvalidInput(
+                "Blocked is decided by safety rules, not the router".to_string(),
+            ));
+        }
+        if !self.router.is_executable(correct_route) {
+            return Err(OrchestratorError::NotFound(format!(
+                "{:?} is not a registered route",
+                correct_route
+            )));
+        }
+        let features = self.recent_turn(turn_id)?.features.clone();
+        self.online.add_correction(features, correct_route);
+
+        let Some(mlp) = self.router.mlp_mut() else {
+            return Ok(0);
+        };
+        Ok(self.online.train(mlp, now_ms()))
+    }
+
+    /// RATING: Rate the answer to `turn_id`, from `0.0` (bad) to `1.0`
+    /// (good).
+    ///
+    /// The turn's outcome is scored with the reward function (see
+    /// `set_reward_fn`) and, when a routing model is loaded, applied as
+    /// a reward-scaled incremental update within the daily step budget.
+    /// Returns the number of update steps taken.
+    pub fn rate_turn(&mut self, turn_id: u64, rating: f32) -> Result<usize, OrchestratorError> {
+        if !(0.0..=1.0).contains(&rating) {
+            return Err(OrchestratorError::InvalidInput(format!(
+                "rating {} is outside 0.0..=1.0",
+                rating
+            )));
+        }
+        let turn = self.recent_turn(turn_id)?;
+        let reward = self.reward_of(&turn.response, Some(rating));
+        let (features, route) = (turn.features.clone(), turn.response.route);
+        self.online.add_reward(features, route, reward);
+
+        let Some(mlp) = self.router.mlp_mut() else {
+            return Ok(0);
+        };
+        Ok(self.online.train(mlp, now_ms()))
+    }
+
+    /// REWARD: Score routing outcomes with `reward` when turns are
+    /// rated and preferences recorded. Defaults to `RewardWeights::default()`.
+    pub fn set_reward_fn(&mut self, reward: Arc<dyn RewardFn>) {
+        self.reward = reward;
+    }
+
+    /// Reward of `response` under the current reward function.
+    fn reward_of(&self, response: &Response, rating: Option<f32>) -> f32 {
+        let cost_usd = self.route_cost(response.route);
+        self.reward
+            .reward(&RouteOutcome::from_response(response, cost_usd, rating))
+    }
+
+    /// Record a call against the SLAs, publish new violations and update
+    /// the router's penalized routes.
+    fn record_sla(&mut self, route: RoutingDecision, latency_ms: u64, cost_usd: f64) {
+        for violation in self.sla.record(route, latency_ms, cost_usd, now_ms()) {
+            self.events.publish(&match violation {
+                SlaViolation::Latency {
+                    route,
+                    p95_ms,
+                    limit_ms,
+                } => Event::SlaViolated {
+                    route,
+                    p95_ms,
+                    limit_ms,
+                },
+                SlaViolation::DailySpend { spent, limit } => Event::BudgetExceeded {
+                    budget: "daily_spend".to_string(),
+                    used: spent,
+                    limit,
+                },
+            });
+        }
+        self.router.set_penalized_routes(self.sla.violating_routes());
+    }
+
+    /// SLA: Current status of a route against its declared SLA.
+    pub fn sla_status(&self, route: RoutingDecision) -> RouteSlaStatus {
+        self.sla.status(route)
+    }
+
+    /// Route corrections stored by the online trainer.
+    pub fn online_trainer(&self) -> &OnlineTrainer {
+        &self.online
+    }
+
+    /// ROUTER MODEL: Install a trained routing model. A bare `MLP` is
+    /// assumed to match the current feature schema; a saved
+    /// [`RouterModel`] is checked against it.
+    pub fn load_router_model(
+        &mut self,
+        model: impl Into<RouterModel>,
+    ) -> Result<(), OrchestratorError> {
+        self.router.set_model(model.into())
+    }
+
+    /// FEATURES: Extractor turning queries into the router's feature
+    /// vectors, for training a model saved with `RouterModel::trained_with`.
+    pub fn feature_extractor(&self) -> &FeatureExtractor {
+        self.router.feature_extractor()
+    }
+
+    /// EMBEDDING: Use `embedder` for router query features and the
+    /// context reservoir's input encoding.
+    pub fn set_embedder(&mut self, embedder: Arc<dyn Embedder>) {
+        self.router.set_embedder(Some(Arc::clone(&embedder)));
+        self.context.set_embedder(embedder);
+    }
+
+    /// REMOTE: Backend answering the Remote route. When it fails with a
+    /// failure the `fallback` configuration retries, the query is answered
+    /// down the fallback chain and the response marked `degraded`.
+    pub fn set_remote_backend(&mut self, backend: Arc<dyn TargetBackend>) {
+        self.remote_backend = Some(backend);
+    }
+
+    /// HYBRID: Backends used together when a query is routed Hybrid, as
+    /// the `hybrid.strategy` of the configuration says (see `hybrid`). By
+    /// default their answers are blended into a merged answer or, when
+    /// they disagree, a "two perspectives" answer. A local model set with
+    /// `set_local_model` answers in place of `local`.
+    pub fn set_hybrid_backends(
+        &mut self,
+        local: Arc<dyn TargetBackend>,
+        remote: Arc<dyn TargetBackend>,
+    ) {
+        self.hybrid_backends = Some((local, remote));
+    }
+
+    /// LOCAL: On-device model answering the Local route and the Local
+    /// half of Hybrid routes, paired with the Remote backend when no
+    /// Hybrid backends are set. Instances built by `factory` are kept warm
+    /// in a pool sized by the `pool` configuration, so strategies calling
+    /// Local several times per query load it once; a supervised task
+    /// unloads instances idle past `pool.idle_timeout_ms`. Returns how
+    /// many instances were loaded up front.
+    pub fn set_local_model<F>(&mut self, factory: F) -> Result<usize, OrchestratorError>
+    where
+        F: Fn() -> Result<Box<dyn LocalModel>, String> + Send + 'static,
+    {
+        let pool = ModelPool::new(self.base_config.pool.clone(), factory);
+        let loaded = pool.warm().map_err(OrchestratorError::BackendFailure)?;
+        self.spawn_background("model-pool", pool.evictor())?;
+        self.local_model = Some(pool);
+        Ok(loaded)
+    }
+
+    /// LOCAL: Usage counters of the local model pool, if a model is set.
+    pub fn local_model_stats(&self) -> Option<PoolStats> {
+        self.local_model.as_ref().map(ModelPool::stats)
+    }
+
+    /// TARGETS: Register a custom route target (companion device, home
+    /// server, gateway...) and return the route that selects it. Queries
+    /// routed there are answered by the target's backend.
+    pub fn register_target(
+        &mut self,
+        target: RouteTarget,
+    ) -> Result<RoutingDecision, OrchestratorError> {
+        self.router.register_target(target)
+    }
+
+    /// Set the confidence calibrator for the routing model.
+    pub fn set_router_calibrator(&mut self, calibrator: Calibrator) {
+        self.router.set_calibrator(calibrator);
+    }
+
+    /// Run a routed query on its backend (Phase 1: placeholder for the
+    /// built-in routes) and account for it. Backends receive the
+    /// assembled prompt rather than the bare query. Routes that run the
+    /// Local model record its context budget as provenance. A custom
+    /// target whose backend fails is answered locally instead; a failing
+    /// Remote backend is retried down the fallback chain. Both mark the
+    /// response `degraded`.
+    fn generate(
+        &mut self,
+        query: &Query,
+        route: RoutingDecision,
+        confidence: f32,
+        turn_id: u64,
+        model: &str,
+        explanation: RoutingExplanation,
+    ) -> Result<Response, OrchestratorError> {
+        let generation =
+            self.plan_generation(query.clone(), route, confidence, turn_id, model, explanation);
+        let (_, response) = self.finish_generation(generation.run(&mut |_| {})?);
+        Ok(response)
+    }
+
+    /// Gather everything `Generation::run` needs to answer `query` on
+    /// `route`: the assembled prompt, the backends and, for Remote and
+    /// Hybrid routes, connectivity, fallback routes and retrieved turns.
+    /// No backend is called yet, so they can run without the orchestrator.
+    fn plan_generation(
+        &mut self,
+        query: Query,
+        route: RoutingDecision,
+        confidence: f32,
+        turn_id: u64,
+        model: &str,
+        mut explanation: RoutingExplanation,
+    ) -> Generation {
+        let prompt = self.build_prompt(&query, route);
+        let backend_query = match &prompt {
+            Some(prompt) => Query {
+                text: prompt.text.clone(),
+                ..query.clone()
+            },
+            None => query.clone(),
+        };
+        self.last_prompt = prompt;
+
+        let target = match route {
+            RoutingDecision::Custom(_) => {
+                let target = self.router.targets().get(route);
+                if target.is_none() {
+                    explanation
+                        .adjustments
+                        .push(format!("{:?} is not registered; using Local", route));
+                }
+                target.map(|target| (target.name.clone(), Arc::clone(&target.backend)))
+            }
+            _ => None,
+        };
+        let online = self.device_state().is_online();
+        let remote = match (route, &self.remote_backend) {
+            (RoutingDecision::Remote, Some(backend)) => Some(RemoteCall {
+                backend: Arc::clone(backend),
+                online,
+                fallback: self.base_config.fallback.clone(),
+                chain: self.fallback_routes(&query),
+            }),
+            _ => None,
+        };
+        let local = self
+            .local_model
+            .clone()
+            .map(|pool| Arc::new(pool) as Arc<dyn TargetBackend>);
+        let backends = match (&self.hybrid_backends, &local) {
+            (Some((_, remote)), Some(local)) => Some((Arc::clone(local), Arc::clone(remote))),
+            (Some((local, remote)), None) => Some((Arc::clone(local), Arc::clone(remote))),
+            (None, Some(local)) => self
+                .remote_backend
+                .as_ref()
+                .map(|remote| (Arc::clone(local), Arc::clone(remote))),
+            (None, None) => None,
+        };
+        let hybrid = match (route, backends) {
+            (RoutingDecision::Hybrid, Some(backends)) => {
+                Some(self.plan_hybrid(&query, backends, online, &mut explanation))
+            }
+            _ => None,
+        };
+        Generation {
+            query,
+            backend_query,
+            route,
+            confidence,
+            turn_id,
+            model: model.to_string(),
+            explanation,
+            target,
+            local,
+            remote,
+            hybrid,
+        }
+    }
+
+    /// Routes to try, in chain order, when the Remote route fails for
+    /// `query`, up to the first one answered by the built-in placeholder.
+    /// Routes the profile does not allow, routes lacking a capability the
+    /// query requires and routes needing the remote API are left out.
+    fn fallback_routes(&self, query: &Query) -> Vec<FallbackRoute> {
+        let mut routes = Vec::new();
+        let chain = self.base_config.fallback.chain.iter().copied();
+        for route in chain.filter(|route| !spends_remote_tokens(*route)) {
+            let usable = self.profile.allows(route)
+                && self.router.is_executable(route)
+                && self.router.missing_capabilities(route, query).is_empty();
+            if !usable {
+                continue;
+            }
+            let Some(target) = self.router.targets().get(route) else {
+                routes.push(FallbackRoute {
+                    route,
+                    target: None,
+                });
+                break;
+            };
+            let backend_query = match self.build_prompt(query, route) {
+                Some(prompt) => Query {
+                    text: prompt.text,
+                    ..query.clone()
                 },
+                None => query.clone(),
+            };
+            routes.push(FallbackRoute {
+                route,
+                target: Some((target.name.clone(), Arc::clone(&target.backend), backend_query)),
             });
         }
+        routes
+    }
 
-        // Step 2: Routing decision
-        let (route, confidence) = self.router.route(&query);
+    /// Hybrid part of `plan_generation`: notes an offline device and, for
+    /// `RetrieveGenerate`, finds the related earlier turns on the device.
+    fn plan_hybrid(
+        &self,
+        query: &Query,
+        (local, remote): (Arc<dyn TargetBackend>, Arc<dyn TargetBackend>),
+        online: bool,
+        explanation: &mut RoutingExplanation,
+    ) -> HybridCall {
+        if !online {
+            explanation
+                .adjustments
+                .push("offline; Hybrid answered by Local only".to_string());
+        }
+        let strategy = self.base_config.hybrid.strategy;
+        let mut retrieved = Vec::new();
+        if strategy == HybridStrategy::RetrieveGenerate {
+            let k = self.base_config.hybrid.retrieved_turns;
+            match self.context.search(&query.text, k) {
+                Ok(matches) => retrieved = matches,
+                Err(e) => explanation
+                    .adjustments
+                    .push(format!("retrieval failed ({}); answering without it", e)),
+            }
+            explanation.adjustments.push(format!(
+                "retrieved {} earlier turns on the device",
+                retrieved.len()
+            ));
+        }
+        HybridCall {
+            local,
+            remote,
+            online,
+            strategy,
+            retrieved,
+            blend: self.base_config.blend.clone(),
+            embedder: Arc::clone(self.context.embedder()),
+        }
+    }
 
-        // Step 3: Generate response (Phase 1: placeholder)
+    /// Turn a generated answer into a response for its query, recording
+    /// the Local context budget's latency and the route's SLA. The
+    /// response carries the measured backend time and its answer's tokens,
+    /// counted with the route's tokenizer.
+    fn finish_generation(&mut self, generated: Generated) -> (Query, Response) {
+        let Generated {
+            query,
+            route,
+            confidence,
+            turn_id,
+            model,
+            text,
+            explanation,
+            degraded,
+            latency_ms,
+        } = generated;
+        let context_budget = matches!(route, RoutingDecision::Local | RoutingDecision::Hybrid)
+            .then(|| self.context_budget.budget());
+        let text = text.unwrap_or_else(|| format!("Response to: {}", query.text));
+        let tokens = u32::try_from(self.tokenizer(route).count(&text)).unwrap_or(u32::MAX);
         let response = Response {
-            text: format!("Response to: {}", query.text),
+            text,
             route,
             confidence,
-            latency_ms: 10,
+            latency_ms,
             metadata: ResponseMetadata {
-                model: Some("orchestrator-phase1".to_string()),
-                tokens: Some(50),
+                model: Some(model),
+                tokens: Some(tokens),
                 cached: false,
+                context_budget,
+                turn_id: Some(turn_id),
+                explanation: Some(explanation),
+                state_fingerprint: None,
+                degraded,
+                energy_joules: None,
             },
         };
 
-        // Step 4: Update context
-        self.context.add_turn(query, response.clone());
+        if context_budget.is_some() {
+            self.context_budget.record_latency(response.latency_ms);
+        }
+        self.record_sla(route, response.latency_ms, self.route_cost(route));
+        (query, response)
+    }
 
-        Ok(response)
+    /// Assemble the backend prompt for `query` from the context snapshot:
+    /// system prompt, project summary, remembered facts about the user,
+    /// recent and related turns, counted with the route's tokenizer and
+    /// kept clear of its context window. Prompts
+    /// for the Local model are held to its adaptive context budget. The
+    /// response style follows the learned signals and, while the user is
+    /// walking, asks for short answers.
+    fn build_prompt(&self, query: &Query, route: RoutingDecision) -> Option<Prompt> {
+        let config = &self.base_config.prompt;
+        if !config.enabled {
+            return None;
+        }
+        // Hybrid prompts go to the Local model too, so must fit its window
+        let chunking = &self.base_config.chunking;
+        let reserve = self.base_config.tokens.reserve_output_tokens;
+        let budget = if matches!(route, RoutingDecision::Local | RoutingDecision::Hybrid) {
+            let window = chunking.local_context_tokens.saturating_sub(reserve);
+            let limit = config.max_tokens.min(window).min(self.context_budget.budget());
+            TokenBudget::new(limit, Arc::clone(&self.local_tokenizer))
+        } else {
+            let window = chunking.remote_context_tokens.saturating_sub(reserve);
+            TokenBudget::new(config.max_tokens.min(window), Arc::clone(&self.remote_tokenizer))
+        };
+        // When the query cannot be embedded, only the recent turns are used
+        let snapshot = self
+            .context
+            .relevant_snapshot(&query.text, config.recent_turns, config.related_turns)
+            .unwrap_or_else(|_| self.context.snapshot(config.recent_turns));
+
+        let mut builder = PromptBuilder::new(budget)
+            .system(config.system_prompt.as_str())
+            .snapshot(&snapshot);
+        let summary = snapshot
+            .project
+            .as_ref()
+            .and_then(|project| self.project_summaries.get(project));
+        if let Some(summary) = summary {
+            builder = builder.project_summary(summary.as_str());
+        }
+        let memory = &self.base_config.memory;
+        let facts = memory
+            .enabled
+            .then(|| self.memory.prompt_section(memory.prompt_facts))
+            .flatten();
+        if let Some(facts) = facts {
+            builder = builder.section("About the user", facts);
+        }
+        let mut style: Vec<String> = self
+            .base_config
+            .personalization
+            .enabled
+            .then(|| self.user_signals().style())
+            .flatten()
+            .into_iter()
+            .collect();
+        if self.context.ambient().prefers_concise() {
+            style.push("- The user is on the move; keep answers short.".to_string());
+        }
+        if !style.is_empty() {
+            builder = builder.section("Response style", style.join("\n"));
+        }
+        Some(builder.build(&query.text))
     }
 
-    /// Set the active project on the underlying ContextManager.
-    pub fn switch_project(&mut self, project: impl Into<String>) {
-        self.context.switch_project(project);
+    /// Token counter of the model answering on `route`: the Local one
+    /// for Local, the Remote one for every route reaching the API or a
+    /// custom target.
+    fn tokenizer(&self, route: RoutingDecision) -> &dyn TokenCounter {
+        match route {
+            RoutingDecision::Local => self.local_tokenizer.as_ref(),
+            _ => self.remote_tokenizer.as_ref(),
+        }
     }
 
-    /// Borrow the active project name, if one is set.
-    pub fn current_project(&self) -> Option<&str> {
-        self.context.current_project()
+    /// Modelled API spend of one call on `route`.
+    fn route_cost(&self, route: RoutingDecision) -> f64 {
+        self.router
+            .route_cost(route)
+            .map_or(0.0, |cost| f64::from(cost.cost_usd))
     }
 
-    /// Drop the active project's conversation history.
-    pub fn clear_history(&mut self) {
-        self.context.clear_history();
+    /// Answer for a query refused before routing, by `rule_id` if known,
+    /// timed from `started`.
+    fn blocked_response(
+        &mut self,
+        text: &str,
+        rule_id: Option<String>,
+        reason: Option<String>,
+        started: Stopwatch,
+    ) -> Response {
+        let explanation = RoutingExplanation {
+            expert_rules: rule_id.iter().cloned().collect(),
+            block_reason: reason.clone(),
+            ..RoutingExplanation::default()
+        };
+        if self.base_config.metrics.enabled {
+            self.metrics.record_blocked(rule_id.as_deref().unwrap_or("unknown"));
+        }
+        if let Some(rule_id) = rule_id {
+            self.events.publish(&Event::RuleTriggered { rule_id, reason });
+        }
+        Response {
+            text: text.to_string(),
+            route: RoutingDecision::Blocked,
+            confidence: 1.0,
+            latency_ms: started.elapsed_ms(),
+            metadata: ResponseMetadata {
+                model: Some("expert-system".to_string()),
+                tokens: None,
+                cached: false,
+                context_budget: None,
+                turn_id: None,
+                explanation: Some(explanation),
+                state_fingerprint: Some(self.state_fingerprint()),
+                degraded: None,
+                energy_joules: None,
+            },
+        }
     }
 
-    /// Borrow the N most recent turns from the active project's history.
-    pub fn recent_history(&self, n: usize) -> Vec<ConversationTurn> {
-        self.context.recent_history(n)
+    /// Count a conversation turn against the remote token budget and feed
+    /// it to the forecaster. The quota usage is written with the next
+    /// flush.
+    fn observe_turn(&mut self, response: &Response) {
+        if spends_remote_tokens(response.route) {
+            let tokens = response.metadata.tokens.unwrap_or(0);
+            self.quota.record_remote_tokens(u64::from(tokens), now_ms());
+        }
+        #[cfg(feature = "persistence")]
+        {
+            self.quota_dirty = true;
+        }
+
+        self.forecaster.observe(
+            response.route,
+            response.metadata.tokens.unwrap_or(0),
+            self.route_cost(response.route),
+            response.latency_ms,
+        );
+        self.warn_spend_forecast();
     }
-}
 
-impl Default for Orchestrator {
-    fn default() -> Self {
-        Self::new()
+    /// Warn, once a day, when the daily spend limit is forecast to be hit
+    /// soon.
+    fn warn_spend_forecast(&mut self) {
+        let Some(limit) = self.sla.config().daily_spend_limit else {
+            return;
+        };
+        let day = now_ms() / DAY_MS;
+        let used = self.sla.spent_today();
+        if self.forecast_warned_day == Some(day) || used > limit {
+            return;
+        }
+        let Some(turns_remaining) = self.forecaster.turns_until_exceeded(limit - used) else {
+            return;
+        };
+        if turns_remaining <= self.forecaster.config().warn_within_turns {
+            self.forecast_warned_day = Some(day);
+            self.events.publish(&Event::BudgetForecast {
+                budget: "daily_spend".to_string(),
+                turns_remaining,
+                used,
+                limit,
+            });
+        }
+    }
+
+    /// FORECAST: Expected tokens, spend and latency of the next `turns`
+    /// turns of this conversation, or `None` before the first turn.
+    pub fn forecast(&self, turns: usize) -> Option<Forecast> {
+        self.forecaster.forecast(turns)
+    }
+
+    /// Keep a processed turn available for feedback and regeneration.
+    fn remember_turn(&mut self, id: u64, query: &Query, response: &Response) {
+        self.recent_turns.push_back(RecentTurn {
+            id,
+            query: query.clone(),
+            features: self.router.extract_features(query),
+            response: response.clone(),
+            alternatives: Vec::new(),
+        });
+        if self.recent_turns.len() > FEEDBACK_WINDOW {
+            self.recent_turns.pop_front();
+        }
+    }
+
+    fn recent_turn(&self, turn_id: u64) -> Result<&RecentTurn, OrchestratorError> {
+        self.recent_turns
+            .iter()
+            .find(|turn| turn.id == turn_id)
+            .ok_or_else(|| {
+                OrchestratorError::NotFound(format!("turn {} is unknown or too old", turn_id))
+            })
+    }
+
+    /// REGENERATE: Re-run a recent turn's query with a different route,
+    /// temperature or provider.
+    ///
+    /// The new response is stored as an alternative next to the original
+    /// (see `alternatives`) and is not added to the conversation history.
+    /// A forced route is reported with confidence 1.0.
+    pub fn regenerate(
+        &mut self,
+        turn_id: u64,
+        options: RegenerateOptions,
+    ) -> Result<Response, OrchestratorError> {
+        if self.shut_down {
+            return Err(OrchestratorError::ShutDown);
+        }
+        if options.route == Some(RoutingDecision::Blocked) {
+            return Err(OrchestratorError::InvalidInput(
+                "cannot regenerate on the Blocked route".to_string(),
+            ));
+        }
+        if let Some(route) = options.route.filter(|route| !self.router.is_executable(*route)) {
+            return Err(OrchestratorError::NotFound(format!(
+                "{:?} is not a registered route",
+                route
+            )));
+        }
+        if let Some(route) = options.route.filter(|route| !self.profile.allows(*route)) {
+            return Err(OrchestratorError::RouteNotAllowed {
+                route,
+                reason: format!("profile {:?} does not allow it", self.profile),
+            });
+        }
+        let turn = self.recent_turn(turn_id)?;
+        let query = turn.query.clone();
+        if let Some(route) = options.route {
+            if spends_remote_tokens(route) && !self.device_state().is_online() {
+                return Err(OrchestratorError::NetworkUnavailable { route });
+            }
+            if spends_remote_tokens(route) && !self.quota.remote_allowed(now_ms()) {
+                return Err(OrchestratorError::RouteNotAllowed {
+                    route,
+                    reason: "the daily remote token budget is spent".to_string(),
+                });
+            }
+            if !self.route_allowed(route, self.query_project(&query)) {
+                return Err(OrchestratorError::RouteNotAllowed {
+                    route,
+                    reason: "the project's policy does not allow it".to_string(),
+                });
+            }
+            self.require_capabilities(route, &query)?;
+        }
+        let mut explanation = turn.response.metadata.explanation.clone().unwrap_or_default();
+        let (route, confidence) = match options.route {
+            Some(route) if route != turn.response.route => {
+                explanation
+                    .adjustments
+                    .push(format!("regeneration forced {:?}", route));
+                (route, 1.0)
+            }
+            _ => (turn.response.route, turn.response.confidence),
+        };
+
+        let model = options.provider.clone();
+        let response = self.generate(
+            &query,
+            route,
+            confidence,
+            turn_id,
+            model.as_deref().unwrap_or(DEFAULT_MODEL),
+            explanation,
+        )?;
+        if let Some(turn) = self.recent_turns.iter_mut().find(|turn| turn.id == turn_id) {
+            turn.alternatives.push(Alternative {
+                options,
+                response: response.clone(),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Regenerated alternatives for a recent turn, oldest first.
+    pub fn alternatives(&self, turn_id: u64) -> Result<&[Alternative], OrchestratorError> {
+        Ok(&self.recent_turn(turn_id)?.alternatives)
+    }
+
+    /// PREFERENCE: Record which response the user preferred for a turn:
+    /// `0` is the original, `i` the i-th alternative. Each response is
+    /// scored with the reward function, the chosen one rated good and the
+    /// others bad. The resulting example is kept for `take_preferences`
+    /// and returned.
+    pub fn record_preference(
+        &mut self,
+        turn_id: u64,
+        choice: usize,
+    ) -> Result<PreferenceExample, OrchestratorError> {
+        let turn = self.recent_turn(turn_id)?;
+        let mut responses: Vec<Response> = std::iter::once(turn.response.clone())
+            .chain(turn.alternatives.iter().map(|alt| alt.response.clone()))
+            .collect();
+        if choice >= responses.len() {
+            return Err(OrchestratorError::NotFound(format!(
+                "turn {} has no response #{} ({} available)",
+                turn_id,
+                choice,
+                responses.len()
+            )));
+        }
+
+        let chosen = responses.remove(choice);
+        let rewards = std::iter::once(self.reward_of(&chosen, Some(1.0)))
+            .chain(responses.iter().map(|r| self.reward_of(r, Some(0.0))))
+            .collect();
+        let example = PreferenceExample {
+            query: turn.query.clone(),
+            features: turn.features.clone(),
+            chosen,
+            rejected: responses,
+            rewards,
+        };
+        self.preferences.push(example.clone());
+        if self.preferences.len() > MAX_PREFERENCES {
+            self.preferences.remove(0);
+        }
+        Ok(example)
+    }
+
+    /// Drain the preference examples recorded so far.
+    pub fn take_preferences(&mut self) -> Vec<PreferenceExample> {
+        std::mem::take(&mut self.preferences)
+    }
+
+    /// AMBIENT: Classify the device situation from recent sensor readings
+    /// and propagate it to the context snapshot and router features.
+    ///
+    /// Readings from sensors the host declared unusable are ignored; if no
+    /// ambient sensor is usable the state stays `Unknown`. The motion
+    /// readings also update the recognized activity, and new GPS fixes and
+    /// the state feed context-switch detection and geofencing.
+    pub fn update_ambient(&mut self, buffer: &SensorBuffer) -> AmbientState {
+        let buffer = self.sensors.filter_buffer(buffer);
+        if !buffer.readings_of_type(SensorType::Accelerometer).is_empty() {
+            let config = &self.base_config.activity;
+            let recognizer = self
+                .activity
+                .get_or_insert_with(|| ActivityRecognizer::new(config.clone()));
+            self.activity_estimate = recognizer.classify(&buffer);
+        } else {
+            self.activity_estimate = ActivityEstimate::default();
+        }
+        let state = if self.sensors.feature_status(SensorFeature::Ambient).is_operational() {
+            self.ambient.classify(&buffer)
+        } else {
+            AmbientState::Unknown
+        };
+        self.context.set_ambient(state);
+        self.router.set_ambient(state);
+        let still = self.ambient.is_still(&buffer);
+        let commands = self.sampling.on_ambient(state, still);
+        self.queue_sampling_commands(commands);
+        let mut changes = self.context_switch.observe(&buffer, state);
+        changes.extend(self.geofences.observe(&buffer));
+        for change in changes {
+            self.publish_context_change(change);
+        }
+        state
+    }
+
+    /// ACTIVITY: Still, walking or driving, as recognized from the motion
+    /// readings of the last `update_ambient`.
+    pub fn activity(&self) -> ActivityEstimate {
+        self.activity_estimate
+    }
+
+    /// CONTEXT SWITCH: The host reports `app` in the foreground. A change
+    /// of app is published as `Event::ContextChanged`, after switching to
+    /// the project `context_switch.app_projects` maps the app to, if any.
+    pub fn notify_foreground_app(&mut self, app: &str) {
+        if let Some(change) = self.context_switch.observe_app(app) {
+            self.publish_context_change(change);
+        }
+    }
+
+    /// GEOFENCE: Watch `region`, replacing any region of the same name.
+    /// Entering and leaving it is published as `Event::ContextChanged`
+    /// from `update_ambient`, and entering it switches to its project.
+    pub fn add_geofence(&mut self, region: Geofence) {
+        self.geofences.add_region(region);
+    }
+
+    /// GEOFENCE: Stop watching the region called `name`; returns whether
+    /// it was watched.
+    pub fn remove_geofence(&mut self, name: &str) -> bool {
+        self.geofences.remove_region(name)
+    }
+
+    /// GEOFENCE: Names of the regions the device is currently in.
+    pub fn current_regions(&self) -> &[String] {
+        self.geofences.inside()
+    }
+
+    /// Apply project auto-switching for `change`, then publish it.
+    fn publish_context_change(&mut self, change: ContextEvent) {
+        let project = match &change {
+            ContextEvent::AppSwitch { to, .. } => self.context_switch.project_for(to),
+            ContextEvent::RegionEntered { region } => self.geofences.project_for(region),
+            _ => None,
+        };
+        if let Some(project) = project.map(str::to_string) {
+            if self.context.current_project() != Some(project.as_str()) {
+                self.switch_project(project);
+            }
+        }
+        self.events.publish(&Event::ContextChanged { change });
+    }
+
+    /// SENSOR BATCH: Ingest readings packed by the host in the
+    /// `sensor_batch` format, so sensor callbacks cross the FFI boundary
+    /// once per batch instead of once per reading.
+    ///
+    /// Readings from unusable sensors are dropped; the rest join the
+    /// orchestrator's own buffer, which `refresh_ambient` classifies.
+    /// Returns how many readings were kept. A malformed batch is rejected
+    /// whole with `InvalidInput`.
+    pub fn push_sensor_batch(&mut self, batch: &[u8]) -> Result<usize, OrchestratorError> {
+        let readings = sensor_batch::decode(batch).map_err(OrchestratorError::InvalidInput)?;
+        Ok(self.buffer_readings(readings))
+    }
+
+    /// Add readings from usable sensors to the sensor buffer, each
+    /// followed by the orientation fused from it, if any; returns how many
+    /// of `readings` were kept. With `sensor_log.enabled` and persistence
+    /// attached, the kept readings are logged too.
+    fn buffer_readings(&mut self, readings: Vec<SensorReading>) -> usize {
+        let sensors = &self.sensors;
+        let usable: Vec<SensorReading> = readings
+            .into_iter()
+            .filter(|reading| sensors.is_usable(reading.sensor_type))
+            .collect();
+        #[cfg(feature = "persistence")]
+        if let Some(log) = self.sensor_log().filter(|_| self.base_config.sensor_log.enabled) {
+            // Logging is best effort; a failed write never drops readings
+            let _ = log.record(&usable);
+        }
+        let kept = usable.len();
+        for reading in usable {
+            let derived = self.fusion.update(&reading);
+            self.sensor_buffer.push(reading);
+            self.sensor_buffer.extend(derived);
+        }
+        kept
+    }
+
+    /// SENSOR LOG: Query the tiered log of received readings (raw for an
+    /// hour, per-second means for a day, hourly statistics for a month by
+    /// default), once persistence is attached. Readings are only logged
+    /// while `sensor_log.enabled` is set.
+    #[cfg(feature = "persistence")]
+    pub fn sensor_log(&self) -> Option<SensorLog<'_>> {
+        let persistence = self.persistence.as_ref()?;
+        Some(persistence.sensor_log(&self.base_config.sensor_log))
+    }
+
+    /// ORIENTATION: Roll, pitch and yaw fused from the motion readings
+    /// received through `push_sensor_batch`, once gravity has been seen.
+    /// Each gyroscope reading also adds a `SensorType::Orientation`
+    /// reading to the sensor buffer.
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.fusion.orientation()
+    }
+
+    /// MQTT: Subscribe to the loaded configuration's `mqtt.topics` on a
+    /// supervised background task, resolving the broker password through
+    /// `provider`. Replaces any previously started stream.
+    #[cfg(feature = "mqtt")]
+    pub fn start_mqtt(
+        &mut self,
+        provider: Arc<dyn SecretProvider>,
+    ) -> Result<(), OrchestratorError> {
+        let stream =
+            MqttSensorStream::start(self.base_config.mqtt.clone(), provider, &mut self.supervisor)
+                .map_err(OrchestratorError::InvalidInput)?;
+        self.mqtt = Some(stream);
+        Ok(())
+    }
+
+    /// MQTT: Move readings received from the broker into the sensor
+    /// buffer; returns how many were kept. `refresh_ambient` does this
+    /// first.
+    #[cfg(feature = "mqtt")]
+    pub fn poll_sensor_streams(&mut self) -> usize {
+        let readings = match &self.mqtt {
+            Some(stream) => stream.drain(),
+            None => return 0,
+        };
+        self.buffer_readings(readings)
+    }
+
+    /// MQTT: Messages received, readings produced and messages rejected,
+    /// once the stream is started.
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt_stats(&self) -> Option<MqttStats> {
+        self.mqtt.as_ref().map(MqttSensorStream::stats)
+    }
+
+    /// SENSOR BATCH: Readings received through `push_sensor_batch`.
+    pub fn sensor_buffer(&self) -> &SensorBuffer {
+        &self.sensor_buffer
+    }
+
+    /// SENSOR BATCH: `update_ambient` over the readings received through
+    /// `push_sensor_batch` and, with the `mqtt` feature, from the broker.
+    pub fn refresh_ambient(&mut self) -> AmbientState {
+        #[cfg(feature = "mqtt")]
+        self.poll_sensor_streams();
+        let buffer = std::mem::replace(&mut self.sensor_buffer, SensorBuffer::new(0));
+        let state = self.update_ambient(&buffer);
+        self.sensor_buffer = buffer;
+        state
+    }
+
+    /// Notify the orchestrator that a wake trigger fired, so audio
+    /// sampling is boosted for the follow-up utterance.
+    pub fn notify_wake_trigger(&mut self) {
+        self.events.publish(&Event::TriggerDetected {
+            kind: "wake".to_string(),
+            confidence: 1.0,
+        });
+        let commands = self.sampling.on_wake_trigger();
+        self.queue_sampling_commands(commands);
+    }
+
+    /// Notify the orchestrator that the wake interaction has ended.
+    pub fn notify_wake_end(&mut self) {
+        let commands = self.sampling.on_wake_end();
+        self.queue_sampling_commands(commands);
+    }
+
+    /// Queue sampling commands, dropping those for unusable sensors.
+    fn queue_sampling_commands(&mut self, commands: Vec<SamplingCommand>) {
+        let sensors = &self.sensors;
+        self.sampling_outbox
+            .extend(commands.into_iter().filter(|c| sensors.is_usable(c.sensor_type)));
+    }
+
+    /// SENSOR AVAILABILITY: Declare whether a sensor exists on this device
+    /// and whether the user has permitted access to it.
+    pub fn declare_sensor(&mut self, sensor_type: SensorType, availability: SensorAvailability) {
+        self.sensors.declare(sensor_type, availability);
+    }
+
+    /// CAPABILITIES: Report sensor availability, the operating status
+    /// (available / degraded / unavailable) of sensor-dependent features
+    /// and the health of background tasks.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            profile: self.profile,
+            background: self.supervisor.health(),
+            ..self.sensors.capabilities()
+        }
+    }
+
+    /// SAMPLING CONTROL: Drain pending sampling-rate requests for the
+    /// host's sensor layer. Hosts should apply these after each
+    /// `update_ambient` or wake notification.
+    pub fn take_sampling_commands(&mut self) -> Vec<SamplingCommand> {
+        std::mem::take(&mut self.sampling_outbox)
+    }
+
+    /// QUOTA: Tracker enforcing the `[expert.limits]` rate limits and
+    /// remote token budget.
+    pub fn quota_tracker(&self) -> Arc<QuotaTracker> {
+        Arc::clone(&self.quota)
+    }
+
+    /// QUOTA: Count this orchestrator's queries and tokens with `tracker`,
+    /// e.g. the tracker of another orchestrator, to limit both together.
+    pub fn set_quota_tracker(&mut self, tracker: Arc<QuotaTracker>) {
+        self.quota = tracker;
+    }
+
+    /// BACKGROUND: Run `body` on a supervised background task named
+    /// `name`. It is restarted with backoff when it fails, reported in
+    /// `capabilities().background` and joined by `shutdown`.
+    pub fn spawn_background(
+        &mut self,
+        name: &str,
+        body: TaskBody,
+    ) -> Result<(), OrchestratorError> {
+        self.supervisor
+            .spawn(name, body)
+            .map_err(OrchestratorError::BackendFailure)
+    }
+
+    /// EVENTS: Subscribe hooks, metrics, or host callbacks to pipeline
+    /// events (route decisions, rule triggers, detector triggers, ...).
+    pub fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// HOST DELEGATE: Register the app's callback object for push-style
+    /// notifications (responses, blocks, budget warnings, triggers, model
+    /// updates). Replaces any previously registered delegate.
+    pub fn set_host_delegate(&mut self, delegate: Arc<dyn HostDelegate>) {
+        self.clear_host_delegate();
+        let forward_to = Arc::clone(&delegate);
+        let id = self
+            .events
+            .subscribe(move |event| host::forward_event(forward_to.as_ref(), event));
+        self.host = Some((delegate, id));
+    }
+
+    /// DEVICE STATE: Register the host's source of battery, network,
+    /// thermal and memory conditions. Once set, routing weighs them
+    /// through the router's policy and reservoir updates pause while the
+    /// device is throttling compute.
+    pub fn set_device_provider(&mut self, provider: Arc<dyn DeviceStateProvider>) {
+        self.device = Some(provider);
+    }
+
+    /// Current device conditions; defaults when no provider is set. An
+    /// activity the provider leaves `Unknown` is the recognized one.
+    pub fn device_state(&self) -> DeviceState {
+        let mut device = self
+            .device
+            .as_ref()
+            .map_or_else(DeviceState::default, |p| p.device_state());
+        if device.activity == Activity::Unknown {
+            device.activity = self.activity_estimate.activity;
+        }
+        device
+    }
+
+    /// DEVICE STATE: Tell the orchestrator that the provider's conditions
+    /// changed; hosts call it from their connectivity listener. Once the
+    /// device is online, queries queued while it was offline are processed
+    /// in the order they were asked, each stored as a normal turn and
+    /// published as `Event::QueuedAnswered`. A replay that fails stays
+    /// queued until it has failed `queue.max_attempts` times; a blocked
+    /// one is dropped. Replaying stops if the device goes offline again.
+    pub fn on_device_state_changed(&mut self) -> Result<Vec<QueueRun>, OrchestratorError> {
+        let mut runs = Vec::new();
+        let waiting: Vec<QueuedQuery> = self.queue.queued().to_vec();
+        for queued in waiting {
+            if !self.device_state().is_online() {
+                break;
+            }
+            let outcome = match self.process(queued.query.clone()) {
+                Ok(response) if response.route == RoutingDecision::Blocked => {
+                    self.queue.cancel(queued.id);
+                    QueueOutcome::Dropped(response.text)
+                }
+                Ok(response) => {
+                    self.queue.cancel(queued.id);
+                    self.events.publish(&Event::QueuedAnswered {
+                        queue_id: queued.id,
+                        query: queued.query.text.clone(),
+                        text: response.text.clone(),
+                    });
+                    QueueOutcome::Answered {
+                        turn_id: response.metadata.turn_id,
+                        text: response.text,
+                    }
+                }
+                Err(e) => {
+                    let attempts = self
+                        .queue
+                        .record_failure(queued.id, e.to_string())
+                        .unwrap_or_default();
+                    if attempts >= self.base_config.queue.max_attempts {
+                        self.queue.cancel(queued.id);
+                        QueueOutcome::Dropped(e.to_string())
+                    } else {
+                        QueueOutcome::Retrying(e.to_string())
+                    }
+                }
+            };
+            runs.push(QueueRun {
+                queue_id: queued.id,
+                outcome,
+            });
+        }
+        if !runs.is_empty() {
+            self.save_query_queue()?;
+        }
+        Ok(runs)
+    }
+
+    /// QUEUE: Queries waiting for the device to be online, oldest first.
+    pub fn queued_queries(&self) -> &[QueuedQuery] {
+        self.queue.queued()
+    }
+
+    /// QUEUE: Withdraw queued query `id`, in storage too. Returns whether
+    /// it was waiting.
+    pub fn cancel_queued(&mut self, id: u64) -> Result<bool, OrchestratorError> {
+        let cancelled = self.queue.cancel(id).is_some();
+        if cancelled {
+            self.save_query_queue()?;
+        }
+        Ok(cancelled)
+    }
+
+    /// Whether optional neural work (reservoir updates, SNN steps) should
+    /// be skipped right now. Hosts scheduling SNN work check this first.
+    pub fn should_throttle_compute(&self) -> bool {
+        self.device_state().should_throttle_compute()
+    }
+
+    /// Unregister the host delegate, if any.
+    pub fn clear_host_delegate(&mut self) {
+        if let Some((_, id)) = self.host.take() {
+            self.events.unsubscribe(id);
+        }
+    }
+
+    /// PERSISTENCE: Attach a storage backend. Processed turns are buffered
+    /// and written behind in batches; call `flush` or `shutdown` to force
+    /// them out.
+    ///
+    /// When the in-memory history is empty (a fresh process), the most
+    /// recent stored turns are restored into it, as is the current
+    /// project's reservoir state. Remembered facts are restored when none
+    /// are held yet, personalization signals when none were learned and
+    /// quota usage when none was counted. Digests are restored when none
+    /// are scheduled yet, otherwise saved. The offline query queue is
+    /// restored when empty, otherwise saved. With `metrics.persist`,
+    /// metrics counters are restored when nothing was counted yet. Stored
+    /// settings are applied over the loaded configuration, without
+    /// publishing them. Returns how many turns were restored.
+    ///
+    /// The database records the profile of the active user, and a
+    /// database recording another user is refused (see `switch_user`).
+    #[cfg(feature = "persistence")]
+    pub fn attach_persistence(
+        &mut self,
+        persistence: PersistenceManager,
+    ) -> Result<usize, OrchestratorError> {
+        let owner = persistence
+            .load_config(USER_PROFILE_KEY)
+            .map_err(persistence_error("failed to load user profile"))?;
+        if let Some(json) = owner {
+            let owner: UserProfile = serde_json::from_str(&json)
+                .map_err(persistence_error("failed to parse user profile"))?;
+            if owner.id != self.user.id {
+                return Err(OrchestratorError::InvalidInput(format!(
+                    "database belongs to user '{}', not '{}'",
+                    owner.id, self.user.id
+                )));
+            }
+        }
+        save_user_profile(&persistence, &self.user)?;
+        let settings = persistence
+            .load_settings()
+            .map_err(persistence_error("failed to restore settings"))?;
+        for setting in settings {
+            self.settings.set(setting);
+        }
+
+        let mut restored = 0;
+        if self.context.recent_history(1).is_empty() {
+            let turns = persistence
+                .load_recent_turns(MAX_HISTORY_SIZE)
+                .map_err(persistence_error("failed to restore conversation history"))?;
+            restored = turns.len();
+            self.context.restore_history(turns);
+        }
+        if self.memory.is_empty() {
+            let facts = persistence
+                .load_memory_facts()
+                .map_err(persistence_error("failed to restore memory"))?;
+            self.memory = MemoryStore::from_facts(facts, self.base_config.memory.max_facts);
+        } else {
+            for fact in self.memory.facts() {
+                persistence
+                    .save_memory_fact(fact)
+                    .map_err(persistence_error("failed to save memory"))?;
+            }
+        }
+        if self.digests.digests().is_empty() {
+            let digests = persistence
+                .load_digests()
+                .map_err(persistence_error("failed to restore digests"))?;
+            let offset = self.base_config.digest.utc_offset_minutes;
+            self.digests = DigestScheduler::from_digests(digests, offset);
+        } else {
+            for digest in self.digests.digests() {
+                persistence
+                    .save_digest(digest)
+                    .map_err(persistence_error("failed to save digest"))?;
+            }
+        }
+        if self.signals.turns() == 0 {
+            let saved = persistence
+                .load_config(USER_SIGNALS_KEY)
+                .map_err(persistence_error("failed to restore personalization"))?;
+            if let Some(json) = saved {
+                self.signals = serde_json::from_str(&json)
+                    .map_err(persistence_error("failed to parse personalization"))?;
+            }
+        }
+        let usage = self.quota.usage(now_ms());
+        if usage.remote_tokens == 0 && usage.recent_queries.is_empty() {
+            let saved = persistence
+                .load_config(QUOTA_USAGE_KEY)
+                .map_err(persistence_error("failed to restore quota usage"))?;
+            if let Some(json) = saved {
+                self.quota.restore(
+                    serde_json::from_str(&json)
+                        .map_err(persistence_error("failed to parse quota usage"))?,
+                );
+            }
+        }
+        if self.queue.is_empty() {
+            let saved = persistence
+                .load_config(QUERY_QUEUE_KEY)
+                .map_err(persistence_error("failed to restore the offline queue"))?;
+            if let Some(json) = saved {
+                self.queue = serde_json::from_str(&json)
+                    .map_err(persistence_error("failed to parse the offline queue"))?;
+            }
+        }
+        if self.base_config.metrics.persist && self.metrics.is_empty() {
+            let saved = persistence
+                .load_config(METRICS_KEY)
+                .map_err(persistence_error("failed to restore metrics"))?;
+            if let Some(json) = saved {
+                self.metrics = serde_json::from_str(&json)
+                    .map_err(persistence_error("failed to parse metrics"))?;
+            }
+        }
+        self.persistence = Some(persistence);
+        self.apply_settings();
+        self.save_query_queue()?;
+        self.restore_reservoir_vector()?;
+        Ok(restored)
+    }
+
+    /// HISTORY: One page of stored history for `project` (`None` = turns
+    /// without a project), oldest first. Offset 0 is the most recent page;
+    /// buffered turns are flushed first so the page is complete. Without
+    /// an attached database the first conversation store answers.
+    pub fn history_page(
+        &mut self,
+        project: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ConversationTurn>, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        {
+            self.flush()?;
+            if let Some(pm) = &self.persistence {
+                return pm
+                    .load_history(project, offset, limit)
+                    .map_err(persistence_error("failed to load history"));
+            }
+        }
+        let Some(store) = self.stores.first() else {
+            return Err(OrchestratorError::NotConfigured(
+                "no persistence backend or conversation store attached".to_string(),
+            ));
+        };
+        store
+            .load_history(project, offset, limit)
+            .map_err(OrchestratorError::PersistenceError)
+    }
+
+    /// IMPORT: Bulk-load history exported from another assistant, oldest
+    /// turn first, each with its project. Turns are read `batch_size` at a
+    /// time, validated, deduplicated against each other and the stored
+    /// history, and each batch is stored in one transaction; the newest
+    /// ones are then merged into the in-memory history and embedded for
+    /// search once. `progress` is called after every batch and may pause.
+    ///
+    /// With persistence attached, progress is checkpointed under `source`
+    /// after every batch, and importing the same source again resumes
+    /// after the turns already read. Without it, only the newest turns are
+    /// kept, in memory.
+    pub fn import_turns<I>(
+        &mut self,
+        source: &str,
+        turns: I,
+        options: &ImportOptions,
+        mut progress: impl FnMut(&ImportProgress) -> ImportControl,
+    ) -> Result<ImportProgress, OrchestratorError>
+    where
+        I: IntoIterator<Item = (Option<String>, ConversationTurn)>,
+    {
+        if self.shut_down {
+            return Err(OrchestratorError::ShutDown);
+        }
+        self.flush()?;
+        let mut state = self.load_import_checkpoint(source)?;
+        state.finished = false;
+        let mut seen = Deduplicator::new();
+        for turn in self.context.recent_history(MAX_HISTORY_SIZE) {
+            seen.insert(&turn);
+        }
+        let mut turns = turns.into_iter().skip(state.read).peekable();
+        let mut newest: VecDeque<(Option<String>, ConversationTurn)> = VecDeque::new();
+        loop {
+            let mut accepted = Vec::new();
+            for (project, turn) in turns.by_ref().take(options.batch_size.max(1)) {
+                let index = state.read;
+                state.read += 1;
+                if let Err(reason) = import::validate(&turn, options) {
+                    state.reject(index, reason);
+                } else if !seen.insert(&turn) || self.is_stored(&turn)? {
+                    state.duplicates += 1;
+                } else {
+                    accepted.push((project, turn));
+                }
+            }
+            self.store_imported(&accepted)?;
+            state.imported += accepted.len();
+            newest.extend(accepted);
+            while newest.len() > MAX_HISTORY_SIZE {
+                newest.pop_front();
+            }
+            state.finished = turns.peek().is_none();
+            self.save_import_checkpoint(source, &state)?;
+            let control = progress(&state);
+            if state.finished || control == ImportControl::Pause {
+                break;
+            }
+        }
+        self.context.merge_history(newest.into());
+        Ok(state)
+    }
+
+    /// FLUSH: Write buffered turns to the attached backend in one
+    /// transaction, returning how many were written. On error nothing is
+    /// written and the turns stay buffered. Quota usage counted since the
+    /// last flush is written too. A no-op without a backend.
+    pub fn flush(&mut self) -> Result<usize, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let written = pm
+                .save_turns(&self.pending_turns)
+                .map_err(persistence_error("failed to flush conversation turns"))?;
+            self.pending_turns.clear();
+            self.pending_since_ms = None;
+            if written > 0 {
+                pm.apply_retention(&self.base_config.retention, now_ms() / 1000)
+                    .map_err(persistence_error("failed to apply retention"))?;
+            }
+            self.save_metrics()?;
+            // The turns are written; a failed quota write is retried on
+            // the next flush rather than failing this one
+            if let Err(_e) = self.save_quota_usage() {
+                #[cfg(feature = "logging")]
+                tracing::warn!("{}; retrying on the next flush", _e);
+            }
+            return Ok(written);
+        }
+        Ok(0)
+    }
+
+    /// FLUSH: Flush if the oldest buffered turn has waited
+    /// `write_behind.max_delay_ms`, returning how many turns were written.
+    /// Checked after every query; hosts call it from a timer so an idle
+    /// app still writes its last turns.
+    pub fn flush_due(&mut self) -> Result<usize, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(since) = self.pending_since_ms {
+            if now_ms().saturating_sub(since) >= self.base_config.write_behind.max_delay_ms {
+                return self.flush();
+            }
+        }
+        Ok(0)
+    }
+
+    /// COMPACT: Flush, apply the `retention` limits and vacuum the
+    /// database now, returning how many stored turns were deleted. Flushes
+    /// apply the limits too, but vacuum only when over the size limit.
+    /// A no-op without a backend.
+    pub fn compact(&mut self) -> Result<usize, OrchestratorError> {
+        self.flush()?;
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let deleted = pm
+                .apply_retention(&self.base_config.retention, now_ms() / 1000)
+                .map_err(persistence_error("failed to apply retention"))?;
+            pm.vacuum()
+                .map_err(persistence_error("failed to vacuum the database"))?;
+            return Ok(deleted);
+        }
+        Ok(0)
+    }
+
+    /// SHUTDOWN: Orderly teardown for when the OS is about to kill the app.
+    ///
+    /// Flushes the write-behind buffer, persists session, reservoir and
+    /// personalization state (including every open chat session), discards
+    /// undelivered sampling commands, releases event subscribers, joins
+    /// background tasks and closes the storage backend. Errors are collected
+    /// in the report rather than aborting teardown. After shutdown,
+    /// `process` returns an error. Calling it twice is harmless.
+    pub fn shutdown(&mut self) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        if self.shut_down {
+            return report;
+        }
+        self.shut_down = true;
+
+        match self.flush() {
+            Ok(n) => report.turns_flushed = n,
+            Err(e) => report.errors.push(e.to_string()),
+        }
+        if let Err(e) = self.save_quota_usage() {
+            report.errors.push(e.to_string());
+        }
+        if self.signals.turns() > 0 {
+            if let Err(e) = self.save_user_signals() {
+                report.errors.push(e.to_string());
+            }
+        }
+
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = self.persistence.take() {
+            match self.context.to_json() {
+                Ok(json) => match pm.save_config(SESSION_STATE_KEY, &json) {
+                    Ok(()) => report.session_saved = true,
+                    Err(e) => report.errors.push(format!("failed to save session: {}", e)),
+                },
+                Err(e) => report.errors.push(format!("failed to serialize session: {}", e)),
+            }
+
+            if let Some(reservoir) = self.context.reservoir() {
+                match pm.save_reservoir_state(self.context.current_project(), reservoir) {
+                    Ok(()) => report.reservoir_saved = true,
+                    Err(e) => report.errors.push(format!("failed to save reservoir: {}", e)),
+                }
+            }
+
+            let mut projects = self.context.parked_reservoir_projects();
+            projects.push(self.context.current_project().map(str::to_string));
+            for project in projects {
+                let Some(state) = self.context.project_reservoir_state(project.as_deref()) else {
+                    continue;
+                };
+                if let Err(e) = pm.save_reservoir_vector(project.as_deref(), &state) {
+                    report.errors.push(format!("failed to save reservoir state: {}", e));
+                }
+            }
+
+            let open = self
+                .session
+                .iter()
+                .map(|session| (session, &self.context))
+                .chain(self.parked_sessions.values().map(|(session, context)| (session, context)));
+            for (session, context) in open {
+                match pm.save_session(session, context) {
+                    Ok(()) => report.sessions_saved += 1,
+                    Err(e) => report.errors.push(format!("failed to save session: {}", e)),
+                }
+            }
+
+            drop(pm);
+            report.backends_closed += 1;
+        }
+
+        report.sampling_commands_discarded = self.take_sampling_commands().len();
+
+        self.host = None;
+        #[cfg(feature = "network")]
+        {
+            self.webhooks = None;
+        }
+        #[cfg(feature = "mqtt")]
+        {
+            self.mqtt = None;
+        }
+        report.tasks_joined = self.supervisor.shutdown();
+        report.subscribers_released = self.events.subscriber_count();
+        self.events = EventBus::new();
+
+        report
+    }
+
+    /// LIFECYCLE: React to a platform lifecycle signal.
+    ///
+    /// - `Background`: pause supervised background tasks and hold
+    ///   scheduled digests until the app returns, and flush buffered
+    ///   writes, since the app may be killed without further notice.
+    /// - `Foreground`: resume them and load the local model back up to
+    ///   `pool.min_idle` instances.
+    /// - `LowMemory`: flush, empty the response cache, unload idle local
+    ///   model instances and release buffers.
+    pub fn on_lifecycle(
+        &mut self,
+        event: LifecycleEvent,
+    ) -> Result<LifecycleReport, OrchestratorError> {
+        let mut report = LifecycleReport::default();
+        match event {
+            LifecycleEvent::Background => {
+                self.lifecycle = LifecycleState::Background;
+                report.tasks_paused = self.supervisor.pause();
+                report.turns_flushed = self.flush()?;
+                let journal_due = self.journal.as_ref().is_some_and(|journal| {
+                    journal.config().enabled && journal.is_due(now_ms())
+                });
+                if journal_due {
+                    report.journal_entries = self.export_journal()?;
+                }
+            }
+            LifecycleEvent::Foreground => {
+                self.lifecycle = LifecycleState::Foreground;
+                self.supervisor.resume();
+                if let Some(pool) = &self.local_model {
+                    report.models_changed =
+                        pool.warm().map_err(OrchestratorError::BackendFailure)?;
+                }
+            }
+            LifecycleEvent::LowMemory => {
+                report.turns_flushed = self.flush()?;
+                report.caches_dropped = self.cache.len();
+                self.cache.clear();
+                report.models_changed = self.local_model.as_ref().map_or(0, ModelPool::clear);
+                self.sampling_outbox.shrink_to_fit();
+                #[cfg(feature = "persistence")]
+                self.pending_turns.shrink_to_fit();
+            }
+        }
+        Ok(report)
+    }
+
+    /// Current foreground/background state.
+    pub fn lifecycle_state(&self) -> LifecycleState {
+        self.lifecycle
+    }
+
+    /// Whether `shutdown` has been called.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Latest ambient classification.
+    pub fn ambient_state(&self) -> AmbientState {
+        self.context.ambient()
+    }
+
+    /// CONTEXT BUDGET: Token budget currently given to the Local backend.
+    pub fn context_budget(&self) -> usize {
+        self.context_budget.budget()
+    }
+
+    /// Set the active project on the underlying ContextManager. Queries
+    /// then follow the policy profile bound to the project, if any.
+    ///
+    /// With the reservoir enabled, the old project's reservoir state is
+    /// parked (and saved when persistence is attached) and the new
+    /// project's state is restored, from storage if it was saved by an
+    /// earlier run.
+    pub fn switch_project(&mut self, project: impl Into<String>) {
+        let project = project.into();
+        #[cfg(feature = "persistence")]
+        if let (Some(pm), Some(state)) = (&self.persistence, self.context.reservoir_state()) {
+            // A failed save or load only loses temporal context; the switch
+            // itself always succeeds
+            let _ = pm.save_reservoir_vector(self.context.current_project(), &state);
+            if self.context.project_reservoir_state(Some(&project)).is_none() {
+                if let Ok(Some(saved)) = pm.load_reservoir_vector(Some(&project)) {
+                    self.context.restore_reservoir_state(Some(&project), saved);
+                }
+            }
+        }
+        self.context.switch_project(project);
+    }
+
+    /// POLICY: Name of the policy profile bound to the active project, if
+    /// any; switching project switches policy.
+    pub fn active_policy(&self) -> Option<&str> {
+        self.expert
+            .policy(self.context.current_project())
+            .map(|policy| policy.name())
+    }
+
+    /// RESERVOIR: Track conversation flow in an echo state network whose
+    /// state is kept per project. With persistence attached, the current
+    /// project's saved state is restored.
+    pub fn enable_reservoir(&mut self) -> Result<(), OrchestratorError> {
+        self.context.enable_reservoir();
+        self.restore_reservoir_vector()
+    }
+
+    /// Load the current project's reservoir state from the attached
+    /// backend, if both exist.
+    fn restore_reservoir_vector(&mut self) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let (Some(pm), Some(_)) = (&self.persistence, self.context.reservoir()) {
+            let project = self.context.current_project();
+            let saved = pm
+                .load_reservoir_vector(project)
+                .map_err(persistence_error("failed to load reservoir state"))?;
+            if let Some(state) = saved {
+                let project = project.map(str::to_string);
+                self.context.restore_reservoir_state(project.as_deref(), state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Borrow the active project name, if one is set.
+    pub fn current_project(&self) -> Option<&str> {
+        self.context.current_project()
+    }
+
+    /// CODE CONTEXT: Register the source files searched for code-help
+    /// queries in `project`, replacing any earlier pack. Only the sections
+    /// relevant to each question are sent to the backend.
+    pub fn set_knowledge_pack(&mut self, project: impl Into<String>, files: Vec<Attachment>) {
+        self.knowledge_packs.insert(project.into(), files);
+    }
+
+    /// CODE CONTEXT: Forget `project`'s knowledge pack.
+    pub fn clear_knowledge_pack(&mut self, project: &str) {
+        self.knowledge_packs.remove(project);
+    }
+
+    /// PROMPTS: Describe `project` in the prompts of its queries, e.g.
+    /// "Android app in Kotlin, targeting API 34".
+    pub fn set_project_summary(&mut self, project: impl Into<String>, summary: impl Into<String>) {
+        self.project_summaries.insert(project.into(), summary.into());
+    }
+
+    /// PROMPTS: Count `route`'s prompt tokens with `counter`, e.g. the
+    /// model's own tokenizer, instead of the configured estimate. Only
+    /// Local and Remote (which also covers custom targets) have one.
+    pub fn set_tokenizer(
+        &mut self,
+        route: RoutingDecision,
+        counter: Arc<dyn TokenCounter>,
+    ) -> Result<(), OrchestratorError> {
+        match route {
+            RoutingDecision::Local => self.local_tokenizer = counter,
+            RoutingDecision::Remote => self.remote_tokenizer = counter,
+            other => {
+                return Err(OrchestratorError::InvalidInput(format!(
+                    "{:?} has no tokenizer of its own",
+                    other
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// FINGERPRINT: Short, deterministic ID of the state answering queries:
+    /// the crate version, the loaded configuration with the active profile
+    /// applied (cache, memory and digest retention included), the routing
+    /// model and calibration, the rule pack and the history limit. It is
+    /// recorded in every response's metadata, so bug reports and A/B
+    /// results can be matched to the exact state that produced them.
+    pub fn state_fingerprint(&self) -> String {
+        let mut config = self.base_config.clone();
+        self.profile.apply(&mut config);
+        let mut fingerprint = StateFingerprint::new();
+        fingerprint
+            .part("version", &env!("CARGO_PKG_VERSION"))
+            .part("config", &config);
+        self.router.fingerprint(&mut fingerprint);
+        self.expert.fingerprint(&mut fingerprint);
+        fingerprint.part("history_limit", &MAX_HISTORY_SIZE);
+        fingerprint.finish()
+    }
+
+    /// PROMPTS: Prompt assembled for the latest generated response: system
+    /// prompt, project summary, retrieved turns and the query.
+    pub fn last_prompt(&self) -> Option<&Prompt> {
+        self.last_prompt.as_ref()
+    }
+
+    /// SESSIONS: Start a chat session with its own history and reservoir
+    /// state, optionally in `project`. The current session (or the
+    /// session-less context) is parked and can be resumed later.
+    pub fn start_session(&mut self, project: Option<&str>) -> Session {
+        let started_at = now_ms();
+        self.next_session_id += 1;
+        let session = Session {
+            id: format!("{:x}-{}", started_at, self.next_session_id),
+            project: project.map(str::to_string),
+            started_at,
+            ended_at: None,
+        };
+        let mut context = ContextManager::with_reservoir(self.context.reservoir().is_some());
+        if let Some(project) = project {
+            context.switch_project(project);
+        }
+        self.activate(Some(session.clone()), context);
+        session
+    }
+
+    /// SESSIONS: End the active session and return to the session-less
+    /// context. With persistence attached the session is saved and can be
+    /// resumed after a restart; otherwise it stays in memory.
+    pub fn end_session(&mut self) -> Result<Session, OrchestratorError> {
+        let Some(mut session) = self.session.take() else {
+            return Err(OrchestratorError::NotFound("no active session".to_string()));
+        };
+        session.ended_at = Some(now_ms());
+        let default = self.parked_default.take().unwrap_or_default();
+        let context = self.swap_context(default);
+        match self.save_session(&session, &context) {
+            Ok(true) => {}
+            saved => {
+                self.parked_sessions
+                    .insert(session.id.clone(), (session.clone(), context));
+                saved?;
+            }
+        }
+        Ok(session)
+    }
+
+    /// SESSIONS: Make session `id` active again, reopening it if it had
+    /// ended. Parked sessions are resumed from memory, others from the
+    /// attached persistence backend.
+    pub fn resume_session(&mut self, id: &str) -> Result<Session, OrchestratorError> {
+        if let Some(session) = self.session.as_ref().filter(|session| session.id == id) {
+            return Ok(session.clone());
+        }
+        let (mut session, context) = match self.parked_sessions.remove(id) {
+            Some(parked) => parked,
+            None => self
+                .load_session(id)?
+                .ok_or_else(|| {
+                    OrchestratorError::NotFound(format!("unknown session '{}'", id))
+                })?,
+        };
+        session.ended_at = None;
+        self.activate(Some(session.clone()), context);
+        Ok(session)
+    }
+
+    /// SESSIONS: The active session, if any.
+    pub fn current_session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// SESSIONS: Sessions known in memory and in the attached backend,
+    /// most recently started first.
+    pub fn sessions(&self) -> Result<Vec<Session>, OrchestratorError> {
+        let mut sessions: Vec<Session> = self
+            .session
+            .iter()
+            .chain(self.parked_sessions.values().map(|(session, _)| session))
+            .cloned()
+            .collect();
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let stored = pm
+                .list_sessions()
+                .map_err(persistence_error("failed to list sessions"))?;
+            for session in stored {
+                if !sessions.iter().any(|known| known.id == session.id) {
+                    sessions.push(session);
+                }
+            }
+        }
+        sessions.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| b.id.cmp(&a.id)));
+        Ok(sessions)
+    }
+
+    /// USERS: Make `user` the active user, returning the previous one.
+    ///
+    /// Buffered turns and personalization are first written to the
+    /// current user's database. Then the current user's history and
+    /// projects, sessions, router model and the feedback training it,
+    /// remembered facts, personalization, digests, queued queries, cached
+    /// answers, project summaries, knowledge packs and database are
+    /// parked, and the new user's are brought back. A user not seen since
+    /// start-up starts afresh, without a database until the host attaches
+    /// theirs. The user's operating profile is applied. Switching to the
+    /// active user only updates their profile.
+    pub fn switch_user(&mut self, user: UserProfile) -> Result<UserProfile, OrchestratorError> {
+        user.validate().map_err(OrchestratorError::InvalidInput)?;
+        if let Some(policy) = &user.policy {
+            if !self.expert.policies().iter().any(|p| p.name() == policy) {
+                return Err(OrchestratorError::InvalidInput(format!(
+                    "unknown policy '{}' for user '{}'",
+                    policy, user.id
+                )));
+            }
+        }
+        let previous = if user.id == self.user.id {
+            std::mem::replace(&mut self.user, user)
+        } else {
+            self.flush()?;
+            self.save_user_signals()?;
+            let mut state = match self.parked_users.remove(&user.id) {
+                Some(mut state) => {
+                    state.profile = user;
+                    state
+                }
+                None => self.fresh_user_state(user),
+            };
+            self.swap_user_state(&mut state);
+            let previous = state.profile.clone();
+            self.parked_users.insert(previous.id.clone(), state);
+            previous
+        };
+        // Checked above
+        let _ = self.expert.set_user_policy(self.user.policy.as_deref());
+        self.profile = self.user.profile;
+        self.apply_settings();
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            save_user_profile(pm, &self.user)?;
+        }
+        Ok(previous)
+    }
+
+    /// USERS: The active user.
+    pub fn current_user(&self) -> &UserProfile {
+        &self.user
+    }
+
+    /// USERS: Every user seen since start-up, the active one first, then
+    /// by id.
+    pub fn users(&self) -> Vec<&UserProfile> {
+        let mut parked: Vec<&UserProfile> =
+            self.parked_users.values().map(|state| &state.profile).collect();
+        parked.sort_by(|a, b| a.id.cmp(&b.id));
+        std::iter::once(&self.user).chain(parked).collect()
+    }
+
+    /// USERS: Forget everything held in memory for the parked user `id`,
+    /// returning whether they were known. Their database is closed but
+    /// left for the host to delete. The active user cannot be removed.
+    pub fn remove_user(&mut self, id: &str) -> bool {
+        self.parked_users.remove(id).is_some()
+    }
+
+    /// State for a user not seen before, built as `with_config` does.
+    /// Their journal goes to a subdirectory of `journal.dir` named by
+    /// their id.
+    fn fresh_user_state(&self, profile: UserProfile) -> UserState {
+        let config = &self.base_config;
+        let journal = (!config.journal.dir.as_os_str().is_empty()).then(|| {
+            JournalExporter::new(crate::journal::JournalConfig {
+                dir: config.journal.dir.join(&profile.id),
+                ..config.journal.clone()
+            })
+        });
+        UserState {
+            profile,
+            router: Router::new(config.router.clone()),
+            online: OnlineTrainer::default(),
+            context: ContextManager::with_reservoir(self.context.reservoir().is_some()),
+            session: None,
+            parked_sessions: HashMap::new(),
+            parked_default: None,
+            recent_turns: VecDeque::new(),
+            preferences: Vec::new(),
+            memory: MemoryStore::new(config.memory.max_facts),
+            signals: SignalTracker::new(),
+            digests: DigestScheduler::new(config.digest.utc_offset_minutes),
+            queue: QueryQueue::new(),
+            cache: ResponseCache::new(config.cache.clone()),
+            project_summaries: HashMap::new(),
+            knowledge_packs: HashMap::new(),
+            settings: self.initial_settings.clone(),
+            journal,
+            metrics: MetricsRegistry::new(),
+            energy: EnergyLedger::new(),
+            forecaster: Forecaster::new(config.forecast.clone()),
+            forecast_warned_day: None,
+            sensor_buffer: SensorBuffer::new(SENSOR_BUFFER_CAPACITY),
+            last_prompt: None,
+            #[cfg(feature = "persistence")]
+            persistence: None,
+        }
+    }
+
+    /// Exchange the active user's state with `state`.
+    fn swap_user_state(&mut self, state: &mut UserState) {
+        use std::mem::swap;
+
+        swap(&mut self.user, &mut state.profile);
+        swap(&mut self.router, &mut state.router);
+        swap(&mut self.online, &mut state.online);
+        state.context = self.swap_context(std::mem::take(&mut state.context));
+        swap(&mut self.session, &mut state.session);
+        swap(&mut self.parked_sessions, &mut state.parked_sessions);
+        swap(&mut self.parked_default, &mut state.parked_default);
+        swap(&mut self.recent_turns, &mut state.recent_turns);
+        swap(&mut self.preferences, &mut state.preferences);
+        swap(&mut self.memory, &mut state.memory);
+        swap(&mut self.signals, &mut state.signals);
+        swap(&mut self.digests, &mut state.digests);
+        swap(&mut self.queue, &mut state.queue);
+        swap(&mut self.cache, &mut state.cache);
+        swap(&mut self.project_summaries, &mut state.project_summaries);
+        swap(&mut self.knowledge_packs, &mut state.knowledge_packs);
+        swap(&mut self.settings, &mut state.settings);
+        swap(&mut self.journal, &mut state.journal);
+        swap(&mut self.metrics, &mut state.metrics);
+        swap(&mut self.energy, &mut state.energy);
+        swap(&mut self.forecaster, &mut state.forecaster);
+        swap(&mut self.forecast_warned_day, &mut state.forecast_warned_day);
+        swap(&mut self.sensor_buffer, &mut state.sensor_buffer);
+        swap(&mut self.last_prompt, &mut state.last_prompt);
+        #[cfg(feature = "persistence")]
+        swap(&mut self.persistence, &mut state.persistence);
+        self.chunked_run = None;
+    }
+
+    /// Make `session` active with `context`, parking the previous one.
+    fn activate(&mut self, session: Option<Session>, context: ContextManager) {
+        let previous = self.swap_context(context);
+        match std::mem::replace(&mut self.session, session) {
+            Some(session) => {
+                self.parked_sessions
+                    .insert(session.id.clone(), (session, previous));
+            }
+            None => self.parked_default = Some(previous),
+        }
+    }
+
+    /// Install `context`, carrying over device-wide state (embedder,
+    /// ambient classification, reservoir pause), and return the old one.
+    fn swap_context(&mut self, mut context: ContextManager) -> ContextManager {
+        if !Arc::ptr_eq(context.embedder(), self.context.embedder()) {
+            context.set_embedder(Arc::clone(self.context.embedder()));
+        }
+        context.set_ambient(self.context.ambient());
+        context.set_reservoir_paused(self.context.reservoir_paused());
+        std::mem::replace(&mut self.context, context)
+    }
+
+    /// Save `session` to the attached backend; `Ok(false)` without one.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn save_session(
+        &self,
+        session: &Session,
+        context: &ContextManager,
+    ) -> Result<bool, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.save_session(session, context)
+                .map_err(persistence_error("failed to save session"))?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Load session `id` from the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn load_session(
+        &self,
+        id: &str,
+    ) -> Result<Option<(Session, ContextManager)>, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            return pm
+                .load_session(id)
+                .map_err(persistence_error("failed to load session"));
+        }
+        Ok(None)
+    }
+
+    /// MEMORY: Facts remembered about the user, oldest first. Facts are
+    /// extracted from queries when `memory.enabled` is set and included
+    /// in prompts.
+    pub fn memory_facts(&self) -> &[MemoryFact] {
+        self.memory.facts()
+    }
+
+    /// MEMORY: Remember a fact entered by the user.
+    pub fn remember_fact(
+        &mut self,
+        kind: FactKind,
+        text: &str,
+    ) -> Result<MemoryFact, OrchestratorError> {
+        if text.trim().is_empty() {
+            return Err(OrchestratorError::InvalidInput("a fact cannot be empty".to_string()));
+        }
+        self.store_fact(|memory| memory.remember(kind, text, None, now_ms()))
+    }
+
+    /// MEMORY: Reword remembered fact `id`.
+    pub fn edit_memory_fact(
+        &mut self,
+        id: u64,
+        text: &str,
+    ) -> Result<MemoryFact, OrchestratorError> {
+        let fact = self
+            .memory
+            .edit(id, text, now_ms())
+            .map_err(OrchestratorError::NotFound)?;
+        self.store_fact(|_| fact)
+    }
+
+    /// MEMORY: Forget fact `id`, in storage too. Returns whether it was
+    /// remembered.
+    pub fn delete_memory_fact(&mut self, id: u64) -> Result<bool, OrchestratorError> {
+        let deleted = self.memory.delete(id);
+        self.forget_facts(&[id])?;
+        Ok(deleted)
+    }
+
+    /// MEMORY: Forget every fact, returning how many there were.
+    pub fn clear_memory(&mut self) -> Result<usize, OrchestratorError> {
+        let ids = self.memory.clear();
+        self.forget_facts(&ids)?;
+        Ok(ids.len())
+    }
+
+    /// DIGESTS: Schedule a recurring query from a definition such as
+    /// "every morning at 8: summarize yesterday's notes" (see `digest`),
+    /// asked in `project` if given.
+    pub fn schedule_digest(
+        &mut self,
+        definition: &str,
+        project: Option<&str>,
+    ) -> Result<Digest, OrchestratorError> {
+        let (schedule, query) =
+            Digest::parse(definition).map_err(OrchestratorError::InvalidInput)?;
+        let digest = self.digests.add(schedule, &query, project, now_ms());
+        self.save_digest(&digest)?;
+        Ok(digest)
+    }
+
+    /// DIGESTS: Scheduled digests, in creation order.
+    pub fn digests(&self) -> &[Digest] {
+        self.digests.digests()
+    }
+
+    /// DIGESTS: Stop digest `id`, in storage too. Returns whether it was
+    /// scheduled.
+    pub fn remove_digest(&mut self, id: u64) -> Result<bool, OrchestratorError> {
+        let removed = self.digests.remove(id);
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.delete_digest(id)
+                .map_err(persistence_error("failed to delete digest"))?;
+        }
+        Ok(removed)
+    }
+
+    /// DIGESTS: Run every digest that is due. Meant to be called from the
+    /// host's background scheduler.
+    ///
+    /// Each digest is asked as a normal query, stored as a normal turn and
+    /// published as `Event::DigestReady`, which reaches
+    /// `HostDelegate::on_digest`. While the battery is low or the network
+    /// metered (see `DigestConfig`), or the device is in a pocket, due
+    /// digests are deferred; past `max_delay_minutes` they are skipped
+    /// until their next occurrence. While the app is in the background
+    /// (see `on_lifecycle`) nothing runs and due digests wait for it.
+    pub fn run_due_digests(&mut self) -> Result<Vec<DigestRun>, OrchestratorError> {
+        self.run_due_digests_at(now_ms())
+    }
+
+    fn run_due_digests_at(&mut self, now: u64) -> Result<Vec<DigestRun>, OrchestratorError> {
+        let config = self.base_config.digest.clone();
+        // In the background digests stay due until the app returns
+        if !config.enabled || self.lifecycle == LifecycleState::Background {
+            return Ok(Vec::new());
+        }
+        let ambient = self.ambient_state();
+        let hold_back = self
+            .device
+            .as_ref()
+            .and_then(|provider| config.hold_back(&provider.device_state()))
+            .or_else(|| {
+                (!ambient.allows_proactive())
+                    .then(|| format!("no proactive answers while {:?}", ambient))
+            });
+        let mut runs = Vec::new();
+        for digest in self.digests.due(now) {
+            let waited = now.saturating_sub(digest.next_run_ms);
+            let outcome = match &hold_back {
+                Some(reason) if waited < config.max_delay_minutes * 60_000 => {
+                    runs.push(DigestRun {
+                        digest_id: digest.id,
+                        outcome: DigestOutcome::Deferred(reason.clone()),
+                    });
+                    continue;
+                }
+                Some(reason) => DigestOutcome::Skipped(format!("held back too long: {}", reason)),
+                None => {
+                    let query = Query {
+                        project_context: digest.project.clone(),
+                        ..Query::new(digest.query.as_str())
+                    };
+                    match self.process(query) {
+                        Ok(response) if response.route == RoutingDecision::Blocked => {
+                            DigestOutcome::Skipped(response.text)
+                        }
+                        Ok(response) => {
+                            self.events.publish(&Event::DigestReady {
+                                digest_id: digest.id,
+                                query: digest.query.clone(),
+                                text: response.text.clone(),
+                            });
+                            DigestOutcome::Ran {
+                                turn_id: response.metadata.turn_id,
+                                text: response.text,
+                            }
+                        }
+                        Err(e) => DigestOutcome::Skipped(e.to_string()),
+                    }
+                }
+            };
+            let ran = matches!(outcome, DigestOutcome::Ran { .. });
+            if let Some(updated) = self.digests.advance(digest.id, now, ran) {
+                self.save_digest(&updated)?;
+            }
+            runs.push(DigestRun {
+                digest_id: digest.id,
+                outcome,
+            });
+        }
+        Ok(runs)
+    }
+
+    /// Write `digest` to the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn save_digest(&self, digest: &Digest) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.save_digest(digest)
+                .map_err(persistence_error("failed to save digest"))?;
+        }
+        Ok(())
+    }
+
+    /// PERSONALIZATION: What has been learned about the user's expertise,
+    /// verbosity preference and typical topics, with the evidence for
+    /// each. Learned when `personalization.enabled` is set; a remembered
+    /// preference about answer length overrides the learned one.
+    pub fn user_signals(&self) -> UserSignals {
+        self.signals
+            .signals(&self.base_config.personalization, self.memory.facts())
+    }
+
+    /// PERSONALIZATION: Forget everything learned about the user, in
+    /// storage too.
+    pub fn reset_user_signals(&mut self) -> Result<(), OrchestratorError> {
+        self.signals = SignalTracker::new();
+        self.router.set_user_signals(None);
+        self.save_user_signals()
+    }
+
+    /// Whether `turn` is already in the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn is_stored(&self, turn: &ConversationTurn) -> Result<bool, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            return pm
+                .has_turn(turn)
+                .map_err(persistence_error("failed to check for duplicate turns"));
+        }
+        Ok(false)
+    }
+
+    /// Write one batch of imported turns to the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn store_imported(
+        &self,
+        turns: &[(Option<String>, ConversationTurn)],
+    ) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.save_turns(turns)
+                .map_err(persistence_error("failed to store imported turns"))?;
+        }
+        Ok(())
+    }
+
+    /// Progress of earlier imports of `source`, from the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn load_import_checkpoint(&self, source: &str) -> Result<ImportProgress, OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let key = format!("{}{}", IMPORT_CHECKPOINT_PREFIX, source);
+            let saved = pm
+                .load_config(&key)
+                .map_err(persistence_error("failed to load import checkpoint"))?;
+            if let Some(json) = saved {
+                return serde_json::from_str(&json)
+                    .map_err(persistence_error("failed to parse import checkpoint"));
+            }
+        }
+        Ok(ImportProgress::default())
+    }
+
+    /// Checkpoint the progress of importing `source` to the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn save_import_checkpoint(
+        &self,
+        source: &str,
+        state: &ImportProgress,
+    ) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let json = serde_json::to_string(state)
+                .map_err(persistence_error("failed to serialize import checkpoint"))?;
+            pm.save_config(&format!("{}{}", IMPORT_CHECKPOINT_PREFIX, source), &json)
+                .map_err(persistence_error("failed to save import checkpoint"))?;
+        }
+        Ok(())
+    }
+
+    /// Write the personalization signals to the attached backend.
+    fn save_user_signals(&self) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let json = serde_json::to_string(&self.signals)
+                .map_err(persistence_error("failed to serialize personalization"))?;
+            pm.save_config(USER_SIGNALS_KEY, &json)
+                .map_err(persistence_error("failed to save personalization"))?;
+        }
+        Ok(())
+    }
+
+    /// Write the metrics counters to the attached backend when
+    /// `metrics.persist` is set.
+    fn save_metrics(&self) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            if !self.base_config.metrics.persist {
+                return Ok(());
+            }
+            let json = serde_json::to_string(&self.metrics)
+                .map_err(persistence_error("failed to serialize metrics"))?;
+            pm.save_config(METRICS_KEY, &json)
+                .map_err(persistence_error("failed to save metrics"))?;
+        }
+        Ok(())
+    }
+
+    /// Write the offline query queue to the attached backend.
+    fn save_query_queue(&self) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            let json = serde_json::to_string(&self.queue)
+                .map_err(persistence_error("failed to serialize the offline queue"))?;
+            pm.save_config(QUERY_QUEUE_KEY, &json)
+                .map_err(persistence_error("failed to save the offline queue"))?;
+        }
+        Ok(())
+    }
+
+    /// Write the quota usage to the attached backend when limits are set
+    /// and it changed since the last write.
+    fn save_quota_usage(&mut self) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            if !self.quota_dirty || !self.quota.config().is_enabled() {
+                return Ok(());
+            }
+            let json = serde_json::to_string(&self.quota.usage(now_ms()))
+                .map_err(persistence_error("failed to serialize quota usage"))?;
+            pm.save_config(QUOTA_USAGE_KEY, &json)
+                .map_err(persistence_error("failed to save quota usage"))?;
+            self.quota_dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Apply `change` to the memory and write the fact it returns to the
+    /// attached backend, forgetting facts evicted to make room.
+    fn store_fact(
+        &mut self,
+        change: impl FnOnce(&mut MemoryStore) -> MemoryFact,
+    ) -> Result<MemoryFact, OrchestratorError> {
+        let before: Vec<u64> = self.memory.facts().iter().map(|fact| fact.id).collect();
+        let fact = change(&mut self.memory);
+        let evicted: Vec<u64> = before
+            .into_iter()
+            .filter(|&id| self.memory.get(id).is_none())
+            .collect();
+        self.forget_facts(&evicted)?;
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            pm.save_memory_fact(&fact)
+                .map_err(persistence_error("failed to save memory"))?;
+        }
+        Ok(fact)
+    }
+
+    /// Delete facts `ids` from the attached backend.
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn forget_facts(&self, ids: &[u64]) -> Result<(), OrchestratorError> {
+        #[cfg(feature = "persistence")]
+        if let Some(pm) = &self.persistence {
+            for &id in ids {
+                pm.delete_memory_fact(id)
+                    .map_err(persistence_error("failed to delete memory"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the active project's conversation history.
+    pub fn clear_history(&mut self) {
+        self.context.clear_history();
+    }
+
+    /// Borrow the N most recent turns from the active project's history.
+    pub fn recent_history(&self, n: usize) -> Vec<ConversationTurn> {
+        self.context.recent_history(n)
+    }
+
+    /// FLASHCARDS: Deck built from `project`'s history (the whole history
+    /// when `None`), oldest turn first, with `tags` on every card.
+    pub fn flashcard_deck(&self, name: &str, project: Option<&str>, tags: &[String]) -> Deck {
+        let mut turns = match project {
+            Some(project) => self.context.project_history(project).unwrap_or_default(),
+            None => self.context.recent_history(usize::MAX),
+        };
+        turns.reverse();
+        Deck::from_turns(name, &turns, tags)
+    }
+
+    /// JOURNAL: Append finished days of conversation to the journal
+    /// directory, returning how many turns were written. Hosts call this
+    /// from their own scheduler; it also runs on `Background` when
+    /// `journal.enabled` is set.
+    pub fn export_journal(&mut self) -> Result<usize, OrchestratorError> {
+        let Some(journal) = self.journal.as_mut() else {
+            return Err(OrchestratorError::NotConfigured(
+                "journal.dir is not configured".to_string(),
+            ));
+        };
+        let turns = self.context.recent_history(usize::MAX);
+        let entries = journal
+            .export(&turns, now_ms())
+            .map_err(OrchestratorError::PersistenceError)?;
+        if let (true, Some(day)) = (entries > 0, journal.last_exported_day()) {
+            self.events.publish(&Event::DailySummaryReady {
+                date: crate::journal::format_date(day),
+                entries,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// WEBHOOKS: Start delivering the events selected in the loaded
+    /// configuration's `webhooks.sinks`, resolving signing secrets through
+    /// `provider`. Replaces any previously started dispatcher.
+    #[cfg(feature = "network")]
+    pub fn start_webhooks(
+        &mut self,
+        provider: Arc<dyn SecretProvider>,
+    ) -> Result<(), OrchestratorError> {
+        let dispatcher = WebhookDispatcher::start(
+            self.base_config.webhooks.clone(),
+            provider,
+            &mut self.supervisor,
+        )
+        .map_err(OrchestratorError::InvalidInput)?;
+        if let Some((_, old)) = self.webhooks.take() {
+            self.events.unsubscribe(old);
+        }
+        let subscription = dispatcher.attach(&mut self.events);
+        self.webhooks = Some((dispatcher, subscription));
+        Ok(())
+    }
+
+    /// Delivered and failed webhook payloads, once webhooks are started.
+    #[cfg(feature = "network")]
+    pub fn webhook_stats(&self) -> Option<WebhookStats> {
+        self.webhooks.as_ref().map(|(dispatcher, _)| dispatcher.stats())
+    }
+
+    /// CACHE: Drop every cached response, e.g. after the user's data
+    /// changed in ways the cached answers do not reflect.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Response cache hit and miss counters.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// METRICS: Per-route counts and latencies, MLP confidences, cache
+    /// hit rate and blocked queries per rule, since startup (or across
+    /// restarts with `metrics.persist`).
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// ENERGY: Estimated battery energy spent answering queries since
+    /// startup, per project, and its share of a full charge.
+    pub fn energy_report(&self) -> EnergyReport {
+        self.energy.report(self.base_config.energy.battery_wh)
+    }
+
+    /// Estimated battery energy spent in `project` (`None` = queries
+    /// outside any project).
+    pub fn project_energy(&self, project: Option<&str>) -> Option<&ProjectEnergy> {
+        self.energy.project(project)
+    }
+
+    /// Start counting metrics from zero.
+    pub fn reset_metrics(&mut self) -> Result<(), OrchestratorError> {
+        self.metrics = MetricsRegistry::new();
+        self.save_metrics()
+    }
+
+    /// Journal export job, e.g. to persist or restore its progress.
+    pub fn journal_mut(&mut self) -> Option<&mut JournalExporter> {
+        self.journal.as_mut()
+    }
+
+    /// SEARCH: The `k` previous turns most similar in meaning to `text`.
+    pub fn search_history(
+        &self,
+        text: &str,
+        k: usize,
+    ) -> Result<Vec<TurnMatch>, OrchestratorError> {
+        self.context
+            .search(text, k)
+            .map_err(OrchestratorError::BackendFailure)
+    }
+}
+
+/// Outcome of `Orchestrator::begin`
+pub(crate) enum Begun {
+    /// Answered without a backend call: blocked, cached or chunked
+    Answered(Box<Response>),
+    /// Routed; waiting for its backends
+    Pending(Box<PendingQuery>),
+}
+
+/// A routed query waiting for its backends
+pub(crate) struct PendingQuery {
+    generation: Generation,
+    turn_id: u64,
+    /// Cache key parts besides the query itself
+    project: Option<String>,
+    embedding: Option<Vec<f32>>,
+    /// Keeps the query counted as in flight until it is completed
+    _in_flight: InFlight,
+}
+
+impl PendingQuery {
+    /// Call the backends; needs no access to the orchestrator
+    pub(crate) fn generate(self) -> GeneratedQuery {
+        self.generate_streaming(&mut |_| {})
+    }
+
+    /// `generate`, passing pieces of the answer to `on_chunk` as the
+    /// backend produces them (see `Generation::run`)
+    pub(crate) fn generate_streaming(self, on_chunk: &mut dyn FnMut(&str)) -> GeneratedQuery {
+        GeneratedQuery {
+            generated: self.generation.run(on_chunk),
+            turn_id: self.turn_id,
+            project: self.project,
+            embedding: self.embedding,
+            _in_flight: self._in_flight,
+        }
+    }
+}
+
+/// A query whose backends answered (or failed), for
+/// `Orchestrator::complete`
+pub(crate) struct GeneratedQuery {
+    generated: Result<Generated, OrchestratorError>,
+    turn_id: u64,
+    project: Option<String>,
+    embedding: Option<Vec<f32>>,
+    _in_flight: InFlight,
+}
+
+/// A routed query ready for its backends, with everything needed to call
+/// them; built by `Orchestrator::plan_generation`.
+struct Generation {
+    query: Query,
+    /// `query` with the assembled prompt as its text
+    backend_query: Query,
+    route: RoutingDecision,
+    confidence: f32,
+    turn_id: u64,
+    model: String,
+    explanation: RoutingExplanation,
+    /// Name and backend of a registered custom target
+    target: Option<(String, Arc<dyn TargetBackend>)>,
+    /// The pooled local model, for queries answered on the Local route
+    local: Option<Arc<dyn TargetBackend>>,
+    remote: Option<RemoteCall>,
+    hybrid: Option<HybridCall>,
+}
+
+/// A generated answer; `None` text is answered by the placeholder.
+struct Generated {
+    query: Query,
+    route: RoutingDecision,
+    confidence: f32,
+    turn_id: u64,
+    model: String,
+    text: Option<String>,
+    explanation: RoutingExplanation,
+    degraded: Option<Degradation>,
+    /// Time spent in the backends, fallbacks included
+    latency_ms: u64,
+}
+
+/// The Remote backend and what to do when it fails
+struct RemoteCall {
+    backend: Arc<dyn TargetBackend>,
+    online: bool,
+    fallback: FallbackConfig,
+    chain: Vec<FallbackRoute>,
+}
+
+/// A route tried after Remote failed; custom targets carry their name,
+/// backend and prompt, built-in routes are answered by the placeholder.
+struct FallbackRoute {
+    route: RoutingDecision,
+    target: Option<(String, Arc<dyn TargetBackend>, Query)>,
+}
+
+/// Both Hybrid backends and what the configured strategy needs
+struct HybridCall {
+    local: Arc<dyn TargetBackend>,
+    remote: Arc<dyn TargetBackend>,
+    online: bool,
+    strategy: HybridStrategy,
+    /// Earlier turns found for `RetrieveGenerate`
+    retrieved: Vec<TurnMatch>,
+    blend: BlendConfig,
+    embedder: Arc<dyn Embedder>,
+}
+
+impl Generation {
+    /// Call the backends: the custom target, the local model, the Remote
+    /// backend and its fallback chain, or the Hybrid pair, timing them.
+    /// Needs nothing from
+    /// the orchestrator, so it can run while other queries are routed.
+    /// The custom target, local model and Remote backend stream their
+    /// answers to `on_chunk`; fallbacks and Hybrid answers, which are
+    /// assembled from several backends, are not streamed.
+    fn run(self, on_chunk: &mut dyn FnMut(&str)) -> Result<Generated, OrchestratorError> {
+        let started = Stopwatch::start();
+        let Generation {
+            query,
+            backend_query,
+            mut route,
+            confidence,
+            turn_id,
+            mut model,
+            mut explanation,
+            target,
+            local,
+            remote,
+            hybrid,
+        } = self;
+        let mut text = None;
+        let mut degraded = None;
+        if let RoutingDecision::Custom(_) = route {
+            if let Some((name, backend)) = target {
+                match backend.generate_streaming(&backend_query, on_chunk) {
+                    Ok(answer) => {
+                        model = name;
+                        text = Some(answer);
+                    }
+                    Err(e) => {
+                        explanation
+                            .adjustments
+                            .push(format!("target '{}' failed ({}); using Local", name, e));
+                        degraded = Some(Degradation {
+                            requested: route,
+                            failure: FailureKind::classify(&e),
+                            error: e,
+                        });
+                    }
+                }
+            }
+            if text.is_none() {
+                route = RoutingDecision::Local;
+            }
+        }
+        if let (RoutingDecision::Local, None, Some(local)) = (route, &text, local) {
+            match local.generate_streaming(&backend_query, on_chunk) {
+                Ok(answer) => {
+                    model = LOCAL_MODEL.to_string();
+                    text = Some(answer);
+                }
+                Err(e) => explanation
+                    .adjustments
+                    .push(format!("local model failed ({}); using the placeholder", e)),
+            }
+        }
+        if let Some(remote) = remote {
+            let outcome = if remote.online {
+                remote.backend.generate_streaming(&backend_query, on_chunk)
+            } else {
+                Err("device is offline".to_string())
+            };
+            match outcome {
+                Ok(answer) => text = Some(answer),
+                Err(error) => {
+                    let failure = FailureKind::classify(&error);
+                    if !remote.fallback.retries(failure) {
+                        return Err(match failure {
+                            FailureKind::Offline => OrchestratorError::NetworkUnavailable { route },
+                            _ => OrchestratorError::BackendFailure(format!(
+                                "Remote {}: {}",
+                                failure, error
+                            )),
+                        });
+                    }
+                    let degradation = Degradation {
+                        requested: route,
+                        failure,
+                        error,
+                    };
+                    let Some((next, answer)) = remote.run_fallback(&mut explanation) else {
+                        return Err(OrchestratorError::BackendFailure(format!(
+                            "{}; no fallback route answered",
+                            degradation
+                        )));
+                    };
+                    explanation
+                        .adjustments
+                        .push(format!("{}; answered by {:?}", degradation, next));
+                    if let Some((answer, target)) = answer {
+                        text = Some(answer);
+                        model = target;
+                    }
+                    route = next;
+                    degraded = Some(degradation);
+                }
+            }
+        }
+        if let Some(hybrid) = hybrid {
+            if let Some((answer, remote_used)) = hybrid.run(&backend_query, &mut explanation) {
+                text = Some(answer);
+                if remote_used {
+                    model = HYBRID_MODEL.to_string();
+                } else {
+                    route = RoutingDecision::Local;
+                }
+            }
+        }
+        Ok(Generated {
+            query,
+            route,
+            confidence,
+            turn_id,
+            model,
+            text,
+            explanation,
+            degraded,
+            latency_ms: started.elapsed_ms(),
+        })
+    }
+}
+
+impl RemoteCall {
+    /// Answer on the first fallback route that can: the route and, unless
+    /// it is the built-in placeholder, the answer and the model that gave
+    /// it.
+    fn run_fallback(
+        &self,
+        explanation: &mut RoutingExplanation,
+    ) -> Option<(RoutingDecision, Option<(String, String)>)> {
+        for fallback in &self.chain {
+            let Some((name, backend, query)) = &fallback.target else {
+                return Some((fallback.route, None));
+            };
+            match backend.generate(query) {
+                Ok(answer) => return Some((fallback.route, Some((answer, name.clone())))),
+                Err(e) => explanation
+                    .adjustments
+                    .push(format!("fallback target '{}' failed ({})", name, e)),
+            }
+        }
+        None
+    }
+}
+
+impl HybridCall {
+    /// Answer a Hybrid query with both backends by the configured
+    /// strategy (see `hybrid`), returning the answer and whether Remote
+    /// contributed to it. Offline, or when Remote fails, Local answers
+    /// alone. `None` without answers.
+    fn run(
+        &self,
+        backend_query: &Query,
+        explanation: &mut RoutingExplanation,
+    ) -> Option<(String, bool)> {
+        let (local, remote, online) = (&self.local, &self.remote, self.online);
+        match self.strategy {
+            HybridStrategy::Blend if online => self.blend(backend_query, explanation),
+            HybridStrategy::Blend => match local.generate(backend_query) {
+                Ok(answer) => Some((answer, false)),
+                Err(e) => {
+                    explanation
+                        .adjustments
+                        .push(format!("Local half of Hybrid failed ({})", e));
+                    None
+                }
+            },
+            HybridStrategy::DraftRefine => {
+                let draft = match local.generate(backend_query) {
+                    Ok(draft) => draft,
+                    Err(e) if online => {
+                        explanation
+                            .adjustments
+                            .push(format!("Local draft failed ({}); using Remote alone", e));
+                        return remote.generate(backend_query).ok().map(|answer| (answer, true));
+                    }
+                    Err(e) => {
+                        explanation
+                            .adjustments
+                            .push(format!("Local draft failed ({})", e));
+                        return None;
+                    }
+                };
+                if !online {
+                    return Some((draft, false));
+                }
+                let refine = Query {
+                    text: hybrid::refine_prompt(&backend_query.text, &draft),
+                    ..backend_query.clone()
+                };
+                match remote.generate(&refine) {
+                    Ok(refined) => {
+                        explanation
+                            .adjustments
+                            .push("Remote refined a Local draft".to_string());
+                        Some((refined, true))
+                    }
+                    Err(e) => {
+                        explanation.adjustments.push(format!(
+                            "Remote refinement failed ({}); using the Local draft",
+                            e
+                        ));
+                        Some((draft, false))
+                    }
+                }
+            }
+            HybridStrategy::RetrieveGenerate => {
+                let augmented = Query {
+                    text: hybrid::retrieval_prompt(&backend_query.text, &self.retrieved),
+                    ..backend_query.clone()
+                };
+                if online {
+                    match remote.generate(&augmented) {
+                        Ok(answer) => return Some((answer, true)),
+                        Err(e) => explanation
+                            .adjustments
+                            .push(format!("Remote half of Hybrid failed ({}); using Local", e)),
+                    }
+                }
+                match local.generate(&augmented) {
+                    Ok(answer) => Some((answer, false)),
+                    Err(e) => {
+                        explanation
+                            .adjustments
+                            .push(format!("Local half of Hybrid failed ({})", e));
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ask both backends and blend their answers, falling back to
+    /// whichever answered.
+    fn blend(&self, query: &Query, explanation: &mut RoutingExplanation) -> Option<(String, bool)> {
+        let config = &self.blend;
+        match (self.local.generate(query), self.remote.generate(query)) {
+            (Ok(local), Ok(remote)) if config.enabled => {
+                let blended = blend::blend(
+                    Candidate {
+                        route: RoutingDecision::Local,
+                        text: local,
+                        confidence: config.local_confidence,
+                    },
+                    Candidate {
+                        route: RoutingDecision::Remote,
+                        text: remote,
+                        confidence: config.remote_confidence,
+                    },
+                    self.embedder.as_ref(),
+                    config,
+                );
+                explanation.adjustments.push(blended.provenance());
+                Some((blended.text, true))
+            }
+            (Ok(_), Ok(remote)) => Some((remote, true)),
+            (Err(e), Ok(remote)) => {
+                explanation
+                    .adjustments
+                    .push(format!("Local half of Hybrid failed ({}); using Remote", e));
+                Some((remote, true))
+            }
+            (Ok(local), Err(e)) => {
+                explanation
+                    .adjustments
+                    .push(format!("Remote half of Hybrid failed ({}); using Local", e));
+                Some((local, false))
+            }
+            (Err(local_error), Err(remote_error)) => {
+                explanation.adjustments.push(format!(
+                    "both Hybrid backends failed ({}; {})",
+                    local_error, remote_error
+                ));
+                None
+            }
+        }
+    }
+}
+
+/// Record `user` as the owner of the database.
+#[cfg(feature = "persistence")]
+fn save_user_profile(pm: &PersistenceManager, user: &UserProfile) -> Result<(), OrchestratorError> {
+    let json = serde_json::to_string(user)
+        .map_err(persistence_error("failed to serialize user profile"))?;
+    pm.save_config(USER_PROFILE_KEY, &json)
+        .map_err(persistence_error("failed to save user profile"))
+}
+
+/// Map a storage or encoding error to a persistence error saying what
+/// was being done.
+#[cfg(feature = "persistence")]
+fn persistence_error<E: std::fmt::Display>(
+    action: &'static str,
+) -> impl FnOnce(E) -> OrchestratorError {
+    move |e| OrchestratorError::PersistenceError(format!("{}: {}", action, e))
+}
+
+/// Whether `route` spends remote API tokens.
+fn spends_remote_tokens(route: RoutingDecision) -> bool {
+    matches!(route, RoutingDecision::Remote | RoutingDecision::Hybrid)
+}
+
+impl Default for Orchestrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_switch::AppProject;
+    use crate::mlp::MLP;
+    use crate::personalization::{Expertise, Verbosity};
+    use crate::router::PERSONAL_FEATURE_OFFSET;
+    use crate::quota::{self, QuotaConfig};
+    use crate::supervisor::TaskState;
+    use crate::types::ModelCapability;
+
+    #[test]
+    fn test_shutdown_without_backend() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.events_mut().subscribe(|_| {});
+        orchestrator.update_ambient(&SensorBuffer::new(1));
+        orchestrator.notify_wake_trigger();
+
+        let report = orchestrator.shutdown();
+        assert!(report.is_clean());
+        assert_eq!(report.sampling_commands_discarded, 1);
+        assert_eq!(report.subscribers_released, 1);
+        assert_eq!(report.backends_closed, 0);
+
+        assert!(orchestrator.is_shut_down());
+        assert!(orchestrator.process(Query::new("hello")).is_err());
+        assert_eq!(orchestrator.shutdown(), ShutdownReport::default());
+    }
+
+    #[test]
+    fn test_sensor_batches_fill_the_buffer_from_usable_sensors() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.declare_sensor(SensorType::Gps, SensorAvailability::PermissionDenied);
+        let batch = sensor_batch::encode(&[
+            SensorReading::with_timestamp(SensorType::Accelerometer, vec![0.0, 0.0, 9.8], 10),
+            SensorReading::with_timestamp(SensorType::Gps, vec![51.5, -0.1], 10),
+            SensorReading::with_timestamp(SensorType::Light, vec![300.0], 20),
+        ]);
+        assert_eq!(orchestrator.push_sensor_batch(&batch).ok(), Some(2));
+        assert_eq!(orchestrator.sensor_buffer().len(), 2);
+        assert!(orchestrator.sensor_buffer().readings_of_type(SensorType::Gps).is_empty());
+
+        let broken = &batch[..batch.len() - 2];
+        assert!(matches!(
+            orchestrator.push_sensor_batch(broken),
+            Err(OrchestratorError::InvalidInput(_))
+        ));
+        assert_eq!(orchestrator.sensor_buffer().len(), 2);
+        orchestrator.refresh_ambient();
+        assert_eq!(orchestrator.sensor_buffer().len(), 2);
+    }
+
+    #[test]
+    fn test_gyroscope_batches_add_fused_orientation() {
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.orientation(), None);
+        let batch = sensor_batch::encode(&[
+            SensorReading::with_timestamp(SensorType::Accelerometer, vec![0.0, 0.0, 9.8], 10),
+            SensorReading::with_timestamp(SensorType::Gyroscope, vec![1.0, 0.0, 0.0], 20),
+            SensorReading::with_timestamp(SensorType::Gyroscope, vec![1.0, 0.0, 0.0], 120),
+        ]);
+        assert_eq!(orchestrator.push_sensor_batch(&batch).ok(), Some(3));
+        let derived = orchestrator
+            .sensor_buffer()
+            .readings_of_type(SensorType::Orientation);
+        assert_eq!(derived.len(), 2);
+        assert_eq!(derived[1].timestamp_ms, 120);
+        let roll = orchestrator.orientation().map_or(0.0, |o| o.roll);
+        assert!((roll - 0.1).abs() < 1e-4, "{}", roll);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_enabled_sensor_log_records_batches() {
+        let mut config = OrchestratorConfig::default();
+        config.sensor_log.enabled = true;
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert!(orchestrator.sensor_log().is_none());
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+
+        let batch = sensor_batch::encode(&[
+            SensorReading::with_timestamp(SensorType::Light, vec![300.0], 1_000),
+            SensorReading::with_timestamp(SensorType::Light, vec![100.0], 1_500),
+        ]);
+        assert_eq!(orchestrator.push_sensor_batch(&batch).ok(), Some(2));
+        let Some(log) = orchestrator.sensor_log() else {
+            panic!("persistence is attached");
+        };
+        assert_eq!(log.raw(SensorType::Light, 0, 2_000).map(|r| r.len()), Ok(2));
+        let Ok(seconds) = log.per_second(SensorType::Light, 0, 2_000) else {
+            panic!("per-second query should succeed");
+        };
+        assert_eq!(seconds[0].values, vec![200.0]);
+    }
+
+    #[test]
+    fn test_recognized_activity_reaches_device_state() {
+        let mut orchestrator = Orchestrator::new();
+        let mut buffer = SensorBuffer::new(64);
+        for i in 0..64 {
+            let z = 9.81 + if i % 2 == 0 { 0.01 } else { -0.01 };
+            buffer.push(SensorReading::with_timestamp(
+                SensorType::Accelerometer,
+                vec![0.0, 0.0, z],
+                i * 20,
+            ));
+        }
+        orchestrator.update_ambient(&buffer);
+        assert_eq!(orchestrator.activity().activity, Activity::Still);
+        assert_eq!(orchestrator.device_state().activity, Activity::Still);
+
+        // A host-reported activity wins over the recognized one
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            activity: Activity::Driving,
+            ..DeviceState::default()
+        }));
+        assert!(orchestrator.should_throttle_compute());
+        orchestrator.update_ambient(&SensorBuffer::new(1));
+        assert_eq!(orchestrator.activity(), ActivityEstimate::default());
+    }
+
+    #[test]
+    fn test_walking_with_phone_in_pocket_keeps_full_motion_rate() {
+        let pocket = |bounce: f32| {
+            let mut buffer = SensorBuffer::new(128);
+            buffer.push(SensorReading::with_timestamp(SensorType::Proximity, vec![0.0], 0));
+            buffer.push(SensorReading::with_timestamp(SensorType::Light, vec![1.0], 0));
+            for i in 0..100 {
+                let y = 9.8 + if i % 2 == 0 { bounce } else { -bounce };
+                buffer.push(SensorReading::with_timestamp(
+                    SensorType::Accelerometer,
+                    vec![0.5, y, 1.0],
+                    i * 20,
+                ));
+            }
+            buffer
+        };
+        let mut orchestrator = Orchestrator::new();
+
+        // Sitting: the pocketed phone's motion sensors slow down
+        assert_eq!(orchestrator.update_ambient(&pocket(0.0)), AmbientState::InPocket);
+        let commands = orchestrator.take_sampling_commands();
+        assert!(commands.iter().all(|c| c.rate_hz == 5.0));
+
+        // Walking: still in the pocket, but back to the full rate
+        assert_eq!(orchestrator.update_ambient(&pocket(4.0)), AmbientState::InPocket);
+        let commands = orchestrator.take_sampling_commands();
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|c| c.rate_hz == 50.0));
+    }
+
+    #[test]
+    fn test_context_changes_are_published_and_switch_projects() {
+        let mut config = OrchestratorConfig::default();
+        config.context_switch.confirmations = 1;
+        config.context_switch.app_projects = vec![AppProject {
+            app: "org.example.ide".to_string(),
+            project: "compiler".to_string(),
+        }];
+        let mut orchestrator = Orchestrator::with_config(config);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::ContextChanged], move |event| {
+                if let (Event::ContextChanged { change }, Ok(mut seen)) = (event, sink.lock()) {
+                    seen.push(change.clone());
+                }
+            });
+
+        orchestrator.notify_foreground_app("org.example.ide");
+        assert_eq!(orchestrator.current_project(), Some("compiler"));
+        orchestrator.notify_foreground_app("org.example.ide");
+        orchestrator.notify_foreground_app("org.example.mail");
+        assert_eq!(orchestrator.current_project(), Some("compiler"));
+
+        let batch = sensor_batch::encode(&[
+            SensorReading::with_timestamp(SensorType::Gps, vec![51.50, -0.1, 5.0], 10),
+            SensorReading::with_timestamp(SensorType::Gps, vec![51.52, -0.1, 5.0], 20),
+        ]);
+        assert_eq!(orchestrator.push_sensor_batch(&batch).ok(), Some(2));
+        orchestrator.refresh_ambient();
+        orchestrator.refresh_ambient();
+
+        let Ok(seen) = seen.lock() else {
+            panic!("event log should not be poisoned");
+        };
+        assert_eq!(seen.len(), 3, "{:?}", seen);
+        assert!(matches!(
+            &seen[0],
+            ContextEvent::AppSwitch { from: None, to } if to.ends_with("ide")
+        ));
+        assert!(matches!(seen[2], ContextEvent::LocationChange { .. }));
+    }
+
+    #[test]
+    fn test_geofence_entry_switches_project() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.switch_project("personal");
+        orchestrator.add_geofence(Geofence {
+            name: "office".to_string(),
+            latitude: 51.50,
+            longitude: -0.1,
+            radius_m: 200.0,
+            project: Some("work".to_string()),
+        });
+
+        let fix = |lat: f32, t: u64| {
+            sensor_batch::encode(&[SensorReading::with_timestamp(
+                SensorType::Gps,
+                vec![lat, -0.1, 5.0],
+                t,
+            )])
+        };
+        assert_eq!(orchestrator.push_sensor_batch(&fix(51.52, 10)).ok(), Some(1));
+        orchestrator.refresh_ambient();
+        assert!(orchestrator.current_regions().is_empty());
+        assert_eq!(orchestrator.current_project(), Some("personal"));
+
+        assert_eq!(orchestrator.push_sensor_batch(&fix(51.5001, 20)).ok(), Some(1));
+        orchestrator.refresh_ambient();
+        assert_eq!(orchestrator.current_regions(), ["office".to_string()]);
+        assert_eq!(orchestrator.current_project(), Some("work"));
+        assert!(orchestrator.remove_geofence("office"));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_compact_applies_retention() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut config = OrchestratorConfig::default();
+        config.retention.max_turns_per_project = 2;
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+
+        for i in 0..5 {
+            let Ok(_) = orchestrator.process(Query::new(format!("query {}", i))) else {
+                panic!("process should succeed");
+            };
+        }
+        // Flushing the buffered turns prunes them
+        let Ok(kept) = orchestrator.history_page(None, 0, 10) else {
+            panic!("history_page should succeed");
+        };
+        let queries: Vec<&str> = kept.iter().map(|t| t.query.text.as_str()).collect();
+        assert_eq!(queries, ["query 3", "query 4"]);
+        assert_eq!(orchestrator.compact(), Ok(0));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_write_behind_loses_at_most_the_unwritten_batch() {
+        let path = std::env::temp_dir().join(format!("write-behind-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = OrchestratorConfig {
+            write_behind: crate::persistence::WriteBehindConfig {
+                max_turns: 3,
+                max_delay_ms: 60_000,
+            },
+            ..OrchestratorConfig::default()
+        };
+        {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let mut orchestrator = Orchestrator::with_config(config.clone());
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            for i in 0..4 {
+                let Ok(_) = orchestrator.process(Query::new(format!("query {}", i))) else {
+                    panic!("process should succeed");
+                };
+            }
+            assert_eq!(orchestrator.flush_due(), Ok(0));
+            // Killed without a shutdown: nothing is written on drop
+        }
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        assert_eq!(pm.conversation_count(None), Ok(3));
+
+        // With no delay allowed, every turn is written at once
+        config.write_behind.max_delay_ms = 0;
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(3));
+        let Ok(_) = orchestrator.process(Query::new("query 4")) else {
+            panic!("process should succeed");
+        };
+        drop(orchestrator);
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        assert_eq!(pm.conversation_count(None), Ok(4));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_switch_user_keeps_users_apart() {
+        let path = std::env::temp_dir().join(format!("users-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut orchestrator = Orchestrator::new();
+        let alice = UserProfile::new("alice", "Alice");
+        let Ok(previous) = orchestrator.switch_user(alice.clone()) else {
+            panic!("switch_user should succeed");
+        };
+        assert_eq!(previous.id, crate::user::DEFAULT_USER);
+        let Ok(db) = PersistenceManager::new(&path) else {
+            panic!("new should succeed");
+        };
+        assert_eq!(orchestrator.attach_persistence(db), Ok(0));
+        let Ok(_) = orchestrator.process(Query::new("alice's question")) else {
+            panic!("process should succeed");
+        };
+
+        let bob = UserProfile {
+            profile: Profile::OfflineOnly,
+            ..UserProfile::new("bob", "Bob")
+        };
+        assert_eq!(orchestrator.switch_user(bob), Ok(alice.clone()));
+        assert!(orchestrator.recent_history(10).is_empty());
+        assert_eq!(orchestrator.profile(), Profile::OfflineOnly);
+        // Alice's turn was written before the switch, and her database is
+        // refused while Bob is active
+        let Ok(db) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        assert_eq!(db.conversation_count(None), Ok(1));
+        assert!(orchestrator.attach_persistence(db).is_err());
+        assert!(orchestrator.switch_user(UserProfile::new("../eve", "")).is_err());
+
+        let ids: Vec<&str> = orchestrator.users().iter().map(|u| u.id.as_str()).collect();
+        assert_eq!(ids, ["bob", "alice", "default"]);
+        let Ok(_) = orchestrator.switch_user(alice) else {
+            panic!("switch_user should succeed");
+        };
+        let history = orchestrator.recent_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].query.text, "alice's question");
+        assert_eq!(orchestrator.profile(), Profile::Balanced);
+        assert!(orchestrator.remove_user("bob"));
+        assert!(!orchestrator.remove_user("alice"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_switch_user_parks_usage_settings_and_policy() {
+        let mut config = OrchestratorConfig::default();
+        config.expert.policies.push(expert::PolicySpec {
+            name: "kids-mode".to_string(),
+            rules: vec![expert::RuleSpec {
+                id: "KIDS_001".to_string(),
+                keywords: vec!["casino".to_string()],
+                ..expert::RuleSpec::default()
+            }],
+            ..expert::PolicySpec::default()
+        });
+        let mut orchestrator = Orchestrator::with_config(config);
+        let defaults = orchestrator.settings().clone();
+        let Ok(_) = orchestrator.process(Query::new("grown-up question")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(orchestrator.update_setting(Setting::RoutingThreshold(0.8)), Ok(true));
+        let parent = orchestrator.settings().clone();
+
+        let unknown = UserProfile {
+            policy: Some("teen-mode".to_string()),
+            ..UserProfile::new("teen", "Teen")
+        };
+        assert!(orchestrator.switch_user(unknown).is_err());
+        let kid = UserProfile {
+            policy: Some("kids-mode".to_string()),
+            ..UserProfile::new("kid", "Kid")
+        };
+        let Ok(previous) = orchestrator.switch_user(kid) else {
+            panic!("switch_user should succeed");
+        };
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 0);
+        assert_eq!(orchestrator.energy_report().total_joules, 0.0);
+        assert!(orchestrator.last_prompt().is_none());
+        assert_eq!(orchestrator.settings(), &defaults);
+        assert_eq!(orchestrator.active_policy(), Some("kids-mode"));
+        let blocked = orchestrator.process(Query::new("best casino games")).map(|r| r.route);
+        assert_eq!(blocked, Ok(RoutingDecision::Blocked));
+
+        let Ok(_) = orchestrator.switch_user(previous) else {
+            panic!("switch_user should succeed");
+        };
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 1);
+        assert!(orchestrator.energy_report().total_joules > 0.0);
+        assert!(orchestrator.last_prompt().is_some());
+        assert_eq!(orchestrator.settings(), &parent);
+        assert_eq!(orchestrator.active_policy(), None);
+        let allowed = orchestrator.process(Query::new("best casino games")).map(|r| r.route);
+        assert_ne!(allowed, Ok(RoutingDecision::Blocked));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_update_setting_applies_saves_and_notifies() {
+        let path = std::env::temp_dir().join(format!("settings-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut orchestrator = Orchestrator::new();
+        let Ok(db) = PersistenceManager::new(&path) else {
+            panic!("new should succeed");
+        };
+        assert_eq!(orchestrator.attach_persistence(db), Ok(0));
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::SettingChanged], move |event| {
+                if let Ok(mut seen) = sink.lock() {
+                    seen.push(event.clone());
+                }
+            });
+
+        let project = Setting::DefaultProject(Some("notes".to_string()));
+        for setting in [
+            Setting::RoutingThreshold(0.8),
+            project.clone(),
+            Setting::TelemetryOptIn(false),
+        ] {
+            assert_eq!(orchestrator.update_setting(setting), Ok(true));
+        }
+        assert_eq!(orchestrator.update_setting(project.clone()), Ok(false));
+        assert!(orchestrator.update_setting(Setting::RoutingThreshold(2.0)).is_err());
+        assert_eq!(orchestrator.router.config().heuristic_threshold, 0.8);
+        assert_eq!(orchestrator.current_project(), Some("notes"));
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 0);
+        orchestrator.set_profile(Profile::QualityFirst);
+        assert!((orchestrator.router.config().heuristic_threshold - 0.48).abs() < 1e-6);
+        let Ok(seen) = seen.lock() else {
+            panic!("event log lock poisoned");
+        };
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[1], Event::SettingChanged { setting: project });
+        drop(seen);
+        drop(orchestrator);
+
+        // A restart picks the stored settings back up
+        let mut restarted = Orchestrator::new();
+        let Ok(db) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let Ok(_) = restarted.attach_persistence(db) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(restarted.settings().routing_threshold, 0.8);
+        assert!(!restarted.settings().telemetry_opt_in);
+        assert_eq!(restarted.current_project(), Some("notes"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_shutdown_flushes_and_persists_session() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+
+        for i in 0..3 {
+            let Ok(_) = orchestrator.process(Query::new(format!("query {}", i))) else {
+                panic!("process should succeed");
+            };
+        }
+
+        let report = orchestrator.shutdown();
+        assert!(report.is_clean(), "errors: {:?}", report.errors);
+        assert_eq!(report.turns_flushed, 3);
+        assert!(report.session_saved);
+        assert!(!report.reservoir_saved);
+        assert_eq!(report.backends_closed, 1);
+    }
+
+    #[test]
+    fn test_local_responses_report_context_budget() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, RoutingDecision::Local);
+        assert_eq!(
+            response.metadata.context_budget,
+            Some(orchestrator.context_budget())
+        );
+    }
+
+    #[test]
+    fn test_slow_local_model_shrinks_context_budget() {
+        let mut config = OrchestratorConfig::default();
+        config.context_budget.target_p95_ms = 5;
+        config.context_budget.window = 2;
+        config.cache.enabled = false;
+        let mut orchestrator = Orchestrator::with_config(config);
+        // Offline, Hybrid queries are answered by the on-device model alone
+        go_offline(&mut orchestrator);
+        orchestrator.set_hybrid_backends(Arc::new(Slow(15)), Arc::new(Fixed("remote")));
+
+        let long = || Query::new("summarize this book").requiring(ModelCapability::LongContext);
+        let mut budgets = Vec::new();
+        for _ in 0..3 {
+            let Ok(response) = orchestrator.process(long()) else {
+                panic!("process should succeed");
+            };
+            assert!(response.text.starts_with("slow: "));
+            assert!(response.latency_ms >= 15);
+            budgets.push(response.metadata.context_budget);
+        }
+        // Two samples over the target shrink the budget by a quarter
+        assert_eq!(budgets, vec![Some(4_096), Some(4_096), Some(3_072)]);
+        assert_eq!(orchestrator.context_budget(), 3_072);
+    }
+
+    #[test]
+    fn test_record_feedback_updates_router() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let Some(turn_id) = response.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+
+        // Without a model the correction is only stored
+        assert_eq!(orchestrator.record_feedback(turn_id, RoutingDecision::Remote), Ok(0));
+        assert_eq!(orchestrator.online_trainer().corrections().len(), 1);
+
+        let model = MLP::new_with_seed(crate::router::FEATURE_DIM, vec![16], 3, 1);
+        assert!(orchestrator.load_router_model(model).is_ok());
+        // The stored correction is applied along with the new one
+        assert_eq!(orchestrator.record_feedback(turn_id, RoutingDecision::Remote), Ok(2));
+
+        assert!(orchestrator.record_feedback(turn_id + 1, RoutingDecision::Local).is_err());
+        assert!(orchestrator.record_feedback(turn_id, RoutingDecision::Blocked).is_err());
+    }
+
+    #[test]
+    fn test_rate_turn_scores_with_reward_fn() {
+        /// Reward equal to the rating, recording every outcome scored
+        struct Recorder(std::sync::Mutex<Vec<crate::reward::RouteOutcome>>);
+        impl RewardFn for Recorder {
+            fn reward(&self, outcome: &crate::reward::RouteOutcome) -> f32 {
+                self.0
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .push(outcome.clone());
+                outcome.rating.unwrap_or(0.0)
+            }
+        }
+
+        let mut orchestrator = Orchestrator::new();
+        let recorder = Arc::new(Recorder(std::sync::Mutex::new(Vec::new())));
+        orchestrator.set_reward_fn(recorder.clone());
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let Some(turn_id) = response.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+
+        // Without a model the outcome is only stored
+        assert_eq!(orchestrator.rate_turn(turn_id, 1.0), Ok(0));
+        assert_eq!(orchestrator.online_trainer().corrections().len(), 1);
+        // A badly rated outcome trains, but is no offline example
+        assert_eq!(orchestrator.rate_turn(turn_id, 0.0), Ok(0));
+        assert_eq!(orchestrator.online_trainer().corrections().len(), 1);
+
+        let model = MLP::new_with_seed(crate::router::FEATURE_DIM, vec![16], 3, 1);
+        assert!(orchestrator.load_router_model(model).is_ok());
+        assert_eq!(orchestrator.rate_turn(turn_id, 0.5), Ok(3));
+
+        let scored = recorder.0.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        assert_eq!(scored.len(), 3);
+        assert_eq!(scored[0].route, response.route);
+        assert_eq!(scored[0].latency_ms, response.latency_ms);
+        assert_eq!(scored[1].rating, Some(0.0));
+        drop(scored);
+
+        assert!(orchestrator.rate_turn(turn_id, 1.5).is_err());
+        assert!(orchestrator.rate_turn(turn_id + 1, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_responses_explain_their_route() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(blocked) = orchestrator.process(Query::new("what is my password")) else {
+            panic!("process should succeed");
+        };
+        let Some(explanation) = blocked.metadata.explanation else {
+            panic!("blocked responses should be explained");
+        };
+        assert_eq!(explanation.expert_rules, vec!["PRIVACY_001".to_string()]);
+
+        let Ok(heuristic) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let Some(explanation) = heuristic.metadata.explanation else {
+            panic!("routed responses should be explained");
+        };
+        assert_eq!(explanation.heuristic_rules.len(), 1);
+        assert!(explanation.top_features.is_empty());
+
+        let model = MLP::new_with_seed(crate::router::FEATURE_DIM, vec![16], 3, 1);
+        assert!(orchestrator.load_router_model(model).is_ok());
+        orchestrator.clear_cache();
+        let Ok(neural) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let Some(explanation) = neural.metadata.explanation else {
+            panic!("routed responses should be explained");
+        };
+        // Only the ambient one-hot is set by the Phase 1 feature extractor
+        assert!(explanation.heuristic_rules.is_empty());
+        assert_eq!(explanation.top_features.len(), 1);
+        assert_eq!(explanation.top_features[0].name, "ambient:Unknown");
+    }
+
+    #[test]
+    fn test_profiles_switch_at_runtime() {
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.capabilities().profile, Profile::Balanced);
+        let balanced_budget = orchestrator.context_budget();
+
+        orchestrator.set_profile(Profile::BatterySaver);
+        assert_eq!(orchestrator.capabilities().profile, Profile::BatterySaver);
+        assert!(orchestrator.context_budget() < balanced_budget);
+
+        orchestrator.set_profile(Profile::OfflineOnly);
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, RoutingDecision::Local);
+        let Some(turn_id) = response.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+        let remote = RegenerateOptions {
+            route: Some(RoutingDecision::Remote),
+            ..RegenerateOptions::default()
+        };
+        assert!(orchestrator.regenerate(turn_id, remote).is_err());
+
+        // Balanced restores the loaded configuration; the budget regrows
+        // from its clamped value as latencies allow
+        orchestrator.set_profile(Profile::Balanced);
+        let default_policy = crate::router::RouterConfig::default().policy;
+        assert_eq!(orchestrator.router.config().policy, default_policy);
+    }
+
+    #[test]
+    fn test_sla_violation_publishes_event() {
+        let mut config = OrchestratorConfig::default();
+        config.sla.remote.max_latency_ms = Some(5);
+        // Every turn has to run inference
+        config.cache.enabled = false;
+        let mut orchestrator = Orchestrator::with_config(config);
+        orchestrator.set_remote_backend(Arc::new(Slow(20)));
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::SlaViolated], move |event| {
+                if let Ok(mut seen) = sink.lock() {
+                    seen.push(event.clone());
+                }
+            });
+
+        // Only Remote offers vision
+        for _ in 0..3 {
+            let query = Query::new("describe this photo").requiring(ModelCapability::Vision);
+            let Ok(response) = orchestrator.process(query) else {
+                panic!("process should succeed");
+            };
+            assert_eq!(response.route, RoutingDecision::Remote);
+            assert!(response.latency_ms >= 20);
+        }
+
+        let Ok(seen) = seen.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        assert_eq!(seen.len(), 1);
+        assert!(orchestrator.sla_status(RoutingDecision::Remote).violating);
+        assert_eq!(orchestrator.sla_status(RoutingDecision::Remote).calls, 3);
+    }
+
+    #[test]
+    fn test_slow_backend_is_penalized_by_the_router() {
+        let mut config = OrchestratorConfig::default();
+        config.sla.hybrid.max_latency_ms = Some(5);
+        config.router.sla_penalty = 0.2;
+        config.cache.enabled = false;
+        let mut orchestrator = Orchestrator::with_config(config);
+        orchestrator.set_hybrid_backends(Arc::new(Slow(20)), Arc::new(Slow(20)));
+
+        // Remote and Hybrid both offer long context; Hybrid wins the tie
+        // until its measured latency breaks the SLA
+        let long = || Query::new("summarize this book").requiring(ModelCapability::LongContext);
+        let Ok(first) = orchestrator.process(long()) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(first.route, RoutingDecision::Hybrid);
+        assert!(first.latency_ms >= 20);
+        assert!(orchestrator.sla_status(RoutingDecision::Hybrid).violating);
+
+        let Ok(second) = orchestrator.process(long()) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(second.route, RoutingDecision::Remote);
+    }
+
+    #[test]
+    fn test_spend_forecast_warns_before_limit() {
+        let mut config = OrchestratorConfig::default();
+        config.router.costs.local.cost_usd = 0.01;
+        config.sla.daily_spend_limit = Some(0.055);
+        // Every turn has to run inference
+        config.cache.enabled = false;
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert_eq!(orchestrator.forecast(3), None);
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::BudgetForecast], move |event| {
+                if let Ok(mut seen) = sink.lock() {
+                    seen.push(event.clone());
+                }
+            });
+
+        for _ in 0..4 {
+            let Ok(_) = orchestrator.process(Query::new("hello")) else {
+                panic!("process should succeed");
+            };
+        }
+
+        let Ok(seen) = seen.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        // After three turns 0.025 remains: three more turns exceed it
+        let [Event::BudgetForecast {
+            turns_remaining, ..
+        }] = seen.as_slice()
+        else {
+            panic!("exactly one forecast warning should be published");
+        };
+        assert_eq!(*turns_remaining, 3);
+
+        let Some(forecast) = orchestrator.forecast(2) else {
+            panic!("forecast should exist after turns");
+        };
+        assert!((forecast.cost_usd - 0.02).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_device_state_steers_routing() {
+        let mut orchestrator = Orchestrator::new();
+        assert!(!orchestrator.should_throttle_compute());
+
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            network: crate::device::NetworkType::Offline,
+            thermal_headroom: 0.1,
+            ..DeviceState::default()
+        }));
+        assert!(orchestrator.should_throttle_compute());
+
+        let query = "Explain the difference between supervised and unsupervised learning";
+        let Ok(response) = orchestrator.process(Query::new(query)) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, RoutingDecision::Local);
+    }
+
+    #[test]
+    fn test_required_capabilities_restrict_routes() {
+        let mut orchestrator = Orchestrator::new();
+        let query = Query::new("describe this photo").requiring(ModelCapability::Vision);
+        let Ok(response) = orchestrator.process(query.clone()) else {
+            panic!("Remote offers vision, so process should succeed");
+        };
+        assert_eq!(response.route, RoutingDecision::Remote);
+
+        // Offline-only leaves Local, which cannot see images
+        orchestrator.set_profile(Profile::OfflineOnly);
+        let Err(OrchestratorError::MissingCapabilities(missing)) = orchestrator.process(query)
+        else {
+            panic!("Local lacks vision");
+        };
+        assert_eq!(missing.missing, vec![ModelCapability::Vision]);
+
+        let impossible = Query::new("call a tool on this image")
+            .requiring(ModelCapability::Vision)
+            .requiring(ModelCapability::FunctionCalling);
+        assert!(orchestrator.check_capabilities(&impossible).is_ok());
+        let mut config = OrchestratorConfig::default();
+        config.router.capabilities.remote = vec![ModelCapability::Vision];
+        let orchestrator = Orchestrator::with_config(config);
+        let Err(error) = orchestrator.check_capabilities(&impossible) else {
+            panic!("no backend offers function calling");
+        };
+        assert_eq!(error.route, RoutingDecision::Remote);
+        assert_eq!(error.missing, vec![ModelCapability::FunctionCalling]);
+    }
+
+    struct Watch;
+
+    impl crate::targets::TargetBackend for Watch {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            Ok(format!("watch: {}", query.text))
+        }
+    }
+
+    struct Fixed(&'static str);
+
+    impl TargetBackend for Fixed {
+        fn generate(&self, _query: &Query) -> Result<String, String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    /// Answers with its name and the prompt it received
+    struct Echo(&'static str);
+
+    impl TargetBackend for Echo {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            Ok(format!("{}: {}", self.0, query.text))
+        }
+    }
+
+    /// Answers after sleeping for its milliseconds
+    struct Slow(u64);
+
+    impl TargetBackend for Slow {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            std::thread::sleep(std::time::Duration::from_millis(self.0));
+            Ok(format!("slow: {}", query.text))
+        }
+    }
+
+    struct Down;
+
+    impl TargetBackend for Down {
+        fn generate(&self, _query: &Query) -> Result<String, String> {
+            Err("connection refused".to_string())
+        }
+    }
+
+    /// Fails every call with its message
+    struct Failing(&'static str);
+
+    impl TargetBackend for Failing {
+        fn generate(&self, _query: &Query) -> Result<String, String> {
+            Err(self.0.to_string())
+        }
+    }
+
+    /// Local model counting the queries it answered
+    struct Loaded(usize);
+
+    impl crate::pool::Reusable for Loaded {
+        fn reset(&mut self) {}
+    }
+
+    impl LocalModel for Loaded {
+        fn generate(&mut self, query: &Query) -> Result<String, String> {
+            self.0 += 1;
+            Ok(format!("local #{}: {}", self.0, query.text))
+        }
+    }
+
+    /// Plugin store noting the project each turn was saved under
+    struct Projects(Arc<std::sync::Mutex<Vec<Option<String>>>>);
+
+    impl ConversationStore for Projects {
+        fn save_turn(&self, project: Option<&str>, _turn: &ConversationTurn) -> Result<(), String> {
+            let mut projects = self.0.lock().map_err(|e| e.to_string())?;
+            projects.push(project.map(str::to_string));
+            Ok(())
+        }
+
+        fn load_history(
+            &self,
+            _project: Option<&str>,
+            _offset: usize,
+            _limit: usize,
+        ) -> Result<Vec<ConversationTurn>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_turn_is_filed_under_its_project_when_switched_while_generating() {
+        let saved = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.add_conversation_store(Box::new(Projects(Arc::clone(&saved))));
+        orchestrator.switch_project("garden");
+        let Ok(Begun::Pending(pending)) = orchestrator.begin(Query::new("when to sow beans")) else {
+            panic!("begin should route the query");
+        };
+        let generated = pending.generate();
+        orchestrator.switch_project("work");
+        let Ok(_) = orchestrator.complete(generated) else {
+            panic!("complete should succeed");
+        };
+        let saved = saved.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        assert_eq!(*saved, [Some("garden".to_string())]);
+        assert!(orchestrator.project_energy(Some("garden")).is_some());
+        assert!(orchestrator.project_energy(Some("work")).is_none());
+    }
+
+    #[test]
+    fn test_local_and_hybrid_routes_share_warm_local_model() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let mut config = OrchestratorConfig::default();
+        config.cache.enabled = false;
+        let mut orchestrator = Orchestrator::with_config(config);
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&loads);
+        let loaded = orchestrator.set_local_model(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Loaded(0)) as Box<dyn LocalModel>)
+        });
+        assert_eq!(loaded, Ok(1));
+        orchestrator.set_remote_backend(Arc::new(Echo("remote")));
+
+        let Ok(local) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(local.route, RoutingDecision::Local);
+        assert_eq!(local.metadata.model.as_deref(), Some(LOCAL_MODEL));
+        assert!(local.text.starts_with("local #1: "));
+
+        let long = Query::new("summarize this book").requiring(ModelCapability::LongContext);
+        let Ok(hybrid) = orchestrator.process(long) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(hybrid.route, RoutingDecision::Hybrid);
+        let Ok(again) = orchestrator.process(Query::new("hello again")) else {
+            panic!("process should succeed");
+        };
+        // One instance answered all three, its state kept between them
+        assert!(again.text.starts_with("local #3: "));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        let Some(stats) = orchestrator.local_model_stats() else {
+            panic!("a local model is set");
+        };
+        assert_eq!((stats.created, stats.reused, stats.in_use), (1, 3, 0));
+        let background = orchestrator.capabilities().background;
+        assert!(background.iter().any(|task| task.name == "model-pool"));
+        assert_eq!(orchestrator.shutdown().tasks_joined, 1);
+    }
+
+    #[test]
+    fn test_failed_remote_falls_back_to_local() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(original) = orchestrator.process(Query::new("explain lifetimes")) else {
+            panic!("process should succeed");
+        };
+        let Some(turn_id) = original.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+        let remote = || RegenerateOptions {
+            route: Some(RoutingDecision::Remote),
+            ..RegenerateOptions::default()
+        };
+
+        orchestrator.set_remote_backend(Arc::new(Echo("remote")));
+        let Ok(answer) = orchestrator.regenerate(turn_id, remote()) else {
+            panic!("a working Remote backend should answer");
+        };
+        assert!(answer.text.starts_with("remote: "));
+        assert_eq!(answer.metadata.degraded, None);
+
+        orchestrator.set_remote_backend(Arc::new(Failing("HTTP 429 Too Many Requests")));
+        let Ok(fallback) = orchestrator.regenerate(turn_id, remote()) else {
+            panic!("a rate-limited Remote should fall back to Local");
+        };
+        assert_eq!(fallback.route, RoutingDecision::Local);
+        let Some(degraded) = fallback.metadata.degraded else {
+            panic!("fallback answers should be marked degraded");
+        };
+        assert_eq!(degraded.requested, RoutingDecision::Remote);
+        assert_eq!(degraded.failure, FailureKind::RateLimited);
+
+        // Failures the policy does not retry surface as errors
+        orchestrator.set_remote_backend(Arc::new(Failing("invalid API key")));
+        let failed = orchestrator.regenerate(turn_id, remote());
+        assert!(matches!(failed, Err(OrchestratorError::BackendFailure(_))));
+
+        // Local cannot stand in for a query needing vision
+        orchestrator.set_remote_backend(Arc::new(Failing("request timed out")));
+        let photo = Query::new("describe this photo").requiring(ModelCapability::Vision);
+        let Err(OrchestratorError::BackendFailure(message)) = orchestrator.process(photo) else {
+            panic!("no fallback route offers vision");
+        };
+        assert!(message.contains("no fallback route answered"));
+    }
+
+    fn hybrid_answer(orchestrator: &mut Orchestrator, text: &str) -> Response {
+        let Ok(response) = orchestrator.generate(
+            &Query::new(text),
+            RoutingDecision::Hybrid,
+            0.7,
+            0,
+            DEFAULT_MODEL,
+            RoutingExplanation::default(),
+        ) else {
+            panic!("Hybrid generation should succeed");
+        };
+        response
+    }
+
+    fn go_offline(orchestrator: &mut Orchestrator) {
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            network: crate::device::NetworkType::Offline,
+            ..DeviceState::default()
+        }));
+    }
+
+    #[test]
+    fn test_hybrid_answers_are_blended() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.set_hybrid_backends(
+            Arc::new(Fixed("No. The ferry does not run on Sundays.")),
+            Arc::new(Fixed("Yes. The ferry does run on Sundays in summer.")),
+        );
+        let query = Query::new("does the ferry run on sundays?");
+        let Ok(response) = orchestrator.generate(
+            &query,
+            RoutingDecision::Hybrid,
+            0.7,
+            0,
+            DEFAULT_MODEL,
+            RoutingExplanation::default(),
+        ) else {
+            panic!("generate should succeed");
+        };
+        assert_eq!(response.metadata.model.as_deref(), Some(HYBRID_MODEL));
+        assert!(response.text.starts_with("Two perspectives:"));
+        assert!(response.text.contains("They disagree on:"));
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation.adjustments[0].starts_with("blended Remote (0.85) and Local (0.60)"));
+
+        go_offline(&mut orchestrator);
+        let response = hybrid_answer(&mut orchestrator, "does the ferry run on sundays?");
+        assert_eq!(response.route, RoutingDecision::Local);
+        assert_eq!(response.text, "No. The ferry does not run on Sundays.");
+    }
+
+    #[test]
+    fn test_hybrid_draft_refine() {
+        let mut config = OrchestratorConfig::default();
+        config.hybrid.strategy = HybridStrategy::DraftRefine;
+        let mut orchestrator = Orchestrator::with_config(config);
+        orchestrator.set_hybrid_backends(Arc::new(Fixed("a draft")), Arc::new(Echo("remote")));
+        let refined = hybrid_answer(&mut orchestrator, "explain borrowing");
+        assert_eq!(refined.route, RoutingDecision::Hybrid);
+        assert_eq!(refined.metadata.model.as_deref(), Some(HYBRID_MODEL));
+        assert!(refined.text.starts_with("remote: "));
+        assert!(refined.text.ends_with("Draft:\na draft"));
+
+        // A failed refinement keeps the draft and charges no remote tokens
+        orchestrator.set_hybrid_backends(Arc::new(Fixed("a draft")), Arc::new(Down));
+        let kept = hybrid_answer(&mut orchestrator, "explain borrowing");
+        assert_eq!((kept.route, kept.text.as_str()), (RoutingDecision::Local, "a draft"));
+
+        orchestrator.set_hybrid_backends(Arc::new(Fixed("a draft")), Arc::new(Echo("remote")));
+        go_offline(&mut orchestrator);
+        let offline = hybrid_answer(&mut orchestrator, "explain borrowing");
+        assert_eq!((offline.route, offline.text.as_str()), (RoutingDecision::Local, "a draft"));
+    }
+
+    #[test]
+    fn test_hybrid_retrieve_generate() {
+        let mut config = OrchestratorConfig::default();
+        config.hybrid.strategy = HybridStrategy::RetrieveGenerate;
+        config.hybrid.retrieved_turns = 1;
+        let mut orchestrator = Orchestrator::with_config(config);
+        let Ok(_) = orchestrator.process(Query::new("the api listens on port 8080")) else {
+            panic!("process should succeed");
+        };
+        orchestrator.set_hybrid_backends(Arc::new(Echo("local")), Arc::new(Echo("remote")));
+
+        let answer = hybrid_answer(&mut orchestrator, "which port does the api use?");
+        assert_eq!(answer.route, RoutingDecision::Hybrid);
+        assert!(answer.text.starts_with("remote: Relevant earlier conversation:"));
+        assert!(answer.text.contains("- User: the api listens on port 8080"));
+
+        go_offline(&mut orchestrator);
+        let offline = hybrid_answer(&mut orchestrator, "which port does the api use?");
+        assert_eq!(offline.route, RoutingDecision::Local);
+        assert!(offline.text.starts_with("local: Relevant earlier conversation:"));
+        let Some(explanation) = offline.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation
+            .adjustments
+            .contains(&"offline; Hybrid answered by Local only".to_string()));
+    }
+
+    #[test]
+    fn test_custom_target_serves_queries() {
+        let mut orchestrator = Orchestrator::new();
+        let watch = RouteTarget::new(
+            "companion-watch",
+            crate::policy::RouteCost {
+                latency_ms: 800.0,
+                energy_mwh: 2.0,
+                cost_usd: 0.0,
+            },
+            crate::policy::RouteConstraints::default(),
+            Arc::new(Watch),
+        );
+        let Ok(route) = orchestrator.register_target(watch) else {
+            panic!("registering a new target should succeed");
+        };
+        assert_eq!(route, RoutingDecision::Custom(0));
+
+        // Offline with no memory for the local model: only the watch is left
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            network: crate::device::NetworkType::Offline,
+            memory_pressure: crate::device::MemoryPressure::Critical,
+            ..DeviceState::default()
+        }));
+        let Ok(response) = orchestrator.process(Query::new("what time is it")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, route);
+        // The watch receives the assembled prompt, ending with the query
+        assert!(response.text.starts_with("watch: "));
+        assert!(response.text.ends_with("User: what time is it\nAssistant:"));
+        assert_eq!(response.metadata.model.as_deref(), Some("companion-watch"));
+
+        // A model must now cover four classes
+        let three = MLP::new_with_seed(crate::router::FEATURE_DIM, vec![8], 3, 1);
+        assert!(orchestrator.load_router_model(three).is_err());
+        let four = MLP::new_with_seed(crate::router::FEATURE_DIM, vec![8], 4, 1);
+        assert!(orchestrator.load_router_model(four).is_ok());
+
+        let Some(turn_id) = response.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+        assert!(orchestrator.record_feedback(turn_id, route).is_ok());
+        assert!(orchestrator
+            .record_feedback(turn_id, RoutingDecision::Custom(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_backends_receive_history_and_project_summary() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.set_hybrid_backends(Arc::new(Watch), Arc::new(Watch));
+        orchestrator.switch_project("garden");
+        orchestrator.set_project_summary("garden", "Balcony vegetable garden");
+        for text in ["which tomatoes grow well in pots?", "when should I water them?"] {
+            assert!(orchestrator.process(Query::new(text)).is_ok());
+        }
+
+        let query = Query::new("how much water per pot?");
+        let Ok(response) = orchestrator.generate(
+            &query,
+            RoutingDecision::Hybrid,
+            0.7,
+            0,
+            DEFAULT_MODEL,
+            RoutingExplanation::default(),
+        ) else {
+            panic!("generate should succeed");
+        };
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("generating should assemble a prompt");
+        };
+        assert_eq!(prompt.recent_turns, 2);
+        assert!(prompt.tokens <= orchestrator.context_budget());
+        for part in [
+            "You are a concise assistant",
+            "Project: garden\nBalcony vegetable garden",
+            "User: which tomatoes grow well in pots?",
+            "User: how much water per pot?\nAssistant:",
+        ] {
+            assert!(response.text.contains(part), "{:?} should reach the backend", part);
+        }
+
+        let mut config = OrchestratorConfig::default();
+        config.prompt.enabled = false;
+        let mut bare = Orchestrator::with_config(config);
+        bare.set_hybrid_backends(Arc::new(Watch), Arc::new(Watch));
+        let Ok(response) = bare.generate(
+            &query,
+            RoutingDecision::Hybrid,
+            0.7,
+            0,
+            DEFAULT_MODEL,
+            RoutingExplanation::default(),
+        ) else {
+            panic!("generate should succeed");
+        };
+        assert!(bare.last_prompt().is_none());
+        assert!(response.text.starts_with("watch: how much water per pot?"));
+    }
+
+    #[derive(Debug)]
+    struct Words;
+
+    impl TokenCounter for Words {
+        fn id(&self) -> String {
+            "words".to_string()
+        }
+
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn test_local_prompts_fit_the_local_window() {
+        let mut config = OrchestratorConfig::default();
+        config.chunking.local_context_tokens = 600;
+        config.chunking.chunk_tokens = 500;
+        let mut orchestrator = Orchestrator::with_config(config);
+        for i in 0..6 {
+            let text = format!("tell me fact {} about the solar system in detail", i);
+            assert!(orchestrator.process(Query::new(text)).is_ok());
+        }
+        assert!(orchestrator.set_tokenizer(RoutingDecision::Local, Arc::new(Words)).is_ok());
+        assert!(orchestrator.set_tokenizer(RoutingDecision::Hybrid, Arc::new(Words)).is_err());
+
+        let generated = orchestrator.generate(
+            &Query::new("and about the moon?"),
+            RoutingDecision::Local,
+            0.9,
+            0,
+            DEFAULT_MODEL,
+            RoutingExplanation::default(),
+        );
+        assert!(generated.is_ok());
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("generating should assemble a prompt");
+        };
+        // 600-token window less 512 reserved for the answer
+        assert!(prompt.tokens <= 88);
+        assert_eq!(prompt.tokens, Words.count(&prompt.text));
+        assert!(prompt.recent_turns >= 1);
+        assert!(prompt.dropped > 0);
+    }
+
+    #[test]
+    fn test_rules_file_blocks_and_warns() {
+        let path = std::env::temp_dir().join(format!("rules-{}.toml", std::process::id()));
+        let rules = "[[rules]]\nid = \"KIDS_001\"\nkeywords = [\"casino\"]\n\n\
+                     [[rules]]\nid = \"LONG_001\"\nlonger_than = 30\naction = \"Warn\"\n";
+        assert!(std::fs::write(&path, rules).is_ok());
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.load_rules(&path), Ok(2));
+        let _ = std::fs::remove_file(&path);
+
+        let Ok(blocked) = orchestrator.process(Query::new("best online casino")) else {
+            panic!("blocked queries still get a response");
+        };
+        assert_eq!(blocked.route, RoutingDecision::Blocked);
+        let Some(explanation) = &blocked.metadata.explanation else {
+            panic!("blocked responses should be explained");
+        };
+        assert_eq!(explanation.expert_rules, vec!["KIDS_001".to_string()]);
+        assert_eq!(
+            explanation.block_reason.as_deref(),
+            Some("Rule KIDS_001 triggered")
+        );
+        let Err(OrchestratorError::Blocked { rule_id, reason }) = blocked.into_result() else {
+            panic!("a blocked response should convert to a Blocked error");
+        };
+        assert_eq!(rule_id.as_deref(), Some("KIDS_001"));
+        assert_eq!(reason, "Rule KIDS_001 triggered");
+
+        let query = Query::new("summarise the plot of this film for me");
+        let Ok(warned) = orchestrator.process(query) else {
+            panic!("warnings let the query through");
+        };
+        assert_ne!(warned.route, RoutingDecision::Blocked);
+        let Some(explanation) = warned.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation
+            .adjustments
+            .contains(&"rule warning: LONG_001: Rule LONG_001 triggered".to_string()));
+    }
+
+    #[test]
+    fn test_regenerate_and_record_preference() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(original) = orchestrator.process(Query::new("explain lifetimes")) else {
+            panic!("process should succeed");
+        };
+        let Some(turn_id) = original.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+
+        let options = RegenerateOptions {
+            route: Some(RoutingDecision::Remote),
+            temperature: Some(0.2),
+            provider: Some("large-model".to_string()),
+        };
+        let Ok(alternative) = orchestrator.regenerate(turn_id, options) else {
+            panic!("regenerate should succeed");
+        };
+        assert_eq!(alternative.route, RoutingDecision::Remote);
+        assert_eq!(alternative.metadata.model.as_deref(), Some("large-model"));
+        assert_eq!(alternative.metadata.turn_id, Some(turn_id));
+        assert_eq!(orchestrator.alternatives(turn_id).map(<[_]>::len), Ok(1));
+        // Alternatives don't enter the conversation history
+        assert_eq!(orchestrator.recent_history(10).len(), 1);
+
+        let Ok(example) = orchestrator.record_preference(turn_id, 1) else {
+            panic!("record_preference should succeed");
+        };
+        assert_eq!(example.chosen, alternative);
+        assert_eq!(example.rejected, vec![original]);
+        assert_eq!(example.rewards.len(), 2);
+        assert!(example.rewards[0] > example.rewards[1]);
+        assert!(orchestrator.record_preference(turn_id, 2).is_err());
+
+        let preferences = orchestrator.take_preferences();
+        let data = crate::regenerate::router_training_data(&preferences);
+        assert_eq!(data.labels, vec![1]);
+        assert!(orchestrator.take_preferences().is_empty());
+    }
+
+    #[test]
+    fn test_regenerate_errors_are_typed() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(original) = orchestrator.process(Query::new("explain lifetimes")) else {
+            panic!("process should succeed");
+        };
+        let Some(turn_id) = original.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+        let remote = || RegenerateOptions {
+            route: Some(RoutingDecision::Remote),
+            ..RegenerateOptions::default()
+        };
+
+        let unknown = orchestrator.regenerate(turn_id + 100, RegenerateOptions::default());
+        assert!(matches!(unknown, Err(OrchestratorError::NotFound(_))));
+
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            network: crate::device::NetworkType::Offline,
+            ..DeviceState::default()
+        }));
+        assert_eq!(
+            orchestrator.regenerate(turn_id, remote()),
+            Err(OrchestratorError::NetworkUnavailable {
+                route: RoutingDecision::Remote
+            })
+        );
+
+        orchestrator.set_profile(Profile::OfflineOnly);
+        let refused = orchestrator.regenerate(turn_id, remote());
+        assert!(matches!(refused, Err(OrchestratorError::RouteNotAllowed { .. })));
+
+        orchestrator.shutdown();
+        assert_eq!(
+            orchestrator.regenerate(turn_id, RegenerateOptions::default()),
+            Err(OrchestratorError::ShutDown)
+        );
+    }
+
+    #[test]
+    fn test_lifecycle_state_transitions() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.lifecycle_state(), LifecycleState::Foreground);
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&loads);
+        let loaded = orchestrator.set_local_model(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Loaded(0)) as Box<dyn LocalModel>)
+        });
+        assert_eq!(loaded, Ok(1));
+        let spawned = orchestrator.spawn_background(
+            "sync",
+            Box::new(|context| {
+                while !context.wait_while_paused() {
+                    if context.wait(std::time::Duration::from_millis(1)) {
+                        break;
+                    }
+                }
+                Ok(())
+            }),
+        );
+        assert_eq!(spawned, Ok(()));
+        let Ok(digest) = orchestrator.schedule_digest("every 6 hours: any news?", None) else {
+            panic!("a valid digest should be scheduled");
+        };
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+
+        // Background work stops and due digests wait
+        let Ok(report) = orchestrator.on_lifecycle(LifecycleEvent::Background) else {
+            panic!("background should succeed");
+        };
+        assert_eq!(orchestrator.lifecycle_state(), LifecycleState::Background);
+        assert_eq!(report.tasks_paused, 2);
+        assert!(orchestrator.supervisor.is_paused());
+        assert_eq!(orchestrator.run_due_digests_at(digest.next_run_ms), Ok(Vec::new()));
+        assert_eq!(orchestrator.digests()[0].next_run_ms, digest.next_run_ms);
+
+        // Memory pressure empties the cache and unloads the idle model,
+        // but does not change foreground/background state
+        let Ok(report) = orchestrator.on_lifecycle(LifecycleEvent::LowMemory) else {
+            panic!("low memory should succeed");
+        };
+        assert_eq!(orchestrator.lifecycle_state(), LifecycleState::Background);
+        assert_eq!((report.caches_dropped, report.models_changed), (1, 1));
+        assert!(orchestrator.cache.is_empty());
+        assert_eq!(orchestrator.local_model.as_ref().map(ModelPool::idle_count), Some(0));
+
+        // Back in front, the model is loaded again and digests run
+        let Ok(report) = orchestrator.on_lifecycle(LifecycleEvent::Foreground) else {
+            panic!("foreground should succeed");
+        };
+        assert_eq!(orchestrator.lifecycle_state(), LifecycleState::Foreground);
+        assert_eq!(report.models_changed, 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert!(!orchestrator.supervisor.is_paused());
+        let Ok(runs) = orchestrator.run_due_digests_at(digest.next_run_ms) else {
+            panic!("running digests should succeed");
+        };
+        assert!(matches!(runs[0].outcome, DigestOutcome::Ran { .. }));
+    }
+
+    #[test]
+    fn test_repeated_query_is_served_from_cache() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(first) = orchestrator.process(Query::new("Convert 5 miles to km")) else {
+            panic!("process should succeed");
+        };
+        let Ok(second) = orchestrator.process(Query::new("convert 5 miles  to KM")) else {
+            panic!("process should succeed");
+        };
+        assert!(!first.metadata.cached);
+        assert!(second.metadata.cached);
+        assert_eq!(second.text, first.text);
+        assert_ne!(second.metadata.turn_id, first.metadata.turn_id);
+        assert_eq!(orchestrator.cache_stats().exact_hits, 1);
+        // Cached turns cost nothing but are still part of the conversation
+        assert_eq!(orchestrator.sla_status(RoutingDecision::Local).calls, 1);
+        assert_eq!(orchestrator.recent_history(10).len(), 2);
+
+        // Blocked answers are never cached
+        for _ in 0..2 {
+            let Ok(blocked) = orchestrator.process(Query::new("what is my password")) else {
+                panic!("process should succeed");
+            };
+            assert!(!blocked.metadata.cached);
+        }
+    }
+
+    #[test]
+    fn test_cache_is_keyed_by_the_query_project() {
+        let mut orchestrator = Orchestrator::new();
+        let in_project = |project: &str| {
+            let mut query = Query::new("Convert 5 miles to km");
+            query.project_context = Some(project.to_string());
+            query
+        };
+        let Ok(first) = orchestrator.process(in_project("travel")) else {
+            panic!("process should succeed");
+        };
+        let Ok(other) = orchestrator.process(in_project("fitness")) else {
+            panic!("process should succeed");
+        };
+        let Ok(again) = orchestrator.process(in_project("travel")) else {
+            panic!("process should succeed");
+        };
+        assert!(!first.metadata.cached);
+        assert!(!other.metadata.cached);
+        assert!(again.metadata.cached);
+        assert_eq!(orchestrator.cache_stats().exact_hits, 1);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_metrics_count_routes_cache_and_blocks_across_restarts() {
+        let path = std::env::temp_dir().join(format!("metrics-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = OrchestratorConfig::default();
+        config.metrics.persist = true;
+        {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let mut orchestrator = Orchestrator::with_config(config.clone());
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            for text in ["Convert 5 miles to km", "convert 5 miles to km", "what is my password"] {
+                let Ok(_) = orchestrator.process(Query::new(text)) else {
+                    panic!("process should succeed");
+                };
+            }
+            let snapshot = orchestrator.metrics_snapshot();
+            let Some(local) = snapshot.route(RoutingDecision::Local) else {
+                panic!("Local should have answered");
+            };
+            assert_eq!(local.count, 2);
+            assert_eq!(local.latency_ms.count(), 2);
+            assert_eq!((snapshot.cache_hits, snapshot.cache_misses), (1, 1));
+            assert_eq!(snapshot.cache_hit_rate(), Some(0.5));
+            assert_eq!(snapshot.blocked.values().sum::<u64>(), 1);
+            assert!(orchestrator.shutdown().is_clean());
+        }
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let mut orchestrator = Orchestrator::with_config(config);
+        let Ok(_) = orchestrator.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 2);
+        assert_eq!(orchestrator.reset_metrics(), Ok(()));
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_responses_carry_energy_and_projects_add_up() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.switch_project("garden");
+        let mut spent = Vec::new();
+        for _ in 0..2 {
+            let Ok(response) = orchestrator.process(Query::new("plan my weekend")) else {
+                panic!("process should succeed");
+            };
+            let Some(joules) = response.metadata.energy_joules else {
+                panic!("responses should carry an energy estimate");
+            };
+            let tokens = orchestrator.local_tokenizer.count(&response.text);
+            assert_eq!(response.metadata.tokens, Some(tokens as u32));
+            spent.push((joules, response.latency_ms, tokens));
+        }
+        // Measured SoC time plus the answer's tokens on a 1B model; the
+        // repeat is cached
+        let (joules, latency_ms, tokens) = spent[0];
+        let expected = 3.0 * latency_ms as f32 / 1_000.0 + 0.05 * tokens as f32;
+        assert!((joules - expected).abs() < 1e-4);
+        assert_eq!(spent[1].0, 0.0);
+
+        let Some(garden) = orchestrator.project_energy(Some("garden")) else {
+            panic!("garden should have spent energy");
+        };
+        assert_eq!(garden.queries, 2);
+        let report = orchestrator.energy_report();
+        assert!((report.total_joules - f64::from(joules)).abs() < 1e-4);
+        assert!(orchestrator.project_energy(None).is_none());
+    }
+
+    #[test]
+    fn test_energy_follows_measured_latency_and_query_project() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.switch_project("garden");
+        orchestrator.set_remote_backend(Arc::new(Slow(30)));
+
+        // Only Remote offers vision; the radio waits for the slow backend
+        let mut query = Query::new("describe this photo").requiring(ModelCapability::Vision);
+        query.project_context = Some("travel".to_string());
+        let Ok(response) = orchestrator.process(query) else {
+            panic!("process should succeed");
+        };
+        assert!(response.latency_ms >= 30);
+        let Some(joules) = response.metadata.energy_joules else {
+            panic!("responses should carry an energy estimate");
+        };
+        let expected = 1.2 * response.latency_ms as f32 / 1_000.0;
+        assert!((joules - expected).abs() < 1e-4);
+
+        let Some(travel) = orchestrator.project_energy(Some("travel")) else {
+            panic!("the query's own project should be charged");
+        };
+        assert_eq!(travel.queries, 1);
+        assert!(orchestrator.project_energy(Some("garden")).is_none());
+    }
+
+    #[test]
+    fn test_code_queries_send_only_relevant_sections() {
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.switch_project("app");
+        let source = "fn render_list(items: &[Item]) {\n    todo!()\n}\n\n\
+                      fn load_items() -> Vec<Item> {\n    Vec::new()\n}\n";
+        orchestrator.set_knowledge_pack(
+            "app",
+            vec![Attachment {
+                name: "src/ui.rs".to_string(),
+                content: source.to_string(),
+            }],
+        );
+
+        let query = Query::new("why does render_list panic?")
+            .with_attachment("src/model.rs", "pub struct Item;\n");
+        let Ok(response) = orchestrator.process(query) else {
+            panic!("process should succeed");
+        };
+        assert!(response.text.contains("--- src/ui.rs:1-3"));
+        assert!(!response.text.contains("load_items"));
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation
+            .adjustments
+            .iter()
+            .any(|note| note.starts_with("code context: 1 sections")));
+
+        // Questions that aren't about code are left alone
+        let Ok(response) = orchestrator.process(Query::new("plan my weekend")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.text, "Response to: plan my weekend");
+    }
+
+    #[test]
+    fn test_pasted_errors_are_distilled_and_cached() {
+        let paste = |noise: usize| {
+            let mut log: String = (0..noise)
+                .map(|i| format!("   Compiling dep-{} v0.1.0\n", i))
+                .collect();
+            log.push_str("error[E0499]: cannot borrow `state` as mutable more than once\n");
+            log.push_str("  --> src/app.rs:14:9\n");
+            log.push_str("error: aborting due to 1 previous error\n");
+            log
+        };
+        let mut orchestrator = Orchestrator::new();
+
+        let Ok(first) = orchestrator.process(Query::new(paste(300))) else {
+            panic!("process should succeed");
+        };
+        assert!(first.text.contains("Explain and fix this rustc error [E0499]"));
+        assert!(first.text.contains("at src/app.rs:14:9"));
+        assert!(!first.text.contains("Compiling"));
+        let Some(explanation) = first.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation
+            .adjustments
+            .contains(&"error triage: rustc error distilled from 303 lines".to_string()));
+
+        // The same error in a different log is a learned answer
+        let Ok(second) = orchestrator.process(Query::new(paste(20))) else {
+            panic!("process should succeed");
+        };
+        assert!(second.metadata.cached);
+        assert_eq!(second.text, first.text);
+    }
+
+    #[test]
+    fn test_oversized_query_is_map_reduced() {
+        let mut config = OrchestratorConfig::default();
+        config.chunking.local_context_tokens = 200;
+        config.chunking.remote_context_tokens = 400;
+        config.chunking.chunk_tokens = 150;
+        config.chunking.overlap_tokens = 10;
+        let mut orchestrator = Orchestrator::with_config(config);
+
+        let seen = Arc::new(std::sync::Mutex::new(0));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::ChunkProcessed], move |_| {
+                if let Ok(mut seen) = sink.lock() {
+                    *seen += 1;
+                }
+            });
+
+        // Short queries are untouched
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert!(orchestrator.chunked_run().is_none());
+
+        let log: String = (0..300).map(|i| format!("12:00:{:02} worker ok\n", i % 60)).collect();
+        let Ok(response) = orchestrator.process(Query::new(log)) else {
+            panic!("process should succeed");
+        };
+        let Some(run) = orchestrator.chunked_run() else {
+            panic!("the oversized query should run as a chunked job");
+        };
+        assert!(run.complete);
+        assert!(run.total_chunks > 1);
+        let Ok(seen) = seen.lock() else {
+            panic!("lock should not be poisoned");
+        };
+        assert!(*seen >= run.total_chunks);
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("chunked responses should be explained");
+        };
+        assert!(explanation.adjustments[0].contains("chunks"));
+        assert_eq!(orchestrator.recent_history(10).len(), 2);
+    }
+
+    #[test]
+    fn test_background_exports_journal() {
+        let dir = std::env::temp_dir().join(format!("orchestrator-journal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = OrchestratorConfig::default();
+        config.journal.enabled = true;
+        config.journal.dir = dir.clone();
+        let mut orchestrator = Orchestrator::with_config(config);
+
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        let yesterday = Query {
+            timestamp: now_ms() / 1_000 - DAY_MS / 1_000,
+            ..Query::new("what did I ask yesterday?")
+        };
+        orchestrator.context.add_turn(yesterday, response);
+
+        let Ok(report) = orchestrator.on_lifecycle(LifecycleEvent::Background) else {
+            panic!("background should succeed");
+        };
+        // Today's turn waits until the day is over
+        assert_eq!(report.journal_entries, 1);
+        let Ok(files) = std::fs::read_dir(&dir) else {
+            panic!("journal directory should exist");
+        };
+        assert_eq!(files.count(), 1);
+        assert_eq!(orchestrator.export_journal(), Ok(0));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_background_flushes_writes() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+
+        let Ok(report) = orchestrator.on_lifecycle(LifecycleEvent::Background) else {
+            panic!("background should succeed");
+        };
+        assert_eq!(report.turns_flushed, 1);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_history_survives_restart_and_pages() {
+        let path = std::env::temp_dir().join(format!("history-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let mut orchestrator = Orchestrator::new();
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            for i in 0..3 {
+                let Ok(_) = orchestrator.process(Query::new(format!("query {}", i))) else {
+                    panic!("process should succeed");
+                };
+            }
+            assert!(orchestrator.shutdown().is_clean());
+        }
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(3));
+        let recent = orchestrator.recent_history(1);
+        assert_eq!(recent[0].query.text, "query 2");
+
+        let Ok(latest) = orchestrator.history_page(None, 0, 2) else {
+            panic!("history_page should succeed");
+        };
+        let texts: Vec<&str> = latest.iter().map(|t| t.query.text.as_str()).collect();
+        assert_eq!(texts, vec!["query 1", "query 2"]);
+        let Ok(older) = orchestrator.history_page(None, 2, 2) else {
+            panic!("history_page should succeed");
+        };
+        assert_eq!(older.len(), 1);
+        assert_eq!(older[0].query.text, "query 0");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_database_holds_no_query_text() {
+        struct Key;
+        impl crate::persistence::KeyProvider for Key {
+            fn database_key(&self) -> Result<[u8; 32], String> {
+                Ok([3; 32])
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("sealed-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = OrchestratorConfig::default();
+        config.personalization.enabled = true;
+        {
+            let Ok(pm) = PersistenceManager::with_encryption(&path, &Key) else {
+                panic!("with_encryption should succeed");
+            };
+            let mut orchestrator = Orchestrator::with_config(config.clone());
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            let Ok(_) = orchestrator.process(Query::new("my cholesterol results came back")) else {
+                panic!("process should succeed");
+            };
+            let report = orchestrator.shutdown();
+            assert!(report.is_clean(), "errors: {:?}", report.errors);
+            assert!(report.session_saved);
+        }
+
+        let Ok(bytes) = std::fs::read(&path) else {
+            panic!("database file should exist");
+        };
+        assert!(!bytes.windows(11).any(|w| w == b"cholesterol"));
+
+        let Ok(pm) = PersistenceManager::with_encryption(&path, &Key) else {
+            panic!("reopening with the key should succeed");
+        };
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(1));
+        assert_eq!(
+            orchestrator.recent_history(1)[0].query.text,
+            "my cholesterol results came back"
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sessions_keep_separate_histories() {
+        let mut orchestrator = Orchestrator::new();
+        let Ok(_) = orchestrator.process(Query::new("before any session")) else {
+            panic!("process should succeed");
+        };
+
+        let chat = orchestrator.start_session(Some("travel"));
+        assert_eq!(orchestrator.current_project(), Some("travel"));
+        assert!(orchestrator.recent_history(10).is_empty());
+        let Ok(_) = orchestrator.process(Query::new("book a train")) else {
+            panic!("process should succeed");
+        };
+
+        let other = orchestrator.start_session(None);
+        assert_ne!(chat.id, other.id);
+        let Ok(_) = orchestrator.process(Query::new("recipe ideas")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(orchestrator.recent_history(10).len(), 1);
+
+        let Ok(resumed) = orchestrator.resume_session(&chat.id) else {
+            panic!("a parked session should resume");
+        };
+        assert_eq!(resumed, chat);
+        let history = orchestrator.recent_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].query.text, "book a train");
+
+        let Ok(ended) = orchestrator.end_session() else {
+            panic!("end_session should succeed");
+        };
+        assert!(ended.ended_at.is_some());
+        assert_eq!(orchestrator.current_session(), None);
+        let history = orchestrator.recent_history(10);
+        assert_eq!(history[0].query.text, "before any session");
+        assert!(orchestrator.end_session().is_err());
+        assert!(orchestrator.resume_session("missing").is_err());
+        assert_eq!(orchestrator.sessions().map(|s| s.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_learned_signals_shape_routing_features_and_prompts() {
+        let mut config = OrchestratorConfig::default();
+        config.personalization.enabled = true;
+        config.personalization.min_turns = 3;
+        config.personalization.smoothing = 0.5;
+        let mut orchestrator = Orchestrator::with_config(config);
+        let mut response = None;
+        for crate_name in ["tokio", "smol", "glommio"] {
+            let text = format!("briefly: does {}::spawn need an async runtime?", crate_name);
+            let query = Query::new(text);
+            let Ok(processed) = orchestrator.process(query) else {
+                panic!("process should succeed");
+            };
+            response = Some(processed);
+        }
+
+        let signals = orchestrator.user_signals();
+        assert!(signals.active);
+        assert_eq!(
+            (signals.expertise, signals.verbosity),
+            (Expertise::Expert, Verbosity::Brief)
+        );
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("generating should assemble a prompt");
+        };
+        assert!(prompt.text.contains(
+            "Response style:\n- The user is an expert; skip the basics.\n- Keep answers short."
+        ));
+        let Some(explanation) = response.and_then(|r| r.metadata.explanation) else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation.adjustments.contains(&signals.summary()));
+        let features = orchestrator.router.extract_features(&Query::new("hi"));
+        assert_eq!(features[PERSONAL_FEATURE_OFFSET + Expertise::Expert.index()], 1.0);
+
+        assert_eq!(orchestrator.reset_user_signals(), Ok(()));
+        assert!(!orchestrator.user_signals().active);
+        let features = orchestrator.router.extract_features(&Query::new("hi"));
+        assert_eq!(features[PERSONAL_FEATURE_OFFSET + Expertise::Expert.index()], 0.0);
+    }
+
+    #[test]
+    fn test_state_fingerprint_tracks_config_model_and_rules() {
+        let mut orchestrator = Orchestrator::new();
+        let initial = orchestrator.state_fingerprint();
+        assert_eq!(initial.len(), 16);
+        assert_eq!(initial, Orchestrator::new().state_fingerprint());
+        let Ok(response) = orchestrator.process(Query::new("what is a lifetime?")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.metadata.state_fingerprint, Some(initial.clone()));
+
+        orchestrator.set_profile(Profile::BatterySaver);
+        let battery = orchestrator.state_fingerprint();
+        assert_ne!(battery, initial);
+        orchestrator.set_profile(Profile::Balanced);
+        assert_eq!(orchestrator.state_fingerprint(), initial);
+
+        let Ok(rule) = expert::RuleBuilder::new("CUSTOM_001").keywords(["x"]).build() else {
+            panic!("the rule should build");
+        };
+        orchestrator.expert.push_rule(rule);
+        let with_rule = orchestrator.state_fingerprint();
+        assert_ne!(with_rule, initial);
+        orchestrator.set_router_calibrator(Calibrator::Temperature { temperature: 2.0 });
+        assert_ne!(orchestrator.state_fingerprint(), with_rule);
+    }
+
+    #[test]
+    fn test_project_allow_rule_overrides_global_block() {
+        let mut config = OrchestratorConfig::default();
+        config.expert.rules.push(expert::RuleSpec {
+            id: "ALLOW_SECURITY_RESEARCH".to_string(),
+            keywords: vec!["malware".to_string()],
+            projects: vec!["security-lab".to_string()],
+            action: expert::RuleAction::Allow,
+            ..expert::RuleSpec::default()
+        });
+        let mut orchestrator = Orchestrator::with_config(config);
+        let query = || Query::new("how does this malware persist?");
+        let blocked = orchestrator.process(query()).map(|r| r.route);
+        assert_eq!(blocked, Ok(RoutingDecision::Blocked));
+
+        orchestrator.switch_project("security-lab");
+        let Ok(response) = orchestrator.process(query()) else {
+            panic!("process should succeed");
+        };
+        assert_ne!(response.route, RoutingDecision::Blocked);
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation
+            .adjustments
+            .contains(&"rule override: SAFETY_001 by ALLOW_SECURITY_RESEARCH".to_string()));
+    }
+
+    #[test]
+    fn test_switching_project_switches_policy() {
+        let mut config = OrchestratorConfig::default();
+        config.expert.policies.push(expert::PolicySpec {
+            name: "kids-mode".to_string(),
+            projects: vec!["homework".to_string()],
+            routes: vec![RoutingDecision::Local],
+            ..expert::PolicySpec::default()
+        });
+        let mut orchestrator = Orchestrator::with_config(config);
+        let photo = || Query::new("describe this photo").requiring(ModelCapability::Vision);
+        assert_eq!(orchestrator.active_policy(), None);
+        let routed = orchestrator.process(photo()).map(|r| r.route);
+        assert_eq!(routed, Ok(RoutingDecision::Remote));
+
+        // kids-mode never leaves the device, and Local cannot see images
+        orchestrator.switch_project("homework");
+        assert_eq!(orchestrator.active_policy(), Some("kids-mode"));
+        assert!(orchestrator.process(photo()).is_err());
+        let Ok(response) = orchestrator.process(Query::new("explain photosynthesis")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.route, RoutingDecision::Local);
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert!(explanation.adjustments.contains(&"policy: kids-mode".to_string()));
+        let Some(turn_id) = response.metadata.turn_id else {
+            panic!("processed turns should have an id");
+        };
+        let remote = RegenerateOptions {
+            route: Some(RoutingDecision::Remote),
+            ..RegenerateOptions::default()
+        };
+        assert!(orchestrator.regenerate(turn_id, remote).is_err());
+
+        orchestrator.switch_project("garden");
+        assert_eq!(orchestrator.active_policy(), None);
+    }
+
+    #[test]
+    fn test_pasted_email_is_redacted_not_blocked() {
+        let mut orchestrator = Orchestrator::new();
+        let query = Query::new("draft a reply to ana@example.com about the invoice");
+        let Ok(response) = orchestrator.process(query) else {
+            panic!("process should succeed");
+        };
+        assert_ne!(response.route, RoutingDecision::Blocked);
+        assert_eq!(
+            response.text,
+            "Response to: draft a reply to [EMAIL] about the invoice"
+        );
+        let Some(explanation) = response.metadata.explanation else {
+            panic!("responses should be explained");
+        };
+        assert_eq!(explanation.redactions.len(), 1);
+        assert_eq!(explanation.redactions[0].rule_id, "PRIVACY_003");
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("generating should assemble a prompt");
+        };
+        assert!(!prompt.text.contains("ana@example.com"));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_bulk_import_deduplicates_and_resumes() {
+        let export = || {
+            (0..500u64).map(|i| {
+                let mut query = Query::new(format!("exported question {}", i));
+                query.timestamp = 1_000 + i;
+                let response = Response {
+                    text: if i == 7 {
+                        String::new()
+                    } else {
+                        format!("exported answer {}", i)
+                    },
+                    route: RoutingDecision::Remote,
+                    confidence: 0.9,
+                    latency_ms: 0,
+                    metadata: ResponseMetadata {
+                        model: None,
+                        tokens: None,
+                        cached: false,
+                        context_budget: None,
+                        turn_id: None,
+                        explanation: None,
+                        state_fingerprint: None,
+                        degraded: None,
+                        energy_joules: None,
+                    },
+                };
+                let project = (i % 2 == 0).then(|| "garden".to_string());
+                (project, ConversationTurn { query, response })
+            })
+        };
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let options = ImportOptions {
+            batch_size: 100,
+            ..ImportOptions::default()
+        };
+
+        // Pause after two batches; the duplicated turn 3 is skipped
+        let mut batches = 0;
+        let pause_after_two = |_: &ImportProgress| {
+            batches += 1;
+            if batches == 2 {
+                ImportControl::Pause
+            } else {
+                ImportControl::Continue
+            }
+        };
+        let with_duplicate = export().take(3).chain(export());
+        let source = "other-assistant";
+        let Ok(paused) =
+            orchestrator.import_turns(source, with_duplicate, &options, pause_after_two)
+        else {
+            panic!("the import should succeed");
+        };
+        assert_eq!((paused.read, paused.finished), (200, false));
+        assert_eq!((paused.duplicates, paused.rejected), (3, 1));
+        assert_eq!(paused.imported, 196);
+        assert_eq!(paused.problems, vec!["turn 10: empty response".to_string()]);
+
+        // Importing the same source again resumes after the turns read
+        let with_duplicate = export().take(3).chain(export());
+        let continue_all = |_: &ImportProgress| ImportControl::Continue;
+        let Ok(done) = orchestrator.import_turns(source, with_duplicate, &options, continue_all)
+        else {
+            panic!("the import should resume");
+        };
+        assert!(done.finished);
+        assert_eq!((done.read, done.imported, done.duplicates), (503, 499, 3));
+        let Some(pm) = &orchestrator.persistence else {
+            panic!("persistence should stay attached");
+        };
+        assert_eq!(pm.conversation_count(None), Ok(249));
+        assert_eq!(pm.conversation_count(Some("garden")), Ok(250));
+
+        // The newest turns are in memory and searchable
+        let recent = orchestrator.context.recent_history(1);
+        assert_eq!(recent[0].response.text, "exported answer 499");
+        let Ok(found) = orchestrator.context.search("exported question 450", 1) else {
+            panic!("search should succeed");
+        };
+        assert_eq!(found[0].turn.query.text, "exported question 450");
+        let rerun = orchestrator.import_turns(source, export(), &options, continue_all);
+        assert_eq!(rerun.map(|state| state.imported), Ok(499));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_digests_run_under_device_constraints_and_survive_restart() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        orchestrator
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::DigestReady], move |event| {
+                if let Ok(mut seen) = sink.lock() {
+                    seen.push(event.clone());
+                }
+            });
+        let Ok(digest) =
+            orchestrator.schedule_digest("every morning at 8: summarize my notes", Some("notes"))
+        else {
+            panic!("a valid digest should be scheduled");
+        };
+        let due = digest.next_run_ms;
+        assert_eq!(orchestrator.run_due_digests_at(due - 1), Ok(Vec::new()));
+
+        // A low battery defers the digest, then skips it to tomorrow
+        orchestrator.set_device_provider(Arc::new(DeviceState {
+            battery_percent: 10.0,
+            ..DeviceState::default()
+        }));
+        let Ok(runs) = orchestrator.run_due_digests_at(due) else {
+            panic!("running digests should succeed");
+        };
+        assert!(matches!(runs[0].outcome, DigestOutcome::Deferred(_)));
+        let Ok(runs) = orchestrator.run_due_digests_at(due + 3 * 3_600_000) else {
+            panic!("running digests should succeed");
+        };
+        assert!(matches!(runs[0].outcome, DigestOutcome::Skipped(_)));
+        let tomorrow = due + 24 * 3_600_000;
+        assert_eq!(orchestrator.digests()[0].next_run_ms, tomorrow);
+
+        orchestrator.set_device_provider(Arc::new(DeviceState::default()));
+        let Ok(runs) = orchestrator.run_due_digests_at(tomorrow) else {
+            panic!("running digests should succeed");
+        };
+        let [DigestRun {
+            outcome: DigestOutcome::Ran { text, .. },
+            ..
+        }] = runs.as_slice()
+        else {
+            panic!("the digest should run once, got {:?}", runs);
+        };
+        assert_eq!(text, "Response to: summarize my notes");
+        assert_eq!(orchestrator.recent_history(1)[0].query.text, "summarize my notes");
+        assert_eq!(seen.lock().map(|seen| seen.len()).unwrap_or_default(), 1);
+
+        let Some(pm) = orchestrator.persistence.take() else {
+            panic!("persistence should be attached");
+        };
+        let mut restarted = Orchestrator::new();
+        let Ok(_) = restarted.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(restarted.digests()[0].last_run_ms, Some(tomorrow));
+        assert_eq!(restarted.digests()[0].project.as_deref(), Some("notes"));
+        assert_eq!(restarted.remove_digest(digest.id), Ok(true));
+    }
+
+    #[test]
+    fn test_ambient_state_shapes_prompts_and_holds_digests() {
+        let mut orchestrator = Orchestrator::new();
+        let mut walking = SensorBuffer::new(64);
+        for i in 0..20 {
+            let bounce = if i % 2 == 0 { 4.0 } else { -4.0 };
+            walking.push(SensorReading::new(
+                SensorType::Accelerometer,
+                vec![0.5, 9.8 + bounce, 1.0],
+            ));
+        }
+        assert_eq!(orchestrator.update_ambient(&walking), AmbientState::Walking);
+        let Ok(_) = orchestrator.process(Query::new("how do I get to the station")) else {
+            panic!("process should succeed");
+        };
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("a prompt should have been built");
+        };
+        assert!(prompt.text.contains("keep answers short"));
+
+        // Nothing is pushed to the user while the phone is in a pocket
+        let Ok(digest) = orchestrator.schedule_digest("every 6 hours: any news?", None) else {
+            panic!("a valid digest should be scheduled");
+        };
+        let mut pocket = SensorBuffer::new(8);
+        pocket.push(SensorReading::new(SensorType::Proximity, vec![0.0]));
+        pocket.push(SensorReading::new(SensorType::Light, vec![1.0]));
+        assert_eq!(orchestrator.update_ambient(&pocket), AmbientState::InPocket);
+        let Ok(runs) = orchestrator.run_due_digests_at(digest.next_run_ms) else {
+            panic!("running digests should succeed");
+        };
+        let DigestOutcome::Deferred(reason) = &runs[0].outcome else {
+            panic!("the digest should wait, got {:?}", runs);
+        };
+        assert!(reason.contains("InPocket"));
+
+        let Ok(_) = orchestrator.process(Query::new("and back home")) else {
+            panic!("process should succeed");
+        };
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("a prompt should have been built");
+        };
+        assert!(!prompt.text.contains("keep answers short"));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_remembered_facts_reach_prompts_and_survive_restart() {
+        let mut config = OrchestratorConfig::default();
+        config.memory.enabled = true;
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::with_config(config.clone());
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let query = Query::new("I prefer metric units. How tall is Everest?");
+        let Ok(_) = orchestrator.process(query) else {
+            panic!("process should succeed");
+        };
+        let facts = orchestrator.memory_facts().to_vec();
+        assert_eq!(facts.len(), 1);
+        assert_eq!(facts[0].text, "prefers metric units");
+        assert_eq!(facts[0].source_turn, Some(0));
+
+        let Ok(_) = orchestrator.process(Query::new("and K2?")) else {
+            panic!("process should succeed");
+        };
+        let Some(prompt) = orchestrator.last_prompt() else {
+            panic!("generating should assemble a prompt");
+        };
+        assert!(prompt.text.contains("About the user:\n- prefers metric units"));
+
+        let Ok(fact) = orchestrator.remember_fact(FactKind::Personal, "lives in Brno") else {
+            panic!("remember_fact should succeed");
+        };
+        assert_eq!(orchestrator.delete_memory_fact(facts[0].id), Ok(true));
+        let Ok(_) = orchestrator.edit_memory_fact(fact.id, "lives in Brno, Czechia") else {
+            panic!("editing a remembered fact should succeed");
+        };
+
+        // A fresh orchestrator on the same storage remembers the edit only
+        let Some(pm) = orchestrator.persistence.take() else {
+            panic!("persistence should be attached");
+        };
+        let mut restarted = Orchestrator::with_config(config);
+        let Ok(_) = restarted.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        let texts: Vec<&str> = restarted
+            .memory_facts()
+            .iter()
+            .map(|fact| fact.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["lives in Brno, Czechia"]);
+        assert_eq!(restarted.clear_memory(), Ok(1));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_ended_session_resumes_after_restart() {
+        let path = std::env::temp_dir().join(format!("sessions-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let id = {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let mut orchestrator = Orchestrator::new();
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            let session = orchestrator.start_session(Some("work"));
+            let Ok(_) = orchestrator.process(Query::new("draft the agenda")) else {
+                panic!("process should succeed");
+            };
+            let Ok(_) = orchestrator.end_session() else {
+                panic!("end_session should succeed");
+            };
+            let open = orchestrator.start_session(None);
+            assert_eq!(orchestrator.shutdown().sessions_saved, 1);
+            assert_ne!(open.id, session.id);
+            session.id
+        };
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        let Ok(_) = orchestrator.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(orchestrator.sessions().map(|s| s.len()), Ok(2));
+        let Ok(session) = orchestrator.resume_session(&id) else {
+            panic!("a stored session should resume");
+        };
+        assert_eq!(session.ended_at, None);
+        assert_eq!(orchestrator.current_project(), Some("work"));
+        let history = orchestrator.recent_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].query.text, "draft the agenda");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_reservoir_state_follows_project_across_restarts() {
+        let path = std::env::temp_dir().join(format!("reservoir-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let work_state = {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let mut orchestrator = Orchestrator::new();
+            assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+            assert_eq!(orchestrator.enable_reservoir(), Ok(()));
+            orchestrator.switch_project("work");
+            let Ok(_) = orchestrator.process(Query::new("summarise the standup")) else {
+                panic!("process should succeed");
+            };
+            let Some(work_state) = orchestrator.context.reservoir_state() else {
+                panic!("the reservoir should be enabled");
+            };
+            assert!(work_state.iter().any(|&v| v != 0.0));
+
+            // A new project starts from a clean state, and switching back
+            // resumes where the old one left off
+            orchestrator.switch_project("home");
+            let Some(home_state) = orchestrator.context.reservoir_state() else {
+                panic!("the reservoir should be enabled");
+            };
+            assert!(home_state.iter().all(|&v| v == 0.0));
+            orchestrator.switch_project("work");
+            assert_eq!(orchestrator.context.reservoir_state(), Some(work_state.clone()));
+            assert!(orchestrator.shutdown().is_clean());
+            work_state
+        };
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        let Ok(_) = orchestrator.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(orchestrator.enable_reservoir(), Ok(()));
+        orchestrator.switch_project("work");
+        assert_eq!(orchestrator.context.reservoir_state(), Some(work_state));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_flush_writes_buffered_turns() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::new();
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+
+        let Ok(_) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(orchestrator.flush(), Ok(1));
+        assert_eq!(orchestrator.flush(), Ok(0));
+    }
+
+    #[test]
+    fn test_resource_limits_block_and_fall_back() {
+        let mut config = OrchestratorConfig::default();
+        config.expert.limits = QuotaConfig {
+            queries_per_minute: Some(3),
+            daily_remote_tokens: Some(1),
+            ..QuotaConfig::default()
+        };
+        let mut orchestrator = Orchestrator::with_config(config);
+        let photo = || Query::new("describe this photo").requiring(ModelCapability::Vision);
+        let routed = orchestrator.process(Query::new("hello")).map(|r| r.route);
+        assert_eq!(routed, Ok(RoutingDecision::Local));
+
+        // The first remote answer spends the budget; Local cannot see images
+        let routed = orchestrator.process(photo()).map(|r| r.route);
+        assert_eq!(routed, Ok(RoutingDecision::Remote));
+        assert!(!orchestrator.quota_tracker().remote_allowed(now_ms()));
+        assert!(orchestrator.process(photo()).is_err());
+
+        let Ok(blocked) = orchestrator.process(Query::new("one more")) else {
+            panic!("a rate-limited query should be answered as blocked");
+        };
+        assert_eq!(blocked.route, RoutingDecision::Blocked);
+        let rules = blocked.metadata.explanation.map(|e| e.expert_rules);
+        assert_eq!(rules, Some(vec![quota::RATE_LIMIT_RULE.to_string()]));
+        assert_eq!(orchestrator.quota_tracker().in_flight(), 0);
+
+        // A second orchestrator sharing the tracker shares the limits
+        let mut other = Orchestrator::new();
+        other.set_quota_tracker(orchestrator.quota_tracker());
+        let routed = other.process(Query::new("hello again")).map(|r| r.route);
+        assert_eq!(routed, Ok(RoutingDecision::Blocked));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_quota_usage_survives_restart() {
+        let mut config = OrchestratorConfig::default();
+        config.expert.limits.daily_remote_tokens = Some(1);
+        let path = std::env::temp_dir().join(format!("quota-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for run in 0..2 {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("opening the database should succeed");
+            };
+            let mut orchestrator = Orchestrator::with_config(config.clone());
+            let Ok(_) = orchestrator.attach_persistence(pm) else {
+                panic!("attach_persistence should succeed");
+            };
+            // The second run restores the spent budget and stays off Remote
+            let photo = Query::new("describe this photo").requiring(ModelCapability::Vision);
+            let routed = orchestrator.process(photo).map(|r| r.route);
+            assert_eq!(routed.is_ok(), run == 0, "run {}: {:?}", run, routed);
+            orchestrator.shutdown();
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_quota_usage_is_written_with_the_flush() {
+        let path = std::env::temp_dir().join(format!("quota-flush-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = OrchestratorConfig {
+            write_behind: crate::persistence::WriteBehindConfig {
+                max_turns: 3,
+                max_delay_ms: 60_000,
+            },
+            ..OrchestratorConfig::default()
+        };
+        config.expert.limits.daily_remote_tokens = Some(100_000);
+        let (Ok(pm), Ok(reader)) = (PersistenceManager::new(&path), PersistenceManager::new(&path))
+        else {
+            panic!("opening the database should succeed");
+        };
+        let mut orchestrator = Orchestrator::with_config(config);
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        let saved = || reader.load_config(QUOTA_USAGE_KEY).ok().flatten();
+
+        for i in 0..2 {
+            let Ok(_) = orchestrator.process(Query::new(format!("query {}", i))) else {
+                panic!("process should succeed");
+            };
+        }
+        // Buffered with the turns rather than written every turn
+        assert_eq!(saved(), None);
+        let Ok(_) = orchestrator.process(Query::new("query 2")) else {
+            panic!("process should succeed");
+        };
+        assert!(saved().is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_offline_queries_wait_for_the_network() {
+        let mut config = OrchestratorConfig::default();
+        config.queue.enabled = true;
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mut orchestrator = Orchestrator::with_config(config.clone());
+        assert_eq!(orchestrator.attach_persistence(pm), Ok(0));
+        go_offline(&mut orchestrator);
+
+        // Local questions are still answered; network ones are queued
+        let Ok(local) = orchestrator.process(Query::new("hello")) else {
+            panic!("local queries should be answered offline");
+        };
+        assert_eq!(local.route, RoutingDecision::Local);
+        for text in ["describe this photo", "describe this chart"] {
+            let query = Query::new(text).requiring(ModelCapability::Vision);
+            let Err(OrchestratorError::Deferred { route, .. }) = orchestrator.process(query) else {
+                panic!("vision queries should wait for the network");
+            };
+            assert_eq!(route, RoutingDecision::Remote);
+        }
+        let Some(chart) = orchestrator.queued_queries().get(1).map(|queued| queued.id) else {
+            panic!("both queries should be queued");
+        };
+        assert_eq!(orchestrator.cancel_queued(chart), Ok(true));
+        assert_eq!(orchestrator.cancel_queued(chart), Ok(false));
+
+        // The queue survives a restart while offline
+        let Some(pm) = orchestrator.persistence.take() else {
+            panic!("persistence should be attached");
+        };
+        let mut restarted = Orchestrator::with_config(config);
+        let Ok(_) = restarted.attach_persistence(pm) else {
+            panic!("attach_persistence should succeed");
+        };
+        assert_eq!(restarted.queued_queries().len(), 1);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        restarted
+            .events_mut()
+            .subscribe_to(&[crate::events::EventKind::QueuedAnswered], move |event| {
+                if let Ok(mut seen) = sink.lock() {
+                    seen.push(event.clone());
+                }
+            });
+
+        go_offline(&mut restarted);
+        assert_eq!(restarted.on_device_state_changed(), Ok(Vec::new()));
+        restarted.set_device_provider(Arc::new(DeviceState::default()));
+        let Ok(runs) = restarted.on_device_state_changed() else {
+            panic!("replaying the queue should succeed");
+        };
+        let [QueueRun {
+            outcome: QueueOutcome::Answered { text, .. },
+            ..
+        }] = runs.as_slice()
+        else {
+            panic!("the queued query should be answered once, got {:?}", runs);
+        };
+        assert_eq!(text, "Response to: describe this photo");
+        assert_eq!(restarted.recent_history(1)[0].response.route, RoutingDecision::Remote);
+        assert!(restarted.queued_queries().is_empty());
+        assert_eq!(seen.lock().map(|seen| seen.len()).unwrap_or_default(), 1);
+    }
+
+    #[test]
+    fn test_background_tasks_are_reported_and_joined() {
+        let mut orchestrator = Orchestrator::new();
+        let spawned = orchestrator.spawn_background(
+            "sync",
+            Box::new(|context| {
+                while !context.wait(std::time::Duration::from_secs(60)) {}
+                Ok(())
+            }),
+        );
+        assert_eq!(spawned, Ok(()));
+        let background = orchestrator.capabilities().background;
+        assert_eq!(background.len(), 1);
+        assert_eq!(background[0].name, "sync");
+
+        let report = orchestrator.shutdown();
+        assert_eq!(report.tasks_joined, 1);
+        let states: Vec<TaskState> = orchestrator
+            .capabilities()
+            .background
+            .iter()
+            .map(|task| task.state)
+            .collect();
+        assert_eq!(states, [TaskState::Stopped]);
+        assert!(orchestrator.spawn_background("late", Box::new(|_| Ok(()))).is_err());
     }
 }
@@ -6,7 +6,31 @@
 //! - Reservoir computing state
 //! - MLP weights (trained models)
 //! - SNN weights
-//! - User preferences and configuration
+//! - User preferences and configuration, and typed `settings`
+//! - Scheduled digest queries
+//! - Embedding vectors, searchable by similarity through [`VectorStore`]
+//! - Sensor readings in downsampling tiers, through `SensorLog`
+//!
+//! Reservoir states and MLP weights are stored as JSON, or with the
+//! `fast-serde` feature in the smaller binary encoding of `binary`; JSON
+//! rows from earlier saves are read either way.
+//!
+//! [`PersistenceManager::apply_retention`] keeps the stored history
+//! within the age, per-project and size limits of a [`RetentionConfig`].
+//!
+//! With the `encryption` feature, [`PersistenceManager::with_encryption`]
+//! encrypts the columns holding what the user said or was told
+//! (conversation text, saved session contexts, remembered facts, digest
+//! queries, embedded texts and `config` values) with AES-256-GCM under a key from the
+//! host's `KeyProvider`. Timestamps, projects, weights and sensor
+//! readings stay in the clear so they can still be queried.
+//!
+//! The schema is built by numbered, forward-only migrations recorded in
+//! the `schema_migrations` table, so databases from earlier releases are
+//! upgraded in place and databases from later ones are refused.
+//!
+//! Calls fail with `rusqlite` errors, which `?` converts into
+//! [`OrchestratorError::PersistenceError`](crate::error::OrchestratorError).
 
 #![forbid(unsafe_code)]
 
@@ -15,70 +39,74 @@
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-use crate::types::{Query, Response, ConversationTurn};
-use crate::reservoir::EchoStateNetwork;
-use crate::mlp::MLP;
+#[cfg(not(feature = "persistence"))]
+use crate::error::OrchestratorError;
 
-/// Database schema version for migrations
-const SCHEMA_VERSION: i32 = 1;
+#[cfg(feature = "persistence")]
+use crate::{
+    context::{ContextManager, Session},
+    digest::Digest,
+    embedding::cosine_similarity,
+    features::RouterModel,
+    hashing::stable_hash,
+    memory::MemoryFact,
+    mlp::MLP,
+    reservoir::EchoStateNetwork,
+    sensor::{SensorReading, SensorType},
+    settings::{Setting, SETTING_KEYS},
+    timeseries::TimeSeriesStore,
+    types::ConversationTurn,
+};
+#[cfg(feature = "persistence")]
+use std::collections::HashMap;
+#[cfg(all(feature = "persistence", feature = "fast-serde"))]
+use crate::binary::BinaryFormat;
 
-/// Persistence layer for conversation state and models
+/// Database schema version: the version of the last migration
 #[cfg(feature = "persistence")]
-pub struct PersistenceManager {
-    conn: Connection,
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// One forward-only schema change
+///
+/// Released migrations are never edited: their checksum is recorded when
+/// they are applied and checked every time a database is opened. Change
+/// the schema by appending a migration with the next version and bumping
+/// [`SCHEMA_VERSION`].
+#[cfg(feature = "persistence")]
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
 }
 
 #[cfg(feature = "persistence")]
-impl PersistenceManager {
-    /// Create a new persistence manager with SQLite backend
-    pub fn new<P: AsRef<Path>>(db_path: P) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
-
-        let manager = PersistenceManager { conn };
-        manager.initialize_schema()?;
-
-        Ok(manager)
-    }
-
-    /// Create in-memory database (for testing)
-    pub fn new_in_memory() -> SqlResult<Self> {
-        let conn = Connection::open_in_memory()?;
-
-        let manager = PersistenceManager { conn };
-        manager.initialize_schema()?;
-
-        Ok(manager)
+impl Migration {
+    /// Stable hash of the SQL, as recorded in `schema_migrations`
+    fn checksum(&self) -> String {
+        format!("{:016x}", stable_hash(self.sql.as_bytes()))
     }
+}
 
-    /// Initialize database schema
-    fn initialize_schema(&self) -> SqlResult<()> {
-        // Metadata table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS metadata (
+/// Every migration, oldest first, numbered from 1 without gaps
+///
+/// Databases created before migrations were tracked already hold the
+/// tables of migration 1 and record `schema_version` 1 in `metadata`;
+/// since migration 1 only creates what is missing, they are migrated like
+/// new databases.
+#[cfg(feature = "persistence")]
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        sql: "
+            -- Metadata table
+            CREATE TABLE IF NOT EXISTS metadata (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
-            )",
-            [],
-        )?;
-
-        // Check schema version
-        let version: Result<i32, _> = self.conn.query_row(
-            "SELECT value FROM metadata WHERE key = 'schema_version'",
-            [],
-            |row| row.get(0),
-        );
+            );
 
-        if version.is_err() {
-            // First time setup
-            self.conn.execute(
-                "INSERT INTO metadata (key, value) VALUES ('schema_version', ?1)",
-                params![SCHEMA_VERSION.to_string()],
-            )?;
-        }
-
-        // Conversations table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS conversations (
+            -- Conversations table
+            CREATE TABLE IF NOT EXISTS conversations (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 project TEXT,
                 query_text TEXT NOT NULL,
@@ -89,39 +117,27 @@ fn initialize_schema(&self) -> SqlResult<()> {
                 response_confidence REAL NOT NULL,
                 response_timestamp INTEGER NOT NULL,
                 created_at INTEGER NOT NULL
-            )",
-            [],
-        )?;
+            );
 
-        // Index for project-based queries
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_conversations_project
-             ON conversations(project)",
-            [],
-        )?;
+            -- Index for project-based queries
+            CREATE INDEX IF NOT EXISTS idx_conversations_project
+                ON conversations(project);
 
-        // Index for timestamp-based queries
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_conversations_timestamp
-             ON conversations(query_timestamp DESC)",
-            [],
-        )?;
+            -- Index for timestamp-based queries
+            CREATE INDEX IF NOT EXISTS idx_conversations_timestamp
+                ON conversations(query_timestamp DESC);
 
-        // Reservoir states table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS reservoir_states (
+            -- Reservoir states table
+            CREATE TABLE IF NOT EXISTS reservoir_states (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 project TEXT,
                 state_json TEXT NOT NULL,
                 saved_at INTEGER NOT NULL,
                 UNIQUE(project)
-            )",
-            [],
-        )?;
+            );
 
-        // Model weights table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS model_weights (
+            -- Model weights table
+            CREATE TABLE IF NOT EXISTS model_weights (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 model_type TEXT NOT NULL,
                 model_name TEXT NOT NULL,
@@ -129,85 +145,562 @@ fn initialize_schema(&self) -> SqlResult<()> {
                 trained_at INTEGER NOT NULL,
                 accuracy REAL,
                 UNIQUE(model_type, model_name)
-            )",
-            [],
-        )?;
+            );
 
-        // Configuration table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS config (
+            -- Sensor feature time series (downsampled, delta-encoded)
+            CREATE TABLE IF NOT EXISTS sensor_timeseries (
+                name TEXT PRIMARY KEY,
+                store_json TEXT NOT NULL,
+                saved_at INTEGER NOT NULL
+            );
+
+            -- Configuration table
+            CREATE TABLE IF NOT EXISTS config (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL,
                 updated_at INTEGER NOT NULL
+            );
+
+            -- Embedding vectors (little-endian f32 blobs) for similarity search
+            CREATE TABLE IF NOT EXISTS embeddings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                collection TEXT NOT NULL,
+                embedder TEXT NOT NULL,
+                text TEXT NOT NULL,
+                dimension INTEGER NOT NULL,
+                vector BLOB NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_embeddings_collection
+                ON embeddings(collection, embedder);
+
+            -- Reservoir state vectors per project ('' = no project)
+            CREATE TABLE IF NOT EXISTS reservoir_vectors (
+                project TEXT PRIMARY KEY NOT NULL,
+                dimension INTEGER NOT NULL,
+                state BLOB NOT NULL,
+                saved_at INTEGER NOT NULL
+            );
+
+            -- Chat sessions with their own history and reservoir state
+            CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                project TEXT,
+                started_at INTEGER NOT NULL,
+                ended_at INTEGER,
+                context_json TEXT NOT NULL,
+                reservoir_json TEXT,
+                saved_at INTEGER NOT NULL
+            );
+
+            -- Long-term memory: facts about the user
+            CREATE TABLE IF NOT EXISTS memory_facts (
+                id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL,
+                text TEXT NOT NULL,
+                source_turn INTEGER,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+
+            -- Scheduled digest queries
+            CREATE TABLE IF NOT EXISTS digests (
+                id INTEGER PRIMARY KEY,
+                schedule TEXT NOT NULL,
+                query TEXT NOT NULL,
+                project TEXT,
+                next_run INTEGER NOT NULL,
+                last_run INTEGER
+            );
+        ",
+    },
+    Migration {
+        version: 2,
+        name: "sensor log tiers",
+        sql: "
+            -- Sensor log tiers: raw readings, per-second sums, bucket statistics
+            CREATE TABLE IF NOT EXISTS sensor_raw (
+                sensor TEXT NOT NULL,
+                timestamp_ms INTEGER NOT NULL,
+                vals BLOB NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_sensor_raw_time
+                ON sensor_raw(sensor, timestamp_ms);
+
+            CREATE TABLE IF NOT EXISTS sensor_seconds (
+                sensor TEXT NOT NULL,
+                start_ms INTEGER NOT NULL,
+                count INTEGER NOT NULL,
+                sums BLOB NOT NULL,
+                PRIMARY KEY (sensor, start_ms)
+            );
+
+            CREATE TABLE IF NOT EXISTS sensor_stats (
+                sensor TEXT NOT NULL,
+                start_ms INTEGER NOT NULL,
+                count INTEGER NOT NULL,
+                sums BLOB NOT NULL,
+                squares BLOB NOT NULL,
+                mins BLOB NOT NULL,
+                maxs BLOB NOT NULL,
+                PRIMARY KEY (sensor, start_ms)
+            );
+        ",
+    },
+];
+
+/// Width of the per-second tier's buckets
+#[cfg(feature = "persistence")]
+const SECOND_MS: i64 = 1_000;
+
+/// Prefix of the `config` keys holding settings
+#[cfg(feature = "persistence")]
+const SETTING_KEY_PREFIX: &str = "setting:";
+
+/// Retention of the sensor log's three tiers
+///
+/// Every reading is written to all three at once, so each tier can be
+/// queried up to the present; each tier then keeps only its own span:
+///
+/// - raw readings, for `raw_retention_ms`
+/// - per-second means, for `per_second_retention_ms`
+/// - count, mean, standard deviation, min and max per `stats_bucket_ms`,
+///   for `stats_retention_ms`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorLogConfig {
+    /// Log readings received by the orchestrator (off by default)
+    pub enabled: bool,
+    /// How long raw readings are kept (ms)
+    pub raw_retention_ms: u64,
+    /// How long per-second means are kept (ms)
+    pub per_second_retention_ms: u64,
+    /// How long statistics are kept (ms)
+    pub stats_retention_ms: u64,
+    /// Width of a statistics bucket (ms)
+    pub stats_bucket_ms: u64,
+}
+
+impl Default for SensorLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            raw_retention_ms: 60 * 60 * 1000,
+            per_second_retention_ms: 24 * 60 * 60 * 1000,
+            stats_retention_ms: 30 * 24 * 60 * 60 * 1000,
+            stats_bucket_ms: 60 * 60 * 1000,
+        }
+    }
+}
+
+/// Summary of one sensor over one statistics bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorStats {
+    /// Start of the bucket (ms since epoch)
+    pub start_ms: u64,
+    /// Readings summarized
+    pub count: u64,
+    /// Per-value mean
+    pub mean: Vec<f32>,
+    /// Per-value population standard deviation
+    pub std: Vec<f32>,
+    /// Per-value minimum
+    pub min: Vec<f32>,
+    /// Per-value maximum
+    pub max: Vec<f32>,
+}
+
+/// When the orchestrator writes buffered conversation turns
+///
+/// Turns are kept in memory after each query and written in one
+/// transaction once `max_turns` are buffered or the oldest has waited
+/// `max_delay_ms`, and on flush, backgrounding and shutdown. A crash loses
+/// at most the turns buffered since the last write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WriteBehindConfig {
+    /// Buffered turns that trigger a write
+    pub max_turns: usize,
+    /// Longest a turn stays buffered (ms)
+    pub max_delay_ms: u64,
+}
+
+impl Default for WriteBehindConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: 32,
+            max_delay_ms: 5_000,
+        }
+    }
+}
+
+/// Limits that keep the database from growing without bound on
+/// storage-constrained devices; zero disables a limit
+///
+/// Conversation turns past `max_turn_age_days`, or beyond the newest
+/// `max_turns_per_project` of their project, are deleted. While the
+/// database is still above `max_database_bytes`, the oldest turns of any
+/// project are deleted in batches until it fits or no turns are left, and
+/// the file is then vacuumed to return the space to the device.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Turns older than this many days are deleted
+    pub max_turn_age_days: u32,
+    /// Turns kept per project (turns without a project count as one)
+    pub max_turns_per_project: usize,
+    /// Size (bytes) the database is kept under
+    pub max_database_bytes: u64,
+}
+
+/// Host-implemented provisioning of the database encryption key
+///
+/// On a phone the key belongs in the platform keystore (Android
+/// Keystore, iOS Keychain), not next to the database. It is asked for
+/// once, when the database is opened.
+#[cfg(feature = "encryption")]
+pub trait KeyProvider: Send + Sync {
+    /// 256-bit key the database is encrypted under
+    fn database_key(&self) -> Result<[u8; 32], String>;
+}
+
+/// Marks an encrypted column value; a control character no typed text
+/// starts with, so plaintext rows are never mistaken for encrypted ones
+#[cfg(feature = "persistence")]
+const ENCRYPTED_PREFIX: &str = "\u{1}enc1:";
+
+/// Plaintext of the `key_check` value recorded in `metadata`
+#[cfg(feature = "persistence")]
+const KEY_CHECK: &str = "mobile-ai-orchestrator key check";
+
+/// Encrypts sensitive text columns, or passes them through when the
+/// database is opened without a key
+///
+/// An encrypted value is [`ENCRYPTED_PREFIX`] followed by the hex of a
+/// random 96-bit nonce and the AES-256-GCM ciphertext with its tag.
+#[cfg(feature = "persistence")]
+#[derive(Default)]
+struct ColumnCipher {
+    #[cfg(feature = "encryption")]
+    key: Option<ring::aead::LessSafeKey>,
+}
+
+#[cfg(feature = "persistence")]
+impl ColumnCipher {
+    #[cfg(feature = "encryption")]
+    fn new(key: &[u8; 32]) -> SqlResult<Self> {
+        let key = ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, key)
+            .map_err(|_| key_error("unusable AES-256 key".to_string()))?;
+        Ok(Self {
+            key: Some(ring::aead::LessSafeKey::new(key)),
+        })
+    }
+
+    #[cfg(feature = "encryption")]
+    fn is_enabled(&self) -> bool {
+        self.key.is_some()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn is_enabled(&self) -> bool {
+        false
+    }
+
+    /// `text` as it is to be stored
+    #[cfg(feature = "encryption")]
+    fn seal(&self, text: &str) -> SqlResult<String> {
+        use ring::aead::{Aad, Nonce, NONCE_LEN};
+        use ring::rand::{SecureRandom, SystemRandom};
+
+        let Some(key) = &self.key else {
+            return Ok(text.to_string());
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| key_error("no randomness for a nonce".to_string()))?;
+        let mut sealed = text.as_bytes().to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+            .map_err(|_| key_error("value too long to encrypt".to_string()))?;
+
+        let mut stored = ENCRYPTED_PREFIX.to_string();
+        for byte in nonce.iter().chain(&sealed) {
+            stored.push_str(&format!("{:02x}", byte));
+        }
+        Ok(stored)
+    }
+
+    /// `text` as it is to be stored
+    #[cfg(not(feature = "encryption"))]
+    fn seal(&self, text: &str) -> SqlResult<String> {
+        Ok(text.to_string())
+    }
+
+    /// Text of a stored value; fails for an encrypted value the key does
+    /// not open
+    fn open(&self, stored: String) -> SqlResult<String> {
+        let Some(hex) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored);
+        };
+        self.decrypt(hex).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                "encrypted value cannot be opened with this key".into(),
+            )
+        })
+    }
+
+    #[cfg(feature = "encryption")]
+    fn decrypt(&self, hex: &str) -> Option<String> {
+        use ring::aead::{Aad, Nonce, NONCE_LEN};
+
+        let key = self.key.as_ref()?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return None;
+        }
+        let mut bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let mut sealed = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).ok()?;
+        let plain = key.open_in_place(nonce, Aad::empty(), &mut sealed).ok()?;
+        String::from_utf8(plain.to_vec()).ok()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn decrypt(&self, _hex: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Persistence layer for conversation state and models
+#[cfg(feature = "persistence")]
+pub struct PersistenceManager {
+    conn: Connection,
+    cipher: ColumnCipher,
+}
+
+#[cfg(feature = "persistence")]
+impl PersistenceManager {
+    /// Create a new persistence manager with SQLite backend
+    pub fn new<P: AsRef<Path>>(db_path: P) -> SqlResult<Self> {
+        Self::open(Connection::open(db_path)?, ColumnCipher::default())
+    }
+
+    /// Create in-memory database (for testing)
+    pub fn new_in_memory() -> SqlResult<Self> {
+        Self::open(Connection::open_in_memory()?, ColumnCipher::default())
+    }
+
+    /// Open the database at `db_path` with its sensitive text columns
+    /// encrypted under the key from `keys`
+    ///
+    /// Fails when the provider has no key or the database was encrypted
+    /// under another one. A database written without encryption may be
+    /// opened this way: its existing rows stay readable and rows written
+    /// from then on are encrypted.
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption<P: AsRef<Path>>(db_path: P, keys: &dyn KeyProvider) -> SqlResult<Self> {
+        let key = keys.database_key().map_err(key_error)?;
+        Self::open(Connection::open(db_path)?, ColumnCipher::new(&key)?)
+    }
+
+    fn open(conn: Connection, cipher: ColumnCipher) -> SqlResult<Self> {
+        let manager = PersistenceManager { conn, cipher };
+        manager.initialize_schema()?;
+        manager.check_key()?;
+        Ok(manager)
+    }
+
+    /// Record a check value the first time the database is opened with a
+    /// key, and refuse a key (or the lack of one) that does not open the
+    /// recorded value
+    fn check_key(&self) -> SqlResult<()> {
+        let recorded = self.conn.query_row(
+            "SELECT value FROM metadata WHERE key = 'key_check'",
+            [],
+            |row| row.get::<_, String>(0),
+        );
+        match recorded {
+            Ok(check) => {
+                if self.cipher.open(check).ok().as_deref() != Some(KEY_CHECK) {
+                    return Err(key_error(
+                        "the database is encrypted under another key".to_string(),
+                    ));
+                }
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) if self.cipher.is_enabled() => {
+                self.conn.execute(
+                    "INSERT INTO metadata (key, value) VALUES ('key_check', ?1)",
+                    params![self.cipher.seal(KEY_CHECK)?],
+                )?;
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Apply pending migrations, after checking that those already
+    /// applied are the ones this build knows
+    ///
+    /// Fails without changing anything when the database was written by a
+    /// newer build, when an applied migration's SQL differs from this
+    /// build's, or when SQLite's integrity check fails before migrating.
+    /// Each migration runs in its own transaction.
+    fn initialize_schema(&self) -> SqlResult<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
             )",
             [],
         )?;
 
+        let applied: Vec<(i64, String)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT version, checksum FROM schema_migrations ORDER BY version")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<SqlResult<_>>()?
+        };
+        for (i, (version, checksum)) in applied.iter().enumerate() {
+            let Some(migration) = MIGRATIONS.get(i) else {
+                return Err(schema_error(format!(
+                    "database schema version {} is newer than this build supports ({})",
+                    version, SCHEMA_VERSION
+                )));
+            };
+            if i64::from(migration.version) != *version {
+                return Err(schema_error(format!(
+                    "migration history has version {} where {} was expected",
+                    version, migration.version
+                )));
+            }
+            if migration.checksum() != *checksum {
+                return Err(schema_error(format!(
+                    "migration {} ({}) differs from the one applied to this database",
+                    migration.version, migration.name
+                )));
+            }
+        }
+
+        let pending = &MIGRATIONS[applied.len()..];
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let integrity: String = self.conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+        if integrity != "ok" {
+            return Err(schema_error(format!(
+                "database failed its integrity check before migrating: {}",
+                integrity
+            )));
+        }
+        for migration in pending {
+            let tx = self.conn.unchecked_transaction()?;
+            tx.execute_batch(migration.sql)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    migration.version,
+                    migration.name,
+                    migration.checksum(),
+                    current_timestamp()
+                ],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO metadata (key, value) VALUES ('schema_version', ?1)",
+                params![migration.version.to_string()],
+            )?;
+            tx.commit()?;
+        }
         Ok(())
     }
 
+    /// Version of the newest migration applied to the database
+    pub fn schema_version(&self) -> SqlResult<u32> {
+        self.conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            [],
+            |row| row.get(0),
+        )
+    }
+
     /// Save a conversation turn
     pub fn save_turn(&self, project: Option<&str>, turn: &ConversationTurn) -> SqlResult<i64> {
-        let now = current_timestamp();
-
-        self.conn.execute(
-            "INSERT INTO conversations (
-                project, query_text, query_priority, query_timestamp,
-                response_text, response_route, response_confidence,
-                response_timestamp, created_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
-                project,
-                turn.query.text,
-                turn.query.priority,
-                turn.query.timestamp,
-                turn.response.text,
-                format!("{:?}", turn.response.route),
-                turn.response.confidence,
-                turn.response.latency_ms as i64,
-                now,
-            ],
-        )?;
+        insert_turn(&self.conn, &self.cipher, project, turn)
+    }
 
-        Ok(self.conn.last_insert_rowid())
+    /// Save `turns` (each with its project) in one transaction: all of them
+    /// are stored or, on error, none
+    pub fn save_turns(&self, turns: &[(Option<String>, ConversationTurn)]) -> SqlResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (project, turn) in turns {
+            insert_turn(&tx, &self.cipher, project.as_deref(), turn)?;
+        }
+        tx.commit()?;
+        Ok(turns.len())
     }
 
-    /// Load recent conversation history for a project
-    pub fn load_history(&self, project: Option<&str>, limit: usize) -> SqlResult<Vec<ConversationTurn>> {
-        let (query, params_vec): (String, Vec<Box<dyn rusqlite::ToSql>>) = if let Some(proj) = project {
-            (
-                "SELECT query_text, query_priority, query_timestamp,
-                        response_text, response_route, response_confidence,
-                        response_timestamp
-                 FROM conversations
-                 WHERE project = ?1
-                 ORDER BY query_timestamp DESC
-                 LIMIT ?2".to_string(),
-                vec![Box::new(proj.to_string()), Box::new(limit as i64)],
-            )
-        } else {
-            (
-                "SELECT query_text, query_priority, query_timestamp,
-                        response_text, response_route, response_confidence,
-                        response_timestamp
-                 FROM conversations
-                 WHERE project IS NULL
-                 ORDER BY query_timestamp DESC
-                 LIMIT ?1".to_string(),
-                vec![Box::new(limit as i64)],
-            )
-        };
+    /// Whether a turn with the same query time, query and response is
+    /// already stored, in any project
+    pub fn has_turn(&self, turn: &ConversationTurn) -> SqlResult<bool> {
+        // Encrypted texts differ on every write, so compare them decrypted
+        let mut stmt = self.conn.prepare(
+            "SELECT query_text, response_text FROM conversations WHERE query_timestamp = ?1",
+        )?;
+        let rows = stmt.query_map(params![turn.query.timestamp], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (query, response) = row?;
+            if self.cipher.open(query)? == turn.query.text
+                && self.cipher.open(response)? == turn.response.text
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
 
-        let mut stmt = self.conn.prepare(&query)?;
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    /// Load one page of a project's history (`None` = turns without a
+    /// project), oldest first. `offset` counts back from the most recent
+    /// turn, so offset 0 is the latest page and the next page starts at
+    /// `offset + limit`.
+    pub fn load_history(
+        &self,
+        project: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> SqlResult<Vec<ConversationTurn>> {
+        // `IS` also matches NULL, i.e. turns without a project
+        let mut stmt = self.conn.prepare(
+            "SELECT query_text, query_priority, query_timestamp,
+                    response_text, response_route, response_confidence,
+                    response_timestamp
+             FROM conversations
+             WHERE project IS ?1
+             ORDER BY query_timestamp DESC, id DESC
+             LIMIT ?2 OFFSET ?3",
+        )?;
 
-        let turns = stmt.query_map(param_refs.as_slice(), |row| {
+        let turns = stmt.query_map(params![project, limit as i64, offset as i64], |row| {
             Ok(ConversationTurn::from_row(row))
         })?;
 
         let mut result = Vec::new();
         for turn in turns {
-            result.push(turn?);
+            result.push(self.open_turn(turn?)?);
         }
 
         // Reverse to get chronological order (oldest first)
@@ -216,17 +709,52 @@ pub fn load_history(&self, project: Option<&str>, limit: usize) -> SqlResult<Vec
         Ok(result)
     }
 
+    /// Load the `limit` most recent turns of every project, oldest first,
+    /// each with its project
+    pub fn load_recent_turns(
+        &self,
+        limit: usize,
+    ) -> SqlResult<Vec<(Option<String>, ConversationTurn)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT query_text, query_priority, query_timestamp,
+                    response_text, response_route, response_confidence,
+                    response_timestamp, project
+             FROM conversations
+             ORDER BY query_timestamp DESC, id DESC
+             LIMIT ?1",
+        )?;
+
+        let turns = stmt.query_map(params![limit as i64], |row| {
+            Ok((row.get(7)?, ConversationTurn::from_row(row)))
+        })?;
+
+        let mut result = Vec::new();
+        for turn in turns {
+            let (project, turn) = turn?;
+            result.push((project, self.open_turn(turn)?));
+        }
+        result.reverse();
+
+        Ok(result)
+    }
+
+    /// `turn` as read from a row, with its texts decrypted
+    fn open_turn(&self, mut turn: ConversationTurn) -> SqlResult<ConversationTurn> {
+        turn.query.text = self.cipher.open(std::mem::take(&mut turn.query.text))?;
+        turn.response.text = self.cipher.open(std::mem::take(&mut turn.response.text))?;
+        Ok(turn)
+    }
+
     /// Save reservoir state for a project
     pub fn save_reservoir_state(&self, project: Option<&str>, esn: &EchoStateNetwork) -> SqlResult<()> {
-        let state_json = serde_json::to_string(&esn)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let state = encode_model(esn)?;
 
         let now = current_timestamp();
 
         self.conn.execute(
             "INSERT OR REPLACE INTO reservoir_states (project, state_json, saved_at)
              VALUES (?1, ?2, ?3)",
-            params![project, state_json, now],
+            params![project, state, now],
         )?;
 
         Ok(())
@@ -234,21 +762,277 @@ pub fn save_reservoir_state(&self, project: Option<&str>, esn: &EchoStateNetwork
 
     /// Load reservoir state for a project
     pub fn load_reservoir_state(&self, project: Option<&str>) -> SqlResult<Option<EchoStateNetwork>> {
-        let result: Result<String, _> = self.conn.query_row(
+        let result = self.conn.query_row(
             "SELECT state_json FROM reservoir_states WHERE project = ?1",
             params![project],
             |row| row.get(0),
         );
 
+        match result {
+            Ok(state) => Ok(Some(decode_model(state)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save just the reservoir state vector of a project; much smaller
+    /// than `save_reservoir_state`, which also stores the trained readout
+    pub fn save_reservoir_vector(&self, project: Option<&str>, state: &[f32]) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO reservoir_vectors (project, dimension, state, saved_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                project.unwrap_or(""),
+                state.len() as i64,
+                f32_blob(state),
+                current_timestamp()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Load the reservoir state vector saved for a project
+    pub fn load_reservoir_vector(&self, project: Option<&str>) -> SqlResult<Option<Vec<f32>>> {
+        let result: Result<Vec<u8>, _> = self.conn.query_row(
+            "SELECT state FROM reservoir_vectors WHERE project = ?1",
+            params![project.unwrap_or("")],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(blob) => Ok(Some(blob_f32(&blob))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save a session together with its context (history and reservoir),
+    /// replacing any earlier save of the same session
+    pub fn save_session(&self, session: &Session, context: &ContextManager) -> SqlResult<()> {
+        let context_json = context.to_json()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let context_json = self.cipher.seal(&context_json)?;
+        let reservoir_json = context.reservoir()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sessions (
+                id, project, started_at, ended_at, context_json, reservoir_json, saved_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                session.id,
+                session.project,
+                session.started_at,
+                session.ended_at,
+                context_json,
+                reservoir_json,
+                current_timestamp(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Load a session and its context
+    pub fn load_session(&self, id: &str) -> SqlResult<Option<(Session, ContextManager)>> {
+        let result = self.conn.query_row(
+            "SELECT id, project, started_at, ended_at, context_json, reservoir_json
+             FROM sessions WHERE id = ?1",
+            params![id],
+            |row| {
+                let session = Session {
+                    id: row.get(0)?,
+                    project: row.get(1)?,
+                    started_at: row.get(2)?,
+                    ended_at: row.get(3)?,
+                };
+                let context_json = self.cipher.open(row.get(4)?)?;
+                let reservoir_json: Option<String> = row.get(5)?;
+                Ok((session, context_json, reservoir_json))
+            },
+        );
+
+        let (session, context_json, reservoir_json) = match result {
+            Ok(row) => row,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let to_sql_error = |e: serde_json::Error| rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            Box::new(e),
+        );
+        let mut context = ContextManager::from_json(&context_json).map_err(to_sql_error)?;
+        if let Some(json) = reservoir_json {
+            let esn: EchoStateNetwork = serde_json::from_str(&json).map_err(to_sql_error)?;
+            context.set_reservoir(esn);
+        }
+        Ok(Some((session, context)))
+    }
+
+    /// All saved sessions, most recently started first
+    pub fn list_sessions(&self) -> SqlResult<Vec<Session>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, project, started_at, ended_at FROM sessions
+             ORDER BY started_at DESC, id DESC"
+        )?;
+        let sessions = stmt.query_map([], |row| {
+            Ok(Session {
+                id: row.get(0)?,
+                project: row.get(1)?,
+                started_at: row.get(2)?,
+                ended_at: row.get(3)?,
+            })
+        })?;
+        sessions.collect()
+    }
+
+    /// Save a remembered fact, replacing any earlier version of it
+    pub fn save_memory_fact(&self, fact: &MemoryFact) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO memory_facts (
+                id, kind, text, source_turn, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                fact.id,
+                fact.kind.to_string(),
+                self.cipher.seal(&fact.text)?,
+                fact.source_turn,
+                fact.created_at,
+                fact.updated_at,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Forget a remembered fact, returning whether it was stored
+    pub fn delete_memory_fact(&self, id: u64) -> SqlResult<bool> {
+        let deleted = self.conn.execute("DELETE FROM memory_facts WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    /// All remembered facts, oldest first
+    pub fn load_memory_facts(&self) -> SqlResult<Vec<MemoryFact>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, kind, text, source_turn, created_at, updated_at
+             FROM memory_facts ORDER BY created_at, id"
+        )?;
+        let facts = stmt.query_map([], |row| {
+            let kind: String = row.get(1)?;
+            Ok(MemoryFact {
+                id: row.get(0)?,
+                kind: kind.parse().map_err(|e: String| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        1,
+                        rusqlite::types::Type::Text,
+                        e.into(),
+                    )
+                })?,
+                text: self.cipher.open(row.get(2)?)?,
+                source_turn: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?;
+        facts.collect()
+    }
+
+    /// Save a digest, replacing an earlier version with the same id
+    pub fn save_digest(&self, digest: &Digest) -> SqlResult<()> {
+        let schedule = serde_json::to_string(&digest.schedule)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO digests (
+                id, schedule, query, project, next_run, last_run
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                digest.id,
+                schedule,
+                self.cipher.seal(&digest.query)?,
+                digest.project,
+                digest.next_run_ms,
+                digest.last_run_ms,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Delete a digest, returning whether it was stored
+    pub fn delete_digest(&self, id: u64) -> SqlResult<bool> {
+        let deleted = self.conn.execute("DELETE FROM digests WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    /// All digests, in creation order
+    pub fn load_digests(&self) -> SqlResult<Vec<Digest>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, schedule, query, project, next_run, last_run
+             FROM digests ORDER BY id"
+        )?;
+        let digests = stmt.query_map([], |row| {
+            let schedule: String = row.get(1)?;
+            Ok(Digest {
+                id: row.get(0)?,
+                schedule: serde_json::from_str(&schedule).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        1,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?,
+                query: self.cipher.open(row.get(2)?)?,
+                project: row.get(3)?,
+                next_run_ms: row.get(4)?,
+                last_run_ms: row.get(5)?,
+            })
+        })?;
+        digests.collect()
+    }
+
+    /// Save a routing model together with its feature schema version
+    pub fn save_router_model(
+        &self,
+        name: &str,
+        model: &RouterModel,
+        accuracy: Option<f32>,
+    ) -> SqlResult<()> {
+        let weights_json = serde_json::to_string(model)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let now = current_timestamp();
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO model_weights (model_type, model_name, weights_json, trained_at, accuracy)
+             VALUES ('router', ?1, ?2, ?3, ?4)",
+            params![name, weights_json, now, accuracy],
+        )?;
+
+        Ok(())
+    }
+
+    /// Load a routing model; install it via `Router::set_model`, which
+    /// checks it against the current feature schema
+    pub fn load_router_model(&self, name: &str) -> SqlResult<Option<RouterModel>> {
+        let result: Result<String, _> = self.conn.query_row(
+            "SELECT weights_json FROM model_weights WHERE model_type = 'router' AND model_name = ?1",
+            params![name],
+            |row| row.get(0),
+        );
+
         match result {
             Ok(json) => {
-                let esn: EchoStateNetwork = serde_json::from_str(&json)
+                let model: RouterModel = serde_json::from_str(&json)
                     .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
                         0,
                         rusqlite::types::Type::Text,
                         Box::new(e),
                     ))?;
-                Ok(Some(esn))
+                Ok(Some(model))
             }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e),
@@ -257,15 +1041,14 @@ pub fn load_reservoir_state(&self, project: Option<&str>) -> SqlResult<Option<Ec
 
     /// Save trained MLP model
     pub fn save_mlp(&self, name: &str, mlp: &MLP, accuracy: Option<f32>) -> SqlResult<()> {
-        let weights_json = serde_json::to_string(&mlp)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let weights = encode_model(mlp)?;
 
         let now = current_timestamp();
 
         self.conn.execute(
             "INSERT OR REPLACE INTO model_weights (model_type, model_name, weights_json, trained_at, accuracy)
              VALUES ('mlp', ?1, ?2, ?3, ?4)",
-            params![name, weights_json, now, accuracy],
+            params![name, weights, now, accuracy],
         )?;
 
         Ok(())
@@ -273,90 +1056,686 @@ pub fn save_mlp(&self, name: &str, mlp: &MLP, accuracy: Option<f32>) -> SqlResul
 
     /// Load trained MLP model
     pub fn load_mlp(&self, name: &str) -> SqlResult<Option<MLP>> {
-        let result: Result<String, _> = self.conn.query_row(
+        let result = self.conn.query_row(
             "SELECT weights_json FROM model_weights WHERE model_type = 'mlp' AND model_name = ?1",
             params![name],
             |row| row.get(0),
         );
 
+        match result {
+            Ok(weights) => Ok(Some(decode_model(weights)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save a sensor time-series store under `name`
+    pub fn save_timeseries(&self, name: &str, store: &TimeSeriesStore) -> SqlResult<()> {
+        let store_json = serde_json::to_string(store)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let now = current_timestamp();
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sensor_timeseries (name, store_json, saved_at)
+             VALUES (?1, ?2, ?3)",
+            params![name, store_json, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Load a sensor time-series store saved under `name`
+    pub fn load_timeseries(&self, name: &str) -> SqlResult<Option<TimeSeriesStore>> {
+        let result: Result<String, _> = self.conn.query_row(
+            "SELECT store_json FROM sensor_timeseries WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        );
+
         match result {
             Ok(json) => {
-                let mlp: MLP = serde_json::from_str(&json)
+                let store: TimeSeriesStore = serde_json::from_str(&json)
                     .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
                         0,
                         rusqlite::types::Type::Text,
                         Box::new(e),
                     ))?;
-                Ok(Some(mlp))
+                Ok(Some(store))
             }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    /// Get conversation count for a project
-    pub fn conversation_count(&self, project: Option<&str>) -> SqlResult<usize> {
-        let count: i64 = if let Some(proj) = project {
-            self.conn.query_row(
-                "SELECT COUNT(*) FROM conversations WHERE project = ?1",
-                params![proj],
-                |row| row.get(0),
-            )?
-        } else {
-            self.conn.query_row(
-                "SELECT COUNT(*) FROM conversations WHERE project IS NULL",
-                [],
-                |row| row.get(0),
-            )?
-        };
+    /// Store a configuration value under `key`, replacing any previous value
+    ///
+    /// Values hold saved contexts, queued queries and learned topics, so
+    /// they are encrypted like the other text columns; keys are not.
+    pub fn save_config(&self, key: &str, value: &str) -> SqlResult<()> {
+        let now = current_timestamp();
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO config (key, value, updated_at) VALUES (?1, ?2, ?3)",
+            params![key, self.cipher.seal(value)?, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Load the configuration value stored under `key`
+    pub fn load_config(&self, key: &str) -> SqlResult<Option<String>> {
+        let result: Result<String, _> = self.conn.query_row(
+            "SELECT value FROM config WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(value) => Ok(Some(self.cipher.open(value)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store `setting` as text in the `config` table, replacing its
+    /// previous value
+    pub fn save_setting(&self, setting: &Setting) -> SqlResult<()> {
+        let key = format!("{}{}", SETTING_KEY_PREFIX, setting.key());
+        self.save_config(&key, &setting.value_string())
+    }
+
+    /// Every stored setting, by key. Keys this build does not know (saved
+    /// by a newer release) are skipped.
+    pub fn load_settings(&self) -> SqlResult<Vec<Setting>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM config WHERE key LIKE ?1 ORDER BY key")?;
+        let rows = stmt.query_map(params![format!("{}%", SETTING_KEY_PREFIX)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut settings = Vec::new();
+        for row in rows {
+            let (key, value) = row?;
+            let key = &key[SETTING_KEY_PREFIX.len()..];
+            if !SETTING_KEYS.contains(&key) {
+                continue;
+            }
+            let value = self.cipher.open(value)?;
+            let setting = Setting::parse(key, &value).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?;
+            settings.push(setting);
+        }
+        Ok(settings)
+    }
+
+    /// Get conversation count for a project
+    pub fn conversation_count(&self, project: Option<&str>) -> SqlResult<usize> {
+        let count: i64 = if let Some(proj) = project {
+            self.conn.query_row(
+                "SELECT COUNT(*) FROM conversations WHERE project = ?1",
+                params![proj],
+                |row| row.get(0),
+            )?
+        } else {
+            self.conn.query_row(
+                "SELECT COUNT(*) FROM conversations WHERE project IS NULL",
+                [],
+                |row| row.get(0),
+            )?
+        };
+
+        Ok(count as usize)
+    }
+
+    /// Clear all conversation history (for privacy/testing)
+    pub fn clear_history(&self, project: Option<&str>) -> SqlResult<usize> {
+        let count = if let Some(proj) = project {
+            self.conn.execute(
+                "DELETE FROM conversations WHERE project = ?1",
+                params![proj],
+            )?
+        } else {
+            self.conn.execute(
+                "DELETE FROM conversations WHERE project IS NULL",
+                [],
+            )?
+        };
+
+        Ok(count)
+    }
+
+    /// Vector store for `collection` (e.g. a project name, or
+    /// `"conversations"`), holding vectors produced by the embedder
+    /// identified by `embedder_id`
+    pub fn vector_store(&self, collection: &str, embedder_id: &str) -> VectorStore<'_> {
+        VectorStore {
+            conn: &self.conn,
+            cipher: &self.cipher,
+            collection: collection.to_string(),
+            embedder: embedder_id.to_string(),
+        }
+    }
+
+    /// Tiered sensor log kept according to `config`
+    pub fn sensor_log(&self, config: &SensorLogConfig) -> SensorLog<'_> {
+        SensorLog {
+            conn: &self.conn,
+            config: config.clone(),
+        }
+    }
+
+    /// Delete the turns `config` does not keep as of `now_secs`, vacuuming
+    /// when the database is over its size limit; returns how many turns
+    /// were deleted
+    pub fn apply_retention(&self, config: &RetentionConfig, now_secs: u64) -> SqlResult<usize> {
+        let mut deleted = 0;
+        if config.max_turn_age_days > 0 {
+            let max_age = u64::from(config.max_turn_age_days) * 24 * 60 * 60;
+            deleted += self.conn.execute(
+                "DELETE FROM conversations WHERE query_timestamp < ?1",
+                params![sql_ms(now_secs.saturating_sub(max_age))],
+            )?;
+        }
+        if config.max_turns_per_project > 0 {
+            deleted += self.conn.execute(
+                "DELETE FROM conversations WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, ROW_NUMBER() OVER (
+                            PARTITION BY project ORDER BY query_timestamp DESC, id DESC
+                        ) AS newer
+                        FROM conversations
+                    ) WHERE newer > ?1
+                )",
+                params![config.max_turns_per_project as i64],
+            )?;
+        }
+        if config.max_database_bytes > 0 && self.database_size()? > config.max_database_bytes {
+            // Deleted rows only free pages for reuse, so count used pages
+            let used_bytes = || -> SqlResult<u64> {
+                let pages = self.pragma("page_count")? - self.pragma("freelist_count")?;
+                Ok(pages * self.pragma("page_size")?)
+            };
+            while used_bytes()? > config.max_database_bytes {
+                let removed = self.conn.execute(
+                    "DELETE FROM conversations WHERE id IN (
+                        SELECT id FROM conversations ORDER BY query_timestamp, id
+                        LIMIT MAX(1, (SELECT COUNT(*) FROM conversations) / 10)
+                    )",
+                    [],
+                )?;
+                if removed == 0 {
+                    break;
+                }
+                deleted += removed;
+            }
+            if self.pragma("freelist_count")? > 0 {
+                self.vacuum()?;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Vacuum database to reclaim space
+    pub fn vacuum(&self) -> SqlResult<()> {
+        self.conn.execute("VACUUM", [])?;
+        Ok(())
+    }
+
+    /// Get database file size (if not in-memory)
+    pub fn database_size(&self) -> SqlResult<u64> {
+        Ok(self.pragma("page_count")? * self.pragma("page_size")?)
+    }
+
+    /// Value of an integer pragma
+    fn pragma(&self, name: &str) -> SqlResult<u64> {
+        let value: i64 = self.conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?;
+        Ok(value.max(0) as u64)
+    }
+}
+
+/// A stored text and its distance from a query vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorMatch {
+    /// Row id returned by [`VectorStore::insert`]
+    pub id: i64,
+    /// Text the vector was computed from
+    pub text: String,
+    /// Cosine distance (`1 - cosine similarity`, 0 = same direction)
+    pub distance: f32,
+}
+
+/// Embedding vectors of one collection, searchable by cosine distance
+///
+/// Vectors from different embedders live in different spaces, so a store
+/// only sees rows written with its own embedder id; re-embedding after a
+/// model change starts from an empty store. Queries scan the collection,
+/// which is fast enough for the few thousand rows a device accumulates.
+#[cfg(feature = "persistence")]
+pub struct VectorStore<'a> {
+    conn: &'a Connection,
+    cipher: &'a ColumnCipher,
+    collection: String,
+    embedder: String,
+}
+
+#[cfg(feature = "persistence")]
+impl VectorStore<'_> {
+    /// Store `embedding` for `text`, returning its row id
+    pub fn insert(&self, text: &str, embedding: &[f32]) -> SqlResult<i64> {
+        let vector = f32_blob(embedding);
+        self.conn.execute(
+            "INSERT INTO embeddings (collection, embedder, text, dimension, vector, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                self.collection,
+                self.embedder,
+                self.cipher.seal(text)?,
+                embedding.len() as i64,
+                vector,
+                current_timestamp()
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// The `k` stored vectors closest to `query` by cosine distance,
+    /// nearest first. Vectors of another dimension are ignored.
+    pub fn knn(&self, query: &[f32], k: usize) -> SqlResult<Vec<VectorMatch>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, text, vector FROM embeddings
+             WHERE collection = ?1 AND embedder = ?2 AND dimension = ?3",
+        )?;
+        let rows = stmt.query_map(
+            params![self.collection, self.embedder, query.len() as i64],
+            |row| {
+                let id: i64 = row.get(0)?;
+                let text = self.cipher.open(row.get(1)?)?;
+                let blob: Vec<u8> = row.get(2)?;
+                Ok((id, text, blob))
+            },
+        )?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            let (id, text, blob) = row?;
+            let vector = blob_f32(&blob);
+            let distance = 1.0 - cosine_similarity(query, &vector);
+            matches.push(VectorMatch { id, text, distance });
+        }
+        matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        matches.truncate(k);
+        Ok(matches)
+    }
+
+    /// Remove the vector stored under `id`, returning whether it existed
+    pub fn delete(&self, id: i64) -> SqlResult<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM embeddings WHERE id = ?1 AND collection = ?2 AND embedder = ?3",
+            params![id, self.collection, self.embedder],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Number of stored vectors
+    pub fn len(&self) -> SqlResult<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM embeddings WHERE collection = ?1 AND embedder = ?2",
+            params![self.collection, self.embedder],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Whether no vectors are stored
+    pub fn is_empty(&self) -> SqlResult<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Remove every vector in the store, returning how many were removed
+    pub fn clear(&self) -> SqlResult<usize> {
+        self.conn.execute(
+            "DELETE FROM embeddings WHERE collection = ?1 AND embedder = ?2",
+            params![self.collection, self.embedder],
+        )
+    }
+}
+
+/// Sensor readings kept in downsampling tiers (see [`SensorLogConfig`])
+///
+/// Custom sensors are logged per id. Readings with non-finite values are
+/// not logged, and the aggregate tiers of a sensor keep the value count of
+/// its first reading in each bucket, skipping readings of another width.
+#[cfg(feature = "persistence")]
+pub struct SensorLog<'a> {
+    conn: &'a Connection,
+    config: SensorLogConfig,
+}
+
+/// Running sums of one aggregate bucket
+#[cfg(feature = "persistence")]
+#[derive(Debug, Clone)]
+struct Aggregate {
+    count: i64,
+    sums: Vec<f64>,
+    squares: Vec<f64>,
+    mins: Vec<f64>,
+    maxs: Vec<f64>,
+}
+
+#[cfg(feature = "persistence")]
+impl Aggregate {
+    fn of(values: &[f32]) -> Self {
+        let values: Vec<f64> = values.iter().map(|&v| f64::from(v)).collect();
+        Self {
+            count: 1,
+            squares: values.iter().map(|v| v * v).collect(),
+            mins: values.clone(),
+            maxs: values.clone(),
+            sums: values,
+        }
+    }
+
+    /// Fold `other` in; buckets of another width are left as they are
+    fn merge(&mut self, other: &Aggregate) {
+        if other.sums.len() != self.sums.len() {
+            return;
+        }
+        self.count += other.count;
+        for i in 0..self.sums.len() {
+            self.sums[i] += other.sums[i];
+            self.squares[i] += other.squares[i];
+            self.mins[i] = self.mins[i].min(other.mins[i]);
+            self.maxs[i] = self.maxs[i].max(other.maxs[i]);
+        }
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl SensorLog<'_> {
+    /// Log `readings` to every tier in one transaction, then drop what
+    /// has aged out of each tier relative to the newest reading. Returns
+    /// how many readings were logged.
+    pub fn record(&self, readings: &[SensorReading]) -> SqlResult<usize> {
+        let stats_ms = sql_ms(self.config.stats_bucket_ms.max(1));
+        let mut seconds: HashMap<(String, i64), Aggregate> = HashMap::new();
+        let mut buckets: HashMap<(String, i64), Aggregate> = HashMap::new();
+        let mut newest = None;
+
+        let tx = self.conn.unchecked_transaction()?;
+        let mut logged = 0;
+        for reading in readings {
+            if reading.values.is_empty() || !reading.values.iter().all(|v| v.is_finite()) {
+                continue;
+            }
+            let sensor = sensor_key(reading.sensor_type);
+            let timestamp = sql_ms(reading.timestamp_ms);
+            tx.execute(
+                "INSERT INTO sensor_raw (sensor, timestamp_ms, vals) VALUES (?1, ?2, ?3)",
+                params![sensor, timestamp, f32_blob(&reading.values)],
+            )?;
+            let aggregate = Aggregate::of(&reading.values);
+            for (tier, width) in [(&mut seconds, SECOND_MS), (&mut buckets, stats_ms)] {
+                let key = (sensor.clone(), timestamp - timestamp % width);
+                match tier.get_mut(&key) {
+                    Some(existing) => existing.merge(&aggregate),
+                    None => {
+                        tier.insert(key, aggregate.clone());
+                    }
+                }
+            }
+            newest = newest.max(Some(reading.timestamp_ms));
+            logged += 1;
+        }
+
+        for ((sensor, start), mut aggregate) in seconds {
+            let stored = tx.query_row(
+                "SELECT count, sums FROM sensor_seconds WHERE sensor = ?1 AND start_ms = ?2",
+                params![sensor, start],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            );
+            match stored {
+                Ok((count, sums)) => {
+                    let mut existing = aggregate.clone();
+                    existing.count = count;
+                    existing.sums = blob_f64(&sums);
+                    existing.merge(&aggregate);
+                    aggregate = existing;
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e),
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO sensor_seconds (sensor, start_ms, count, sums)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![sensor, start, aggregate.count, f64_blob(&aggregate.sums)],
+            )?;
+        }
+
+        for ((sensor, start), mut aggregate) in buckets {
+            let stored = tx.query_row(
+                "SELECT count, sums, squares, mins, maxs FROM sensor_stats
+                 WHERE sensor = ?1 AND start_ms = ?2",
+                params![sensor, start],
+                |row| {
+                    Ok(Aggregate {
+                        count: row.get(0)?,
+                        sums: blob_f64(&row.get::<_, Vec<u8>>(1)?),
+                        squares: blob_f64(&row.get::<_, Vec<u8>>(2)?),
+                        mins: blob_f64(&row.get::<_, Vec<u8>>(3)?),
+                        maxs: blob_f64(&row.get::<_, Vec<u8>>(4)?),
+                    })
+                },
+            );
+            match stored {
+                Ok(mut existing) => {
+                    existing.merge(&aggregate);
+                    aggregate = existing;
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e),
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO sensor_stats
+                 (sensor, start_ms, count, sums, squares, mins, maxs)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    sensor,
+                    start,
+                    aggregate.count,
+                    f64_blob(&aggregate.sums),
+                    f64_blob(&aggregate.squares),
+                    f64_blob(&aggregate.mins),
+                    f64_blob(&aggregate.maxs)
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        if let Some(newest) = newest {
+            self.apply_retention(newest)?;
+        }
+        Ok(logged)
+    }
+
+    /// Drop rows older than each tier's retention before `now_ms`;
+    /// returns how many rows were removed
+    pub fn apply_retention(&self, now_ms: u64) -> SqlResult<usize> {
+        let cutoff = |retention_ms: u64| sql_ms(now_ms.saturating_sub(retention_ms));
+        let raw = self.conn.execute(
+            "DELETE FROM sensor_raw WHERE timestamp_ms < ?1",
+            params![cutoff(self.config.raw_retention_ms)],
+        )?;
+        let seconds = self.conn.execute(
+            "DELETE FROM sensor_seconds WHERE start_ms + ?1 <= ?2",
+            params![SECOND_MS, cutoff(self.config.per_second_retention_ms)],
+        )?;
+        let stats = self.conn.execute(
+            "DELETE FROM sensor_stats WHERE start_ms + ?1 <= ?2",
+            params![
+                sql_ms(self.config.stats_bucket_ms.max(1)),
+                cutoff(self.config.stats_retention_ms)
+            ],
+        )?;
+        Ok(raw + seconds + stats)
+    }
 
-        Ok(count as usize)
+    /// Raw `sensor_type` readings with `start_ms <= timestamp < end_ms`,
+    /// oldest first; accuracy is not logged and reads back as the default
+    pub fn raw(
+        &self,
+        sensor_type: SensorType,
+        start_ms: u64,
+        end_ms: u64,
+    ) -> SqlResult<Vec<SensorReading>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp_ms, vals FROM sensor_raw
+             WHERE sensor = ?1 AND timestamp_ms >= ?2 AND timestamp_ms < ?3
+             ORDER BY timestamp_ms",
+        )?;
+        let rows = stmt.query_map(
+            params![sensor_key(sensor_type), sql_ms(start_ms), sql_ms(end_ms)],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+        )?;
+        rows.map(|row| {
+            let (timestamp, vals) = row?;
+            Ok(SensorReading::with_timestamp(sensor_type, blob_f32(&vals), timestamp as u64))
+        })
+        .collect()
     }
 
-    /// Clear all conversation history (for privacy/testing)
-    pub fn clear_history(&self, project: Option<&str>) -> SqlResult<usize> {
-        let count = if let Some(proj) = project {
-            self.conn.execute(
-                "DELETE FROM conversations WHERE project = ?1",
-                params![proj],
-            )?
-        } else {
-            self.conn.execute(
-                "DELETE FROM conversations WHERE project IS NULL",
-                [],
-            )?
-        };
+    /// Per-second means of `sensor_type` for seconds starting in
+    /// `start_ms..end_ms`, as readings stamped with the second's start
+    pub fn per_second(
+        &self,
+        sensor_type: SensorType,
+        start_ms: u64,
+        end_ms: u64,
+    ) -> SqlResult<Vec<SensorReading>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT start_ms, count, sums FROM sensor_seconds
+             WHERE sensor = ?1 AND start_ms >= ?2 AND start_ms < ?3
+             ORDER BY start_ms",
+        )?;
+        let rows = stmt.query_map(
+            params![sensor_key(sensor_type), sql_ms(start_ms), sql_ms(end_ms)],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Vec<u8>>(2)?,
+                ))
+            },
+        )?;
+        rows.map(|row| {
+            let (start, count, sums) = row?;
+            let count = count.max(1) as f64;
+            let means = blob_f64(&sums).iter().map(|s| (s / count) as f32).collect();
+            Ok(SensorReading::with_timestamp(sensor_type, means, start as u64))
+        })
+        .collect()
+    }
 
-        Ok(count)
+    /// Statistics of `sensor_type` for buckets starting in
+    /// `start_ms..end_ms`, oldest first
+    pub fn stats(
+        &self,
+        sensor_type: SensorType,
+        start_ms: u64,
+        end_ms: u64,
+    ) -> SqlResult<Vec<SensorStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT start_ms, count, sums, squares, mins, maxs FROM sensor_stats
+             WHERE sensor = ?1 AND start_ms >= ?2 AND start_ms < ?3
+             ORDER BY start_ms",
+        )?;
+        let rows = stmt.query_map(
+            params![sensor_key(sensor_type), sql_ms(start_ms), sql_ms(end_ms)],
+            |row| {
+                let start: i64 = row.get(0)?;
+                let aggregate = Aggregate {
+                    count: row.get(1)?,
+                    sums: blob_f64(&row.get::<_, Vec<u8>>(2)?),
+                    squares: blob_f64(&row.get::<_, Vec<u8>>(3)?),
+                    mins: blob_f64(&row.get::<_, Vec<u8>>(4)?),
+                    maxs: blob_f64(&row.get::<_, Vec<u8>>(5)?),
+                };
+                Ok((start, aggregate))
+            },
+        )?;
+        rows.map(|row| {
+            let (start, aggregate) = row?;
+            let n = aggregate.count.max(1) as f64;
+            let mean: Vec<f64> = aggregate.sums.iter().map(|s| s / n).collect();
+            let std = aggregate
+                .squares
+                .iter()
+                .zip(&mean)
+                .map(|(sq, m)| (sq / n - m * m).max(0.0).sqrt() as f32)
+                .collect();
+            let to_f32 = |values: &[f64]| values.iter().map(|&v| v as f32).collect();
+            Ok(SensorStats {
+                start_ms: start as u64,
+                count: aggregate.count as u64,
+                mean: to_f32(&mean),
+                std,
+                min: to_f32(&aggregate.mins),
+                max: to_f32(&aggregate.maxs),
+            })
+        })
+        .collect()
     }
 
-    /// Vacuum database to reclaim space
-    pub fn vacuum(&self) -> SqlResult<()> {
-        self.conn.execute("VACUUM", [])?;
-        Ok(())
+    /// Remove everything logged, in every tier; returns how many rows
+    /// were removed
+    pub fn clear(&self) -> SqlResult<usize> {
+        let mut removed = 0;
+        for table in ["sensor_raw", "sensor_seconds", "sensor_stats"] {
+            removed += self.conn.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        Ok(removed)
     }
+}
 
-    /// Get database file size (if not in-memory)
-    pub fn database_size(&self) -> SqlResult<u64> {
-        let page_count: i64 = self.conn.query_row(
-            "PRAGMA page_count",
-            [],
-            |row| row.get(0),
-        )?;
+/// Error for a database whose schema this build cannot safely use
+#[cfg(feature = "persistence")]
+fn schema_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+        Some(message),
+    )
+}
 
-        let page_size: i64 = self.conn.query_row(
-            "PRAGMA page_size",
-            [],
-            |row| row.get(0),
-        )?;
+/// Error for a database key that is missing, unusable or not the one the
+/// database was encrypted under; SQLCipher reports a wrong key the same way
+#[cfg(feature = "persistence")]
+fn key_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTADB),
+        Some(message),
+    )
+}
 
-        Ok((page_count * page_size) as u64)
+/// `ms` as an SQLite integer, saturating at `i64::MAX`
+#[cfg(feature = "persistence")]
+fn sql_ms(ms: u64) -> i64 {
+    i64::try_from(ms).unwrap_or(i64::MAX)
+}
+
+/// Name a sensor is logged under; custom sensors keep their id
+#[cfg(feature = "persistence")]
+fn sensor_key(sensor_type: SensorType) -> String {
+    match sensor_type {
+        SensorType::Custom(id) => format!("custom:{}", id),
+        other => other.name().to_string(),
     }
 }
 
 // Helper for ConversationTurn construction from SQLite row
+#[cfg(feature = "persistence")]
 impl ConversationTurn {
-    #[cfg(feature = "persistence")]
     fn from_row(row: &rusqlite::Row) -> Self {
         use crate::types::{Query, Response, RoutingDecision, ResponseMetadata};
 
@@ -377,7 +1756,11 @@ fn from_row(row: &rusqlite::Row) -> Self {
             "Local" => RoutingDecision::Local,
             "Remote" => RoutingDecision::Remote,
             "Hybrid" => RoutingDecision::Hybrid,
-            _ => RoutingDecision::Blocked,
+            other => other
+                .strip_prefix("Custom(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|index| index.parse().ok())
+                .map_or(RoutingDecision::Blocked, RoutingDecision::Custom),
         };
 
         ConversationTurn {
@@ -386,6 +1769,8 @@ fn from_row(row: &rusqlite::Row) -> Self {
                 project_context: None, // Not stored in simple schema
                 priority: query_priority,
                 timestamp: query_timestamp,
+                required_capabilities: Vec::new(), // Not stored in simple schema
+                attachments: Vec::new(), // Not stored in simple schema
             },
             response: Response {
                 text: response_text,
@@ -396,32 +1781,141 @@ fn from_row(row: &rusqlite::Row) -> Self {
                     model: None,
                     tokens: None,
                     cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
                 },
             },
         }
     }
 }
 
+/// Insert one conversation turn, returning its row id
+#[cfg(feature = "persistence")]
+fn insert_turn(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    project: Option<&str>,
+    turn: &ConversationTurn,
+) -> SqlResult<i64> {
+    let now = current_timestamp();
+
+    conn.execute(
+        "INSERT INTO conversations (
+            project, query_text, query_priority, query_timestamp,
+            response_text, response_route, response_confidence,
+            response_timestamp, created_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            project,
+            cipher.seal(&turn.query.text)?,
+            turn.query.priority,
+            turn.query.timestamp,
+            cipher.seal(&turn.response.text)?,
+            format!("{:?}", turn.response.route),
+            turn.response.confidence,
+            turn.response.latency_ms as i64,
+            now,
+        ],
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+/// A model stored in the database: as a binary blob under `fast-serde`,
+/// otherwise as JSON text
+#[cfg(all(feature = "persistence", feature = "fast-serde"))]
+trait StoredModel: BinaryFormat {}
+
+#[cfg(all(feature = "persistence", feature = "fast-serde"))]
+impl<T: BinaryFormat> StoredModel for T {}
+
+/// A model stored in the database: as a binary blob under `fast-serde`,
+/// otherwise as JSON text
+#[cfg(all(feature = "persistence", not(feature = "fast-serde")))]
+trait StoredModel: Serialize + serde::de::DeserializeOwned {}
+
+#[cfg(all(feature = "persistence", not(feature = "fast-serde")))]
+impl<T: Serialize + serde::de::DeserializeOwned> StoredModel for T {}
+
+/// Column value storing `model`
+#[cfg(feature = "persistence")]
+fn encode_model<T: StoredModel>(model: &T) -> SqlResult<rusqlite::types::Value> {
+    #[cfg(feature = "fast-serde")]
+    let value = model.to_binary().map(rusqlite::types::Value::Blob).map_err(Into::into);
+    #[cfg(not(feature = "fast-serde"))]
+    let value = serde_json::to_string(model).map(rusqlite::types::Value::Text).map_err(Into::into);
+    value.map_err(rusqlite::Error::ToSqlConversionFailure)
+}
+
+/// Model stored in a column value; JSON text is read whatever the
+/// features, so databases written without `fast-serde` stay readable
+#[cfg(feature = "persistence")]
+fn decode_model<T: StoredModel>(value: rusqlite::types::Value) -> SqlResult<T> {
+    use rusqlite::types::{Type, Value};
+
+    let decoded = match value {
+        Value::Text(json) => serde_json::from_str(&json).map_err(Into::into),
+        #[cfg(feature = "fast-serde")]
+        Value::Blob(bytes) => T::from_binary(&bytes).map_err(Into::into),
+        _ => Err("unrecognized model encoding".into()),
+    };
+    decoded.map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e))
+}
+
+/// Little-endian bytes of `values`
+#[cfg(feature = "persistence")]
+fn f32_blob(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Values of a blob written by `f32_blob`
+#[cfg(feature = "persistence")]
+fn blob_f32(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Little-endian bytes of `values`
+#[cfg(feature = "persistence")]
+fn f64_blob(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Values of a blob written by `f64_blob`
+#[cfg(feature = "persistence")]
+fn blob_f64(blob: &[u8]) -> Vec<f64> {
+    blob.chunks_exact(8)
+        .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect()
+}
+
 /// Get current Unix timestamp
+#[cfg(feature = "persistence")]
 fn current_timestamp() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .expect("system clock invariant: time is after UNIX_EPOCH (1970-01-01)")
-        .as_secs()
+    crate::clock::now_secs()
 }
 
 // No-op implementation when persistence is disabled
+/// Stand-in for the SQLite backend when the `persistence` feature is
+/// off (e.g. WebAssembly builds); it cannot be constructed
 #[cfg(not(feature = "persistence"))]
 pub struct PersistenceManager;
 
 #[cfg(not(feature = "persistence"))]
 impl PersistenceManager {
-    pub fn new<P: AsRef<Path>>(_db_path: P) -> Result<Self, String> {
-        Err("Persistence feature not enabled".to_string())
+    /// Always fails: persistence is not compiled in
+    pub fn new<P: AsRef<Path>>(_db_path: P) -> Result<Self, OrchestratorError> {
+        Err(OrchestratorError::NotConfigured("Persistence feature not enabled".to_string()))
     }
 
-    pub fn new_in_memory() -> Result<Self, String> {
-        Err("Persistence feature not enabled".to_string())
+    /// Always fails: persistence is not compiled in
+    pub fn new_in_memory() -> Result<Self, OrchestratorError> {
+        Err(OrchestratorError::NotConfigured("Persistence feature not enabled".to_string()))
     }
 }
 
@@ -441,6 +1935,69 @@ fn test_persistence_manager_creation() {
         assert_eq!(count, 0);
     }
 
+    #[test]
+    fn test_migrations_upgrade_old_databases_and_refuse_unknown_ones() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(versions, (1..=SCHEMA_VERSION).collect::<Vec<_>>());
+
+        let path = std::env::temp_dir().join(format!("migrations-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // A database from before migrations were tracked
+        {
+            let Ok(conn) = Connection::open(&path) else {
+                panic!("open should succeed");
+            };
+            let Ok(()) = conn.execute_batch(
+                "CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO metadata VALUES ('schema_version', '1');
+                 CREATE TABLE config (key TEXT PRIMARY KEY, value TEXT NOT NULL,
+                                      updated_at INTEGER NOT NULL);
+                 INSERT INTO config VALUES ('theme', 'dark', 0);",
+            ) else {
+                panic!("legacy schema should be created");
+            };
+        }
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("a legacy database should be migrated");
+        };
+        assert_eq!(pm.schema_version(), Ok(SCHEMA_VERSION));
+        assert_eq!(pm.load_config("theme"), Ok(Some("dark".to_string())));
+        drop(pm);
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening a migrated database should succeed");
+        };
+        assert_eq!(pm.schema_version(), Ok(SCHEMA_VERSION));
+
+        let Ok(_) = pm.conn.execute(
+            "UPDATE schema_migrations SET checksum = 'edited' WHERE version = 1",
+            [],
+        ) else {
+            panic!("update should succeed");
+        };
+        drop(pm);
+        assert!(PersistenceManager::new(&path).is_err());
+
+        let Ok(conn) = Connection::open(&path) else {
+            panic!("open should succeed");
+        };
+        let restore = format!(
+            "UPDATE schema_migrations SET checksum = '{}' WHERE version = 1;
+             INSERT INTO schema_migrations VALUES (99, 'from the future', '', 0);",
+            MIGRATIONS[0].checksum()
+        );
+        let Ok(()) = conn.execute_batch(&restore) else {
+            panic!("update should succeed");
+        };
+        drop(conn);
+        let Err(error) = PersistenceManager::new(&path) else {
+            panic!("a newer database should be refused");
+        };
+        assert!(error.to_string().contains("newer"), "{}", error);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
     #[test]
     fn test_save_and_load_turn() {
         let Ok(pm) = PersistenceManager::new_in_memory() else {
@@ -457,6 +2014,12 @@ fn test_save_and_load_turn() {
                 model: Some("local-model".to_string()),
                 tokens: Some(10),
                 cached: false,
+                context_budget: None,
+                turn_id: None,
+                explanation: None,
+                state_fingerprint: None,
+                degraded: None,
+                energy_joules: None,
             },
         };
 
@@ -469,7 +2032,7 @@ fn test_save_and_load_turn() {
             panic!("save_turn should succeed");
         };
 
-        let Ok(history) = pm.load_history(None, 10) else {
+        let Ok(history) = pm.load_history(None, 0, 10) else {
             panic!("load_history should succeed");
         };
         assert_eq!(history.len(), 1);
@@ -477,6 +2040,73 @@ fn test_save_and_load_turn() {
         assert_eq!(history[0].response.text, response.text);
     }
 
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_columns_need_the_right_key() {
+        struct Key(u8);
+        impl KeyProvider for Key {
+            fn database_key(&self) -> Result<[u8; 32], String> {
+                Ok([self.0; 32])
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("encrypted-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let turn = ConversationTurn {
+            query: Query::new("my blood pressure readings"),
+            response: Response {
+                text: "They are within the normal range.".to_string(),
+                route: RoutingDecision::Local,
+                confidence: 0.9,
+                latency_ms: 5,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        };
+        {
+            let Ok(pm) = PersistenceManager::with_encryption(&path, &Key(7)) else {
+                panic!("with_encryption should succeed");
+            };
+            let Ok(_) = pm.save_turn(None, &turn) else {
+                panic!("save_turn should succeed");
+            };
+            let Ok(_) = pm.vector_store("notes", "e").insert("blood pressure", &[1.0]) else {
+                panic!("insert should succeed");
+            };
+            assert!(matches!(pm.has_turn(&turn), Ok(true)));
+        }
+
+        let Ok(bytes) = std::fs::read(&path) else {
+            panic!("database file should exist");
+        };
+        assert!(!bytes.windows(14).any(|w| w == b"blood pressure"));
+
+        assert!(PersistenceManager::with_encryption(&path, &Key(8)).is_err());
+        assert!(PersistenceManager::new(&path).is_err());
+        let Ok(pm) = PersistenceManager::with_encryption(&path, &Key(7)) else {
+            panic!("reopening with the key should succeed");
+        };
+        let Ok(history) = pm.load_history(None, 0, 10) else {
+            panic!("load_history should succeed");
+        };
+        assert_eq!(history[0].query.text, turn.query.text);
+        assert_eq!(history[0].response.text, turn.response.text);
+        let Ok(matches) = pm.vector_store("notes", "e").knn(&[1.0], 1) else {
+            panic!("knn should succeed");
+        };
+        assert_eq!(matches[0].text, "blood pressure");
+        let _ = std::fs::remove_file(&path);
+    }
+
     #[test]
     fn test_project_isolation() {
         let Ok(pm) = PersistenceManager::new_in_memory() else {
@@ -494,6 +2124,12 @@ fn test_project_isolation() {
                     model: None,
                     tokens: Some(10),
                     cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
                 },
             },
         };
@@ -509,6 +2145,12 @@ fn test_project_isolation() {
                     model: None,
                     tokens: Some(20),
                     cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
                 },
             },
         };
@@ -520,10 +2162,10 @@ fn test_project_isolation() {
             panic!("save_turn should succeed");
         };
 
-        let Ok(history_a) = pm.load_history(Some("project_a"), 10) else {
+        let Ok(history_a) = pm.load_history(Some("project_a"), 0, 10) else {
             panic!("load_history should succeed");
         };
-        let Ok(history_b) = pm.load_history(Some("project_b"), 10) else {
+        let Ok(history_b) = pm.load_history(Some("project_b"), 0, 10) else {
             panic!("load_history should succeed");
         };
 
@@ -587,6 +2229,262 @@ fn test_mlp_persistence() {
         assert_eq!(output.len(), 3);
     }
 
+    #[cfg(feature = "fast-serde")]
+    #[test]
+    fn test_models_are_stored_binary_and_json_rows_still_load() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let mlp = MLP::new(16, vec![8], 3);
+        let Ok(_) = pm.save_mlp("binary", &mlp, None) else {
+            panic!("save_mlp should succeed");
+        };
+        let Ok(json) = serde_json::to_string(&mlp) else {
+            panic!("MLP should encode as JSON");
+        };
+        let Ok(_) = pm.conn.execute(
+            "INSERT INTO model_weights (model_type, model_name, weights_json, trained_at)
+             VALUES ('mlp', 'json', ?1, 0)",
+            params![json],
+        ) else {
+            panic!("insert should succeed");
+        };
+
+        let Ok(kind) = pm.conn.query_row(
+            "SELECT typeof(weights_json) FROM model_weights WHERE model_name = 'binary'",
+            [],
+            |row| row.get::<_, String>(0),
+        ) else {
+            panic!("query should succeed");
+        };
+        assert_eq!(kind, "blob");
+        let input = vec![0.5; 16];
+        for name in ["binary", "json"] {
+            let Ok(Some(loaded)) = pm.load_mlp(name) else {
+                panic!("load_mlp({}) should succeed", name);
+            };
+            assert_eq!(loaded.forward(&input), mlp.forward(&input));
+        }
+    }
+
+    #[test]
+    fn test_router_model_persistence_keeps_schema() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+
+        let model = RouterModel::new(MLP::new(384, vec![16], 3));
+        let Ok(_) = pm.save_router_model("default", &model, None) else {
+            panic!("save_router_model should succeed");
+        };
+        let Ok(Some(loaded)) = pm.load_router_model("default") else {
+            panic!("load_router_model should find the saved model");
+        };
+        assert_eq!(loaded.feature_schema_version, crate::features::FEATURE_SCHEMA_VERSION);
+        let extractor = crate::features::FeatureExtractor::new();
+        assert!(loaded.check_compatible(&extractor, &[]).is_ok());
+
+        // Router models and bare MLPs live in separate namespaces
+        let Ok(bare) = pm.load_mlp("default") else {
+            panic!("load_mlp should succeed");
+        };
+        assert!(bare.is_none());
+    }
+
+    #[test]
+    fn test_timeseries_persistence() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+
+        let mut store = TimeSeriesStore::default();
+        for minute in 0..10u64 {
+            let Ok(()) = store.record_features("motion", minute * 60_000, &[minute as f32, 0.5]) else {
+                panic!("record_features should succeed");
+            };
+        }
+        store.flush();
+
+        let Ok(_) = pm.save_timeseries("device", &store) else {
+            panic!("save_timeseries should succeed");
+        };
+
+        let Ok(Some(loaded)) = pm.load_timeseries("device") else {
+            panic!("load_timeseries should return the saved store");
+        };
+        let Some(series) = loaded.series("motion") else {
+            panic!("motion series should be restored");
+        };
+        assert_eq!(series.len(), 10);
+
+        let Ok(missing) = pm.load_timeseries("other") else {
+            panic!("load_timeseries should succeed");
+        };
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn test_sensor_log_keeps_downsampled_tiers() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        let config = SensorLogConfig {
+            raw_retention_ms: 2_000,
+            per_second_retention_ms: 5_000,
+            stats_retention_ms: 20_000,
+            stats_bucket_ms: 10_000,
+            ..SensorLogConfig::default()
+        };
+        let log = pm.sensor_log(&config);
+
+        // 10 Hz light readings over three seconds, in two batches
+        let readings: Vec<SensorReading> = (0..30u64)
+            .map(|i| SensorReading::with_timestamp(SensorType::Light, vec![i as f32], i * 100))
+            .collect();
+        assert_eq!(log.record(&readings[..15]).ok(), Some(15));
+        let nan = SensorReading::with_timestamp(SensorType::Light, vec![f32::NAN], 2_950);
+        assert_eq!(log.record(&[&readings[15..], &[nan]].concat()).ok(), Some(15));
+
+        // Raw readings older than two seconds before the newest are gone
+        let Ok(raw) = log.raw(SensorType::Light, 0, u64::MAX) else {
+            panic!("raw query should succeed");
+        };
+        assert_eq!(raw.first().map(|r| r.timestamp_ms), Some(900));
+        assert_eq!(raw.len(), 21);
+
+        let Ok(seconds) = log.per_second(SensorType::Light, 0, 3_000) else {
+            panic!("per-second query should succeed");
+        };
+        let means: Vec<f32> = seconds.iter().map(|r| r.values[0]).collect();
+        assert_eq!(means, vec![4.5, 14.5, 24.5]);
+
+        let Ok(stats) = log.stats(SensorType::Light, 0, u64::MAX) else {
+            panic!("stats query should succeed");
+        };
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].count, 30);
+        assert_eq!((stats[0].min[0], stats[0].max[0], stats[0].mean[0]), (0.0, 29.0, 14.5));
+        assert!((stats[0].std[0] - 8.655).abs() < 1e-3, "{:?}", stats[0].std);
+        assert_eq!(log.stats(SensorType::Custom(1), 0, u64::MAX).map(|s| s.len()), Ok(0));
+
+        // A month later only the statistics tier has anything to drop
+        assert_eq!(log.apply_retention(40_000).ok(), Some(21 + 3 + 1));
+        assert_eq!(log.clear().ok(), Some(0));
+    }
+
+    #[test]
+    fn test_vector_store_knn_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("vectors-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let Ok(pm) = PersistenceManager::new(&path) else {
+                panic!("new should succeed");
+            };
+            let store = pm.vector_store("notes", "test-embedder");
+            for (text, vector) in [
+                ("rust ownership", [1.0, 0.0, 0.0]),
+                ("borrow checker", [0.9, 0.1, 0.0]),
+                ("sourdough recipe", [0.0, 0.0, 1.0]),
+            ] {
+                let Ok(_) = store.insert(text, &vector) else {
+                    panic!("insert should succeed");
+                };
+            }
+            // Other embedders and dimensions are never compared
+            let Ok(_) = pm.vector_store("notes", "other").insert("noise", &[1.0, 0.0, 0.0]) else {
+                panic!("insert should succeed");
+            };
+            let Ok(_) = store.insert("short", &[1.0, 0.0]) else {
+                panic!("insert should succeed");
+            };
+        }
+
+        let Ok(pm) = PersistenceManager::new(&path) else {
+            panic!("reopening should succeed");
+        };
+        let store = pm.vector_store("notes", "test-embedder");
+        let Ok(matches) = store.knn(&[1.0, 0.05, 0.0], 2) else {
+            panic!("knn should succeed");
+        };
+        let texts: Vec<&str> = matches.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["rust ownership", "borrow checker"]);
+        assert!(matches[0].distance < 0.01);
+
+        let Ok(true) = store.delete(matches[0].id) else {
+            panic!("delete should remove the row");
+        };
+        assert_eq!(store.len(), Ok(3));
+        assert_eq!(store.clear(), Ok(3));
+        assert_eq!(store.is_empty(), Ok(true));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_retention_limits_age_count_and_size() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        const DAY: u64 = 24 * 60 * 60;
+        let turn = |day: u64| ConversationTurn {
+            query: Query {
+                timestamp: day * DAY,
+                ..Query::new("x".repeat(2_000))
+            },
+            response: Response {
+                text: format!("Response {}", day),
+                route: RoutingDecision::Local,
+                confidence: 0.9,
+                latency_ms: 5,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        };
+        for day in 0..20 {
+            let Ok(_) = pm.save_turn(Some("a"), &turn(day)) else {
+                panic!("save_turn should succeed");
+            };
+        }
+        for _ in 0..5 {
+            let Ok(_) = pm.save_turn(None, &turn(19)) else {
+                panic!("save_turn should succeed");
+            };
+        }
+
+        // Nothing is limited by default
+        assert_eq!(pm.apply_retention(&RetentionConfig::default(), 20 * DAY), Ok(0));
+        let config = RetentionConfig {
+            max_turn_age_days: 10,
+            max_turns_per_project: 4,
+            ..RetentionConfig::default()
+        };
+        assert_eq!(pm.apply_retention(&config, 20 * DAY), Ok(10 + 6 + 1));
+        let Ok(kept) = pm.load_history(Some("a"), 0, 10) else {
+            panic!("load_history should succeed");
+        };
+        let days: Vec<&str> = kept.iter().map(|t| t.response.text.as_str()).collect();
+        assert_eq!(days, ["Response 16", "Response 17", "Response 18", "Response 19"]);
+        assert_eq!(pm.conversation_count(None), Ok(4));
+
+        let Ok(before) = pm.database_size() else {
+            panic!("database_size should succeed");
+        };
+        let config = RetentionConfig {
+            max_database_bytes: 1,
+            ..RetentionConfig::default()
+        };
+        assert_eq!(pm.apply_retention(&config, 20 * DAY), Ok(8));
+        assert!(pm.database_size().is_ok_and(|after| after < before));
+    }
+
     #[test]
     fn test_clear_history() {
         let Ok(pm) = PersistenceManager::new_in_memory() else {
@@ -595,7 +2493,7 @@ fn test_clear_history() {
 
         for i in 0..10 {
             let turn = ConversationTurn {
-                query: Query::new(&format!("Query {}", i)),
+                query: Query::new(format!("Query {}", i)),
                 response: Response {
                     text: format!("Response {}", i),
                     route: RoutingDecision::Local,
@@ -605,6 +2503,12 @@ fn test_clear_history() {
                         model: None,
                         tokens: Some(10),
                         cached: false,
+                        context_budget: None,
+                        turn_id: None,
+                        explanation: None,
+                        state_fingerprint: None,
+                        degraded: None,
+                        energy_joules: None,
                     },
                 },
             };
@@ -635,7 +2539,7 @@ fn test_history_limit() {
 
         let base_timestamp = current_timestamp();
         for i in 0..100 {
-            let mut query = Query::new(&format!("Query {}", i));
+            let mut query = Query::new(format!("Query {}", i));
             // Set explicit timestamp to ensure ordering
             query.timestamp = base_timestamp + i as u64;
 
@@ -650,6 +2554,12 @@ fn test_history_limit() {
                         model: None,
                         tokens: Some(10),
                         cached: false,
+                        context_budget: None,
+                        turn_id: None,
+                        explanation: None,
+                        state_fingerprint: None,
+                        degraded: None,
+                        energy_joules: None,
                     },
                 },
             };
@@ -658,7 +2568,7 @@ fn test_history_limit() {
             };
         }
 
-        let Ok(history) = pm.load_history(None, 10) else {
+        let Ok(history) = pm.load_history(None, 0, 10) else {
             panic!("load_history should succeed");
         };
         assert_eq!(history.len(), 10);
@@ -667,4 +2577,29 @@ fn test_history_limit() {
         assert_eq!(history[0].query.text, "Query 90");
         assert_eq!(history[9].query.text, "Query 99");
     }
+
+    #[test]
+    fn test_settings_round_trip_as_text() {
+        let Ok(pm) = PersistenceManager::new_in_memory() else {
+            panic!("new_in_memory should succeed");
+        };
+        for setting in [Setting::CacheTtlSecs(600), Setting::DefaultProject(None)] {
+            let Ok(()) = pm.save_setting(&setting) else {
+                panic!("save_setting should succeed");
+            };
+        }
+        let Ok(()) = pm.save_config("setting:volume", "11") else {
+            panic!("save_config should succeed");
+        };
+        assert_eq!(pm.load_config("setting:cache_ttl_secs"), Ok(Some("600".to_string())));
+        assert_eq!(
+            pm.load_settings(),
+            Ok(vec![Setting::CacheTtlSecs(600), Setting::DefaultProject(None)])
+        );
+
+        let Ok(()) = pm.save_config("setting:telemetry_opt_in", "maybe") else {
+            panic!("save_config should succeed");
+        };
+        assert!(pm.load_settings().is_err());
+    }
 }
@@ -0,0 +1,513 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Personalization From Long-Term Signals
+//!
+//! Over many turns the orchestrator learns, entirely on the device, how the
+//! user asks:
+//!
+//! - **Expertise**: jargon, code and identifiers raise it; "what is",
+//!   "new to" or "in simple terms" lower it.
+//! - **Verbosity preference**: requests for brief or detailed answers, and
+//!   remembered preferences such as "prefers short answers", which win.
+//! - **Typical topics**: the content words the user keeps returning to.
+//!
+//! Expertise and verbosity are one-hot router features (see
+//! [`crate::features`]), so a model trained on the user's feedback can
+//! route an expert's questions differently. Topics are open-ended and only
+//! shape the prompt. All three become a "Response style" prompt section.
+//!
+//! Signals are exponential moving averages, so they follow the user over
+//! time. [`UserSignals`] reports them with the evidence behind each one;
+//! nothing is inferred that cannot be shown to the user and reset.
+
+#![forbid(unsafe_code)]
+
+use crate::blend::keyphrases;
+use crate::memory::{FactKind, MemoryFact};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Phrases of someone new to a subject
+const NOVICE_CUES: &[&str] = &[
+    "what is",
+    "what's a",
+    "what does",
+    "eli5",
+    "explain like",
+    "in simple terms",
+    "for beginners",
+    "beginner",
+    "new to",
+    "i don't understand",
+];
+
+/// Words that mark a specialist's query
+const JARGON: &[&str] = &[
+    "api",
+    "async",
+    "compiler",
+    "config",
+    "gradient",
+    "kernel",
+    "latency",
+    "mutex",
+    "protocol",
+    "regex",
+    "runtime",
+    "schema",
+    "tensor",
+    "thread",
+    "throughput",
+];
+
+/// Requests for short answers
+const BRIEF_CUES: &[&str] = &[
+    "briefly",
+    "brief",
+    "short answer",
+    "tl;dr",
+    "tldr",
+    "concise",
+    "in one sentence",
+    "one line",
+    "keep it short",
+];
+
+/// Requests for long answers
+const DETAILED_CUES: &[&str] = &[
+    "in detail",
+    "detailed",
+    "step by step",
+    "step-by-step",
+    "thorough",
+    "elaborate",
+    "walk me through",
+    "comprehensive",
+];
+
+/// Words of a remembered preference about answers
+const ANSWER_WORDS: &[&str] = &["answer", "response", "explanation", "reply"];
+
+/// Words too generic to be a topic
+const GENERIC_WORDS: &[&str] = &[
+    "explain", "help", "know", "like", "make", "need", "please", "tell", "thanks", "want",
+];
+
+/// Topic weight at which a topic counts as typical
+const TOPIC_THRESHOLD: f32 = 2.0;
+
+/// Topic weight below which a topic is forgotten
+const TOPIC_FLOOR: f32 = 0.05;
+
+/// Most topics tracked at once
+const MAX_TRACKED_TOPICS: usize = 256;
+
+/// Personalization settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonalizationConfig {
+    /// Learn signals from queries and use them for routing and prompts
+    pub enabled: bool,
+    /// Turns observed before signals are used
+    pub min_turns: u64,
+    /// Weight of each new turn in the moving averages (0.0-1.0)
+    pub smoothing: f32,
+    /// Most typical topics named in prompts
+    pub max_topics: usize,
+}
+
+impl Default for PersonalizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_turns: 5,
+            smoothing: 0.1,
+            max_topics: 3,
+        }
+    }
+}
+
+/// How familiar the user is with what they ask about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Expertise {
+    /// Asks for basics and plain words
+    Novice,
+    /// Neither
+    #[default]
+    Intermediate,
+    /// Uses jargon, code and identifiers
+    Expert,
+}
+
+impl Expertise {
+    /// Number of levels (width of the one-hot feature block)
+    pub const COUNT: usize = 3;
+
+    /// Every level, in feature-block order
+    pub const ALL: [Expertise; Self::COUNT] = [
+        Expertise::Novice,
+        Expertise::Intermediate,
+        Expertise::Expert,
+    ];
+
+    /// Stable index of this level within the feature block
+    pub const fn index(&self) -> usize {
+        match self {
+            Expertise::Novice => 0,
+            Expertise::Intermediate => 1,
+            Expertise::Expert => 2,
+        }
+    }
+}
+
+/// How long the user likes answers to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Verbosity {
+    /// Short answers
+    Brief,
+    /// No stated preference
+    #[default]
+    Balanced,
+    /// Thorough, step-by-step answers
+    Detailed,
+}
+
+impl Verbosity {
+    /// Number of preferences (width of the one-hot feature block)
+    pub const COUNT: usize = 3;
+
+    /// Every preference, in feature-block order
+    pub const ALL: [Verbosity; Self::COUNT] =
+        [Verbosity::Brief, Verbosity::Balanced, Verbosity::Detailed];
+
+    /// Stable index of this preference within the feature block
+    pub const fn index(&self) -> usize {
+        match self {
+            Verbosity::Brief => 0,
+            Verbosity::Balanced => 1,
+            Verbosity::Detailed => 2,
+        }
+    }
+}
+
+/// What has been learned about the user, with its evidence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserSignals {
+    /// Enough turns were observed for the signals to be used
+    pub active: bool,
+    /// Expertise level
+    pub expertise: Expertise,
+    /// Verbosity preference
+    pub verbosity: Verbosity,
+    /// Typical topics, most frequent first
+    pub topics: Vec<String>,
+    /// Turns observed
+    pub observed_turns: u64,
+    /// Why each signal has its value, in plain words
+    pub evidence: Vec<String>,
+}
+
+impl UserSignals {
+    /// Router feature block: expertise one-hot, then verbosity one-hot
+    pub fn to_features(&self) -> Vec<f32> {
+        let mut features = vec![0.0; Expertise::COUNT + Verbosity::COUNT];
+        features[self.expertise.index()] = 1.0;
+        features[Expertise::COUNT + self.verbosity.index()] = 1.0;
+        features
+    }
+
+    /// Body of the prompt's "Response style" section; `None` when inactive
+    /// or nothing sets the user apart
+    pub fn style(&self) -> Option<String> {
+        if !self.active {
+            return None;
+        }
+        let mut lines = Vec::new();
+        match self.expertise {
+            Expertise::Novice => lines.push("- Explain terms a newcomer may not know."),
+            Expertise::Expert => lines.push("- The user is an expert; skip the basics."),
+            Expertise::Intermediate => {}
+        }
+        match self.verbosity {
+            Verbosity::Brief => lines.push("- Keep answers short."),
+            Verbosity::Detailed => lines.push("- Give thorough, step-by-step answers."),
+            Verbosity::Balanced => {}
+        }
+        let mut lines: Vec<String> = lines.into_iter().map(str::to_string).collect();
+        if !self.topics.is_empty() {
+            lines.push(format!("- Frequent topics: {}.", self.topics.join(", ")));
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// One-line summary for routing explanations
+    pub fn summary(&self) -> String {
+        format!(
+            "personalization: {:?} expertise, {:?} answers",
+            self.expertise, self.verbosity
+        )
+    }
+}
+
+/// Moving averages behind [`UserSignals`], updated once per query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignalTracker {
+    /// Expertise from 0.0 (novice) to 1.0 (expert)
+    expertise_score: f32,
+    /// Verbosity from -1.0 (brief) to 1.0 (detailed)
+    verbosity_score: f32,
+    /// Queries that asked for a length of answer
+    verbosity_requests: u64,
+    /// Decayed mentions per topic
+    topics: BTreeMap<String, f32>,
+    turns: u64,
+}
+
+impl Default for SignalTracker {
+    fn default() -> Self {
+        Self {
+            expertise_score: 0.5,
+            verbosity_score: 0.0,
+            verbosity_requests: 0,
+            topics: BTreeMap::new(),
+            turns: 0,
+        }
+    }
+}
+
+impl SignalTracker {
+    /// A tracker that has observed nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turns observed
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    /// Learn from one query, weighting it by `smoothing`
+    pub fn observe(&mut self, text: &str, smoothing: f32) {
+        let smoothing = smoothing.clamp(0.0, 1.0);
+        let lower = text.to_lowercase();
+        self.turns += 1;
+
+        self.expertise_score += smoothing * (expertise_score(text, &lower) - self.expertise_score);
+
+        let brief = BRIEF_CUES.iter().any(|cue| lower.contains(cue));
+        let detailed = DETAILED_CUES.iter().any(|cue| lower.contains(cue));
+        if brief != detailed {
+            let target = if brief { -1.0 } else { 1.0 };
+            self.verbosity_score += smoothing * (target - self.verbosity_score);
+            self.verbosity_requests += 1;
+        }
+
+        for weight in self.topics.values_mut() {
+            *weight *= 1.0 - smoothing;
+        }
+        for topic in keyphrases(text) {
+            if !GENERIC_WORDS.contains(&topic.as_str()) && !is_cue_word(&topic) {
+                *self.topics.entry(topic).or_insert(0.0) += 1.0;
+            }
+        }
+        self.topics.retain(|_, weight| *weight >= TOPIC_FLOOR);
+        while self.topics.len() > MAX_TRACKED_TOPICS {
+            let Some(weakest) = self
+                .topics
+                .iter()
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(topic, _)| topic.clone())
+            else {
+                break;
+            };
+            self.topics.remove(&weakest);
+        }
+    }
+
+    /// Current signals. A remembered preference about answer length
+    /// (among `facts`) overrides the inferred verbosity.
+    pub fn signals(&self, config: &PersonalizationConfig, facts: &[MemoryFact]) -> UserSignals {
+        let mut evidence = vec![format!(
+            "expertise score {:.2} over {} turns",
+            self.expertise_score, self.turns
+        )];
+        let expertise = if self.expertise_score < 0.35 {
+            Expertise::Novice
+        } else if self.expertise_score > 0.65 {
+            Expertise::Expert
+        } else {
+            Expertise::Intermediate
+        };
+
+        let remembered = facts
+            .iter()
+            .filter(|fact| fact.kind == FactKind::Preference)
+            .filter_map(|fact| Some((fact, stated_verbosity(&fact.text)?)))
+            .max_by_key(|(fact, _)| fact.updated_at);
+        let verbosity = match remembered {
+            Some((fact, verbosity)) => {
+                evidence.push(format!(
+                    "verbosity from remembered preference #{}: {}",
+                    fact.id, fact.text
+                ));
+                verbosity
+            }
+            None => {
+                evidence.push(format!(
+                    "verbosity score {:.2} from {} requests",
+                    self.verbosity_score, self.verbosity_requests
+                ));
+                if self.verbosity_score < -0.3 {
+                    Verbosity::Brief
+                } else if self.verbosity_score > 0.3 {
+                    Verbosity::Detailed
+                } else {
+                    Verbosity::Balanced
+                }
+            }
+        };
+
+        let mut topics: Vec<(&String, f32)> = self
+            .topics
+            .iter()
+            .filter(|(_, &weight)| weight >= TOPIC_THRESHOLD)
+            .map(|(topic, &weight)| (topic, weight))
+            .collect();
+        topics.sort_by(|a, b| b.1.total_cmp(&a.1));
+        topics.truncate(config.max_topics);
+        if !topics.is_empty() {
+            let weights: Vec<String> = topics
+                .iter()
+                .map(|(topic, weight)| format!("{} ({:.1})", topic, weight))
+                .collect();
+            evidence.push(format!("topics by recent mentions: {}", weights.join(", ")));
+        }
+
+        UserSignals {
+            active: self.turns >= config.min_turns,
+            expertise,
+            verbosity,
+            topics: topics.into_iter().map(|(topic, _)| topic.clone()).collect(),
+            observed_turns: self.turns,
+            evidence,
+        }
+    }
+}
+
+/// Expertise shown by one query, from 0.0 to 1.0
+fn expertise_score(text: &str, lower: &str) -> f32 {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return 0.5;
+    }
+    let technical = words.iter().filter(|word| is_technical(word)).count();
+    let mut score = 0.5 + (technical as f32 / words.len() as f32 * 2.0).min(0.5);
+    if NOVICE_CUES.iter().any(|cue| lower.contains(cue)) {
+        score -= 0.5;
+    }
+    score.clamp(0.0, 1.0)
+}
+
+/// Jargon, code or an identifier
+fn is_technical(word: &str) -> bool {
+    let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let camel_case = bare
+        .chars()
+        .zip(bare.chars().skip(1))
+        .any(|(a, b)| a.is_lowercase() && b.is_uppercase());
+    let letters_and_digits =
+        bare.chars().any(|c| c.is_ascii_digit()) && bare.chars().any(char::is_alphabetic);
+    JARGON.contains(&bare.to_lowercase().as_str())
+        || ["::", "()", "->", "`", "{", "_", "=", "/"]
+            .iter()
+            .any(|mark| word.contains(mark))
+        || camel_case
+        || letters_and_digits
+}
+
+/// Part of a request for a length of answer rather than a topic
+fn is_cue_word(word: &str) -> bool {
+    BRIEF_CUES
+        .iter()
+        .chain(DETAILED_CUES)
+        .any(|cue| cue.split(' ').any(|part| part == word))
+}
+
+/// Answer length a preference asks for, if any
+fn stated_verbosity(text: &str) -> Option<Verbosity> {
+    let lower = text.to_lowercase();
+    if !ANSWER_WORDS.iter().any(|word| lower.contains(word)) {
+        return None;
+    }
+    if ["short", "brief", "concise"]
+        .iter()
+        .any(|word| lower.contains(word))
+    {
+        Some(Verbosity::Brief)
+    } else if ["detail", "thorough", "long"]
+        .iter()
+        .any(|word| lower.contains(word))
+    {
+        Some(Verbosity::Detailed)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_follow_the_user_over_time() {
+        let config = PersonalizationConfig::default();
+        let mut tracker = SignalTracker::new();
+        for _ in 0..4 {
+            tracker.observe(
+                "briefly: why does tokio::spawn need an async Send future?",
+                0.3,
+            );
+        }
+        let signals = tracker.signals(&config, &[]);
+        assert!(!signals.active, "too few turns to be used");
+        assert_eq!(signals.expertise, Expertise::Expert);
+        assert_eq!(signals.verbosity, Verbosity::Brief);
+        assert_eq!(signals.topics, vec!["async", "future", "send"]);
+
+        for _ in 0..10 {
+            tracker.observe("what is a garden bed? explain it step by step", 0.3);
+        }
+        let signals = tracker.signals(&config, &[]);
+        assert!(signals.active);
+        assert_eq!(signals.expertise, Expertise::Novice);
+        assert_eq!(signals.verbosity, Verbosity::Detailed);
+        assert_eq!(signals.topics, vec!["garden"]);
+        assert_eq!(signals.to_features(), vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let Some(style) = signals.style() else {
+            panic!("an active novice should get a style section");
+        };
+        assert!(style.contains("newcomer"));
+        assert!(style.ends_with("Frequent topics: garden."));
+    }
+
+    #[test]
+    fn test_remembered_preference_overrides_inferred_verbosity() {
+        let mut tracker = SignalTracker::new();
+        tracker.observe("walk me through it in detail", 1.0);
+        let fact = MemoryFact {
+            id: 7,
+            kind: FactKind::Preference,
+            text: "prefers short answers".to_string(),
+            source_turn: None,
+            created_at: 0,
+            updated_at: 0,
+        };
+        let config = PersonalizationConfig::default();
+        assert_eq!(tracker.signals(&config, &[]).verbosity, Verbosity::Detailed);
+        let signals = tracker.signals(&config, &[fact]);
+        assert_eq!(signals.verbosity, Verbosity::Brief);
+        assert!(signals
+            .evidence
+            .contains(&"verbosity from remembered preference #7: prefers short answers".into()));
+    }
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Plugin API — Stable Extension Points for Third-Party Crates
+//!
+//! Everything a plugin needs, in one module that follows semver: items
+//! here only change in a major release, while the rest of the crate is
+//! free to churn. Plugins should import from `plugin_api` only.
+//! [`RouteTarget`], [`RouteCost`] and [`RouteConstraints`] are
+//! `#[non_exhaustive]` so they can gain fields in a minor release; build
+//! them with their constructors or `Default` and set fields from there.
+//!
+//! | Extension | Trait or type | Attach with |
+//! |-----------|---------------|-------------|
+//! | Inference backend | [`TargetBackend`] | `Orchestrator::register_target` |
+//! | Text embedding | [`Embedder`] | `Orchestrator::set_embedder` |
+//! | Router features | [`FeatureExtractor`] | read with `Orchestrator::feature_extractor` |
+//! | Routing model | [`RouterModel`] | `Orchestrator::load_router_model` |
+//! | Safety/policy rule | [`QueryRule`] | `Orchestrator::add_rule` |
+//! | Response post-processor | [`PostProcessor`] | `Orchestrator::add_post_processor` |
+//! | Conversation storage | [`ConversationStore`] | `Orchestrator::add_conversation_store` |
+//!
+//! Plugin rules run after the built-in rules; post-processors run in
+//! registration order before a response is cached or stored; stores
+//! receive every turn as it completes, and the first one answers
+//! `Orchestrator::history_page` when no database is attached. A routing model is trained on the
+//! vectors of the orchestrator's [`FeatureExtractor`] and saved with
+//! [`RouterModel::trained_with`], which records the feature schema so a
+//! model for another layout is refused.
+
+#![forbid(unsafe_code)]
+
+use std::fmt;
+
+pub use crate::embedding::Embedder;
+pub use crate::features::{FeatureExtractor, RouterModel, FEATURE_SCHEMA_VERSION};
+pub use crate::mlp::MLP;
+pub use crate::policy::{RouteConstraints, RouteCost};
+pub use crate::targets::{RouteTarget, TargetBackend};
+pub use crate::types::{ConversationTurn, ModelCapability, Query, Response, RoutingDecision};
+
+/// Version of this API; bumped only for breaking changes
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// A safety or policy rule checked before a query is routed
+pub trait QueryRule: Send + Sync + fmt::Debug {
+    /// Identifier reported when the rule blocks a query
+    fn id(&self) -> &str;
+
+    /// `Some(reason)` to block `query`, `None` to let it through
+    fn check(&self, query: &Query) -> Option<String>;
+}
+
+/// Rewrites responses before they reach the user
+pub trait PostProcessor: Send + Sync + fmt::Debug {
+    /// Adjust `response` to `query` in place
+    fn process(&self, query: &Query, response: &mut Response);
+}
+
+/// Durable storage for completed turns
+pub trait ConversationStore: Send {
+    /// Store a completed turn of `project` (`None` = no project)
+    fn save_turn(&self, project: Option<&str>, turn: &ConversationTurn) -> Result<(), String>;
+
+    /// One page of `project`'s turns, oldest first; offset 0 is the most
+    /// recent page
+    fn load_history(
+        &self,
+        project: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ConversationTurn>, String>;
+}
+
+#[cfg(feature = "persistence")]
+impl ConversationStore for crate::persistence::PersistenceManager {
+    fn save_turn(&self, project: Option<&str>, turn: &ConversationTurn) -> Result<(), String> {
+        crate::persistence::PersistenceManager::save_turn(self, project, turn)
+            .map(|_| ())
+            .map_err(|e| format!("failed to save turn: {}", e))
+    }
+
+    fn load_history(
+        &self,
+        project: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ConversationTurn>, String> {
+        crate::persistence::PersistenceManager::load_history(self, project, offset, limit)
+            .map_err(|e| format!("failed to load history: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Orchestrator;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct NoSpoilers;
+
+    impl QueryRule for NoSpoilers {
+        fn id(&self) -> &str {
+            "PLUGIN_SPOILERS"
+        }
+
+        fn check(&self, query: &Query) -> Option<String> {
+            query
+                .text
+                .contains("ending")
+                .then(|| "no spoilers".to_string())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Shout;
+
+    impl PostProcessor for Shout {
+        fn process(&self, _query: &Query, response: &mut Response) {
+            response.text = response.text.to_uppercase();
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<Vec<ConversationTurn>>);
+
+    impl ConversationStore for Arc<MemoryStore> {
+        fn save_turn(&self, _project: Option<&str>, turn: &ConversationTurn) -> Result<(), String> {
+            let mut turns = self.0.lock().map_err(|e| e.to_string())?;
+            turns.push(turn.clone());
+            Ok(())
+        }
+
+        fn load_history(
+            &self,
+            _project: Option<&str>,
+            offset: usize,
+            limit: usize,
+        ) -> Result<Vec<ConversationTurn>, String> {
+            let turns = self.0.lock().map_err(|e| e.to_string())?;
+            let end = turns.len().saturating_sub(offset);
+            Ok(turns[end.saturating_sub(limit)..end].to_vec())
+        }
+    }
+
+    #[test]
+    fn test_plugins_extend_the_pipeline() {
+        let store = Arc::new(MemoryStore::default());
+        let mut orchestrator = Orchestrator::new();
+        orchestrator.add_rule(Arc::new(NoSpoilers));
+        orchestrator.add_post_processor(Arc::new(Shout));
+        orchestrator.add_conversation_store(Box::new(Arc::clone(&store)));
+
+        let Ok(blocked) = orchestrator.process(Query::new("how does the ending go")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(blocked.route, RoutingDecision::Blocked);
+        let Some(explanation) = blocked.metadata.explanation else {
+            panic!("blocked responses should be explained");
+        };
+        assert_eq!(
+            explanation.expert_rules,
+            vec!["PLUGIN_SPOILERS".to_string()]
+        );
+
+        let Ok(response) = orchestrator.process(Query::new("hello")) else {
+            panic!("process should succeed");
+        };
+        assert_eq!(response.text, response.text.to_uppercase());
+        let Ok(stored) = orchestrator.history_page(None, 0, 10) else {
+            panic!("the store should answer history_page");
+        };
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].response.text, response.text);
+    }
+
+    #[test]
+    fn test_router_model_trained_on_the_extractor_loads() {
+        let mut orchestrator = Orchestrator::new();
+        let extractor = orchestrator.feature_extractor();
+        assert_eq!(extractor.schema_version(), FEATURE_SCHEMA_VERSION);
+        let dim = extractor.dim();
+        assert_eq!(extractor.extract(&Query::new("hello")).len(), dim);
+
+        let model = RouterModel::trained_with(MLP::new_with_seed(dim, vec![8], 3, 7), extractor);
+        assert!(orchestrator.load_router_model(model).is_ok());
+
+        let mut stale = RouterModel::new(MLP::new_with_seed(dim, vec![8], 3, 7));
+        stale.feature_schema_version = FEATURE_SCHEMA_VERSION - 1;
+        assert!(orchestrator.load_router_model(stale).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Cost-, Latency- and Battery-Aware Routing Policy
+//!
+//! The router's classifier says which route is most likely to give a good
+//! answer; the policy says what each route costs right now. Each route
+//! has a [`RouteCost`] (expected latency, energy drawn on the device, and
+//! API spend), and [`RoutingPolicy`] turns those into a penalty in the
+//! same units as the classifier's probabilities:
+//!
+//! ```text
+//! utility(route) = score(route) - latency - energy - spend - metered
+//! ```
+//!
+//! Device state shifts the balance: energy counts for nothing while
+//! charging and several times over on low battery, routes that use the
+//! network pay an extra penalty on metered connections, and routes that
+//! run the on-device model pay for lost thermal headroom, and again while
+//! the user is driving, when heavy on-device work is deferred. Routes that
+//! cannot run at all (network routes while offline, the on-device model
+//! under critical memory pressure) are never chosen.
+//!
+//! What a route needs from the device is described by
+//! [`RouteConstraints`]; the built-in routes have fixed constraints, and
+//! host-registered targets (see `targets`) declare their own.
+
+#![forbid(unsafe_code)]
+
+use crate::activity::Activity;
+use crate::device::DeviceState;
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+
+/// Expected cost of one query on a route
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RouteCost {
+    /// Expected end-to-end latency (ms)
+    pub latency_ms: f32,
+    /// Energy drawn from the device battery (mWh)
+    pub energy_mwh: f32,
+    /// Monetary API cost (USD)
+    pub cost_usd: f32,
+}
+
+impl RouteCost {
+    /// Cost of `latency_ms`, `energy_mwh` of battery and `cost_usd` of API
+    /// spend per query
+    pub fn new(latency_ms: f32, energy_mwh: f32, cost_usd: f32) -> Self {
+        Self {
+            latency_ms,
+            energy_mwh,
+            cost_usd,
+        }
+    }
+}
+
+/// What a route needs from the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct RouteConstraints {
+    /// Needs a network connection (and pays the metered penalty)
+    pub uses_network: bool,
+    /// Runs a model on this device (needs memory, pays for heat)
+    pub uses_device_model: bool,
+    /// Refuses to run on a metered connection
+    pub unmetered_only: bool,
+}
+
+impl RouteConstraints {
+    /// Constraints of a built-in route
+    pub fn builtin(route: RoutingDecision) -> Self {
+        Self {
+            uses_network: matches!(route, RoutingDecision::Remote | RoutingDecision::Hybrid),
+            uses_device_model: matches!(route, RoutingDecision::Local | RoutingDecision::Hybrid),
+            unmetered_only: false,
+        }
+    }
+
+    /// Whether a route with these constraints can run on `device`
+    pub fn allows(&self, device: &DeviceState) -> bool {
+        (device.is_online() || !self.uses_network)
+            && (device.can_run_local_model() || !self.uses_device_model)
+            && !(self.unmetered_only && device.metered_network)
+    }
+}
+
+/// Cost models for every executable route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteCosts {
+    /// On-device inference: slow-ish and power hungry, but free
+    pub local: RouteCost,
+    /// Cloud inference: radio energy plus API spend
+    pub remote: RouteCost,
+    /// Local preprocessing followed by a (smaller) remote call
+    pub hybrid: RouteCost,
+}
+
+impl Default for RouteCosts {
+    fn default() -> Self {
+        Self {
+            local: RouteCost {
+                latency_ms: 1_500.0,
+                energy_mwh: 50.0,
+                cost_usd: 0.0,
+            },
+            remote: RouteCost {
+                latency_ms: 3_000.0,
+                energy_mwh: 15.0,
+                cost_usd: 0.002,
+            },
+            hybrid: RouteCost {
+                latency_ms: 4_000.0,
+                energy_mwh: 40.0,
+                cost_usd: 0.001,
+            },
+        }
+    }
+}
+
+impl RouteCosts {
+    /// Cost model for a built-in `route`; Blocked costs nothing and
+    /// custom targets carry their own
+    pub fn route(&self, route: RoutingDecision) -> Option<&RouteCost> {
+        match route {
+            RoutingDecision::Local => Some(&self.local),
+            RoutingDecision::Remote => Some(&self.remote),
+            RoutingDecision::Hybrid => Some(&self.hybrid),
+            RoutingDecision::Blocked | RoutingDecision::Custom(_) => None,
+        }
+    }
+}
+
+/// Weights converting route costs into score penalties
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingPolicy {
+    /// Penalty per second of expected latency
+    pub latency_weight: f32,
+    /// Penalty per mWh of battery energy
+    pub energy_weight: f32,
+    /// Penalty per USD of API spend
+    pub cost_weight: f32,
+    /// Extra penalty for network routes on a metered connection
+    pub metered_penalty: f32,
+    /// Battery level (%) below which energy is weighted more heavily
+    pub low_battery_percent: f32,
+    /// Energy weight multiplier on low battery
+    pub low_battery_multiplier: f32,
+    /// Penalty for on-device routes when no thermal headroom is left,
+    /// scaled linearly by the headroom used
+    pub thermal_weight: f32,
+    /// Penalty for on-device routes while the user is driving
+    pub driving_penalty: f32,
+}
+
+impl Default for RoutingPolicy {
+    fn default() -> Self {
+        Self {
+            latency_weight: 0.05,
+            energy_weight: 0.002,
+            cost_weight: 10.0,
+            metered_penalty: 0.2,
+            low_battery_percent: 20.0,
+            low_battery_multiplier: 4.0,
+            thermal_weight: 0.3,
+            driving_penalty: 0.3,
+        }
+    }
+}
+
+impl RoutingPolicy {
+    /// Score penalty for running one query on `route` given the device
+    /// state
+    pub fn penalty(&self, cost: &RouteCost, route: RoutingDecision, device: &DeviceState) -> f32 {
+        self.constrained_penalty(cost, &RouteConstraints::builtin(route), device)
+    }
+
+    /// Score penalty for running one query on a route with the given
+    /// constraints
+    pub fn constrained_penalty(
+        &self,
+        cost: &RouteCost,
+        constraints: &RouteConstraints,
+        device: &DeviceState,
+    ) -> f32 {
+        let energy_weight = if device.charging {
+            0.0
+        } else if device.battery_percent < self.low_battery_percent {
+            self.energy_weight * self.low_battery_multiplier
+        } else {
+            self.energy_weight
+        };
+        let metered = if constraints.uses_network && device.metered_network {
+            self.metered_penalty
+        } else {
+            0.0
+        };
+        let thermal = if constraints.uses_device_model {
+            self.thermal_weight * (1.0 - device.thermal_headroom.clamp(0.0, 1.0))
+        } else {
+            0.0
+        };
+        let driving = if constraints.uses_device_model && device.activity == Activity::Driving {
+            self.driving_penalty
+        } else {
+            0.0
+        };
+
+        self.latency_weight * cost.latency_ms / 1_000.0
+            + energy_weight * cost.energy_mwh
+            + self.cost_weight * cost.cost_usd
+            + metered
+            + thermal
+            + driving
+    }
+
+    /// Pick the route with the highest utility from per-route scores,
+    /// returning it with its utility
+    pub fn choose(
+        &self,
+        scores: &[(RoutingDecision, f32)],
+        costs: &RouteCosts,
+        device: &DeviceState,
+    ) -> Option<(RoutingDecision, f32)> {
+        self.choose_with(
+            scores,
+            |route| {
+                costs
+                    .route(route)
+                    .map(|cost| (*cost, RouteConstraints::builtin(route)))
+            },
+            device,
+        )
+    }
+
+    /// Like [`RoutingPolicy::choose`], with costs and constraints looked
+    /// up per route by `lookup`; routes it returns `None` for are skipped
+    pub fn choose_with(
+        &self,
+        scores: &[(RoutingDecision, f32)],
+        lookup: impl Fn(RoutingDecision) -> Option<(RouteCost, RouteConstraints)>,
+        device: &DeviceState,
+    ) -> Option<(RoutingDecision, f32)> {
+        scores
+            .iter()
+            .filter_map(|&(route, score)| {
+                let (cost, constraints) = lookup(route)?;
+                constraints.allows(device).then(|| {
+                    (route, score - self.constrained_penalty(&cost, &constraints, device))
+                })
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{MemoryPressure, NetworkType};
+
+    /// Classifier that slightly prefers Local
+    const SCORES: [(RoutingDecision, f32); 3] = [
+        (RoutingDecision::Local, 0.45),
+        (RoutingDecision::Remote, 0.35),
+        (RoutingDecision::Hybrid, 0.2),
+    ];
+
+    fn choose(device: &DeviceState) -> Option<RoutingDecision> {
+        RoutingPolicy::default()
+            .choose(&SCORES, &RouteCosts::default(), device)
+            .map(|(route, _)| route)
+    }
+
+    #[test]
+    fn test_normal_conditions_follow_classifier() {
+        assert_eq!(
+            choose(&DeviceState::default()),
+            Some(RoutingDecision::Local)
+        );
+    }
+
+    #[test]
+    fn test_low_battery_offloads_to_remote() {
+        let device = DeviceState {
+            battery_percent: 10.0,
+            ..DeviceState::default()
+        };
+        assert_eq!(choose(&device), Some(RoutingDecision::Remote));
+
+        // Energy is free while charging
+        let charging = DeviceState {
+            charging: true,
+            ..device
+        };
+        assert_eq!(choose(&charging), Some(RoutingDecision::Local));
+    }
+
+    #[test]
+    fn test_driving_offloads_on_device_inference() {
+        let driving = DeviceState {
+            activity: Activity::Driving,
+            ..DeviceState::default()
+        };
+        assert_eq!(choose(&driving), Some(RoutingDecision::Remote));
+        assert!(driving.should_throttle_compute());
+    }
+
+    #[test]
+    fn test_metered_network_penalizes_network_routes() {
+        let policy = RoutingPolicy::default();
+        let costs = RouteCosts::default();
+        let metered = DeviceState {
+            metered_network: true,
+            ..DeviceState::default()
+        };
+
+        let local = policy.penalty(&costs.local, RoutingDecision::Local, &metered);
+        let remote = policy.penalty(&costs.remote, RoutingDecision::Remote, &metered);
+        let remote_unmetered = policy.penalty(
+            &costs.remote,
+            RoutingDecision::Remote,
+            &DeviceState::default(),
+        );
+        assert_eq!(
+            local,
+            policy.penalty(
+                &costs.local,
+                RoutingDecision::Local,
+                &DeviceState::default()
+            )
+        );
+        assert!((remote - remote_unmetered - policy.metered_penalty).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_unavailable_routes_are_excluded() {
+        let offline = DeviceState {
+            network: NetworkType::Offline,
+            battery_percent: 10.0,
+            ..DeviceState::default()
+        };
+        assert_eq!(choose(&offline), Some(RoutingDecision::Local));
+
+        let critical = DeviceState {
+            memory_pressure: MemoryPressure::Critical,
+            ..DeviceState::default()
+        };
+        assert_eq!(choose(&critical), Some(RoutingDecision::Remote));
+
+        let stranded = DeviceState {
+            memory_pressure: MemoryPressure::Critical,
+            ..offline
+        };
+        assert_eq!(choose(&stranded), None);
+    }
+
+    #[test]
+    fn test_hot_device_offloads_to_remote() {
+        let hot = DeviceState {
+            thermal_headroom: 0.0,
+            ..DeviceState::default()
+        };
+        assert_eq!(choose(&hot), Some(RoutingDecision::Remote));
+    }
+
+    #[test]
+    fn test_blocked_is_never_chosen() {
+        let scores = [(RoutingDecision::Blocked, 1.0)];
+        let policy = RoutingPolicy::default();
+        assert_eq!(
+            policy.choose(&scores, &RouteCosts::default(), &DeviceState::default()),
+            None
+        );
+    }
+}
@@ -0,0 +1,446 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Warm Pool for Local Inference Resources
+//!
+//! Creating a local model context (loading weights, allocating KV caches
+//! and scratch buffers) costs far more than running a short inference.
+//! Hybrid and speculative strategies call the Local backend several times
+//! per query, so [`WarmPool`] keeps a few ready-made instances around and
+//! hands them out again instead of rebuilding them.
+//!
+//! - Released instances are [`Reusable::reset`] (buffers cleared, capacity
+//!   kept) and returned to the pool, up to `max_idle`.
+//! - The most recently released instance is handed out first, since its
+//!   memory is most likely still hot.
+//! - Instances idle for longer than `idle_timeout_ms` are evicted by
+//!   [`WarmPool::evict_idle`], down to `min_idle`.
+//!
+//! [`ModelPool`] is the pool the orchestrator keeps for an on-device
+//! [`LocalModel`]: it answers the Local route and the Local half of
+//! Hybrid routes, and a supervised task evicts its idle instances.
+
+#![forbid(unsafe_code)]
+
+use crate::clock::now_ms;
+use crate::supervisor::TaskBody;
+use crate::targets::TargetBackend;
+use crate::types::Query;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time::Duration;
+
+/// Shortest wait between idle-eviction passes
+const MIN_EVICT_INTERVAL_MS: u64 = 1_000;
+
+/// Resource that can be reset and handed out again
+pub trait Reusable {
+    /// Clear per-inference state while keeping allocations
+    fn reset(&mut self);
+}
+
+/// Pool sizing and eviction settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Maximum idle instances retained; extra releases are dropped
+    pub max_idle: usize,
+    /// Instances kept warm even when idle past the timeout
+    pub min_idle: usize,
+    /// Idle time after which an instance may be evicted (ms)
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle: 2,
+            min_idle: 1,
+            idle_timeout_ms: 60_000,
+        }
+    }
+}
+
+/// Counters describing pool behaviour
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Instances built by the factory
+    pub created: usize,
+    /// Acquisitions served from an idle instance
+    pub reused: usize,
+    /// Idle instances dropped by eviction, overflow or `clear`
+    pub evicted: usize,
+    /// Instances currently checked out
+    pub in_use: usize,
+}
+
+type Factory<T> = Box<dyn Fn() -> Result<T, String> + Send>;
+
+/// Pool of warm, reusable instances built on demand by a factory
+pub struct WarmPool<T> {
+    config: PoolConfig,
+    factory: Factory<T>,
+    /// Idle instances with the time they were released, oldest first
+    idle: Vec<(T, u64)>,
+    stats: PoolStats,
+}
+
+impl<T> fmt::Debug for WarmPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WarmPool")
+            .field("config", &self.config)
+            .field("idle", &self.idle.len())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<T: Reusable> WarmPool<T> {
+    /// Create an empty pool; nothing is built until `warm` or `acquire`
+    pub fn new<F>(config: PoolConfig, factory: F) -> Self
+    where
+        F: Fn() -> Result<T, String> + Send + 'static,
+    {
+        Self {
+            config,
+            factory: Box::new(factory),
+            idle: Vec::new(),
+            stats: PoolStats::default(),
+        }
+    }
+
+    /// Pre-build instances until `min_idle` are ready, returning how many
+    /// were created
+    pub fn warm(&mut self, now_ms: u64) -> Result<usize, String> {
+        let target = self.config.min_idle.min(self.config.max_idle);
+        let mut created = 0;
+        while self.idle.len() < target {
+            let item = (self.factory)()?;
+            self.stats.created += 1;
+            self.idle.push((item, now_ms));
+            created += 1;
+        }
+        Ok(created)
+    }
+
+    /// Take a warm instance, building a new one if none is idle
+    pub fn acquire(&mut self) -> Result<T, String> {
+        let item = match self.idle.pop() {
+            Some((item, _)) => {
+                self.stats.reused += 1;
+                item
+            }
+            None => {
+                let item = (self.factory)()?;
+                self.stats.created += 1;
+                item
+            }
+        };
+        self.stats.in_use += 1;
+        Ok(item)
+    }
+
+    /// Return an instance to the pool
+    ///
+    /// The instance is reset and kept if there is room, otherwise dropped.
+    pub fn release(&mut self, mut item: T, now_ms: u64) {
+        self.stats.in_use = self.stats.in_use.saturating_sub(1);
+        if self.idle.len() < self.config.max_idle {
+            item.reset();
+            self.idle.push((item, now_ms));
+        } else {
+            self.stats.evicted += 1;
+        }
+    }
+
+    /// Drop instances idle longer than the timeout, keeping at least
+    /// `min_idle`; returns how many were evicted
+    pub fn evict_idle(&mut self, now_ms: u64) -> usize {
+        let timeout = self.config.idle_timeout_ms;
+        let mut evicted = 0;
+        // Oldest releases sit at the front
+        while self.idle.len() > self.config.min_idle
+            && self
+                .idle
+                .first()
+                .is_some_and(|(_, at)| now_ms.saturating_sub(*at) >= timeout)
+        {
+            self.idle.remove(0);
+            evicted += 1;
+        }
+        self.stats.evicted += evicted;
+        evicted
+    }
+
+    /// Drop every idle instance (e.g. under memory pressure)
+    pub fn clear(&mut self) -> usize {
+        let n = self.idle.len();
+        self.idle.clear();
+        self.stats.evicted += n;
+        n
+    }
+
+    /// Number of idle instances ready to hand out
+    pub fn idle_count(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// Pool settings
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Usage counters
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+/// A loaded on-device model instance
+pub trait LocalModel: Reusable + Send {
+    /// Answer `query`
+    fn generate(&mut self, query: &Query) -> Result<String, String>;
+
+    /// Answer `query`, passing each decoded piece to `on_chunk`; by
+    /// default the whole answer is one piece
+    fn generate_streaming(
+        &mut self,
+        query: &Query,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> Result<String, String> {
+        let answer = self.generate(query)?;
+        on_chunk(&answer);
+        Ok(answer)
+    }
+}
+
+impl Reusable for Box<dyn LocalModel> {
+    fn reset(&mut self) {
+        (**self).reset();
+    }
+}
+
+/// Warm instances of a local model, shared by every query that runs it
+///
+/// Instances are taken from the pool for one inference and returned
+/// afterwards; the pool itself is locked only to take and return them.
+#[derive(Clone)]
+pub struct ModelPool {
+    pool: Arc<Mutex<WarmPool<Box<dyn LocalModel>>>>,
+}
+
+impl fmt::Debug for ModelPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ModelPool").field(&*self.lock()).finish()
+    }
+}
+
+impl ModelPool {
+    /// Pool of instances built by `factory`; nothing is built until
+    /// `warm` or the first query
+    pub fn new<F>(config: PoolConfig, factory: F) -> Self
+    where
+        F: Fn() -> Result<Box<dyn LocalModel>, String> + Send + 'static,
+    {
+        Self {
+            pool: Arc::new(Mutex::new(WarmPool::new(config, factory))),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WarmPool<Box<dyn LocalModel>>> {
+        self.pool.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Load instances until `min_idle` are ready, returning how many were
+    /// loaded
+    pub fn warm(&self) -> Result<usize, String> {
+        self.lock().warm(now_ms())
+    }
+
+    /// Unload instances idle longer than `idle_timeout_ms`, down to
+    /// `min_idle`; returns how many were unloaded
+    pub fn evict_idle(&self) -> usize {
+        self.lock().evict_idle(now_ms())
+    }
+
+    /// Unload every idle instance, returning how many
+    pub fn clear(&self) -> usize {
+        self.lock().clear()
+    }
+
+    /// Instances loaded and ready
+    pub fn idle_count(&self) -> usize {
+        self.lock().idle_count()
+    }
+
+    /// Pool settings
+    pub fn config(&self) -> PoolConfig {
+        self.lock().config().clone()
+    }
+
+    /// Usage counters
+    pub fn stats(&self) -> PoolStats {
+        self.lock().stats()
+    }
+
+    /// Body of a supervised task that runs `evict_idle` every half
+    /// `idle_timeout_ms` unless paused, returning once the pool is dropped
+    pub fn evictor(&self) -> TaskBody {
+        let pool: Weak<Mutex<_>> = Arc::downgrade(&self.pool);
+        let interval = (self.config().idle_timeout_ms / 2).max(MIN_EVICT_INTERVAL_MS);
+        Box::new(move |context| {
+            while !context.wait(Duration::from_millis(interval)) {
+                if context.wait_while_paused() {
+                    break;
+                }
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                ModelPool { pool }.evict_idle();
+            }
+            Ok(())
+        })
+    }
+}
+
+impl TargetBackend for ModelPool {
+    fn generate(&self, query: &Query) -> Result<String, String> {
+        self.generate_streaming(query, &mut |_| {})
+    }
+
+    fn generate_streaming(
+        &self,
+        query: &Query,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> Result<String, String> {
+        let mut model = self.lock().acquire()?;
+        let answer = model.generate_streaming(query, on_chunk);
+        self.lock().release(model, now_ms());
+        answer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct Buffers {
+        id: usize,
+        scratch: Vec<f32>,
+    }
+
+    impl Reusable for Buffers {
+        fn reset(&mut self) {
+            self.scratch.clear();
+        }
+    }
+
+    fn pool(config: PoolConfig) -> (WarmPool<Buffers>, Arc<AtomicUsize>) {
+        let built = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&built);
+        let pool = WarmPool::new(config, move || {
+            Ok(Buffers {
+                id: counter.fetch_add(1, Ordering::SeqCst),
+                scratch: Vec::with_capacity(1024),
+            })
+        });
+        (pool, built)
+    }
+
+    #[test]
+    fn test_reuses_released_instances() {
+        let (mut pool, built) = pool(PoolConfig::default());
+
+        for _ in 0..5 {
+            let Ok(mut buffers) = pool.acquire() else {
+                panic!("acquire should succeed");
+            };
+            buffers.scratch.push(1.0);
+            pool.release(buffers, 0);
+        }
+
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.stats().reused, 4);
+        assert_eq!(pool.stats().in_use, 0);
+
+        let Ok(buffers) = pool.acquire() else {
+            panic!("acquire should succeed");
+        };
+        assert_eq!(buffers.id, 0);
+        assert!(buffers.scratch.is_empty());
+        assert!(buffers.scratch.capacity() >= 1024);
+    }
+
+    #[test]
+    fn test_overflow_releases_are_dropped() {
+        let (mut pool, _) = pool(PoolConfig {
+            max_idle: 1,
+            ..PoolConfig::default()
+        });
+        let (Ok(a), Ok(b)) = (pool.acquire(), pool.acquire()) else {
+            panic!("acquire should succeed");
+        };
+        assert_eq!(pool.stats().in_use, 2);
+
+        pool.release(a, 0);
+        pool.release(b, 0);
+        assert_eq!(pool.idle_count(), 1);
+        assert_eq!(pool.stats().evicted, 1);
+    }
+
+    #[test]
+    fn test_warm_and_idle_eviction() {
+        let (mut pool, built) = pool(PoolConfig {
+            max_idle: 3,
+            min_idle: 1,
+            idle_timeout_ms: 1_000,
+        });
+        assert_eq!(pool.warm(0), Ok(1));
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        let (Ok(a), Ok(b)) = (pool.acquire(), pool.acquire()) else {
+            panic!("acquire should succeed");
+        };
+        pool.release(a, 100);
+        pool.release(b, 900);
+
+        // Only the first release has timed out
+        assert_eq!(pool.evict_idle(1_500), 1);
+        assert_eq!(pool.idle_count(), 1);
+        // min_idle keeps the last one warm
+        assert_eq!(pool.evict_idle(10_000), 0);
+        assert_eq!(pool.clear(), 1);
+    }
+
+    struct Model {
+        calls: usize,
+    }
+
+    impl Reusable for Model {
+        fn reset(&mut self) {}
+    }
+
+    impl LocalModel for Model {
+        fn generate(&mut self, query: &Query) -> Result<String, String> {
+            self.calls += 1;
+            Ok(format!("{} ({})", query.text, self.calls))
+        }
+    }
+
+    #[test]
+    fn test_model_pool_answers_on_warm_instances() {
+        let pool = ModelPool::new(PoolConfig::default(), || {
+            Ok(Box::new(Model { calls: 0 }) as Box<dyn LocalModel>)
+        });
+        assert_eq!(pool.warm(), Ok(1));
+        let backend: &dyn TargetBackend = &pool;
+        assert_eq!(backend.generate(&Query::new("a")), Ok("a (1)".to_string()));
+        assert_eq!(backend.generate(&Query::new("b")), Ok("b (2)".to_string()));
+        assert_eq!(pool.stats().created, 1);
+        assert_eq!(pool.stats().reused, 2);
+        assert_eq!(pool.clear(), 1);
+        assert_eq!(pool.idle_count(), 0);
+    }
+}
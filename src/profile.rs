@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Operating Profiles
+//!
+//! A [`Profile`] is a user-facing switch ("battery saver", "best
+//! answers", "offline") that adjusts several subsystems at once: routing
+//! policy weights and thresholds, the Local context budget, whether the
+//! reservoir tracks conversation state, and which backends may run.
+//! `Orchestrator::set_profile` applies one atomically on top of the loaded
+//! configuration, so switching back to [`Profile::Balanced`] restores it
+//! exactly.
+
+#![forbid(unsafe_code)]
+
+use crate::config::OrchestratorConfig;
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+
+/// Context budget ceiling (tokens) in battery-saver mode
+const BATTERY_SAVER_MAX_TOKENS: usize = 1_024;
+
+/// High-level operating mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Profile {
+    /// Minimize on-device energy: heavier energy weighting, smaller
+    /// context, no reservoir updates
+    BatterySaver,
+    /// The configuration as loaded
+    #[default]
+    Balanced,
+    /// Favour answer quality: escalate sooner, care less about spend
+    QualityFirst,
+    /// Never use the network; everything runs on the device
+    OfflineOnly,
+}
+
+impl Profile {
+    /// All profiles
+    pub const ALL: [Profile; 4] = [
+        Profile::BatterySaver,
+        Profile::Balanced,
+        Profile::QualityFirst,
+        Profile::OfflineOnly,
+    ];
+
+    /// Adjust `config` for this profile
+    pub fn apply(&self, config: &mut OrchestratorConfig) {
+        let policy = &mut config.router.policy;
+        match self {
+            Profile::Balanced | Profile::OfflineOnly => {}
+            Profile::BatterySaver => {
+                policy.energy_weight *= 4.0;
+                policy.latency_weight /= 2.0;
+                policy.low_battery_percent = policy.low_battery_percent.max(50.0);
+                let budget = &mut config.context_budget;
+                budget.max_tokens = budget.max_tokens.min(BATTERY_SAVER_MAX_TOKENS);
+                budget.min_tokens = budget.min_tokens.min(budget.max_tokens);
+            }
+            Profile::QualityFirst => {
+                policy.cost_weight /= 5.0;
+                policy.latency_weight /= 2.0;
+                config.router.heuristic_threshold *= 0.6;
+            }
+        }
+    }
+
+    /// Whether the reservoir should track conversation state
+    pub fn uses_reservoir(&self) -> bool {
+        !matches!(self, Profile::BatterySaver)
+    }
+
+    /// Whether `route` may run under this profile
+    pub fn allows(&self, route: RoutingDecision) -> bool {
+        match self {
+            Profile::OfflineOnly => route == RoutingDecision::Local,
+            _ => route != RoutingDecision::Blocked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced_keeps_config() {
+        let mut config = OrchestratorConfig::default();
+        Profile::Balanced.apply(&mut config);
+        assert_eq!(
+            config.router.policy,
+            OrchestratorConfig::default().router.policy
+        );
+        assert!(Profile::Balanced.allows(RoutingDecision::Remote));
+    }
+
+    #[test]
+    fn test_battery_saver_shrinks_budget() {
+        let mut config = OrchestratorConfig::default();
+        Profile::BatterySaver.apply(&mut config);
+        assert_eq!(config.context_budget.max_tokens, BATTERY_SAVER_MAX_TOKENS);
+        assert!(config.context_budget.min_tokens <= config.context_budget.max_tokens);
+        assert!(
+            config.router.policy.energy_weight
+                > OrchestratorConfig::default().router.policy.energy_weight
+        );
+        assert!(!Profile::BatterySaver.uses_reservoir());
+    }
+
+    #[test]
+    fn test_offline_only_allows_local() {
+        assert!(Profile::OfflineOnly.allows(RoutingDecision::Local));
+        assert!(!Profile::OfflineOnly.allows(RoutingDecision::Remote));
+        assert!(!Profile::OfflineOnly.allows(RoutingDecision::Hybrid));
+    }
+}
@@ -0,0 +1,342 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Prompt Assembly
+//!
+//! Backends receive more than the bare query: [`PromptBuilder`] assembles
+//! the system prompt, the active project and its summary, extra sections
+//! (such as remembered user facts), earlier turns related to the query and
+//! the most recent turns from a [`ContextSnapshot`], and finally the query.
+//!
+//! Everything but the query competes for a [`TokenBudget`], in this order
+//! of priority: system prompt, project, extra sections, recent turns (newest
+//! first), related turns. A section that does not fit is shortened to the
+//! tokens left; turns that do not fit are left out. The query is always
+//! included in full (queries too long for any window are chunked before
+//! they get here).
+
+#![forbid(unsafe_code)]
+
+use crate::ambient::AmbientState;
+use crate::tokens::{Keep, TokenBudget};
+use crate::types::{ContextSnapshot, ConversationTurn};
+use serde::{Deserialize, Serialize};
+
+/// Label of the block of recent turns
+const RECENT_LABEL: &str = "Recent conversation:";
+
+/// Label of the block of related earlier turns
+const RELATED_LABEL: &str = "Related earlier conversation:";
+
+/// Prompt assembly settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Send assembled prompts; when off, backends receive the bare query
+    pub enabled: bool,
+    /// Instructions placed at the top of every prompt
+    pub system_prompt: String,
+    /// Most tokens per prompt; prompts are also held to the backend's
+    /// context window and Local prompts to the adaptive context budget
+    pub max_tokens: usize,
+    /// Most recent turns included
+    pub recent_turns: usize,
+    /// Most earlier, semantically related turns included
+    pub related_turns: usize,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            system_prompt: "You are a concise assistant running on the user's phone.".to_string(),
+            max_tokens: 2_048,
+            recent_turns: 4,
+            related_turns: 2,
+        }
+    }
+}
+
+/// An assembled prompt
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prompt {
+    /// Text sent to the backend
+    pub text: String,
+    /// Tokens of `text`, as counted by the budget's tokenizer
+    pub tokens: usize,
+    /// Recent turns included
+    pub recent_turns: usize,
+    /// Related turns included
+    pub related_turns: usize,
+    /// Parts (sections or turns) left out for lack of budget
+    pub dropped: usize,
+    /// Sections shortened to fit the budget
+    pub truncated: usize,
+}
+
+/// Builds the text sent to a backend for one query
+#[derive(Debug, Clone)]
+pub struct PromptBuilder {
+    budget: TokenBudget,
+    system: Option<String>,
+    project: Option<String>,
+    project_summary: Option<String>,
+    ambient: AmbientState,
+    sections: Vec<(String, String)>,
+    recent: Vec<ConversationTurn>,
+    related: Vec<ConversationTurn>,
+}
+
+impl PromptBuilder {
+    /// A builder whose parts share `budget`
+    pub fn new(budget: TokenBudget) -> Self {
+        Self {
+            budget,
+            system: None,
+            project: None,
+            project_summary: None,
+            ambient: AmbientState::default(),
+            sections: Vec::new(),
+            recent: Vec::new(),
+            related: Vec::new(),
+        }
+    }
+
+    /// Instructions placed at the top
+    pub fn system(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        self.system = (!text.trim().is_empty()).then_some(text);
+        self
+    }
+
+    /// Short description of the active project
+    pub fn project_summary(mut self, summary: impl Into<String>) -> Self {
+        self.project_summary = Some(summary.into());
+        self
+    }
+
+    /// Extra titled section, e.g. remembered facts about the user
+    pub fn section(mut self, title: impl Into<String>, body: impl Into<String>) -> Self {
+        self.sections.push((title.into(), body.into()));
+        self
+    }
+
+    /// Project, situation, recent and related turns of `snapshot`
+    pub fn snapshot(mut self, snapshot: &ContextSnapshot) -> Self {
+        self.project = snapshot.project.clone();
+        self.ambient = snapshot.ambient;
+        self.recent = snapshot.history.clone();
+        self.related = snapshot.related.clone();
+        self
+    }
+
+    /// The prompt for `query`. Separators and block labels are charged
+    /// to the budget too, so the whole text fits it.
+    pub fn build(&self, query: &str) -> Prompt {
+        let tail = format!("User: {}\nAssistant:", query);
+        let mut budget = self.budget.clone();
+        budget.reserve(&tail);
+        let mut prompt = Prompt::default();
+        // A part is charged with the separator that follows it
+        let take = |budget: &mut TokenBudget, text: String, prompt: &mut Prompt| {
+            if budget.take(&format!("{}\n\n", text)) {
+                Some(text)
+            } else {
+                prompt.dropped += 1;
+                None
+            }
+        };
+
+        let mut header = self.system.clone().unwrap_or_default();
+        if self.ambient != AmbientState::Unknown {
+            header.push_str(&format!(
+                "\nThe user's phone is currently: {:?}.",
+                self.ambient
+            ));
+        }
+        let header = (!header.trim().is_empty())
+            .then(|| take(&mut budget, header.trim().to_string(), &mut prompt))
+            .flatten();
+        let project = self.project.as_ref().and_then(|name| {
+            let mut text = format!("Project: {}", name);
+            if let Some(summary) = &self.project_summary {
+                text.push_str(&format!("\n{}", summary));
+            }
+            take(&mut budget, text, &mut prompt)
+        });
+        let mut sections = Vec::new();
+        for (title, body) in &self.sections {
+            let text = format!("{}:\n{}", title, body);
+            // Shortened rather than dropped when more than its title fits
+            let mut trial = budget.clone();
+            trial.reserve("\n\n");
+            match trial.truncate(&text, Keep::Head) {
+                Some(short) if short == text => {
+                    budget = trial;
+                    sections.push(text);
+                }
+                Some(short) if short.len() > title.len() + 2 => {
+                    budget = trial;
+                    prompt.truncated += 1;
+                    sections.push(short);
+                }
+                _ => prompt.dropped += 1,
+            }
+        }
+        // Newest first while budgeting, oldest first in the prompt
+        let mut recent: Vec<String> = Vec::new();
+        for (i, turn) in self.recent.iter().enumerate() {
+            let text = render_turn(turn);
+            let label = if recent.is_empty() { RECENT_LABEL } else { "" };
+            if budget.take(&format!("{}\n{}\n", label, text)) {
+                recent.insert(0, text);
+            } else {
+                // Older turns are not tried: no gaps in the conversation
+                prompt.dropped += self.recent.len() - i;
+                break;
+            }
+        }
+        let mut related: Vec<String> = Vec::new();
+        for turn in &self.related {
+            let text = render_turn(turn);
+            let label = if related.is_empty() {
+                RELATED_LABEL
+            } else {
+                ""
+            };
+            if budget.take(&format!("{}\n{}\n", label, text)) {
+                related.push(text);
+            } else {
+                prompt.dropped += 1;
+            }
+        }
+
+        let mut parts: Vec<String> = header.into_iter().chain(project).chain(sections).collect();
+        if !related.is_empty() {
+            parts.push(format!("{}\n{}", RELATED_LABEL, related.join("\n")));
+        }
+        if !recent.is_empty() {
+            parts.push(format!("{}\n{}", RECENT_LABEL, recent.join("\n")));
+        }
+        parts.push(tail);
+
+        prompt.recent_turns = recent.len();
+        prompt.related_turns = related.len();
+        prompt.text = parts.join("\n\n");
+        prompt.tokens = self.budget.count(&prompt.text);
+        prompt
+    }
+}
+
+fn render_turn(turn: &ConversationTurn) -> String {
+    format!(
+        "User: {}\nAssistant: {}",
+        turn.query.text.trim(),
+        turn.response.text.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Query, Response, ResponseMetadata, RoutingDecision};
+
+    fn turn(query: &str, answer: &str) -> ConversationTurn {
+        ConversationTurn {
+            query: Query::new(query),
+            response: Response {
+                text: answer.to_string(),
+                route: RoutingDecision::Local,
+                confidence: 1.0,
+                latency_ms: 10,
+                metadata: ResponseMetadata {
+                    model: None,
+                    tokens: None,
+                    cached: false,
+                    context_budget: None,
+                    turn_id: None,
+                    explanation: None,
+                    state_fingerprint: None,
+                    degraded: None,
+                    energy_joules: None,
+                },
+            },
+        }
+    }
+
+    fn snapshot() -> ContextSnapshot {
+        ContextSnapshot {
+            project: Some("garden".to_string()),
+            // Most recent first
+            history: vec![
+                turn("when should I water them?", "Early in the morning."),
+                turn("which tomatoes grow well in pots?", "Cherry varieties."),
+            ],
+            related: vec![turn("soil for tomatoes?", "Loose, well-drained soil.")],
+            reservoir_state: None,
+            ambient: AmbientState::OnDesk,
+        }
+    }
+
+    #[test]
+    fn test_prompt_orders_parts_and_ends_with_query() {
+        let prompt = PromptBuilder::new(TokenBudget::estimated(1_000))
+            .system("Be brief.")
+            .project_summary("Balcony vegetable garden")
+            .section("About the user", "- prefers metric units")
+            .snapshot(&snapshot())
+            .build("how much water per pot?");
+
+        let order = [
+            "Be brief.",
+            "The user's phone is currently: OnDesk.",
+            "Project: garden\nBalcony vegetable garden",
+            "About the user:\n- prefers metric units",
+            "Related earlier conversation:\nUser: soil for tomatoes?",
+            "Recent conversation:\nUser: which tomatoes grow well in pots?",
+            "User: when should I water them?",
+            "User: how much water per pot?\nAssistant:",
+        ];
+        let mut last = 0;
+        for part in order {
+            let Some(position) = prompt.text.find(part) else {
+                panic!("prompt should contain {:?}", part);
+            };
+            assert!(position >= last, "{:?} is out of order", part);
+            last = position;
+        }
+        assert!(prompt.text.ends_with("Assistant:"));
+        assert_eq!(
+            (prompt.recent_turns, prompt.related_turns, prompt.dropped),
+            (2, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_budget_drops_oldest_context_but_never_the_query() {
+        let query = "how much water per pot?";
+        let tight = PromptBuilder::new(TokenBudget::estimated(60))
+            .system("Be brief.")
+            .snapshot(&snapshot())
+            .build(query);
+        // Header, project and the newest turn fit; older turns do not
+        assert_eq!(tight.recent_turns, 1);
+        assert_eq!(tight.related_turns, 0);
+        assert_eq!(tight.dropped, 2);
+        assert!(tight.text.contains("Early in the morning."));
+        assert!(tight.tokens <= 60);
+
+        let facts: Vec<String> = (0..40).map(|i| format!("- fact number {}", i)).collect();
+        let shortened = PromptBuilder::new(TokenBudget::estimated(60))
+            .section("About the user", facts.join("\n"))
+            .build(query);
+        assert_eq!((shortened.truncated, shortened.dropped), (1, 0));
+        assert!(shortened
+            .text
+            .starts_with("About the user:\n- fact number 0\n"));
+        assert!(shortened.tokens <= 60);
+
+        let starved = PromptBuilder::new(TokenBudget::estimated(0))
+            .snapshot(&snapshot())
+            .build(query);
+        assert_eq!(starved.text, format!("User: {}\nAssistant:", query));
+    }
+}
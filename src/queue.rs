@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Offline Query Queue
+//!
+//! On the underground or in a plane, a question that needs the remote
+//! model would otherwise get the on-device model's weaker answer, or an
+//! error. With `queue.enabled` it is kept in a [`QueryQueue`] instead and
+//! asked again when the device is back online:
+//!
+//! - `Orchestrator::process` answers such a query with
+//!   `OrchestratorError::Deferred`, carrying its queue id.
+//! - The host calls `Orchestrator::on_device_state_changed` from its
+//!   connectivity listener; once the device is online every queued query
+//!   is processed as a normal turn and its answer published as
+//!   `Event::QueuedAnswered`, which reaches `HostDelegate::on_queued_answer`.
+//! - `Orchestrator::queued_queries` and `Orchestrator::cancel_queued` let
+//!   the UI list and withdraw waiting queries.
+//!
+//! The queue is saved with the attached persistence backend, so queries
+//! survive the app being killed while offline. Queries are queued only
+//! when the profile and project policy allow the network route; otherwise
+//! they are answered locally as before.
+//!
+//! ```toml
+//! [queue]
+//! enabled = true
+//! max_queued = 50
+//! max_attempts = 3
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::types::{Query, RoutingDecision};
+use serde::{Deserialize, Serialize};
+
+/// Offline queueing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Queue network-route queries while offline instead of answering
+    /// them locally
+    pub enabled: bool,
+    /// Queries kept at most; later ones are answered locally
+    pub max_queued: usize,
+    /// Failed replays after which a query is dropped
+    pub max_attempts: u32,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_queued: 50,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// A query waiting for the network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedQuery {
+    /// Queue id, for cancellation
+    pub id: u64,
+    /// The query, as redacted by the safety rules
+    pub query: Query,
+    /// Route the router chose before the device policy saw it offline
+    pub route: RoutingDecision,
+    /// When it was queued (ms since the epoch)
+    pub queued_at_ms: u64,
+    /// Failed replays so far
+    pub attempts: u32,
+    /// Error of the latest failed replay
+    pub last_error: Option<String>,
+}
+
+/// What happened to a queued query when it was replayed
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueOutcome {
+    /// It was answered as turn `turn_id`
+    Answered {
+        /// Turn holding the answer
+        turn_id: Option<u64>,
+        /// The answer
+        text: String,
+    },
+    /// It failed; it stays queued for the next replay
+    Retrying(String),
+    /// It was refused or failed too often; it left the queue
+    Dropped(String),
+}
+
+/// One queued query handled by `Orchestrator::on_device_state_changed`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueRun {
+    /// Queue id of the query
+    pub queue_id: u64,
+    /// What happened
+    pub outcome: QueueOutcome,
+}
+
+/// Queries waiting for the network, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryQueue {
+    queued: Vec<QueuedQuery>,
+    next_id: u64,
+}
+
+impl QueryQueue {
+    /// An empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `query` for `route`; `None` when `max_queued` are waiting
+    pub fn push(
+        &mut self,
+        query: Query,
+        route: RoutingDecision,
+        max_queued: usize,
+        now_ms: u64,
+    ) -> Option<&QueuedQuery> {
+        if self.queued.len() >= max_queued {
+            return None;
+        }
+        self.queued.push(QueuedQuery {
+            id: self.next_id,
+            query,
+            route,
+            queued_at_ms: now_ms,
+            attempts: 0,
+            last_error: None,
+        });
+        self.next_id += 1;
+        self.queued.last()
+    }
+
+    /// Waiting queries, oldest first
+    pub fn queued(&self) -> &[QueuedQuery] {
+        &self.queued
+    }
+
+    /// Remove query `id`, returning it if it was waiting
+    pub fn cancel(&mut self, id: u64) -> Option<QueuedQuery> {
+        let index = self.queued.iter().position(|queued| queued.id == id)?;
+        Some(self.queued.remove(index))
+    }
+
+    /// Record a failed replay of query `id`; returns its attempts so far
+    pub fn record_failure(&mut self, id: u64, error: String) -> Option<u32> {
+        let queued = self.queued.iter_mut().find(|queued| queued.id == id)?;
+        queued.attempts += 1;
+        queued.last_error = Some(error);
+        Some(queued.attempts)
+    }
+
+    /// Number of waiting queries
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Whether no query is waiting
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_keeps_order_and_cancels() {
+        let mut queue = QueryQueue::new();
+        for text in ["first", "second", "third"] {
+            let pushed = queue.push(Query::new(text), RoutingDecision::Remote, 3, 1_000);
+            assert!(pushed.is_some());
+        }
+        assert!(queue
+            .push(Query::new("fourth"), RoutingDecision::Remote, 3, 1_000)
+            .is_none());
+
+        let Some(cancelled) = queue.cancel(1) else {
+            panic!("query 1 should be waiting");
+        };
+        assert_eq!(cancelled.query.text, "second");
+        assert!(queue.cancel(1).is_none());
+        let texts: Vec<&str> = queue
+            .queued()
+            .iter()
+            .map(|q| q.query.text.as_str())
+            .collect();
+        assert_eq!(texts, ["first", "third"]);
+
+        assert_eq!(queue.record_failure(2, "timed out".to_string()), Some(1));
+        assert_eq!(queue.queued()[1].last_error.as_deref(), Some("timed out"));
+        assert_eq!(queue.record_failure(7, "gone".to_string()), None);
+    }
+}
@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Rate Limits and Quotas
+//!
+//! Content rules look at one query at a time; a runaway client is only
+//! visible across queries. [`QuotaTracker`] enforces the resource limits
+//! of `[expert.limits]`:
+//!
+//! - `queries_per_minute`: queries over the limit in any sliding minute are
+//!   blocked by rule [`RATE_LIMIT_RULE`].
+//! - `max_in_flight`: queries started while this many are still running
+//!   are blocked by rule [`CONCURRENCY_RULE`].
+//! - `daily_remote_tokens`: once the remote routes (`Remote`, `Hybrid`)
+//!   have used this many tokens in a UTC day, queries fall back to `Local`
+//!   instead of spending more of the user's API quota.
+//!
+//! ```toml
+//! [expert.limits]
+//! queries_per_minute = 30
+//! max_in_flight = 2
+//! daily_remote_tokens = 200000
+//! ```
+//!
+//! Usage is kept in [`QuotaUsage`], which the orchestrator writes to the
+//! attached persistence backend, so restarting the app does not reset the
+//! daily budget. One tracker can be shared by several orchestrators to
+//! limit them together.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Rule reported when the per-minute query limit is hit
+pub const RATE_LIMIT_RULE: &str = "RESOURCE_001";
+
+/// Rule reported when too many queries are in flight
+pub const CONCURRENCY_RULE: &str = "RESOURCE_002";
+
+/// Length of the rate-limit window
+const MINUTE_MS: u64 = 60_000;
+
+/// Length of a quota day (UTC)
+const DAY_MS: u64 = 86_400_000;
+
+/// Resource limits; every limit is off unless set
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Most queries accepted in any sliding minute
+    pub queries_per_minute: Option<u32>,
+    /// Most remote-route tokens spent per UTC day
+    pub daily_remote_tokens: Option<u64>,
+    /// Most queries processed at the same time
+    pub max_in_flight: Option<usize>,
+}
+
+impl QuotaConfig {
+    /// Whether any limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.queries_per_minute.is_some()
+            || self.daily_remote_tokens.is_some()
+            || self.max_in_flight.is_some()
+    }
+}
+
+/// Usage counted against the limits; persisted between runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Acceptance times (ms since epoch) within the last minute
+    pub recent_queries: VecDeque<u64>,
+    /// UTC day (days since epoch) `remote_tokens` were counted on
+    pub day: u64,
+    /// Remote-route tokens spent on `day`
+    pub remote_tokens: u64,
+}
+
+impl QuotaUsage {
+    /// Drop what no longer counts at `now_ms`
+    fn expire(&mut self, now_ms: u64) {
+        while let Some(&oldest) = self.recent_queries.front() {
+            if now_ms.saturating_sub(oldest) < MINUTE_MS {
+                break;
+            }
+            self.recent_queries.pop_front();
+        }
+        let today = now_ms / DAY_MS;
+        if self.day != today {
+            self.day = today;
+            self.remote_tokens = 0;
+        }
+    }
+}
+
+/// Why a query was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    /// Resource rule that refused it
+    pub rule_id: &'static str,
+    /// Explanation for the user
+    pub reason: String,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule_id, self.reason)
+    }
+}
+
+#[derive(Debug, Default)]
+struct QuotaState {
+    usage: QuotaUsage,
+    in_flight: usize,
+}
+
+/// Counts queries and remote tokens against a [`QuotaConfig`]
+#[derive(Debug)]
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    state: Mutex<QuotaState>,
+}
+
+impl QuotaTracker {
+    /// Tracker with no usage yet
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QuotaState::default()),
+        }
+    }
+
+    /// Limits enforced
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QuotaState> {
+        // Counters stay consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Accept a query starting at `now_ms`, or say which limit refuses it.
+    /// The query counts as in flight until the returned guard is dropped.
+    pub fn admit(self: &Arc<Self>, now_ms: u64) -> Result<InFlight, QuotaExceeded> {
+        let mut state = self.lock();
+        state.usage.expire(now_ms);
+        if let Some(max) = self.config.max_in_flight {
+            if state.in_flight >= max {
+                return Err(QuotaExceeded {
+                    rule_id: CONCURRENCY_RULE,
+                    reason: format!("{} queries are already in flight", state.in_flight),
+                });
+            }
+        }
+        if let Some(limit) = self.config.queries_per_minute {
+            if state.usage.recent_queries.len() >= limit as usize {
+                return Err(QuotaExceeded {
+                    rule_id: RATE_LIMIT_RULE,
+                    reason: format!("more than {} queries in a minute", limit),
+                });
+            }
+        }
+        state.usage.recent_queries.push_back(now_ms);
+        state.in_flight += 1;
+        Ok(InFlight {
+            tracker: Arc::clone(self),
+        })
+    }
+
+    /// Whether the remote routes may still spend tokens today
+    pub fn remote_allowed(&self, now_ms: u64) -> bool {
+        let mut state = self.lock();
+        state.usage.expire(now_ms);
+        self.config
+            .daily_remote_tokens
+            .into_iter()
+            .all(|budget| state.usage.remote_tokens < budget)
+    }
+
+    /// Count `tokens` spent on a remote route
+    pub fn record_remote_tokens(&self, tokens: u64, now_ms: u64) {
+        let mut state = self.lock();
+        state.usage.expire(now_ms);
+        state.usage.remote_tokens = state.usage.remote_tokens.saturating_add(tokens);
+    }
+
+    /// Queries currently in flight
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// Usage at `now_ms`, for persistence
+    pub fn usage(&self, now_ms: u64) -> QuotaUsage {
+        let mut state = self.lock();
+        state.usage.expire(now_ms);
+        state.usage.clone()
+    }
+
+    /// Replace the usage with one saved earlier
+    pub fn restore(&self, usage: QuotaUsage) {
+        self.lock().usage = usage;
+    }
+}
+
+/// A query counted as in flight until dropped
+#[derive(Debug)]
+pub struct InFlight {
+    tracker: Arc<QuotaTracker>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut state = self.tracker.lock();
+        state.in_flight = state.in_flight.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_and_concurrency_limits() {
+        let tracker = Arc::new(QuotaTracker::new(QuotaConfig {
+            queries_per_minute: Some(3),
+            max_in_flight: Some(2),
+            ..QuotaConfig::default()
+        }));
+        let Ok(first) = tracker.admit(0) else {
+            panic!("the first query should be admitted");
+        };
+        let Ok(second) = tracker.admit(1_000) else {
+            panic!("a second concurrent query should be admitted");
+        };
+        let Err(busy) = tracker.admit(2_000) else {
+            panic!("a third concurrent query should be refused");
+        };
+        assert_eq!(busy.rule_id, CONCURRENCY_RULE);
+        drop((first, second));
+        assert_eq!(tracker.in_flight(), 0);
+
+        let Ok(_third) = tracker.admit(3_000) else {
+            panic!("finished queries free their slots");
+        };
+        let Err(limited) = tracker.admit(4_000) else {
+            panic!("a fourth query within the minute should be refused");
+        };
+        assert_eq!(limited.rule_id, RATE_LIMIT_RULE);
+        // The first query leaves the window a minute after it started
+        assert!(tracker.admit(60_000).is_ok());
+    }
+
+    #[test]
+    fn test_daily_remote_token_budget_resets_each_day() {
+        let tracker = QuotaTracker::new(QuotaConfig {
+            daily_remote_tokens: Some(100),
+            ..QuotaConfig::default()
+        });
+        assert!(tracker.remote_allowed(0));
+        tracker.record_remote_tokens(60, 1_000);
+        assert!(tracker.remote_allowed(2_000));
+        tracker.record_remote_tokens(60, 3_000);
+        assert!(!tracker.remote_allowed(4_000));
+
+        let saved = tracker.usage(5_000);
+        assert_eq!(saved.remote_tokens, 120);
+        let restarted = QuotaTracker::new(tracker.config().clone());
+        restarted.restore(saved);
+        assert!(!restarted.remote_allowed(6_000));
+        assert!(restarted.remote_allowed(DAY_MS));
+    }
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Response Regeneration and Preference Data
+//!
+//! `Orchestrator::regenerate` re-runs a past query with a different route,
+//! sampling temperature or provider. The result is kept as an
+//! [`Alternative`] next to the original turn so comparison UIs can show
+//! both. When the user picks one, `Orchestrator::record_preference` turns
+//! the choice into a [`PreferenceExample`]: the preferred response and the
+//! ones it beat, which feed router training (which route should have been
+//! taken) and quality-model training (which answer was better). Each
+//! response is scored with the orchestrator's reward function, the chosen
+//! one rated good and the rest bad, so the route that should have been
+//! taken also weighs latency, spend and energy.
+
+#![forbid(unsafe_code)]
+
+use crate::training::RouterTrainingData;
+use crate::types::{Query, Response, RoutingDecision};
+use serde::{Deserialize, Serialize};
+
+/// What to change when regenerating a response
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegenerateOptions {
+    /// Route to force; `None` keeps the original route
+    pub route: Option<RoutingDecision>,
+    /// Sampling temperature for the backend
+    pub temperature: Option<f32>,
+    /// Provider or model to use instead of the default
+    pub provider: Option<String>,
+}
+
+/// A regenerated response stored alongside the original turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alternative {
+    /// Options it was generated with
+    pub options: RegenerateOptions,
+    /// The regenerated response
+    pub response: Response,
+}
+
+/// A user's choice between an original response and its alternatives
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreferenceExample {
+    /// The query all responses answer
+    pub query: Query,
+    /// Router features of the query
+    pub features: Vec<f32>,
+    /// The preferred response
+    pub chosen: Response,
+    /// The responses it was preferred over
+    pub rejected: Vec<Response>,
+    /// Reward of the chosen response followed by those of the rejected
+    /// ones, in order; empty when not scored
+    #[serde(default)]
+    pub rewards: Vec<f32>,
+}
+
+impl PreferenceExample {
+    /// Route of the best-rewarded response; the chosen one's when
+    /// unscored or tied
+    pub fn best_route(&self) -> RoutingDecision {
+        let mut best = (self.chosen.route, self.rewards.first().copied());
+        for (response, &reward) in self.rejected.iter().zip(self.rewards.iter().skip(1)) {
+            if best.1.is_some_and(|top| reward > top) {
+                best = (response.route, Some(reward));
+            }
+        }
+        best.0
+    }
+}
+
+/// Router training data from preferences: each query labelled with the
+/// route of its best-rewarded response
+pub fn router_training_data(examples: &[PreferenceExample]) -> RouterTrainingData {
+    let mut data = RouterTrainingData::new();
+    for example in examples {
+        data.add_example(example.features.clone(), example.best_route());
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(rewards: Vec<f32>) -> PreferenceExample {
+        PreferenceExample {
+            query: Query::new("q"),
+            features: vec![0.0],
+            chosen: Response::new("a", RoutingDecision::Remote, 1.0),
+            rejected: vec![Response::new("b", RoutingDecision::Local, 1.0)],
+            rewards,
+        }
+    }
+
+    #[test]
+    fn test_label_follows_reward() {
+        assert_eq!(example(Vec::new()).best_route(), RoutingDecision::Remote);
+        assert_eq!(example(vec![0.8, 0.8]).best_route(), RoutingDecision::Remote);
+        assert_eq!(example(vec![0.8, -0.2]).best_route(), RoutingDecision::Remote);
+        // Chosen but expensive enough that the rejected answer scores higher
+        assert_eq!(example(vec![-1.0, -0.2]).best_route(), RoutingDecision::Local);
+
+        let data = router_training_data(&[example(vec![-1.0, -0.2])]);
+        assert_eq!(data.labels, vec![crate::training::route_label(RoutingDecision::Local)]);
+    }
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Capability Requirements
+//!
+//! A query can declare the [`ModelCapability`]s it needs (code, vision,
+//! long context, function calling) and every backend declares what it
+//! supports. Routing only considers backends that cover the query's
+//! requirements; when none does, the caller gets a [`MissingCapabilities`]
+//! error naming the closest backend and what it lacks.
+//!
+//! Built-in backends are declared in [`BackendCapabilities`] (part of the
+//! router configuration); custom targets declare their own.
+
+#![forbid(unsafe_code)]
+
+use crate::types::{ModelCapability, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Capabilities of the built-in backends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackendCapabilities {
+    /// On-device model
+    pub local: Vec<ModelCapability>,
+    /// Cloud model
+    pub remote: Vec<ModelCapability>,
+    /// Local preprocessing plus a remote call
+    pub hybrid: Vec<ModelCapability>,
+}
+
+impl Default for BackendCapabilities {
+    fn default() -> Self {
+        Self {
+            local: vec![ModelCapability::Code],
+            remote: vec![
+                ModelCapability::Code,
+                ModelCapability::Vision,
+                ModelCapability::LongContext,
+                ModelCapability::FunctionCalling,
+            ],
+            hybrid: vec![ModelCapability::Code, ModelCapability::LongContext],
+        }
+    }
+}
+
+impl BackendCapabilities {
+    /// Capabilities of a built-in `route`; Blocked and custom targets
+    /// have none here
+    pub fn route(&self, route: RoutingDecision) -> &[ModelCapability] {
+        match route {
+            RoutingDecision::Local => &self.local,
+            RoutingDecision::Remote => &self.remote,
+            RoutingDecision::Hybrid => &self.hybrid,
+            RoutingDecision::Blocked | RoutingDecision::Custom(_) => &[],
+        }
+    }
+}
+
+/// Capabilities in `required` that `supported` lacks, in `required` order
+pub fn missing(
+    required: &[ModelCapability],
+    supported: &[ModelCapability],
+) -> Vec<ModelCapability> {
+    required
+        .iter()
+        .filter(|capability| !supported.contains(capability))
+        .copied()
+        .collect()
+}
+
+/// No backend supports everything a query requires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingCapabilities {
+    /// Backend that came closest (fewest missing capabilities)
+    pub route: RoutingDecision,
+    /// Required capabilities that backend lacks
+    pub missing: Vec<ModelCapability>,
+}
+
+impl fmt::Display for MissingCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no backend supports all required capabilities; closest ({:?}) lacks: ",
+            self.route
+        )?;
+        for (i, capability) in self.missing.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", capability)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingCapabilities {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_lists_unsupported_capabilities() {
+        let defaults = BackendCapabilities::default();
+        let required = [ModelCapability::Code, ModelCapability::Vision];
+        assert_eq!(
+            missing(&required, defaults.route(RoutingDecision::Local)),
+            vec![ModelCapability::Vision]
+        );
+        assert!(missing(&required, defaults.route(RoutingDecision::Remote)).is_empty());
+        assert_eq!(
+            missing(&required, defaults.route(RoutingDecision::Blocked)),
+            required.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_error_names_route_and_capabilities() {
+        let error = MissingCapabilities {
+            route: RoutingDecision::Remote,
+            missing: vec![ModelCapability::Vision, ModelCapability::FunctionCalling],
+        };
+        assert_eq!(
+            error.to_string(),
+            "no backend supports all required capabilities; closest (Remote) lacks: \
+             vision, function-calling"
+        );
+    }
+}
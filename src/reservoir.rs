@@ -21,10 +21,25 @@
 
 #![forbid(unsafe_code)]
 
+use crate::embedding::HashedBagOfWords;
+use crate::linalg::{ridge_regression, Matrix, RidgeConfig, RidgeReport};
+use crate::rng::SeededRng;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// On-disk format version written by [`EchoStateNetwork::save`]
+pub const ESN_FORMAT_VERSION: u32 = 1;
+
+/// Seed used for the fixed reservoir/input weights when none is given
+const DEFAULT_SEED: u64 = 42;
 
 /// Echo State Network for temporal context processing
+///
+/// The fixed reservoir and input matrices are not serialized; they are
+/// regenerated deterministically from `seed` on deserialization, so only
+/// the trained readout and the current state are stored.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "EsnFields")]
 pub struct EchoStateNetwork {
     /// Size of the reservoir (number of neurons)
     reservoir_size: usize,
@@ -34,12 +49,12 @@ pub struct EchoStateNetwork {
     output_size: usize,
     /// Reservoir weights (fixed, random, sparse)
     #[serde(skip)]
-    reservoir_weights: Vec<Vec<f32>>,
+    reservoir_weights: Matrix,
     /// Input weights (fixed, random)
     #[serde(skip)]
-    input_weights: Vec<Vec<f32>>,
+    input_weights: Matrix,
     /// Output weights (trainable)
-    output_weights: Vec<Vec<f32>>,
+    output_weights: Matrix,
     /// Current reservoir state
     state: Vec<f32>,
     /// Leak rate (0.0 - 1.0, higher = more memory)
@@ -48,6 +63,80 @@ pub struct EchoStateNetwork {
     spectral_radius: f32,
     /// Input scaling factor
     input_scaling: f32,
+    /// Seed for the fixed reservoir and input weights
+    seed: u64,
+}
+
+/// Serialized fields of an [`EchoStateNetwork`]; the fixed matrices are
+/// rebuilt from `seed` when converting back, after checking the state and
+/// readout against the stored sizes.
+#[derive(Deserialize)]
+struct EsnFields {
+    reservoir_size: usize,
+    input_size: usize,
+    output_size: usize,
+    output_weights: Matrix,
+    state: Vec<f32>,
+    leak_rate: f32,
+    spectral_radius: f32,
+    input_scaling: f32,
+    /// Absent in snapshots written before the seed was recorded
+    #[serde(default = "default_seed")]
+    seed: u64,
+}
+
+fn default_seed() -> u64 {
+    DEFAULT_SEED
+}
+
+impl TryFrom<EsnFields> for EchoStateNetwork {
+    type Error = String;
+
+    fn try_from(fields: EsnFields) -> Result<Self, String> {
+        if fields.state.len() != fields.reservoir_size {
+            return Err(format!(
+                "state has {} values, expected reservoir_size {}",
+                fields.state.len(),
+                fields.reservoir_size
+            ));
+        }
+        let readout = &fields.output_weights;
+        // An empty readout deserializes as 0×0 whatever the reservoir size
+        if readout.nrows() != fields.output_size
+            || (fields.output_size > 0 && readout.ncols() != fields.reservoir_size)
+        {
+            return Err(format!(
+                "output weights are {}×{}, expected {}×{}",
+                readout.nrows(),
+                readout.ncols(),
+                fields.output_size,
+                fields.reservoir_size
+            ));
+        }
+        let mut esn = Self {
+            reservoir_size: fields.reservoir_size,
+            input_size: fields.input_size,
+            output_size: fields.output_size,
+            reservoir_weights: Matrix::zeros(fields.reservoir_size, fields.reservoir_size),
+            input_weights: Matrix::zeros(fields.reservoir_size, fields.input_size),
+            output_weights: fields.output_weights,
+            state: fields.state,
+            leak_rate: fields.leak_rate,
+            spectral_radius: fields.spectral_radius,
+            input_scaling: fields.input_scaling,
+            seed: fields.seed,
+        };
+
+        esn.initialize_weights();
+        Ok(esn)
+    }
+}
+
+/// Versioned envelope written by [`EchoStateNetwork::save`]
+#[derive(Serialize, Deserialize)]
+struct EsnSnapshot {
+    format_version: u32,
+    network: EchoStateNetwork,
 }
 
 impl EchoStateNetwork {
@@ -74,18 +163,41 @@ pub fn new(
         output_size: usize,
         leak_rate: f32,
         spectral_radius: f32,
+    ) -> Self {
+        Self::new_with_seed(
+            input_size,
+            reservoir_size,
+            output_size,
+            leak_rate,
+            spectral_radius,
+            DEFAULT_SEED,
+        )
+    }
+
+    /// Create an Echo State Network whose fixed weights derive from `seed`
+    ///
+    /// Two networks with the same dimensions, parameters, and seed have
+    /// identical reservoir and input matrices.
+    pub fn new_with_seed(
+        input_size: usize,
+        reservoir_size: usize,
+        output_size: usize,
+        leak_rate: f32,
+        spectral_radius: f32,
+        seed: u64,
     ) -> Self {
         let mut esn = Self {
             reservoir_size,
             input_size,
             output_size,
-            reservoir_weights: vec![vec![0.0; reservoir_size]; reservoir_size],
-            input_weights: vec![vec![0.0; input_size]; reservoir_size],
-            output_weights: vec![vec![0.0; reservoir_size]; output_size],
+            reservoir_weights: Matrix::zeros(reservoir_size, reservoir_size),
+            input_weights: Matrix::zeros(reservoir_size, input_size),
+            output_weights: Matrix::zeros(output_size, reservoir_size),
             state: vec![0.0; reservoir_size],
             leak_rate,
             spectral_radius,
             input_scaling: 1.0,
+            seed,
         };
 
         esn.initialize_weights();
@@ -94,39 +206,30 @@ pub fn new(
 
     /// Initialize reservoir and input weights randomly
     fn initialize_weights(&mut self) {
-        // Simple pseudo-random initialization
-        // In production, use a proper RNG with seed for reproducibility
-        let mut seed = 42u64;
+        // Deterministic in `self.seed` so deserialized networks get
+        // identical fixed weights
+        let mut rng = SeededRng::new(self.seed);
 
-        // Initialize reservoir weights (sparse, random)
-        for i in 0..self.reservoir_size {
-            for j in 0..self.reservoir_size {
-                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-
-                // Sparse connectivity (~10%)
-                if rand < 0.1 {
-                    self.reservoir_weights[i][j] = (rand - 0.5) * 2.0;
-                }
+        // Initialize reservoir weights (sparse, random), row-major
+        for w in self.reservoir_weights.as_mut_slice() {
+            let rand = rng.next_f32();
+
+            // Sparse connectivity (~10%)
+            if rand < 0.1 {
+                *w = (rand - 0.5) * 2.0;
             }
         }
 
         // Scale reservoir weights by spectral radius
         // Simplified: just multiply by spectral_radius
         // Proper implementation would compute actual spectral radius
-        for i in 0..self.reservoir_size {
-            for j in 0..self.reservoir_size {
-                self.reservoir_weights[i][j] *= self.spectral_radius;
-            }
+        for w in self.reservoir_weights.as_mut_slice() {
+            *w *= self.spectral_radius;
         }
 
         // Initialize input weights (dense, random)
-        for i in 0..self.reservoir_size {
-            for j in 0..self.input_size {
-                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-                self.input_weights[i][j] = (rand - 0.5) * 2.0 * self.input_scaling;
-            }
+        for w in self.input_weights.as_mut_slice() {
+            *w = rng.next_symmetric(self.input_scaling);
         }
     }
 
@@ -153,20 +256,10 @@ pub fn update(&mut self, input: &[f32]) -> Vec<f32> {
         );
 
         // Compute input activation: W_in * u(t)
-        let mut input_activation = vec![0.0; self.reservoir_size];
-        for i in 0..self.reservoir_size {
-            for j in 0..self.input_size {
-                input_activation[i] += self.input_weights[i][j] * input[j];
-            }
-        }
+        let input_activation = self.input_weights.matvec(input);
 
         // Compute reservoir activation: W * x(t)
-        let mut reservoir_activation = vec![0.0; self.reservoir_size];
-        for i in 0..self.reservoir_size {
-            for j in 0..self.reservoir_size {
-                reservoir_activation[i] += self.reservoir_weights[i][j] * self.state[j];
-            }
-        }
+        let reservoir_activation = self.reservoir_weights.matvec(&self.state);
 
         // Update state: x(t+1) = (1-α)*x(t) + α*tanh(W_in*u(t) + W*x(t))
         for i in 0..self.reservoir_size {
@@ -185,13 +278,7 @@ pub fn update(&mut self, input: &[f32]) -> Vec<f32> {
     ///
     /// Output vector of size `output_size`
     pub fn output(&self) -> Vec<f32> {
-        let mut output = vec![0.0; self.output_size];
-        for i in 0..self.output_size {
-            for j in 0..self.reservoir_size {
-                output[i] += self.output_weights[i][j] * self.state[j];
-            }
-        }
-        output
+        self.output_weights.matvec(&self.state)
     }
 
     /// Train the output weights using ridge regression
@@ -202,37 +289,49 @@ pub fn output(&self) -> Vec<f32> {
     /// * `targets` - Target outputs (one per training sample)
     /// * `regularization` - Ridge regression parameter (typically 1e-6 to 1e-3)
     ///
-    /// # Panics
-    ///
-    /// Panics if `states.len() != targets.len()`
-    pub fn train(&mut self, states: &[Vec<f32>], targets: &[Vec<f32>], regularization: f32) {
-        assert_eq!(
-            states.len(),
-            targets.len(),
-            "Number of states and targets must match"
-        );
-
+    /// The solve raises `regularization` when the states are too
+    /// ill-conditioned for it; the returned report holds the value used.
+    /// Errors if the sample counts or vector sizes do not match the network.
+    pub fn train(
+        &mut self,
+        states: &[Vec<f32>],
+        targets: &[Vec<f32>],
+        regularization: f32,
+    ) -> Result<RidgeReport, String> {
+        if states.len() != targets.len() {
+            return Err(format!(
+                "{} states but {} targets",
+                states.len(),
+                targets.len()
+            ));
+        }
         if states.is_empty() {
-            return;
+            return Ok(RidgeReport::default());
         }
-
-        // Simple ridge regression: W_out = (X^T X + λI)^-1 X^T Y
-        // For production: use proper linear algebra library
-        // Here: simplified pseudo-inverse approximation
-
-        let n_samples = states.len();
-
-        // Compute W_out ≈ Y X^T (X X^T + λI)^-1
-        // Simplified: just averaging for now (proper implementation would use LAPACK)
-        for i in 0..self.output_size {
-            for j in 0..self.reservoir_size {
-                let mut sum = 0.0;
-                for k in 0..n_samples {
-                    sum += targets[k][i] * states[k][j];
-                }
-                self.output_weights[i][j] = sum / (n_samples as f32 + regularization);
-            }
+        if let Some(bad) = states.iter().position(|s| s.len() != self.reservoir_size) {
+            return Err(format!(
+                "state {} has {} values, reservoir has {} neurons",
+                bad,
+                states[bad].len(),
+                self.reservoir_size
+            ));
+        }
+        if let Some(bad) = targets.iter().position(|t| t.len() != self.output_size) {
+            return Err(format!(
+                "target {} has {} values, expected {}",
+                bad,
+                targets[bad].len(),
+                self.output_size
+            ));
         }
+
+        // W_out = Y X (XᵀX + λI)⁻¹, solved per output by conjugate gradient
+        let x = Matrix::from_fn(states.len(), self.reservoir_size, |r, c| states[r][c]);
+        let y = Matrix::from_fn(targets.len(), self.output_size, |r, c| targets[r][c]);
+        let (weights, report) =
+            ridge_regression(&x, &y, regularization, &RidgeConfig::default())?;
+        self.output_weights = weights;
+        Ok(report)
     }
 
     /// Reset reservoir state to zero
@@ -245,47 +344,77 @@ pub fn state(&self) -> &[f32] {
         &self.state
     }
 
+    /// Replace the current state, e.g. with one saved for a project
+    pub fn set_state(&mut self, state: &[f32]) -> Result<(), String> {
+        if state.len() != self.reservoir_size {
+            return Err(format!(
+                "state has {} values, reservoir has {} neurons",
+                state.len(),
+                self.reservoir_size
+            ));
+        }
+        self.state.copy_from_slice(state);
+        Ok(())
+    }
+
     /// Get reservoir size
     pub fn reservoir_size(&self) -> usize {
         self.reservoir_size
     }
-}
 
-/// Encode text into a simple vector representation
-///
-/// This is a placeholder for Phase 2. In production, use:
-/// - sentence-transformers (e.g., all-MiniLM-L6-v2)
-/// - OpenAI embeddings API
-/// - Custom fine-tuned embedding model
-///
-/// Current implementation: Bag-of-words with simple hashing
-pub fn encode_text(text: &str, dimension: usize) -> Vec<f32> {
-    let mut vector = vec![0.0; dimension];
+    /// Seed the fixed reservoir and input weights were generated from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
 
-    // Simple bag-of-words encoding
-    for word in text.split_whitespace() {
-        let hash = simple_hash(word) % dimension;
-        vector[hash] += 1.0;
+    /// Serialize to versioned JSON (readout weights, state, and seed)
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&EsnSnapshot {
+            format_version: ESN_FORMAT_VERSION,
+            network: self.clone(),
+        })
     }
 
-    // Normalize
-    let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if magnitude > 0.0 {
-        for v in &mut vector {
-            *v /= magnitude;
+    /// Deserialize from versioned JSON produced by [`Self::to_json`]
+    ///
+    /// Rejects snapshots written by a newer format version.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let snapshot: EsnSnapshot =
+            serde_json::from_str(json).map_err(|e| format!("Invalid ESN snapshot: {}", e))?;
+
+        if snapshot.format_version > ESN_FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported ESN format version {} (max supported {})",
+                snapshot.format_version, ESN_FORMAT_VERSION
+            ));
         }
+
+        Ok(snapshot.network)
     }
 
-    vector
-}
+    /// Save the network to a file so it survives app restarts
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let json = self
+            .to_json()
+            .map_err(|e| format!("Failed to serialize ESN: {}", e))?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write ESN: {}", e))
+    }
 
-/// Simple string hash function
-fn simple_hash(s: &str) -> usize {
-    let mut hash = 0usize;
-    for byte in s.bytes() {
-        hash = hash.wrapping_mul(31).wrapping_add(byte as usize);
+    /// Load a network previously written by [`Self::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read ESN: {}", e))?;
+        Self::from_json(&json)
     }
-    hash
+}
+
+/// Encode text into a simple vector representation
+///
+/// Hashed bag-of-words, kept for callers that want a plain function; use
+/// an [`Embedder`](crate::embedding::Embedder) to make the encoding
+/// swappable.
+pub fn encode_text(text: &str, dimension: usize) -> Vec<f32> {
+    HashedBagOfWords::new(dimension).encode(text)
 }
 
 #[cfg(test)]
@@ -341,10 +470,21 @@ fn test_esn_train() {
         let states = vec![vec![1.0; 50]; 10];
         let targets = vec![vec![0.5; 5]; 10];
 
-        esn.train(&states, &targets, 1e-6);
+        let Ok(report) = esn.train(&states, &targets, 1e-6) else {
+            panic!("train should succeed with matching sizes");
+        };
+        // Identical states are rank one, so λ must have been raised
+        assert!(report.lambda > 1e-6);
+
+        // Output weights should be non-zero after training, and fit the targets
+        assert!(esn.output_weights.rows().any(|row| row.iter().any(|&w| w != 0.0)));
+        let Ok(()) = esn.set_state(&states[0]) else {
+            panic!("a collected state should fit the reservoir");
+        };
+        assert!(esn.output().iter().all(|o| (o - 0.5).abs() < 0.01));
 
-        // Output weights should be non-zero after training
-        assert!(esn.output_weights.iter().any(|row| row.iter().any(|&w| w != 0.0)));
+        assert!(esn.train(&states, &targets[..3], 1e-6).is_err());
+        assert!(esn.train(&[vec![1.0; 3]], &[vec![0.5; 5]], 1e-6).is_err());
     }
 
     #[test]
@@ -389,4 +529,99 @@ fn test_esn_serialization() {
         assert_eq!(esn.reservoir_size, deserialized.reservoir_size);
         assert_eq!(esn.state, deserialized.state);
     }
+
+    #[test]
+    fn test_esn_deserialization_regenerates_weights() {
+        let mut esn = EchoStateNetwork::new(10, 50, 5, 0.7, 0.95);
+        let Ok(json) = serde_json::to_string(&esn) else {
+            panic!("to_string should succeed for serializable ESN");
+        };
+        let Ok(mut restored) = serde_json::from_str::<EchoStateNetwork>(&json) else {
+            panic!("from_str should succeed for valid JSON");
+        };
+
+        assert_eq!(esn.reservoir_weights, restored.reservoir_weights);
+        assert_eq!(esn.input_weights, restored.input_weights);
+
+        let input = vec![0.5; 10];
+        assert_eq!(esn.update(&input), restored.update(&input));
+    }
+
+    #[test]
+    fn test_esn_seeded_construction() {
+        let a = EchoStateNetwork::new_with_seed(10, 50, 5, 0.7, 0.95, 7);
+        let b = EchoStateNetwork::new_with_seed(10, 50, 5, 0.7, 0.95, 7);
+        let c = EchoStateNetwork::new_with_seed(10, 50, 5, 0.7, 0.95, 8);
+
+        assert_eq!(a.reservoir_weights, b.reservoir_weights);
+        assert_eq!(a.input_weights, b.input_weights);
+        assert_ne!(a.input_weights, c.input_weights);
+        assert_eq!(EchoStateNetwork::new(10, 50, 5, 0.7, 0.95).seed(), DEFAULT_SEED);
+    }
+
+    #[test]
+    fn test_esn_save_load_roundtrip() {
+        let mut esn = EchoStateNetwork::new(10, 50, 5, 0.7, 0.95);
+        let states = vec![vec![1.0; 50]; 4];
+        let targets = vec![vec![0.5; 5]; 4];
+        let Ok(_) = esn.train(&states, &targets, 1e-6) else {
+            panic!("train should succeed with matching sizes");
+        };
+        esn.update(&[0.3; 10]);
+
+        let path = std::env::temp_dir().join(format!("esn-roundtrip-{}.json", std::process::id()));
+        let Ok(()) = esn.save(&path) else {
+            panic!("save should succeed to temp dir");
+        };
+        let loaded = EchoStateNetwork::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let Ok(loaded) = loaded else {
+            panic!("load should succeed for a freshly saved ESN");
+        };
+
+        assert_eq!(esn.output(), loaded.output());
+        assert_eq!(esn.seed(), loaded.seed());
+    }
+
+    #[test]
+    fn test_esn_rejects_newer_format() {
+        let esn = EchoStateNetwork::new(4, 8, 2, 0.7, 0.95);
+        let Ok(json) = esn.to_json() else {
+            panic!("to_json should succeed");
+        };
+        let newer = json.replacen(
+            &format!("\"format_version\":{}", ESN_FORMAT_VERSION),
+            &format!("\"format_version\":{}", ESN_FORMAT_VERSION + 1),
+            1,
+        );
+
+        assert!(EchoStateNetwork::from_json(&newer).is_err());
+    }
+
+    #[test]
+    fn test_esn_rejects_snapshot_with_wrong_dimensions() {
+        let esn = EchoStateNetwork::new(4, 8, 2, 0.7, 0.95);
+        let Ok(snapshot) = serde_json::to_value(EsnSnapshot {
+            format_version: ESN_FORMAT_VERSION,
+            network: esn,
+        }) else {
+            panic!("snapshot should serialize");
+        };
+        let with = |field: &str, value: serde_json::Value| {
+            let mut snapshot = snapshot.clone();
+            snapshot["network"][field] = value;
+            EchoStateNetwork::from_json(&snapshot.to_string())
+        };
+        assert!(with("state", serde_json::json!(vec![0.0; 8])).is_ok());
+
+        let Err(error) = with("state", serde_json::json!(vec![0.0; 7])) else {
+            panic!("a short state should be rejected");
+        };
+        assert!(error.contains("reservoir_size 8"), "{}", error);
+        let Err(error) = with("output_weights", serde_json::json!(vec![vec![0.0; 7]; 2])) else {
+            panic!("a readout of the wrong width should be rejected");
+        };
+        assert!(error.contains("expected 2×8"), "{}", error);
+        assert!(with("output_weights", serde_json::json!(vec![vec![0.0; 8]])).is_err());
+    }
 }
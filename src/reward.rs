@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Routing Rewards
+//!
+//! Learning-based routing modes (bandits, A/B comparisons, offline RL on
+//! exported traces) all need to score how well a routed query went. A
+//! [`RewardFn`] turns a [`RouteOutcome`] into a single scalar so that
+//! every consumer optimizes the same thing.
+//!
+//! [`RewardWeights`] is the standard implementation: a weighted sum of the
+//! user's rating minus latency, spend, energy and an escalation penalty.
+//! Presets cover the common trade-offs:
+//!
+//! | Preset | Favours |
+//! |--------|---------|
+//! | [`RewardWeights::battery_saver`] | low on-device energy |
+//! | [`RewardWeights::quality_first`] | user ratings, avoiding escalation |
+//! | [`RewardWeights::cost_minimizer`] | low API spend |
+//!
+//! The orchestrator scores with the function set by
+//! `Orchestrator::set_reward_fn`: rated turns nudge the router online and
+//! recorded preferences label router training data by reward.
+
+#![forbid(unsafe_code)]
+
+use crate::types::{Response, RoutingDecision};
+use serde::{Deserialize, Serialize};
+
+/// What happened when a query was served on a route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteOutcome {
+    /// Route that served the query
+    pub route: RoutingDecision,
+    /// End-to-end latency (ms)
+    pub latency_ms: u64,
+    /// API spend (USD)
+    pub cost_usd: f64,
+    /// Battery energy used on the device (mWh)
+    pub energy_mwh: f32,
+    /// User rating (0.0 = bad, 1.0 = good), if given
+    pub rating: Option<f32>,
+    /// Whether the answer had to be escalated (retried on another route,
+    /// regenerated, or handed to a fallback)
+    pub escalated: bool,
+}
+
+impl RouteOutcome {
+    /// Outcome of `response`, which cost `cost_usd`; degraded answers
+    /// count as escalated
+    pub fn from_response(response: &Response, cost_usd: f64, rating: Option<f32>) -> Self {
+        Self {
+            route: response.route,
+            latency_ms: response.latency_ms,
+            cost_usd,
+            // 1 mWh = 3.6 J
+            energy_mwh: response.metadata.energy_joules.unwrap_or(0.0) / 3.6,
+            rating,
+            escalated: response.metadata.degraded.is_some(),
+        }
+    }
+}
+
+/// Scores a routing outcome; higher is better
+pub trait RewardFn: Send + Sync {
+    /// Reward for `outcome`
+    fn reward(&self, outcome: &RouteOutcome) -> f32;
+}
+
+/// Weighted-sum reward
+///
+/// `reward = rating_weight * rating - latency_weight * seconds
+///           - cost_weight * usd - energy_weight * mwh
+///           - escalation_penalty * escalated`
+///
+/// Unrated outcomes use `default_rating`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardWeights {
+    /// Weight on the user rating
+    pub rating_weight: f32,
+    /// Rating assumed when the user gave none
+    pub default_rating: f32,
+    /// Penalty per second of latency
+    pub latency_weight: f32,
+    /// Penalty per USD of spend
+    pub cost_weight: f32,
+    /// Penalty per mWh of battery energy
+    pub energy_weight: f32,
+    /// Penalty when the answer was escalated
+    pub escalation_penalty: f32,
+}
+
+impl Default for RewardWeights {
+    fn default() -> Self {
+        Self {
+            rating_weight: 1.0,
+            default_rating: 0.5,
+            latency_weight: 0.05,
+            cost_weight: 10.0,
+            energy_weight: 0.002,
+            escalation_penalty: 0.5,
+        }
+    }
+}
+
+impl RewardWeights {
+    /// Penalize battery energy heavily; latency matters little
+    pub fn battery_saver() -> Self {
+        Self {
+            latency_weight: 0.02,
+            energy_weight: 0.01,
+            ..Self::default()
+        }
+    }
+
+    /// Reward ratings and punish escalations; cost and energy matter
+    /// little
+    pub fn quality_first() -> Self {
+        Self {
+            rating_weight: 2.0,
+            cost_weight: 2.0,
+            energy_weight: 0.0005,
+            escalation_penalty: 1.0,
+            ..Self::default()
+        }
+    }
+
+    /// Penalize API spend heavily
+    pub fn cost_minimizer() -> Self {
+        Self {
+            cost_weight: 100.0,
+            ..Self::default()
+        }
+    }
+}
+
+impl RewardFn for RewardWeights {
+    fn reward(&self, outcome: &RouteOutcome) -> f32 {
+        let rating = outcome
+            .rating
+            .unwrap_or(self.default_rating)
+            .clamp(0.0, 1.0);
+        let escalation = if outcome.escalated {
+            self.escalation_penalty
+        } else {
+            0.0
+        };
+
+        self.rating_weight * rating
+            - self.latency_weight * outcome.latency_ms as f32 / 1_000.0
+            - self.cost_weight * outcome.cost_usd as f32
+            - self.energy_weight * outcome.energy_mwh
+            - escalation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local() -> RouteOutcome {
+        RouteOutcome {
+            route: RoutingDecision::Local,
+            latency_ms: 1_500,
+            cost_usd: 0.0,
+            energy_mwh: 50.0,
+            rating: None,
+            escalated: false,
+        }
+    }
+
+    fn remote() -> RouteOutcome {
+        RouteOutcome {
+            route: RoutingDecision::Remote,
+            latency_ms: 3_000,
+            cost_usd: 0.01,
+            energy_mwh: 15.0,
+            rating: None,
+            escalated: false,
+        }
+    }
+
+    #[test]
+    fn test_presets_rank_routes_differently() {
+        let battery = RewardWeights::battery_saver();
+        assert!(battery.reward(&remote()) > battery.reward(&local()));
+
+        let cost = RewardWeights::cost_minimizer();
+        assert!(cost.reward(&local()) > cost.reward(&remote()));
+    }
+
+    #[test]
+    fn test_rating_and_escalation() {
+        let weights = RewardWeights::quality_first();
+        let rated = RouteOutcome {
+            rating: Some(1.0),
+            ..local()
+        };
+        let escalated = RouteOutcome {
+            escalated: true,
+            ..local()
+        };
+        assert!(weights.reward(&rated) > weights.reward(&local()));
+        assert!(weights.reward(&escalated) < weights.reward(&local()));
+        assert!((weights.reward(&rated) - weights.reward(&local()) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_reward_fn_is_object_safe() {
+        let rewards: Vec<Box<dyn RewardFn>> = vec![
+            Box::new(RewardWeights::default()),
+            Box::new(RewardWeights::cost_minimizer()),
+        ];
+        assert!(rewards.iter().all(|r| r.reward(&local()).is_finite()));
+    }
+}
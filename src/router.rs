@@ -16,16 +16,65 @@
 //! - Semantic indicators (how, what, why keywords).
 //! - Structural density (length, punctuation, uppercase ratio).
 //! - Metadata (priority, timestamp, project context).
+//! - Ambient device state (one-hot, final `AmbientState::COUNT` slots).
+//!
+//! Extraction lives in [`FeatureExtractor`], versioned by
+//! `FEATURE_SCHEMA_VERSION` so models trained on another layout are
+//! rejected at load time.
+//!
+//! CUSTOM TARGETS:
+//! Host-registered targets (see `targets`) extend the route set; the MLP
+//! gains one output class per target and the policy weighs each with its
+//! own cost model and constraints.
+//!
+//! CAPABILITIES:
+//! Routes whose backend lacks a capability the query requires (see
+//! `requirements`) are never chosen.
 
-use crate::types::{Query, RoutingDecision};
+use crate::ambient::AmbientState;
+use crate::calibration::Calibrator;
+use crate::device::DeviceState;
+use crate::embedding::Embedder;
+use crate::error::OrchestratorError;
+use crate::features::{FeatureExtractor, RouterModel};
+use crate::fingerprint::StateFingerprint;
+use crate::personalization::UserSignals;
+use crate::policy::{RouteConstraints, RouteCost, RouteCosts, RoutingPolicy};
+use crate::requirements::{self, BackendCapabilities, MissingCapabilities};
+use crate::targets::{RouteTarget, TargetRegistry};
+use crate::types::{
+    FeatureContribution, ModelCapability, Query, RoutingDecision, RoutingExplanation,
+};
 use crate::mlp::MLP;
+use crate::training::{label_route, route_label};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub use crate::features::{AMBIENT_FEATURE_OFFSET, FEATURE_DIM, PERSONAL_FEATURE_OFFSET};
+
+/// Number of MLP features reported in a routing explanation.
+const EXPLAINED_FEATURES: usize = 5;
+
+/// Rule applied by the Phase 1 heuristic router.
+const HEURISTIC_DEFAULT_RULE: &str = "HEURISTIC_DEFAULT_LOCAL";
 
 /// ROUTER CONFIG: Configuration parameters for the router.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RouterConfig {
+    /// Use the MLP model when one is loaded.
     pub enable_mlp: bool,
+    /// Score above which the heuristic router escalates to Remote.
     pub heuristic_threshold: f32,
+    /// Probability subtracted from routes currently violating their SLA
+    /// (0.0 disables the penalty). Applies to MLP routing.
+    pub sla_penalty: f32,
+    /// Expected latency, energy and API cost of each route.
+    pub costs: RouteCosts,
+    /// Weights trading classifier score against route costs.
+    pub policy: RoutingPolicy,
+    /// Model capabilities of the built-in backends.
+    pub capabilities: BackendCapabilities,
 }
 
 impl Default for RouterConfig {
@@ -33,6 +82,10 @@ fn default() -> Self {
         Self {
             enable_mlp: true,
             heuristic_threshold: 0.5,
+            sla_penalty: 0.0,
+            costs: RouteCosts::default(),
+            policy: RoutingPolicy::default(),
+            capabilities: BackendCapabilities::default(),
         }
     }
 }
@@ -41,8 +94,12 @@ fn default() -> Self {
 #[derive(Debug, Clone)]
 pub struct Router {
     config: RouterConfig,
-    mlp: Option<MLP>, // The neural model (optional in Phase 1).
-    use_mlp: bool,    // Toggles between neural and heuristic modes.
+    mlp: Option<MLP>,      // The neural model (optional in Phase 1).
+    use_mlp: bool,         // Toggles between neural and heuristic modes.
+    features: FeatureExtractor, // Query + ambient state -> MLP input.
+    penalized: Vec<RoutingDecision>, // Routes violating their SLA.
+    calibrator: Calibrator, // Maps MLP probabilities to confidences.
+    targets: TargetRegistry, // Host-registered routes beyond the built-ins.
 }
 
 impl Router {
@@ -52,23 +109,262 @@ pub fn new(config: RouterConfig) -> Self {
             use_mlp: config.enable_mlp,
             config,
             mlp: None,
+            features: FeatureExtractor::new(),
+            penalized: Vec::new(),
+            calibrator: Calibrator::Identity,
+            targets: TargetRegistry::new(),
         }
     }
 
+    /// Borrow the active router configuration.
+    pub fn config(&self) -> &RouterConfig {
+        &self.config
+    }
+
+    /// Whether routing decisions come from the MLP rather than the
+    /// heuristics.
+    pub fn uses_mlp(&self) -> bool {
+        self.use_mlp && self.mlp.is_some()
+    }
+
+    /// Replace the router configuration, keeping the loaded model,
+    /// calibrator and penalized routes.
+    pub fn set_config(&mut self, config: RouterConfig) {
+        self.use_mlp = config.enable_mlp;
+        self.config = config;
+    }
+
     /// ROUTE: The primary decision function.
     /// Returns a `RoutingDecision` and a confidence score (0.0 to 1.0).
+    /// When the preferred route lacks a capability the query requires,
+    /// the best-scoring capable route is used instead.
     pub fn route(&self, query: &Query) -> (RoutingDecision, f32) {
-        if self.use_mlp && self.mlp.is_some() {
+        let (route, confidence) = if self.use_mlp && self.mlp.is_some() {
             self.route_with_mlp(query)
         } else {
             self.route_heuristic(query)
+        };
+        if self.supports(route, query) {
+            return (route, confidence);
         }
+        self.route_scores(query)
+            .into_iter()
+            .filter(|(route, _)| self.supports(*route, query))
+            .max_by(|(ra, a), (rb, b)| {
+                (a - self.sla_penalty(*ra))
+                    .partial_cmp(&(b - self.sla_penalty(*rb)))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or((route, confidence))
     }
 
     /// Route using the MLP neural model.
-    fn route_with_mlp(&self, _query: &Query) -> (RoutingDecision, f32) {
-        // Phase 2 implementation
-        (RoutingDecision::Local, 0.5)
+    fn route_with_mlp(&self, query: &Query) -> (RoutingDecision, f32) {
+        let Some(mlp) = &self.mlp else {
+            return self.route_heuristic(query);
+        };
+        let probs = MLP::softmax(&mlp.forward(&self.extract_features(query)));
+        let scores: Vec<f32> = probs
+            .iter()
+            .enumerate()
+            .map(|(label, p)| p - self.sla_penalty(label_route(label)))
+            .collect();
+        let label = MLP::argmax(&scores);
+        (label_route(label), self.calibrator.confidence(&probs, label))
+    }
+
+    /// POLICY ROUTE: Combine classifier scores with the per-route cost
+    /// models and the current device state (battery, network, thermal,
+    /// memory).
+    /// Returns the chosen route and the classifier's confidence in it.
+    pub fn route_with_policy(
+        &self,
+        query: &Query,
+        device: &DeviceState,
+    ) -> (RoutingDecision, f32) {
+        let scores = self.route_scores(query);
+        let adjusted: Vec<(RoutingDecision, f32)> = scores
+            .iter()
+            .filter(|(route, _)| self.supports(*route, query))
+            .map(|&(route, score)| (route, score - self.sla_penalty(route)))
+            .collect();
+
+        let Some((route, _)) = self.config.policy.choose_with(
+            &adjusted,
+            |route| self.route_profile(route),
+            device,
+        ) else {
+            return self.route(query);
+        };
+        let confidence = scores
+            .iter()
+            .find(|(r, _)| *r == route)
+            .map_or(0.0, |(_, score)| *score);
+        (route, confidence)
+    }
+
+    /// Classifier confidence for every executable route.
+    fn route_scores(&self, query: &Query) -> Vec<(RoutingDecision, f32)> {
+        let routes: Vec<RoutingDecision> = (0..self.route_classes()).map(label_route).collect();
+        match &self.mlp {
+            Some(mlp) if self.use_mlp => {
+                let probs = MLP::softmax(&mlp.forward(&self.extract_features(query)));
+                (0..probs.len())
+                    .map(|label| (label_route(label), self.calibrator.confidence(&probs, label)))
+                    .collect()
+            }
+            _ => {
+                // The heuristic yields one route; spread the remaining
+                // mass evenly over the others.
+                let (chosen, confidence) = self.route_heuristic(query);
+                let rest = (1.0 - confidence) / (routes.len() - 1) as f32;
+                routes
+                    .iter()
+                    .map(|&route| (route, if route == chosen { confidence } else { rest }))
+                    .collect()
+            }
+        }
+    }
+
+    /// Cost model and constraints of a built-in or registered route.
+    fn route_profile(&self, route: RoutingDecision) -> Option<(RouteCost, RouteConstraints)> {
+        match self.targets.get(route) {
+            Some(target) => Some((target.cost, target.constraints)),
+            None => self
+                .config
+                .costs
+                .route(route)
+                .map(|cost| (*cost, RouteConstraints::builtin(route))),
+        }
+    }
+
+    /// Expected cost of one query on `route`, built-in or registered.
+    pub fn route_cost(&self, route: RoutingDecision) -> Option<RouteCost> {
+        self.route_profile(route).map(|(cost, _)| cost)
+    }
+
+    /// What a built-in or registered route needs from the device;
+    /// Blocked needs nothing.
+    pub fn route_constraints(&self, route: RoutingDecision) -> RouteConstraints {
+        self.route_profile(route)
+            .map_or(RouteConstraints::builtin(route), |(_, constraints)| constraints)
+    }
+
+    /// Whether `route` can be executed: a built-in route other than
+    /// Blocked, or a registered target.
+    pub fn is_executable(&self, route: RoutingDecision) -> bool {
+        self.route_profile(route).is_some()
+    }
+
+    /// Model capabilities of a built-in or registered route.
+    pub fn capabilities(&self, route: RoutingDecision) -> &[ModelCapability] {
+        match self.targets.get(route) {
+            Some(target) => &target.capabilities,
+            None => self.config.capabilities.route(route),
+        }
+    }
+
+    /// Capabilities `query` requires that `route` lacks.
+    pub fn missing_capabilities(
+        &self,
+        route: RoutingDecision,
+        query: &Query,
+    ) -> Vec<ModelCapability> {
+        requirements::missing(&query.required_capabilities, self.capabilities(route))
+    }
+
+    /// Whether `route` offers every capability `query` requires.
+    fn supports(&self, route: RoutingDecision, query: &Query) -> bool {
+        self.missing_capabilities(route, query).is_empty()
+    }
+
+    /// CAPABILITIES: Check that some executable route covers the query's
+    /// requirements; otherwise name the closest route and what it lacks.
+    pub fn check_capabilities(&self, query: &Query) -> Result<(), MissingCapabilities> {
+        let closest = (0..self.route_classes())
+            .map(label_route)
+            .map(|route| (route, self.missing_capabilities(route, query)))
+            .min_by_key(|(_, missing)| missing.len());
+        match closest {
+            Some((route, missing)) if !missing.is_empty() => {
+                Err(MissingCapabilities { route, missing })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// TARGETS: Register a custom route target, returning the route that
+    /// selects it. Register targets before loading a routing model; the
+    /// model must be trained with the same targets.
+    pub fn register_target(
+        &mut self,
+        target: RouteTarget,
+    ) -> Result<RoutingDecision, OrchestratorError> {
+        self.targets.register(target).map_err(OrchestratorError::InvalidInput)
+    }
+
+    /// Registered custom route targets.
+    pub fn targets(&self) -> &TargetRegistry {
+        &self.targets
+    }
+
+    /// Number of routing classes: the built-in routes plus registered
+    /// targets. This is the output size a routing MLP must have.
+    pub fn route_classes(&self) -> usize {
+        self.targets.class_count()
+    }
+
+    /// Score penalty for a route currently violating its SLA.
+    fn sla_penalty(&self, route: RoutingDecision) -> f32 {
+        if self.penalized.contains(&route) {
+            self.config.sla_penalty
+        } else {
+            0.0
+        }
+    }
+
+    /// CALIBRATION: Set the calibrator applied to MLP confidences,
+    /// typically fitted on validation data after training.
+    pub fn set_calibrator(&mut self, calibrator: Calibrator) {
+        self.calibrator = calibrator;
+    }
+
+    /// SLA: Set the routes currently violating their SLA, penalized by
+    /// `sla_penalty`.
+    pub fn set_penalized_routes(&mut self, routes: Vec<RoutingDecision>) {
+        self.penalized = routes;
+    }
+
+    /// MODEL: Install a trained routing model.
+    /// It must take `FEATURE_DIM` inputs and produce `route_classes()`
+    /// classes (Local, Remote, Hybrid, then registered targets).
+    pub fn set_mlp(&mut self, mlp: MLP) -> Result<(), OrchestratorError> {
+        self.set_model(RouterModel::new(mlp))
+    }
+
+    /// MODEL: Install a saved routing model, rejecting models trained
+    /// under a different feature schema or for different targets.
+    pub fn set_model(&mut self, model: RouterModel) -> Result<(), OrchestratorError> {
+        let mlp = model
+            .into_mlp(&self.features, &self.targets.names())
+            .map_err(OrchestratorError::InvalidInput)?;
+        self.mlp = Some(mlp);
+        Ok(())
+    }
+
+    /// FINGERPRINT: Add the routing model, its calibration, the feature
+    /// schema and the registered targets to `fingerprint`.
+    pub fn fingerprint(&self, fingerprint: &mut StateFingerprint) {
+        fingerprint
+            .part("feature_schema", &self.features.schema_version())
+            .part("model", &self.mlp)
+            .part("calibrator", &self.calibrator)
+            .part("targets", &self.targets.names());
+    }
+
+    /// Borrow the routing model mutably (e.g. for online updates).
+    pub fn mlp_mut(&mut self) -> Option<&mut MLP> {
+        self.mlp.as_mut()
     }
 
     /// Route using heuristic rules.
@@ -77,10 +373,91 @@ fn route_heuristic(&self, _query: &Query) -> (RoutingDecision, f32) {
         (RoutingDecision::Local, 0.5)
     }
 
+    /// EXPLAIN: Why `query` was sent to `route`.
+    ///
+    /// MLP decisions report the features whose removal most lowers the
+    /// chosen route's probability (occlusion); heuristic decisions report
+    /// the rules that fired. SLA penalties on any route are listed as
+    /// adjustments.
+    pub fn explain(&self, query: &Query, route: RoutingDecision) -> RoutingExplanation {
+        let mut explanation = RoutingExplanation::default();
+        match &self.mlp {
+            Some(mlp) if self.use_mlp => {
+                explanation.top_features = self.top_features(mlp, query, route);
+            }
+            _ => explanation.heuristic_rules.push(HEURISTIC_DEFAULT_RULE.to_string()),
+        }
+        if self.config.sla_penalty > 0.0 {
+            for penalized in &self.penalized {
+                explanation.adjustments.push(format!(
+                    "{:?} penalized by {} for violating its SLA",
+                    penalized, self.config.sla_penalty
+                ));
+            }
+        }
+        explanation
+    }
+
+    /// Features whose occlusion most lowers `route`'s probability.
+    fn top_features(
+        &self,
+        mlp: &MLP,
+        query: &Query,
+        route: RoutingDecision,
+    ) -> Vec<FeatureContribution> {
+        let label = route_label(route);
+        let features = self.extract_features(query);
+        let base = MLP::softmax(&mlp.forward(&features))[label];
+
+        let mut contributions: Vec<FeatureContribution> = features
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value != 0.0)
+            .map(|(index, _)| {
+                let mut occluded = features.clone();
+                occluded[index] = 0.0;
+                FeatureContribution {
+                    index,
+                    name: FeatureExtractor::feature_name(index),
+                    contribution: base - MLP::softmax(&mlp.forward(&occluded))[label],
+                }
+            })
+            .collect();
+        contributions.sort_by(|a, b| {
+            b.contribution
+                .partial_cmp(&a.contribution)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        contributions.truncate(EXPLAINED_FEATURES);
+        contributions
+    }
+
+    /// Update the ambient state fed into the feature vector.
+    pub fn set_ambient(&mut self, ambient: AmbientState) {
+        self.features.set_ambient(ambient);
+    }
+
+    /// PERSONALIZATION: Update the user signals fed into the feature
+    /// vector; `None` clears them.
+    pub fn set_user_signals(&mut self, signals: Option<&UserSignals>) {
+        self.features.set_user_signals(signals);
+    }
+
+    /// EMBEDDING: Set the text embedder behind the query features. A
+    /// loaded model trained with a different embedder should be
+    /// retrained.
+    pub fn set_embedder(&mut self, embedder: Option<Arc<dyn Embedder>>) {
+        self.features.set_embedder(embedder);
+    }
+
+    /// Borrow the feature extractor feeding the MLP.
+    pub fn feature_extractor(&self) -> &FeatureExtractor {
+        &self.features
+    }
+
     /// FEATURE EXTRACTION: Normalizes a query into a fixed-width vector.
     /// Used as input for the MLP classifier.
-    pub fn extract_features(&self, _query: &Query) -> Vec<f32> {
-        // ... [Numerical encoding implementation]
-        vec![0.0; 384]
+    pub fn extract_features(&self, query: &Query) -> Vec<f32> {
+        self.features.extract(query)
     }
 }
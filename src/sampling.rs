@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Adaptive Sampling Control
+//!
+//! Closes the power-management loop between the orchestrator and the
+//! host's sensor layer. Instead of passively consuming whatever arrives,
+//! the orchestrator emits [`SamplingCommand`]s asking the host to slow
+//! down or speed up individual sensors:
+//!
+//! - Accelerometer/gyroscope drop to a low rate while the device is still
+//!   (on a desk, in the pocket of someone sitting) and return to full rate
+//!   when it moves, pocketed or not.
+//! - Audio is boosted after a wake trigger and restored afterwards.
+//!
+//! Commands are deduplicated: a command is only emitted when the requested
+//! rate for a sensor actually changes.
+
+#![forbid(unsafe_code)]
+
+use crate::ambient::AmbientState;
+use crate::sensor::SensorType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Request to the host to change a sensor's sampling rate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingCommand {
+    /// Sensor to reconfigure
+    pub sensor_type: SensorType,
+    /// Requested sampling rate in Hz
+    pub rate_hz: f32,
+    /// Short machine-readable reason (e.g. "stationary", "wake_trigger")
+    pub reason: String,
+}
+
+/// Sampling rates used by [`SamplingController`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /// Motion sensor rate while the device is still (Hz)
+    pub stationary_motion_hz: f32,
+    /// Motion sensor rate while the device is moving or handled (Hz)
+    pub active_motion_hz: f32,
+    /// Audio sample rate during normal listening (Hz)
+    pub idle_audio_hz: f32,
+    /// Audio sample rate after a wake trigger (Hz)
+    pub boosted_audio_hz: f32,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            stationary_motion_hz: 5.0,
+            active_motion_hz: 50.0,
+            idle_audio_hz: 8000.0,
+            boosted_audio_hz: 16000.0,
+        }
+    }
+}
+
+/// Derives sampling-rate commands from ambient state and wake events
+#[derive(Debug, Clone, Default)]
+pub struct SamplingController {
+    config: SamplingConfig,
+    /// Last rate requested per sensor, for deduplication
+    requested: HashMap<SensorType, f32>,
+}
+
+impl SamplingController {
+    /// Create a controller with the given rates
+    pub fn new(config: SamplingConfig) -> Self {
+        Self {
+            config,
+            requested: HashMap::new(),
+        }
+    }
+
+    /// Commands implied by a new ambient state; `still` is whether the
+    /// motion readings barely vary (see `AmbientClassifier::is_still`)
+    ///
+    /// A pocketed device is only slowed down when still: its owner may be
+    /// walking, and activity recognition needs the full rate then.
+    pub fn on_ambient(&mut self, ambient: AmbientState, still: bool) -> Vec<SamplingCommand> {
+        let (rate, reason) = match ambient {
+            AmbientState::OnDesk => (self.config.stationary_motion_hz, "stationary"),
+            AmbientState::InPocket if still => (self.config.stationary_motion_hz, "stationary"),
+            AmbientState::InPocket | AmbientState::InHand | AmbientState::Walking => {
+                (self.config.active_motion_hz, "moving")
+            }
+            AmbientState::DarkRoom | AmbientState::Unknown => return Vec::new(),
+        };
+
+        [SensorType::Accelerometer, SensorType::Gyroscope]
+            .into_iter()
+            .filter_map(|sensor| self.request(sensor, rate, reason))
+            .collect()
+    }
+
+    /// Commands to boost audio capture after a wake trigger
+    pub fn on_wake_trigger(&mut self) -> Vec<SamplingCommand> {
+        self.request(
+            SensorType::Audio,
+            self.config.boosted_audio_hz,
+            "wake_trigger",
+        )
+        .into_iter()
+        .collect()
+    }
+
+    /// Commands to restore audio capture once the wake interaction ends
+    pub fn on_wake_end(&mut self) -> Vec<SamplingCommand> {
+        self.request(SensorType::Audio, self.config.idle_audio_hz, "wake_end")
+            .into_iter()
+            .collect()
+    }
+
+    /// Last rate requested for a sensor, if any
+    pub fn requested_rate(&self, sensor_type: SensorType) -> Option<f32> {
+        self.requested.get(&sensor_type).copied()
+    }
+
+    /// Record a request, returning a command only if the rate changed
+    fn request(
+        &mut self,
+        sensor_type: SensorType,
+        rate_hz: f32,
+        reason: &str,
+    ) -> Option<SamplingCommand> {
+        if self.requested.get(&sensor_type) == Some(&rate_hz) {
+            return None;
+        }
+        self.requested.insert(sensor_type, rate_hz);
+
+        Some(SamplingCommand {
+            sensor_type,
+            rate_hz,
+            reason: reason.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stationary_lowers_motion_rate() {
+        let mut controller = SamplingController::default();
+        let commands = controller.on_ambient(AmbientState::OnDesk, true);
+
+        assert_eq!(commands.len(), 2);
+        assert!(commands
+            .iter()
+            .all(|c| c.rate_hz == 5.0 && c.reason == "stationary"));
+        assert_eq!(
+            controller.requested_rate(SensorType::Accelerometer),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn test_commands_are_deduplicated() {
+        let mut controller = SamplingController::default();
+        assert!(!controller.on_ambient(AmbientState::Walking, false).is_empty());
+        assert!(controller.on_ambient(AmbientState::InHand, false).is_empty());
+        assert!(!controller.on_ambient(AmbientState::OnDesk, true).is_empty());
+    }
+
+    #[test]
+    fn test_pocket_is_slowed_only_when_still() {
+        let mut controller = SamplingController::default();
+        controller.on_ambient(AmbientState::InPocket, true);
+        assert_eq!(controller.requested_rate(SensorType::Gyroscope), Some(5.0));
+
+        // Walking with the phone in a pocket brings the full rate back
+        let commands = controller.on_ambient(AmbientState::InPocket, false);
+        assert_eq!(commands.len(), 2);
+        assert!(commands
+            .iter()
+            .all(|c| c.rate_hz == 50.0 && c.reason == "moving"));
+    }
+
+    #[test]
+    fn test_unknown_state_leaves_rates_alone() {
+        let mut controller = SamplingController::default();
+        assert!(controller.on_ambient(AmbientState::Unknown, false).is_empty());
+        assert_eq!(controller.requested_rate(SensorType::Accelerometer), None);
+    }
+
+    #[test]
+    fn test_wake_trigger_boosts_and_restores_audio() {
+        let mut controller = SamplingController::default();
+
+        let boost = controller.on_wake_trigger();
+        assert_eq!(boost.len(), 1);
+        assert_eq!(boost[0].sensor_type, SensorType::Audio);
+        assert_eq!(boost[0].rate_hz, 16000.0);
+        assert!(controller.on_wake_trigger().is_empty());
+
+        let restore = controller.on_wake_end();
+        assert_eq!(restore[0].rate_hz, 8000.0);
+    }
+}
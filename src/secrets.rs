@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Secret Indirection for API Keys
+//!
+//! Config files never hold raw key material. Instead a key field holds a
+//! reference that is resolved when a backend makes a call:
+//!
+//! - `${OPENAI_API_KEY}` reads an environment variable;
+//! - `secret:openai` asks the host's [`SecretProvider`] (Android
+//!   Keystore, iOS Keychain, ...).
+//!
+//! Resolved keys are wrapped in [`Secret`], whose `Debug` and `Display`
+//! print `[REDACTED]`, so keys cannot leak through logs or traces by
+//! accident. [`redact`] scrubs known key material from free-form text.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Placeholder printed in place of secret material
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Prefix for references resolved through the [`SecretProvider`]
+const PROVIDER_PREFIX: &str = "secret:";
+
+/// Host-implemented secret storage
+///
+/// Queried at call time, so keys can be rotated or revoked without
+/// restarting the orchestrator.
+pub trait SecretProvider: Send + Sync {
+    /// Secret stored under `name`, if any
+    fn get_secret(&self, name: &str) -> Option<String>;
+}
+
+/// Provider backed by process environment variables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn get_secret(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// String whose contents never appear in `Debug` or `Display` output
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Wrap key material
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The raw value, for placing in a request header
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Where a secret comes from, as written in config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SecretRef {
+    /// `${NAME}`: environment variable
+    Env(String),
+    /// `secret:NAME`: host secret provider
+    Provider(String),
+}
+
+impl SecretRef {
+    /// Parse a config value; raw key material is rejected
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Some(name) = value
+            .strip_prefix("${")
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            if is_identifier(name) {
+                return Ok(SecretRef::Env(name.to_string()));
+            }
+            return Err(format!("invalid environment variable name `{}`", name));
+        }
+        if let Some(name) = value.strip_prefix(PROVIDER_PREFIX) {
+            if !name.is_empty() {
+                return Ok(SecretRef::Provider(name.to_string()));
+            }
+        }
+        Err(
+            "raw API keys must not be stored in config; use `${ENV_VAR}` or `secret:<name>`"
+                .to_string(),
+        )
+    }
+
+    /// Resolve the secret now; environment references use the process env
+    pub fn resolve(&self, provider: &dyn SecretProvider) -> Result<Secret, String> {
+        let (value, what) = match self {
+            SecretRef::Env(name) => (EnvSecretProvider.get_secret(name), "environment variable"),
+            SecretRef::Provider(name) => (provider.get_secret(name), "secret"),
+        };
+        value
+            .filter(|v| !v.is_empty())
+            .map(Secret::new)
+            .ok_or_else(|| format!("{} `{}` is not set", what, self.name()))
+    }
+
+    /// Variable or secret name
+    pub fn name(&self) -> &str {
+        match self {
+            SecretRef::Env(name) | SecretRef::Provider(name) => name,
+        }
+    }
+}
+
+impl TryFrom<String> for SecretRef {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        Self::parse(&value)
+    }
+}
+
+impl From<SecretRef> for String {
+    fn from(secret: SecretRef) -> Self {
+        match secret {
+            SecretRef::Env(name) => format!("${{{}}}", name),
+            SecretRef::Provider(name) => format!("{}{}", PROVIDER_PREFIX, name),
+        }
+    }
+}
+
+/// Replace `${NAME}` with environment variable values
+///
+/// Unset variables are an error so a missing key is caught at load time
+/// rather than sent as an empty string.
+pub fn interpolate_env(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            return Err(format!("unterminated `${{` in `{}`", text));
+        };
+        let name = &after[..end];
+        let value = std::env::var(name)
+            .map_err(|_| format!("environment variable `{}` is not set", name))?;
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Replace every occurrence of the given secrets in `text`
+pub fn redact(text: &str, secrets: &[&Secret]) -> String {
+    let mut out = text.to_string();
+    for secret in secrets {
+        if !secret.expose().is_empty() {
+            out = out.replace(secret.expose(), REDACTED);
+        }
+    }
+    out
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct MapProvider(HashMap<String, String>);
+
+    impl SecretProvider for MapProvider {
+        fn get_secret(&self, name: &str) -> Option<String> {
+            self.0.get(name).cloned()
+        }
+    }
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(
+            SecretRef::parse("${OPENAI_KEY}"),
+            Ok(SecretRef::Env("OPENAI_KEY".to_string()))
+        );
+        assert_eq!(
+            SecretRef::parse("secret:openai"),
+            Ok(SecretRef::Provider("openai".to_string()))
+        );
+        assert!(SecretRef::parse("sk-live-1234").is_err());
+        assert!(SecretRef::parse("${BAD NAME}").is_err());
+    }
+
+    #[test]
+    fn test_resolve_through_provider() {
+        let provider = MapProvider(HashMap::from([(
+            "openai".to_string(),
+            "sk-test".to_string(),
+        )]));
+
+        let Ok(secret) = SecretRef::Provider("openai".to_string()).resolve(&provider) else {
+            panic!("known secret should resolve");
+        };
+        assert_eq!(secret.expose(), "sk-test");
+        assert!(SecretRef::Provider("missing".to_string())
+            .resolve(&provider)
+            .is_err());
+    }
+
+    #[test]
+    fn test_secret_is_redacted_in_output() {
+        let secret = Secret::new("sk-very-secret");
+        assert_eq!(format!("{}", secret), REDACTED);
+        assert!(!format!("{:?}", secret).contains("sk-very-secret"));
+        assert_eq!(
+            redact("Authorization: Bearer sk-very-secret", &[&secret]),
+            "Authorization: Bearer [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_env() {
+        let Ok(path) = std::env::var("PATH") else {
+            panic!("PATH should be set in the test environment");
+        };
+        assert_eq!(interpolate_env("p=${PATH};"), Ok(format!("p={};", path)));
+        assert!(interpolate_env("${MOBILE_AI_SURELY_UNSET_VAR}").is_err());
+        assert!(interpolate_env("${UNTERMINATED").is_err());
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let Ok(json) = serde_json::to_string(&SecretRef::Env("KEY".to_string())) else {
+            panic!("to_string should succeed");
+        };
+        assert_eq!(json, "\"${KEY}\"");
+        assert!(serde_json::from_str::<SecretRef>("\"raw-key\"").is_err());
+    }
+}
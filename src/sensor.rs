@@ -27,6 +27,7 @@
 #![forbid(unsafe_code)]
 
 use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
 /// Sensor types supported by the orchestrator
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -49,6 +50,9 @@ pub enum SensorType {
     Audio,
     /// Touch coordinates (x, y normalized 0-1)
     Touch,
+    /// Device orientation (roll, pitch, yaw in radians), derived by
+    /// `fusion::OrientationFilter` rather than read from hardware
+    Orientation,
     /// Custom/user-defined sensor
     Custom(u8),
 }
@@ -66,6 +70,7 @@ pub const fn dimensions(&self) -> usize {
             SensorType::Gps => 3,
             SensorType::Audio => 1,
             SensorType::Touch => 2,
+            SensorType::Orientation => 3,
             SensorType::Custom(_) => 1,
         }
     }
@@ -82,6 +87,7 @@ pub const fn name(&self) -> &'static str {
             SensorType::Gps => "gps",
             SensorType::Audio => "audio",
             SensorType::Touch => "touch",
+            SensorType::Orientation => "orientation",
             SensorType::Custom(_) => "custom",
         }
     }
@@ -159,6 +165,7 @@ pub fn to_features(&self) -> Vec<f32> {
             SensorType::Gps => 180.0,           // lat/lon degrees
             SensorType::Audio => 1.0,           // assume pre-normalized
             SensorType::Touch => 1.0,           // already 0-1
+            SensorType::Orientation => PI,      // roll/pitch/yaw radians
             SensorType::Custom(_) => 1.0,       // assume pre-normalized
         };
 
@@ -176,9 +183,16 @@ pub fn magnitude(&self) -> f32 {
 }
 
 /// Buffer for collecting sensor readings over time
+///
+/// A ring buffer that keeps its readings contiguous: evicting the oldest
+/// reading only advances a start offset, and the evicted prefix is
+/// dropped in one go once it reaches `max_size`. Pushing is therefore
+/// amortized O(1) while [`SensorBuffer::readings`] still returns a slice.
 #[derive(Debug, Clone)]
 pub struct SensorBuffer {
+    /// Storage; the live readings are `readings[start..]`
     readings: Vec<SensorReading>,
+    start: usize,
     max_size: usize,
 }
 
@@ -187,64 +201,260 @@ impl SensorBuffer {
     pub fn new(max_size: usize) -> Self {
         Self {
             readings: Vec::with_capacity(max_size),
+            start: 0,
             max_size,
         }
     }
 
     /// Add a reading (drops oldest if full)
     pub fn push(&mut self, reading: SensorReading) {
-        if self.readings.len() >= self.max_size {
-            self.readings.remove(0);
+        if self.max_size == 0 {
+            return;
+        }
+        if self.len() >= self.max_size {
+            self.start += 1;
         }
         self.readings.push(reading);
+        self.compact();
+    }
+
+    /// Add several readings at once, dropping the oldest beyond capacity
+    pub fn extend(&mut self, readings: impl IntoIterator<Item = SensorReading>) {
+        for reading in readings {
+            self.push(reading);
+        }
+    }
+
+    /// Drop the evicted prefix once it is as long as the live readings can
+    /// be, so each reading is moved at most once
+    fn compact(&mut self) {
+        if self.start >= self.max_size {
+            self.readings.drain(..self.start);
+            self.start = 0;
+        }
     }
 
     /// Get all readings
     pub fn readings(&self) -> &[SensorReading] {
-        &self.readings
+        &self.readings[self.start..]
     }
 
     /// Get readings of a specific type
     pub fn readings_of_type(&self, sensor_type: SensorType) -> Vec<&SensorReading> {
-        self.readings
+        self.readings()
             .iter()
             .filter(|r| r.sensor_type == sensor_type)
             .collect()
     }
 
+    /// Maximum number of readings held before the oldest are dropped
+    pub fn capacity(&self) -> usize {
+        self.max_size
+    }
+
+    /// Whether the next push will drop the oldest reading
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.max_size
+    }
+
+    /// Oldest and newest timestamps in the buffer, if it holds any readings
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        let mut timestamps = self.readings().iter().map(|r| r.timestamp_ms);
+        let first = timestamps.next()?;
+        Some(timestamps.fold((first, first), |(min, max), t| (min.min(t), max.max(t))))
+    }
+
+    /// Readings with `start_ms <= timestamp_ms < end_ms`, in buffer order
+    pub fn readings_between(&self, start_ms: u64, end_ms: u64) -> Vec<&SensorReading> {
+        self.readings()
+            .iter()
+            .filter(|r| (start_ms..end_ms).contains(&r.timestamp_ms))
+            .collect()
+    }
+
+    /// Readings from the last `duration_ms` before the newest timestamp,
+    /// inclusive of both ends
+    pub fn latest_span(&self, duration_ms: u64) -> Vec<&SensorReading> {
+        let Some((_, newest)) = self.time_range() else {
+            return Vec::new();
+        };
+        self.readings_between(newest.saturating_sub(duration_ms), newest.saturating_add(1))
+    }
+
     /// Convert buffer to feature matrix (flattened)
     ///
-    /// Returns a flat vector suitable for reservoir/SNN input
+    /// Returns a flat vector suitable for reservoir/SNN input. Its length
+    /// follows the number of readings; use [`SensorBuffer::windows`] and
+    /// [`SensorWindow::feature_vector`] for fixed-size input.
     pub fn to_feature_vector(&self) -> Vec<f32> {
-        self.readings
+        self.readings()
             .iter()
             .flat_map(|r| r.to_features())
             .collect()
     }
 
+    /// Split the buffered time span into windows of `duration_ms`, one
+    /// starting every `stride_ms` from the oldest reading, oldest first
+    ///
+    /// Only complete windows are returned: the last one ends at or before
+    /// the newest reading. Windows overlap when the stride is shorter than
+    /// the duration. A zero duration yields no windows; a zero stride is
+    /// treated as equal to the duration.
+    pub fn windows(&self, duration_ms: u64, stride_ms: u64) -> Vec<SensorWindow<'_>> {
+        let mut sorted: Vec<&SensorReading> = self.readings().iter().collect();
+        sorted.sort_by_key(|r| r.timestamp_ms);
+        let (Some(first), Some(last)) = (sorted.first(), sorted.last()) else {
+            return Vec::new();
+        };
+        if duration_ms == 0 {
+            return Vec::new();
+        }
+        let stride_ms = if stride_ms == 0 { duration_ms } else { stride_ms };
+
+        let mut windows = Vec::new();
+        let mut start_ms = first.timestamp_ms;
+        while start_ms.saturating_add(duration_ms) <= last.timestamp_ms + 1 {
+            let end_ms = start_ms + duration_ms;
+            let from = sorted.partition_point(|r| r.timestamp_ms < start_ms);
+            let to = sorted.partition_point(|r| r.timestamp_ms < end_ms);
+            windows.push(SensorWindow {
+                start_ms,
+                end_ms,
+                readings: sorted[from..to].to_vec(),
+            });
+            start_ms += stride_ms;
+        }
+        windows
+    }
+
     /// Clear the buffer
     pub fn clear(&mut self) {
         self.readings.clear();
+        self.start = 0;
     }
 
     /// Number of readings in buffer
     pub fn len(&self) -> usize {
-        self.readings.len()
+        self.readings.len() - self.start
     }
 
     /// Check if buffer is empty
     pub fn is_empty(&self) -> bool {
-        self.readings.is_empty()
+        self.len() == 0
+    }
+}
+
+/// Readings falling in one `[start_ms, end_ms)` window of a buffer
+#[derive(Debug, Clone)]
+pub struct SensorWindow<'a> {
+    /// Start of the window (inclusive), in milliseconds since epoch
+    pub start_ms: u64,
+    /// End of the window (exclusive)
+    pub end_ms: u64,
+    readings: Vec<&'a SensorReading>,
+}
+
+impl<'a> SensorWindow<'a> {
+    /// Readings in the window, oldest first
+    pub fn readings(&self) -> &[&'a SensorReading] {
+        &self.readings
+    }
+
+    /// Statistics of each of `sensor_type`'s `dimensions()` channels;
+    /// channels without readings have all-zero statistics
+    pub fn stats(&self, sensor_type: SensorType) -> Vec<ChannelStats> {
+        (0..sensor_type.dimensions())
+            .map(|channel| {
+                let values: Vec<f32> = self
+                    .readings
+                    .iter()
+                    .filter(|r| r.sensor_type == sensor_type)
+                    .filter_map(|r| r.values.get(channel).copied())
+                    .collect();
+                ChannelStats::of(&values)
+            })
+            .collect()
+    }
+
+    /// Statistics of every channel of `sensor_types`, flattened in that
+    /// order; the length is always [`ChannelStats::LEN`] times the summed
+    /// dimensions, whatever readings the window holds
+    pub fn feature_vector(&self, sensor_types: &[SensorType]) -> Vec<f32> {
+        sensor_types
+            .iter()
+            .flat_map(|&sensor_type| self.stats(sensor_type))
+            .flat_map(|stats| stats.to_array())
+            .collect()
+    }
+}
+
+/// Summary statistics of one value channel over a window
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ChannelStats {
+    /// Mean value
+    pub mean: f32,
+    /// Population standard deviation
+    pub std: f32,
+    /// Smallest value
+    pub min: f32,
+    /// Largest value
+    pub max: f32,
+    /// Root mean square
+    pub rms: f32,
+    /// Sign changes about the mean, which for signals riding on an offset
+    /// (gravity, air pressure) is the meaningful zero-crossing count
+    pub zero_crossings: u32,
+}
+
+impl ChannelStats {
+    /// Number of statistics in [`ChannelStats::to_array`]
+    pub const LEN: usize = 6;
+
+    /// Statistics of `values`; all zero for an empty slice
+    pub fn of(values: &[f32]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let n = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        let mut crossings = 0;
+        let mut previous: Option<bool> = None;
+        for v in values {
+            let above = *v > mean;
+            if *v != mean {
+                if previous.is_some_and(|p| p != above) {
+                    crossings += 1;
+                }
+                previous = Some(above);
+            }
+        }
+        Self {
+            mean,
+            std: variance.sqrt(),
+            min: values.iter().copied().fold(f32::INFINITY, f32::min),
+            max: values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+            rms: (values.iter().map(|v| v * v).sum::<f32>() / n).sqrt(),
+            zero_crossings: crossings,
+        }
+    }
+
+    /// `[mean, std, min, max, rms, zero_crossings]`
+    pub fn to_array(&self) -> [f32; Self::LEN] {
+        [
+            self.mean,
+            self.std,
+            self.min,
+            self.max,
+            self.rms,
+            self.zero_crossings as f32,
+        ]
     }
 }
 
 /// Get current timestamp in milliseconds
 fn current_timestamp_ms() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_millis() as u64)
-        .unwrap_or(0)
+    crate::clock::now_ms()
 }
 
 #[cfg(test)]
@@ -290,4 +500,71 @@ fn test_buffer() {
         assert_eq!(buffer.len(), 3);
         assert_eq!(buffer.readings()[0].values[0], 200.0);
     }
+
+    #[test]
+    fn test_buffer_ring_and_time_queries() {
+        let mut buffer = SensorBuffer::new(3);
+        assert_eq!(buffer.time_range(), None);
+        for t in 0..10u64 {
+            buffer.push(SensorReading::with_timestamp(
+                SensorType::Light,
+                vec![t as f32],
+                t * 10,
+            ));
+            assert!(buffer.len() <= 3 && buffer.readings.len() < 6);
+        }
+        let values: Vec<f32> = buffer.readings().iter().map(|r| r.values[0]).collect();
+        assert_eq!(values, vec![7.0, 8.0, 9.0]);
+        assert!(buffer.is_full());
+        assert_eq!(buffer.capacity(), 3);
+        assert_eq!(buffer.time_range(), Some((70, 90)));
+        assert_eq!(buffer.readings_between(75, 90).len(), 1);
+        assert_eq!(buffer.latest_span(10).len(), 2);
+
+        buffer.extend((0..5u64).map(|t| {
+            SensorReading::with_timestamp(SensorType::Light, vec![100.0 + t as f32], 200 + t)
+        }));
+        assert_eq!(buffer.readings()[0].values[0], 102.0);
+        buffer.clear();
+        assert!(buffer.is_empty());
+
+        let mut none = SensorBuffer::new(0);
+        none.push(SensorReading::new(SensorType::Light, vec![1.0]));
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_windows_give_fixed_size_features() {
+        let mut buffer = SensorBuffer::new(64);
+        for i in 0..10u64 {
+            let y = if i % 2 == 0 { 1.0 } else { -1.0 };
+            buffer.push(SensorReading::with_timestamp(
+                SensorType::Accelerometer,
+                vec![0.0, y, 9.8],
+                1_000 + i * 100,
+            ));
+        }
+        buffer.push(SensorReading::with_timestamp(SensorType::Light, vec![50.0], 1_050));
+
+        let windows = buffer.windows(400, 200);
+        let starts: Vec<u64> = windows.iter().map(|w| w.start_ms).collect();
+        assert_eq!(starts, vec![1_000, 1_200, 1_400]);
+        assert_eq!(windows[0].readings().len(), 5);
+        assert_eq!(buffer.windows(400, 0).len(), 2);
+        assert!(buffer.windows(2_000, 100).is_empty());
+
+        let y = windows[1].stats(SensorType::Accelerometer)[1];
+        assert_eq!((y.min, y.max, y.mean), (-1.0, 1.0, 0.0));
+        assert!((y.std - 1.0).abs() < 1e-6 && (y.rms - 1.0).abs() < 1e-6);
+        assert_eq!(y.zero_crossings, 3);
+
+        let sensors = [SensorType::Accelerometer, SensorType::Light];
+        let lengths: Vec<usize> = windows
+            .iter()
+            .map(|w| w.feature_vector(&sensors).len())
+            .collect();
+        assert_eq!(lengths, vec![4 * ChannelStats::LEN; 3]);
+        assert_eq!(windows[0].feature_vector(&sensors)[18], 50.0);
+        assert_eq!(windows[1].feature_vector(&sensors)[18..], [0.0; 6]);
+    }
 }
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Batched Sensor Ingestion
+//!
+//! Android and iOS sensor callbacks fire hundreds of times a second;
+//! crossing the JNI or UniFFI boundary once per reading costs more than
+//! the reading is worth. Hosts instead pack readings into one byte buffer
+//! (e.g. a little-endian direct `ByteBuffer`) and hand the whole batch to
+//! `Orchestrator::push_sensor_batch` in a single call. No pointers cross
+//! the boundary, only bytes.
+//!
+//! The format, all integers and floats little-endian:
+//!
+//! ```text
+//! batch  := version:u8 record*
+//! record := sensor:u8 custom_id:u8 accuracy:u8 count:u8
+//!           timestamp_ms:u64 value:f32 * count
+//! ```
+//!
+//! `sensor` numbers the [`SensorType`] variants in declaration order
+//! (0 = accelerometer ... 8 = touch, 9 = custom with `custom_id`), with
+//! the derived orientation added later as 10.
+//! `accuracy` is 0-3 (unreliable, low, medium, high), the same values as
+//! Android's `SENSOR_STATUS_*` constants. A batch is decoded in full
+//! before any reading is used, so a malformed batch changes nothing.
+
+#![forbid(unsafe_code)]
+
+use crate::sensor::{SensorAccuracy, SensorReading, SensorType};
+
+/// Version byte of the batch format
+pub const BATCH_VERSION: u8 = 1;
+
+/// Bytes of a record before its values
+const RECORD_HEADER: usize = 12;
+
+/// Sensor number of `sensor_type` and its custom id
+fn sensor_code(sensor_type: SensorType) -> (u8, u8) {
+    match sensor_type {
+        SensorType::Accelerometer => (0, 0),
+        SensorType::Gyroscope => (1, 0),
+        SensorType::Magnetometer => (2, 0),
+        SensorType::Light => (3, 0),
+        SensorType::Proximity => (4, 0),
+        SensorType::Barometer => (5, 0),
+        SensorType::Gps => (6, 0),
+        SensorType::Audio => (7, 0),
+        SensorType::Touch => (8, 0),
+        SensorType::Custom(id) => (9, id),
+        SensorType::Orientation => (10, 0),
+    }
+}
+
+fn sensor_type(code: u8, custom_id: u8) -> Option<SensorType> {
+    Some(match code {
+        0 => SensorType::Accelerometer,
+        1 => SensorType::Gyroscope,
+        2 => SensorType::Magnetometer,
+        3 => SensorType::Light,
+        4 => SensorType::Proximity,
+        5 => SensorType::Barometer,
+        6 => SensorType::Gps,
+        7 => SensorType::Audio,
+        8 => SensorType::Touch,
+        9 => SensorType::Custom(custom_id),
+        10 => SensorType::Orientation,
+        _ => return None,
+    })
+}
+
+fn accuracy_code(accuracy: SensorAccuracy) -> u8 {
+    match accuracy {
+        SensorAccuracy::Unreliable => 0,
+        SensorAccuracy::Low => 1,
+        SensorAccuracy::Medium => 2,
+        SensorAccuracy::High => 3,
+    }
+}
+
+fn accuracy(code: u8) -> Option<SensorAccuracy> {
+    Some(match code {
+        0 => SensorAccuracy::Unreliable,
+        1 => SensorAccuracy::Low,
+        2 => SensorAccuracy::Medium,
+        3 => SensorAccuracy::High,
+        _ => return None,
+    })
+}
+
+/// Pack `readings` into a batch; readings with more than 255 values are
+/// truncated
+pub fn encode(readings: &[SensorReading]) -> Vec<u8> {
+    let values: usize = readings.iter().map(|r| r.values.len().min(255)).sum();
+    let mut bytes = Vec::with_capacity(1 + readings.len() * RECORD_HEADER + values * 4);
+    bytes.push(BATCH_VERSION);
+    for reading in readings {
+        let (code, custom_id) = sensor_code(reading.sensor_type);
+        let count = reading.values.len().min(255);
+        bytes.extend_from_slice(&[
+            code,
+            custom_id,
+            accuracy_code(reading.accuracy),
+            count as u8,
+        ]);
+        bytes.extend_from_slice(&reading.timestamp_ms.to_le_bytes());
+        for value in &reading.values[..count] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Unpack a batch written by `encode` or a host in the same format
+pub fn decode(bytes: &[u8]) -> Result<Vec<SensorReading>, String> {
+    let Some((&version, mut rest)) = bytes.split_first() else {
+        return Err("empty sensor batch".to_string());
+    };
+    if version != BATCH_VERSION {
+        return Err(format!(
+            "unsupported sensor batch version {} (expected {})",
+            version, BATCH_VERSION
+        ));
+    }
+    let mut readings = Vec::new();
+    while !rest.is_empty() {
+        let offset = bytes.len() - rest.len();
+        if rest.len() < RECORD_HEADER {
+            return Err(format!("truncated record header at byte {}", offset));
+        }
+        let (header, tail) = rest.split_at(RECORD_HEADER);
+        let sensor_type = sensor_type(header[0], header[1])
+            .ok_or_else(|| format!("unknown sensor {} at byte {}", header[0], offset))?;
+        let accuracy = accuracy(header[2])
+            .ok_or_else(|| format!("unknown accuracy {} at byte {}", header[2], offset))?;
+        let count = usize::from(header[3]);
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&header[4..RECORD_HEADER]);
+        if tail.len() < count * 4 {
+            return Err(format!(
+                "truncated values at byte {}",
+                offset + RECORD_HEADER
+            ));
+        }
+        let (values, tail) = tail.split_at(count * 4);
+        let values = values
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        readings.push(
+            SensorReading::with_timestamp(sensor_type, values, u64::from_le_bytes(timestamp))
+                .with_accuracy(accuracy),
+        );
+        rest = tail;
+    }
+    Ok(readings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_round_trip_and_reject_damage() {
+        let readings = vec![
+            SensorReading::with_timestamp(SensorType::Accelerometer, vec![0.1, -9.8, 0.3], 1_000)
+                .with_accuracy(SensorAccuracy::High),
+            SensorReading::with_timestamp(SensorType::Custom(7), vec![42.0], 1_005),
+        ];
+        let bytes = encode(&readings);
+        assert_eq!(bytes.len(), 1 + 12 + 12 + 12 + 4);
+        let Ok(decoded) = decode(&bytes) else {
+            panic!("decode should succeed");
+        };
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].values, readings[0].values);
+        assert_eq!(decoded[0].accuracy, SensorAccuracy::High);
+        assert_eq!(decoded[1].sensor_type, SensorType::Custom(7));
+        assert_eq!(decoded[1].timestamp_ms, 1_005);
+        assert_eq!(decode(&[BATCH_VERSION]).map(|r| r.len()), Ok(0));
+
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode(&[]).is_err());
+        let mut future = bytes.clone();
+        future[0] = 2;
+        assert!(decode(&future).is_err());
+        let mut unknown = bytes;
+        unknown[1] = 42;
+        assert!(decode(&unknown).is_err_and(|e| e.contains("unknown sensor 42")));
+    }
+}
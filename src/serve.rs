@@ -0,0 +1,677 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Local HTTP Daemon
+//!
+//! `mobile-ai serve` runs one orchestrator for every app on the device, or
+//! for a desktop companion, over a small JSON API:
+//!
+//! | Method | Path           | Body / query                        | Answer                      |
+//! |--------|----------------|-------------------------------------|-----------------------------|
+//! | POST   | `/v1/process`  | `{"text", "project"?, "priority"?}` | `Response`                  |
+//! | GET    | `/v1/history`  | `?limit=N` (default 10)             | `[ConversationTurn]`        |
+//! | POST   | `/v1/feedback` | `{"turn_id", "correct_route"}`      | `{"steps"}`                 |
+//! | GET    | `/v1/metrics`  |                                     | `{"metrics", "energy"}`     |
+//!
+//! Every request must carry `Authorization: Bearer <token>`, the token
+//! being the [`ServeConfig::token`] secret (by default the
+//! `MOBILE_AI_SERVE_TOKEN` environment variable); it is resolved on each
+//! request, so it can be rotated without a restart. Requests without it
+//! answer 401.
+//!
+//! Errors answer `{"error": "..."}` with a status that follows the
+//! [`OrchestratorError`] variant (400 invalid input, 403 route not allowed,
+//! 404 unknown turn, 503 offline or shut down, ...).
+//!
+//! The server speaks plain HTTP/1.1 over `std::net`, one thread per
+//! connection and one request per connection, at most
+//! `max_connections` at a time (more answer 503), and binds to loopback
+//! by default: it is meant for clients on the same device, not the
+//! network. A `project` in a process request applies to that query only;
+//! the orchestrator is not locked while backends generate, so clients
+//! are answered concurrently.
+
+#![forbid(unsafe_code)]
+
+use crate::error::OrchestratorError;
+use crate::orchestrator::{Begun, Orchestrator};
+use crate::secrets::{SecretProvider, SecretRef};
+use crate::types::{Query, RoutingDecision};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Environment variable holding the default bearer token
+pub const DEFAULT_TOKEN_VAR: &str = "MOBILE_AI_SERVE_TOKEN";
+
+/// Daemon settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// Address to listen on
+    pub addr: String,
+    /// Largest request body accepted (bytes)
+    pub max_body_bytes: usize,
+    /// Most turns one history request returns
+    pub max_history: usize,
+    /// How long a client may take to send its request (ms)
+    pub read_timeout_ms: u64,
+    /// Bearer token clients must send
+    pub token: SecretRef,
+    /// Most connections served at once
+    pub max_connections: usize,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:8737".to_string(),
+            max_body_bytes: 64 * 1024,
+            max_history: 100,
+            read_timeout_ms: 10_000,
+            token: SecretRef::Env(DEFAULT_TOKEN_VAR.to_string()),
+            max_connections: 16,
+        }
+    }
+}
+
+/// Longest request line or header accepted (bytes)
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+/// A parsed HTTP request
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    /// Method, e.g. `GET`
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Query string after `?`, if any
+    pub query: String,
+    /// Value of the `Authorization` header, if any
+    pub authorization: Option<String>,
+    /// Request body
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// A request with an empty body
+    pub fn new(method: &str, target: &str) -> Self {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            authorization: None,
+            body: Vec::new(),
+        }
+    }
+
+    /// The same request authorized with bearer `token`
+    pub fn with_bearer(mut self, token: &str) -> Self {
+        self.authorization = Some(format!("Bearer {}", token));
+        self
+    }
+
+    /// The same request carrying `body`
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Value of query parameter `name`
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// A JSON answer and its status code
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    /// Status code
+    pub status: u16,
+    /// JSON body
+    pub body: serde_json::Value,
+}
+
+impl HttpResponse {
+    fn ok(body: serde_json::Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        let body = self.body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status,
+            reason_phrase(self.status),
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+impl From<OrchestratorError> for HttpResponse {
+    fn from(error: OrchestratorError) -> Self {
+        let status = match &error {
+            OrchestratorError::Blocked { .. } | OrchestratorError::RouteNotAllowed { .. } => 403,
+            OrchestratorError::Deferred { .. } => 202,
+            OrchestratorError::ShutDown | OrchestratorError::NetworkUnavailable { .. } => 503,
+            OrchestratorError::MissingCapabilities(_) => 422,
+            OrchestratorError::InvalidInput(_) => 400,
+            OrchestratorError::NotFound(_) => 404,
+            OrchestratorError::BackendFailure(_)
+            | OrchestratorError::PersistenceError(_)
+            | OrchestratorError::NotConfigured(_) => 500,
+        };
+        Self::error(status, error.to_string())
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Body of `POST /v1/process`
+#[derive(Debug, Deserialize)]
+struct ProcessRequest {
+    text: String,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    priority: Option<u8>,
+}
+
+/// Body of `POST /v1/feedback`
+#[derive(Debug, Deserialize)]
+struct FeedbackRequest {
+    turn_id: u64,
+    correct_route: RoutingDecision,
+}
+
+/// Answer `request` with `orchestrator`, once its bearer token matches
+/// `config.token` as resolved through `secrets`
+pub fn handle(
+    orchestrator: &Mutex<Orchestrator>,
+    config: &ServeConfig,
+    secrets: &dyn SecretProvider,
+    request: &HttpRequest,
+) -> HttpResponse {
+    let token = match config.token.resolve(secrets) {
+        Ok(token) => token,
+        Err(e) => return HttpResponse::error(503, format!("no token configured: {}", e)),
+    };
+    let presented = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| same_token(presented, token.expose())) {
+        return HttpResponse::error(401, "missing or invalid bearer token");
+    }
+
+    let lock = || orchestrator.lock().unwrap_or_else(PoisonError::into_inner);
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/v1/process") => parse::<ProcessRequest>(&request.body).and_then(|body| {
+            let mut query = Query::new(body.text);
+            query.project_context = body.project;
+            if let Some(priority) = body.priority {
+                query.priority = priority.clamp(1, 10);
+            }
+            // Backends generate without the lock, as in `Dispatcher`
+            let begun = lock().begin(query)?;
+            let response = match begun {
+                Begun::Answered(response) => *response,
+                Begun::Pending(pending) => {
+                    let generated = pending.generate();
+                    lock().complete(generated)?
+                }
+            };
+            to_json(&response)
+        }),
+        ("GET", "/v1/history") => {
+            let limit = match request.param("limit").map(str::parse::<usize>) {
+                None => Ok(10),
+                Some(Ok(limit)) => Ok(limit.min(config.max_history)),
+                Some(Err(_)) => Err(OrchestratorError::InvalidInput(
+                    "limit must be a number".to_string(),
+                )),
+            };
+            limit.and_then(|limit| to_json(&lock().recent_history(limit)))
+        }
+        ("POST", "/v1/feedback") => parse::<FeedbackRequest>(&request.body).and_then(|body| {
+            let steps = lock().record_feedback(body.turn_id, body.correct_route)?;
+            Ok(json!({ "steps": steps }))
+        }),
+        ("GET", "/v1/metrics") => {
+            let orchestrator = lock();
+            Ok(json!({
+                "metrics": orchestrator.metrics_snapshot(),
+                "energy": orchestrator.energy_report(),
+            }))
+        }
+        (_, "/v1/process" | "/v1/history" | "/v1/feedback" | "/v1/metrics") => {
+            return HttpResponse::error(405, format!("{} not allowed", request.method));
+        }
+        (_, path) => return HttpResponse::error(404, format!("no endpoint {}", path)),
+    };
+    match result {
+        Ok(body) => HttpResponse::ok(body),
+        Err(error) => error.into(),
+    }
+}
+
+/// Compare tokens without stopping at the first differing byte
+fn same_token(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (presented.as_bytes(), expected.as_bytes());
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn parse<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, OrchestratorError> {
+    serde_json::from_slice(body)
+        .map_err(|e| OrchestratorError::InvalidInput(format!("invalid request body: {}", e)))
+}
+
+fn to_json(value: &impl Serialize) -> Result<serde_json::Value, OrchestratorError> {
+    serde_json::to_value(value).map_err(|e| OrchestratorError::BackendFailure(e.to_string()))
+}
+
+/// Read one request from `stream`; `Err` carries the response to send
+/// instead
+fn read_request(stream: impl Read, config: &ServeConfig) -> Result<HttpRequest, HttpResponse> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let mut read_line = |line: &mut String| -> Result<(), HttpResponse> {
+        line.clear();
+        let limit = (MAX_HEADER_LINE + 1) as u64;
+        match reader.by_ref().take(limit).read_line(line) {
+            Ok(n) if n > MAX_HEADER_LINE => Err(HttpResponse::error(400, "header too long")),
+            Ok(_) => Ok(()),
+            Err(e) => Err(HttpResponse::error(400, e.to_string())),
+        }
+    };
+
+    read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(HttpResponse::error(400, "malformed request line"));
+    };
+    let mut request = HttpRequest::new(method, target);
+
+    let mut content_length = 0;
+    let mut header = String::new();
+    loop {
+        read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| HttpResponse::error(400, "invalid Content-Length"))?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                request.authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    if content_length > config.max_body_bytes {
+        return Err(HttpResponse::error(413, "request body too large"));
+    }
+    request.body = vec![0; content_length];
+    reader
+        .read_exact(&mut request.body)
+        .map_err(|e| HttpResponse::error(400, e.to_string()))?;
+    Ok(request)
+}
+
+/// Listens for clients and answers them with one shared orchestrator
+pub struct Server {
+    listener: TcpListener,
+    orchestrator: Arc<Mutex<Orchestrator>>,
+    config: ServeConfig,
+    secrets: Arc<dyn SecretProvider>,
+    /// Connections being served
+    active: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// Listen on `config.addr`, resolving the bearer token through
+    /// `secrets`; fails if the token is not set
+    pub fn bind(
+        orchestrator: Arc<Mutex<Orchestrator>>,
+        config: ServeConfig,
+        secrets: Arc<dyn SecretProvider>,
+    ) -> io::Result<Self> {
+        config
+            .token
+            .resolve(secrets.as_ref())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if config.max_connections == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "max_connections must be at least 1",
+            ));
+        }
+        Ok(Self {
+            listener: TcpListener::bind(&config.addr)?,
+            orchestrator,
+            config,
+            secrets,
+            active: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Address actually bound, e.g. when `addr` asked for port 0
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve clients until accepting fails
+    pub fn run(&self) -> io::Result<()> {
+        loop {
+            self.serve_next()?;
+        }
+    }
+
+    /// Accept one client and answer it on its own thread; `None` when
+    /// `max_connections` are already being served and the client was
+    /// turned away with 503
+    pub fn serve_next(&self) -> io::Result<Option<std::thread::JoinHandle<()>>> {
+        let (mut stream, _) = self.listener.accept()?;
+        let Some(slot) = ConnectionSlot::claim(&self.active, self.config.max_connections) else {
+            // The client may already have hung up; there is nobody to tell.
+            let _ = HttpResponse::error(503, "too many connections").write_to(&mut stream);
+            return Ok(None);
+        };
+        let orchestrator = Arc::clone(&self.orchestrator);
+        let config = self.config.clone();
+        let secrets = Arc::clone(&self.secrets);
+        std::thread::Builder::new()
+            .name("mobile-ai-serve".to_string())
+            .spawn(move || {
+                let _slot = slot;
+                serve_connection(stream, &orchestrator, &config, secrets.as_ref());
+            })
+            .map(Some)
+    }
+
+    /// Connections being served right now
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+}
+
+/// One of `max_connections`, given back when dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn claim(active: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(active)))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    orchestrator: &Mutex<Orchestrator>,
+    config: &ServeConfig,
+    secrets: &dyn SecretProvider,
+) {
+    let timeout = Some(Duration::from_millis(config.read_timeout_ms.max(1)));
+    // A client that never sends is dropped when the timeout expires; if
+    // the timeout cannot be set it can only hold its own slot.
+    let _ = stream.set_read_timeout(timeout);
+    let response = match read_request(&stream, config) {
+        Ok(request) => handle(orchestrator, config, secrets, &request),
+        Err(response) => response,
+    };
+    // The client may already have hung up; there is nobody to tell.
+    let _ = response.write_to(&mut stream);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::{LocalModel, Reusable};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{OnceLock, Weak};
+
+    const TOKEN: &str = "s3cret";
+
+    /// Host secret store holding the daemon's token
+    struct Tokens;
+
+    impl SecretProvider for Tokens {
+        fn get_secret(&self, name: &str) -> Option<String> {
+            (name == "serve").then(|| TOKEN.to_string())
+        }
+    }
+
+    fn config() -> ServeConfig {
+        ServeConfig {
+            addr: "127.0.0.1:0".to_string(),
+            token: SecretRef::Provider("serve".to_string()),
+            ..ServeConfig::default()
+        }
+    }
+
+    /// Local model noting whether the orchestrator was unlocked while it
+    /// generated
+    #[derive(Clone, Default)]
+    struct Probe {
+        orchestrator: Arc<OnceLock<Weak<Mutex<Orchestrator>>>>,
+        unlocked: Arc<AtomicBool>,
+    }
+
+    impl Reusable for Probe {
+        fn reset(&mut self) {}
+    }
+
+    impl LocalModel for Probe {
+        fn generate(&mut self, query: &Query) -> Result<String, String> {
+            if let Some(orchestrator) = self.orchestrator.get().and_then(Weak::upgrade) {
+                let unlocked = orchestrator.try_lock().is_ok();
+                self.unlocked.store(unlocked, Ordering::SeqCst);
+            }
+            Ok(format!("local: {}", query.text))
+        }
+    }
+
+    #[test]
+    fn test_endpoints_answer_from_one_orchestrator() {
+        let orchestrator = Mutex::new(Orchestrator::new());
+        let config = config();
+        let answer = |request: HttpRequest| handle(&orchestrator, &config, &Tokens, &request);
+        let process = HttpRequest::new("POST", "/v1/process")
+            .with_body(r#"{"text": "What is a monad?", "project": "garden"}"#);
+        let answer_ok = answer(process.clone().with_bearer(TOKEN));
+        assert_eq!(answer_ok.status, 200);
+        assert_eq!(answer_ok.body["text"], "Response to: What is a monad?");
+
+        let history = answer(HttpRequest::new("GET", "/v1/history?limit=5").with_bearer(TOKEN));
+        assert_eq!(history.status, 200);
+        assert_eq!(history.body.as_array().map(Vec::len), Some(1));
+        assert_eq!(history.body[0]["query"]["project_context"], "garden");
+
+        let metrics = answer(HttpRequest::new("GET", "/v1/metrics").with_bearer(TOKEN));
+        assert_eq!(metrics.status, 200);
+        assert!(metrics.body["energy"]["total_joules"].is_number());
+
+        let feedback = HttpRequest::new("POST", "/v1/feedback")
+            .with_body(r#"{"turn_id": 999, "correct_route": "Remote"}"#)
+            .with_bearer(TOKEN);
+        assert_eq!(answer(feedback).status, 404);
+        let garbage = HttpRequest::new("POST", "/v1/process").with_body("{");
+        assert_eq!(answer(garbage.with_bearer(TOKEN)).status, 400);
+        let wrong_method = HttpRequest::new("GET", "/v1/process").with_bearer(TOKEN);
+        assert_eq!(answer(wrong_method).status, 405);
+        assert_eq!(
+            answer(HttpRequest::new("GET", "/nope").with_bearer(TOKEN)).status,
+            404
+        );
+
+        // No token, or the wrong one, is refused before anything runs
+        assert_eq!(answer(process.clone()).status, 401);
+        assert_eq!(answer(process.with_bearer("guess")).status, 401);
+        let unset = ServeConfig {
+            token: SecretRef::Provider("missing".to_string()),
+            ..config.clone()
+        };
+        let metrics = HttpRequest::new("GET", "/v1/metrics").with_bearer(TOKEN);
+        assert_eq!(handle(&orchestrator, &unset, &Tokens, &metrics).status, 503);
+        let orchestrator = orchestrator.lock().unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(orchestrator.metrics_snapshot().total_queries(), 1);
+    }
+
+    #[test]
+    fn test_process_keeps_project_per_query_and_generates_unlocked() {
+        let probe = Probe::default();
+        let mut orchestrator = Orchestrator::new();
+        let model = probe.clone();
+        let loaded = orchestrator.set_local_model(move || Ok(Box::new(model.clone()) as _));
+        assert_eq!(loaded, Ok(1));
+        let orchestrator = Arc::new(Mutex::new(orchestrator));
+        assert!(probe
+            .orchestrator
+            .set(Arc::downgrade(&orchestrator))
+            .is_ok());
+
+        let process = HttpRequest::new("POST", "/v1/process")
+            .with_body(r#"{"text": "hello", "project": "garden"}"#)
+            .with_bearer(TOKEN);
+        let answer = handle(&orchestrator, &config(), &Tokens, &process);
+        assert_eq!(answer.status, 200);
+        assert!(answer.body["text"]
+            .as_str()
+            .is_some_and(|text| text.starts_with("local: ")));
+        assert!(probe.unlocked.load(Ordering::SeqCst));
+
+        // The project went with the query; other clients' context is untouched
+        let orchestrator = orchestrator.lock().unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(orchestrator.current_project(), None);
+        let history = orchestrator.recent_history(1);
+        assert_eq!(history[0].query.project_context.as_deref(), Some("garden"));
+    }
+
+    fn request(body: &str) -> String {
+        format!(
+            "POST /v1/process HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            TOKEN,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_server_answers_over_tcp() {
+        let orchestrator = Arc::new(Mutex::new(Orchestrator::new()));
+        let unset = ServeConfig {
+            token: SecretRef::Provider("missing".to_string()),
+            ..config()
+        };
+        assert!(Server::bind(Arc::clone(&orchestrator), unset, Arc::new(Tokens)).is_err());
+        let Ok(server) = Server::bind(orchestrator, config(), Arc::new(Tokens)) else {
+            panic!("bind should succeed");
+        };
+        let Ok(addr) = server.local_addr() else {
+            panic!("local_addr should succeed");
+        };
+        let Ok(mut client) = TcpStream::connect(addr) else {
+            panic!("connect should succeed");
+        };
+        assert!(client
+            .write_all(request(r#"{"text": "hello"}"#).as_bytes())
+            .is_ok());
+        let Ok(Some(worker)) = server.serve_next() else {
+            panic!("serve_next should accept the client");
+        };
+        let mut reply = String::new();
+        assert!(client.read_to_string(&mut reply).is_ok());
+        assert!(worker.join().is_ok());
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.ends_with("}") && reply.contains("Response to: hello"));
+    }
+
+    #[test]
+    fn test_server_turns_away_clients_over_the_connection_cap() {
+        let config = ServeConfig {
+            max_connections: 1,
+            ..config()
+        };
+        let orchestrator = Arc::new(Mutex::new(Orchestrator::new()));
+        let Ok(server) = Server::bind(orchestrator, config, Arc::new(Tokens)) else {
+            panic!("bind should succeed");
+        };
+        let Ok(addr) = server.local_addr() else {
+            panic!("local_addr should succeed");
+        };
+        // The first client holds the only slot until it sends its request
+        let (Ok(mut slow), Ok(mut late)) = (TcpStream::connect(addr), TcpStream::connect(addr))
+        else {
+            panic!("connect should succeed");
+        };
+        let Ok(Some(worker)) = server.serve_next() else {
+            panic!("the first client should be served");
+        };
+        assert_eq!(server.active_connections(), 1);
+        assert!(matches!(server.serve_next(), Ok(None)));
+        let mut refused = String::new();
+        assert!(late.read_to_string(&mut refused).is_ok());
+        assert!(refused.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+
+        assert!(slow
+            .write_all(request(r#"{"text": "hello"}"#).as_bytes())
+            .is_ok());
+        let mut reply = String::new();
+        assert!(slow.read_to_string(&mut reply).is_ok());
+        assert!(worker.join().is_ok());
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(server.active_connections(), 0);
+    }
+}
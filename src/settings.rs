@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Runtime Settings
+//!
+//! The few knobs a user may change from the CLI or an app's settings
+//! screen without editing the configuration file: the router's escalation
+//! threshold, the project to start in, how long cached answers stay valid
+//! and whether usage metrics are collected. Each change is a [`Setting`],
+//! a key with a typed value; [`Settings`] holds the current value of every
+//! key, starting from the loaded configuration.
+//!
+//! `Orchestrator::update_setting` applies a change at once, stores it in
+//! the attached database (`PersistenceManager::save_setting`) and
+//! publishes it as `Event::SettingChanged`, which reaches
+//! `HostDelegate::on_setting_changed`. Stored settings override the
+//! configuration file when a database is attached. Shells that only deal
+//! in text (the CLI's `/set`, string-backed preference screens) use
+//! [`Setting::parse`] and [`Setting::value_string`].
+
+#![forbid(unsafe_code)]
+
+use crate::config::OrchestratorConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Keys of every setting, in display order
+pub const SETTING_KEYS: [&str; 4] = [
+    "routing_threshold",
+    "default_project",
+    "cache_ttl_secs",
+    "telemetry_opt_in",
+];
+
+/// One setting and its new value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "key", content = "value", rename_all = "snake_case")]
+pub enum Setting {
+    /// Score above which the heuristic router escalates to Remote (0..=1)
+    RoutingThreshold(f32),
+    /// Project made active while none is; `None` for no project
+    DefaultProject(Option<String>),
+    /// Seconds a cached answer stays valid
+    CacheTtlSecs(u64),
+    /// Whether usage metrics are collected
+    TelemetryOptIn(bool),
+}
+
+impl Setting {
+    /// Key naming the setting, one of [`SETTING_KEYS`]
+    pub fn key(&self) -> &'static str {
+        match self {
+            Setting::RoutingThreshold(_) => SETTING_KEYS[0],
+            Setting::DefaultProject(_) => SETTING_KEYS[1],
+            Setting::CacheTtlSecs(_) => SETTING_KEYS[2],
+            Setting::TelemetryOptIn(_) => SETTING_KEYS[3],
+        }
+    }
+
+    /// The value as text, as read by [`Setting::parse`]; no default
+    /// project is the empty string
+    pub fn value_string(&self) -> String {
+        match self {
+            Setting::RoutingThreshold(threshold) => threshold.to_string(),
+            Setting::DefaultProject(project) => project.clone().unwrap_or_default(),
+            Setting::CacheTtlSecs(ttl) => ttl.to_string(),
+            Setting::TelemetryOptIn(opt_in) => opt_in.to_string(),
+        }
+    }
+
+    /// Parse `value` as the setting called `key`, checking its range
+    pub fn parse(key: &str, value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let invalid = |expected: &str| format!("{} must be {}, got '{}'", key, expected, value);
+        let setting = match key {
+            "routing_threshold" => {
+                Setting::RoutingThreshold(value.parse().map_err(|_| invalid("a number"))?)
+            }
+            "default_project" => {
+                Setting::DefaultProject((!value.is_empty()).then(|| value.to_string()))
+            }
+            "cache_ttl_secs" => Setting::CacheTtlSecs(
+                value
+                    .parse()
+                    .map_err(|_| invalid("a whole number of seconds"))?,
+            ),
+            "telemetry_opt_in" => Setting::TelemetryOptIn(match value {
+                "true" | "on" | "yes" | "1" => true,
+                "false" | "off" | "no" | "0" => false,
+                _ => return Err(invalid("true or false")),
+            }),
+            _ => {
+                return Err(format!(
+                    "unknown setting '{}' (expected one of {})",
+                    key,
+                    SETTING_KEYS.join(", ")
+                ))
+            }
+        };
+        setting.validate()?;
+        Ok(setting)
+    }
+
+    /// Check the value is in range
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Setting::RoutingThreshold(threshold) if !(0.0..=1.0).contains(threshold) => {
+                Err(format!(
+                    "routing_threshold must be between 0 and 1, got {}",
+                    threshold
+                ))
+            }
+            Setting::DefaultProject(Some(project)) if project.trim().is_empty() => {
+                Err("default_project must not be blank".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.key(), self.value_string())
+    }
+}
+
+/// Current value of every setting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// See [`Setting::RoutingThreshold`]
+    pub routing_threshold: f32,
+    /// See [`Setting::DefaultProject`]
+    pub default_project: Option<String>,
+    /// See [`Setting::CacheTtlSecs`]
+    pub cache_ttl_secs: u64,
+    /// See [`Setting::TelemetryOptIn`]
+    pub telemetry_opt_in: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_config(&OrchestratorConfig::default())
+    }
+}
+
+impl Settings {
+    /// Values from a loaded configuration, with no default project
+    pub fn from_config(config: &OrchestratorConfig) -> Self {
+        Self {
+            routing_threshold: config.router.heuristic_threshold,
+            default_project: None,
+            cache_ttl_secs: config.cache.ttl_secs,
+            telemetry_opt_in: config.metrics.enabled,
+        }
+    }
+
+    /// Current value of the setting called `key`
+    pub fn get(&self, key: &str) -> Option<Setting> {
+        self.all().into_iter().find(|setting| setting.key() == key)
+    }
+
+    /// Every setting, in the order of [`SETTING_KEYS`]
+    pub fn all(&self) -> Vec<Setting> {
+        vec![
+            Setting::RoutingThreshold(self.routing_threshold),
+            Setting::DefaultProject(self.default_project.clone()),
+            Setting::CacheTtlSecs(self.cache_ttl_secs),
+            Setting::TelemetryOptIn(self.telemetry_opt_in),
+        ]
+    }
+
+    /// Store `setting`; returns whether the value changed
+    pub fn set(&mut self, setting: Setting) -> bool {
+        if self.get(setting.key()).as_ref() == Some(&setting) {
+            return false;
+        }
+        match setting {
+            Setting::RoutingThreshold(threshold) => self.routing_threshold = threshold,
+            Setting::DefaultProject(project) => self.default_project = project,
+            Setting::CacheTtlSecs(ttl) => self.cache_ttl_secs = ttl,
+            Setting::TelemetryOptIn(opt_in) => self.telemetry_opt_in = opt_in,
+        }
+        true
+    }
+
+    /// Write the settings over the matching fields of `config`
+    pub fn apply_to(&self, config: &mut OrchestratorConfig) {
+        config.router.heuristic_threshold = self.routing_threshold;
+        config.cache.ttl_secs = self.cache_ttl_secs;
+        config.metrics.enabled = self.telemetry_opt_in;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_sets_and_applies_typed_settings() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.all().len(), SETTING_KEYS.len());
+        assert_eq!(
+            settings.get("cache_ttl_secs"),
+            Some(Setting::CacheTtlSecs(3_600))
+        );
+        assert_eq!(settings.get("volume"), None);
+
+        for setting in settings.all() {
+            assert_eq!(
+                Setting::parse(setting.key(), &setting.value_string()),
+                Ok(setting)
+            );
+        }
+        let Ok(project) = Setting::parse("default_project", " notes ") else {
+            panic!("a project name should parse");
+        };
+        assert_eq!(project.to_string(), "default_project = notes");
+        assert_eq!(
+            Setting::parse("telemetry_opt_in", "off"),
+            Ok(Setting::TelemetryOptIn(false))
+        );
+        assert!(Setting::parse("routing_threshold", "1.5").is_err());
+        assert!(Setting::parse("routing_threshold", "high").is_err());
+        assert!(Setting::parse("cache_ttl_secs", "-1").is_err());
+        assert!(Setting::parse("volume", "11").is_err());
+
+        assert!(settings.set(project.clone()));
+        assert!(!settings.set(project));
+        assert!(settings.set(Setting::RoutingThreshold(0.8)));
+        assert!(settings.set(Setting::TelemetryOptIn(false)));
+        let mut config = OrchestratorConfig::default();
+        settings.apply_to(&mut config);
+        assert_eq!(config.router.heuristic_threshold, 0.8);
+        assert!(!config.metrics.enabled);
+        assert_eq!(settings.default_project.as_deref(), Some("notes"));
+    }
+}
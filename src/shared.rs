@@ -0,0 +1,433 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Resources Shared Between Orchestrators
+//!
+//! An app can run several orchestrators at once, e.g. one per app profile
+//! or per signed-in user. Each loading its own copy of the embedding model
+//! and the local LLM would cost hundreds of megabytes per instance, so a
+//! device-wide [`ResourceHub`] loads each heavyweight resource once and
+//! hands out [`SharedHandle`]s to it:
+//!
+//! - **Reference counting**: a resource stays loaded while any handle to it
+//!   is alive and is dropped with the last one; asking again reloads it.
+//! - **Fair scheduling**: at most `max_concurrent` calls run on a resource
+//!   at a time. Waiting calls are served round-robin across handles, so an
+//!   instance with a burst of queries cannot starve the others.
+//!
+//! A handle to a backend is itself a [`TargetBackend`] and a handle to an
+//! embedder an [`Embedder`], so it is passed to an orchestrator like the
+//! resource itself (`set_embedder`, `set_hybrid_backends`,
+//! `register_target`).
+
+#![forbid(unsafe_code)]
+
+use crate::embedding::Embedder;
+use crate::targets::TargetBackend;
+use crate::types::Query;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+
+/// Kind of shared resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// A [`TargetBackend`], e.g. the local LLM
+    Backend,
+    /// An [`Embedder`]
+    Embedder,
+}
+
+/// Usage of one loaded resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceStatus {
+    /// Name it was loaded under
+    pub name: String,
+    /// What it is
+    pub kind: ResourceKind,
+    /// Live handles
+    pub handles: usize,
+    /// Calls running
+    pub in_flight: usize,
+    /// Calls waiting for their turn
+    pub waiting: usize,
+}
+
+/// Round-robin admission of calls from several clients
+#[derive(Debug)]
+struct FairScheduler {
+    max_concurrent: usize,
+    state: Mutex<SchedulerState>,
+    turn: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    running: usize,
+    /// Waiting tickets per client, oldest first
+    waiting: BTreeMap<u64, VecDeque<u64>>,
+    granted: HashSet<u64>,
+    last_client: Option<u64>,
+    next_ticket: u64,
+}
+
+impl SchedulerState {
+    /// Grant free slots, starting with the client after the last one served
+    fn dispatch(&mut self, max_concurrent: usize) {
+        while self.running < max_concurrent {
+            let next = self
+                .last_client
+                .and_then(|last| self.waiting.range(last + 1..).next())
+                .or_else(|| self.waiting.iter().next())
+                .map(|(&client, _)| client);
+            let Some(client) = next else {
+                return;
+            };
+            let Some(queue) = self.waiting.get_mut(&client) else {
+                return;
+            };
+            if let Some(ticket) = queue.pop_front() {
+                self.granted.insert(ticket);
+                self.running += 1;
+            }
+            if queue.is_empty() {
+                self.waiting.remove(&client);
+            }
+            self.last_client = Some(client);
+        }
+    }
+}
+
+/// A granted slot, released when dropped
+struct Permit<'a>(&'a FairScheduler);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let scheduler = self.0;
+        let mut state = scheduler.lock();
+        state.running -= 1;
+        state.dispatch(scheduler.max_concurrent);
+        scheduler.turn.notify_all();
+    }
+}
+
+impl FairScheduler {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            state: Mutex::new(SchedulerState::default()),
+            turn: Condvar::new(),
+        }
+    }
+
+    // The state stays consistent even if a caller panicked while holding it
+    fn lock(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for `client`'s turn
+    fn acquire(&self, client: u64) -> Permit<'_> {
+        let mut state = self.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.entry(client).or_default().push_back(ticket);
+        state.dispatch(self.max_concurrent);
+        while !state.granted.remove(&ticket) {
+            state = self
+                .turn
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        self.turn.notify_all();
+        Permit(self)
+    }
+
+    fn load(&self) -> (usize, usize) {
+        let state = self.lock();
+        (
+            state.running,
+            state.waiting.values().map(VecDeque::len).sum(),
+        )
+    }
+}
+
+/// One loaded resource and the calls queued on it
+struct Slot<T: ?Sized> {
+    name: String,
+    resource: Arc<T>,
+    scheduler: FairScheduler,
+}
+
+/// A client's handle to a shared resource
+pub struct SharedHandle<T: ?Sized> {
+    slot: Arc<Slot<T>>,
+    client: u64,
+}
+
+impl<T: ?Sized> Clone for SharedHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: Arc::clone(&self.slot),
+            client: self.client,
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for SharedHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedHandle")
+            .field("name", &self.slot.name)
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> SharedHandle<T> {
+    /// Name the resource was loaded under
+    pub fn name(&self) -> &str {
+        &self.slot.name
+    }
+
+    /// Run `call` on the resource once it is this handle's turn
+    pub fn with<R>(&self, call: impl FnOnce(&T) -> R) -> R {
+        let _permit = self.slot.scheduler.acquire(self.client);
+        call(&self.slot.resource)
+    }
+}
+
+impl TargetBackend for SharedHandle<dyn TargetBackend> {
+    fn generate(&self, query: &Query) -> Result<String, String> {
+        self.with(|backend| backend.generate(query))
+    }
+}
+
+impl Embedder for SharedHandle<dyn Embedder> {
+    fn id(&self) -> String {
+        self.slot.resource.id()
+    }
+
+    fn dimension(&self) -> usize {
+        self.slot.resource.dimension()
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        self.with(|embedder| embedder.embed(text))
+    }
+}
+
+#[derive(Default)]
+struct HubState {
+    backends: HashMap<String, Weak<Slot<dyn TargetBackend>>>,
+    embedders: HashMap<String, Weak<Slot<dyn Embedder>>>,
+    next_client: u64,
+}
+
+/// Device-wide registry of shared resources; clones share the registry
+#[derive(Clone)]
+pub struct ResourceHub {
+    state: Arc<Mutex<HubState>>,
+    max_concurrent: usize,
+}
+
+impl fmt::Debug for ResourceHub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResourceHub")
+            .field("loaded", &self.status().len())
+            .field("max_concurrent", &self.max_concurrent)
+            .finish()
+    }
+}
+
+impl Default for ResourceHub {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ResourceHub {
+    /// A hub running at most `max_concurrent` calls per resource at once
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(HubState::default())),
+            max_concurrent: max_concurrent.max(1),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HubState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// A handle to backend `name`, calling `load` only if it is not loaded.
+    /// Loading holds the hub, so two instances never load the same model.
+    pub fn backend(
+        &self,
+        name: &str,
+        load: impl FnOnce() -> Result<Arc<dyn TargetBackend>, String>,
+    ) -> Result<SharedHandle<dyn TargetBackend>, String> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let client = next_client(&mut state.next_client);
+        let slot = share(&mut state.backends, name, self.max_concurrent, load)?;
+        Ok(SharedHandle { slot, client })
+    }
+
+    /// A handle to embedder `name`, calling `load` only if it is not loaded
+    pub fn embedder(
+        &self,
+        name: &str,
+        load: impl FnOnce() -> Result<Arc<dyn Embedder>, String>,
+    ) -> Result<SharedHandle<dyn Embedder>, String> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let client = next_client(&mut state.next_client);
+        let slot = share(&mut state.embedders, name, self.max_concurrent, load)?;
+        Ok(SharedHandle { slot, client })
+    }
+
+    /// Resources currently loaded, by name
+    pub fn status(&self) -> Vec<ResourceStatus> {
+        let mut state = self.lock();
+        state.backends.retain(|_, slot| slot.strong_count() > 0);
+        state.embedders.retain(|_, slot| slot.strong_count() > 0);
+        let backends = state
+            .backends
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|slot| status(&slot, ResourceKind::Backend));
+        let embedders = state
+            .embedders
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|slot| status(&slot, ResourceKind::Embedder));
+        let mut all: Vec<ResourceStatus> = backends.chain(embedders).collect();
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+}
+
+fn next_client(counter: &mut u64) -> u64 {
+    *counter += 1;
+    *counter
+}
+
+/// The loaded slot `name`, loading it if no handle keeps it alive
+fn share<T: ?Sized>(
+    slots: &mut HashMap<String, Weak<Slot<T>>>,
+    name: &str,
+    max_concurrent: usize,
+    load: impl FnOnce() -> Result<Arc<T>, String>,
+) -> Result<Arc<Slot<T>>, String> {
+    if let Some(slot) = slots.get(name).and_then(Weak::upgrade) {
+        return Ok(slot);
+    }
+    let slot = Arc::new(Slot {
+        name: name.to_string(),
+        resource: load()?,
+        scheduler: FairScheduler::new(max_concurrent),
+    });
+    slots.insert(name.to_string(), Arc::downgrade(&slot));
+    Ok(slot)
+}
+
+fn status<T: ?Sized>(slot: &Arc<Slot<T>>, kind: ResourceKind) -> ResourceStatus {
+    let (in_flight, waiting) = slot.scheduler.load();
+    ResourceStatus {
+        name: slot.name.clone(),
+        kind,
+        // Not counting the temporary upgrade made to read the status
+        handles: Arc::strong_count(slot) - 1,
+        in_flight,
+        waiting,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::HashedBagOfWords;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Records the order of calls, each blocking until the test lets it go
+    struct Gated {
+        order: Mutex<Vec<String>>,
+        gate: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl TargetBackend for Gated {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            if let Ok(mut order) = self.order.lock() {
+                order.push(query.text.clone());
+            }
+            let gate = self.gate.lock().map_err(|e| e.to_string())?;
+            gate.recv().map_err(|e| e.to_string())?;
+            Ok(query.text.clone())
+        }
+    }
+
+    #[test]
+    fn test_resources_load_once_and_unload_with_the_last_handle() {
+        let hub = ResourceHub::default();
+        let mut loads = 0;
+        let mut load = || -> Result<Arc<dyn Embedder>, String> {
+            loads += 1;
+            Ok(Arc::new(HashedBagOfWords::new(32)))
+        };
+        let Ok(first) = hub.embedder("minilm", &mut load) else {
+            panic!("loading should succeed");
+        };
+        let Ok(second) = hub.embedder("minilm", &mut load) else {
+            panic!("sharing should succeed");
+        };
+        assert_eq!(first.embed("hello"), second.embed("hello"));
+        assert_eq!(hub.status()[0].handles, 2);
+
+        drop((first, second));
+        assert!(hub.status().is_empty());
+        assert!(hub.embedder("minilm", &mut load).is_ok());
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn test_waiting_calls_are_served_round_robin() {
+        let (release, gate) = mpsc::channel();
+        let backend = Arc::new(Gated {
+            order: Mutex::new(Vec::new()),
+            gate: Mutex::new(gate),
+        });
+        let hub = ResourceHub::default();
+        let shared = Arc::clone(&backend);
+        let load = move || -> Result<Arc<dyn TargetBackend>, String> { Ok(shared) };
+        let Ok(busy) = hub.backend("llm", load) else {
+            panic!("loading should succeed");
+        };
+        let Ok(quiet) = hub.backend("llm", || Err("already loaded".to_string())) else {
+            panic!("sharing should not load again");
+        };
+
+        let wait_for = |in_flight: usize, waiting: usize| {
+            while hub.status()[0].in_flight != in_flight || hub.status()[0].waiting != waiting {
+                thread::yield_now();
+            }
+        };
+        let mut calls = Vec::new();
+        let mut call = |handle: &SharedHandle<dyn TargetBackend>, text: &str| {
+            let (handle, query) = (handle.clone(), Query::new(text));
+            calls.push(thread::spawn(move || handle.generate(&query)));
+        };
+        call(&busy, "busy 1");
+        wait_for(1, 0);
+        call(&busy, "busy 2");
+        call(&busy, "busy 3");
+        wait_for(1, 2);
+        call(&quiet, "quiet 1");
+        wait_for(1, 3);
+
+        for _ in 0..4 {
+            let _ = release.send(());
+        }
+        for call in calls {
+            assert!(matches!(call.join(), Ok(Ok(_))));
+        }
+        let order = backend.order.lock().map(|o| o.clone()).unwrap_or_default();
+        assert_eq!(order, vec!["busy 1", "quiet 1", "busy 2", "busy 3"]);
+    }
+}
@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Per-Route Service Level Agreements
+//!
+//! Integrators declare what each route must deliver:
+//!
+//! ```toml
+//! [sla]
+//! daily_spend_limit = 2.50   # USD across all paid routes
+//!
+//! [sla.local]
+//! max_latency_ms = 3000
+//!
+//! [sla.remote]
+//! max_latency_ms = 8000
+//! ```
+//!
+//! [`SlaTracker`] records every call and reports when a route starts
+//! violating its SLA. A latency SLA is violated while the p95 latency of
+//! the route's last `window` calls exceeds the limit; the spend SLA is
+//! violated once the day's spend passes the limit, and then applies to the
+//! paid routes (Remote and Hybrid) until the next day.
+//!
+//! The orchestrator publishes violations on the event bus and, when
+//! `router.sla_penalty` is set, tells the router to penalize violating
+//! routes.
+
+#![forbid(unsafe_code)]
+
+use crate::types::RoutingDecision;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Milliseconds per day, the window for the spend SLA
+const DAY_MS: u64 = 86_400_000;
+
+/// Routes that incur API cost and so fall under the spend SLA
+const PAID_ROUTES: [RoutingDecision; 2] = [RoutingDecision::Remote, RoutingDecision::Hybrid];
+
+/// Limits for a single route
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteSla {
+    /// Maximum p95 latency (ms)
+    pub max_latency_ms: Option<u64>,
+}
+
+/// SLA declarations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlaConfig {
+    /// Local route limits
+    pub local: RouteSla,
+    /// Remote route limits
+    pub remote: RouteSla,
+    /// Hybrid route limits
+    pub hybrid: RouteSla,
+    /// Maximum spend per day across paid routes (USD)
+    pub daily_spend_limit: Option<f64>,
+    /// Recent calls per route used for the p95 latency
+    pub window: usize,
+}
+
+impl Default for SlaConfig {
+    fn default() -> Self {
+        Self {
+            local: RouteSla::default(),
+            remote: RouteSla::default(),
+            hybrid: RouteSla::default(),
+            daily_spend_limit: None,
+            window: 20,
+        }
+    }
+}
+
+impl SlaConfig {
+    /// Limits for `route`; Blocked and custom targets have none
+    pub fn route(&self, route: RoutingDecision) -> Option<&RouteSla> {
+        match route {
+            RoutingDecision::Local => Some(&self.local),
+            RoutingDecision::Remote => Some(&self.remote),
+            RoutingDecision::Hybrid => Some(&self.hybrid),
+            RoutingDecision::Blocked | RoutingDecision::Custom(_) => None,
+        }
+    }
+}
+
+/// A newly started SLA violation
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlaViolation {
+    /// A route's p95 latency went over its limit
+    Latency {
+        /// Violating route
+        route: RoutingDecision,
+        /// Observed p95 latency (ms)
+        p95_ms: u64,
+        /// Declared limit (ms)
+        limit_ms: u64,
+    },
+    /// Today's spend went over the daily limit
+    DailySpend {
+        /// Spent so far today (USD)
+        spent: f64,
+        /// Declared limit (USD)
+        limit: f64,
+    },
+}
+
+/// Per-route SLA status
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteSlaStatus {
+    /// Calls recorded
+    pub calls: usize,
+    /// Calls that individually exceeded the latency limit
+    pub slow_calls: usize,
+    /// Current p95 latency over the window (ms)
+    pub p95_ms: Option<u64>,
+    /// Whether the route is currently violating any SLA
+    pub violating: bool,
+}
+
+/// Tracks calls against the declared SLAs
+#[derive(Debug, Clone, Default)]
+pub struct SlaTracker {
+    config: SlaConfig,
+    latencies: HashMap<RoutingDecision, VecDeque<u64>>,
+    status: HashMap<RoutingDecision, RouteSlaStatus>,
+    /// Routes currently over their latency limit
+    slow: HashSet<RoutingDecision>,
+    day: u64,
+    spent_today: f64,
+}
+
+impl SlaTracker {
+    /// Create a tracker for the given SLAs
+    pub fn new(config: SlaConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Record one call, returning any violations that started with it
+    pub fn record(
+        &mut self,
+        route: RoutingDecision,
+        latency_ms: u64,
+        cost_usd: f64,
+        now_ms: u64,
+    ) -> Vec<SlaViolation> {
+        if route == RoutingDecision::Blocked {
+            return Vec::new();
+        }
+        let mut violations = Vec::new();
+
+        // Custom targets have no latency SLA but still count towards spend.
+        if let Some(limit_ms) = self.config.route(route).map(|sla| sla.max_latency_ms) {
+            let window = self.latencies.entry(route).or_default();
+            window.push_back(latency_ms);
+            if window.len() > self.config.window.max(1) {
+                window.pop_front();
+            }
+            let p95_ms = p95(window);
+
+            let status = self.status.entry(route).or_default();
+            status.calls += 1;
+            status.p95_ms = p95_ms;
+            if let (Some(limit_ms), Some(p95_ms)) = (limit_ms, p95_ms) {
+                if latency_ms > limit_ms {
+                    status.slow_calls += 1;
+                }
+                if p95_ms > limit_ms {
+                    if self.slow.insert(route) {
+                        violations.push(SlaViolation::Latency {
+                            route,
+                            p95_ms,
+                            limit_ms,
+                        });
+                    }
+                } else {
+                    self.slow.remove(&route);
+                }
+            }
+        }
+
+        let was_over_budget = self.over_budget(now_ms);
+        if now_ms / DAY_MS != self.day {
+            self.day = now_ms / DAY_MS;
+            self.spent_today = 0.0;
+        }
+        self.spent_today += cost_usd;
+        if let Some(limit) = self.config.daily_spend_limit {
+            if !was_over_budget && self.spent_today > limit {
+                violations.push(SlaViolation::DailySpend {
+                    spent: self.spent_today,
+                    limit,
+                });
+            }
+        }
+
+        violations
+    }
+
+    /// Whether `route` is currently violating its latency or spend SLA
+    pub fn is_violating(&self, route: RoutingDecision) -> bool {
+        self.slow.contains(&route) || (PAID_ROUTES.contains(&route) && self.spend_exceeded())
+    }
+
+    /// Routes currently violating their SLA
+    pub fn violating_routes(&self) -> Vec<RoutingDecision> {
+        [
+            RoutingDecision::Local,
+            RoutingDecision::Remote,
+            RoutingDecision::Hybrid,
+        ]
+        .into_iter()
+        .filter(|route| self.is_violating(*route))
+        .collect()
+    }
+
+    /// Per-route status for dashboards and reports
+    pub fn status(&self, route: RoutingDecision) -> RouteSlaStatus {
+        let mut status = self.status.get(&route).cloned().unwrap_or_default();
+        status.violating = self.is_violating(route);
+        status
+    }
+
+    /// Spend recorded on the current day (USD)
+    pub fn spent_today(&self) -> f64 {
+        self.spent_today
+    }
+
+    /// Declared SLAs
+    pub fn config(&self) -> &SlaConfig {
+        &self.config
+    }
+
+    fn spend_exceeded(&self) -> bool {
+        self.config
+            .daily_spend_limit
+            .is_some_and(|limit| self.spent_today > limit)
+    }
+
+    /// Whether the spend SLA was already violated on the day of `now_ms`
+    fn over_budget(&self, now_ms: u64) -> bool {
+        now_ms / DAY_MS == self.day && self.spend_exceeded()
+    }
+}
+
+/// Nearest-rank 95th percentile
+fn p95(values: &VecDeque<u64>) -> Option<u64> {
+    let mut sorted: Vec<u64> = values.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = (sorted.len() as f32 * 0.95).ceil() as usize;
+    sorted.get(rank.checked_sub(1)?).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> SlaTracker {
+        SlaTracker::new(SlaConfig {
+            local: RouteSla {
+                max_latency_ms: Some(3_000),
+            },
+            daily_spend_limit: Some(1.0),
+            window: 5,
+            ..SlaConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_latency_violation_starts_and_clears() {
+        let mut sla = tracker();
+        for _ in 0..5 {
+            assert!(sla.record(RoutingDecision::Local, 1_000, 0.0, 0).is_empty());
+        }
+
+        let violations = sla.record(RoutingDecision::Local, 5_000, 0.0, 0);
+        assert_eq!(
+            violations,
+            vec![SlaViolation::Latency {
+                route: RoutingDecision::Local,
+                p95_ms: 5_000,
+                limit_ms: 3_000,
+            }]
+        );
+        assert!(sla.is_violating(RoutingDecision::Local));
+        // Reported once, not on every slow call
+        assert!(sla.record(RoutingDecision::Local, 5_000, 0.0, 0).is_empty());
+        assert_eq!(sla.status(RoutingDecision::Local).slow_calls, 2);
+
+        for _ in 0..5 {
+            sla.record(RoutingDecision::Local, 1_000, 0.0, 0);
+        }
+        assert!(!sla.is_violating(RoutingDecision::Local));
+    }
+
+    #[test]
+    fn test_daily_spend_violation_resets_next_day() {
+        let mut sla = tracker();
+        assert!(sla.record(RoutingDecision::Remote, 500, 0.6, 0).is_empty());
+
+        let violations = sla.record(RoutingDecision::Remote, 500, 0.6, 1_000);
+        assert_eq!(violations.len(), 1);
+        assert!(sla.is_violating(RoutingDecision::Remote));
+        assert!(sla.is_violating(RoutingDecision::Hybrid));
+        assert!(!sla.is_violating(RoutingDecision::Local));
+        assert!(sla
+            .record(RoutingDecision::Remote, 500, 0.6, 2_000)
+            .is_empty());
+
+        assert!(sla
+            .record(RoutingDecision::Remote, 500, 0.1, DAY_MS)
+            .is_empty());
+        assert!(sla.violating_routes().is_empty());
+    }
+
+    #[test]
+    fn test_routes_without_sla_never_violate() {
+        let mut sla = SlaTracker::default();
+        for _ in 0..50 {
+            assert!(sla
+                .record(RoutingDecision::Remote, 60_000, 10.0, 0)
+                .is_empty());
+        }
+        assert!(sla.violating_routes().is_empty());
+    }
+}
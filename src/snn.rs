@@ -11,7 +11,234 @@
 
 #![forbid(unsafe_code)]
 
+use crate::rng::SeededRng;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Seed used by [`SpikingNetwork::new`]
+const DEFAULT_SEED: u64 = 789;
+
+/// Refractory period of neurons built by [`LIFNeuron::new`], in steps
+const DEFAULT_REFRACTORY: u32 = 5;
+
+/// Distance from rest below which a leaking neuron counts as at rest and
+/// stops being updated
+const SETTLED: f32 = 1e-6;
+
+fn default_refractory() -> u32 {
+    DEFAULT_REFRACTORY
+}
+
+/// Parameters shared by the LIF neurons of one layer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeuronParams {
+    /// Potential at which the neuron fires
+    pub threshold: f32,
+    /// Membrane time constant; larger values leak more slowly
+    pub tau: f32,
+    /// Steps the neuron stays silent after firing
+    pub refractory: u32,
+}
+
+impl Default for NeuronParams {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            tau: 10.0,
+            refractory: DEFAULT_REFRACTORY,
+        }
+    }
+}
+
+/// Topology and neuron parameters of a [`SpikingNetwork`]
+///
+/// Sparser connectivity and higher thresholds mean fewer spikes and less
+/// work per step; denser, more excitable networks separate inputs better.
+/// The defaults reproduce [`SpikingNetwork::new`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnnConfig {
+    /// Input layer neurons
+    pub input: NeuronParams,
+    /// Hidden layer neurons
+    pub hidden: NeuronParams,
+    /// Output layer neurons
+    pub output: NeuronParams,
+    /// Fraction of possible input→hidden and hidden→output synapses present
+    pub connectivity: f32,
+    /// Fraction of input and hidden neurons whose outgoing synapses all
+    /// inhibit; 0 leaves synapse signs mixed
+    pub inhibitory_fraction: f32,
+    /// Fraction of possible hidden→hidden synapses present; recurrent
+    /// spikes arrive one step later. 0 keeps the network feed-forward
+    pub recurrent_connectivity: f32,
+    /// Current injected into an input neuron by an input spike
+    pub input_current: f32,
+    /// Seed of the synaptic weights
+    pub seed: u64,
+}
+
+impl Default for SnnConfig {
+    fn default() -> Self {
+        Self {
+            input: NeuronParams::default(),
+            hidden: NeuronParams::default(),
+            output: NeuronParams::default(),
+            connectivity: 0.2,
+            inhibitory_fraction: 0.0,
+            recurrent_connectivity: 0.0,
+            input_current: 2.0,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl SnnConfig {
+    /// Check that densities and fractions lie in `[0, 1]` and neuron
+    /// parameters are positive
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("connectivity", self.connectivity),
+            ("inhibitory_fraction", self.inhibitory_fraction),
+            ("recurrent_connectivity", self.recurrent_connectivity),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        for (layer, params) in [
+            ("input", self.input),
+            ("hidden", self.hidden),
+            ("output", self.output),
+        ] {
+            if !(params.threshold > 0.0 && params.tau > 0.0) {
+                return Err(format!("{} threshold and tau must be positive", layer));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether neuron `index` is one of the evenly spread `fraction` of its
+/// layer that inhibit
+fn is_inhibitory(index: usize, fraction: f32) -> bool {
+    ((index + 1) as f32 * fraction).floor() > (index as f32 * fraction).floor()
+}
+
+/// Outgoing synapse of a neuron
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Synapse {
+    /// Index of the postsynaptic neuron in the next layer
+    target: u32,
+    /// Current delivered when the presynaptic neuron fires
+    weight: f32,
+}
+
+/// Outgoing synapses of `sources` neurons onto `targets` neurons, each
+/// present with probability `density`; synapses from inhibitory sources
+/// are negative. With `recurrent`, neurons do not synapse onto themselves
+fn random_synapses(
+    rng: &mut SeededRng,
+    sources: usize,
+    targets: usize,
+    density: f32,
+    inhibitory_fraction: f32,
+    recurrent: bool,
+) -> Vec<Vec<Synapse>> {
+    let mut synapses = vec![Vec::new(); sources];
+    // Drawn target by target so a seed yields the same weights as the
+    // dense matrices this replaced
+    for target in 0..targets {
+        for (source, outgoing) in synapses.iter_mut().enumerate() {
+            // Weight drawn independently of the connection test: reusing
+            // the draw would make every weight of a sparse layer negative
+            // and leave `inhibitory_fraction` 0 all-inhibitory
+            if rng.next_f32() < density {
+                let mut weight = (rng.next_f32() - 0.25) * 2.0;
+                if is_inhibitory(source, inhibitory_fraction) {
+                    weight = -weight.abs();
+                }
+                if !(recurrent && source == target) {
+                    outgoing.push(Synapse {
+                        target: target as u32,
+                        weight,
+                    });
+                }
+            }
+        }
+    }
+    synapses
+}
+
+/// A layer of neurons, updated only where something is happening
+///
+/// A neuron at rest that receives no current stays at rest, so it is
+/// skipped. Neurons enter the active list when current arrives and leave
+/// it once they have leaked back to rest and finished their refractory
+/// period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Layer {
+    neurons: Vec<LIFNeuron>,
+    /// Current delivered to each neuron this step
+    current: Vec<f32>,
+    /// Neurons to update this step
+    active: Vec<usize>,
+    /// Whether each neuron is in `active`
+    queued: Vec<bool>,
+}
+
+impl Layer {
+    fn new(size: usize, params: &NeuronParams) -> Self {
+        Self {
+            neurons: (0..size).map(|_| LIFNeuron::with_params(params)).collect(),
+            current: vec![0.0; size],
+            active: Vec::new(),
+            queued: vec![false; size],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.neurons.len()
+    }
+
+    /// Deliver `amount` of current to `neuron` this step
+    fn inject(&mut self, neuron: usize, amount: f32) {
+        self.current[neuron] += amount;
+        if !self.queued[neuron] {
+            self.queued[neuron] = true;
+            self.active.push(neuron);
+        }
+    }
+
+    /// Advance the active neurons one step, returning those that fired
+    fn step(&mut self, dt: f32) -> Vec<usize> {
+        let mut fired = Vec::new();
+        let active = std::mem::take(&mut self.active);
+        for index in active {
+            let neuron = &mut self.neurons[index];
+            let current = std::mem::take(&mut self.current[index]);
+            if neuron.update(current, dt) {
+                fired.push(index);
+            }
+            if neuron.settle() {
+                self.queued[index] = false;
+            } else {
+                self.active.push(index);
+            }
+        }
+        fired
+    }
+
+    fn reset(&mut self) {
+        for neuron in &mut self.neurons {
+            neuron.reset();
+        }
+        self.current.fill(0.0);
+        self.active.clear();
+        self.queued.fill(false);
+    }
+}
 
 /// Leaky Integrate-and-Fire neuron model
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +253,9 @@ pub struct LIFNeuron {
     pub tau: f32,
     /// Refractory period counter
     pub refractory: u32,
+    /// Steps the refractory counter starts from after a spike
+    #[serde(default = "default_refractory")]
+    pub refractory_period: u32,
 }
 
 impl LIFNeuron {
@@ -37,6 +267,15 @@ pub fn new(threshold: f32, tau: f32) -> Self {
             threshold,
             tau,
             refractory: 0,
+            refractory_period: DEFAULT_REFRACTORY,
+        }
+    }
+
+    /// Create a neuron from layer parameters
+    pub fn with_params(params: &NeuronParams) -> Self {
+        Self {
+            refractory_period: params.refractory,
+            ..Self::new(params.threshold, params.tau)
         }
     }
 
@@ -64,7 +303,7 @@ pub fn update(&mut self, input_current: f32, dt: f32) -> bool {
         // Check for spike
         if self.potential >= self.threshold {
             self.potential = self.rest_potential;
-            self.refractory = 5; // 5ms refractory period
+            self.refractory = self.refractory_period;
             true
         } else {
             false
@@ -76,23 +315,170 @@ pub fn reset(&mut self) {
         self.potential = self.rest_potential;
         self.refractory = 0;
     }
+
+    /// Snap a neuron that has leaked to within `SETTLED` of rest onto
+    /// rest; returns whether it is now idle
+    fn settle(&mut self) -> bool {
+        if self.refractory > 0 {
+            return false;
+        }
+        if (self.potential - self.rest_potential).abs() < SETTLED {
+            self.potential = self.rest_potential;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Layer a recorded spike came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpikeLayer {
+    /// Input layer
+    Input,
+    /// Hidden layer
+    Hidden,
+    /// Output layer
+    Output,
+}
+
+impl SpikeLayer {
+    /// Lowercase name, as written to CSV
+    pub const fn name(&self) -> &'static str {
+        match self {
+            SpikeLayer::Input => "input",
+            SpikeLayer::Hidden => "hidden",
+            SpikeLayer::Output => "output",
+        }
+    }
+}
+
+/// One spike in a raster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpikeEvent {
+    /// Step the spike happened on, counted from when recording started
+    pub step: u64,
+    /// Layer of the neuron
+    pub layer: SpikeLayer,
+    /// Index of the neuron within its layer
+    pub neuron: usize,
+}
+
+/// Spike raster of a network, keeping the most recent `capacity` spikes
+///
+/// Enable it with [`SpikingNetwork::record_spikes`] to see which neurons
+/// fire, and when, on the way to a detection (or to a missed one). Older
+/// spikes are dropped once the raster is full, so a recorder left on in
+/// a long-running detector holds bounded memory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpikeRecorder {
+    capacity: usize,
+    /// Steps recorded so far
+    steps: u64,
+    /// Spikes dropped to stay within `capacity`
+    dropped: u64,
+    events: VecDeque<SpikeEvent>,
+}
+
+impl SpikeRecorder {
+    /// Create a recorder holding at most `capacity` spikes (at least one)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            steps: 0,
+            dropped: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, layer: SpikeLayer, fired: &[usize]) {
+        for &neuron in fired {
+            if self.events.len() == self.capacity {
+                self.events.pop_front();
+                self.dropped += 1;
+            }
+            self.events.push_back(SpikeEvent {
+                step: self.steps,
+                layer,
+                neuron,
+            });
+        }
+    }
+
+    /// Recorded spikes, oldest first
+    pub fn events(&self) -> impl Iterator<Item = &SpikeEvent> {
+        self.events.iter()
+    }
+
+    /// Steps recorded so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Spikes dropped because the raster was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Forget recorded spikes and restart the step count
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.steps = 0;
+        self.dropped = 0;
+    }
+
+    /// Serialize the raster, including its step and drop counts
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("cannot encode spike raster: {}", e))
+    }
+
+    /// Raster as `step,layer,neuron` rows with a header, for plotting tools
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,layer,neuron\n");
+        for event in &self.events {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                event.step,
+                event.layer.name(),
+                event.neuron
+            ));
+        }
+        csv
+    }
 }
 
 /// Simple Spiking Neural Network
+///
+/// Updates are event-driven: synapses are stored as per-neuron adjacency
+/// lists, a step only follows the synapses of neurons that fired, and only
+/// neurons that received current or are still settling are updated. Work
+/// per step therefore scales with spike activity rather than network size,
+/// and a silent network costs next to nothing.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpikingNetwork {
     /// Input layer neurons
-    input_neurons: Vec<LIFNeuron>,
+    input: Layer,
     /// Hidden layer neurons
-    hidden_neurons: Vec<LIFNeuron>,
+    hidden: Layer,
     /// Output layer neurons
-    output_neurons: Vec<LIFNeuron>,
-    /// Synaptic weights (input → hidden)
-    weights_ih: Vec<Vec<f32>>,
-    /// Synaptic weights (hidden → output)
-    weights_ho: Vec<Vec<f32>>,
+    output: Layer,
+    /// Outgoing synapses of each input neuron (input → hidden)
+    synapses_ih: Vec<Vec<Synapse>>,
+    /// Outgoing synapses of each hidden neuron (hidden → output)
+    synapses_ho: Vec<Vec<Synapse>>,
+    /// Recurrent synapses of each hidden neuron (hidden → hidden); empty
+    /// when the network is feed-forward
+    synapses_hh: Vec<Vec<Synapse>>,
+    /// Hidden neurons that fired on the previous step
+    hidden_fired: Vec<usize>,
+    /// Parameters the network was built with
+    config: SnnConfig,
     /// Spike history (for analysis)
     spike_counts: Vec<usize>,
+    /// Spike raster, when recording
+    #[serde(skip)]
+    recorder: Option<SpikeRecorder>,
 }
 
 impl SpikingNetwork {
@@ -104,53 +490,58 @@ impl SpikingNetwork {
     /// * `n_hidden` - Number of hidden neurons
     /// * `n_output` - Number of output neurons
     pub fn new(n_input: usize, n_hidden: usize, n_output: usize) -> Self {
-        let input_neurons = (0..n_input)
-            .map(|_| LIFNeuron::new(1.0, 10.0))
-            .collect();
-
-        let hidden_neurons = (0..n_hidden)
-            .map(|_| LIFNeuron::new(1.0, 10.0))
-            .collect();
+        Self::new_with_seed(n_input, n_hidden, n_output, DEFAULT_SEED)
+    }
 
-        let output_neurons = (0..n_output)
-            .map(|_| LIFNeuron::new(1.0, 10.0))
-            .collect();
+    /// Create a spiking neural network whose synaptic weights derive from `seed`
+    pub fn new_with_seed(n_input: usize, n_hidden: usize, n_output: usize, seed: u64) -> Self {
+        let config = SnnConfig {
+            seed,
+            ..SnnConfig::default()
+        };
+        Self::with_config(n_input, n_hidden, n_output, config)
+    }
 
+    /// Create a spiking neural network with the given topology and neuron
+    /// parameters
+    pub fn with_config(
+        n_input: usize,
+        n_hidden: usize,
+        n_output: usize,
+        config: SnnConfig,
+    ) -> Self {
         // Random sparse weights
-        let mut seed = 789u64;
-        let mut weights_ih = vec![vec![0.0; n_input]; n_hidden];
-        for row in &mut weights_ih {
-            for w in row {
-                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-                if rand < 0.2 {
-                    // 20% connectivity
-                    *w = (rand - 0.5) * 0.5;
-                }
-            }
-        }
-
-        let mut weights_ho = vec![vec![0.0; n_hidden]; n_output];
-        for row in &mut weights_ho {
-            for w in row {
-                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-                let rand = ((seed / 65536) % 32768) as f32 / 32768.0;
-                if rand < 0.2 {
-                    *w = (rand - 0.5) * 0.5;
-                }
-            }
-        }
+        let mut rng = SeededRng::new(config.seed);
+        let density = config.connectivity;
+        let inhibitory = config.inhibitory_fraction;
+        let synapses_ih = random_synapses(&mut rng, n_input, n_hidden, density, inhibitory, false);
+        let synapses_ho = random_synapses(&mut rng, n_hidden, n_output, density, inhibitory, false);
+        let synapses_hh = if config.recurrent_connectivity > 0.0 {
+            let density = config.recurrent_connectivity;
+            random_synapses(&mut rng, n_hidden, n_hidden, density, inhibitory, true)
+        } else {
+            Vec::new()
+        };
 
         Self {
-            input_neurons,
-            hidden_neurons,
-            output_neurons,
-            weights_ih,
-            weights_ho,
+            input: Layer::new(n_input, &config.input),
+            hidden: Layer::new(n_hidden, &config.hidden),
+            output: Layer::new(n_output, &config.output),
+            synapses_ih,
+            synapses_ho,
+            synapses_hh,
+            hidden_fired: Vec::new(),
             spike_counts: vec![0; n_output],
+            config,
+            recorder: None,
         }
     }
 
+    /// Topology and neuron parameters the network was built with
+    pub fn config(&self) -> &SnnConfig {
+        &self.config
+    }
+
     /// Process one time step
     ///
     /// # Arguments
@@ -162,55 +553,53 @@ pub fn new(n_input: usize, n_hidden: usize, n_output: usize) -> Self {
     ///
     /// Vector of output spike indicators
     pub fn step(&mut self, input_spikes: &[bool], dt: f32) -> Vec<bool> {
-        assert_eq!(input_spikes.len(), self.input_neurons.len());
+        assert_eq!(input_spikes.len(), self.input.len());
 
         // Update input layer
-        for (i, neuron) in self.input_neurons.iter_mut().enumerate() {
-            if input_spikes[i] {
-                neuron.update(2.0, dt);
-            } else {
-                neuron.update(0.0, dt);
-            }
+        let current = self.config.input_current;
+        for (i, _) in input_spikes.iter().enumerate().filter(|(_, &spike)| spike) {
+            self.input.inject(i, current);
+        }
+        let input_fired = self.input.step(dt);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(SpikeLayer::Input, &input_fired);
         }
 
-        // Compute hidden layer currents
-        let mut hidden_currents = vec![0.0; self.hidden_neurons.len()];
-        for (i, neuron) in self.input_neurons.iter().enumerate() {
-            if neuron.potential > 0.5 {
-                // Approximate spike
-                for (h, current) in hidden_currents.iter_mut().enumerate() {
-                    *current += self.weights_ih[h][i];
-                }
+        // Deliver input and last step's recurrent spikes to the hidden layer
+        for &i in &input_fired {
+            for synapse in &self.synapses_ih[i] {
+                self.hidden.inject(synapse.target as usize, synapse.weight);
             }
         }
-
-        // Update hidden layer
-        for (neuron, &current) in self.hidden_neurons.iter_mut().zip(&hidden_currents) {
-            neuron.update(current, dt);
+        for h in std::mem::take(&mut self.hidden_fired) {
+            for synapse in &self.synapses_hh[h] {
+                self.hidden.inject(synapse.target as usize, synapse.weight);
+            }
+        }
+        let hidden_fired = self.hidden.step(dt);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(SpikeLayer::Hidden, &hidden_fired);
         }
 
-        // Compute output layer currents
-        let mut output_currents = vec![0.0; self.output_neurons.len()];
-        for (h, neuron) in self.hidden_neurons.iter().enumerate() {
-            if neuron.potential > 0.5 {
-                for (o, current) in output_currents.iter_mut().enumerate() {
-                    *current += self.weights_ho[o][h];
-                }
+        // Deliver hidden spikes to the output layer
+        for &h in &hidden_fired {
+            for synapse in &self.synapses_ho[h] {
+                self.output.inject(synapse.target as usize, synapse.weight);
             }
         }
+        if !self.synapses_hh.is_empty() {
+            self.hidden_fired = hidden_fired;
+        }
 
-        // Update output layer
-        let mut output_spikes = vec![false; self.output_neurons.len()];
-        for (i, (neuron, &current)) in self
-            .output_neurons
-            .iter_mut()
-            .zip(&output_currents)
-            .enumerate()
-        {
-            if neuron.update(current, dt) {
-                output_spikes[i] = true;
-                self.spike_counts[i] += 1;
-            }
+        let output_fired = self.output.step(dt);
+        let mut output_spikes = vec![false; self.output.len()];
+        for &o in &output_fired {
+            output_spikes[o] = true;
+            self.spike_counts[o] += 1;
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(SpikeLayer::Output, &output_fired);
+            recorder.steps += 1;
         }
 
         output_spikes
@@ -218,18 +607,44 @@ pub fn step(&mut self, input_spikes: &[bool], dt: f32) -> Vec<bool> {
 
     /// Reset all neurons
     pub fn reset(&mut self) {
-        for neuron in &mut self.input_neurons {
-            neuron.reset();
-        }
-        for neuron in &mut self.hidden_neurons {
-            neuron.reset();
-        }
-        for neuron in &mut self.output_neurons {
-            neuron.reset();
-        }
+        self.input.reset();
+        self.hidden.reset();
+        self.output.reset();
+        self.hidden_fired.clear();
         self.spike_counts.fill(0);
     }
 
+    /// Start recording spikes into a raster of at most `capacity` spikes,
+    /// replacing any recording in progress; recording survives `reset`
+    pub fn record_spikes(&mut self, capacity: usize) {
+        self.recorder = Some(SpikeRecorder::new(capacity));
+    }
+
+    /// Spike raster recorded so far, if recording
+    pub fn recorder(&self) -> Option<&SpikeRecorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Stop recording and return the raster
+    pub fn take_recording(&mut self) -> Option<SpikeRecorder> {
+        self.recorder.take()
+    }
+
+    /// Number of synapses in the network
+    pub fn synapse_count(&self) -> usize {
+        [&self.synapses_ih, &self.synapses_ho, &self.synapses_hh]
+            .iter()
+            .flat_map(|layer| layer.iter())
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Neurons the next step will update even without input spikes; 0
+    /// once the network has gone quiet
+    pub fn active_neurons(&self) -> usize {
+        self.input.active.len() + self.hidden.active.len() + self.output.active.len()
+    }
+
     /// Get spike counts for output neurons
     pub fn spike_counts(&self) -> &[usize] {
         &self.spike_counts
@@ -281,9 +696,9 @@ fn test_lif_neuron_reset() {
     #[test]
     fn test_spiking_network_creation() {
         let snn = SpikingNetwork::new(10, 20, 3);
-        assert_eq!(snn.input_neurons.len(), 10);
-        assert_eq!(snn.hidden_neurons.len(), 20);
-        assert_eq!(snn.output_neurons.len(), 3);
+        assert_eq!(snn.input.len(), 10);
+        assert_eq!(snn.hidden.len(), 20);
+        assert_eq!(snn.output.len(), 3);
     }
 
     #[test]
@@ -311,6 +726,161 @@ fn test_spiking_network_reset() {
         assert!(snn.spike_counts().iter().all(|&c| c == 0));
     }
 
+    #[test]
+    fn test_spiking_network_seeded_construction() {
+        let a = SpikingNetwork::new_with_seed(10, 20, 3, 5);
+        let b = SpikingNetwork::new_with_seed(10, 20, 3, 5);
+        let c = SpikingNetwork::new_with_seed(10, 20, 3, 6);
+
+        assert_eq!(a.synapses_ih, b.synapses_ih);
+        assert_eq!(a.synapses_ho, b.synapses_ho);
+        assert_ne!(a.synapses_ih, c.synapses_ih);
+    }
+
+    #[test]
+    fn test_spiking_network_config() {
+        let default = SpikingNetwork::with_config(10, 20, 3, SnnConfig::default());
+        let legacy = SpikingNetwork::new(10, 20, 3);
+        assert_eq!(default.synapses_ih, legacy.synapses_ih);
+        assert!(default.synapses_hh.is_empty());
+        // No inhibitory neurons leaves signs mixed
+        let weights = || default.synapses_ih.iter().flatten().map(|s| s.weight);
+        assert!(weights().any(|w| w > 0.0) && weights().any(|w| w < 0.0));
+
+        let bad = SnnConfig {
+            connectivity: 1.5,
+            ..SnnConfig::default()
+        };
+        assert!(bad.validate().is_err_and(|e| e.contains("connectivity")));
+
+        // All-inhibitory synapses can never drive the hidden layer
+        let config = SnnConfig {
+            connectivity: 1.0,
+            inhibitory_fraction: 1.0,
+            recurrent_connectivity: 0.5,
+            hidden: NeuronParams {
+                refractory: 0,
+                ..NeuronParams::default()
+            },
+            ..SnnConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        let mut snn = SpikingNetwork::with_config(4, 6, 2, config);
+        assert!(snn.synapses_ih.iter().flatten().all(|s| s.weight < 0.0));
+        let self_synapse = |h: usize| snn.synapses_hh[h].iter().any(|s| s.target as usize == h);
+        assert!(!(0..6).any(self_synapse));
+        assert_eq!(snn.hidden.neurons[0].refractory_period, 0);
+        for _ in 0..20 {
+            snn.step(&[true; 4], 1.0);
+        }
+        assert_eq!(snn.spike_counts(), &[0, 0]);
+    }
+
+    #[test]
+    fn test_event_driven_step_matches_dense_update() {
+        let config = SnnConfig {
+            recurrent_connectivity: 0.1,
+            ..SnnConfig::default()
+        };
+        let (n_input, n_hidden, n_output) = (12, 30, 4);
+        let mut snn = SpikingNetwork::with_config(n_input, n_hidden, n_output, config.clone());
+        let dense = |synapses: &[Vec<Synapse>], targets: usize| {
+            let mut weights = vec![vec![0.0; synapses.len()]; targets];
+            for (source, outgoing) in synapses.iter().enumerate() {
+                for synapse in outgoing {
+                    weights[synapse.target as usize][source] = synapse.weight;
+                }
+            }
+            weights
+        };
+        let (w_ih, w_hh, w_ho) = (
+            dense(&snn.synapses_ih, n_hidden),
+            dense(&snn.synapses_hh, n_hidden),
+            dense(&snn.synapses_ho, n_output),
+        );
+
+        // Reference: every neuron and every synapse, every step
+        let neurons = |n: usize| vec![LIFNeuron::with_params(&NeuronParams::default()); n];
+        let (mut input, mut hidden, mut output) =
+            (neurons(n_input), neurons(n_hidden), neurons(n_output));
+        let mut last_hidden = vec![false; n_hidden];
+        let mut rng = SeededRng::new(3);
+        for step in 0..300 {
+            // A burst of activity, then silence
+            let spikes: Vec<bool> = (0..n_input)
+                .map(|_| step < 150 && rng.next_f32() < 0.3)
+                .collect();
+            let input_fired: Vec<bool> = input
+                .iter_mut()
+                .zip(&spikes)
+                .map(|(n, &s)| n.update(if s { config.input_current } else { 0.0 }, 1.0))
+                .collect();
+            let hidden_fired: Vec<bool> = (0..n_hidden)
+                .map(|h| {
+                    let current: f32 = (0..n_input)
+                        .filter(|&i| input_fired[i])
+                        .map(|i| w_ih[h][i])
+                        .chain(
+                            (0..n_hidden)
+                                .filter(|&r| last_hidden[r])
+                                .map(|r| w_hh[h][r]),
+                        )
+                        .sum();
+                    hidden[h].update(current, 1.0)
+                })
+                .collect();
+            let expected: Vec<bool> = (0..n_output)
+                .map(|o| {
+                    let current: f32 = (0..n_hidden)
+                        .filter(|&h| hidden_fired[h])
+                        .map(|h| w_ho[o][h])
+                        .sum();
+                    output[o].update(current, 1.0)
+                })
+                .collect();
+            last_hidden = hidden_fired;
+
+            assert_eq!(snn.step(&spikes, 1.0), expected, "step {}", step);
+        }
+        assert!(snn.spike_counts().iter().any(|&c| c > 0));
+        assert_eq!(snn.active_neurons(), 0);
+        assert!(snn.synapse_count() > 0);
+    }
+
+    #[test]
+    fn test_spike_recorder_keeps_the_latest_spikes() {
+        let mut snn = SpikingNetwork::new(10, 20, 3);
+        assert!(snn.recorder().is_none());
+        snn.record_spikes(8);
+        for _ in 0..20 {
+            snn.step(&[true; 10], 1.0);
+        }
+        snn.reset();
+        snn.step(&[false; 10], 1.0);
+
+        let Some(raster) = snn.take_recording() else {
+            panic!("recording should be on");
+        };
+        assert!(snn.recorder().is_none());
+        assert_eq!(raster.steps(), 21);
+        assert_eq!(raster.events().count(), 8);
+        assert!(raster.dropped() > 0);
+        let steps: Vec<u64> = raster.events().map(|e| e.step).collect();
+        assert!(steps.windows(2).all(|w| w[0] <= w[1]));
+        assert!(raster.events().all(|e| e.step < 20));
+
+        let csv = raster.to_csv();
+        assert!(csv.starts_with("step,layer,neuron\n"));
+        assert_eq!(csv.lines().count(), 9);
+        let Ok(json) = raster.to_json() else {
+            panic!("to_json should succeed");
+        };
+        let Ok(decoded) = serde_json::from_str::<SpikeRecorder>(&json) else {
+            panic!("raster JSON should decode");
+        };
+        assert_eq!(decoded, raster);
+    }
+
     #[test]
     fn test_spiking_network_serialization() {
         let snn = SpikingNetwork::new(10, 20, 3);
@@ -0,0 +1,492 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Background Task Supervision
+//!
+//! Subsystems that work in the background (webhook delivery today;
+//! scheduling, sync and telemetry as they arrive) each used to own a
+//! detached thread. A thread that panicked stayed dead, and one still
+//! running at exit could be cut off halfway through a write.
+//!
+//! A [`Supervisor`] owns every background task instead:
+//!
+//! - **Restarts**: a task that returns an error or panics is started
+//!   again after an exponential backoff ([`RestartPolicy`]), until it has
+//!   failed `max_restarts` times in a row.
+//! - **Health**: [`Supervisor::health`] reports each task's state, restart
+//!   count and last error; the orchestrator includes it in `capabilities()`.
+//! - **Pause**: [`Supervisor::pause`] holds tasks while the app is in the
+//!   background, until [`Supervisor::resume`].
+//! - **Shutdown**: [`Supervisor::shutdown`] asks every task to stop and
+//!   joins its thread, so nothing outlives the orchestrator. Dropping the
+//!   supervisor does the same.
+//!
+//! Tasks cooperate through their [`TaskContext`]: long-running loops check
+//! [`TaskContext::should_stop`], sleep with [`TaskContext::wait`], which
+//! returns early when shutdown starts, and hold with
+//! [`TaskContext::wait_while_paused`] before each unit of work.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// When and how often a failed task is restarted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    /// Wait before the first restart
+    pub initial_backoff_ms: u64,
+    /// Longest wait between restarts; each failure doubles the wait
+    pub max_backoff_ms: u64,
+    /// Consecutive failures after which the task is given up
+    pub max_restarts: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 60_000,
+            max_restarts: 10,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Wait before restart number `attempt` (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        let ms = self.initial_backoff_ms.saturating_mul(factor);
+        Duration::from_millis(ms.min(self.max_backoff_ms))
+    }
+}
+
+/// Where a supervised task stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskState {
+    /// Running its body
+    Running,
+    /// Failed; waiting to be restarted
+    Backoff,
+    /// Returned successfully; not restarted
+    Finished,
+    /// Failed too often in a row; not restarted
+    GaveUp,
+    /// Stopped by shutdown
+    Stopped,
+}
+
+/// Health of one supervised task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskHealth {
+    /// Name the task was spawned under
+    pub name: String,
+    /// Current state
+    pub state: TaskState,
+    /// Restarts since the task was spawned
+    pub restarts: u32,
+    /// Most recent error or panic message
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Flags {
+    stopped: bool,
+    paused: bool,
+}
+
+/// Stop and pause signals shared by the supervisor and its tasks
+#[derive(Debug, Default)]
+struct StopSignal {
+    flags: Mutex<Flags>,
+    changed: Condvar,
+}
+
+impl StopSignal {
+    fn lock(&self) -> MutexGuard<'_, Flags> {
+        self.flags.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_set(&self) -> bool {
+        self.lock().stopped
+    }
+
+    fn set(&self) {
+        self.lock().stopped = true;
+        self.changed.notify_all();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.lock().paused = paused;
+        self.changed.notify_all();
+    }
+
+    /// Sleep up to `timeout`; true if stopped meanwhile
+    fn wait(&self, timeout: Duration) -> bool {
+        let guard = self.lock();
+        let (guard, _) = self
+            .changed
+            .wait_timeout_while(guard, timeout, |flags| !flags.stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+        guard.stopped
+    }
+
+    /// Block while paused; true if stopped
+    fn wait_while_paused(&self) -> bool {
+        let guard = self.lock();
+        let guard = self
+            .changed
+            .wait_while(guard, |flags| flags.paused && !flags.stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+        guard.stopped
+    }
+}
+
+/// Handle a task body uses to cooperate with shutdown
+#[derive(Debug, Clone)]
+pub struct TaskContext {
+    name: String,
+    stop: Arc<StopSignal>,
+}
+
+impl TaskContext {
+    /// Name of the task
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the task should return as soon as it can
+    pub fn should_stop(&self) -> bool {
+        self.stop.is_set()
+    }
+
+    /// Sleep for `duration`, waking early on shutdown; returns whether the
+    /// task should stop
+    pub fn wait(&self, duration: Duration) -> bool {
+        self.stop.wait(duration)
+    }
+
+    /// Whether the supervisor is paused
+    pub fn is_paused(&self) -> bool {
+        self.stop.is_paused()
+    }
+
+    /// Block while the supervisor is paused; returns whether the task
+    /// should stop
+    pub fn wait_while_paused(&self) -> bool {
+        self.stop.wait_while_paused()
+    }
+}
+
+/// Body of a supervised task, run again on every restart
+pub type TaskBody = Box<dyn FnMut(&TaskContext) -> Result<(), String> + Send>;
+
+struct Task {
+    health: Arc<Mutex<TaskHealth>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Owns, restarts and joins background tasks
+pub struct Supervisor {
+    policy: RestartPolicy,
+    stop: Arc<StopSignal>,
+    tasks: Vec<Task>,
+}
+
+impl std::fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("policy", &self.policy)
+            .field("tasks", &self.tasks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new(RestartPolicy::default())
+    }
+}
+
+impl Supervisor {
+    /// Supervisor restarting failed tasks by `policy`
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            stop: Arc::new(StopSignal::default()),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Run `body` on its own thread under supervision
+    pub fn spawn(&mut self, name: impl Into<String>, body: TaskBody) -> Result<(), String> {
+        let name = name.into();
+        if self.stop.is_set() {
+            return Err(format!("cannot start {}: supervisor is shut down", name));
+        }
+        let health = Arc::new(Mutex::new(TaskHealth {
+            name: name.clone(),
+            state: TaskState::Running,
+            restarts: 0,
+            last_error: None,
+        }));
+        let context = TaskContext {
+            name: name.clone(),
+            stop: Arc::clone(&self.stop),
+        };
+        let policy = self.policy.clone();
+        let task_health = Arc::clone(&health);
+        let thread = thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || run(body, &context, &policy, &task_health))
+            .map_err(|e| format!("failed to start {}: {}", name, e))?;
+        self.tasks.push(Task {
+            health,
+            thread: Some(thread),
+        });
+        Ok(())
+    }
+
+    /// Health of every task, in spawn order
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.tasks
+            .iter()
+            .map(|task| lock(&task.health).clone())
+            .collect()
+    }
+
+    /// Hold tasks at their next `wait_while_paused`, returning how many
+    /// are running or waiting to restart
+    pub fn pause(&self) -> usize {
+        self.stop.set_paused(true);
+        self.tasks
+            .iter()
+            .filter(|task| {
+                let state = lock(&task.health).state;
+                matches!(state, TaskState::Running | TaskState::Backoff)
+            })
+            .count()
+    }
+
+    /// Let paused tasks continue
+    pub fn resume(&self) {
+        self.stop.set_paused(false);
+    }
+
+    /// Whether tasks are held by `pause`
+    pub fn is_paused(&self) -> bool {
+        self.stop.is_paused()
+    }
+
+    /// Stop every task and join its thread; returns how many were joined.
+    /// No task can be spawned afterwards.
+    pub fn shutdown(&mut self) -> usize {
+        self.stop.set();
+        let mut joined = 0;
+        for task in &mut self.tasks {
+            if let Some(thread) = task.thread.take() {
+                // The body's panics are caught inside the thread
+                let _ = thread.join();
+                joined += 1;
+            }
+        }
+        joined
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn lock(health: &Mutex<TaskHealth>) -> MutexGuard<'_, TaskHealth> {
+    health.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Run `body` until it succeeds, shutdown starts or it fails too often
+fn run(
+    mut body: TaskBody,
+    context: &TaskContext,
+    policy: &RestartPolicy,
+    health: &Mutex<TaskHealth>,
+) {
+    let mut failures = 0;
+    loop {
+        lock(health).state = TaskState::Running;
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| body(context)));
+        let error = match outcome {
+            Ok(Ok(())) => {
+                lock(health).state = if context.should_stop() {
+                    TaskState::Stopped
+                } else {
+                    TaskState::Finished
+                };
+                return;
+            }
+            Ok(Err(e)) => e,
+            Err(payload) => panic_message(payload.as_ref()),
+        };
+
+        failures += 1;
+        let mut state = lock(health);
+        state.last_error = Some(error);
+        if context.should_stop() {
+            state.state = TaskState::Stopped;
+            return;
+        }
+        if failures > policy.max_restarts {
+            state.state = TaskState::GaveUp;
+            return;
+        }
+        state.state = TaskState::Backoff;
+        drop(state);
+
+        if context.wait(policy.backoff(failures)) {
+            lock(health).state = TaskState::Stopped;
+            return;
+        }
+        lock(health).restarts += 1;
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Instant;
+
+    fn fast() -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+            max_restarts: 3,
+        }
+    }
+
+    fn wait_for(supervisor: &Supervisor, state: TaskState) -> TaskHealth {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let health = supervisor.health().remove(0);
+            if health.state == state || Instant::now() > deadline {
+                return health;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_millis(2_000));
+        assert_eq!(policy.backoff(40), Duration::from_millis(60_000));
+    }
+
+    #[test]
+    fn test_failing_tasks_restart_then_give_up() {
+        let mut supervisor = Supervisor::new(fast());
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&runs);
+        let spawned = supervisor.spawn(
+            "flaky",
+            Box::new(move |_| {
+                // Panics once, errors once, then succeeds
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => panic!("boom"),
+                    1 => Err("disk full".to_string()),
+                    _ => Ok(()),
+                }
+            }),
+        );
+        assert_eq!(spawned, Ok(()));
+        let health = wait_for(&supervisor, TaskState::Finished);
+        assert_eq!(health.state, TaskState::Finished);
+        assert_eq!(health.restarts, 2);
+        assert_eq!(health.last_error.as_deref(), Some("disk full"));
+
+        let mut broken = Supervisor::new(fast());
+        let spawned = broken.spawn("broken", Box::new(|_| Err("no network".to_string())));
+        assert_eq!(spawned, Ok(()));
+        let health = wait_for(&broken, TaskState::GaveUp);
+        assert_eq!(health.state, TaskState::GaveUp);
+        assert_eq!(health.restarts, 3);
+    }
+
+    #[test]
+    fn test_paused_tasks_hold_until_resumed_or_stopped() {
+        let mut supervisor = Supervisor::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&runs);
+        let spawned = supervisor.spawn(
+            "sync",
+            Box::new(move |context| {
+                while !context.wait_while_paused() {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    if context.wait(Duration::from_millis(1)) {
+                        break;
+                    }
+                }
+                Ok(())
+            }),
+        );
+        assert_eq!(spawned, Ok(()));
+        assert_eq!(supervisor.pause(), 1);
+        assert!(supervisor.is_paused());
+        // Let the task reach its pause point
+        thread::sleep(Duration::from_millis(20));
+        let held = runs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(runs.load(Ordering::SeqCst), held);
+
+        supervisor.resume();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while runs.load(Ordering::SeqCst) == held && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(runs.load(Ordering::SeqCst) > held);
+
+        // Shutdown releases a paused task too
+        supervisor.pause();
+        assert_eq!(supervisor.shutdown(), 1);
+    }
+
+    #[test]
+    fn test_shutdown_stops_and_joins_every_task() {
+        let mut supervisor = Supervisor::default();
+        for name in ["sync", "telemetry"] {
+            let spawned = supervisor.spawn(
+                name,
+                Box::new(|context| {
+                    while !context.wait(Duration::from_secs(60)) {}
+                    Ok(())
+                }),
+            );
+            assert_eq!(spawned, Ok(()));
+        }
+        assert_eq!(wait_for(&supervisor, TaskState::Running).name, "sync");
+
+        let started = Instant::now();
+        assert_eq!(supervisor.shutdown(), 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let states: Vec<TaskState> = supervisor.health().iter().map(|h| h.state).collect();
+        assert_eq!(states, [TaskState::Stopped, TaskState::Stopped]);
+        assert!(supervisor.spawn("late", Box::new(|_| Ok(()))).is_err());
+        assert_eq!(supervisor.shutdown(), 0);
+    }
+}
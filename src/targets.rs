@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Custom Routing Targets
+//!
+//! Besides the built-in Local, Remote and Hybrid routes, a host app can
+//! register its own execution targets: a companion watch, a home server,
+//! an enterprise gateway. Each [`RouteTarget`] brings the
+//! [`TargetBackend`] that answers queries, a [`RouteCost`] model, and the
+//! [`RouteConstraints`] the routing policy checks against device state.
+//!
+//! Registered targets become `RoutingDecision::Custom(i)` in registration
+//! order, and class `3 + i` of the routing MLP. A router model has to be
+//! trained with the same targets, in the same order, before it can be
+//! loaded (see `RouterModel::with_targets`).
+
+#![forbid(unsafe_code)]
+
+use crate::policy::{RouteConstraints, RouteCost};
+use crate::training::BUILTIN_ROUTE_CLASSES;
+use crate::types::{ModelCapability, Query, RoutingDecision};
+use std::fmt;
+use std::sync::Arc;
+
+/// Produces answers for a custom target
+pub trait TargetBackend: Send + Sync {
+    /// Answer `query`
+    fn generate(&self, query: &Query) -> Result<String, String>;
+
+    /// Answer `query`, passing each piece of the answer to `on_chunk` as
+    /// it is produced, and return the whole answer. By default the answer
+    /// is one piece; backends that decode token by token override this.
+    fn generate_streaming(
+        &self,
+        query: &Query,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> Result<String, String> {
+        let answer = self.generate(query)?;
+        on_chunk(&answer);
+        Ok(answer)
+    }
+}
+
+/// A host-registered routing target
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct RouteTarget {
+    /// Unique name, also reported as the response's model
+    pub name: String,
+    /// Expected cost of one query
+    pub cost: RouteCost,
+    /// What the target needs from the device
+    pub constraints: RouteConstraints,
+    /// Model capabilities the target offers
+    pub capabilities: Vec<ModelCapability>,
+    /// Backend answering queries routed here
+    pub backend: Arc<dyn TargetBackend>,
+}
+
+impl fmt::Debug for RouteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RouteTarget")
+            .field("name", &self.name)
+            .field("cost", &self.cost)
+            .field("constraints", &self.constraints)
+            .field("capabilities", &self.capabilities)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RouteTarget {
+    /// Create a target served by `backend`, declaring no model
+    /// capabilities
+    pub fn new(
+        name: impl Into<String>,
+        cost: RouteCost,
+        constraints: RouteConstraints,
+        backend: Arc<dyn TargetBackend>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            cost,
+            constraints,
+            capabilities: Vec::new(),
+            backend,
+        }
+    }
+
+    /// Declare the model capabilities the target offers
+    pub fn with_capabilities(mut self, capabilities: Vec<ModelCapability>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+}
+
+/// Custom targets in registration order
+#[derive(Debug, Clone, Default)]
+pub struct TargetRegistry {
+    targets: Vec<RouteTarget>,
+}
+
+impl TargetRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `target`, returning the route that selects it
+    pub fn register(&mut self, target: RouteTarget) -> Result<RoutingDecision, String> {
+        if self.targets.iter().any(|t| t.name == target.name) {
+            return Err(format!(
+                "route target '{}' is already registered",
+                target.name
+            ));
+        }
+        let index = u16::try_from(self.targets.len())
+            .map_err(|_| "too many route targets registered".to_string())?;
+        self.targets.push(target);
+        Ok(RoutingDecision::Custom(index))
+    }
+
+    /// Target selected by `route`, if it is a registered custom route
+    pub fn get(&self, route: RoutingDecision) -> Option<&RouteTarget> {
+        match route {
+            RoutingDecision::Custom(index) => self.targets.get(usize::from(index)),
+            _ => None,
+        }
+    }
+
+    /// Route selecting the target called `name`
+    pub fn find(&self, name: &str) -> Option<RoutingDecision> {
+        self.targets
+            .iter()
+            .position(|t| t.name == name)
+            .and_then(|index| u16::try_from(index).ok())
+            .map(RoutingDecision::Custom)
+    }
+
+    /// Routes of all registered targets
+    pub fn routes(&self) -> impl Iterator<Item = RoutingDecision> + '_ {
+        (0..self.targets.len())
+            .filter_map(|index| u16::try_from(index).ok().map(RoutingDecision::Custom))
+    }
+
+    /// Names of all registered targets, in class order
+    pub fn names(&self) -> Vec<String> {
+        self.targets.iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// Number of registered targets
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Whether no targets are registered
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Routing classes including the built-in routes
+    pub fn class_count(&self) -> usize {
+        BUILTIN_ROUTE_CLASSES + self.targets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl TargetBackend for Echo {
+        fn generate(&self, query: &Query) -> Result<String, String> {
+            Ok(format!("echo: {}", query.text))
+        }
+    }
+
+    fn target(name: &str) -> RouteTarget {
+        RouteTarget::new(
+            name,
+            RouteCost {
+                latency_ms: 500.0,
+                energy_mwh: 5.0,
+                cost_usd: 0.0,
+            },
+            RouteConstraints::default(),
+            Arc::new(Echo),
+        )
+    }
+
+    #[test]
+    fn test_register_assigns_custom_routes_in_order() {
+        let mut registry = TargetRegistry::new();
+        let Ok(watch) = registry.register(target("watch")) else {
+            panic!("first registration should succeed");
+        };
+        let Ok(server) = registry.register(target("home-server")) else {
+            panic!("second registration should succeed");
+        };
+
+        assert_eq!(watch, RoutingDecision::Custom(0));
+        assert_eq!(server, RoutingDecision::Custom(1));
+        assert_eq!(registry.class_count(), 5);
+        assert_eq!(registry.find("home-server"), Some(server));
+        assert_eq!(registry.routes().collect::<Vec<_>>(), vec![watch, server]);
+
+        let Some(found) = registry.get(server) else {
+            panic!("registered route should resolve");
+        };
+        let Ok(text) = found.backend.generate(&Query::new("hi")) else {
+            panic!("echo backend never fails");
+        };
+        assert_eq!(text, "echo: hi");
+        assert!(registry.get(RoutingDecision::Local).is_none());
+        assert!(registry.get(RoutingDecision::Custom(7)).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_names_are_rejected() {
+        let mut registry = TargetRegistry::new();
+        assert!(registry.register(target("watch")).is_ok());
+        assert!(registry.register(target("watch")).is_err());
+        assert_eq!(registry.len(), 1);
+    }
+}
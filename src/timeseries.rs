@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Time-Series Store for Sensor-Derived Features
+//!
+//! Keeps days of windowed sensor features and detector events without
+//! storing raw high-rate samples:
+//!
+//! - **Downsampled**: feature vectors are averaged into fixed-width time
+//!   buckets (one minute by default) before they are stored.
+//! - **Delta-encoded**: each stored point keeps its timestamp as a delta
+//!   from the previous point and its values as quantized deltas from the
+//!   previous point's values.
+//! - **Append-only**: points are only added at the end; retention drops
+//!   the oldest points.
+//!
+//! The store is serde-serializable and can be saved through
+//! `PersistenceManager::save_timeseries`.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Default downsampling bucket width (one minute)
+pub const DEFAULT_BUCKET_MS: u64 = 60_000;
+
+/// Default quantization step for stored values
+pub const DEFAULT_RESOLUTION: f32 = 1e-3;
+
+/// How much history to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Drop points and events older than this (ms before "now")
+    pub max_age_ms: u64,
+    /// Keep at most this many points per series (and events overall)
+    pub max_points: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_ms: 30 * 24 * 60 * 60 * 1000,
+            max_points: 50_000,
+        }
+    }
+}
+
+/// Discrete event emitted by a detector (wake word, activity change, ...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectorEvent {
+    /// Timestamp in milliseconds since epoch
+    pub timestamp_ms: u64,
+    /// Detector-defined event kind (e.g. "walking_started")
+    pub kind: String,
+    /// Detector confidence or magnitude
+    pub value: f32,
+}
+
+/// Bucket currently being averaged, not yet committed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingBucket {
+    start_ms: u64,
+    sum: Vec<f32>,
+    count: u32,
+}
+
+/// One downsampled, delta-encoded series of fixed-width feature vectors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureSeries {
+    dims: usize,
+    bucket_ms: u64,
+    resolution: f32,
+    /// Timestamp of the first stored point
+    base_timestamp_ms: u64,
+    /// Per-point timestamp delta from the previous point (first is 0)
+    timestamp_deltas: Vec<u64>,
+    /// Per-point quantized value deltas, `dims` entries per point
+    value_deltas: Vec<i32>,
+    /// Timestamp of the last stored point
+    last_timestamp_ms: u64,
+    /// Quantized values of the last stored point
+    last_quantized: Vec<i32>,
+    pending: Option<PendingBucket>,
+}
+
+impl FeatureSeries {
+    /// Empty series of `dims`-wide vectors averaged into `bucket_ms` buckets
+    pub fn new(dims: usize, bucket_ms: u64) -> Self {
+        Self {
+            dims,
+            bucket_ms: bucket_ms.max(1),
+            resolution: DEFAULT_RESOLUTION,
+            base_timestamp_ms: 0,
+            timestamp_deltas: Vec::new(),
+            value_deltas: Vec::new(),
+            last_timestamp_ms: 0,
+            last_quantized: vec![0; dims],
+            pending: None,
+        }
+    }
+
+    /// Width of each feature vector
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// Number of committed points (excludes the bucket still being averaged)
+    pub fn len(&self) -> usize {
+        self.timestamp_deltas.len()
+    }
+
+    /// Whether no points have been committed
+    pub fn is_empty(&self) -> bool {
+        self.timestamp_deltas.is_empty()
+    }
+
+    /// Add a sample; samples in the same bucket are averaged
+    ///
+    /// Samples must arrive in time order: a sample older than the bucket
+    /// being averaged is rejected.
+    pub fn append(&mut self, timestamp_ms: u64, values: &[f32]) -> Result<(), String> {
+        if values.len() != self.dims {
+            return Err(format!(
+                "feature width mismatch: expected {}, got {}",
+                self.dims,
+                values.len()
+            ));
+        }
+
+        let bucket_start = timestamp_ms - timestamp_ms % self.bucket_ms;
+
+        if let Some(pending) = &mut self.pending {
+            if bucket_start < pending.start_ms {
+                return Err(format!(
+                    "out-of-order sample at {} (current bucket starts at {})",
+                    timestamp_ms, pending.start_ms
+                ));
+            }
+            if bucket_start == pending.start_ms {
+                for (s, v) in pending.sum.iter_mut().zip(values) {
+                    *s += v;
+                }
+                pending.count += 1;
+                return Ok(());
+            }
+        }
+
+        self.flush();
+        self.pending = Some(PendingBucket {
+            start_ms: bucket_start,
+            sum: values.to_vec(),
+            count: 1,
+        });
+        Ok(())
+    }
+
+    /// Commit the bucket currently being averaged, if any
+    pub fn flush(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            let mean: Vec<f32> = pending
+                .sum
+                .iter()
+                .map(|s| s / pending.count as f32)
+                .collect();
+            self.push_point(pending.start_ms, &mean);
+        }
+    }
+
+    /// Decode all committed points as `(timestamp_ms, values)`
+    pub fn points(&self) -> Vec<(u64, Vec<f32>)> {
+        self.decode_quantized()
+            .into_iter()
+            .map(|(t, q)| (t, q.iter().map(|&v| v as f32 * self.resolution).collect()))
+            .collect()
+    }
+
+    /// Committed points with `start_ms <= timestamp < end_ms`
+    pub fn points_between(&self, start_ms: u64, end_ms: u64) -> Vec<(u64, Vec<f32>)> {
+        self.points()
+            .into_iter()
+            .filter(|(t, _)| (start_ms..end_ms).contains(t))
+            .collect()
+    }
+
+    /// Drop points outside `policy`, returning how many were removed
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy, now_ms: u64) -> usize {
+        let cutoff = now_ms.saturating_sub(policy.max_age_ms);
+        let points = self.decode_quantized();
+
+        let too_old = points.iter().take_while(|(t, _)| *t < cutoff).count();
+        let over_cap = points.len().saturating_sub(policy.max_points);
+        let drop = too_old.max(over_cap);
+        if drop == 0 {
+            return 0;
+        }
+
+        self.timestamp_deltas.clear();
+        self.value_deltas.clear();
+        self.last_timestamp_ms = 0;
+        self.last_quantized = vec![0; self.dims];
+        for (t, q) in points.into_iter().skip(drop) {
+            self.push_quantized(t, q);
+        }
+        drop
+    }
+
+    /// Append a point, quantizing and delta-encoding its values
+    fn push_point(&mut self, timestamp_ms: u64, values: &[f32]) {
+        let quantized = values
+            .iter()
+            .map(|v| (v / self.resolution).round() as i32)
+            .collect();
+        self.push_quantized(timestamp_ms, quantized);
+    }
+
+    fn push_quantized(&mut self, timestamp_ms: u64, quantized: Vec<i32>) {
+        let delta = if self.is_empty() {
+            self.base_timestamp_ms = timestamp_ms;
+            0
+        } else {
+            timestamp_ms - self.last_timestamp_ms
+        };
+
+        self.timestamp_deltas.push(delta);
+        self.value_deltas.extend(
+            quantized
+                .iter()
+                .zip(&self.last_quantized)
+                .map(|(q, last)| q.wrapping_sub(*last)),
+        );
+        self.last_timestamp_ms = timestamp_ms;
+        self.last_quantized = quantized;
+    }
+
+    /// Undo delta encoding, returning quantized values
+    fn decode_quantized(&self) -> Vec<(u64, Vec<i32>)> {
+        let mut timestamp = self.base_timestamp_ms;
+        let mut values = vec![0i32; self.dims];
+        let mut points = Vec::with_capacity(self.len());
+
+        for (i, &delta) in self.timestamp_deltas.iter().enumerate() {
+            timestamp += delta;
+            let deltas = &self.value_deltas[i * self.dims..(i + 1) * self.dims];
+            for (v, d) in values.iter_mut().zip(deltas) {
+                *v = v.wrapping_add(*d);
+            }
+            points.push((timestamp, values.clone()));
+        }
+        points
+    }
+}
+
+/// Named feature series plus a detector event log, with retention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeSeriesStore {
+    bucket_ms: u64,
+    retention: RetentionPolicy,
+    series: BTreeMap<String, FeatureSeries>,
+    events: Vec<DetectorEvent>,
+}
+
+impl Default for TimeSeriesStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKET_MS, RetentionPolicy::default())
+    }
+}
+
+impl TimeSeriesStore {
+    /// Create a store that downsamples into `bucket_ms` buckets
+    pub fn new(bucket_ms: u64, retention: RetentionPolicy) -> Self {
+        Self {
+            bucket_ms,
+            retention,
+            series: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Retention policy applied by [`TimeSeriesStore::apply_retention`]
+    pub fn retention(&self) -> &RetentionPolicy {
+        &self.retention
+    }
+
+    /// Record a feature vector under `name`, creating the series on first use
+    pub fn record_features(
+        &mut self,
+        name: &str,
+        timestamp_ms: u64,
+        values: &[f32],
+    ) -> Result<(), String> {
+        let bucket_ms = self.bucket_ms;
+        self.series
+            .entry(name.to_string())
+            .or_insert_with(|| FeatureSeries::new(values.len(), bucket_ms))
+            .append(timestamp_ms, values)
+    }
+
+    /// Record a detector event
+    pub fn record_event(&mut self, event: DetectorEvent) {
+        self.events.push(event);
+    }
+
+    /// Series by name
+    pub fn series(&self, name: &str) -> Option<&FeatureSeries> {
+        self.series.get(name)
+    }
+
+    /// Names of all series
+    pub fn series_names(&self) -> impl Iterator<Item = &str> {
+        self.series.keys().map(String::as_str)
+    }
+
+    /// Events with `start_ms <= timestamp < end_ms`
+    pub fn events_between(&self, start_ms: u64, end_ms: u64) -> Vec<&DetectorEvent> {
+        self.events
+            .iter()
+            .filter(|e| (start_ms..end_ms).contains(&e.timestamp_ms))
+            .collect()
+    }
+
+    /// Commit every series' in-progress bucket
+    pub fn flush(&mut self) {
+        for series in self.series.values_mut() {
+            series.flush();
+        }
+    }
+
+    /// Drop data outside the retention policy, returning points + events removed
+    pub fn apply_retention(&mut self, now_ms: u64) -> usize {
+        let policy = self.retention;
+        let mut removed: usize = self
+            .series
+            .values_mut()
+            .map(|s| s.apply_retention(&policy, now_ms))
+            .sum();
+
+        let cutoff = now_ms.saturating_sub(policy.max_age_ms);
+        let before = self.events.len();
+        self.events.retain(|e| e.timestamp_ms >= cutoff);
+        if self.events.len() > policy.max_points {
+            let excess = self.events.len() - policy.max_points;
+            self.events.drain(..excess);
+        }
+        removed += before - self.events.len();
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_are_downsampled_into_buckets() {
+        let mut series = FeatureSeries::new(2, 1000);
+        for (t, v) in [(0, 1.0), (400, 3.0), (900, 2.0), (1200, 10.0)] {
+            let Ok(()) = series.append(t, &[v, -v]) else {
+                panic!("in-order append should succeed");
+            };
+        }
+        series.flush();
+
+        let points = series.points();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].0, 0);
+        assert!((points[0].1[0] - 2.0).abs() < 1e-3);
+        assert!((points[0].1[1] + 2.0).abs() < 1e-3);
+        assert_eq!(points[1].0, 1000);
+        assert!((points[1].1[0] - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_delta_encoding_roundtrip() {
+        let mut series = FeatureSeries::new(3, 10);
+        let inputs: Vec<(u64, Vec<f32>)> = (0..50)
+            .map(|i| (1_700_000_000_000 + i * 10, vec![i as f32 * 0.1, -0.5, 9.81]))
+            .collect();
+        for (t, v) in &inputs {
+            let Ok(()) = series.append(*t, v) else {
+                panic!("in-order append should succeed");
+            };
+        }
+        series.flush();
+
+        let points = series.points();
+        assert_eq!(points.len(), inputs.len());
+        for ((t, v), (pt, pv)) in inputs.iter().zip(&points) {
+            assert_eq!(t, pt);
+            for (a, b) in v.iter().zip(pv) {
+                assert!((a - b).abs() <= DEFAULT_RESOLUTION);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_samples() {
+        let mut series = FeatureSeries::new(2, 1000);
+        assert!(series.append(5000, &[1.0]).is_err());
+        assert!(series.append(5000, &[1.0, 2.0]).is_ok());
+        assert!(series.append(3000, &[1.0, 2.0]).is_err());
+    }
+
+    #[test]
+    fn test_retention_by_age_and_count() {
+        let mut store = TimeSeriesStore::new(
+            1000,
+            RetentionPolicy {
+                max_age_ms: 10_000,
+                max_points: 5,
+            },
+        );
+        for i in 0..20u64 {
+            let Ok(()) = store.record_features("motion", i * 1000, &[i as f32]) else {
+                panic!("record_features should succeed");
+            };
+            store.record_event(DetectorEvent {
+                timestamp_ms: i * 1000,
+                kind: "step".to_string(),
+                value: 1.0,
+            });
+        }
+        store.flush();
+
+        store.apply_retention(20_000);
+
+        let Some(series) = store.series("motion") else {
+            panic!("series should exist");
+        };
+        let points = series.points();
+        assert_eq!(points.len(), 5);
+        assert_eq!(points[0].0, 15_000);
+        assert!((points[0].1[0] - 15.0).abs() < 1e-3);
+        assert_eq!(store.events_between(0, u64::MAX).len(), 5);
+    }
+
+    #[test]
+    fn test_store_serialization_roundtrip() {
+        let mut store = TimeSeriesStore::default();
+        let Ok(()) = store.record_features("light", 60_000, &[0.25]) else {
+            panic!("record_features should succeed");
+        };
+        store.flush();
+
+        let Ok(json) = serde_json::to_string(&store) else {
+            panic!("to_string should succeed");
+        };
+        let Ok(restored) = serde_json::from_str::<TimeSeriesStore>(&json) else {
+            panic!("from_str should succeed");
+        };
+        let Some(series) = restored.series("light") else {
+            panic!("series should survive roundtrip");
+        };
+        assert_eq!(series.len(), 1);
+    }
+}
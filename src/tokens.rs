@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Token Counting and Budgets
+//!
+//! `estimate_tokens` assumes four characters per token, which undercounts
+//! code, numbers and non-Latin scripts badly enough for a long prompt to
+//! overflow a small Local context window. This module counts tokens the way
+//! the models do:
+//!
+//! - [`BpeEstimator`] splits text like byte-pair-encoding tokenizers do
+//!   before merging (words with their leading space, digit groups, single
+//!   punctuation marks, line breaks) and counts long words as several
+//!   pieces. It needs no vocabulary and is the default.
+//! - [`CharRatio`] is the plain characters-per-token heuristic.
+//! - Any real tokenizer can implement [`TokenCounter`], e.g. MiniLM's
+//!   WordPiece tokenizer with the `minilm` feature.
+//!
+//! A [`TokenBudget`] charges text against a model's window and truncates
+//! what does not fit at a word boundary.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// Marker appended to (or prepended to) truncated text
+const ELLIPSIS: &str = "…";
+
+/// Counts the tokens a model would see for some text
+pub trait TokenCounter: Send + Sync + fmt::Debug {
+    /// Identifier of the tokenizer and its settings
+    fn id(&self) -> String;
+
+    /// Tokens in `text`
+    fn count(&self, text: &str) -> usize;
+}
+
+/// How a model's tokens are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenizerKind {
+    /// Byte-pair-encoding estimate ([`BpeEstimator`])
+    Bpe,
+    /// Characters per token ([`CharRatio`])
+    Chars,
+}
+
+/// Token counting settings of one model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenizerConfig {
+    /// Counting method
+    pub kind: TokenizerKind,
+    /// `Bpe`: letters per subword piece of a long word
+    pub letters_per_piece: usize,
+    /// `Bpe`: digits per token (3 for GPT-style, 1 for LLaMA-style models)
+    pub digits_per_token: usize,
+    /// `Chars`: characters per token
+    pub chars_per_token: f32,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            kind: TokenizerKind::Bpe,
+            letters_per_piece: 6,
+            digits_per_token: 3,
+            chars_per_token: 4.0,
+        }
+    }
+}
+
+impl TokenizerConfig {
+    /// The counter these settings describe
+    pub fn counter(&self) -> Arc<dyn TokenCounter> {
+        match self.kind {
+            TokenizerKind::Bpe => Arc::new(BpeEstimator::new(
+                self.letters_per_piece,
+                self.digits_per_token,
+            )),
+            TokenizerKind::Chars => Arc::new(CharRatio::new(self.chars_per_token)),
+        }
+    }
+}
+
+/// Token counting settings per backend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokensConfig {
+    /// Tokenizer of the on-device model
+    pub local: TokenizerConfig,
+    /// Tokenizer of the remote model (also used for custom targets)
+    pub remote: TokenizerConfig,
+    /// Tokens of each context window kept free for the answer
+    pub reserve_output_tokens: usize,
+}
+
+impl Default for TokensConfig {
+    fn default() -> Self {
+        Self {
+            local: TokenizerConfig {
+                digits_per_token: 1,
+                ..TokenizerConfig::default()
+            },
+            remote: TokenizerConfig::default(),
+            reserve_output_tokens: 512,
+        }
+    }
+}
+
+/// Fixed number of characters per token
+#[derive(Debug, Clone)]
+pub struct CharRatio {
+    chars_per_token: f32,
+}
+
+impl CharRatio {
+    /// A counter assuming `chars_per_token` characters per token
+    pub fn new(chars_per_token: f32) -> Self {
+        Self {
+            chars_per_token: chars_per_token.max(0.1),
+        }
+    }
+}
+
+impl TokenCounter for CharRatio {
+    fn id(&self) -> String {
+        format!("chars/{}", self.chars_per_token)
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (text.chars().count() as f32 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Byte-pair-encoding token estimate without a vocabulary
+#[derive(Debug, Clone)]
+pub struct BpeEstimator {
+    letters_per_piece: usize,
+    digits_per_token: usize,
+}
+
+impl BpeEstimator {
+    /// An estimator splitting words into pieces of `letters_per_piece`
+    /// letters and numbers into groups of `digits_per_token` digits
+    pub fn new(letters_per_piece: usize, digits_per_token: usize) -> Self {
+        Self {
+            letters_per_piece: letters_per_piece.max(1),
+            digits_per_token: digits_per_token.max(1),
+        }
+    }
+}
+
+impl Default for BpeEstimator {
+    fn default() -> Self {
+        Self::new(6, 3)
+    }
+}
+
+/// Character classes of the pre-tokenizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Letter,
+    /// Scripts without spaces between words, about a token per character
+    Ideograph,
+    Digit,
+    Space,
+    Break,
+    Symbol,
+}
+
+fn class(c: char) -> Class {
+    if c == '\n' || c == '\r' {
+        Class::Break
+    } else if c.is_whitespace() {
+        Class::Space
+    } else if c.is_ascii_digit() {
+        Class::Digit
+    } else if c.is_alphabetic() && u32::from(c) >= 0x2E80 {
+        Class::Ideograph
+    } else if c.is_alphanumeric() {
+        Class::Letter
+    } else {
+        Class::Symbol
+    }
+}
+
+impl TokenCounter for BpeEstimator {
+    fn id(&self) -> String {
+        format!("bpe/{}/{}", self.letters_per_piece, self.digits_per_token)
+    }
+
+    fn count(&self, text: &str) -> usize {
+        let mut tokens = 0;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let kind = class(c);
+            let mut run: usize = 1;
+            if matches!(
+                kind,
+                Class::Letter | Class::Digit | Class::Space | Class::Break
+            ) {
+                while chars.peek().is_some_and(|&next| class(next) == kind) {
+                    chars.next();
+                    run += 1;
+                }
+            }
+            tokens += match kind {
+                Class::Letter => run.div_ceil(self.letters_per_piece),
+                Class::Digit => run.div_ceil(self.digits_per_token),
+                // A single space merges into the following word
+                Class::Space => usize::from(
+                    run > 1
+                        || chars
+                            .peek()
+                            .map_or(true, |&next| class(next) != Class::Letter),
+                ),
+                Class::Break => 1,
+                Class::Ideograph | Class::Symbol => 1,
+            };
+        }
+        tokens
+    }
+}
+
+/// Which end of a text survives truncation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    /// The beginning, e.g. of a document
+    Head,
+    /// The end, e.g. of a log
+    Tail,
+}
+
+/// Tokens left in a model's window while a prompt is assembled
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    counter: Arc<dyn TokenCounter>,
+    limit: usize,
+    used: usize,
+}
+
+impl TokenBudget {
+    /// A budget of `limit` tokens counted by `counter`
+    pub fn new(limit: usize, counter: Arc<dyn TokenCounter>) -> Self {
+        Self {
+            counter,
+            limit,
+            used: 0,
+        }
+    }
+
+    /// A budget of `limit` tokens counted with the default [`BpeEstimator`]
+    pub fn estimated(limit: usize) -> Self {
+        Self::new(limit, Arc::new(BpeEstimator::default()))
+    }
+
+    /// Tokens in `text`
+    pub fn count(&self, text: &str) -> usize {
+        self.counter.count(text)
+    }
+
+    /// The counter in use
+    pub fn counter(&self) -> &Arc<dyn TokenCounter> {
+        &self.counter
+    }
+
+    /// Size of the budget
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Tokens charged so far
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Tokens still available
+    pub fn remaining(&self) -> usize {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Charge `text` even when it does not fit, e.g. for the query itself
+    pub fn reserve(&mut self, text: &str) {
+        self.used += self.count(text);
+    }
+
+    /// Charge `text` if it fits, returning whether it did
+    pub fn take(&mut self, text: &str) -> bool {
+        let tokens = self.count(text);
+        let fits = tokens <= self.remaining();
+        if fits {
+            self.used += tokens;
+        }
+        fits
+    }
+
+    /// Charge as much of `text` as fits, cut at a word boundary and marked
+    /// with an ellipsis. The whole text when it fits; `None` when not even
+    /// one word does.
+    pub fn truncate(&mut self, text: &str, keep: Keep) -> Option<String> {
+        if self.take(text) {
+            return Some(text.to_string());
+        }
+        // Cut points at word starts, from the kept end outwards
+        let mut cuts: Vec<usize> = text
+            .char_indices()
+            .filter(|&(i, c)| i > 0 && c.is_whitespace())
+            .map(|(i, _)| i)
+            .collect();
+        if keep == Keep::Tail {
+            cuts.reverse();
+        }
+        let piece = |cut: usize| match keep {
+            Keep::Head => format!("{}{}", text[..cut].trim_end(), ELLIPSIS),
+            Keep::Tail => format!("{}{}", ELLIPSIS, text[cut..].trim_start()),
+        };
+        // Token counts grow with the piece, so the longest fit is found by
+        // bisection
+        let fitting = cuts.partition_point(|&cut| self.count(&piece(cut)) <= self.remaining());
+        let cut = *cuts.get(fitting.checked_sub(1)?)?;
+        let piece = piece(cut);
+        self.used += self.count(&piece);
+        Some(piece)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpe_estimate_counts_like_a_tokenizer() {
+        let bpe = BpeEstimator::default();
+        // "Hello" "," " world" "!"
+        assert_eq!(bpe.count("Hello, world!"), 4);
+        // Long words split into pieces, numbers into digit groups
+        assert_eq!(bpe.count("internationalization"), 4);
+        assert_eq!(bpe.count("1234567"), 3);
+        assert_eq!(BpeEstimator::new(6, 1).count("1234567"), 7);
+        // Code is denser than prose: chars/4 undercounts it
+        let code = "fn main() { let x: Vec<u8> = vec![1, 2]; }";
+        assert!(bpe.count(code) > CharRatio::new(4.0).count(code));
+        assert_eq!(bpe.count("東京都"), 3);
+        assert_eq!(bpe.count(""), 0);
+    }
+
+    #[test]
+    fn test_budget_takes_and_truncates_at_word_boundaries() {
+        let mut budget = TokenBudget::estimated(10);
+        budget.reserve("one two three");
+        assert_eq!(budget.remaining(), 7);
+        assert!(!budget.take("a b c d e f g h"));
+        assert!(budget.take("a b"));
+        assert_eq!(budget.remaining(), 5);
+
+        let text = "alpha beta gamma delta epsilon zeta";
+        let Some(head) = budget.clone().truncate(text, Keep::Head) else {
+            panic!("some words should fit");
+        };
+        assert_eq!(head, "alpha beta gamma delta…");
+        let Some(tail) = budget.truncate(text, Keep::Tail) else {
+            panic!("some words should fit");
+        };
+        assert_eq!(tail, "…delta epsilon zeta");
+        assert_eq!(budget.remaining(), 0);
+        assert_eq!(budget.truncate(text, Keep::Head), None);
+    }
+}
@@ -10,11 +10,43 @@
 
 #![forbid(unsafe_code)]
 
-use crate::mlp::MLP;
+use crate::error::OrchestratorError;
+use crate::mlp::{Gradients, Loss, MLP};
 use crate::reservoir::EchoStateNetwork;
-use crate::types::{Query, RoutingDecision};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use crate::rng::SeededRng;
+use crate::types::RoutingDecision;
+use std::collections::VecDeque;
+
+/// Milliseconds per day, the window for the online step budget
+const DAY_MS: u64 = 86_400_000;
+
+/// Number of built-in routing classes (Local, Remote, Hybrid); custom
+/// targets follow them in registration order
+pub const BUILTIN_ROUTE_CLASSES: usize = 3;
+
+/// Class index of a routing decision (0=Local, 1=Remote, 2=Hybrid,
+/// 3.. custom targets)
+pub fn route_label(route: RoutingDecision) -> usize {
+    match route {
+        RoutingDecision::Local => 0,
+        RoutingDecision::Remote => 1,
+        RoutingDecision::Hybrid => 2,
+        RoutingDecision::Blocked => 0, // Treat as local for now
+        RoutingDecision::Custom(index) => BUILTIN_ROUTE_CLASSES + usize::from(index),
+    }
+}
+
+/// Routing decision for a class index; indices past the built-in routes
+/// are custom targets
+pub fn label_route(label: usize) -> RoutingDecision {
+    match label {
+        0 => RoutingDecision::Local,
+        1 => RoutingDecision::Remote,
+        2 => RoutingDecision::Hybrid,
+        _ => u16::try_from(label - BUILTIN_ROUTE_CLASSES)
+            .map_or(RoutingDecision::Local, RoutingDecision::Custom),
+    }
+}
 
 /// Training data for router MLP
 #[derive(Debug, Clone)]
@@ -37,12 +69,7 @@ pub fn new() -> Self {
     /// Add a training example
     pub fn add_example(&mut self, features: Vec<f32>, label: RoutingDecision) {
         self.features.push(features);
-        self.labels.push(match label {
-            RoutingDecision::Local => 0,
-            RoutingDecision::Remote => 1,
-            RoutingDecision::Hybrid => 2,
-            RoutingDecision::Blocked => 0, // Treat as local for now
-        });
+        self.labels.push(route_label(label));
     }
 
     /// Number of examples
@@ -60,28 +87,89 @@ pub fn train_test_split(&self, train_ratio: f32) -> (RouterTrainingData, RouterT
         let n_train = (self.len() as f32 * train_ratio) as usize;
 
         let mut indices: Vec<usize> = (0..self.len()).collect();
-        indices.shuffle(&mut thread_rng());
+        SeededRng::from_clock().shuffle(&mut indices);
 
-        let train_indices = &indices[..n_train];
-        let test_indices = &indices[n_train..];
+        (
+            self.subset(&indices[..n_train]),
+            self.subset(&indices[n_train..]),
+        )
+    }
 
-        let train = RouterTrainingData {
-            features: train_indices
-                .iter()
-                .map(|&i| self.features[i].clone())
-                .collect(),
-            labels: train_indices.iter().map(|&i| self.labels[i]).collect(),
-        };
+    /// Number of classes a model needs to learn these labels: the
+    /// built-in routes plus any custom targets that appear
+    pub fn num_classes(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|&label| label + 1)
+            .max()
+            .unwrap_or(0)
+            .max(BUILTIN_ROUTE_CLASSES)
+    }
 
-        let test = RouterTrainingData {
-            features: test_indices
-                .iter()
-                .map(|&i| self.features[i].clone())
-                .collect(),
-            labels: test_indices.iter().map(|&i| self.labels[i]).collect(),
-        };
+    /// Split into train/test sets, preserving each class's proportion
+    ///
+    /// Every class with at least two examples appears in both sets, so
+    /// small classes (typically Hybrid) can't vanish from the test set.
+    pub fn stratified_train_test_split(
+        &self,
+        train_ratio: f32,
+    ) -> (RouterTrainingData, RouterTrainingData) {
+        let mut train_indices = Vec::new();
+        let mut test_indices = Vec::new();
+        let mut rng = SeededRng::from_clock();
+
+        for mut class in self.indices_by_class() {
+            rng.shuffle(&mut class);
+            let n = class.len();
+            let mut n_train = (n as f32 * train_ratio).round() as usize;
+            if n >= 2 {
+                n_train = n_train.clamp(1, n - 1);
+            }
+            train_indices.extend_from_slice(&class[..n_train.min(n)]);
+            test_indices.extend_from_slice(&class[n_train.min(n)..]);
+        }
 
-        (train, test)
+        rng.shuffle(&mut train_indices);
+        rng.shuffle(&mut test_indices);
+        (self.subset(&train_indices), self.subset(&test_indices))
+    }
+
+    /// Partition example indices into `k` folds with class proportions
+    /// preserved in each fold
+    pub fn stratified_folds(&self, k: usize) -> Vec<Vec<usize>> {
+        let k = k.max(1);
+        let mut folds = vec![Vec::new(); k];
+        let mut next = 0;
+        let mut rng = SeededRng::from_clock();
+
+        // Deal each class round-robin, continuing where the previous
+        // class stopped so fold sizes stay balanced.
+        for mut class in self.indices_by_class() {
+            rng.shuffle(&mut class);
+            for i in class {
+                folds[next].push(i);
+                next = (next + 1) % k;
+            }
+        }
+        folds
+    }
+
+    /// Examples at `indices`, in that order
+    fn subset(&self, indices: &[usize]) -> RouterTrainingData {
+        RouterTrainingData {
+            features: indices.iter().map(|&i| self.features[i].clone()).collect(),
+            labels: indices.iter().map(|&i| self.labels[i]).collect(),
+        }
+    }
+
+    /// Example indices grouped by label, in label order
+    fn indices_by_class(&self) -> Vec<Vec<usize>> {
+        let n_classes = self.labels.iter().max().map_or(0, |&m| m + 1);
+        let mut classes = vec![Vec::new(); n_classes];
+        for (i, &label) in self.labels.iter().enumerate() {
+            classes[label].push(i);
+        }
+        classes
     }
 }
 
@@ -91,29 +179,261 @@ fn default() -> Self {
     }
 }
 
+/// Parameter update rule used by [`MLPTrainer`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Optimizer {
+    /// Plain stochastic gradient descent
+    #[default]
+    Sgd,
+    /// SGD with a running velocity: `v = beta * v + g`
+    Momentum {
+        /// Velocity decay (typically 0.9)
+        beta: f32,
+    },
+    /// Adam with bias-corrected first and second moment estimates
+    Adam {
+        /// First moment decay (typically 0.9)
+        beta1: f32,
+        /// Second moment decay (typically 0.999)
+        beta2: f32,
+        /// Numerical stability term
+        epsilon: f32,
+    },
+    /// Adam with decoupled weight decay applied to the weights (not biases)
+    AdamW {
+        /// First moment decay (typically 0.9)
+        beta1: f32,
+        /// Second moment decay (typically 0.999)
+        beta2: f32,
+        /// Numerical stability term
+        epsilon: f32,
+        /// Decoupled weight decay coefficient
+        weight_decay: f32,
+    },
+}
+
+impl Optimizer {
+    /// Momentum with the usual `beta = 0.9`
+    pub fn momentum() -> Self {
+        Optimizer::Momentum { beta: 0.9 }
+    }
+
+    /// Adam with the usual defaults
+    pub fn adam() -> Self {
+        Optimizer::Adam {
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+
+    /// AdamW with the usual defaults and the given weight decay
+    pub fn adamw(weight_decay: f32) -> Self {
+        Optimizer::AdamW {
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            weight_decay,
+        }
+    }
+}
+
+/// Per-parameter optimizer state (velocity / moment estimates)
+///
+/// Shaped like the network it was created for; one instance lives for
+/// the duration of a training run.
+#[derive(Debug, Clone)]
+pub struct OptimizerState {
+    optimizer: Optimizer,
+    /// Velocity (Momentum) or first moment (Adam/AdamW)
+    first: Gradients,
+    /// Second moment (Adam/AdamW)
+    second: Gradients,
+    /// Number of steps taken, for Adam bias correction
+    t: i32,
+}
+
+impl OptimizerState {
+    /// Fresh state for `optimizer` shaped like `mlp`
+    pub fn new(optimizer: Optimizer, mlp: &MLP) -> Self {
+        Self {
+            optimizer,
+            first: Gradients::zeros_like(mlp),
+            second: Gradients::zeros_like(mlp),
+            t: 0,
+        }
+    }
+
+    /// Apply one update to `mlp` from `gradients`
+    pub fn step(&mut self, mlp: &mut MLP, gradients: &Gradients, learning_rate: f32) {
+        self.t = self.t.saturating_add(1);
+
+        let step = match self.optimizer {
+            Optimizer::Sgd => {
+                mlp.update(gradients, learning_rate);
+                return;
+            }
+            Optimizer::Momentum { beta } => {
+                for_each_param(&mut self.first, gradients, |v, g| *v = beta * *v + g);
+                self.first.clone()
+            }
+            Optimizer::Adam {
+                beta1,
+                beta2,
+                epsilon,
+            } => self.adam_step(gradients, beta1, beta2, epsilon),
+            Optimizer::AdamW {
+                beta1,
+                beta2,
+                epsilon,
+                weight_decay,
+            } => {
+                let mut step = self.adam_step(gradients, beta1, beta2, epsilon);
+                for (s, w) in step.weights.iter_mut().zip(mlp.weights()) {
+                    for (s, w) in s.as_mut_slice().iter_mut().zip(w.as_slice()) {
+                        *s += weight_decay * w;
+                    }
+                }
+                step
+            }
+        };
+
+        mlp.update(&step, learning_rate);
+    }
+
+    /// Update moment estimates and return the bias-corrected Adam step
+    fn adam_step(
+        &mut self,
+        gradients: &Gradients,
+        beta1: f32,
+        beta2: f32,
+        epsilon: f32,
+    ) -> Gradients {
+        for_each_param(&mut self.first, gradients, |m, g| {
+            *m = beta1 * *m + (1.0 - beta1) * g
+        });
+        for_each_param(&mut self.second, gradients, |v, g| {
+            *v = beta2 * *v + (1.0 - beta2) * g * g
+        });
+
+        let m_correction = 1.0 - beta1.powi(self.t);
+        let v_correction = 1.0 - beta2.powi(self.t);
+
+        let mut step = self.first.clone();
+        for_each_param(&mut step, &self.second, |m, v| {
+            *m = (*m / m_correction) / ((v / v_correction).sqrt() + epsilon)
+        });
+        step
+    }
+}
+
+/// Apply `f(target, source)` to every matching pair of parameters
+fn for_each_param(target: &mut Gradients, source: &Gradients, mut f: impl FnMut(&mut f32, f32)) {
+    for (t, s) in target.weights.iter_mut().zip(&source.weights) {
+        for (t, &s) in t.as_mut_slice().iter_mut().zip(s.as_slice()) {
+            f(t, s);
+        }
+    }
+    for (t, s) in target.biases.iter_mut().zip(&source.biases) {
+        for (t, &s) in t.iter_mut().zip(s) {
+            f(t, s);
+        }
+    }
+}
+
+/// Per-epoch learning-rate schedule used by [`MLPTrainer`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LrSchedule {
+    /// Fixed learning rate
+    #[default]
+    Constant,
+    /// Multiply the rate by `gamma` every `step_size` epochs
+    StepDecay {
+        /// Epochs between decays
+        step_size: usize,
+        /// Decay factor
+        gamma: f32,
+    },
+    /// Cosine annealing from the base rate down to `min_lr` over the run
+    Cosine {
+        /// Rate reached at the final epoch
+        min_lr: f32,
+    },
+}
+
+impl LrSchedule {
+    /// Learning rate for `epoch` (0-based) of a `total_epochs` run
+    ///
+    /// During the first `warmup_epochs` epochs the rate ramps linearly up
+    /// to `base_lr`; the schedule then runs over the remaining epochs.
+    pub fn learning_rate(
+        &self,
+        base_lr: f32,
+        epoch: usize,
+        total_epochs: usize,
+        warmup_epochs: usize,
+    ) -> f32 {
+        if epoch < warmup_epochs {
+            return base_lr * (epoch + 1) as f32 / warmup_epochs as f32;
+        }
+
+        let epoch = epoch - warmup_epochs;
+        let span = total_epochs.saturating_sub(warmup_epochs);
+        match *self {
+            LrSchedule::Constant => base_lr,
+            LrSchedule::StepDecay { step_size, gamma } => {
+                let steps = epoch / step_size.max(1);
+                base_lr * gamma.powi(steps as i32)
+            }
+            LrSchedule::Cosine { min_lr } => {
+                if span <= 1 {
+                    return base_lr;
+                }
+                let progress = epoch as f32 / (span - 1) as f32;
+                min_lr + 0.5 * (base_lr - min_lr) * (1.0 + (std::f32::consts::PI * progress).cos())
+            }
+        }
+    }
+}
+
 /// Training configuration for MLP
 #[derive(Debug, Clone)]
 pub struct MLPTrainingConfig {
-    /// Learning rate
+    /// Base learning rate
     pub learning_rate: f32,
+    /// Per-epoch schedule applied to `learning_rate`
+    pub lr_schedule: LrSchedule,
+    /// Epochs of linear warmup before the schedule starts (0 disables)
+    pub warmup_epochs: usize,
     /// Number of epochs
     pub epochs: usize,
     /// Batch size (0 = full batch)
     pub batch_size: usize,
     /// Early stopping patience (epochs without improvement)
     pub patience: usize,
-    /// L2 regularization strength
+    /// L2 regularization strength (weight decay on the gradient)
     pub l2_reg: f32,
+    /// L1 regularization strength (0 disables; encourages sparse weights)
+    pub l1_reg: f32,
+    /// Parameter update rule
+    pub optimizer: Optimizer,
+    /// Training objective
+    pub loss: Loss,
 }
 
 impl Default for MLPTrainingConfig {
     fn default() -> Self {
         Self {
             learning_rate: 0.01,
+            lr_schedule: LrSchedule::default(),
+            warmup_epochs: 0,
             epochs: 100,
             batch_size: 32,
             patience: 10,
             l2_reg: 0.001,
+            l1_reg: 0.0,
+            optimizer: Optimizer::default(),
+            loss: Loss::SoftmaxCrossEntropy,
         }
     }
 }
@@ -129,6 +449,10 @@ pub struct TrainingMetrics {
     pub test_accuracy: f32,
     /// Confusion matrix [true_label][pred_label]
     pub confusion_matrix: Vec<Vec<usize>>,
+    /// Learning-rate schedule used for the run
+    pub lr_schedule: LrSchedule,
+    /// Learning rate applied in each epoch
+    pub learning_rates: Vec<f32>,
 }
 
 /// MLP trainer
@@ -151,10 +475,20 @@ pub fn train(
     ) -> TrainingMetrics {
         let mut train_losses = Vec::new();
         let mut val_accuracies = Vec::new();
+        let mut learning_rates = Vec::new();
         let mut best_val_acc = 0.0;
         let mut patience_counter = 0;
+        let mut optimizer = OptimizerState::new(self.config.optimizer, mlp);
 
         for epoch in 0..self.config.epochs {
+            let learning_rate = self.config.lr_schedule.learning_rate(
+                self.config.learning_rate,
+                epoch,
+                self.config.epochs,
+                self.config.warmup_epochs,
+            );
+            learning_rates.push(learning_rate);
+
             // Training
             let mut epoch_loss = 0.0;
 
@@ -169,10 +503,19 @@ pub fn train(
                     let mut batch_loss = 0.0;
 
                     for i in start..end {
-                        let target = one_hot(train_data.labels[i], 3);
-                        let (loss, gradients) = mlp.backward(&train_data.features[i], &target);
+                        let target = one_hot(train_data.labels[i], mlp.output_size());
+                        let (loss, mut gradients) = mlp.backward_with_loss(
+                            &train_data.features[i],
+                            &target,
+                            self.config.loss,
+                        );
+                        mlp.add_weight_penalty(
+                            &mut gradients,
+                            self.config.l2_reg,
+                            self.config.l1_reg,
+                        );
 
-                        mlp.update(&gradients, self.config.learning_rate);
+                        optimizer.step(mlp, &gradients, learning_rate);
 
                         batch_loss += loss;
                     }
@@ -184,10 +527,12 @@ pub fn train(
             } else {
                 // Full batch training
                 for i in 0..train_data.len() {
-                    let target = one_hot(train_data.labels[i], 3);
-                    let (loss, gradients) = mlp.backward(&train_data.features[i], &target);
+                    let target = one_hot(train_data.labels[i], mlp.output_size());
+                    let (loss, mut gradients) =
+                        mlp.backward_with_loss(&train_data.features[i], &target, self.config.loss);
+                    mlp.add_weight_penalty(&mut gradients, self.config.l2_reg, self.config.l1_reg);
 
-                    mlp.update(&gradients, self.config.learning_rate);
+                    optimizer.step(mlp, &gradients, learning_rate);
 
                     epoch_loss += loss;
                 }
@@ -246,34 +591,30 @@ pub fn train(
             val_accuracies,
             test_accuracy,
             confusion_matrix,
+            lr_schedule: self.config.lr_schedule,
+            learning_rates,
         }
     }
 
     /// Evaluate accuracy on dataset
     fn evaluate_accuracy(&self, mlp: &MLP, data: &RouterTrainingData) -> f32 {
-        let mut correct = 0;
-
-        for i in 0..data.len() {
-            let logits = mlp.forward(&data.features[i]);
-            let pred = MLP::argmax(&logits);
-
-            if pred == data.labels[i] {
-                correct += 1;
-            }
-        }
+        let correct = mlp
+            .forward_batch(&data.features)
+            .iter()
+            .zip(&data.labels)
+            .filter(|(logits, &label)| MLP::argmax(logits) == label)
+            .count();
 
         correct as f32 / data.len() as f32
     }
 
     /// Compute confusion matrix
     fn confusion_matrix(&self, mlp: &MLP, data: &RouterTrainingData) -> Vec<Vec<usize>> {
-        let mut matrix = vec![vec![0; 3]; 3];
-
-        for i in 0..data.len() {
-            let logits = mlp.forward(&data.features[i]);
-            let pred = MLP::argmax(&logits);
-            let true_label = data.labels[i];
+        let classes = mlp.output_size();
+        let mut matrix = vec![vec![0; classes]; classes];
 
+        for (logits, &true_label) in mlp.forward_batch(&data.features).iter().zip(&data.labels) {
+            let pred = MLP::argmax(logits);
             matrix[true_label][pred] += 1;
         }
 
@@ -288,37 +629,46 @@ pub fn cross_validate(
         k_folds: usize,
     ) -> Vec<f32> {
         let fold_size = data.len() / k_folds;
-        let mut accuracies = Vec::new();
-
-        for fold in 0..k_folds {
-            let start = fold * fold_size;
-            let end = (start + fold_size).min(data.len());
+        let folds: Vec<Vec<usize>> = (0..k_folds)
+            .map(|fold| {
+                let start = fold * fold_size;
+                (start..(start + fold_size).min(data.len())).collect()
+            })
+            .collect();
+
+        self.evaluate_folds(mlp_template, data, &folds)
+    }
 
-            // Create train/val split
-            let mut train_features = Vec::new();
-            let mut train_labels = Vec::new();
-            let mut val_features = Vec::new();
-            let mut val_labels = Vec::new();
+    /// Cross-validation with stratified folds (see
+    /// [`RouterTrainingData::stratified_folds`])
+    pub fn cross_validate_stratified(
+        &self,
+        mlp_template: &MLP,
+        data: &RouterTrainingData,
+        k_folds: usize,
+    ) -> Vec<f32> {
+        self.evaluate_folds(mlp_template, data, &data.stratified_folds(k_folds))
+    }
 
-            for i in 0..data.len() {
-                if i >= start && i < end {
-                    val_features.push(data.features[i].clone());
-                    val_labels.push(data.labels[i]);
-                } else {
-                    train_features.push(data.features[i].clone());
-                    train_labels.push(data.labels[i]);
-                }
-            }
+    /// Train on all-but-one fold and validate on the held-out fold
+    fn evaluate_folds(
+        &self,
+        mlp_template: &MLP,
+        data: &RouterTrainingData,
+        folds: &[Vec<usize>],
+    ) -> Vec<f32> {
+        let mut accuracies = Vec::new();
 
-            let train_data = RouterTrainingData {
-                features: train_features,
-                labels: train_labels,
-            };
+        for (fold, val_indices) in folds.iter().enumerate() {
+            let train_indices: Vec<usize> = folds
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != fold)
+                .flat_map(|(_, indices)| indices.iter().copied())
+                .collect();
 
-            let val_data = RouterTrainingData {
-                features: val_features,
-                labels: val_labels,
-            };
+            let train_data = data.subset(&train_indices);
+            let val_data = data.subset(val_indices);
 
             // Train on this fold
             let mut mlp = mlp_template.clone();
@@ -351,9 +701,11 @@ pub fn train(
         esn: &mut EchoStateNetwork,
         inputs: &[Vec<f32>],
         targets: &[Vec<f32>],
-    ) -> Result<f32, String> {
+    ) -> Result<f32, OrchestratorError> {
         if inputs.len() != targets.len() {
-            return Err("Inputs and targets must have same length".to_string());
+            return Err(OrchestratorError::InvalidInput(
+                "Inputs and targets must have same length".to_string(),
+            ));
         }
 
         // Collect reservoir states
@@ -365,18 +717,19 @@ pub fn train(
         }
 
         // Train output weights using ridge regression
-        esn.train(&states, targets, self.lambda);
+        esn.train(&states, targets, self.lambda)
+            .map_err(OrchestratorError::InvalidInput)?;
 
         // Compute MSE
         let mut mse = 0.0;
         esn.reset(); // Reset state before testing
 
-        for i in 0..inputs.len() {
-            esn.update(&inputs[i]);
+        for (input, target) in inputs.iter().zip(targets) {
+            esn.update(input);
             let output = esn.output();
             let error: f32 = output
                 .iter()
-                .zip(&targets[i])
+                .zip(target)
                 .map(|(o, t)| (o - t).powi(2))
                 .sum();
             mse += error;
@@ -387,10 +740,176 @@ pub fn train(
     }
 }
 
+/// Online trainer configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnlineTrainingConfig {
+    /// SGD step size; kept small so single corrections nudge the router
+    pub learning_rate: f32,
+    /// L2 penalty on the weights, limiting drift from the base model
+    pub l2_reg: f32,
+    /// Maximum incremental updates per day (battery and drift bound)
+    pub max_steps_per_day: usize,
+    /// Corrections retained for later offline retraining
+    pub max_stored: usize,
+}
+
+impl Default for OnlineTrainingConfig {
+    fn default() -> Self {
+        Self {
+            learning_rate: 0.005,
+            l2_reg: 0.001,
+            max_steps_per_day: 200,
+            max_stored: 1_000,
+        }
+    }
+}
+
+/// Incremental on-device router training from user corrections and
+/// rewarded outcomes
+///
+/// Each correction is queued and applied as one small SGD step, subject
+/// to a daily step budget. Corrections that exceed today's budget stay
+/// queued until the next day. A rewarded outcome is a step towards the
+/// route it was served on, scaled by its reward: negative rewards push
+/// the router away from it. All examples are kept (up to `max_stored`)
+/// so the corrections and well-rewarded outcomes can feed a full
+/// offline retrain.
+#[derive(Debug, Clone)]
+pub struct OnlineTrainer {
+    config: OnlineTrainingConfig,
+    /// Stored examples (features, route label, step scale), oldest first
+    corrections: VecDeque<(Vec<f32>, usize, f32)>,
+    /// Number of most recent corrections not yet trained on
+    pending: usize,
+    /// Day index (ms / DAY_MS) the step count refers to
+    day: u64,
+    steps_today: usize,
+}
+
+impl OnlineTrainer {
+    /// Create an online trainer
+    pub fn new(config: OnlineTrainingConfig) -> Self {
+        Self {
+            config,
+            corrections: VecDeque::new(),
+            pending: 0,
+            day: 0,
+            steps_today: 0,
+        }
+    }
+
+    /// Queue a correction: the route that should have been taken for
+    /// these features
+    pub fn add_correction(&mut self, features: Vec<f32>, correct_route: RoutingDecision) {
+        self.push(features, correct_route, 1.0);
+    }
+
+    /// Queue an outcome served on `route` with `reward` from a
+    /// [`RewardFn`](crate::reward::RewardFn). The reward is clamped to
+    /// [-1, 1], so one outcome never moves the router more than a
+    /// correction.
+    pub fn add_reward(&mut self, features: Vec<f32>, route: RoutingDecision, reward: f32) {
+        let scale = if reward.is_finite() {
+            reward.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        self.push(features, route, scale);
+    }
+
+    fn push(&mut self, features: Vec<f32>, route: RoutingDecision, scale: f32) {
+        self.corrections.push_back((features, route_label(route), scale));
+        self.pending += 1;
+        if self.corrections.len() > self.config.max_stored.max(1) {
+            self.corrections.pop_front();
+        }
+        self.pending = self.pending.min(self.corrections.len());
+    }
+
+    /// Apply queued corrections to `mlp` within today's step budget,
+    /// returning the number of steps taken
+    pub fn train(&mut self, mlp: &mut MLP, now_ms: u64) -> usize {
+        let steps = self.pending.min(self.remaining_steps(now_ms));
+        self.roll_day(now_ms);
+
+        let start = self.corrections.len() - self.pending;
+        for (features, label, scale) in self.corrections.iter().skip(start).take(steps) {
+            let target = one_hot(*label, mlp.output_size());
+            let (_, mut gradients) =
+                mlp.backward_with_loss(features, &target, Loss::SoftmaxCrossEntropy);
+            scale_gradients(&mut gradients, *scale);
+            mlp.add_weight_penalty(&mut gradients, self.config.l2_reg, 0.0);
+            mlp.update(&gradients, self.config.learning_rate);
+        }
+
+        self.pending -= steps;
+        self.steps_today += steps;
+        steps
+    }
+
+    /// Steps still allowed on the day containing `now_ms`
+    pub fn remaining_steps(&self, now_ms: u64) -> usize {
+        let used = if now_ms / DAY_MS == self.day {
+            self.steps_today
+        } else {
+            0
+        };
+        self.config.max_steps_per_day.saturating_sub(used)
+    }
+
+    /// Corrections queued but not yet trained on
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Stored corrections and positively rewarded outcomes, for offline
+    /// retraining
+    pub fn corrections(&self) -> RouterTrainingData {
+        let kept = || self.corrections.iter().filter(|(_, _, scale)| *scale > 0.0);
+        RouterTrainingData {
+            features: kept().map(|(f, _, _)| f.clone()).collect(),
+            labels: kept().map(|&(_, l, _)| l).collect(),
+        }
+    }
+
+    fn roll_day(&mut self, now_ms: u64) {
+        let day = now_ms / DAY_MS;
+        if day != self.day {
+            self.day = day;
+            self.steps_today = 0;
+        }
+    }
+}
+
+impl Default for OnlineTrainer {
+    fn default() -> Self {
+        Self::new(OnlineTrainingConfig::default())
+    }
+}
+
+/// Multiply every gradient by `scale`
+fn scale_gradients(gradients: &mut Gradients, scale: f32) {
+    if scale == 1.0 {
+        return;
+    }
+    for g in &mut gradients.weights {
+        for g in g.as_mut_slice() {
+            *g *= scale;
+        }
+    }
+    for g in gradients.biases.iter_mut().flatten() {
+        *g *= scale;
+    }
+}
+
 /// Convert label to one-hot encoding
 fn one_hot(label: usize, num_classes: usize) -> Vec<f32> {
     let mut vec = vec![0.0; num_classes];
-    vec[label] = 1.0;
+    // Labels past the model's outputs (targets registered after it was
+    // built) train towards no class rather than panicking.
+    if let Some(slot) = vec.get_mut(label) {
+        *slot = 1.0;
+    }
     vec
 }
 
@@ -401,15 +920,13 @@ pub fn collect_training_data_from_feedback(
     router: &crate::router::Router,
     project: Option<&str>,
     limit: usize,
-) -> Result<RouterTrainingData, String> {
-    use crate::types::ConversationTurn;
-
+) -> Result<RouterTrainingData, OrchestratorError> {
     let mut data = RouterTrainingData::new();
 
     // Load conversation history
-    let history = pm
-        .load_history(project, limit)
-        .map_err(|e| format!("Failed to load history: {}", e))?;
+    let history = pm.load_history(project, 0, limit).map_err(|e| {
+        OrchestratorError::PersistenceError(format!("Failed to load history: {}", e))
+    })?;
 
     // Extract features and labels
     for turn in history {
@@ -434,6 +951,19 @@ fn test_training_data_creation() {
         assert_eq!(data.labels[0], 0);
     }
 
+    #[test]
+    fn test_custom_routes_extend_classes() {
+        let mut data = RouterTrainingData::new();
+        data.add_example(vec![0.0; 4], RoutingDecision::Remote);
+        assert_eq!(data.num_classes(), BUILTIN_ROUTE_CLASSES);
+
+        data.add_example(vec![0.0; 4], RoutingDecision::Custom(1));
+        assert_eq!(data.labels[1], 4);
+        assert_eq!(data.num_classes(), 5);
+        assert_eq!(label_route(4), RoutingDecision::Custom(1));
+        assert_eq!(one_hot(4, 3), vec![0.0; 3]);
+    }
+
     #[test]
     fn test_train_test_split() {
         let mut data = RouterTrainingData::new();
@@ -452,6 +982,119 @@ fn test_train_test_split() {
         assert_eq!(test.len(), 20);
     }
 
+    fn imbalanced_data() -> RouterTrainingData {
+        let mut data = RouterTrainingData::new();
+        for _ in 0..45 {
+            data.add_example(vec![0.1], RoutingDecision::Local);
+            data.add_example(vec![0.9], RoutingDecision::Remote);
+        }
+        for _ in 0..4 {
+            data.add_example(vec![0.5], RoutingDecision::Hybrid);
+        }
+        data
+    }
+
+    fn count(labels: &[usize], label: usize) -> usize {
+        labels.iter().filter(|&&l| l == label).count()
+    }
+
+    #[test]
+    fn test_stratified_split_keeps_small_classes() {
+        let data = imbalanced_data();
+        for _ in 0..20 {
+            let (train, test) = data.stratified_train_test_split(0.8);
+            assert_eq!(train.len() + test.len(), data.len());
+            assert_eq!(count(&train.labels, 2), 3);
+            assert_eq!(count(&test.labels, 2), 1);
+            assert_eq!(count(&test.labels, 0), 9);
+        }
+    }
+
+    #[test]
+    fn test_stratified_folds_balance_classes() {
+        let data = imbalanced_data();
+        let folds = data.stratified_folds(4);
+
+        assert_eq!(folds.len(), 4);
+        assert_eq!(folds.iter().map(Vec::len).sum::<usize>(), data.len());
+        for fold in &folds {
+            let labels: Vec<usize> = fold.iter().map(|&i| data.labels[i]).collect();
+            assert_eq!(count(&labels, 2), 1);
+            assert!((11..=12).contains(&count(&labels, 0)));
+        }
+    }
+
+    #[test]
+    fn test_online_trainer_respects_daily_budget() {
+        let mut mlp = MLP::new_with_seed(4, vec![8], 3, 7);
+        let mut trainer = OnlineTrainer::new(OnlineTrainingConfig {
+            max_steps_per_day: 3,
+            ..OnlineTrainingConfig::default()
+        });
+        for _ in 0..5 {
+            trainer.add_correction(vec![1.0, 0.0, 0.0, 1.0], RoutingDecision::Remote);
+        }
+
+        assert_eq!(trainer.train(&mut mlp, 1_000), 3);
+        assert_eq!(trainer.pending(), 2);
+        assert_eq!(trainer.train(&mut mlp, 2_000), 0);
+
+        // Budget resets the next day
+        assert_eq!(trainer.remaining_steps(DAY_MS + 1), 3);
+        assert_eq!(trainer.train(&mut mlp, DAY_MS + 1), 2);
+        assert_eq!(trainer.pending(), 0);
+        assert_eq!(trainer.corrections().len(), 5);
+    }
+
+    #[test]
+    fn test_online_corrections_shift_prediction() {
+        let mut mlp = MLP::new_with_seed(4, vec![8], 3, 7);
+        let input = vec![1.0, 0.0, 0.0, 1.0];
+        let remote = route_label(RoutingDecision::Remote);
+        let before = MLP::softmax(&mlp.forward(&input))[remote];
+
+        let mut trainer = OnlineTrainer::default();
+        for _ in 0..20 {
+            trainer.add_correction(input.clone(), RoutingDecision::Remote);
+        }
+        trainer.train(&mut mlp, 0);
+
+        let after = MLP::softmax(&mlp.forward(&input))[remote];
+        assert!(after > before, "{} should exceed {}", after, before);
+    }
+
+    #[test]
+    fn test_negative_reward_shifts_prediction_away() {
+        let mut mlp = MLP::new_with_seed(4, vec![8], 3, 7);
+        let input = vec![1.0, 0.0, 0.0, 1.0];
+        let remote = route_label(RoutingDecision::Remote);
+        let before = MLP::softmax(&mlp.forward(&input))[remote];
+
+        let mut trainer = OnlineTrainer::default();
+        for _ in 0..20 {
+            trainer.add_reward(input.clone(), RoutingDecision::Remote, -5.0);
+        }
+        assert_eq!(trainer.train(&mut mlp, 0), 20);
+
+        let after = MLP::softmax(&mlp.forward(&input))[remote];
+        assert!(after < before, "{} should be below {}", after, before);
+        // Only corrections and well-rewarded outcomes are kept for retraining
+        assert!(trainer.corrections().is_empty());
+    }
+
+    #[test]
+    fn test_online_trainer_caps_storage() {
+        let mut trainer = OnlineTrainer::new(OnlineTrainingConfig {
+            max_stored: 2,
+            ..OnlineTrainingConfig::default()
+        });
+        for _ in 0..4 {
+            trainer.add_correction(vec![0.0], RoutingDecision::Hybrid);
+        }
+        assert_eq!(trainer.corrections().len(), 2);
+        assert_eq!(trainer.pending(), 2);
+    }
+
     #[test]
     fn test_one_hot_encoding() {
         let hot = one_hot(1, 3);
@@ -492,6 +1135,7 @@ fn test_mlp_training() {
             batch_size: 10,
             patience: 5,
             l2_reg: 0.0001,
+            ..MLPTrainingConfig::default()
         };
 
         let trainer = MLPTrainer::new(config);
@@ -503,6 +1147,178 @@ fn test_mlp_training() {
         println!("Training completed - infrastructure verified");
     }
 
+    /// Small two-class dataset separable on the first feature
+    fn separable_data() -> RouterTrainingData {
+        let mut data = RouterTrainingData::new();
+        for i in 0..20 {
+            let jitter = (i % 5) as f32 * 0.02;
+            data.add_example(vec![0.1 + jitter, 0.5, 0.3, 0.2], RoutingDecision::Local);
+            data.add_example(vec![0.9 - jitter, 0.5, 0.3, 0.2], RoutingDecision::Remote);
+        }
+        data
+    }
+
+    fn train_losses(optimizer: Optimizer) -> Vec<f32> {
+        let data = separable_data();
+        let mut mlp = MLP::new(4, vec![8], 3);
+        let config = MLPTrainingConfig {
+            learning_rate: 0.01,
+            epochs: 20,
+            batch_size: 0,
+            optimizer,
+            ..MLPTrainingConfig::default()
+        };
+        MLPTrainer::new(config)
+            .train(&mut mlp, &data, None)
+            .train_losses
+    }
+
+    #[test]
+    fn test_optimizers_reduce_loss() {
+        for optimizer in [
+            Optimizer::Sgd,
+            Optimizer::momentum(),
+            Optimizer::adam(),
+            Optimizer::adamw(0.01),
+        ] {
+            let losses = train_losses(optimizer);
+            let (Some(first), Some(last)) = (losses.first(), losses.last()) else {
+                panic!("training should record losses for {:?}", optimizer);
+            };
+            assert!(
+                last < first,
+                "{:?} should reduce loss: {} -> {}",
+                optimizer,
+                first,
+                last
+            );
+        }
+    }
+
+    fn train_with_reg(l2_reg: f32, l1_reg: f32) -> MLP {
+        let data = separable_data();
+        let mut mlp = MLP::new(4, vec![8], 3);
+        let config = MLPTrainingConfig {
+            learning_rate: 0.05,
+            epochs: 30,
+            batch_size: 0,
+            l2_reg,
+            l1_reg,
+            ..MLPTrainingConfig::default()
+        };
+        MLPTrainer::new(config).train(&mut mlp, &data, None);
+        mlp
+    }
+
+    #[test]
+    fn test_l2_reg_shrinks_weight_norm() {
+        let plain = train_with_reg(0.0, 0.0);
+        let decayed = train_with_reg(0.05, 0.0);
+        assert!(decayed.weight_norm_sq() < plain.weight_norm_sq());
+    }
+
+    #[test]
+    fn test_l1_reg_sparsifies_weights() {
+        let near_zero = |mlp: &MLP| {
+            mlp.weights()
+                .iter()
+                .flat_map(|w| w.as_slice())
+                .filter(|w| w.abs() < 0.02)
+                .count()
+        };
+        let plain = train_with_reg(0.0, 0.0);
+        let sparse = train_with_reg(0.0, 0.01);
+        assert!(near_zero(&sparse) > near_zero(&plain));
+    }
+
+    #[test]
+    fn test_softmax_cross_entropy_learns_separable_classes() {
+        let data = separable_data();
+        let mut mlp = MLP::new(4, vec![8], 3);
+        let config = MLPTrainingConfig {
+            learning_rate: 0.05,
+            epochs: 30,
+            batch_size: 0,
+            optimizer: Optimizer::adam(),
+            loss: Loss::SoftmaxCrossEntropy,
+            ..MLPTrainingConfig::default()
+        };
+        let trainer = MLPTrainer::new(config);
+        trainer.train(&mut mlp, &data, None);
+
+        assert_eq!(trainer.evaluate_accuracy(&mlp, &data), 1.0);
+    }
+
+    #[test]
+    fn test_lr_schedules() {
+        let step = LrSchedule::StepDecay {
+            step_size: 10,
+            gamma: 0.5,
+        };
+        assert_eq!(step.learning_rate(0.1, 9, 30, 0), 0.1);
+        assert_eq!(step.learning_rate(0.1, 10, 30, 0), 0.05);
+        assert_eq!(step.learning_rate(0.1, 25, 30, 0), 0.025);
+
+        let cosine = LrSchedule::Cosine { min_lr: 0.0 };
+        assert!((cosine.learning_rate(0.1, 0, 11, 0) - 0.1).abs() < 1e-6);
+        assert!((cosine.learning_rate(0.1, 5, 11, 0) - 0.05).abs() < 1e-6);
+        assert!(cosine.learning_rate(0.1, 10, 11, 0).abs() < 1e-6);
+
+        // Linear warmup, then the schedule starts from the base rate
+        let constant = LrSchedule::Constant;
+        assert!((constant.learning_rate(0.1, 0, 10, 4) - 0.025).abs() < 1e-6);
+        assert!((constant.learning_rate(0.1, 3, 10, 4) - 0.1).abs() < 1e-6);
+        assert!((cosine.learning_rate(0.1, 4, 11, 4) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_schedule_recorded_in_metrics() {
+        let data = separable_data();
+        let mut mlp = MLP::new(4, vec![8], 3);
+        let schedule = LrSchedule::StepDecay {
+            step_size: 2,
+            gamma: 0.5,
+        };
+        let config = MLPTrainingConfig {
+            learning_rate: 0.04,
+            lr_schedule: schedule,
+            epochs: 4,
+            batch_size: 0,
+            ..MLPTrainingConfig::default()
+        };
+
+        let metrics = MLPTrainer::new(config).train(&mut mlp, &data, None);
+        assert_eq!(metrics.lr_schedule, schedule);
+        assert_eq!(metrics.learning_rates, vec![0.04, 0.04, 0.02, 0.02]);
+    }
+
+    #[test]
+    fn test_momentum_accumulates_velocity() {
+        let mut mlp = MLP::new(2, vec![], 1);
+        let mut state = OptimizerState::new(Optimizer::momentum(), &mlp);
+        let mut gradients = Gradients::zeros_like(&mlp);
+        gradients.biases[0][0] = 1.0;
+
+        state.step(&mut mlp, &gradients, 0.1);
+        state.step(&mut mlp, &gradients, 0.1);
+
+        // Bias steps: 0.1 * 1.0, then 0.1 * (0.9 + 1.0)
+        assert!((mlp.biases()[0][0] + 0.29).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adam_first_step_is_learning_rate() {
+        let mut mlp = MLP::new(2, vec![], 1);
+        let mut state = OptimizerState::new(Optimizer::adam(), &mlp);
+        let mut gradients = Gradients::zeros_like(&mlp);
+        gradients.biases[0][0] = 123.0;
+
+        state.step(&mut mlp, &gradients, 0.01);
+
+        // Bias-corrected Adam steps by ~lr regardless of gradient scale
+        assert!((mlp.biases()[0][0] + 0.01).abs() < 1e-5);
+    }
+
     #[test]
     fn test_reservoir_training() {
         // Create simple temporal pattern
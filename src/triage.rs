@@ -0,0 +1,449 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Error-Message Triage
+//!
+//! "Here's my error" queries are often a 300-line build log or stack
+//! trace in which three lines matter. [`parse`] recognises common compiler
+//! and runtime formats and keeps the essentials:
+//!
+//! | Format | Recognised by |
+//! |--------|---------------|
+//! | rustc | `error[E0382]: ...` followed by `--> file:line:col` |
+//! | Rust panic | `thread '...' panicked at ...` plus backtrace frames |
+//! | Gradle | Kotlin `e: file:line:col message`, javac `File.java:12: error:`, `What went wrong:` |
+//! | JVM exception | `...Exception: message` with `at ...` frames and `Caused by:` |
+//! | xcodebuild | `File.swift:12:5: error: message` |
+//!
+//! The orchestrator replaces the pasted text with the [`ErrorReport`]
+//! summary before consulting the response cache, so a recurring error is
+//! answered from earlier answers whatever noise surrounds it, and only the
+//! summary is ever sent Remote.
+
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Frame prefixes belonging to runtimes and frameworks rather than the
+/// user's code
+const LIBRARY_FRAMES: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "rust_begin_unwind",
+    "__rust",
+    "<alloc::",
+    "<core::",
+    "<std::",
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "kotlin.",
+    "kotlinx.",
+    "android.",
+    "androidx.",
+    "com.android.",
+    "dalvik.",
+];
+
+/// Error triage settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriageConfig {
+    /// Distil pasted errors before routing
+    pub enabled: bool,
+    /// Most stack frames kept in the summary
+    pub max_frames: usize,
+}
+
+impl Default for TriageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_frames: 3,
+        }
+    }
+}
+
+/// Tool or runtime that produced an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorFormat {
+    /// Rust compiler diagnostics
+    Rustc,
+    /// Rust runtime panic
+    RustPanic,
+    /// Gradle build (Kotlin or Java compiler)
+    Gradle,
+    /// JVM exception with stack trace
+    JvmException,
+    /// Xcode build (Swift, Objective-C, clang)
+    Xcodebuild,
+}
+
+impl fmt::Display for ErrorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorFormat::Rustc => "rustc",
+            ErrorFormat::RustPanic => "Rust panic",
+            ErrorFormat::Gradle => "Gradle",
+            ErrorFormat::JvmException => "JVM exception",
+            ErrorFormat::Xcodebuild => "xcodebuild",
+        })
+    }
+}
+
+/// The essentials of a pasted error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// Tool or runtime that produced it
+    pub format: ErrorFormat,
+    /// Error code, e.g. `E0382`
+    pub code: Option<String>,
+    /// First error message
+    pub message: String,
+    /// Where it happened (`file:line:col`)
+    pub location: Option<String>,
+    /// Innermost frames from the user's own code
+    pub frames: Vec<String>,
+    /// Errors of the same kind in the paste, including the first
+    pub error_count: usize,
+    /// Lines in the paste
+    pub source_lines: usize,
+}
+
+impl ErrorReport {
+    /// Compact description sent in place of the paste
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Explain and fix this {} error", self.format);
+        if let Some(code) = &self.code {
+            summary.push_str(&format!(" [{}]", code));
+        }
+        summary.push_str(&format!(": {}", self.message));
+        if let Some(location) = &self.location {
+            summary.push_str(&format!("\n  at {}", location));
+        }
+        for frame in &self.frames {
+            summary.push_str(&format!("\n  in {}", frame));
+        }
+        if self.error_count > 1 {
+            summary.push_str(&format!("\n  ({} more errors)", self.error_count - 1));
+        }
+        summary
+    }
+}
+
+/// Distil `text` if it contains an error in a known format
+pub fn parse(text: &str, max_frames: usize) -> Option<ErrorReport> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut report = parse_rustc(&lines)
+        .or_else(|| parse_panic(&lines, max_frames))
+        .or_else(|| parse_kotlin(&lines))
+        .or_else(|| parse_jvm_exception(&lines, max_frames))
+        .or_else(|| parse_located_error(&lines))
+        .or_else(|| parse_gradle_summary(&lines))?;
+    report.source_lines = lines.len();
+    Some(report)
+}
+
+/// `error[E0382]: message` / `error: message`, then `--> file:line:col`
+fn parse_rustc(lines: &[&str]) -> Option<ErrorReport> {
+    let is_error = |line: &str| {
+        let line = line.trim_start();
+        (line.starts_with("error[") || line.starts_with("error: "))
+            && !line.starts_with("error: aborting")
+            && !line.starts_with("error: could not compile")
+    };
+    let first = lines.iter().position(|line| is_error(line))?;
+    let line = lines[first].trim_start();
+    let (code, message) = match line.strip_prefix("error[") {
+        Some(rest) => {
+            let (code, message) = rest.split_once("]:")?;
+            (Some(code.to_string()), message)
+        }
+        None => (None, &line["error:".len()..]),
+    };
+    let location = lines[first + 1..]
+        .iter()
+        .take(3)
+        .find_map(|line| line.trim_start().strip_prefix("--> "));
+    // Plain `error:` lines are also printed by other tools; rustc always
+    // points at the source
+    if code.is_none() && location.is_none() {
+        return None;
+    }
+    Some(ErrorReport {
+        format: ErrorFormat::Rustc,
+        code,
+        message: message.trim().to_string(),
+        location: location.map(|l| l.trim().to_string()),
+        frames: Vec::new(),
+        error_count: lines.iter().filter(|line| is_error(line)).count(),
+        source_lines: 0,
+    })
+}
+
+/// `thread 'main' panicked at src/main.rs:4:5:` then the message (or the
+/// pre-1.73 `panicked at 'message', src/main.rs:4:5`), then backtrace
+fn parse_panic(lines: &[&str], max_frames: usize) -> Option<ErrorReport> {
+    let first = lines
+        .iter()
+        .position(|line| line.contains(" panicked at "))?;
+    let (_, rest) = lines[first].split_once(" panicked at ")?;
+    let (message, location) = match rest.strip_prefix('\'') {
+        Some(quoted) => {
+            let (message, location) = quoted.rsplit_once("', ")?;
+            (message.to_string(), location.to_string())
+        }
+        None => {
+            let message = lines.get(first + 1).map_or("", |line| line.trim());
+            (message.to_string(), rest.trim_end_matches(':').to_string())
+        }
+    };
+
+    // Backtrace entries look like `  12: crate::module::function`
+    let frames = lines[first..]
+        .iter()
+        .filter_map(|line| {
+            let (index, symbol) = line.trim_start().split_once(": ")?;
+            index.parse::<usize>().ok()?;
+            Some(symbol.trim())
+        })
+        .filter(|symbol| is_user_frame(symbol))
+        .take(max_frames)
+        .map(str::to_string)
+        .collect();
+
+    Some(ErrorReport {
+        format: ErrorFormat::RustPanic,
+        code: None,
+        message,
+        location: Some(location),
+        frames,
+        error_count: 1,
+        source_lines: 0,
+    })
+}
+
+/// Kotlin compiler output from Gradle: `e: file:///Foo.kt:12:5 message`
+fn parse_kotlin(lines: &[&str]) -> Option<ErrorReport> {
+    let errors: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| line.trim_start().strip_prefix("e: "))
+        .collect();
+    let first = errors.first()?;
+    let first = first.strip_prefix("file://").unwrap_or(first);
+    let (location, message) = match first.split_once(' ') {
+        Some((location, message)) if location.contains(':') => (Some(location), message),
+        _ => (None, first),
+    };
+    Some(ErrorReport {
+        format: ErrorFormat::Gradle,
+        code: None,
+        message: message.trim().to_string(),
+        location: location.map(|l| l.trim_end_matches(':').to_string()),
+        frames: Vec::new(),
+        error_count: errors.len(),
+        source_lines: 0,
+    })
+}
+
+/// `java.lang.IllegalStateException: message` followed by `at ...` frames;
+/// the last `Caused by:` is the root cause
+fn parse_jvm_exception(lines: &[&str], max_frames: usize) -> Option<ErrorReport> {
+    let is_frame = |line: &str| line.trim_start().starts_with("at ");
+    let first = lines.iter().enumerate().position(|(i, line)| {
+        looks_like_exception(line) && lines.get(i + 1).is_some_and(|next| is_frame(next))
+    })?;
+    let header = lines[first].trim();
+    let header = header
+        .strip_prefix("Exception in thread ")
+        .and_then(|rest| rest.split_once("\" "))
+        .map_or(header, |(_, exception)| exception);
+    let root = lines[first..]
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("Caused by: "))
+        .map_or(first, |offset| first + offset);
+    let cause = lines[root].trim().trim_start_matches("Caused by: ");
+
+    let mut message = header.to_string();
+    if root != first {
+        message.push_str(&format!(" (caused by {})", cause));
+    }
+    let frames: Vec<String> = lines[root + 1..]
+        .iter()
+        .take_while(|line| is_frame(line))
+        .map(|line| line.trim_start().trim_start_matches("at "))
+        .filter(|frame| is_user_frame(frame))
+        .take(max_frames)
+        .map(str::to_string)
+        .collect();
+    // `Foo.bar(Foo.java:12)`: the file and line of the innermost user frame
+    let location = frames.first().and_then(|frame| {
+        let (_, inside) = frame.split_once('(')?;
+        Some(inside.trim_end_matches(')').to_string())
+    });
+
+    Some(ErrorReport {
+        format: ErrorFormat::JvmException,
+        code: None,
+        message,
+        location,
+        frames,
+        error_count: 1,
+        source_lines: 0,
+    })
+}
+
+/// `path:line[:col]: error: message`, as printed by clang, swiftc and
+/// javac; Gradle logs are told apart by their task lines
+fn parse_located_error(lines: &[&str]) -> Option<ErrorReport> {
+    let errors: Vec<(&str, &str)> = lines
+        .iter()
+        .filter_map(|line| {
+            let (location, message) = line
+                .split_once(": error: ")
+                .or_else(|| line.split_once(": fatal error: "))?;
+            location
+                .rsplit(':')
+                .next()
+                .is_some_and(|n| n.parse::<u32>().is_ok())
+                .then_some((location.trim(), message.trim()))
+        })
+        .collect();
+    let (location, message) = *errors.first()?;
+    let gradle = lines
+        .iter()
+        .any(|line| line.starts_with("> Task :") || line.contains("What went wrong"));
+    let format = if gradle || location.contains(".java:") {
+        ErrorFormat::Gradle
+    } else {
+        ErrorFormat::Xcodebuild
+    };
+    Some(ErrorReport {
+        format,
+        code: None,
+        message: message.to_string(),
+        location: Some(location.to_string()),
+        frames: Vec::new(),
+        error_count: errors.len(),
+        source_lines: 0,
+    })
+}
+
+/// Gradle's `* What went wrong:` block when no compiler line was found
+fn parse_gradle_summary(lines: &[&str]) -> Option<ErrorReport> {
+    let heading = lines
+        .iter()
+        .position(|line| line.trim() == "* What went wrong:")?;
+    let message = lines[heading + 1..]
+        .iter()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())?;
+    Some(ErrorReport {
+        format: ErrorFormat::Gradle,
+        code: None,
+        message: message.to_string(),
+        location: None,
+        frames: Vec::new(),
+        error_count: 1,
+        source_lines: 0,
+    })
+}
+
+/// `some.package.FooException: message` or `Exception in thread ...`
+fn looks_like_exception(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with("Exception in thread ") {
+        return true;
+    }
+    let name = line.split(':').next().unwrap_or_default();
+    !name.contains(' ')
+        && name.contains('.')
+        && (name.ends_with("Exception") || name.ends_with("Error"))
+}
+
+fn is_user_frame(frame: &str) -> bool {
+    !LIBRARY_FRAMES
+        .iter()
+        .any(|prefix| frame.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rustc_and_panic() {
+        let rustc = "   Compiling app v0.1.0\n\
+                     error[E0382]: borrow of moved value: `items`\n  \
+                     --> src/main.rs:5:20\n   |\n\
+                     error[E0308]: mismatched types\n  --> src/lib.rs:9:1\n\
+                     error: aborting due to 2 previous errors\n";
+        let Some(report) = parse(rustc, 3) else {
+            panic!("rustc output should parse");
+        };
+        assert_eq!(report.format, ErrorFormat::Rustc);
+        assert_eq!(report.code.as_deref(), Some("E0382"));
+        assert_eq!(report.location.as_deref(), Some("src/main.rs:5:20"));
+        assert_eq!(report.error_count, 2);
+        assert!(report.summary().contains("(1 more errors)"));
+
+        let panic = "thread 'main' panicked at src/parser.rs:42:9:\n\
+                     index out of bounds: the len is 3 but the index is 7\n\
+                     stack backtrace:\n   0: rust_begin_unwind\n   \
+                     1: core::panicking::panic_fmt\n   2: app::parser::next_token\n   \
+                     3: app::main\n";
+        let Some(report) = parse(panic, 1) else {
+            panic!("panics should parse");
+        };
+        assert_eq!(report.format, ErrorFormat::RustPanic);
+        assert_eq!(report.location.as_deref(), Some("src/parser.rs:42:9"));
+        assert!(report.message.starts_with("index out of bounds"));
+        assert_eq!(report.frames, vec!["app::parser::next_token".to_string()]);
+    }
+
+    #[test]
+    fn test_gradle_jvm_and_xcode() {
+        let gradle = "> Task :app:compileDebugKotlin FAILED\n\
+                      e: file:///app/src/main/java/Feed.kt:12:5 Unresolved reference: bind\n\n\
+                      * What went wrong:\nExecution failed for task ':app:compileDebugKotlin'.\n";
+        let Some(report) = parse(gradle, 3) else {
+            panic!("gradle output should parse");
+        };
+        assert_eq!(report.format, ErrorFormat::Gradle);
+        assert_eq!(report.message, "Unresolved reference: bind");
+        assert_eq!(
+            report.location.as_deref(),
+            Some("/app/src/main/java/Feed.kt:12:5")
+        );
+
+        let jvm = "FATAL EXCEPTION: main\n\
+                   java.lang.RuntimeException: Unable to start activity\n\
+                   \tat android.app.ActivityThread.performLaunchActivity(ActivityThread.java:3449)\n\
+                   Caused by: java.lang.NullPointerException: title was null\n\
+                   \tat java.util.Objects.requireNonNull(Objects.java:228)\n\
+                   \tat com.example.feed.FeedActivity.onCreate(FeedActivity.kt:31)\n";
+        let Some(report) = parse(jvm, 3) else {
+            panic!("exceptions should parse");
+        };
+        assert_eq!(report.format, ErrorFormat::JvmException);
+        assert!(report
+            .message
+            .contains("caused by java.lang.NullPointerException"));
+        assert_eq!(report.location.as_deref(), Some("FeedActivity.kt:31"));
+        assert_eq!(report.frames.len(), 1);
+
+        let xcode = "CompileSwift normal arm64 Feed.swift\n\
+                     /Users/dev/App/Feed.swift:18:9: error: cannot find 'render' in scope\n\
+                     ** BUILD FAILED **\n";
+        let Some(report) = parse(xcode, 3) else {
+            panic!("xcodebuild output should parse");
+        };
+        assert_eq!(report.format, ErrorFormat::Xcodebuild);
+        assert_eq!(
+            report.location.as_deref(),
+            Some("/Users/dev/App/Feed.swift:18:9")
+        );
+        assert!(parse("what's the weather like?", 3).is_none());
+    }
+}
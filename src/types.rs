@@ -5,81 +5,286 @@
 //! mobile AI framework. All types are optimized for low-overhead 
 //! serialization (`serde`) and memory-efficient transfer on mobile hardware.
 
+use crate::ambient::AmbientState;
+use crate::clock;
+use crate::fallback::Degradation;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
 
 /// QUERY: Represents a single user request.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Query {
+    /// Raw query text as entered by the user.
     pub text: String,
+    /// Project the query belongs to, if any.
     pub project_context: Option<String>,
-    pub priority: u8, // Scale of 1-10
+    /// Scheduling priority on a scale of 1-10.
+    pub priority: u8,
+    /// Creation time in seconds since UNIX_EPOCH.
     pub timestamp: u64,
+    /// Capabilities the answering model must have.
+    #[serde(default)]
+    pub required_capabilities: Vec<ModelCapability>,
+    /// Files attached to the query, e.g. source code for a code-help
+    /// question.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
 }
 
 impl Query {
     /// Create a new query with default priority and current timestamp.
     pub fn new(text: impl Into<String>) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system clock invariant: time is after UNIX_EPOCH (1970-01-01)")
-            .as_secs();
-
         Self {
             text: text.into(),
             project_context: None,
             priority: 5,
-            timestamp,
+            timestamp: clock::now_secs(),
+            required_capabilities: Vec::new(),
+            attachments: Vec::new(),
         }
     }
+
+    /// Require `capability` from whichever backend answers the query.
+    pub fn requiring(mut self, capability: ModelCapability) -> Self {
+        if !self.required_capabilities.contains(&capability) {
+            self.required_capabilities.push(capability);
+        }
+        self
+    }
+
+    /// Attach a file (`name` is usually its path) to the query.
+    pub fn with_attachment(mut self, name: impl Into<String>, content: impl Into<String>) -> Self {
+        self.attachments.push(Attachment {
+            name: name.into(),
+            content: content.into(),
+        });
+        self
+    }
+}
+
+/// ATTACHMENT: A text file sent along with a query.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Attachment {
+    /// File name or path.
+    pub name: String,
+    /// File contents.
+    pub content: String,
+}
+
+/// MODEL CAPABILITY: Something a query may need from the model answering it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ModelCapability {
+    /// Reading and writing source code.
+    Code,
+    /// Image input.
+    Vision,
+    /// Prompts well beyond the on-device context window.
+    LongContext,
+    /// Structured tool / function calls.
+    FunctionCalling,
+}
+
+impl std::fmt::Display for ModelCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ModelCapability::Code => "code",
+            ModelCapability::Vision => "vision",
+            ModelCapability::LongContext => "long-context",
+            ModelCapability::FunctionCalling => "function-calling",
+        })
+    }
 }
 
 /// RESPONSE: The final output produced by the orchestrator.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Response {
+    /// Generated answer text.
     pub text: String,
-    pub route: RoutingDecision, // How the response was generated.
+    /// How the response was generated.
+    pub route: RoutingDecision,
+    /// Router confidence in the chosen route (0.0 to 1.0).
     pub confidence: f32,
+    /// End-to-end processing time in milliseconds.
     pub latency_ms: u64,
+    /// Additional provenance information.
     pub metadata: ResponseMetadata,
 }
 
+impl Response {
+    /// A response with no latency and empty metadata, e.g. for a plugin
+    /// store replaying saved turns
+    pub fn new(text: impl Into<String>, route: RoutingDecision, confidence: f32) -> Self {
+        Self {
+            text: text.into(),
+            route,
+            confidence,
+            latency_ms: 0,
+            metadata: ResponseMetadata::default(),
+        }
+    }
+
+    /// Turn a `Blocked` response into [`OrchestratorError::Blocked`],
+    /// for callers that handle refusals with `?`; other responses pass
+    /// through.
+    ///
+    /// [`OrchestratorError::Blocked`]: crate::error::OrchestratorError::Blocked
+    pub fn into_result(self) -> Result<Self, crate::error::OrchestratorError> {
+        if self.route != RoutingDecision::Blocked {
+            return Ok(self);
+        }
+        let explanation = self.metadata.explanation.unwrap_or_default();
+        Err(crate::error::OrchestratorError::Blocked {
+            rule_id: explanation.expert_rules.into_iter().next(),
+            reason: explanation.block_reason.unwrap_or(self.text),
+        })
+    }
+}
+
 /// ROUTING DECISION: The execution strategy chosen for a query.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum RoutingDecision {
-    Local,   // Handled by on-device model.
-    Remote,  // Dispatched to cloud API.
-    Hybrid,  // Combined local/remote execution.
-    Blocked, // Rejected by safety rules.
+    /// Handled by on-device model.
+    Local,
+    /// Dispatched to cloud API.
+    Remote,
+    /// Combined local/remote execution.
+    Hybrid,
+    /// Rejected by safety rules.
+    Blocked,
+    /// Host-registered route target, by registration index (see
+    /// `targets::TargetRegistry`).
+    Custom(u16),
 }
 
 /// EVALUATION: The result of an expert system rule check.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RuleEvaluation {
+    /// Whether the query may proceed.
     pub allowed: bool,
+    /// Human-readable explanation when a rule fired.
     pub reason: Option<String>,
+    /// Identifier of the rule that fired, if any.
     pub rule_id: Option<String>,
+    /// Warning rules that matched, as "rule_id: reason".
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Query text with sensitive spans masked, if a redaction rule matched.
+    #[serde(default)]
+    pub redacted_text: Option<String>,
+    /// Redaction rules that masked part of the query.
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+    /// Block rules that matched but were overridden by an allow rule, as
+    /// "block_id by allow_id".
+    #[serde(default)]
+    pub overrides: Vec<String>,
+}
+
+/// REDACTION: Sensitive text masked out of a query before it was routed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Redaction {
+    /// Identifier of the redaction rule.
+    pub rule_id: String,
+    /// Number of spans masked.
+    pub spans: usize,
+    /// Why the spans were masked.
+    pub reason: String,
 }
 
 /// CONVERSATION TURN: A paired query-response interaction.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ConversationTurn {
+    /// The user's query.
     pub query: Query,
+    /// The orchestrator's answer.
     pub response: Response,
 }
 
+impl ConversationTurn {
+    /// Pair `query` with its `response`.
+    pub fn new(query: Query, response: Response) -> Self {
+        Self { query, response }
+    }
+}
+
 /// RESPONSE METADATA: Additional information about how a response was produced.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct ResponseMetadata {
+    /// Model or component that produced the response.
     pub model: Option<String>,
+    /// Approximate token count of the response.
     pub tokens: Option<u32>,
+    /// Whether the response was served from cache.
     pub cached: bool,
+    /// Context token budget given to the Local backend, if it ran.
+    #[serde(default)]
+    pub context_budget: Option<usize>,
+    /// Identifier for `Orchestrator::record_feedback`, if the turn can
+    /// receive route corrections.
+    #[serde(default)]
+    pub turn_id: Option<u64>,
+    /// Why the query took its route, for auditing and verbose output.
+    #[serde(default)]
+    pub explanation: Option<RoutingExplanation>,
+    /// `Orchestrator::state_fingerprint` of the state that produced it.
+    #[serde(default)]
+    pub state_fingerprint: Option<String>,
+    /// Set when the chosen route failed and a fallback answered; the
+    /// answer may be of lower quality.
+    #[serde(default)]
+    pub degraded: Option<Degradation>,
+    /// Estimated battery energy the device spent on it (joules); see
+    /// `energy`.
+    #[serde(default)]
+    pub energy_joules: Option<f32>,
+}
+
+/// ROUTING EXPLANATION: An auditable answer to "why did this go remote?".
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoutingExplanation {
+    /// Heuristic rules that fired (heuristic routing only).
+    pub heuristic_rules: Vec<String>,
+    /// MLP input features that pushed hardest towards the chosen route,
+    /// strongest first (MLP routing only).
+    pub top_features: Vec<FeatureContribution>,
+    /// Expert-system rules that triggered.
+    pub expert_rules: Vec<String>,
+    /// Why a safety rule or resource limit refused the query (`Blocked`
+    /// responses only).
+    #[serde(default)]
+    pub block_reason: Option<String>,
+    /// Adjustments made after scoring (SLA penalties, device policy).
+    pub adjustments: Vec<String>,
+    /// Sensitive spans masked out of the query before routing.
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+}
+
+/// FEATURE CONTRIBUTION: How much one input feature moved the router
+/// towards its chosen route.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeatureContribution {
+    /// Position in the router feature vector.
+    pub index: usize,
+    /// Human-readable feature name.
+    pub name: String,
+    /// Drop in the chosen route's probability when the feature is zeroed.
+    pub contribution: f32,
 }
 
 /// CONTEXT SNAPSHOT: A frozen state of the conversation context.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextSnapshot {
+    /// Active project at snapshot time.
     pub project: Option<String>,
+    /// Recent turns, most recent first.
     pub history: Vec<ConversationTurn>,
+    /// Older turns semantically related to the current query, most
+    /// similar first (see `ContextManager::relevant_snapshot`).
+    #[serde(default)]
+    pub related: Vec<ConversationTurn>,
+    /// Reservoir state vector, if reservoir computing is enabled.
     pub reservoir_state: Option<Vec<f32>>,
+    /// Physical situation of the device (pocket, desk, walking, ...).
+    #[serde(default)]
+    pub ambient: AmbientState,
 }
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+//! User Profiles
+//!
+//! A shared device (a family tablet, a pool phone) may serve several
+//! people. Each has a [`UserProfile`], and `Orchestrator::switch_user`
+//! parks everything belonging to the current user (conversation history
+//! and projects, sessions, the trained router and its feedback,
+//! remembered facts, personalization, digests, queued queries, cached
+//! answers, settings, metrics, energy and spend records, the journal,
+//! sensor readings and the attached database) and brings back the next
+//! user's, so nothing one person said or taught is visible to another.
+//! A profile can also name a policy profile, e.g. a kids mode, that
+//! governs all of its user's queries.
+//!
+//! Stored data is separated by database: each user gets their own file,
+//! conventionally [`UserProfile::database_file`], which records the
+//! profile of its owner, and attaching it while someone else is active
+//! is refused.
+
+#![forbid(unsafe_code)]
+
+use crate::profile::Profile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Id of the user active before any switch
+pub const DEFAULT_USER: &str = "default";
+
+/// Someone using the device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserProfile {
+    /// Stable id: ASCII letters, digits, `-` and `_`
+    pub id: String,
+    /// Name shown in the app
+    pub display_name: String,
+    /// Free-form settings the app keeps per user (units, language, ...)
+    #[serde(default)]
+    pub preferences: BTreeMap<String, String>,
+    /// Operating profile applied while the user is active
+    #[serde(default)]
+    pub profile: Profile,
+    /// Policy profile (by name) governing every query while the user is
+    /// active, whatever the project
+    #[serde(default)]
+    pub policy: Option<String>,
+}
+
+impl Default for UserProfile {
+    fn default() -> Self {
+        Self::new(DEFAULT_USER, "")
+    }
+}
+
+impl UserProfile {
+    /// Profile with no preferences, the balanced operating profile and
+    /// no policy profile
+    pub fn new(id: &str, display_name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            preferences: BTreeMap::new(),
+            profile: Profile::default(),
+            policy: None,
+        }
+    }
+
+    /// Check that the id is non-empty and safe to use in a file name
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if self.id.is_empty() || !self.id.chars().all(valid) {
+            return Err(format!(
+                "user id '{}' must be non-empty ASCII letters, digits, '-' or '_'",
+                self.id
+            ));
+        }
+        Ok(())
+    }
+
+    /// File name of the user's database, e.g. `user-alice.db`
+    pub fn database_file(&self) -> String {
+        format!("user-{}.db", self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validates_ids_used_in_file_names() {
+        let user = UserProfile::new("alice_2", "Alice");
+        assert_eq!(user.validate(), Ok(()));
+        assert_eq!(user.database_file(), "user-alice_2.db");
+        assert_eq!(UserProfile::default().id, DEFAULT_USER);
+        for id in ["", "../bob", "bob smith", "bób"] {
+            assert!(UserProfile::new(id, "").validate().is_err(), "{:?}", id);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Wake-Word Spotting
+//!
+//! Listens for enrolled keywords with the spiking network, the low-power
+//! path the SNN exists for. Each audio frame goes through:
+//!
+//! 1. **Filterbank**: Goertzel energies at `bands` log-spaced frequencies
+//!    between `min_hz` and `max_hz`, relative to a slowly adapting noise
+//!    floor per band, squashed to an intensity in 0..=1.
+//! 2. **Spike coding**: one input neuron per band, driven for
+//!    `steps_per_frame` SNN steps. Rate coding fires with probability equal
+//!    to the intensity at every step; temporal (latency) coding fires once,
+//!    earlier for louder bands.
+//! 3. **Signature**: the output neurons' spike counts over the frame.
+//!
+//! A keyword is enrolled from one recording; its signature sequence is the
+//! template. `feed` compares the most recent frames against every template
+//! and reports a [`Detection`] when the mean cosine similarity clears the
+//! threshold set by `sensitivity`. Quiet frames skip the network entirely,
+//! so silence costs only the filterbank. Hosts typically answer a
+//! detection with `Orchestrator::notify_wake_trigger`.
+
+#![forbid(unsafe_code)]
+
+use crate::clock::now_ms;
+use crate::rng::SeededRng;
+use crate::snn::SpikingNetwork;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+/// How frame intensities become input spikes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpikeCoding {
+    /// Fire at every step with probability equal to the intensity
+    #[default]
+    Rate,
+    /// Fire once per frame, earlier the louder the band
+    Temporal,
+}
+
+/// Wake-word detector settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeWordConfig {
+    /// Audio sample rate (Hz)
+    pub sample_rate: u32,
+    /// Filterbank bands, one SNN input neuron each
+    pub bands: usize,
+    /// Lowest band frequency (Hz)
+    pub min_hz: f32,
+    /// Highest band frequency (Hz)
+    pub max_hz: f32,
+    /// Hidden neurons
+    pub hidden: usize,
+    /// Output neurons forming the frame signature
+    pub outputs: usize,
+    /// SNN steps per audio frame
+    pub steps_per_frame: usize,
+    /// Spike coding of band intensities
+    pub coding: SpikeCoding,
+    /// 0 = only near-exact matches, 1 = loose matches
+    pub sensitivity: f32,
+    /// Frames without detections after one fires
+    pub cooldown_frames: usize,
+    /// Seed for the network weights and rate coding
+    pub seed: u64,
+}
+
+impl Default for WakeWordConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            bands: 16,
+            min_hz: 100.0,
+            max_hz: 4_000.0,
+            hidden: 64,
+            outputs: 16,
+            steps_per_frame: 8,
+            coding: SpikeCoding::Rate,
+            sensitivity: 0.5,
+            cooldown_frames: 25,
+            seed: 4_242,
+        }
+    }
+}
+
+impl WakeWordConfig {
+    /// Mean similarity a window needs to count as the keyword
+    pub fn threshold(&self) -> f32 {
+        0.95 - 0.3 * self.sensitivity.clamp(0.0, 1.0)
+    }
+}
+
+/// A keyword was heard
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Detection {
+    /// Enrolled keyword
+    pub keyword: String,
+    /// Mean cosine similarity to its template (0..=1)
+    pub score: f32,
+    /// When the last frame of the keyword arrived (ms since the epoch)
+    pub timestamp_ms: u64,
+}
+
+/// Intensities below this count as silence
+const SILENCE: f32 = 0.05;
+
+/// Floor adaptation rate per frame
+const FLOOR_RATE: f32 = 0.02;
+
+/// Band energy over its floor that makes a frame voiced (10 dB)
+const VOICED_RATIO: f32 = 10.0;
+
+/// Levels further than this below the loudest band read as 0
+const DYNAMIC_RANGE_DB: f32 = 24.0;
+
+/// Energy of digital silence, so ratios stay finite
+const MIN_ENERGY: f32 = 1e-9;
+
+/// Spots enrolled keywords in a stream of audio frames
+#[derive(Debug, Clone)]
+pub struct WakeWordDetector {
+    config: WakeWordConfig,
+    frequencies: Vec<f32>,
+    floors: Vec<f32>,
+    network: SpikingNetwork,
+    rng: SeededRng,
+    templates: Vec<(String, Vec<Vec<f32>>)>,
+    recent: VecDeque<Vec<f32>>,
+    cooldown: usize,
+}
+
+impl WakeWordDetector {
+    /// A detector with no keywords enrolled
+    pub fn new(config: WakeWordConfig) -> Self {
+        let bands = config.bands.max(1);
+        let ratio = (config.max_hz / config.min_hz.max(1.0)).max(1.0);
+        let frequencies = (0..bands)
+            .map(|b| {
+                let position = b as f32 / (bands - 1).max(1) as f32;
+                config.min_hz.max(1.0) * ratio.powf(position)
+            })
+            .collect();
+        Self {
+            frequencies,
+            floors: Vec::new(),
+            network: SpikingNetwork::new_with_seed(
+                bands,
+                config.hidden.max(1),
+                config.outputs.max(1),
+                config.seed,
+            ),
+            rng: SeededRng::new(config.seed),
+            templates: Vec::new(),
+            recent: VecDeque::new(),
+            cooldown: 0,
+            config,
+        }
+    }
+
+    /// Learn `keyword` from one recording of it, split into frames
+    ///
+    /// The recording should hold the keyword and little else; leading and
+    /// trailing silence is trimmed. Returns the template length in frames.
+    pub fn enroll(&mut self, keyword: impl Into<String>, frames: &[Vec<f32>]) -> usize {
+        let floors = std::mem::take(&mut self.floors);
+        self.network.reset();
+        let signatures: Vec<Vec<f32>> = frames.iter().map(|f| self.signature(f)).collect();
+        self.floors = floors;
+        self.network.reset();
+
+        let voiced = |s: &&Vec<f32>| s.iter().any(|&count| count > 0.0);
+        let first = signatures.iter().position(|s| voiced(&s));
+        let last = signatures.iter().rposition(|s| voiced(&s));
+        let template = match (first, last) {
+            (Some(first), Some(last)) => signatures[first..=last].to_vec(),
+            _ => Vec::new(),
+        };
+        let len = template.len();
+        if len > 0 {
+            self.templates.push((keyword.into(), template));
+        }
+        len
+    }
+
+    /// Enrolled keywords
+    pub fn keywords(&self) -> Vec<&str> {
+        self.templates.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    /// Process one audio frame (samples in -1..=1)
+    pub fn feed(&mut self, frame: &[f32]) -> Option<Detection> {
+        let signature = self.signature(frame);
+        let longest = self.templates.iter().map(|(_, t)| t.len()).max()?;
+        self.recent.push_back(signature);
+        while self.recent.len() > longest {
+            self.recent.pop_front();
+        }
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return None;
+        }
+
+        let threshold = self.config.threshold();
+        let best = self
+            .templates
+            .iter()
+            .filter(|(_, template)| template.len() <= self.recent.len())
+            .map(|(keyword, template)| {
+                let window = self.recent.iter().skip(self.recent.len() - template.len());
+                let total: f32 = window.zip(template).map(|(a, b)| similarity(a, b)).sum();
+                (keyword, total / template.len() as f32)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        if best.1 < threshold {
+            return None;
+        }
+        self.cooldown = self.config.cooldown_frames;
+        self.recent.clear();
+        Some(Detection {
+            keyword: best.0.clone(),
+            score: best.1,
+            timestamp_ms: now_ms(),
+        })
+    }
+
+    /// Forget recent audio, e.g. after the microphone was paused
+    pub fn reset(&mut self) {
+        self.network.reset();
+        self.recent.clear();
+        self.cooldown = 0;
+    }
+
+    /// Output spike counts for one frame
+    fn signature(&mut self, frame: &[f32]) -> Vec<f32> {
+        let intensities = self.intensities(frame);
+        let outputs = self.config.outputs.max(1);
+        if intensities.iter().all(|&i| i < SILENCE) {
+            self.network.reset();
+            return vec![0.0; outputs];
+        }
+
+        let steps = self.config.steps_per_frame.max(1);
+        let mut counts = vec![0.0; outputs];
+        let mut spikes = vec![false; intensities.len()];
+        for step in 0..steps {
+            for (spike, &intensity) in spikes.iter_mut().zip(&intensities) {
+                *spike = match self.config.coding {
+                    SpikeCoding::Rate => self.rng.next_f32() < intensity,
+                    SpikeCoding::Temporal => {
+                        intensity >= SILENCE
+                            && step == ((1.0 - intensity) * (steps - 1) as f32).round() as usize
+                    }
+                };
+            }
+            for (count, fired) in counts.iter_mut().zip(self.network.step(&spikes, 1.0)) {
+                if fired {
+                    *count += 1.0;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Band intensities (0..=1) of one frame: each band's level below
+    /// the loudest band, or all zero when no band rises above its floor
+    fn intensities(&mut self, frame: &[f32]) -> Vec<f32> {
+        let energies: Vec<f32> = self
+            .frequencies
+            .iter()
+            .map(|&hz| goertzel(frame, hz, self.config.sample_rate as f32).max(MIN_ENERGY))
+            .collect();
+        if self.floors.len() != energies.len() {
+            // First frame: assume the room sounds like this
+            self.floors = energies.clone();
+        }
+        let mut loud = false;
+        for (&energy, floor) in energies.iter().zip(&mut self.floors) {
+            if energy > *floor * VOICED_RATIO {
+                loud = true;
+            } else {
+                // Only quiet bands move the floor, so speech is not
+                // learned as background
+                *floor += FLOOR_RATE * (energy - *floor);
+            }
+        }
+        let peak = energies.iter().copied().fold(MIN_ENERGY, f32::max);
+        energies
+            .iter()
+            .map(|&energy| match loud {
+                true => (1.0 + 10.0 * (energy / peak).log10() / DYNAMIC_RANGE_DB).clamp(0.0, 1.0),
+                false => 0.0,
+            })
+            .collect()
+    }
+}
+
+/// Power of `frame` at `hz`, Hann-windowed to limit leakage into
+/// neighbouring bands
+fn goertzel(frame: &[f32], hz: f32, sample_rate: f32) -> f32 {
+    let coefficient = 2.0 * (2.0 * PI * hz / sample_rate).cos();
+    let span = frame.len().saturating_sub(1).max(1) as f32;
+    let (mut previous, mut before) = (0.0f32, 0.0f32);
+    for (n, &sample) in frame.iter().enumerate() {
+        let window = 0.5 - 0.5 * (2.0 * PI * n as f32 / span).cos();
+        let current = sample * window + coefficient * previous - before;
+        before = previous;
+        previous = current;
+    }
+    let power = previous * previous + before * before - coefficient * previous * before;
+    power / frame.len().max(1) as f32
+}
+
+/// Cosine similarity of two signatures; two silent frames match
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    match (norm_a > 0.0, norm_b > 0.0) {
+        (true, true) => dot / (norm_a * norm_b),
+        (false, false) => 1.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 25 ms frames of a tone per segment, with silence around them
+    fn utterance(tones: &[f32]) -> Vec<Vec<f32>> {
+        let silence = vec![vec![0.0; 400]; 4];
+        let mut frames = silence.clone();
+        for &hz in tones {
+            for frame in 0..3 {
+                frames.push(
+                    (0..400)
+                        .map(|n| {
+                            let t = (frame * 400 + n) as f32 / 16_000.0;
+                            0.5 * (2.0 * PI * hz * t).sin()
+                        })
+                        .collect(),
+                );
+            }
+        }
+        frames.extend(silence);
+        frames
+    }
+
+    #[test]
+    fn test_detects_the_enrolled_keyword_only() {
+        for coding in [SpikeCoding::Rate, SpikeCoding::Temporal] {
+            let mut detector = WakeWordDetector::new(WakeWordConfig {
+                coding,
+                ..WakeWordConfig::default()
+            });
+            assert!(detector.feed(&[0.0; 400]).is_none());
+            let keyword = [300.0, 1_200.0, 600.0, 2_400.0];
+            assert_eq!(detector.enroll("hey phone", &utterance(&keyword)), 12);
+            assert_eq!(detector.keywords(), vec!["hey phone"]);
+
+            let other = utterance(&[2_400.0, 150.0, 3_500.0, 800.0]);
+            assert!(other.iter().all(|frame| detector.feed(frame).is_none()));
+
+            let heard: Vec<Detection> = utterance(&keyword)
+                .iter()
+                .filter_map(|frame| detector.feed(frame))
+                .collect();
+            assert_eq!(heard.len(), 1, "{:?} coding", coding);
+            assert_eq!(heard[0].keyword, "hey phone");
+            assert!(heard[0].score >= detector.config.threshold());
+        }
+    }
+}
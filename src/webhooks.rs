@@ -0,0 +1,381 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Webhook Event Sinks
+//!
+//! Forwards selected [`Event`]s to automation stacks (Home Assistant,
+//! n8n, a shell script on the same device) as signed JSON payloads:
+//!
+//! ```toml
+//! [[webhooks.sinks]]
+//! url = "https://hooks.example.com/orchestrator"
+//! events = ["BudgetExceeded", "RuleTriggered"]
+//! secret = "${WEBHOOK_SECRET}"
+//!
+//! [[webhooks.sinks]]
+//! url = "unix:/data/local/tmp/orchestrator.sock"
+//! events = ["DailySummaryReady"]
+//! ```
+//!
+//! The body is `{"timestamp": <ms>, "kind": "<EventKind>", "event": {...}}`.
+//! With a secret configured, the signature is
+//! `sha256=<hex HMAC-SHA256(secret, "<timestamp>.<body>")>`, sent as the
+//! `X-Orchestrator-Signature` header next to `X-Orchestrator-Timestamp`.
+//! Unix-socket sinks receive one line per event:
+//! `<timestamp> <signature or -> <body>`.
+//!
+//! Delivery happens on a supervised background task so event publishers
+//! never wait on the network; the task is restarted if it fails, and
+//! payloads still queued at shutdown are dropped. Failed deliveries are
+//! counted, not retried.
+
+#![forbid(unsafe_code)]
+
+use crate::events::{Event, EventBus, EventKind, SubscriptionId};
+use crate::secrets::{self, SecretProvider, SecretRef};
+use crate::supervisor::Supervisor;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header carrying the payload signature
+pub const SIGNATURE_HEADER: &str = "X-Orchestrator-Signature";
+
+/// Header carrying the signed timestamp (ms since the UNIX epoch)
+pub const TIMESTAMP_HEADER: &str = "X-Orchestrator-Timestamp";
+
+/// URL prefix selecting a Unix-socket sink
+const UNIX_PREFIX: &str = "unix:";
+
+/// Longest wait for one HTTP delivery
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often an idle delivery task checks for shutdown
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// Outbound webhook settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Destinations, each with its own event selection
+    pub sinks: Vec<WebhookSink>,
+}
+
+/// One webhook destination
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSink {
+    /// `http(s)://` endpoint, or `unix:<path>` for a local socket
+    pub url: String,
+    /// Event kinds delivered to this sink
+    pub events: Vec<EventKind>,
+    /// HMAC key used to sign payloads
+    #[serde(default)]
+    pub secret: Option<SecretRef>,
+}
+
+/// Delivery counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WebhookStats {
+    /// Payloads accepted by their sink
+    pub delivered: u64,
+    /// Payloads that could not be delivered
+    pub failed: u64,
+}
+
+/// An event on its way to one sink
+struct Delivery {
+    sink: usize,
+    event: Event,
+    timestamp_ms: u64,
+}
+
+/// Signs and delivers events to the configured sinks
+pub struct WebhookDispatcher {
+    sinks: Vec<WebhookSink>,
+    sender: mpsc::Sender<Delivery>,
+    stats: Arc<Mutex<WebhookStats>>,
+}
+
+impl std::fmt::Debug for WebhookDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookDispatcher")
+            .field("sinks", &self.sinks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebhookDispatcher {
+    /// Start the delivery task under `supervisor`; secrets are resolved
+    /// through `provider` when each payload is signed
+    pub fn start(
+        config: WebhookConfig,
+        provider: Arc<dyn SecretProvider>,
+        supervisor: &mut Supervisor,
+    ) -> Result<Self, String> {
+        for sink in &config.sinks {
+            let is_http = sink.url.starts_with("http://") || sink.url.starts_with("https://");
+            if !is_http && !sink.url.starts_with(UNIX_PREFIX) {
+                return Err(format!(
+                    "webhook url '{}' must start with http://, https:// or unix:",
+                    sink.url
+                ));
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel::<Delivery>();
+        // Shared so a restarted task picks up where the failed one stopped
+        let receiver = Arc::new(Mutex::new(receiver));
+        let stats = Arc::new(Mutex::new(WebhookStats::default()));
+
+        let sinks = config.sinks.clone();
+        let worker_stats = Arc::clone(&stats);
+        let body = move |context: &crate::supervisor::TaskContext| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("failed to start webhook runtime: {}", e))?;
+            let client = reqwest::Client::new();
+            let receiver = receiver.lock().unwrap_or_else(PoisonError::into_inner);
+            // Held while the app is in the background
+            while !context.wait_while_paused() {
+                let delivery = match receiver.recv_timeout(IDLE_POLL) {
+                    Ok(delivery) => delivery,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // The dispatcher and its subscription are gone
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let sink = &sinks[delivery.sink];
+                let ok = deliver(&runtime, &client, sink, provider.as_ref(), &delivery).is_ok();
+                if let Ok(mut stats) = worker_stats.lock() {
+                    if ok {
+                        stats.delivered += 1;
+                    } else {
+                        stats.failed += 1;
+                    }
+                }
+            }
+            Ok(())
+        };
+        supervisor.spawn("webhooks", Box::new(body))?;
+
+        Ok(Self {
+            sinks: config.sinks,
+            sender,
+            stats,
+        })
+    }
+
+    /// Subscribe to the events any sink wants
+    pub fn attach(&self, bus: &mut EventBus) -> SubscriptionId {
+        let mut kinds: Vec<EventKind> = Vec::new();
+        for kind in self.sinks.iter().flat_map(|sink| &sink.events) {
+            if !kinds.contains(kind) {
+                kinds.push(*kind);
+            }
+        }
+        let routes: Vec<Vec<EventKind>> = self.sinks.iter().map(|s| s.events.clone()).collect();
+        let sender = self.sender.clone();
+        bus.subscribe_to(&kinds, move |event| {
+            let timestamp_ms = now_ms();
+            for (sink, events) in routes.iter().enumerate() {
+                if events.contains(&event.kind()) {
+                    let _ = sender.send(Delivery {
+                        sink,
+                        event: event.clone(),
+                        timestamp_ms,
+                    });
+                }
+            }
+        })
+    }
+
+    /// Delivered and failed payloads so far
+    pub fn stats(&self) -> WebhookStats {
+        self.stats.lock().map(|stats| *stats).unwrap_or_default()
+    }
+}
+
+/// JSON body sent for `event`
+pub fn payload(event: &Event, timestamp_ms: u64) -> String {
+    serde_json::json!({
+        "timestamp": timestamp_ms,
+        "kind": event.kind(),
+        "event": event,
+    })
+    .to_string()
+}
+
+/// `sha256=<hex>` signature of `body` sent at `timestamp_ms`
+///
+/// Receivers recompute it over `"<timestamp>.<body>"` and should reject
+/// stale timestamps to stop replays.
+pub fn sign(secret: &[u8], timestamp_ms: u64, body: &str) -> String {
+    let message = format!("{}.{}", timestamp_ms, body);
+    format!("sha256={}", hmac_sha256_hex(secret, message.as_bytes()))
+}
+
+fn hmac_sha256_hex(key: &[u8], message: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key);
+    ring::hmac::sign(&key, message)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn deliver(
+    runtime: &tokio::runtime::Runtime,
+    client: &reqwest::Client,
+    sink: &WebhookSink,
+    provider: &dyn SecretProvider,
+    delivery: &Delivery,
+) -> Result<(), String> {
+    let body = payload(&delivery.event, delivery.timestamp_ms);
+    let secret = sink
+        .secret
+        .as_ref()
+        .map(|secret| secret.resolve(provider))
+        .transpose()?;
+    let signature = secret
+        .as_ref()
+        .map(|secret| sign(secret.expose().as_bytes(), delivery.timestamp_ms, &body));
+
+    if let Some(path) = sink.url.strip_prefix(UNIX_PREFIX) {
+        return write_socket(path, delivery.timestamp_ms, signature.as_deref(), &body);
+    }
+
+    let mut request = client
+        .post(&sink.url)
+        .timeout(HTTP_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, delivery.timestamp_ms.to_string())
+        .body(body);
+    if let Some(signature) = signature {
+        request = request.header(SIGNATURE_HEADER, signature);
+    }
+    runtime
+        .block_on(request.send())
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|e| {
+            let error = format!("webhook delivery to {} failed: {}", sink.url, e.without_url());
+            secrets::redact(&error, &secret.iter().collect::<Vec<_>>())
+        })
+}
+
+#[cfg(unix)]
+fn write_socket(
+    path: &str,
+    timestamp_ms: u64,
+    signature: Option<&str>,
+    body: &str,
+) -> Result<(), String> {
+    use std::io::Write;
+    let line = format!("{} {} {}\n", timestamp_ms, signature.unwrap_or("-"), body);
+    std::os::unix::net::UnixStream::connect(path)
+        .and_then(|mut stream| stream.write_all(line.as_bytes()))
+        .map_err(|e| format!("webhook delivery to unix:{} failed: {}", path, e))
+}
+
+#[cfg(not(unix))]
+fn write_socket(
+    path: &str,
+    _timestamp_ms: u64,
+    _signature: Option<&str>,
+    _body: &str,
+) -> Result<(), String> {
+    Err(format!("unix:{} sinks need a Unix platform", path))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc_4231() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let event = Event::ModelPromoted {
+            model: "router".to_string(),
+            version: "2".to_string(),
+        };
+        let body = payload(&event, 42);
+        assert!(body.contains("\"kind\":\"ModelPromoted\""));
+        assert_eq!(sign(b"k", 42, &body), sign(b"k", 42, &body));
+        assert_ne!(sign(b"k", 42, &body), sign(b"k", 43, &body));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_sink_receives_selected_events() {
+        use std::io::BufRead;
+        let path = std::env::temp_dir().join(format!("webhooks-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let Ok(listener) = std::os::unix::net::UnixListener::bind(&path) else {
+            panic!("binding a temp socket should succeed");
+        };
+
+        struct Keys;
+        impl SecretProvider for Keys {
+            fn get_secret(&self, name: &str) -> Option<String> {
+                (name == "hook").then(|| "s3cret".to_string())
+            }
+        }
+        let Ok(secret) = SecretRef::parse("secret:hook") else {
+            panic!("secret reference should parse");
+        };
+        let config = WebhookConfig {
+            sinks: vec![WebhookSink {
+                url: format!("unix:{}", path.display()),
+                events: vec![EventKind::BudgetExceeded],
+                secret: Some(secret),
+            }],
+        };
+        let mut supervisor = Supervisor::default();
+        let dispatcher = WebhookDispatcher::start(config, Arc::new(Keys), &mut supervisor);
+        let Ok(dispatcher) = dispatcher else {
+            panic!("dispatcher should start");
+        };
+        let mut bus = EventBus::new();
+        dispatcher.attach(&mut bus);
+        bus.publish(&Event::ModelPromoted {
+            model: "router".to_string(),
+            version: "2".to_string(),
+        });
+        bus.publish(&Event::BudgetExceeded {
+            budget: "daily_spend".to_string(),
+            used: 1.5,
+            limit: 1.0,
+        });
+
+        let Ok((stream, _)) = listener.accept() else {
+            panic!("the sink should connect");
+        };
+        let mut line = String::new();
+        let Ok(_) = std::io::BufReader::new(stream).read_line(&mut line) else {
+            panic!("the sink should write a line");
+        };
+        let mut parts = line.trim_end().splitn(3, ' ');
+        let (Some(timestamp), Some(signature), Some(body)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            panic!("line should have timestamp, signature and body");
+        };
+        let Ok(timestamp) = timestamp.parse::<u64>() else {
+            panic!("timestamp should be numeric");
+        };
+        assert!(body.contains("BudgetExceeded"));
+        assert_eq!(signature, sign(b"s3cret", timestamp, body));
+        assert_eq!(supervisor.shutdown(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -7,6 +7,7 @@
 /// Library should compile and expose expected modules.
 /// This test passes if `cargo test` can link against the crate.
 #[test]
+#[allow(clippy::assertions_on_constants)]
 fn crate_is_present() {
     // If this compiles, the crate is buildable
     assert!(true, "crate compiled successfully");
@@ -22,6 +23,7 @@ fn no_panics_on_empty_env() {
 
 /// Validate that the crate respects Rust edition 2021 semantics.
 #[test]
+#[allow(clippy::useless_vec)]
 fn edition_2021_closure_capture() {
     let data = vec![1u32, 2, 3];
     let sum: u32 = data.iter().copied().sum();